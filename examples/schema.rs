@@ -0,0 +1,34 @@
+use std::{fs, path::Path, process::ExitCode};
+
+const SCHEMA_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/schema/interop.schema.json");
+
+// NOTE: `--check` compares instead of writing, for CI to fail when the
+// interop types change without the checked-in schema being regenerated.
+fn main() -> ExitCode {
+    let schema = relay::schema::interop_schema_json();
+    let path = Path::new(SCHEMA_PATH);
+
+    if std::env::args().any(|arg| arg == "--check") {
+        return match fs::read_to_string(path) {
+            Ok(current) if current == schema => ExitCode::SUCCESS,
+            Ok(_) => {
+                eprintln!(
+                    "{} is out of date, run `cargo run --example schema --features schema`",
+                    path.display()
+                );
+                ExitCode::FAILURE
+            }
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", path.display(), e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    if let Err(e) = fs::write(path, schema) {
+        eprintln!("Failed to write {}: {}", path.display(), e);
+        return ExitCode::FAILURE;
+    }
+    println!("Wrote {}", path.display());
+    ExitCode::SUCCESS
+}
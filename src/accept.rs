@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use crate::interop::{AcceptEntry, ContentType, MediaType, NegotiationInfo, Request, Response};
+
+impl AcceptEntry {
+    pub fn new(media_type: impl Into<String>, quality: f32) -> Self {
+        Self {
+            media_type: media_type.into(),
+            quality,
+        }
+    }
+
+    // NOTE: Thousandths, the precision a q-value allows. Out of range values
+    // are clamped.
+    fn weight(&self) -> u32 {
+        (self.quality.clamp(0.0, 1.0) * 1000.0).round() as u32
+    }
+
+    fn essence(&self) -> String {
+        self.media_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase()
+    }
+
+    // NOTE: How closely the entry names `essence`, `None` when it does not
+    // cover it at all.
+    fn specificity(&self, essence: &str) -> Option<u8> {
+        let range = self.essence();
+        if range == essence {
+            return Some(2);
+        }
+        if range == "*/*" {
+            return Some(0);
+        }
+        let prefix = range.strip_suffix("/*")?;
+        let (kind, _) = essence.split_once('/')?;
+        (prefix == kind).then_some(1)
+    }
+}
+
+impl From<MediaType> for AcceptEntry {
+    fn from(media_type: MediaType) -> Self {
+        Self::new(media_type.to_string(), 1.0)
+    }
+}
+
+// NOTE: Earlier types are preferred, each one weighs a step less than the
+// one before it. Steps are a tenth for up to ten types and shrink to fit
+// longer lists, so no type drops to `q=0`, which would refuse it.
+pub(crate) fn weighted(types: impl IntoIterator<Item = MediaType>) -> Vec<AcceptEntry> {
+    let types = types
+        .into_iter()
+        .filter(|media_type| *media_type != MediaType::Other)
+        .collect::<Vec<_>>();
+    let step = if types.len() <= 10 {
+        100
+    } else {
+        900 / (types.len() - 1)
+    };
+
+    types
+        .into_iter()
+        .enumerate()
+        .map(|(index, media_type)| {
+            AcceptEntry::new(
+                media_type.to_string(),
+                (1000 - index * step) as f32 / 1000.0,
+            )
+        })
+        .collect()
+}
+
+// NOTE: Ordered by quality, ties keep the order they were given in. `q=1`
+// is left implicit and other q-values use at most three decimals, with
+// trailing zeros dropped.
+fn header_value(entries: &[AcceptEntry]) -> String {
+    let mut entries = entries
+        .iter()
+        .filter(|entry| !entry.media_type.trim().is_empty())
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.weight()));
+
+    entries
+        .iter()
+        .map(|entry| match entry.weight() {
+            1000 => entry.media_type.trim().to_string(),
+            weight => format!(
+                "{};q={}",
+                entry.media_type.trim(),
+                format!("0.{:03}", weight)
+                    .trim_end_matches('0')
+                    .trim_end_matches('.')
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn parse_header(value: &str) -> Vec<AcceptEntry> {
+    value
+        .split(',')
+        .filter_map(|range| {
+            let mut params = range.split(';');
+            let media_type = params.next()?.trim();
+            if media_type.is_empty() {
+                return None;
+            }
+            let quality = params
+                .filter_map(|param| param.split_once('='))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+                .and_then(|(_, quality)| quality.trim().parse().ok())
+                .unwrap_or(1.0);
+            Some(AcceptEntry::new(media_type, quality))
+        })
+        .collect()
+}
+
+// NOTE: JSON content without an explicit `accept` asks for JSON back first,
+// anything else is still acceptable.
+fn entries(request: &Request) -> Option<Vec<AcceptEntry>> {
+    if let Some(ref entries) = request.accept {
+        return Some(entries.clone());
+    }
+    match request.content {
+        Some(ContentType::Json { .. }) => Some(vec![
+            AcceptEntry::from(MediaType::Json),
+            AcceptEntry::new("*/*", 0.8),
+        ]),
+        _ => None,
+    }
+}
+
+fn explicit_header(request: &Request) -> Option<&str> {
+    request
+        .headers
+        .as_ref()
+        .and_then(|headers| headers.get("accept"))
+}
+
+// NOTE: An `Accept` header the caller set, or suppressed, wins over `accept`.
+pub(crate) fn headers(request: &Request) -> HashMap<String, String> {
+    let mut headers = HashMap::new();
+    let Some(entries) = entries(request) else {
+        return headers;
+    };
+
+    let has_accept = request
+        .headers
+        .as_ref()
+        .is_some_and(|headers| headers.contains_key("accept"));
+    let value = header_value(&entries);
+    if !has_accept && !value.is_empty() {
+        headers.insert("Accept".to_string(), value);
+    }
+    headers
+}
+
+// NOTE: Compares the response type against the `Accept` header that was
+// sent, the caller's own included. For each type the most specific entry
+// decides, as RFC 9110 orders media ranges.
+pub(crate) fn info(request: &Request, response: &Response) -> Option<NegotiationInfo> {
+    let entries = match explicit_header(request) {
+        Some(value) => parse_header(value),
+        None if request
+            .headers
+            .as_ref()
+            .is_some_and(|headers| headers.is_suppressed("accept")) =>
+        {
+            return None
+        }
+        None => entries(request)?,
+    };
+    if entries.is_empty() {
+        return None;
+    }
+
+    let essence = response
+        .header_str("content-type")?
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    if essence.is_empty() {
+        return None;
+    }
+
+    let matched = entries
+        .iter()
+        .filter_map(|entry| Some((entry.specificity(&essence)?, entry)))
+        .fold(
+            None,
+            |best: Option<(u8, &AcceptEntry)>, (specificity, entry)| match best {
+                Some((best_specificity, _)) if best_specificity >= specificity => best,
+                _ => Some((specificity, entry)),
+            },
+        )
+        .map(|(_, entry)| entry.clone());
+    let honored = matched.as_ref().is_some_and(|entry| entry.weight() > 0);
+    if !honored {
+        tracing::debug!(content_type = %essence, "Server ignored the Accept header");
+    }
+
+    Some(NegotiationInfo { honored, matched })
+}
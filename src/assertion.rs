@@ -0,0 +1,206 @@
+use regex::Regex;
+use serde_json::Value;
+
+use crate::interop::{Assertion, AssertionResult, Response};
+
+// NOTE: The dotted subset of JSONPath, `$.items[0].id` or `items.0.id`, with
+// bracketed indices and quoted keys for names holding dots, `$['a.b']`.
+// `None` for a malformed path.
+fn path_segments(path: &str) -> Option<Vec<&str>> {
+    let path = path.trim();
+    let mut rest = path.strip_prefix('$').unwrap_or(path);
+
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        if let Some(inner) = rest.strip_prefix('[') {
+            let (segment, after) = match inner.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let (key, after) = inner[1..].split_once(quote)?;
+                    (key, after.strip_prefix(']')?)
+                }
+                _ => {
+                    let (index, after) = inner.split_once(']')?;
+                    (index.trim(), after)
+                }
+            };
+            segments.push(segment);
+            rest = after;
+        } else {
+            let name = rest.strip_prefix('.').unwrap_or(rest);
+            let end = name.find(['.', '[']).unwrap_or(name.len());
+            if end == 0 {
+                return None;
+            }
+            segments.push(&name[..end]);
+            rest = &name[end..];
+        }
+    }
+    Some(segments)
+}
+
+fn lookup<'a>(value: &'a Value, segments: &[&str]) -> Option<&'a Value> {
+    segments
+        .iter()
+        .try_fold(value, |value, segment| match value {
+            Value::Object(map) => map.get(*segment),
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => None,
+        })
+}
+
+struct Evaluation<'a> {
+    response: &'a Response,
+    json: Option<Option<Value>>,
+}
+
+impl<'a> Evaluation<'a> {
+    // NOTE: Parsed at most once however many assertions read the body.
+    fn json(&mut self) -> Option<&Value> {
+        let body = &self.response.body.body;
+        self.json
+            .get_or_insert_with(|| serde_json::from_slice(body).ok())
+            .as_ref()
+    }
+
+    fn json_path(&mut self, path: &str) -> Result<Option<Value>, String> {
+        let segments = path_segments(path).ok_or_else(|| format!("Invalid JSON path {}", path))?;
+        let json = self.json().ok_or("Body is not JSON")?;
+        Ok(lookup(json, &segments).cloned())
+    }
+
+    fn header(&self, name: &str) -> Option<Value> {
+        self.response
+            .header_str(name)
+            .map(|value| Value::String(value.to_string()))
+    }
+
+    fn check(&mut self, assertion: &Assertion) -> AssertionResult {
+        let (passed, actual, message) = match assertion {
+            Assertion::StatusEquals { status } => {
+                let actual = self.response.status.as_u16();
+                (
+                    actual == *status,
+                    Some(actual.into()),
+                    format!("Expected status {}, got {}", status, actual),
+                )
+            }
+            Assertion::StatusInRange { min, max } => {
+                let actual = self.response.status.as_u16();
+                (
+                    (*min..=*max).contains(&actual),
+                    Some(actual.into()),
+                    format!("Expected status in {}..={}, got {}", min, max, actual),
+                )
+            }
+            Assertion::HeaderPresent { name } => {
+                let actual = self.header(name);
+                (
+                    actual.is_some(),
+                    actual,
+                    format!("Expected header {} to be present", name),
+                )
+            }
+            Assertion::HeaderEquals { name, value } => {
+                let actual = self.header(name);
+                (
+                    actual.as_ref().and_then(Value::as_str) == Some(value.as_str()),
+                    actual,
+                    format!("Expected header {} to equal {:?}", name, value),
+                )
+            }
+            Assertion::HeaderMatches { name, pattern } => {
+                let actual = self.header(name);
+                match Regex::new(pattern) {
+                    Ok(regex) => (
+                        actual
+                            .as_ref()
+                            .and_then(Value::as_str)
+                            .is_some_and(|value| regex.is_match(value)),
+                        actual,
+                        format!("Expected header {} to match /{}/", name, pattern),
+                    ),
+                    Err(e) => (
+                        false,
+                        actual,
+                        format!("Invalid pattern /{}/: {}", pattern, e),
+                    ),
+                }
+            }
+            Assertion::JsonPathEquals { path, value } => match self.json_path(path) {
+                Ok(actual) => (
+                    actual.as_ref() == Some(value),
+                    actual,
+                    format!("Expected {} to equal {}", path, value),
+                ),
+                Err(message) => (false, None, message),
+            },
+            Assertion::JsonPathExists { path } => match self.json_path(path) {
+                Ok(actual) => (
+                    actual.is_some(),
+                    actual,
+                    format!("Expected {} to exist", path),
+                ),
+                Err(message) => (false, None, message),
+            },
+            Assertion::BodyContains { text } => {
+                let body = String::from_utf8_lossy(&self.response.body.body);
+                (
+                    body.contains(text.as_str()),
+                    None,
+                    format!("Expected body to contain {:?}", text),
+                )
+            }
+            Assertion::ResponseTimeUnder { limit_ms } => {
+                let actual = self.response.meta.timing.duration().as_millis() as u64;
+                (
+                    actual < *limit_ms,
+                    Some(actual.into()),
+                    format!(
+                        "Expected response in under {}ms, took {}ms",
+                        limit_ms, actual
+                    ),
+                )
+            }
+            Assertion::BodySizeUnder { limit_bytes } => {
+                let actual = self.response.body.body.len() as u64;
+                (
+                    actual < *limit_bytes,
+                    Some(actual.into()),
+                    format!(
+                        "Expected body under {} bytes, got {} bytes",
+                        limit_bytes, actual
+                    ),
+                )
+            }
+        };
+
+        AssertionResult {
+            assertion: assertion.clone(),
+            passed,
+            actual,
+            message,
+        }
+    }
+}
+
+impl Response {
+    // NOTE: The same checks `RequestOptions.assertions` runs, for responses
+    // that did not come straight from a transfer, e.g. replayed ones.
+    pub fn check(&self, assertions: &[Assertion]) -> Vec<AssertionResult> {
+        let mut evaluation = Evaluation {
+            response: self,
+            json: None,
+        };
+        let results = assertions
+            .iter()
+            .map(|assertion| evaluation.check(assertion))
+            .collect::<Vec<_>>();
+
+        tracing::debug!(
+            total = results.len(),
+            failed = results.iter().filter(|result| !result.passed).count(),
+            "Evaluated response assertions"
+        );
+        results
+    }
+}
@@ -1,19 +1,33 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use curl::easy::Easy;
 use std::collections::HashMap;
 
 use crate::{
     error::{RelayError, Result},
     interop::{ApiKeyLocation, AuthType, GrantType, TokenResponse},
+    target_policy::Guard,
 };
 
 pub(crate) struct AuthHandler<'a> {
     handle: &'a mut Easy,
     headers: &'a mut HashMap<String, String>,
+    target_policy: Option<Guard>,
 }
 
 impl<'a> AuthHandler<'a> {
     pub(crate) fn new(handle: &'a mut Easy, headers: &'a mut HashMap<String, String>) -> Self {
-        Self { handle, headers }
+        Self {
+            handle,
+            headers,
+            target_policy: None,
+        }
+    }
+
+    // NOTE: Token endpoints are checked like the request itself, a token
+    // exchange must not become a way around the policy.
+    pub(crate) fn with_target_policy(mut self, target_policy: Option<Guard>) -> Self {
+        self.target_policy = target_policy;
+        self
     }
 
     #[tracing::instrument(skip(self), level = "debug")]
@@ -23,9 +37,18 @@ impl<'a> AuthHandler<'a> {
                 tracing::info!(username = %username, "Setting basic auth");
                 self.set_basic_auth(username, password)
             }
-            AuthType::Bearer { token } => {
+            AuthType::Bearer { .. } => {
                 tracing::info!("Setting bearer auth");
-                self.set_bearer_auth(token)
+                self.headers.extend(auth_headers(auth));
+                Ok(())
+            }
+            AuthType::Digest {
+                username,
+                nonce: Some(_),
+                ..
+            } => {
+                tracing::info!(username = %username, "Answering known digest challenge");
+                Ok(())
             }
             AuthType::Digest {
                 username, password, ..
@@ -33,13 +56,10 @@ impl<'a> AuthHandler<'a> {
                 tracing::info!(username = %username, "Setting digest auth");
                 self.set_digest_auth(username, password)
             }
-            AuthType::ApiKey {
-                key,
-                value,
-                location,
-            } => {
-                tracing::info!(key = %key, "Setting API key auth");
-                self.set_apikey_auth(key, value, location)
+            AuthType::ApiKey { key, location, .. } => {
+                tracing::info!(key = %key, location = ?location, "Setting API key auth");
+                self.headers.extend(auth_headers(auth));
+                Ok(())
             }
             AuthType::Aws {
                 access_key,
@@ -64,9 +84,10 @@ impl<'a> AuthHandler<'a> {
                 access_token,
                 refresh_token,
             } => {
-                if let Some(token) = access_token {
+                if access_token.is_some() {
                     tracing::info!("Using existing OAuth2 access token");
-                    self.set_bearer_auth(token)
+                    self.headers.extend(auth_headers(auth));
+                    Ok(())
                 } else if let Some(refresh_token) = refresh_token {
                     tracing::info!("Refreshing OAuth2 token");
                     self.refresh_oauth2_token(grant_type, refresh_token)
@@ -89,7 +110,7 @@ impl<'a> AuthHandler<'a> {
             tracing::error!(error = %e, "Failed to set username");
             RelayError::Network {
                 message: "Failed to set username".into(),
-                cause: Some(e.to_string()),
+                cause: Some(e.into()),
             }
         })?;
 
@@ -97,7 +118,7 @@ impl<'a> AuthHandler<'a> {
             tracing::error!(error = %e, "Failed to set password");
             RelayError::Network {
                 message: "Failed to set password".into(),
-                cause: Some(e.to_string()),
+                cause: Some(e.into()),
             }
         })?;
 
@@ -106,27 +127,8 @@ impl<'a> AuthHandler<'a> {
     }
 
     fn set_bearer_auth(&mut self, token: &str) -> Result<()> {
-        self.headers
-            .insert("Authorization".to_string(), format!("Bearer {}", token));
-        Ok(())
-    }
-
-    fn set_apikey_auth(&mut self, key: &str, value: &str, location: &ApiKeyLocation) -> Result<()> {
-        tracing::debug!(key = %key, location = ?location, "Setting API key auth");
-
-        match location {
-            ApiKeyLocation::Header => {
-                tracing::debug!("Adding API key as header: {}", key);
-                self.headers.insert(key.to_string(), value.to_string());
-            }
-            ApiKeyLocation::Query => {
-                // For query parameters, we don't need to do anything here
-                // This is handled in the request.rs file before setting the URL
-                tracing::debug!("API key will be added to query parameters in URL");
-            }
-        }
-
-        tracing::debug!("API key auth configured successfully");
+        let (name, value) = bearer_header(token);
+        self.headers.insert(name, value);
         Ok(())
     }
 
@@ -155,7 +157,7 @@ impl<'a> AuthHandler<'a> {
             tracing::error!(error = %e, "Failed to set digest authentication");
             RelayError::Network {
                 message: "Failed to set digest authentication".into(),
-                cause: Some(e.to_string()),
+                cause: Some(e.into()),
             }
         })?;
 
@@ -261,14 +263,21 @@ impl<'a> AuthHandler<'a> {
     }
 
     fn request_token(&mut self, token_endpoint: &str, params: &[(&str, &str)]) -> Result<()> {
+        let target_policy = self.target_policy.as_ref();
+        if let Some(target_policy) = target_policy {
+            target_policy.check_url(token_endpoint)?;
+        }
         let mut handle = Easy::new();
+        if let Some(target_policy) = target_policy {
+            target_policy.attach(&mut handle)?;
+        }
         tracing::debug!(endpoint = %token_endpoint, "Requesting OAuth2 token");
 
         handle.url(token_endpoint).map_err(|e| {
             tracing::error!(error = %e, "Failed to set token endpoint URL");
             RelayError::Network {
                 message: "Failed to set token endpoint URL".into(),
-                cause: Some(e.to_string()),
+                cause: Some(e.into()),
             }
         })?;
 
@@ -282,7 +291,7 @@ impl<'a> AuthHandler<'a> {
             tracing::error!(error = %e, "Failed to set form data");
             RelayError::Network {
                 message: "Failed to set form data".into(),
-                cause: Some(e.to_string()),
+                cause: Some(e.into()),
             }
         })?;
 
@@ -298,16 +307,19 @@ impl<'a> AuthHandler<'a> {
                     tracing::error!(error = %e, "Failed to set write callback");
                     RelayError::Network {
                         message: "Failed to set write callback".into(),
-                        cause: Some(e.to_string()),
+                        cause: Some(e.into()),
                     }
                 })?;
 
             tracing::debug!("Performing token request");
             transfer.perform().map_err(|e| {
+                if let Some(violation) = target_policy.and_then(Guard::take_violation) {
+                    return violation;
+                }
                 tracing::error!(error = %e, "Failed to perform token request");
                 RelayError::Network {
                     message: "Failed to perform token request".into(),
-                    cause: Some(e.to_string()),
+                    cause: Some(e.into()),
                 }
             })?;
         }
@@ -316,7 +328,8 @@ impl<'a> AuthHandler<'a> {
             tracing::error!(error = %e, "Failed to parse token response");
             RelayError::Parse {
                 message: "Failed to parse token response".into(),
-                cause: Some(e.to_string()),
+                cause: Some(e.into()),
+                partial: None,
             }
         })?;
 
@@ -324,3 +337,48 @@ impl<'a> AuthHandler<'a> {
         self.set_bearer_auth(&token_response.access_token)
     }
 }
+
+fn bearer_header(token: &str) -> (String, String) {
+    ("Authorization".to_string(), format!("Bearer {}", token))
+}
+
+// NOTE: The `Authorization` curl would build from basic credentials, for
+// when the header has to be known before the transfer, e.g. to sign it.
+pub(crate) fn basic_header(username: &str, password: &str) -> (String, String) {
+    (
+        "Authorization".to_string(),
+        format!(
+            "Basic {}",
+            BASE64.encode(format!("{}:{}", username, password))
+        ),
+    )
+}
+
+// NOTE: Headers that follow from the auth configuration alone. Basic and digest
+// credentials are handed to curl, which builds `Authorization` itself, and
+// OAuth2 tokens that still need an exchange are only known at send time.
+// API keys in the query are not sent, see `CurlRequest::setup_basics`.
+pub(crate) fn auth_headers(auth: &AuthType) -> HashMap<String, String> {
+    let mut headers = HashMap::new();
+
+    match auth {
+        AuthType::Bearer { token }
+        | AuthType::OAuth2 {
+            access_token: Some(token),
+            ..
+        } => {
+            let (name, value) = bearer_header(token);
+            headers.insert(name, value);
+        }
+        AuthType::ApiKey {
+            key,
+            value,
+            location: ApiKeyLocation::Header,
+        } => {
+            headers.insert(key.clone(), value.clone());
+        }
+        _ => {}
+    }
+
+    headers
+}
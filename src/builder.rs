@@ -0,0 +1,1107 @@
+use std::{path::Path, str::FromStr, time::Duration};
+
+use bytes::Bytes;
+use http::{Method, Version};
+use serde::Serialize;
+
+use crate::{
+    accept, content, csv,
+    error::{RelayError, Result},
+    ext_value::{self, HeaderEncoding},
+    header::{self, Headers},
+    id,
+    interop::{
+        AcceptEntry, Assertion, AuthType, BodyCapture, BodyHash, ByteRange, CertificateConfig,
+        CertificateType, ContentType, FormData, FormValue, IdempotencyKey, InsecureOverride,
+        MediaType, RawCurlOption, RelatedPart, Request, RequestMeta, RequestOptions,
+        SecurityConfig, TlsVersion, TraceContext,
+    },
+    query,
+};
+
+#[derive(Debug, Clone)]
+pub struct RequestBuilder {
+    id: Option<i64>,
+    method: Method,
+    url: Option<String>,
+    version: Version,
+    headers: Headers,
+    query: Vec<(String, String)>,
+    content: Option<ContentType>,
+    multipart: FormData,
+    sort_fields: bool,
+    auth: Option<AuthType>,
+    security: Option<SecurityConfig>,
+    trace_context: Option<TraceContext>,
+    range: Option<ByteRange>,
+    accept: Option<Vec<AcceptEntry>>,
+    options: RequestOptions,
+    raw_options: Vec<RawCurlOption>,
+    error: Option<RelayError>,
+}
+
+impl Default for RequestBuilder {
+    fn default() -> Self {
+        Self {
+            id: None,
+            method: Method::GET,
+            url: None,
+            version: Version::HTTP_11,
+            headers: Headers::new(),
+            query: Vec::new(),
+            content: None,
+            multipart: Vec::new(),
+            sort_fields: false,
+            auth: None,
+            security: None,
+            trace_context: None,
+            range: None,
+            accept: None,
+            options: RequestOptions::default(),
+            raw_options: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+impl Request {
+    pub fn builder() -> RequestBuilder {
+        RequestBuilder::new()
+    }
+}
+
+impl RequestBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn method(mut self, method: Method, url: impl Into<String>) -> Self {
+        self.method = method;
+        self.url = Some(url.into());
+        self
+    }
+
+    pub fn get(self, url: impl Into<String>) -> Self {
+        self.method(Method::GET, url)
+    }
+
+    pub fn post(self, url: impl Into<String>) -> Self {
+        self.method(Method::POST, url)
+    }
+
+    pub fn put(self, url: impl Into<String>) -> Self {
+        self.method(Method::PUT, url)
+    }
+
+    pub fn patch(self, url: impl Into<String>) -> Self {
+        self.method(Method::PATCH, url)
+    }
+
+    pub fn delete(self, url: impl Into<String>) -> Self {
+        self.method(Method::DELETE, url)
+    }
+
+    pub fn head(self, url: impl Into<String>) -> Self {
+        self.method(Method::HEAD, url)
+    }
+
+    pub fn options(self, url: impl Into<String>) -> Self {
+        self.method(Method::OPTIONS, url)
+    }
+
+    pub fn id(mut self, id: i64) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    pub fn version(mut self, version: Version) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    // NOTE: For values outside ASCII, which curl sends as raw UTF-8 bytes
+    // that servers often read as Latin-1.
+    pub fn header_encoded(
+        self,
+        name: impl Into<String>,
+        value: impl AsRef<str>,
+        encoding: HeaderEncoding,
+    ) -> Self {
+        self.header(
+            name,
+            ext_value::encode_header_value(value.as_ref(), encoding),
+        )
+    }
+
+    // NOTE: Unlike `header`, earlier values are kept and each one is sent as
+    // its own header line, in the order they were added.
+    pub fn append_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    // NOTE: Keeps the header off the wire entirely, including defaults curl
+    // or the content would otherwise add, e.g. `Accept` or `Content-Type`.
+    pub fn suppress_header(mut self, name: impl Into<String>) -> Self {
+        self.headers.suppress(name);
+        self
+    }
+
+    pub fn query(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn text(mut self, content: impl Into<String>) -> Self {
+        self.content = Some(ContentType::Text {
+            content: content.into(),
+            media_type: MediaType::TextPlain,
+        });
+        self
+    }
+
+    pub fn json<T: Serialize + ?Sized>(mut self, value: &T) -> Self {
+        match serde_json::to_value(value) {
+            Ok(content) => {
+                self.content = Some(ContentType::Json {
+                    content,
+                    raw: None,
+                    media_type: MediaType::Json,
+                })
+            }
+            Err(e) => self.fail(RelayError::Parse {
+                message: "Failed to serialize JSON content".into(),
+                cause: Some(e.into()),
+                partial: None,
+            }),
+        }
+        self
+    }
+
+    // NOTE: Sends `text` exactly as given, parsing it only to check it is
+    // JSON, e.g. a body whose signature was computed over these bytes.
+    pub fn raw_json(mut self, text: impl Into<String>) -> Self {
+        let text = text.into();
+        match serde_json::from_str(&text) {
+            Ok(content) => {
+                self.content = Some(ContentType::Json {
+                    content,
+                    raw: Some(text),
+                    media_type: MediaType::Json,
+                })
+            }
+            Err(e) => self.fail(RelayError::Parse {
+                message: "Invalid JSON content".into(),
+                cause: Some(e.into()),
+                partial: None,
+            }),
+        }
+        self
+    }
+
+    pub fn json_patch(mut self, operations: serde_json::Value) -> Self {
+        match content::validate_json_patch(&operations) {
+            Ok(()) => self.content = Some(ContentType::JsonPatch { operations }),
+            Err(e) => self.fail(e),
+        }
+        self
+    }
+
+    pub fn merge_patch<T: Serialize + ?Sized>(mut self, value: &T) -> Self {
+        match serde_json::to_value(value) {
+            Ok(content) => self.content = Some(ContentType::JsonMergePatch { content }),
+            Err(e) => self.fail(RelayError::Parse {
+                message: "Failed to serialize JSON merge patch".into(),
+                cause: Some(e.into()),
+                partial: None,
+            }),
+        }
+        self
+    }
+
+    pub fn csv(self, content: impl Into<String>, has_header: bool) -> Self {
+        self.csv_with_delimiter(content, has_header, csv::DEFAULT_DELIMITER)
+    }
+
+    pub fn csv_with_delimiter(
+        mut self,
+        content: impl Into<String>,
+        has_header: bool,
+        delimiter: char,
+    ) -> Self {
+        self.content = Some(ContentType::Csv {
+            content: content.into(),
+            has_header,
+            delimiter,
+        });
+        self
+    }
+
+    pub fn form<K, V>(mut self, pairs: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let content = url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(pairs)
+            .finish();
+        self.content = Some(ContentType::Urlencoded {
+            content,
+            media_type: MediaType::FormUrlEncoded,
+        });
+        self
+    }
+
+    pub fn binary(mut self, content: impl Into<Bytes>, media_type: MediaType) -> Self {
+        self.content = Some(ContentType::Binary {
+            content: content.into(),
+            media_type,
+            filename: None,
+        });
+        self
+    }
+
+    pub fn multipart_text(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.push_part(
+            name.into(),
+            FormValue::Text {
+                value: value.into(),
+            },
+        );
+        self
+    }
+
+    pub fn multipart_file(mut self, name: impl Into<String>, path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        match std::fs::read(path) {
+            Ok(data) => {
+                let filename = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or("file")
+                    .to_string();
+                let content_type = infer::get(&data)
+                    .and_then(|kind| MediaType::from_str(kind.mime_type()).ok())
+                    .unwrap_or(MediaType::OctetStream);
+                self.push_part(
+                    name.into(),
+                    FormValue::File {
+                        filename,
+                        content_type,
+                        data: data.into(),
+                    },
+                );
+            }
+            Err(e) => self.fail(RelayError::Io {
+                message: format!("Failed to read multipart file {}", path.display()),
+                cause: Some(e.into()),
+            }),
+        }
+        self
+    }
+
+    // NOTE: The `type` parameter is taken from the root's content type, set
+    // a different one through `ContentType::MultipartRelated` directly.
+    pub fn multipart_related(
+        mut self,
+        root: RelatedPart,
+        parts: impl IntoIterator<Item = RelatedPart>,
+    ) -> Self {
+        self.content = Some(ContentType::MultipartRelated {
+            root,
+            parts: parts.into_iter().collect(),
+            type_param: String::new(),
+        });
+        self
+    }
+
+    // NOTE: Fields are otherwise sent in the order they were added, this
+    // sorts form, multipart and urlencoded fields by name instead.
+    pub fn sort_form_fields(mut self) -> Self {
+        self.sort_fields = true;
+        self
+    }
+
+    pub fn basic_auth(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.auth = Some(AuthType::Basic {
+            username: username.into(),
+            password: password.into(),
+        });
+        self
+    }
+
+    // NOTE: curl answers the server's challenge, see
+    // `AuthType::negotiate_digest` to pick the algorithm beforehand.
+    pub fn digest_auth(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.auth = Some(AuthType::Digest {
+            username: username.into(),
+            password: password.into(),
+            realm: None,
+            nonce: None,
+            opaque: None,
+            algorithm: None,
+            qop: None,
+            nc: None,
+            cnonce: None,
+        });
+        self
+    }
+
+    pub fn bearer(mut self, token: impl Into<String>) -> Self {
+        self.auth = Some(AuthType::Bearer {
+            token: token.into(),
+        });
+        self
+    }
+
+    pub fn security(mut self, security: SecurityConfig) -> Self {
+        self.security = Some(security);
+        self
+    }
+
+    pub fn trace_context(mut self, context: TraceContext) -> Self {
+        self.trace_context = Some(context);
+        self
+    }
+
+    // NOTE: Most preferred first, later types get descending q-values. Use
+    // `accept_entries` to pick the q-values or send wildcards.
+    pub fn accept(mut self, types: impl IntoIterator<Item = MediaType>) -> Self {
+        self.accept = Some(accept::weighted(types));
+        self
+    }
+
+    pub fn accept_entries(mut self, entries: impl IntoIterator<Item = AcceptEntry>) -> Self {
+        self.accept = Some(entries.into_iter().collect());
+        self
+    }
+
+    pub fn range(mut self, range: ByteRange) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    pub fn idempotency_key(mut self, key: IdempotencyKey) -> Self {
+        self.options.idempotency_key = Some(key);
+        self
+    }
+
+    pub fn idempotency_header(mut self, name: impl Into<String>) -> Self {
+        self.options.idempotency_header = Some(name.into());
+        self
+    }
+
+    // NOTE: Off asks the server for `identity` instead of every coding curl
+    // can decode.
+    pub fn decompress(mut self, enable: bool) -> Self {
+        self.options.decompress = Some(enable);
+        self
+    }
+
+    // NOTE: For servers that send gzip without saying so, bodies labelled
+    // `application/gzip` are left alone.
+    pub fn detect_gzip(mut self, enable: bool) -> Self {
+        self.options.detect_gzip = Some(enable);
+        self
+    }
+
+    // NOTE: For previews that only need the start of the body, sizes still
+    // count every byte and `Response.body_truncated` marks a cut body.
+    pub fn body_capture(mut self, capture: BodyCapture) -> Self {
+        self.options.body_capture = Some(capture);
+        self
+    }
+
+    pub fn capture_trace(mut self, capture: bool) -> Self {
+        self.options.capture_trace = Some(capture);
+        self
+    }
+
+    pub fn force_charset(mut self, charset: impl Into<String>) -> Self {
+        self.options.force_charset = Some(charset.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeout = Some(timeout.as_millis().min(u64::MAX as u128) as u64);
+        self
+    }
+
+    pub fn first_byte_timeout(mut self, timeout: Duration) -> Self {
+        self.options.first_byte_timeout = Some(timeout.as_millis().min(u64::MAX as u128) as u64);
+        self
+    }
+
+    pub fn happy_eyeballs_timeout(mut self, timeout: Duration) -> Self {
+        self.options.happy_eyeballs_timeout_ms =
+            Some(timeout.as_millis().min(u64::MAX as u128) as u64);
+        self
+    }
+
+    pub fn dns_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.options.dns_cache_ttl = Some(ttl.as_secs());
+        self
+    }
+
+    pub fn max_response_time(mut self, limit: Duration) -> Self {
+        self.options.max_response_time = Some(limit.as_millis().min(u64::MAX as u128) as u64);
+        self
+    }
+
+    // NOTE: Applied after every managed setting, so a raw option wins over
+    // the builder method for the same curl option.
+    pub fn raw_option(mut self, option: RawCurlOption) -> Self {
+        self.raw_options.push(option);
+        self
+    }
+
+    pub fn max_partial_body_bytes(mut self, limit: u64) -> Self {
+        self.options.max_partial_body_bytes = Some(limit);
+        self
+    }
+
+    pub fn fail_on_error(mut self, fail: bool) -> Self {
+        self.options.fail_on_error = Some(fail);
+        self
+    }
+
+    // NOTE: Through `RelayClient` the cookie jar is shared by every request
+    // that enables it, otherwise it only lasts for this request's redirects.
+    pub fn cookies(mut self, enable: bool) -> Self {
+        self.options.cookies = Some(enable);
+        self
+    }
+
+    pub fn follow_redirects(mut self, follow: bool) -> Self {
+        self.options.follow_redirects = Some(follow);
+        self
+    }
+
+    pub fn max_redirects(mut self, max: u32) -> Self {
+        self.options.max_redirects = Some(max);
+        self
+    }
+
+    // NOTE: Fails the request with `BodyHashMismatch` when the body does not
+    // match `expected`, the digest is on `Response.body_hash` either way.
+    pub fn body_hash(mut self, hash: BodyHash) -> Self {
+        self.options.body_hash = Some(hash);
+        self
+    }
+
+    // NOTE: Adds to the assertions already given, verdicts are on
+    // `ResponseMeta.assertions`.
+    pub fn assertion(mut self, assertion: Assertion) -> Self {
+        self.options
+            .assertions
+            .get_or_insert_with(Vec::new)
+            .push(assertion);
+        self
+    }
+
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.options.buffer_size = Some(size);
+        self
+    }
+
+    pub fn upload_buffer_size(mut self, size: usize) -> Self {
+        self.options.upload_buffer_size = Some(size);
+        self
+    }
+
+    pub fn tcp_fastopen(mut self, enable: bool) -> Self {
+        self.options.tcp_fastopen = Some(enable);
+        self
+    }
+
+    // NOTE: Either an interface name such as `eth0` or a local IP address,
+    // curl's `if!` and `host!` prefixes force one interpretation.
+    pub fn interface(mut self, interface: impl Into<String>) -> Self {
+        self.options.interface = Some(interface.into());
+        self
+    }
+
+    // NOTE: `host[:port]` addresses, used instead of the system resolver. Only
+    // available when libcurl is built with c-ares.
+    pub fn dns_servers<I, S>(mut self, servers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.dns_servers = Some(servers.into_iter().map(Into::into).collect());
+        self
+    }
+
+    // NOTE: The interface DNS queries go out on, e.g. `eth1`, when the
+    // resolver is only reachable from there. Needs c-ares like `dns_servers`.
+    pub fn dns_interface(mut self, interface: impl Into<String>) -> Self {
+        self.options.dns_interface = Some(interface.into());
+        self
+    }
+
+    pub fn dns_local_ip4(mut self, ip: impl Into<String>) -> Self {
+        self.options.dns_local_ip4 = Some(ip.into());
+        self
+    }
+
+    pub fn dns_local_ip6(mut self, ip: impl Into<String>) -> Self {
+        self.options.dns_local_ip6 = Some(ip.into());
+        self
+    }
+
+    // NOTE: Resolves host names over DNS-over-HTTPS through this endpoint,
+    // e.g. `https://dns.example/dns-query`.
+    pub fn doh_url(mut self, url: impl Into<String>) -> Self {
+        self.options.doh_url = Some(url.into());
+        self
+    }
+
+    // NOTE: Skips TLS verification for this request only, as long as its URL
+    // points at `host`. Never serialized, so it cannot be saved or exported.
+    pub fn insecure_override(mut self, host: impl Into<String>, reason: impl Into<String>) -> Self {
+        self.options.insecure_override = Some(InsecureOverride {
+            host: host.into(),
+            reason: reason.into(),
+        });
+        self
+    }
+
+    // NOTE: Trims whitespace around header names and values and drops headers
+    // left without a value, instead of rejecting or sending them as-is.
+    pub fn sanitize_headers(mut self, sanitize: bool) -> Self {
+        self.options.sanitize_headers = Some(sanitize);
+        self
+    }
+
+    fn push_part(&mut self, name: String, value: FormValue) {
+        content::push_field(&mut self.multipart, name, value);
+    }
+
+    fn fail(&mut self, error: RelayError) {
+        tracing::error!(error = %error, "Invalid request builder input");
+        self.error.get_or_insert(error);
+    }
+
+    #[tracing::instrument(skip(self), level = "debug")]
+    pub fn build(self) -> Result<Request> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+
+        let url = self.url.ok_or_else(|| RelayError::Parse {
+            message: "Request URL is required".into(),
+            cause: None,
+            partial: None,
+        })?;
+
+        let (raw, zone) = query::split_zone(&url);
+        let mut url = query::parse(&raw)?;
+
+        // NOTE: `ws` and `wss` are for `RelayClient::websocket`, sending them
+        // as plain requests fails validation.
+        if !matches!(url.scheme(), "http" | "https" | "ws" | "wss") {
+            return Err(RelayError::Parse {
+                message: format!("Unsupported URL scheme: {}", url.scheme()),
+                cause: None,
+                partial: None,
+            });
+        }
+
+        if !self.query.is_empty() {
+            url.query_pairs_mut().extend_pairs(&self.query);
+        }
+
+        let mut content = match (self.content, self.multipart.is_empty()) {
+            (Some(_), false) => {
+                return Err(RelayError::Parse {
+                    message: "Request cannot have both a body and multipart fields".into(),
+                    cause: None,
+                    partial: None,
+                })
+            }
+            (content, true) => content,
+            (None, false) => Some(ContentType::Multipart {
+                content: self.multipart,
+                media_type: MediaType::MultipartFormData,
+            }),
+        };
+        if self.sort_fields {
+            content.iter_mut().for_each(content::sort_fields);
+        }
+
+        let mut headers = match self.options.sanitize_headers {
+            Some(true) => header::sanitize(self.headers),
+            _ => self.headers,
+        };
+        for name in headers.keys().chain(headers.suppressed()) {
+            header::check_name(name).map_err(|reason| RelayError::InvalidHeader {
+                name: name.clone(),
+                reason,
+            })?;
+        }
+        for (name, value) in &headers {
+            header::check_value(value).map_err(|reason| RelayError::InvalidHeader {
+                name: name.clone(),
+                reason,
+            })?;
+        }
+
+        // NOTE: Multipart boundaries are generated by curl, or with the
+        // parameters of `multipart/related` when the body is built, so only
+        // bodies sent as-is get their `Content-Type` derived from the content
+        // media type, or the parameters it is sent with.
+        let content_type = content
+            .as_ref()
+            .filter(|content| {
+                !matches!(
+                    content,
+                    ContentType::Form { .. }
+                        | ContentType::Multipart { .. }
+                        | ContentType::MultipartRelated { .. }
+                )
+            })
+            .map(|content| {
+                content::content_headers(content)
+                    .remove("Content-Type")
+                    .unwrap_or_else(|| content::media_type(content).to_string())
+            });
+
+        if let Some(content_type) = content_type {
+            if !headers.contains_key("content-type") {
+                headers.append("Content-Type", content_type);
+            }
+        }
+
+        let id = self.id.unwrap_or_else(id::next_request_id);
+
+        let request = Request {
+            id,
+            url: query::with_zone(&url, zone.as_deref()),
+            method: self.method,
+            version: self.version,
+            headers: (!headers.is_empty()).then_some(headers),
+            params: None,
+            content,
+            auth: self.auth,
+            security: self.security,
+            proxy: None,
+            meta: Some(RequestMeta {
+                options: Some(self.options),
+            }),
+            trace_context: self.trace_context,
+            range: self.range,
+            accept: self.accept,
+            raw_options: self.raw_options,
+            secrets: Default::default(),
+            target_policy: None,
+        };
+
+        tracing::debug!(
+            request_id = request.id,
+            method = %request.method,
+            url = %request.url,
+            "Built request"
+        );
+
+        Ok(request)
+    }
+}
+
+/// Builds a `SecurityConfig` that is secure unless told otherwise.
+///
+/// Without further calls the result verifies both the peer certificate and
+/// the host name and refuses anything older than TLS 1.2. Verification is
+/// only turned off by an explicit `insecure()`.
+#[derive(Debug, Clone)]
+pub struct SecurityConfigBuilder {
+    verify: bool,
+    client: Option<CertificateType>,
+    ca: Vec<Bytes>,
+    min_tls_version: TlsVersion,
+    enable_alpn: Option<bool>,
+    false_start: Option<bool>,
+    session_id_cache: Option<bool>,
+}
+
+impl Default for SecurityConfigBuilder {
+    fn default() -> Self {
+        Self {
+            verify: true,
+            client: None,
+            ca: Vec::new(),
+            min_tls_version: TlsVersion::Tls1_2,
+            enable_alpn: None,
+            false_start: None,
+            session_id_cache: None,
+        }
+    }
+}
+
+impl SecurityConfig {
+    pub fn builder() -> SecurityConfigBuilder {
+        SecurityConfigBuilder::new()
+    }
+}
+
+impl SecurityConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // NOTE: The default, spelled out for call sites that want it visible or
+    // to undo an earlier `insecure()`.
+    pub fn verify_all(mut self) -> Self {
+        self.verify = true;
+        self
+    }
+
+    // NOTE: Accepts any certificate for any host name. Prefer
+    // `RequestBuilder::insecure_override`, which is scoped to a single host.
+    pub fn insecure(mut self) -> Self {
+        tracing::warn!("Building a security config with TLS verification disabled");
+        self.verify = false;
+        self
+    }
+
+    pub fn with_client_cert(mut self, certificate: CertificateType) -> Self {
+        self.client = Some(certificate);
+        self
+    }
+
+    // NOTE: Adds a PEM encoded CA to trust, may be called once per CA.
+    pub fn with_ca(mut self, ca: impl Into<Bytes>) -> Self {
+        self.ca.push(ca.into());
+        self
+    }
+
+    pub fn min_tls(mut self, version: TlsVersion) -> Self {
+        self.min_tls_version = version;
+        self
+    }
+
+    pub fn enable_alpn(mut self, enable: bool) -> Self {
+        self.enable_alpn = Some(enable);
+        self
+    }
+
+    pub fn false_start(mut self, enable: bool) -> Self {
+        self.false_start = Some(enable);
+        self
+    }
+
+    pub fn session_id_cache(mut self, enable: bool) -> Self {
+        self.session_id_cache = Some(enable);
+        self
+    }
+
+    pub fn build(self) -> SecurityConfig {
+        let certificates =
+            (self.client.is_some() || !self.ca.is_empty()).then(|| CertificateConfig {
+                client: self.client,
+                ca: (!self.ca.is_empty()).then_some(self.ca),
+            });
+
+        SecurityConfig {
+            certificates,
+            verify_host: Some(self.verify),
+            verify_peer: Some(self.verify),
+            min_tls_version: Some(self.min_tls_version),
+            enable_alpn: self.enable_alpn,
+            false_start: self.false_start,
+            session_id_cache: self.session_id_cache,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn parse_error(builder: RequestBuilder) -> String {
+        match builder.build() {
+            Err(RelayError::Parse { message, .. }) => message,
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    fn content_type(request: &Request) -> Option<&str> {
+        request.headers.as_ref()?.get("content-type")
+    }
+
+    #[test]
+    fn requires_a_valid_http_url() {
+        assert_eq!(
+            parse_error(RequestBuilder::new()),
+            "Request URL is required"
+        );
+        assert_eq!(
+            parse_error(RequestBuilder::new().get("ftp://example.com/file")),
+            "Unsupported URL scheme: ftp"
+        );
+        assert_eq!(
+            parse_error(RequestBuilder::new().get("https://exa mple.com/")),
+            "Invalid URL, unexpected ' ' at position 11"
+        );
+        assert!(RequestBuilder::new()
+            .get("wss://example.com/")
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn assigns_fresh_ids_unless_given_one() {
+        let first = Request::builder()
+            .get("https://example.com/")
+            .build()
+            .unwrap();
+        let second = Request::builder()
+            .get("https://example.com/")
+            .build()
+            .unwrap();
+        assert!(first.id > 0);
+        assert!(second.id > first.id);
+
+        let given = Request::builder()
+            .get("https://example.com/")
+            .id(42)
+            .build()
+            .unwrap();
+        assert_eq!(given.id, 42);
+    }
+
+    #[test]
+    fn appends_query_pairs_to_the_url() {
+        let request = Request::builder()
+            .get("https://example.com/search?page=1")
+            .query("q", "a b&c")
+            .build()
+            .unwrap();
+        assert_eq!(request.url, "https://example.com/search?page=1&q=a+b%26c");
+    }
+
+    #[test]
+    fn json_sets_the_content_and_its_type() {
+        let request = Request::builder()
+            .post("https://example.com/")
+            .json(&json!({ "a": [1, 2] }))
+            .build()
+            .unwrap();
+
+        assert!(matches!(
+            request.content,
+            Some(ContentType::Json { ref content, raw: None, media_type: MediaType::Json })
+                if *content == json!({ "a": [1, 2] })
+        ));
+        assert_eq!(content_type(&request), Some("application/json"));
+    }
+
+    #[test]
+    fn keeps_an_explicit_content_type() {
+        let request = Request::builder()
+            .post("https://example.com/")
+            .header("Content-Type", "application/vnd.api+json")
+            .json(&json!({}))
+            .build()
+            .unwrap();
+
+        assert_eq!(content_type(&request), Some("application/vnd.api+json"));
+        assert_eq!(request.headers.unwrap().get_all("content-type").count(), 1);
+    }
+
+    #[test]
+    fn form_encodes_pairs_in_order() {
+        let request = Request::builder()
+            .post("https://example.com/")
+            .form([("name", "a b"), ("tags", "x&y"), ("name", "c")])
+            .build()
+            .unwrap();
+
+        assert!(matches!(
+            request.content,
+            Some(ContentType::Urlencoded { ref content, media_type: MediaType::FormUrlEncoded })
+                if content == "name=a+b&tags=x%26y&name=c"
+        ));
+        assert_eq!(
+            content_type(&request),
+            Some("application/x-www-form-urlencoded")
+        );
+    }
+
+    #[test]
+    fn multipart_file_reads_the_file_and_sniffs_its_type() {
+        let path = std::env::temp_dir().join(format!("relay-builder-{}.pdf", std::process::id()));
+        std::fs::write(&path, b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n").unwrap();
+
+        let request = Request::builder()
+            .post("https://example.com/")
+            .multipart_text("title", "report")
+            .multipart_file("upload", &path)
+            .build()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let Some(ContentType::Multipart { content, .. }) = request.content else {
+            panic!("expected multipart content");
+        };
+        let names = content
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["title", "upload"]);
+        assert!(matches!(
+            &content[1].1[..],
+            [FormValue::File { filename, content_type: MediaType::ApplicationPdf, data }]
+                if filename == path.file_name().unwrap().to_str().unwrap()
+                    && data.starts_with(b"%PDF-")
+        ));
+        // NOTE: curl picks the boundary, so the header is left to it.
+        assert_eq!(content_type(&request), None);
+    }
+
+    #[test]
+    fn multipart_file_fails_the_build_when_unreadable() {
+        let result = Request::builder()
+            .post("https://example.com/")
+            .multipart_file("upload", "/nonexistent/relay/file.bin")
+            .build();
+        assert!(matches!(result, Err(RelayError::Io { .. })));
+    }
+
+    #[test]
+    fn rejects_a_body_together_with_multipart_fields() {
+        let builder = Request::builder()
+            .post("https://example.com/")
+            .text("body")
+            .multipart_text("field", "value");
+        assert_eq!(
+            parse_error(builder),
+            "Request cannot have both a body and multipart fields"
+        );
+    }
+
+    #[test]
+    fn sets_basic_and_bearer_auth() {
+        let basic = Request::builder()
+            .get("https://example.com/")
+            .basic_auth("user", "pass")
+            .build()
+            .unwrap();
+        assert!(matches!(
+            basic.auth,
+            Some(AuthType::Basic { ref username, ref password })
+                if username == "user" && password == "pass"
+        ));
+
+        let bearer = Request::builder()
+            .get("https://example.com/")
+            .basic_auth("user", "pass")
+            .bearer("token")
+            .build()
+            .unwrap();
+        assert!(matches!(
+            bearer.auth,
+            Some(AuthType::Bearer { ref token }) if token == "token"
+        ));
+    }
+
+    fn verification(config: &SecurityConfig) -> (Option<bool>, Option<bool>) {
+        (config.verify_peer, config.verify_host)
+    }
+
+    #[test]
+    fn security_defaults_verify_everything_from_tls_1_2() {
+        let config = SecurityConfig::builder().build();
+        assert_eq!(verification(&config), (Some(true), Some(true)));
+        assert_eq!(config.min_tls_version, Some(TlsVersion::Tls1_2));
+        assert!(config.certificates.is_none());
+        assert_eq!(
+            (
+                config.enable_alpn,
+                config.false_start,
+                config.session_id_cache
+            ),
+            (None, None, None)
+        );
+    }
+
+    #[test]
+    fn the_last_of_insecure_and_verify_all_wins() {
+        let insecure = SecurityConfig::builder().insecure().build();
+        assert_eq!(verification(&insecure), (Some(false), Some(false)));
+
+        let restored = SecurityConfig::builder().insecure().verify_all().build();
+        assert_eq!(verification(&restored), (Some(true), Some(true)));
+
+        let overridden = SecurityConfig::builder().verify_all().insecure().build();
+        assert_eq!(verification(&overridden), (Some(false), Some(false)));
+    }
+
+    #[test]
+    fn cas_are_kept_in_order_whatever_the_verification() {
+        let config = SecurityConfig::builder()
+            .with_ca("first")
+            .insecure()
+            .with_ca(Bytes::from_static(b"second"))
+            .min_tls(TlsVersion::Tls1_3)
+            .build();
+
+        assert_eq!(verification(&config), (Some(false), Some(false)));
+        assert_eq!(config.min_tls_version, Some(TlsVersion::Tls1_3));
+        let certificates = config.certificates.unwrap();
+        assert!(certificates.client.is_none());
+        assert_eq!(
+            certificates.ca.unwrap(),
+            [Bytes::from_static(b"first"), Bytes::from_static(b"second")]
+        );
+    }
+
+    #[test]
+    fn a_client_certificate_alone_adds_no_cas() {
+        let config = SecurityConfig::builder()
+            .with_client_cert(CertificateType::Pem {
+                cert: Bytes::from_static(b"cert"),
+                key: Bytes::from_static(b"key"),
+            })
+            .min_tls(TlsVersion::Tls1_2)
+            .build();
+
+        assert_eq!(verification(&config), (Some(true), Some(true)));
+        let certificates = config.certificates.unwrap();
+        assert!(matches!(
+            certificates.client,
+            Some(CertificateType::Pem { ref cert, .. }) if cert == "cert"
+        ));
+        assert!(certificates.ca.is_none());
+    }
+
+    #[test]
+    fn tls_toggles_are_carried_over() {
+        let config = SecurityConfig::builder()
+            .enable_alpn(false)
+            .false_start(true)
+            .session_id_cache(false)
+            .build();
+        assert_eq!(
+            (
+                config.enable_alpn,
+                config.false_start,
+                config.session_id_cache
+            ),
+            (Some(false), Some(true), Some(false))
+        );
+
+        let request = Request::builder()
+            .get("https://example.com/")
+            .security(config)
+            .build()
+            .unwrap();
+        assert_eq!(request.security.unwrap().enable_alpn, Some(false));
+    }
+}
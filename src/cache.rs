@@ -0,0 +1,607 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, SystemTime},
+};
+
+use http::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc2822, OffsetDateTime};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::{RelayError, Result},
+    interop::{AuthType, CacheStatus, Request, Response},
+    query,
+    transport::Transport,
+};
+
+// NOTE: Statuses RFC 9111 lets a cache store without explicit permission,
+// anything else is always fetched.
+const CACHEABLE_STATUSES: &[u16] = &[200, 203, 204, 300, 301, 308, 404, 405, 410, 414, 501];
+
+// NOTE: Headers a `304 Not Modified` must not overwrite on the stored
+// response, they describe the empty 304 body rather than the cached one.
+const KEEP_ON_REVALIDATE: &[&str] = &["content-length", "content-encoding", "transfer-encoding"];
+
+// NOTE: URLs kept in memory before the least recently used is dropped.
+const DEFAULT_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub revalidated: u64,
+    pub misses: u64,
+    pub bypassed: u64,
+    pub evicted: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    max_age: Option<u64>,
+}
+
+impl CacheControl {
+    // NOTE: Directives are case-insensitive and unknown ones are ignored, a
+    // `max-age` that does not parse counts as already stale.
+    fn parse(value: Option<&str>) -> Self {
+        let mut directives = Self::default();
+        let Some(value) = value else {
+            return directives;
+        };
+
+        for directive in value.split(',') {
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name.trim(), Some(argument.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            if name.eq_ignore_ascii_case("no-store") {
+                directives.no_store = true;
+            } else if name.eq_ignore_ascii_case("no-cache") {
+                directives.no_cache = true;
+            } else if name.eq_ignore_ascii_case("max-age") {
+                directives.max_age = Some(argument.and_then(|age| age.parse().ok()).unwrap_or(0));
+            }
+        }
+
+        directives
+    }
+}
+
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    OffsetDateTime::parse(value.trim(), &Rfc2822)
+        .map(SystemTime::from)
+        .map_err(|e| tracing::debug!(value = %value, error = %e, "Unparseable HTTP date"))
+        .ok()
+}
+
+impl Response {
+    fn cache_control(&self) -> CacheControl {
+        CacheControl::parse(self.header_joined("cache-control").as_deref())
+    }
+
+    // NOTE: `max-age` wins over `Expires`, which is measured against the
+    // server's `Date` so clock skew between the two hosts does not matter.
+    // An `Expires` that does not parse means already expired.
+    fn freshness_lifetime(&self, stored_at: SystemTime) -> Duration {
+        if let Some(max_age) = self.cache_control().max_age {
+            return Duration::from_secs(max_age);
+        }
+        let Some(expires) = self.header_str("expires") else {
+            return Duration::ZERO;
+        };
+        let date = self
+            .header_str("date")
+            .and_then(parse_http_date)
+            .unwrap_or(stored_at);
+        parse_http_date(expires)
+            .and_then(|expires| expires.duration_since(date).ok())
+            .unwrap_or(Duration::ZERO)
+    }
+
+    fn is_storable(&self) -> bool {
+        let cache_control = self.cache_control();
+        CACHEABLE_STATUSES.contains(&self.status.as_u16())
+            && !self.body_truncated
+            && !cache_control.no_store
+            && self.header_str("vary").map(str::trim) != Some("*")
+            && (cache_control.max_age.is_some()
+                || self.header_str("expires").is_some()
+                || self.etag().is_some()
+                || self.last_modified().is_some())
+    }
+}
+
+fn request_header(request: &Request, name: &str) -> Option<String> {
+    let headers = request.headers.as_ref()?;
+    let values = headers.get_all(name).collect::<Vec<_>>();
+    (!values.is_empty()).then(|| values.join(", "))
+}
+
+fn is_authenticated(request: &Request) -> bool {
+    !matches!(request.auth, None | Some(AuthType::None))
+        || request_header(request, "authorization").is_some()
+}
+
+fn cache_key(method: &Method, url: &str) -> String {
+    format!("{} {}", method, url)
+}
+
+// NOTE: One stored response, together with the request header values it
+// was selected by according to its `Vary`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    vary: Vec<(String, Option<String>)>,
+    response: Response,
+    stored_at: SystemTime,
+}
+
+impl CacheEntry {
+    fn new(request: &Request, response: Response, stored_at: SystemTime) -> Self {
+        let vary = response
+            .header_joined("vary")
+            .map(|vary| {
+                vary.split(',')
+                    .map(|name| name.trim().to_ascii_lowercase())
+                    .filter(|name| !name.is_empty())
+                    .map(|name| {
+                        let value = request_header(request, &name);
+                        (name, value)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            vary,
+            response,
+            stored_at,
+        }
+    }
+
+    fn matches(&self, request: &Request) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| request_header(request, name) == *value)
+    }
+
+    fn is_fresh(&self, now: SystemTime) -> bool {
+        if self.response.cache_control().no_cache {
+            return false;
+        }
+        let age = self
+            .response
+            .header_str("age")
+            .and_then(|age| age.trim().parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or_default();
+        let resident = now.duration_since(self.stored_at).unwrap_or_default();
+        age + resident < self.response.freshness_lifetime(self.stored_at)
+    }
+
+    fn has_validators(&self) -> bool {
+        self.response.etag().is_some() || self.response.last_modified().is_some()
+    }
+}
+
+enum Lookup {
+    Bypass,
+    Miss(String),
+    Fresh(Response),
+    Stale(String, CacheEntry),
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<String, Vec<CacheEntry>>,
+    // NOTE: When each key was last used, in ticks of a counter rather than
+    // the clock so a test clock standing still cannot tie them.
+    used: HashMap<String, u64>,
+    ticks: u64,
+    stats: CacheStats,
+}
+
+impl CacheState {
+    fn touch(&mut self, key: &str) {
+        self.ticks += 1;
+        self.used.insert(key.to_string(), self.ticks);
+    }
+
+    fn insert(&mut self, key: String, entries: Vec<CacheEntry>, capacity: usize) {
+        self.touch(&key);
+        self.entries.insert(key, entries);
+        while self.entries.len() > capacity {
+            let Some(oldest) = self
+                .used
+                .iter()
+                .min_by_key(|(_, tick)| **tick)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            tracing::debug!(key = %oldest, "Evicting least recently used cache entry");
+            self.forget(&oldest);
+            self.stats.evicted += 1;
+        }
+    }
+
+    fn forget(&mut self, key: &str) {
+        self.entries.remove(key);
+        self.used.remove(key);
+    }
+}
+
+// NOTE: Private HTTP cache for a `RelayClient`. Stores `GET` responses by
+// URL and `Vary`, serving them while fresh under `Cache-Control: max-age` or
+// `Expires` and revalidating them with `If-None-Match` and
+// `If-Modified-Since` once stale. Clones share their entries and statistics.
+#[derive(Clone)]
+pub struct HttpCache {
+    state: Arc<Mutex<CacheState>>,
+    dir: Option<PathBuf>,
+    clock: Arc<dyn Clock>,
+    authenticated: bool,
+    capacity: usize,
+}
+
+impl Default for HttpCache {
+    fn default() -> Self {
+        Self {
+            state: Arc::default(),
+            dir: None,
+            clock: Arc::new(SystemClock),
+            authenticated: false,
+            capacity: DEFAULT_CAPACITY,
+        }
+    }
+}
+
+impl std::fmt::Debug for HttpCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpCache")
+            .field("dir", &self.dir)
+            .field("authenticated", &self.authenticated)
+            .field("capacity", &self.capacity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HttpCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // NOTE: Also writes entries to `dir`, one JSON file per URL, and reads
+    // them back on a miss in memory so the cache survives restarts. The
+    // directory belongs to the cache, `purge` empties it.
+    pub fn persistent(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).map_err(|e| {
+            tracing::error!(error = %e, path = %dir.display(), "Failed to create cache directory");
+            RelayError::Io {
+                message: format!("Failed to create cache directory {}", dir.display()),
+                cause: Some(e.into()),
+            }
+        })?;
+
+        Ok(Self {
+            dir: Some(dir),
+            ..Self::default()
+        })
+    }
+
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    // NOTE: Requests with `auth` or an `Authorization` header bypass the
+    // cache unless enabled, their responses are often per user.
+    pub fn with_authenticated(mut self, enable: bool) -> Self {
+        self.authenticated = enable;
+        self
+    }
+
+    // NOTE: The most URLs held in memory, each with all its `Vary` variants.
+    // Past it the least recently used URL is dropped from memory, a
+    // persistent cache keeps its file and loads it again when next asked.
+    pub fn with_capacity(mut self, urls: usize) -> Self {
+        self.capacity = urls;
+        self
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.state().stats
+    }
+
+    // NOTE: Counts the responses held in memory, entries only on disk are
+    // loaded the first time their URL is requested.
+    pub fn len(&self) -> usize {
+        self.state().entries.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn purge(&self) -> Result<()> {
+        {
+            let mut state = self.state();
+            state.entries.clear();
+            state.used.clear();
+        }
+
+        let Some(ref dir) = self.dir else {
+            return Ok(());
+        };
+        let entries = std::fs::read_dir(dir).map_err(|e| RelayError::Io {
+            message: format!("Failed to read cache directory {}", dir.display()),
+            cause: Some(e.into()),
+        })?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                remove_file(&path)?;
+            }
+        }
+
+        tracing::debug!(path = %dir.display(), "Purged cache");
+        Ok(())
+    }
+
+    // NOTE: Drops every stored variant of a `GET` for `url`.
+    pub fn purge_url(&self, url: &str) -> Result<()> {
+        let url = query::parse(url)
+            .map(|url| url.to_string())
+            .unwrap_or_else(|_| url.to_string());
+        self.remove(&cache_key(&Method::GET, &url))
+    }
+
+    fn state(&self) -> MutexGuard<'_, CacheState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        let digest = openssl::sha::sha256(key.as_bytes());
+        let name = digest
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.json", name)))
+    }
+
+    // NOTE: A cache file that cannot be read is treated as absent, the
+    // response is fetched again and the file rewritten.
+    fn load(&self, key: &str) -> Option<Vec<CacheEntry>> {
+        let path = self.path(key)?;
+        let data = std::fs::read(&path).ok()?;
+        serde_json::from_slice(&data)
+            .map_err(|e| {
+                tracing::warn!(error = %e, path = %path.display(), "Ignoring unreadable cache file")
+            })
+            .ok()
+    }
+
+    fn save(&self, key: &str, entries: &[CacheEntry]) {
+        let Some(path) = self.path(key) else {
+            return;
+        };
+        let written = serde_json::to_vec(entries)
+            .map_err(|e| e.to_string())
+            .and_then(|data| std::fs::write(&path, data).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            tracing::warn!(error = %e, path = %path.display(), "Failed to write cache file");
+        }
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        self.state().forget(key);
+        match self.path(key) {
+            Some(path) if path.exists() => remove_file(&path),
+            _ => Ok(()),
+        }
+    }
+
+    // NOTE: Requests that set their own validators or a `Range` expect the
+    // server's answer to those, not a stored full response.
+    fn bypasses(&self, request: &Request) -> bool {
+        request.method != Method::GET
+            || request.range.is_some()
+            || (!self.authenticated && is_authenticated(request))
+            || CacheControl::parse(request_header(request, "cache-control").as_deref()).no_store
+            || ["if-none-match", "if-modified-since", "range"]
+                .iter()
+                .any(|name| request_header(request, name).is_some())
+    }
+
+    fn lookup(&self, request: &Request) -> Lookup {
+        let mut state = self.state();
+
+        let url = match query::wire_url(request) {
+            Ok(url) if !self.bypasses(request) => url,
+            _ => {
+                state.stats.bypassed += 1;
+                return Lookup::Bypass;
+            }
+        };
+
+        let key = cache_key(&request.method, &url);
+        if !state.entries.contains_key(&key) {
+            if let Some(entries) = self.load(&key) {
+                state.insert(key.clone(), entries, self.capacity);
+            }
+        }
+        if state.entries.contains_key(&key) {
+            state.touch(&key);
+        }
+        let Some(entry) = state
+            .entries
+            .get(&key)
+            .and_then(|entries| entries.iter().find(|entry| entry.matches(request)))
+            .cloned()
+        else {
+            state.stats.misses += 1;
+            return Lookup::Miss(key);
+        };
+
+        let no_cache =
+            CacheControl::parse(request_header(request, "cache-control").as_deref()).no_cache;
+        if !no_cache && entry.is_fresh(self.clock.now()) {
+            state.stats.hits += 1;
+            return Lookup::Fresh(entry.response);
+        }
+        if entry.has_validators() {
+            return Lookup::Stale(key, entry);
+        }
+
+        state.stats.misses += 1;
+        Lookup::Miss(key)
+    }
+
+    // NOTE: Replaces the variant `request` selects, or only drops it when
+    // `entry` is `None`.
+    fn replace(&self, key: &str, request: &Request, entry: Option<CacheEntry>) {
+        let mut state = self.state();
+        let mut entries = state.entries.remove(key).unwrap_or_default();
+        entries.retain(|existing| !existing.matches(request));
+        entries.extend(entry);
+        if entries.is_empty() {
+            state.forget(key);
+        } else {
+            state.insert(key.to_string(), entries.clone(), self.capacity);
+        }
+        drop(state);
+
+        if !entries.is_empty() {
+            self.save(key, &entries);
+        } else if let Err(e) = self.remove(key) {
+            tracing::warn!(error = %e, key = %key, "Failed to remove cache entry");
+        }
+    }
+
+    fn store(&self, key: &str, request: &Request, response: &Response) {
+        let entry = response.is_storable().then(|| {
+            tracing::debug!(key = %key, "Storing response in cache");
+            CacheEntry::new(request, response.clone(), self.clock.now())
+        });
+        self.replace(key, request, entry);
+    }
+
+    // NOTE: Refreshes the stored response with the headers of a
+    // `304 Not Modified` and returns it in place of the 304.
+    fn revalidated(
+        &self,
+        key: &str,
+        request: &Request,
+        mut entry: CacheEntry,
+        not_modified: Response,
+    ) -> Response {
+        for (name, value) in &not_modified.headers {
+            if KEEP_ON_REVALIDATE.contains(&name.to_ascii_lowercase().as_str()) {
+                continue;
+            }
+            entry
+                .response
+                .headers
+                .retain(|existing, _| !existing.eq_ignore_ascii_case(name));
+            entry.response.headers.insert(name.clone(), value.clone());
+        }
+        entry.stored_at = self.clock.now();
+
+        let mut response = entry.response.clone();
+        response.id = not_modified.id;
+        response.meta.timing = not_modified.meta.timing;
+        response.meta.size = not_modified.meta.size;
+        response.meta.trace = not_modified.meta.trace;
+        response.metrics = not_modified.metrics;
+        response.early_hints = not_modified.early_hints;
+
+        self.state().stats.revalidated += 1;
+        self.replace(key, request, Some(entry));
+        response
+    }
+
+    // NOTE: A successful unsafe request invalidates what is stored for its
+    // URL, as RFC 9111 asks, since it likely changed the resource.
+    fn invalidate(&self, request: &Request, response: &Response) {
+        let unsafe_method = !matches!(
+            request.method,
+            Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+        );
+        if !unsafe_method || !(response.status.is_success() || response.status.is_redirection()) {
+            return;
+        }
+        let Ok(url) = query::wire_url(request) else {
+            return;
+        };
+        if let Err(e) = self.remove(&cache_key(&Method::GET, &url)) {
+            tracing::warn!(error = %e, url = %url, "Failed to invalidate cache entry");
+        }
+    }
+
+    #[tracing::instrument(skip(self, transport, request), fields(request_id = request.id), level = "debug")]
+    pub(crate) async fn send(
+        &self,
+        transport: &dyn Transport,
+        request: Request,
+    ) -> Result<Response> {
+        match self.lookup(&request) {
+            Lookup::Bypass => {
+                tracing::debug!("Request bypasses the cache");
+                let mut response = transport.send(request.clone()).await?;
+                self.invalidate(&request, &response);
+                response.meta.cache = Some(CacheStatus::Bypass);
+                Ok(response)
+            }
+            Lookup::Fresh(mut response) => {
+                tracing::debug!("Serving fresh response from cache");
+                response.id = request.id;
+                response.meta.cache = Some(CacheStatus::Hit);
+                response.meta.timing.phases = None;
+                response.metrics = None;
+                Ok(response)
+            }
+            Lookup::Stale(key, entry) => {
+                tracing::debug!("Revalidating stale cached response");
+                let conditional = request.clone().with_conditions_from(&entry.response);
+                let mut response = transport.send(conditional).await?;
+                if response.status == StatusCode::NOT_MODIFIED {
+                    let mut response = self.revalidated(&key, &request, entry, response);
+                    response.meta.cache = Some(CacheStatus::Revalidated);
+                    return Ok(response);
+                }
+
+                self.state().stats.misses += 1;
+                self.store(&key, &request, &response);
+                response.meta.cache = Some(CacheStatus::Miss);
+                Ok(response)
+            }
+            Lookup::Miss(key) => {
+                let mut response = transport.send(request.clone()).await?;
+                self.store(&key, &request, &response);
+                response.meta.cache = Some(CacheStatus::Miss);
+                Ok(response)
+            }
+        }
+    }
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    std::fs::remove_file(path).map_err(|e| {
+        tracing::error!(error = %e, path = %path.display(), "Failed to remove cache file");
+        RelayError::Io {
+            message: format!("Failed to remove cache file {}", path.display()),
+            cause: Some(e.into()),
+        }
+    })
+}
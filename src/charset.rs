@@ -0,0 +1,55 @@
+use encoding_rs::Encoding;
+
+use crate::{
+    error::{RelayError, Result},
+    interop::{Response, ResponseBody},
+};
+
+// NOTE: Labels follow the WHATWG Encoding Standard, which is how browsers
+// read them, so `iso-8859-1` and `us-ascii` decode as windows-1252 and
+// `shift_jis`, `euc-kr` or `gb2312` work as they would in a page. A byte
+// order mark wins over the label, malformed text is an error rather than
+// replacement characters.
+fn decode(bytes: &[u8], label: &str) -> Result<String> {
+    let encoding =
+        Encoding::for_label(label.trim().trim_matches('"').as_bytes()).ok_or_else(|| {
+            RelayError::UnsupportedFeature {
+                feature: format!("{} charset", label),
+                message: format!("Cannot decode text as {}", label),
+                relay: "curl".into(),
+            }
+        })?;
+    let (encoding, bytes) = match Encoding::for_bom(bytes) {
+        Some((encoding, bom)) => (encoding, &bytes[bom..]),
+        None => (encoding, bytes),
+    };
+    tracing::trace!(label = %label, encoding = encoding.name(), "Decoding text");
+    encoding
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .map(|text| text.into_owned())
+        .ok_or_else(|| RelayError::Parse {
+            message: format!("Response body is not valid {}", encoding.name()),
+            cause: None,
+            partial: None,
+        })
+}
+
+impl ResponseBody {
+    // NOTE: Decodes with `charset`, the one the server declared or the
+    // request's `forceCharset`, falling back to UTF-8.
+    pub fn text(&self) -> Result<String> {
+        self.text_with_charset(self.charset.as_deref().unwrap_or("utf-8"))
+    }
+
+    // NOTE: Ignores whatever charset the response declared, for servers that
+    // declare the wrong one.
+    pub fn text_with_charset(&self, charset: &str) -> Result<String> {
+        decode(&self.body, charset)
+    }
+}
+
+impl Response {
+    pub fn text(&self) -> Result<String> {
+        self.body.text()
+    }
+}
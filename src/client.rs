@@ -0,0 +1,423 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    cache::HttpCache,
+    config::ClientConfig,
+    error::{Cause, RelayError, Result},
+    events,
+    interceptor::{Interceptor, InterceptorChain},
+    interop::{CertificateType, ErrorPayload, Request, Response, TransferEvent, TransferEventKind},
+    pool::{ConnectionLimit, LimitedTransport},
+    retry::{AttemptLog, RetryPolicy},
+    security::HostCertificates,
+    share::{self, Share},
+    signing::{self, RequestSigner},
+    target_policy::TargetPolicy,
+    transport::{CurlTransport, SessionTransport, Transport},
+    ws::{self, WsConnection},
+};
+
+#[derive(Clone)]
+pub struct RelayClient {
+    interceptors: InterceptorChain,
+    transport: Arc<dyn Transport>,
+    retry: Option<RetryPolicy>,
+    certificates: HostCertificates,
+    config: ClientConfig,
+    share: Option<Arc<Share>>,
+    custom_transport: bool,
+    cache: Option<HttpCache>,
+    signer: Option<Arc<dyn RequestSigner>>,
+    connections: Option<Arc<ConnectionLimit>>,
+    target_policy: Option<Arc<TargetPolicy>>,
+}
+
+// NOTE: Without a share every transfer still works, only the client wide
+// reuse of lookups, TLS sessions and cookies is lost.
+fn session(tls_sessions: bool) -> (Option<Arc<Share>>, Arc<dyn Transport>) {
+    let share = Share::session(tls_sessions)
+        .map(Arc::new)
+        .map_err(|e| tracing::warn!(error = %e, "Client transfers will not share state"))
+        .ok();
+    let transport: Arc<dyn Transport> = match share {
+        Some(ref share) => Arc::new(SessionTransport::new(Arc::clone(share))),
+        None => Arc::new(CurlTransport),
+    };
+    (share, transport)
+}
+
+impl Default for RelayClient {
+    fn default() -> Self {
+        let (share, transport) = session(true);
+
+        Self {
+            interceptors: InterceptorChain::default(),
+            transport,
+            retry: None,
+            certificates: HostCertificates::default(),
+            config: ClientConfig::default(),
+            share,
+            custom_transport: false,
+            cache: None,
+            signer: None,
+            connections: None,
+            target_policy: None,
+        }
+    }
+}
+
+impl RelayClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // NOTE: A custom transport takes over connection handling, transfers no
+    // longer go through the client's share.
+    pub fn with_transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Arc::new(transport);
+        self.custom_transport = true;
+        self
+    }
+
+    // NOTE: Whether TLS sessions from one transfer are resumed by the next
+    // to the same host on any of the client's handles, on by default. Off,
+    // each handle only resumes the sessions it made itself, for testing how
+    // a server handles resumption. Starts the client over with an empty
+    // DNS cache and cookie jar, so call it while building the client.
+    pub fn with_tls_session_sharing(mut self, enable: bool) -> Self {
+        let (share, transport) = session(enable);
+        self.share = share;
+        if !self.custom_transport {
+            self.transport = transport;
+        }
+        self
+    }
+
+    pub fn with_interceptor<I: Interceptor + 'static>(mut self, interceptor: I) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    // NOTE: Looked up after interceptors and defaults are applied, so the
+    // cache sees the request as sent. Keep a clone of `cache` to read its
+    // statistics or purge it.
+    pub fn with_cache(mut self, cache: HttpCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn cache(&self) -> Option<&HttpCache> {
+        self.cache.as_ref()
+    }
+
+    // NOTE: Signs every request the client sends over curl, after
+    // interceptors, client defaults and the transfer's own headers are
+    // applied. A custom transport sends requests unsigned.
+    pub fn with_signer<S: RequestSigner + 'static>(mut self, signer: S) -> Self {
+        self.signer = Some(Arc::new(signer));
+        self
+    }
+
+    // NOTE: At most `max` transfers are in flight across all hosts, each
+    // holding one connection, and the rest wait their turn in order. Clones
+    // of the client share the limit. Cache hits and WebSockets are not
+    // counted.
+    pub fn with_max_total_connections(mut self, max: usize) -> Self {
+        self.connections = Some(Arc::new(ConnectionLimit::new(max)));
+        self
+    }
+
+    // NOTE: Refuses requests, redirects and connections the policy does not
+    // allow with `RelayError::PolicyViolation`. A custom transport only gets
+    // the URL checked.
+    pub fn with_target_policy(mut self, policy: TargetPolicy) -> Self {
+        self.target_policy = Some(Arc::new(policy));
+        self
+    }
+
+    pub fn with_client_certificate(mut self, host: &str, certificate: CertificateType) -> Self {
+        self.certificates.insert(host, certificate);
+        self
+    }
+
+    pub fn with_default_client_certificate(mut self, certificate: CertificateType) -> Self {
+        self.certificates.set_default(certificate);
+        self
+    }
+
+    // NOTE: Replaces every client wide default at once, the `with_*` setters
+    // below adjust individual fields of it.
+    pub fn with_config(mut self, config: ClientConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    pub fn with_buffer_size(mut self, size: usize) -> Self {
+        self.config.buffer_size = Some(size);
+        self
+    }
+
+    pub fn with_upload_buffer_size(mut self, size: usize) -> Self {
+        self.config.upload_buffer_size = Some(size);
+        self
+    }
+
+    // NOTE: How long curl waits on the preferred address family, usually
+    // IPv6, before racing the other one. libcurl defaults to 200ms.
+    pub fn with_happy_eyeballs_timeout(mut self, timeout: Duration) -> Self {
+        self.config.happy_eyeballs_timeout_ms =
+            Some(timeout.as_millis().min(u64::MAX as u128) as u64);
+        self
+    }
+
+    // NOTE: How long the client's transfers reuse a resolved name, rounded
+    // down to whole seconds. libcurl defaults to 60 seconds.
+    pub fn with_dns_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.config.dns_cache_ttl = Some(ttl.as_secs());
+        self
+    }
+
+    // NOTE: Sends the request id under `name`, e.g. `X-Request-Id`, so server
+    // side logs can be joined with relay's.
+    pub fn with_request_id_header(mut self, name: impl Into<String>) -> Self {
+        self.config.request_id_header = Some(name.into());
+        self
+    }
+
+    // NOTE: Sent unless a request sets or suppresses `User-Agent` itself,
+    // libcurl sends none by default.
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.config.user_agent = Some(user_agent.into());
+        self
+    }
+
+    // NOTE: Replaces the `Accept: */*` curl sends for requests that do not
+    // set or suppress `Accept` themselves.
+    pub fn with_accept(mut self, accept: impl Into<String>) -> Self {
+        self.config.headers.insert("Accept", accept);
+        self
+    }
+
+    // NOTE: Picks the proxy from `HTTPS_PROXY`, `HTTP_PROXY` or `ALL_PROXY`
+    // by request scheme, skipping hosts listed in `NO_PROXY`. A proxy set on
+    // the request itself always wins.
+    pub fn with_proxy_from_env(mut self, enable: bool) -> Self {
+        self.config.proxy_from_env = enable;
+        self
+    }
+
+    // NOTE: The request as it will reach the transport, with client
+    // certificates and defaults merged in, so UIs can show what is actually
+    // sent. Interceptors run later and are not reflected.
+    pub fn effective_request(&self, request: &Request) -> Request {
+        let mut request = request.clone();
+        self.certificates.apply(&mut request);
+        self.config.apply(&mut request);
+        request
+    }
+
+    // NOTE: Resolves `host` ahead of the first request to it, filling the DNS
+    // cache the client's transfers share. Entries expire after the client's
+    // `with_dns_cache_ttl`, 60 seconds by default. Blocks the calling thread
+    // for the lookup.
+    pub fn warm_dns(&self, host: &str, port: u16) -> Result<()> {
+        share::warm_dns(host, port, self.share.as_deref(), self.config.dns_cache_ttl)
+    }
+
+    // NOTE: Forgets every name the client resolved, e.g. after switching
+    // VPNs, the next request to each host looks it up again. TLS sessions
+    // and cookies are kept.
+    pub fn flush_dns(&self) {
+        if let Some(ref share) = self.share {
+            share.flush_dns();
+        }
+    }
+
+    #[tracing::instrument(skip(self, request), fields(request_id = request.id), level = "debug")]
+    pub async fn execute(&self, mut request: Request) -> Result<Response> {
+        // NOTE: Assigned once up front so every retry attempt, interceptor and
+        // the response share the same id.
+        let request_id = request.ensure_id();
+        tracing::Span::current().record("request_id", request_id);
+        // NOTE: Pinned before the first attempt for the same reason, a retry
+        // must reuse the key for the server to recognize it.
+        request.ensure_idempotency_key();
+
+        tracing::info!(
+            method = %request.method,
+            url = %request.url,
+            interceptor_count = self.interceptors.len(),
+            "Executing request through client"
+        );
+
+        #[cfg(feature = "metrics")]
+        let (method, url, started) = (
+            request.method.clone(),
+            request.url.clone(),
+            std::time::Instant::now(),
+        );
+
+        let result = self.execute_with_retries(&request, request_id).await;
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_request(&method, &url, &result, started.elapsed());
+
+        result
+    }
+
+    async fn execute_with_retries(&self, request: &Request, request_id: i64) -> Result<Response> {
+        let Some(policy) = self
+            .retry
+            .as_ref()
+            .filter(|policy| policy.is_retryable_request(request))
+        else {
+            let mut log = AttemptLog::default();
+            let result = self.execute_attempt(request).await;
+            log.attempted(&result);
+            return log.finish(result);
+        };
+
+        let mut log = AttemptLog::default();
+        let mut attempt = 0;
+        let mut delay = Duration::ZERO;
+        loop {
+            let result = self.execute_attempt(request).await;
+            log.attempted(&result);
+
+            let should_retry = match result {
+                Ok(ref response) => policy.should_retry_response(response),
+                Err(ref error) => policy.should_retry_error(error),
+            };
+
+            if !should_retry || attempt >= policy.max_retries {
+                return log.finish(result);
+            }
+
+            delay = policy.backoff.next_delay_after(attempt, delay);
+            tracing::warn!(
+                attempt = attempt + 1,
+                max_retries = policy.max_retries,
+                delay_ms = delay.as_millis() as u64,
+                "Retrying request"
+            );
+            #[cfg(feature = "metrics")]
+            crate::metrics::record_retry(&request.method);
+            if let Some(events) = events::emitter(request_id) {
+                events.emit(TransferEventKind::Retry {
+                    attempt: attempt + 1,
+                    delay_ms: delay.as_millis() as u64,
+                });
+            }
+
+            tokio::time::sleep(delay).await;
+            log.waited(delay);
+            attempt += 1;
+        }
+    }
+
+    // NOTE: Like `execute`, with errors as an `ErrorPayload` carrying the
+    // request's id.
+    pub async fn execute_interop(
+        &self,
+        mut request: Request,
+    ) -> std::result::Result<Response, ErrorPayload> {
+        let request_id = request.ensure_id();
+        self.execute(request)
+            .await
+            .map_err(|e| ErrorPayload::new(request_id, e))
+    }
+
+    // NOTE: Like `execute`, with the request's `TransferEvent`s going to
+    // `listener` across every retry attempt, ending with `Done` or `Failed`.
+    pub async fn execute_with_events<F>(
+        &self,
+        mut request: Request,
+        listener: F,
+    ) -> Result<Response>
+    where
+        F: Fn(&TransferEvent) + Send + Sync + 'static,
+    {
+        let registration = events::listen(request.ensure_id(), Arc::new(listener));
+        let result = self.execute(request).await;
+        registration.emitter().finish(&result);
+        result
+    }
+
+    // NOTE: Opens a WebSocket with the request's URL (`ws`, `wss`, `http` or
+    // `https`), going through the same interceptors and client defaults as
+    // `execute`. Retries do not apply to the upgrade.
+    #[tracing::instrument(skip(self, request), fields(request_id = request.id), level = "debug")]
+    pub async fn websocket(&self, mut request: Request) -> Result<WsConnection> {
+        request.ensure_id();
+        self.interceptors.before_send(&mut request)?;
+        self.certificates.apply(&mut request);
+        self.config.apply(&mut request);
+
+        self.enforce_target_policy(&mut request)?;
+        // NOTE: The upgrade is a blocking perform, it must not hold up the
+        // runtime's thread.
+        let share = self.share.clone();
+        tokio::task::spawn_blocking(move || ws::connect_shared(&request, share))
+            .await
+            .unwrap_or_else(|e| match e.try_into_panic() {
+                Ok(panic) => std::panic::resume_unwind(panic),
+                Err(e) => Err(RelayError::Network {
+                    message: "WebSocket upgrade was cancelled".into(),
+                    cause: Some(Cause::new(e)),
+                }),
+            })
+    }
+
+    // NOTE: Checked after interceptors and defaults, which may change the
+    // URL or add a proxy.
+    fn enforce_target_policy(&self, request: &mut Request) -> Result<()> {
+        request.target_policy = self.target_policy.clone();
+        if let Some(ref policy) = request.target_policy {
+            policy.check_url(&request.url)?;
+        }
+        Ok(())
+    }
+
+    async fn execute_attempt(&self, request: &Request) -> Result<Response> {
+        self.send(request, self.cache.as_ref()).await
+    }
+
+    // NOTE: One attempt through the interceptors and client defaults, going
+    // through `cache` when given.
+    pub(crate) async fn send(
+        &self,
+        request: &Request,
+        cache: Option<&HttpCache>,
+    ) -> Result<Response> {
+        let mut request = request.clone();
+        self.interceptors.before_send(&mut request)?;
+        self.certificates.apply(&mut request);
+        self.config.apply(&mut request);
+
+        self.enforce_target_policy(&mut request)?;
+        let _signing = self
+            .signer
+            .as_ref()
+            .map(|signer| signing::register(request.id, Arc::clone(signer)));
+        let transport = LimitedTransport {
+            transport: self.transport.as_ref(),
+            limit: self.connections.as_deref(),
+        };
+        let mut response = match cache {
+            Some(cache) => cache.send(&transport, request.clone()).await?,
+            None => transport.send(request.clone()).await?,
+        };
+        self.interceptors.after_receive(&request, &mut response)?;
+
+        Ok(response)
+    }
+}
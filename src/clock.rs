@@ -0,0 +1,182 @@
+use std::{
+    fmt,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::interop::TimingInfo;
+
+/// Time source for response timing.
+///
+/// The wall clock only stamps when a request started, durations are measured
+/// on the monotonic clock so a wall clock adjustment mid-request cannot make
+/// `end` come before `start`.
+pub trait Clock: Send + Sync + fmt::Debug {
+    fn now(&self) -> SystemTime;
+    fn monotonic(&self) -> Instant;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn monotonic(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl TimingInfo {
+    // NOTE: `start` and `end` are wall clock stamps for display, the duration
+    // is clamped to zero should they ever be out of order.
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.end.saturating_sub(self.start))
+    }
+}
+
+pub(crate) struct Stopwatch<'a> {
+    clock: &'a dyn Clock,
+    started: SystemTime,
+    started_at: Instant,
+}
+
+impl<'a> Stopwatch<'a> {
+    pub(crate) fn start(clock: &'a dyn Clock) -> Self {
+        Self {
+            clock,
+            started: clock.now(),
+            started_at: clock.monotonic(),
+        }
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.clock
+            .monotonic()
+            .saturating_duration_since(self.started_at)
+    }
+
+    pub(crate) fn timing(&self) -> TimingInfo {
+        // NOTE: A wall clock set before the epoch is the only way this can
+        // fail, timing then starts at zero rather than failing the response.
+        let start = self
+            .started
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|since| since.as_millis() as u64)
+            .unwrap_or_else(|e| {
+                tracing::warn!(error = %e, "Wall clock is before the epoch");
+                0
+            });
+        let elapsed = self.elapsed().as_millis() as u64;
+
+        tracing::trace!(
+            start_ms = start,
+            duration_ms = elapsed,
+            "Calculated request timing"
+        );
+
+        TimingInfo {
+            start,
+            end: start.saturating_add(elapsed),
+            dns_cache_hit: None,
+            phases: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    // NOTE: Wall and monotonic time move separately, so a test can set the
+    // wall clock back mid-request.
+    #[derive(Debug)]
+    struct TestClock {
+        now: Mutex<SystemTime>,
+        origin: Instant,
+        elapsed: Mutex<Duration>,
+    }
+
+    impl TestClock {
+        fn at(ms: u64) -> Self {
+            Self {
+                now: Mutex::new(SystemTime::UNIX_EPOCH + Duration::from_millis(ms)),
+                origin: Instant::now(),
+                elapsed: Mutex::new(Duration::ZERO),
+            }
+        }
+
+        fn advance(&self, duration: Duration) {
+            *self.now.lock().unwrap() += duration;
+            *self.elapsed.lock().unwrap() += duration;
+        }
+
+        fn set_back(&self, duration: Duration) {
+            *self.now.lock().unwrap() -= duration;
+        }
+    }
+
+    impl Clock for TestClock {
+        fn now(&self) -> SystemTime {
+            *self.now.lock().unwrap()
+        }
+
+        fn monotonic(&self) -> Instant {
+            self.origin + *self.elapsed.lock().unwrap()
+        }
+    }
+
+    #[test]
+    fn times_from_the_start_stamp() {
+        let clock = TestClock::at(1_000);
+        let stopwatch = Stopwatch::start(&clock);
+        clock.advance(Duration::from_millis(250));
+
+        let timing = stopwatch.timing();
+        assert_eq!((timing.start, timing.end), (1_000, 1_250));
+        assert_eq!(timing.duration(), Duration::from_millis(250));
+        assert_eq!(stopwatch.elapsed(), Duration::from_millis(250));
+    }
+
+    #[test]
+    fn ignores_wall_clock_changes_mid_request() {
+        let clock = TestClock::at(10_000);
+        let stopwatch = Stopwatch::start(&clock);
+        clock.advance(Duration::from_millis(40));
+        clock.set_back(Duration::from_secs(5));
+
+        let timing = stopwatch.timing();
+        assert_eq!((timing.start, timing.end), (10_000, 10_040));
+    }
+
+    #[test]
+    fn starts_at_zero_before_the_epoch() {
+        let clock = TestClock::at(0);
+        clock.set_back(Duration::from_secs(1));
+        let stopwatch = Stopwatch::start(&clock);
+        clock.advance(Duration::from_millis(5));
+
+        let timing = stopwatch.timing();
+        assert_eq!((timing.start, timing.end), (0, 5));
+    }
+
+    #[test]
+    fn clamps_duration_when_end_is_before_start() {
+        let timing = TimingInfo {
+            start: 2_000,
+            end: 1_500,
+            dns_cache_hit: None,
+            phases: None,
+        };
+        assert_eq!(timing.duration(), Duration::ZERO);
+
+        let timing = TimingInfo {
+            end: 2_300,
+            ..timing
+        };
+        assert_eq!(timing.duration(), Duration::from_millis(300));
+    }
+}
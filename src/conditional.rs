@@ -0,0 +1,118 @@
+use http::StatusCode;
+use time::{OffsetDateTime, UtcOffset};
+
+use crate::interop::{Request, Response};
+
+// NOTE: The IMF-fixdate form of an HTTP-date, always in GMT, e.g.
+// `Sun, 06 Nov 1994 08:49:37 GMT`.
+// See: https://datatracker.ietf.org/doc/html/rfc7231#section-7.1.1.1
+pub(crate) fn http_date(date: OffsetDateTime) -> String {
+    let date = date.to_offset(UtcOffset::UTC);
+    let weekday = date.weekday().to_string();
+    let month = date.month().to_string();
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        &weekday[..3],
+        date.day(),
+        &month[..3],
+        date.year(),
+        date.hour(),
+        date.minute(),
+        date.second()
+    )
+}
+
+// NOTE: Entity tags go out quoted, a bare `abc` is sent as `"abc"` while
+// `W/"abc"` and the `*` wildcard are kept as given.
+fn entity_tag(etag: &str) -> String {
+    let etag = etag.trim();
+    if etag == "*"
+        || etag.starts_with("W/")
+        || (etag.len() >= 2 && etag.starts_with('"') && etag.ends_with('"'))
+    {
+        etag.to_string()
+    } else {
+        format!("\"{}\"", etag.trim_matches('"'))
+    }
+}
+
+impl Response {
+    // NOTE: Returned exactly as sent, weak validators keep their `W/` prefix.
+    pub fn etag(&self) -> Option<&str> {
+        self.header_str("etag")
+            .map(str::trim)
+            .filter(|etag| !etag.is_empty())
+    }
+
+    pub fn last_modified(&self) -> Option<&str> {
+        self.header_str("last-modified")
+            .map(str::trim)
+            .filter(|date| !date.is_empty())
+    }
+
+    pub fn is_not_modified(&self) -> bool {
+        self.status == StatusCode::NOT_MODIFIED
+    }
+}
+
+impl Request {
+    fn set_condition(&mut self, name: &str, value: Option<&str>) {
+        let Some(value) = value else {
+            return;
+        };
+        tracing::debug!(header = name, value = %value, "Setting conditional header");
+        self.headers
+            .get_or_insert_with(Default::default)
+            .insert(name, value);
+    }
+
+    // NOTE: For revalidating a cached response, the server answers
+    // `304 Not Modified` when nothing changed since `response`. Validators
+    // missing from the response leave the matching header untouched.
+    pub fn with_conditions_from(mut self, response: &Response) -> Self {
+        self.set_condition("If-None-Match", response.etag());
+        self.set_condition("If-Modified-Since", response.last_modified());
+        self
+    }
+
+    // NOTE: For optimistic-concurrency writes, the server answers
+    // `412 Precondition Failed` when the resource changed since `response`.
+    // `If-Match` uses strong comparison so a weak ETag would never match, it
+    // is left out and only `If-Unmodified-Since` guards the write.
+    pub fn with_preconditions_from(mut self, response: &Response) -> Self {
+        let etag = response.etag();
+        if etag.is_some_and(|etag| etag.starts_with("W/")) {
+            tracing::warn!(etag = ?etag, "Skipping weak ETag, If-Match can never match it");
+        } else {
+            self.set_condition("If-Match", etag);
+        }
+        self.set_condition("If-Unmodified-Since", response.last_modified());
+        self
+    }
+
+    // NOTE: The write only goes through while the resource still has `etag`,
+    // otherwise the server answers `412 Precondition Failed`.
+    pub fn if_match(mut self, etag: &str) -> Self {
+        if etag.trim().starts_with("W/") {
+            tracing::warn!(etag = %etag, "Weak ETag in If-Match can never match");
+        }
+        self.set_condition("If-Match", Some(&entity_tag(etag)));
+        self
+    }
+
+    // NOTE: `*` makes a create fail when the resource already exists.
+    pub fn if_none_match(mut self, etag: &str) -> Self {
+        self.set_condition("If-None-Match", Some(&entity_tag(etag)));
+        self
+    }
+
+    pub fn if_modified_since(mut self, date: OffsetDateTime) -> Self {
+        self.set_condition("If-Modified-Since", Some(&http_date(date)));
+        self
+    }
+
+    pub fn if_unmodified_since(mut self, date: OffsetDateTime) -> Self {
+        self.set_condition("If-Unmodified-Since", Some(&http_date(date)));
+        self
+    }
+}
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    header::Headers,
+    interop::{CertificateConfig, ProxyConfig, Request, SecurityConfig},
+    proxy,
+};
+
+// NOTE: Client wide defaults merged into every request. Anything the request
+// sets itself wins, so a default is cleared by setting the request value to
+// its neutral form: a suppressed header, a proxy with an empty `url`, a
+// `timeout` of `0`, `followRedirects: false` or `verifyPeer: true`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(default, rename_all = "camelCase")]
+pub struct ClientConfig {
+    pub headers: Headers,
+    // NOTE: Takes precedence over a `User-Agent` in `headers`.
+    pub user_agent: Option<String>,
+    pub security: Option<SecurityConfig>,
+    pub proxy: Option<ProxyConfig>,
+    pub proxy_from_env: bool,
+    pub timeout: Option<u64>,
+    pub first_byte_timeout: Option<u64>,
+    pub follow_redirects: Option<bool>,
+    pub max_redirects: Option<u32>,
+    pub buffer_size: Option<usize>,
+    pub upload_buffer_size: Option<usize>,
+    pub request_id_header: Option<String>,
+    pub happy_eyeballs_timeout_ms: Option<u64>,
+    pub dns_cache_ttl: Option<u64>,
+}
+
+impl ClientConfig {
+    fn default_headers(&self) -> Headers {
+        let mut headers = self.headers.clone();
+        if let Some(ref user_agent) = self.user_agent {
+            headers.insert("User-Agent", user_agent.clone());
+        }
+        headers
+    }
+
+    fn has_options(&self) -> bool {
+        self.timeout.is_some()
+            || self.first_byte_timeout.is_some()
+            || self.follow_redirects.is_some()
+            || self.max_redirects.is_some()
+            || self.buffer_size.is_some()
+            || self.upload_buffer_size.is_some()
+            || self.request_id_header.is_some()
+            || self.happy_eyeballs_timeout_ms.is_some()
+            || self.dns_cache_ttl.is_some()
+    }
+
+    #[tracing::instrument(skip(self, request), fields(request_id = request.id), level = "debug")]
+    pub(crate) fn apply(&self, request: &mut Request) {
+        self.apply_headers(request);
+        self.apply_security(request);
+
+        if request.proxy.is_none() {
+            request.proxy = self.proxy.clone();
+        }
+        if self.proxy_from_env && request.proxy.is_none() {
+            request.proxy = proxy::from_env(&request.url);
+        }
+
+        if !self.has_options() {
+            return;
+        }
+
+        let options = request
+            .meta
+            .get_or_insert_with(Default::default)
+            .options
+            .get_or_insert_with(Default::default);
+
+        options.timeout = options.timeout.or(self.timeout);
+        options.first_byte_timeout = options.first_byte_timeout.or(self.first_byte_timeout);
+        options.follow_redirects = options.follow_redirects.or(self.follow_redirects);
+        options.max_redirects = options.max_redirects.or(self.max_redirects);
+        options.buffer_size = options.buffer_size.or(self.buffer_size);
+        options.upload_buffer_size = options.upload_buffer_size.or(self.upload_buffer_size);
+        options.happy_eyeballs_timeout_ms = options
+            .happy_eyeballs_timeout_ms
+            .or(self.happy_eyeballs_timeout_ms);
+        options.dns_cache_ttl = options.dns_cache_ttl.or(self.dns_cache_ttl);
+        if options.request_id_header.is_none() {
+            options.request_id_header = self.request_id_header.clone();
+        }
+    }
+
+    // NOTE: Merged by name, a header the request sets or suppresses keeps
+    // every default of that name off the request.
+    fn apply_headers(&self, request: &mut Request) {
+        let defaults = self.default_headers();
+        if defaults.is_empty() {
+            return;
+        }
+
+        let headers = request.headers.get_or_insert_with(Default::default);
+        let explicit = headers.clone();
+        for (name, value) in &defaults {
+            let negotiated = request.accept.is_some() && name.eq_ignore_ascii_case("accept");
+            if !explicit.contains_key(name) && !negotiated {
+                tracing::trace!(header = %name, "Applying default header");
+                headers.append(name.clone(), value.clone());
+            }
+        }
+    }
+
+    // NOTE: Merged field by field so a request that only pins a CA still
+    // picks up the default client certificate and verification settings.
+    fn apply_security(&self, request: &mut Request) {
+        let Some(ref defaults) = self.security else {
+            return;
+        };
+
+        let Some(ref mut security) = request.security else {
+            request.security = Some(defaults.clone());
+            return;
+        };
+
+        security.verify_host = security.verify_host.or(defaults.verify_host);
+        security.verify_peer = security.verify_peer.or(defaults.verify_peer);
+        security.min_tls_version = security.min_tls_version.or(defaults.min_tls_version);
+
+        let Some(ref default_certificates) = defaults.certificates else {
+            return;
+        };
+        let certificates = security
+            .certificates
+            .get_or_insert_with(|| CertificateConfig {
+                client: None,
+                ca: None,
+            });
+        if certificates.client.is_none() {
+            certificates.client = default_certificates.client.clone();
+        }
+        if certificates.ca.is_none() {
+            certificates.ca = default_certificates.ca.clone();
+        }
+    }
+}
@@ -3,8 +3,12 @@ use http::HeaderName;
 use std::{collections::HashMap, path::Path};
 
 use crate::{
+    csv,
     error::{RelayError, Result},
-    interop::{ContentType, FormValue, MediaType},
+    interop::{
+        BodyEstimate, ContentType, FormData, FormValue, MediaType, PartProgress, RelatedPart,
+    },
+    multipart,
 };
 
 pub(crate) struct ContentHandler<'a> {
@@ -57,13 +61,31 @@ impl<'a> ContentHandler<'a> {
                 tracing::info!(content_length = content.len(), "Setting text content");
                 self.set_text_content(content, media_type)
             }
+            ContentType::Json {
+                raw: Some(raw),
+                media_type,
+                ..
+            } => {
+                tracing::info!(content_length = raw.len(), "Setting raw JSON content");
+                self.set_text_content(raw, media_type)
+            }
             ContentType::Json {
                 content,
+                raw: None,
                 media_type,
             } => {
                 tracing::info!("Setting JSON content");
                 self.set_json_content(content, media_type)
             }
+            ContentType::JsonPatch { operations } => {
+                tracing::info!("Setting JSON Patch content");
+                validate_json_patch(operations)?;
+                self.set_patch_content(operations, MediaType::JsonPatch)
+            }
+            ContentType::JsonMergePatch { content } => {
+                tracing::info!("Setting JSON merge patch content");
+                self.set_patch_content(content, MediaType::JsonMergePatch)
+            }
             ContentType::Form {
                 content,
                 media_type,
@@ -97,6 +119,15 @@ impl<'a> ContentHandler<'a> {
                 tracing::info!("Setting XML content");
                 self.set_text_content(content, media_type)
             }
+            ContentType::Csv {
+                content,
+                has_header,
+                ..
+            } => {
+                tracing::info!(content_length = content.len(), "Setting CSV content");
+                self.merge_headers(csv_headers(*has_header));
+                self.set_text_content(content, &MediaType::TextCsv)
+            }
             ContentType::Urlencoded {
                 content,
                 media_type,
@@ -104,9 +135,46 @@ impl<'a> ContentHandler<'a> {
                 tracing::info!(field_count = content.len(), "Setting URL-encoded content");
                 self.set_urlencoded_content(content, media_type)
             }
+            ContentType::MultipartRelated {
+                root,
+                parts,
+                type_param,
+            } => {
+                tracing::info!(
+                    part_count = parts.len() + 1,
+                    "Setting multipart/related content"
+                );
+                self.set_related_content(root, parts, type_param)
+            }
         }
     }
 
+    // NOTE: Built here rather than through curl's MIME API, which has no way
+    // to set the `type` and `start` parameters on the top-level type.
+    fn set_related_content(
+        &mut self,
+        root: &RelatedPart,
+        parts: &[RelatedPart],
+        type_param: &str,
+    ) -> Result<()> {
+        let (content_type, body) = multipart::related(root, parts, type_param);
+        self.merge_headers(HashMap::from([("Content-Type".to_string(), content_type)]));
+
+        self.handle.post_fields_copy(&body).map_err(|e| {
+            tracing::error!(error = %e, "Failed to set multipart/related content");
+            RelayError::Network {
+                message: "Failed to set multipart/related content".into(),
+                cause: Some(e.into()),
+            }
+        })?;
+
+        tracing::debug!(
+            body_size = body.len(),
+            "Multipart/related content set successfully"
+        );
+        Ok(())
+    }
+
     fn set_text_content(&mut self, content: &str, media_type: &MediaType) -> Result<()> {
         /* TODO: Look into reintroducing this when auth handling is done by kernel */
         // let mut headers = HashMap::new();
@@ -119,7 +187,7 @@ impl<'a> ContentHandler<'a> {
                 tracing::error!(error = %e, "Failed to set text content");
                 RelayError::Network {
                     message: "Failed to set text content".into(),
-                    cause: Some(e.to_string()),
+                    cause: Some(e.into()),
                 }
             })?;
 
@@ -136,7 +204,8 @@ impl<'a> ContentHandler<'a> {
             tracing::error!(error = %e, "Failed to serialize JSON");
             RelayError::Parse {
                 message: "Failed to serialize JSON".into(),
-                cause: Some(e.to_string()),
+                cause: Some(e.into()),
+                partial: None,
             }
         })?;
 
@@ -151,7 +220,7 @@ impl<'a> ContentHandler<'a> {
                 tracing::error!(error = %e, "Failed to set JSON content");
                 RelayError::Network {
                     message: "Failed to set JSON content".into(),
-                    cause: Some(e.to_string()),
+                    cause: Some(e.into()),
                 }
             })?;
 
@@ -159,6 +228,17 @@ impl<'a> ContentHandler<'a> {
         Ok(())
     }
 
+    // NOTE: Unlike plain JSON, servers reject patch documents without the exact
+    // media type, so `Content-Type` is always set unless given explicitly.
+    fn set_patch_content(
+        &mut self,
+        content: &serde_json::Value,
+        media_type: MediaType,
+    ) -> Result<()> {
+        self.merge_headers(patch_headers(media_type));
+        self.set_json_content(content, &media_type)
+    }
+
     fn set_binary_content(
         &mut self,
         content: &[u8],
@@ -190,7 +270,7 @@ impl<'a> ContentHandler<'a> {
             tracing::error!(error = %e, "Failed to set binary content");
             RelayError::Network {
                 message: "Failed to set binary content".into(),
-                cause: Some(e.to_string()),
+                cause: Some(e.into()),
             }
         })?;
 
@@ -222,7 +302,7 @@ impl<'a> ContentHandler<'a> {
                                 tracing::error!(error = %e, key = %key, "Failed to add form text field");
                                 RelayError::Network {
                                     message: format!("Failed to add form text field: {}", key),
-                                    cause: Some(e.to_string()),
+                                    cause: Some(e.into()),
                                 }
                             })?;
                     }
@@ -254,7 +334,7 @@ impl<'a> ContentHandler<'a> {
                                         "Failed to add form file field: {} ({})",
                                         key, filename
                                     ),
-                                    cause: Some(e.to_string()),
+                                    cause: Some(e.into()),
                                 }
                             })?;
                     }
@@ -266,7 +346,7 @@ impl<'a> ContentHandler<'a> {
             tracing::error!(error = %e, "Failed to set form data");
             RelayError::Network {
                 message: "Failed to set form data".into(),
-                cause: Some(e.to_string()),
+                cause: Some(e.into()),
             }
         })?;
 
@@ -296,7 +376,7 @@ impl<'a> ContentHandler<'a> {
                 tracing::error!(error = %e, "Failed to set urlencoded content");
                 RelayError::Network {
                     message: "Failed to set urlencoded content".into(),
-                    cause: Some(e.to_string()),
+                    cause: Some(e.into()),
                 }
             })?;
 
@@ -304,3 +384,193 @@ impl<'a> ContentHandler<'a> {
         Ok(())
     }
 }
+
+// NOTE: Appends a field, keeping the order fields were given in. Only a
+// value for the same name as the last field is grouped with it, so `a`, `b`,
+// `a` stays three fields rather than becoming `a`, `a`, `b`.
+pub(crate) fn push_field(fields: &mut FormData, name: String, value: FormValue) {
+    match fields.last_mut() {
+        Some((last, values)) if *last == name => values.push(value),
+        _ => fields.push((name, vec![value])),
+    }
+}
+
+// NOTE: Orders fields by name for servers and signatures that expect it.
+// The sort is stable, so values under one name keep their order, and
+// urlencoded pairs are compared by their encoded names and never re-encoded.
+pub(crate) fn sort_fields(content: &mut ContentType) {
+    match content {
+        ContentType::Form { content, .. } | ContentType::Multipart { content, .. } => {
+            content.sort_by(|(a, _), (b, _)| a.cmp(b));
+        }
+        ContentType::Urlencoded { content, .. } => {
+            let mut pairs = content
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .collect::<Vec<_>>();
+            pairs.sort_by_key(|pair| pair.split('=').next().unwrap_or_default());
+            *content = pairs.join("&");
+        }
+        _ => {}
+    }
+}
+
+// NOTE: Raw bytes sent on the wire for content that is sent as-is,
+// form and multipart bodies are encoded by curl and have no stable form.
+pub(crate) fn body_bytes(content: &ContentType) -> Option<Vec<u8>> {
+    match content {
+        ContentType::Text { content, .. }
+        | ContentType::Xml { content, .. }
+        | ContentType::Csv { content, .. }
+        | ContentType::Urlencoded { content, .. }
+        | ContentType::Json {
+            raw: Some(content), ..
+        } => Some(content.as_bytes().to_vec()),
+        ContentType::Json { content, .. }
+        | ContentType::JsonMergePatch { content }
+        | ContentType::JsonPatch {
+            operations: content,
+        } => serde_json::to_vec(content).ok(),
+        ContentType::Binary { content, .. } => Some(content.to_vec()),
+        ContentType::MultipartRelated {
+            root,
+            parts,
+            type_param,
+        } => Some(multipart::related(root, parts, type_param).1),
+        ContentType::Form { .. } | ContentType::Multipart { .. } => None,
+    }
+}
+
+fn patch_headers(media_type: MediaType) -> HashMap<String, String> {
+    HashMap::from([("Content-Type".to_string(), media_type.to_string())])
+}
+
+fn csv_headers(has_header: bool) -> HashMap<String, String> {
+    HashMap::from([("Content-Type".to_string(), csv::media_type(has_header))])
+}
+
+// NOTE: Headers added for the content itself, anything curl derives on its own
+// such as `Content-Length` or multipart boundaries is not included.
+pub(crate) fn content_headers(content: &ContentType) -> HashMap<String, String> {
+    match content {
+        ContentType::JsonPatch { .. } => patch_headers(MediaType::JsonPatch),
+        ContentType::JsonMergePatch { .. } => patch_headers(MediaType::JsonMergePatch),
+        ContentType::Csv { has_header, .. } => csv_headers(*has_header),
+        ContentType::MultipartRelated {
+            root,
+            parts,
+            type_param,
+        } => HashMap::from([(
+            "Content-Type".to_string(),
+            multipart::related(root, parts, type_param).0,
+        )]),
+        _ => HashMap::new(),
+    }
+}
+
+pub(crate) fn media_type(content: &ContentType) -> MediaType {
+    match content {
+        ContentType::Text { media_type, .. }
+        | ContentType::Json { media_type, .. }
+        | ContentType::Xml { media_type, .. }
+        | ContentType::Form { media_type, .. }
+        | ContentType::Binary { media_type, .. }
+        | ContentType::Multipart { media_type, .. }
+        | ContentType::Urlencoded { media_type, .. } => *media_type,
+        ContentType::JsonPatch { .. } => MediaType::JsonPatch,
+        ContentType::JsonMergePatch { .. } => MediaType::JsonMergePatch,
+        ContentType::MultipartRelated { .. } => MediaType::MultipartRelated,
+        ContentType::Csv { .. } => MediaType::TextCsv,
+    }
+}
+
+// NOTE: Structural checks only, paths are not resolved against any document,
+// see: https://datatracker.ietf.org/doc/html/rfc6902#section-4
+pub(crate) fn validate_json_patch(operations: &serde_json::Value) -> Result<()> {
+    let invalid = |message: String| {
+        tracing::error!(message = %message, "Invalid JSON Patch document");
+        RelayError::Parse {
+            message: format!("Invalid JSON Patch document: {}", message),
+            cause: None,
+            partial: None,
+        }
+    };
+
+    let operations = operations
+        .as_array()
+        .ok_or_else(|| invalid("expected an array of operations".into()))?;
+
+    for (index, operation) in operations.iter().enumerate() {
+        let operation = operation
+            .as_object()
+            .ok_or_else(|| invalid(format!("operation {} is not an object", index)))?;
+
+        let op = operation
+            .get("op")
+            .and_then(|op| op.as_str())
+            .ok_or_else(|| invalid(format!("operation {} is missing 'op'", index)))?;
+
+        if !operation.get("path").is_some_and(|path| path.is_string()) {
+            return Err(invalid(format!("operation {} is missing 'path'", index)));
+        }
+
+        let required = match op {
+            "add" | "replace" | "test" => Some("value"),
+            "move" | "copy" => Some("from"),
+            "remove" => None,
+            other => {
+                return Err(invalid(format!(
+                    "operation {} has unknown op '{}'",
+                    index, other
+                )))
+            }
+        };
+
+        if let Some(field) = required {
+            if !operation.contains_key(field) {
+                return Err(invalid(format!(
+                    "operation {} ('{}') is missing '{}'",
+                    index, op, field
+                )));
+            }
+        }
+    }
+
+    tracing::debug!(
+        operations = operations.len(),
+        "JSON Patch document validated"
+    );
+    Ok(())
+}
+
+// NOTE: Part offsets come from `ContentType::estimated_size`, which counts
+// the bytes curl sends. They are still scaled to the upload total curl
+// reports, so progress stays in bounds should the two ever disagree.
+pub(crate) fn locate_part(
+    estimate: &BodyEstimate,
+    uploaded: u64,
+    total: u64,
+) -> Option<PartProgress> {
+    let parts = &estimate.parts;
+    parts.last()?;
+
+    let position = match total {
+        0 => uploaded,
+        total => (uploaded as u128 * estimate.total_bytes as u128 / total as u128) as u64,
+    };
+
+    let index = parts
+        .iter()
+        .position(|part| position < part.offset + part.total_bytes)
+        .unwrap_or(parts.len() - 1);
+    let part = &parts[index];
+
+    Some(PartProgress {
+        index,
+        count: parts.len(),
+        name: part.name.clone(),
+        filename: part.filename.clone(),
+        sent: position.saturating_sub(part.offset).min(part.total_bytes),
+        size: part.total_bytes,
+    })
+}
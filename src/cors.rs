@@ -0,0 +1,164 @@
+use std::str::FromStr;
+
+use http::Method;
+
+use crate::{
+    client::RelayClient,
+    error::Result,
+    interop::{CorsInfo, CorsVerdict, Request, Response},
+};
+
+// NOTE: Methods and request headers a browser sends cross-origin without a
+// preflight. `Content-Type` is left out, it is only safelisted for form and
+// plain text bodies, and callers asking are usually sending JSON.
+const SAFELISTED_METHODS: &[Method] = &[Method::GET, Method::HEAD, Method::POST];
+const SAFELISTED_HEADERS: &[&str] = &["accept", "accept-language", "content-language"];
+
+fn split_list(value: Option<String>) -> Vec<String> {
+    value
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl Response {
+    // NOTE: Unknown or malformed methods in `Allow` are skipped.
+    pub fn allow(&self) -> Vec<Method> {
+        split_list(self.header_joined("allow"))
+            .iter()
+            .filter_map(|method| Method::from_str(method).ok())
+            .collect()
+    }
+
+    pub fn cors(&self) -> Option<CorsInfo> {
+        let has_cors = self
+            .headers
+            .keys()
+            .any(|name| name.to_ascii_lowercase().starts_with("access-control-"));
+        if !has_cors {
+            return None;
+        }
+
+        Some(CorsInfo {
+            allow_origin: self
+                .header_str("access-control-allow-origin")
+                .map(|origin| origin.trim().to_string()),
+            allow_methods: split_list(self.header_joined("access-control-allow-methods")),
+            allow_headers: split_list(self.header_joined("access-control-allow-headers")),
+            allow_credentials: self
+                .header_str("access-control-allow-credentials")
+                .is_some_and(|credentials| credentials.trim() == "true"),
+            expose_headers: split_list(self.header_joined("access-control-expose-headers")),
+            max_age: self
+                .header_str("access-control-max-age")
+                .and_then(|age| age.trim().parse().ok()),
+        })
+    }
+
+    // NOTE: Evaluates this response as the preflight for a request with
+    // `method` and `headers` from `origin`, the way the Fetch standard does
+    // for requests without credentials. Methods compare case-sensitively and
+    // header names case-insensitively, and `*` never covers `Authorization`.
+    pub fn permits(&self, method: &Method, origin: &str, headers: &[&str]) -> CorsVerdict {
+        let cors = self.cors().unwrap_or_default();
+        let mut reasons = Vec::new();
+
+        if !self.is_success() {
+            reasons.push(format!("Preflight answered {}, not a 2xx", self.status));
+        }
+
+        match cors.allow_origin.as_deref() {
+            None => reasons.push("No Access-Control-Allow-Origin header".to_string()),
+            Some("*") => {}
+            Some(allowed) if allowed == origin => {}
+            Some(allowed) => reasons.push(format!(
+                "Access-Control-Allow-Origin is {}, not {}",
+                allowed, origin
+            )),
+        }
+
+        let method_allowed = SAFELISTED_METHODS.contains(method)
+            || cors
+                .allow_methods
+                .iter()
+                .any(|allowed| allowed == "*" || allowed == method.as_str());
+        if !method_allowed {
+            reasons.push(format!(
+                "Method {} is not in Access-Control-Allow-Methods",
+                method
+            ));
+        }
+
+        for header in headers {
+            let header = header.trim();
+            let allowed = SAFELISTED_HEADERS
+                .iter()
+                .any(|safelisted| header.eq_ignore_ascii_case(safelisted))
+                || cors.allow_headers.iter().any(|allowed| {
+                    allowed.eq_ignore_ascii_case(header)
+                        || (allowed == "*" && !header.eq_ignore_ascii_case("authorization"))
+                });
+            if !allowed {
+                reasons.push(format!(
+                    "Header {} is not in Access-Control-Allow-Headers",
+                    header
+                ));
+            }
+        }
+
+        if reasons.is_empty() {
+            CorsVerdict::Allowed
+        } else {
+            tracing::debug!(reasons = ?reasons, "Preflight would fail");
+            CorsVerdict::Denied { reasons }
+        }
+    }
+}
+
+impl CorsVerdict {
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, Self::Allowed)
+    }
+}
+
+impl RelayClient {
+    // NOTE: What the server says `url` supports, read it with
+    // `Response::allow`.
+    pub async fn options(&self, url: impl Into<String>) -> Result<Response> {
+        self.execute(Request::builder().options(url).build()?).await
+    }
+
+    // NOTE: Sends the preflight a browser would for `method` and `headers`
+    // from `origin`, pass the same values to `Response::permits` for the
+    // verdict.
+    pub async fn preflight(
+        &self,
+        url: impl Into<String>,
+        origin: &str,
+        method: &Method,
+        headers: &[&str],
+    ) -> Result<Response> {
+        let mut builder = Request::builder()
+            .options(url)
+            .header("Origin", origin)
+            .header("Access-Control-Request-Method", method.as_str());
+        if !headers.is_empty() {
+            builder = builder.header(
+                "Access-Control-Request-Headers",
+                headers
+                    .iter()
+                    .map(|header| header.trim().to_ascii_lowercase())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            );
+        }
+
+        self.execute(builder.build()?).await
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{
+    error::{RelayError, Result},
+    interop::Response,
+};
+
+pub(crate) const DEFAULT_DELIMITER: char = ',';
+
+// NOTE: RFC 4180's optional `header` parameter of `text/csv`, which is how a
+// request says whether its first row names the columns.
+pub(crate) fn media_type(has_header: bool) -> String {
+    format!(
+        "text/csv; header={}",
+        if has_header { "present" } else { "absent" }
+    )
+}
+
+fn invalid(line: usize, cause: String) -> RelayError {
+    tracing::error!(line = line, cause = %cause, "Invalid CSV");
+    RelayError::Parse {
+        message: format!("Invalid CSV at line {}", line),
+        cause: Some(cause.into()),
+        partial: None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    // NOTE: At the start of a field, where a quote opens a quoted field.
+    FieldStart,
+    Unquoted,
+    Quoted,
+    // NOTE: Just read a quote inside a quoted field, either the closing one
+    // or the first half of an escaped `""`.
+    QuoteInQuoted,
+}
+
+// NOTE: RFC 4180 with any delimiter. Records end in CRLF or a bare LF, the
+// last one may go without, and quoted fields keep their delimiters and line
+// breaks as they are. A quote inside an unquoted field is taken literally,
+// as most writers that produce one mean it that way.
+pub(crate) fn parse(text: &str, delimiter: char) -> Result<Vec<Vec<String>>> {
+    if matches!(delimiter, '"' | '\r' | '\n') {
+        return Err(invalid(
+            1,
+            format!("{:?} cannot be used as the delimiter", delimiter),
+        ));
+    }
+
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut state = State::FieldStart;
+    let mut line = 1;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (state, c) {
+            (State::Quoted, '"') => state = State::QuoteInQuoted,
+            (State::Quoted, c) => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+            (State::QuoteInQuoted, '"') => {
+                field.push('"');
+                state = State::Quoted;
+            }
+            (State::FieldStart, '"') => state = State::Quoted,
+            (_, c) if c == delimiter => {
+                record.push(std::mem::take(&mut field));
+                state = State::FieldStart;
+            }
+            (_, '\r') if chars.peek() == Some(&'\n') => {}
+            (_, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+                state = State::FieldStart;
+                line += 1;
+            }
+            (State::QuoteInQuoted, c) => {
+                return Err(invalid(
+                    line,
+                    format!(
+                        "expected a delimiter or line break after a closing quote but found {:?}",
+                        c
+                    ),
+                ));
+            }
+            (State::FieldStart | State::Unquoted, c) => {
+                field.push(c);
+                state = State::Unquoted;
+            }
+        }
+    }
+
+    match state {
+        State::Quoted => return Err(invalid(line, "quoted field is never closed".to_string())),
+        // NOTE: Input that ends in a line break has no record after it.
+        State::FieldStart if record.is_empty() => {}
+        _ => {
+            record.push(field);
+            records.push(record);
+        }
+    }
+
+    Ok(records)
+}
+
+impl Response {
+    // NOTE: Reads the body as comma separated, leaving out the first row when
+    // the response's `Content-Type` has `header=present`.
+    pub fn csv_records(&self) -> Result<Vec<Vec<String>>> {
+        let has_header = self
+            .header_str("content-type")
+            .into_iter()
+            .flat_map(|content_type| content_type.split(';').skip(1))
+            .filter_map(|parameter| parameter.split_once('='))
+            .any(|(name, value)| {
+                name.trim().eq_ignore_ascii_case("header")
+                    && value
+                        .trim()
+                        .trim_matches('"')
+                        .eq_ignore_ascii_case("present")
+            });
+        self.csv_records_with(DEFAULT_DELIMITER, has_header)
+    }
+
+    // NOTE: For bodies in another dialect, e.g. tab or semicolon separated,
+    // or a server that does not say whether there is a header row.
+    pub fn csv_records_with(&self, delimiter: char, has_header: bool) -> Result<Vec<Vec<String>>> {
+        let mut records = parse(&self.text()?, delimiter)?;
+        if has_header && !records.is_empty() {
+            records.remove(0);
+        }
+        tracing::debug!(records = records.len(), "Parsed CSV body");
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(records: &[&[&str]]) -> Vec<Vec<String>> {
+        records
+            .iter()
+            .map(|record| record.iter().map(|field| field.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn quoted_fields_keep_delimiters_and_line_breaks() {
+        let text = "name,note\r\n\"Smith, J\",\"first line\r\nsecond line\"\r\nplain,\"\"\r\n";
+        assert_eq!(
+            parse(text, ',').unwrap(),
+            rows(&[
+                &["name", "note"],
+                &["Smith, J", "first line\r\nsecond line"],
+                &["plain", ""],
+            ])
+        );
+    }
+
+    #[test]
+    fn doubled_quotes_are_one_quote() {
+        let text = "\"say \"\"hi\"\"\",\"\"\"\"\nhe said \"no\",x";
+        assert_eq!(
+            parse(text, ',').unwrap(),
+            rows(&[&["say \"hi\"", "\""], &["he said \"no\"", "x"]])
+        );
+    }
+
+    #[test]
+    fn uses_the_given_delimiter() {
+        let text = "a;\"b;c\";d,e\n1;2;3";
+        assert_eq!(
+            parse(text, ';').unwrap(),
+            rows(&[&["a", "b;c", "d,e"], &["1", "2", "3"]])
+        );
+        assert_eq!(parse("a\tb\n", '\t').unwrap(), rows(&[&["a", "b"]]));
+    }
+
+    #[test]
+    fn handles_boms_and_empty_fields() {
+        assert_eq!(
+            parse("\u{feff}a,,c\n,\n", ',').unwrap(),
+            rows(&[&["a", "", "c"], &["", ""]])
+        );
+        assert!(parse("", ',').unwrap().is_empty());
+    }
+
+    #[test]
+    fn reports_the_line_of_malformed_quoting() {
+        let line = |result: Result<Vec<Vec<String>>>| match result {
+            Err(RelayError::Parse { message, .. }) => message,
+            other => panic!("expected a parse error, got {:?}", other),
+        };
+
+        assert_eq!(line(parse("a\n\"b\nc", ',')), "Invalid CSV at line 3");
+        assert_eq!(line(parse("a\n\"b\"c,d", ',')), "Invalid CSV at line 2");
+        assert_eq!(line(parse("a,b", '"')), "Invalid CSV at line 1");
+    }
+}
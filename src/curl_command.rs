@@ -0,0 +1,1026 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use http::{Method, Version};
+use mime::Mime;
+
+use crate::{
+    content, csv,
+    error::{self, CurlParseError, RelayError},
+    export::ExportOptions,
+    header::Headers,
+    interop::{
+        AuthType, ByteRange, CertificateConfig, CertificateType, ContentType, FormValue, MediaType,
+        ProxyAuth, ProxyAuthScheme, ProxyConfig, Request, RequestMeta, RequestOptions,
+        SecurityConfig,
+    },
+    query, render,
+};
+
+#[derive(Debug, Clone)]
+pub struct CurlCommand {
+    pub request: Request,
+    pub warnings: Vec<String>,
+    // NOTE: Paths the command references with `@file`, `--cert`, `--key` and
+    // `--cacert`, as written, whether or not they were read.
+    pub files: Vec<String>,
+}
+
+impl Request {
+    // NOTE: Builds a request from a pasted `curl` invocation, e.g. a
+    // browser's "Copy as cURL". Files the command references with `@file`,
+    // `--cert`, `--key` and `--cacert` are not read, the parts that need them
+    // are left out. Use `CurlCommand::parse` to see what was ignored.
+    pub fn from_curl_command(command: &str, id: i64) -> error::Result<Request> {
+        Self::curl_command_request(CurlCommand::parse(command), id)
+    }
+
+    // NOTE: Reads the files the command references, relative paths from
+    // `dir`. A pasted command can name any file, e.g. `-d @/etc/passwd`, so
+    // only for commands from a trusted source.
+    pub fn from_curl_command_with_files(
+        command: &str,
+        id: i64,
+        dir: impl AsRef<Path>,
+    ) -> error::Result<Request> {
+        Self::curl_command_request(CurlCommand::parse_with_files(command, dir), id)
+    }
+
+    fn curl_command_request(
+        parsed: Result<CurlCommand, CurlParseError>,
+        id: i64,
+    ) -> error::Result<Request> {
+        let parsed = parsed.map_err(|e| RelayError::Parse {
+            message: e.to_string(),
+            cause: Some(e.into()),
+            partial: None,
+        })?;
+        for warning in &parsed.warnings {
+            tracing::warn!(warning = %warning, "Ignored part of curl command");
+        }
+        Ok(Request {
+            id,
+            ..parsed.request
+        })
+    }
+
+    // NOTE: Secret headers are redacted as `ExportOptions::default()` decides,
+    // see `to_curl_command_with`.
+    pub fn to_curl_command(&self) -> error::Result<String> {
+        self.to_curl_command_with(&ExportOptions::default())
+    }
+
+    // NOTE: The inverse of `from_curl_command`, one flag per line. Headers
+    // curl adds on its own are left to it, form file parts reference their
+    // filename, and a body that is not UTF-8 is shown as a placeholder.
+    #[tracing::instrument(skip(self, options), fields(request_id = self.id), level = "debug")]
+    pub fn to_curl_command_with(&self, options: &ExportOptions) -> error::Result<String> {
+        let url = query::wire_url(self)?;
+        let mut args = vec![quote(&options.url(self, &url))];
+
+        let has_body = self.content.is_some();
+        if self.method == Method::HEAD {
+            args.push("--head".into());
+        } else if !(self.method == Method::GET && !has_body
+            || self.method == Method::POST && has_body)
+        {
+            args.push(format!("-X {}", self.method));
+        }
+
+        match self.version {
+            Version::HTTP_10 => args.push("--http1.0".into()),
+            Version::HTTP_2 => args.push("--http2".into()),
+            Version::HTTP_3 => args.push("--http3".into()),
+            _ => {}
+        }
+
+        let headers = render::derived_headers(self);
+        for name in headers.suppressed() {
+            args.push(format!("-H {}", quote(&format!("{}:", name))));
+        }
+        for (name, value) in headers.into_iter() {
+            let value = options.header_value(self, &name, &value);
+            args.push(format!("-H {}", quote(&format!("{}: {}", name, value))));
+        }
+
+        let credentials = |username: &str, password: &str| {
+            let password = options.header_value(self, "authorization", password);
+            quote(&format!("{}:{}", username, password))
+        };
+        match self.auth {
+            Some(AuthType::Basic {
+                ref username,
+                ref password,
+            }) => args.push(format!("-u {}", credentials(username, password))),
+            Some(AuthType::Digest {
+                ref username,
+                ref password,
+                ..
+            }) => args.push(format!("--digest -u {}", credentials(username, password))),
+            _ => {}
+        }
+
+        match self.content {
+            Some(ContentType::Form { ref content, .. })
+            | Some(ContentType::Multipart { ref content, .. }) => {
+                for (name, values) in content {
+                    for value in values {
+                        args.push(match value {
+                            FormValue::Text { value } => {
+                                format!("--form-string {}", quote(&format!("{}={}", name, value)))
+                            }
+                            FormValue::File {
+                                filename,
+                                content_type,
+                                ..
+                            } => format!(
+                                "-F {}",
+                                quote(&format!("{}=@{};type={}", name, filename, content_type))
+                            ),
+                        });
+                    }
+                }
+            }
+            Some(ref content) => {
+                let body = content::body_bytes(content).unwrap_or_default();
+                let text = render::body_text(&body, content::media_type(content));
+                args.push(format!("--data-raw {}", quote(&text)));
+            }
+            None => {}
+        }
+
+        let request_options = self.meta.as_ref().and_then(|meta| meta.options.as_ref());
+        if let Some(request_options) = request_options {
+            if request_options.follow_redirects == Some(true) {
+                args.push("-L".into());
+            }
+            if let Some(max) = request_options.max_redirects {
+                args.push(format!("--max-redirs {}", max));
+            }
+            if let Some(timeout) = request_options.timeout {
+                args.push(format!("-m {}", timeout as f64 / 1000.0));
+            }
+            if request_options.fail_on_error == Some(true) {
+                args.push("--fail-with-body".into());
+            }
+            if let Some(ref interface) = request_options.interface {
+                args.push(format!("--interface {}", quote(interface)));
+            }
+        }
+        // NOTE: relay decompresses unless told not to, the curl tool only
+        // with `--compressed`.
+        if request_options.and_then(|options| options.decompress) != Some(false) {
+            args.push("--compressed".into());
+        }
+
+        if let Some(ref security) = self.security {
+            if security.verify_peer == Some(false) || security.verify_host == Some(false) {
+                args.push("-k".into());
+            }
+        }
+        if let Some(ref proxy) = self.proxy {
+            args.push(format!("-x {}", quote(&proxy.url)));
+        }
+
+        tracing::debug!(flags = args.len(), "Exported request as curl command");
+        Ok(format!("curl {}", args.join(" \\\n  ")))
+    }
+}
+
+// NOTE: Single quotes keep everything literal in POSIX shells, a quote itself
+// has to close the string, be escaped and reopen it.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+// NOTE: Flags that take a value but have no equivalent on `Request`,
+// their value is consumed so it isn't mistaken for the URL. Any other flag
+// is taken to have no value, as most of curl's flags have none.
+const IGNORED_WITH_VALUE: &[&str] = &[
+    "-o",
+    "--output",
+    "--output-dir",
+    "-w",
+    "--write-out",
+    "-c",
+    "--cookie-jar",
+    "-D",
+    "--dump-header",
+    "--stderr",
+    "-K",
+    "--config",
+    "-T",
+    "--upload-file",
+    "-C",
+    "--continue-at",
+    "-z",
+    "--time-cond",
+    "--capath",
+    "--ciphers",
+    "--tls-max",
+    "--pinnedpubkey",
+    "--key-type",
+    "--pass",
+    "--proxy-cacert",
+    "--proxy-cert",
+    "--proxy-key",
+    "--proxy-header",
+    "--preproxy",
+    "--socks5",
+    "--socks5-hostname",
+    "--noproxy",
+    "--resolve",
+    "--connect-to",
+    "--unix-socket",
+    "--abstract-unix-socket",
+    "--local-port",
+    "--proto",
+    "--proto-redir",
+    "--connect-timeout",
+    "--expect100-timeout",
+    "--happy-eyeballs-timeout-ms",
+    "--keepalive-time",
+    "-Y",
+    "--speed-limit",
+    "-y",
+    "--speed-time",
+    "--limit-rate",
+    "--max-filesize",
+    "--retry",
+    "--retry-delay",
+    "--retry-max-time",
+    "--oauth2-bearer",
+    "--aws-sigv4",
+    "--request-target",
+    "--netrc-file",
+    "--hsts",
+    "--alt-svc",
+    "--etag-save",
+    "--etag-compare",
+    "--trace",
+    "--trace-ascii",
+];
+
+const IGNORED_FLAGS: &[&str] = &[
+    "-s",
+    "--silent",
+    "-S",
+    "--show-error",
+    "-v",
+    "--verbose",
+    "-i",
+    "--include",
+    "-#",
+    "--progress-bar",
+    "--no-progress-meter",
+];
+
+fn takes_value(flag: &str) -> bool {
+    matches!(
+        flag,
+        "-X" | "--request"
+            | "-H"
+            | "--header"
+            | "-d"
+            | "--data"
+            | "--data-raw"
+            | "--data-binary"
+            | "--data-ascii"
+            | "--data-urlencode"
+            | "-F"
+            | "--form"
+            | "-u"
+            | "--user"
+            | "--url"
+            | "-x"
+            | "--proxy"
+            | "-U"
+            | "--proxy-user"
+            | "-b"
+            | "--cookie"
+            | "-A"
+            | "--user-agent"
+            | "-e"
+            | "--referer"
+            | "-m"
+            | "--max-time"
+            | "--max-redirs"
+            | "-r"
+            | "--range"
+            | "--interface"
+            | "--dns-servers"
+            | "--dns-interface"
+            | "--dns-ipv4-addr"
+            | "--dns-ipv6-addr"
+            | "--doh-url"
+            | "-E"
+            | "--cert"
+            | "--cert-type"
+            | "--key"
+            | "--cacert"
+    ) || IGNORED_WITH_VALUE.contains(&flag)
+}
+
+#[derive(Default)]
+struct Builder {
+    method: Option<Method>,
+    url: Option<String>,
+    version: Option<Version>,
+    headers: Vec<(String, String)>,
+    data: Vec<String>,
+    form: Vec<(String, FormValue)>,
+    auth: Option<AuthType>,
+    proxy: Option<ProxyConfig>,
+    proxy_user: Option<(String, String)>,
+    proxy_scheme: Option<ProxyAuthScheme>,
+    options: RequestOptions,
+    range: Option<ByteRange>,
+    cert: Option<(String, Option<String>)>,
+    cert_type: Option<String>,
+    key: Option<String>,
+    cacert: Vec<String>,
+    files_dir: Option<PathBuf>,
+    files: Vec<String>,
+    insecure: bool,
+    enable_alpn: Option<bool>,
+    false_start: Option<bool>,
+    session_id_cache: Option<bool>,
+    get: bool,
+    head: bool,
+    warnings: Vec<String>,
+}
+
+impl CurlCommand {
+    // NOTE: Never touches the file system, referenced files are listed in
+    // `files` and what needs them is left out with a warning.
+    pub fn parse(command: &str) -> Result<Self, CurlParseError> {
+        Self::parse_in(command, None)
+    }
+
+    // NOTE: Like `parse` but reads referenced files, relative paths from
+    // `dir`, see `Request::from_curl_command_with_files`.
+    pub fn parse_with_files(command: &str, dir: impl AsRef<Path>) -> Result<Self, CurlParseError> {
+        Self::parse_in(command, Some(dir.as_ref().to_path_buf()))
+    }
+
+    #[tracing::instrument(level = "debug")]
+    fn parse_in(command: &str, files_dir: Option<PathBuf>) -> Result<Self, CurlParseError> {
+        let tokens = tokenize(command)?;
+        let mut tokens = tokens.into_iter();
+
+        match tokens.next() {
+            None => return Err(CurlParseError::Empty),
+            Some(program) if program == "curl" || program.ends_with("/curl") => {}
+            Some(program) => return Err(CurlParseError::NotCurl(program)),
+        }
+
+        let mut builder = Builder {
+            files_dir,
+            ..Builder::default()
+        };
+
+        while let Some(token) = tokens.next() {
+            if token == "--" {
+                for url in tokens.by_ref() {
+                    builder.set_url(url);
+                }
+                break;
+            }
+
+            if let Some((flag, value)) = token.split_once('=').filter(|_| token.starts_with("--")) {
+                if takes_value(flag) {
+                    builder.apply(flag, Some(value.to_string()))?;
+                    continue;
+                }
+            }
+
+            if token.starts_with("--") {
+                let value = if takes_value(&token) {
+                    Some(
+                        tokens
+                            .next()
+                            .ok_or_else(|| CurlParseError::MissingValue(token.clone()))?,
+                    )
+                } else {
+                    None
+                };
+                builder.apply(&token, value)?;
+                continue;
+            }
+
+            if token.starts_with('-') && token.len() > 1 {
+                // NOTE: Short flags can be combined (`-sSL`) and can carry their
+                // value attached (`-XPOST`), curl stops at the first value flag.
+                let chars = token[1..].char_indices().collect::<Vec<_>>();
+                for (idx, c) in chars {
+                    let flag = format!("-{}", c);
+                    if takes_value(&flag) {
+                        let rest = &token[1 + idx + c.len_utf8()..];
+                        let value = if rest.is_empty() {
+                            tokens
+                                .next()
+                                .ok_or_else(|| CurlParseError::MissingValue(flag.clone()))?
+                        } else {
+                            rest.to_string()
+                        };
+                        builder.apply(&flag, Some(value))?;
+                        break;
+                    }
+                    builder.apply(&flag, None)?;
+                }
+                continue;
+            }
+
+            builder.set_url(token);
+        }
+
+        builder.build()
+    }
+}
+
+impl Builder {
+    fn set_url(&mut self, url: String) {
+        if let Some(ref existing) = self.url {
+            self.warnings.push(format!(
+                "Multiple URLs found, ignoring '{}' in favor of '{}'",
+                url, existing
+            ));
+        } else {
+            self.url = Some(url);
+        }
+    }
+
+    fn apply(&mut self, flag: &str, value: Option<String>) -> Result<(), CurlParseError> {
+        let arg = || value.clone().unwrap_or_default();
+
+        match flag {
+            "-X" | "--request" => {
+                let method = arg();
+                self.method = Some(Method::from_str(&method.to_uppercase()).map_err(|_| {
+                    CurlParseError::InvalidValue {
+                        flag: flag.into(),
+                        value: method,
+                    }
+                })?);
+            }
+            "-H" | "--header" => {
+                let header = arg();
+                let (name, value) = header
+                    .split_once(':')
+                    .ok_or_else(|| CurlParseError::InvalidHeader(header.clone()))?;
+                self.headers
+                    .push((name.trim().to_string(), value.trim().to_string()));
+            }
+            "-d" | "--data" | "--data-ascii" | "--data-raw" | "--data-binary" => {
+                let data = arg();
+                match data.strip_prefix('@').filter(|_| flag != "--data-raw") {
+                    Some("-") => {
+                        self.warnings.push(format!(
+                            "Reading '{}' from stdin is not supported, sent as literal data",
+                            flag
+                        ));
+                        self.data.push(data);
+                    }
+                    Some(path) => {
+                        if let Some(data) = self.read_text(flag, path)? {
+                            // NOTE: curl strips line breaks from files unless
+                            // they are sent with `--data-binary`.
+                            self.data.push(if flag == "--data-binary" {
+                                data
+                            } else {
+                                data.replace(['\r', '\n'], "")
+                            });
+                        }
+                    }
+                    None => self.data.push(data),
+                }
+            }
+            "--data-urlencode" => {
+                let data = arg();
+                let encoded = match data.split_once('=') {
+                    Some((name, content)) => format!("{}={}", name, urlencoding::encode(content)),
+                    None => urlencoding::encode(&data).into_owned(),
+                };
+                self.data.push(encoded);
+            }
+            "-F" | "--form" => {
+                let field = arg();
+                let Some((name, content)) = field.split_once('=') else {
+                    return Err(CurlParseError::InvalidValue {
+                        flag: flag.into(),
+                        value: field,
+                    });
+                };
+                if content.starts_with('@') || content.starts_with('<') {
+                    self.warnings.push(format!(
+                        "File upload '{}' in form field '{}' is not supported, field skipped",
+                        content, name
+                    ));
+                } else {
+                    self.form.push((
+                        name.to_string(),
+                        FormValue::Text {
+                            value: content.to_string(),
+                        },
+                    ));
+                }
+            }
+            "-u" | "--user" => {
+                let credentials = arg();
+                let (username, password) = credentials
+                    .split_once(':')
+                    .map(|(u, p)| (u.to_string(), p.to_string()))
+                    .unwrap_or((credentials, String::new()));
+                self.auth = Some(AuthType::Basic { username, password });
+            }
+            "--url" => self.set_url(arg()),
+            "-L" | "--location" => self.options.follow_redirects = Some(true),
+            "-k" | "--insecure" => self.insecure = true,
+            "--no-alpn" => self.enable_alpn = Some(false),
+            "--false-start" => self.false_start = Some(true),
+            "--no-sessionid" => self.session_id_cache = Some(false),
+            "-r" | "--range" => {
+                let range = arg();
+                self.range = Some(range.parse().map_err(|_| CurlParseError::InvalidValue {
+                    flag: flag.into(),
+                    value: range.clone(),
+                })?);
+            }
+            "-x" | "--proxy" => {
+                self.proxy = Some(ProxyConfig {
+                    url: arg(),
+                    auth: None,
+                })
+            }
+            "-U" | "--proxy-user" => {
+                let credentials = arg();
+                self.proxy_user = credentials
+                    .split_once(':')
+                    .map(|(u, p)| (u.to_string(), p.to_string()));
+            }
+            "--proxy-basic" => self.proxy_scheme = Some(ProxyAuthScheme::Basic),
+            "--proxy-digest" => self.proxy_scheme = Some(ProxyAuthScheme::Digest),
+            "--proxy-ntlm" => self.proxy_scheme = Some(ProxyAuthScheme::Ntlm),
+            "--proxy-negotiate" => self.proxy_scheme = Some(ProxyAuthScheme::Negotiate),
+            "--proxy-anyauth" => self.proxy_scheme = None,
+            "--compressed" => self.options.decompress = Some(true),
+            "-b" | "--cookie" => {
+                let cookie = arg();
+                if cookie.contains('=') {
+                    self.headers.push(("Cookie".into(), cookie));
+                } else {
+                    self.warnings.push(format!(
+                        "Cookie file '{}' is not supported, cookies ignored",
+                        cookie
+                    ));
+                }
+            }
+            "-A" | "--user-agent" => self.headers.push(("User-Agent".into(), arg())),
+            "-e" | "--referer" => self.headers.push(("Referer".into(), arg())),
+            "-m" | "--max-time" => {
+                let seconds = arg();
+                let parsed = seconds
+                    .parse::<f64>()
+                    .map_err(|_| CurlParseError::InvalidValue {
+                        flag: flag.into(),
+                        value: seconds.clone(),
+                    })?;
+                self.options.timeout = Some((parsed * 1000.0) as u64);
+            }
+            "--max-redirs" => {
+                let max = arg();
+                self.options.max_redirects =
+                    Some(max.parse().map_err(|_| CurlParseError::InvalidValue {
+                        flag: flag.into(),
+                        value: max.clone(),
+                    })?);
+            }
+            "-E" | "--cert" => self.cert = Some(split_cert_password(&arg())),
+            "--cert-type" => self.cert_type = Some(arg()),
+            "--key" => self.key = Some(arg()),
+            "--cacert" => self.cacert.push(arg()),
+            "--interface" => self.options.interface = Some(arg()),
+            "--dns-servers" => {
+                self.options.dns_servers =
+                    Some(arg().split(',').map(|s| s.trim().to_string()).collect())
+            }
+            "--dns-interface" => self.options.dns_interface = Some(arg()),
+            "--dns-ipv4-addr" => self.options.dns_local_ip4 = Some(arg()),
+            "--dns-ipv6-addr" => self.options.dns_local_ip6 = Some(arg()),
+            "--doh-url" => self.options.doh_url = Some(arg()),
+            // NOTE: relay always keeps the body on `RelayError::HttpStatus`,
+            // curl's plain `--fail` discards it.
+            "-f" | "--fail" => {
+                self.options.fail_on_error = Some(true);
+                self.warnings.push(format!(
+                    "'{}' imported as '--fail-with-body', the error keeps the response body",
+                    flag
+                ));
+            }
+            "--fail-with-body" => self.options.fail_on_error = Some(true),
+            "-G" | "--get" => self.get = true,
+            "-I" | "--head" => self.head = true,
+            "-0" | "--http1.0" => self.version = Some(Version::HTTP_10),
+            "--http1.1" => self.version = Some(Version::HTTP_11),
+            "--http2" | "--http2-prior-knowledge" => self.version = Some(Version::HTTP_2),
+            "--http3" | "--http3-only" => self.version = Some(Version::HTTP_3),
+            flag if IGNORED_FLAGS.contains(&flag) => {}
+            flag if IGNORED_WITH_VALUE.contains(&flag) => {
+                self.warnings.push(format!(
+                    "Unsupported flag '{}' ignored along with its value",
+                    flag
+                ));
+            }
+            flag => self
+                .warnings
+                .push(format!("Unknown flag '{}' ignored", flag)),
+        }
+
+        Ok(())
+    }
+
+    // NOTE: `None` when reading files was not asked for.
+    fn read(&mut self, flag: &str, path: &str) -> Result<Option<Vec<u8>>, CurlParseError> {
+        self.files.push(path.to_string());
+        let Some(ref dir) = self.files_dir else {
+            self.warnings.push(format!(
+                "File '{}' for '{}' was not read, ignored",
+                path, flag
+            ));
+            return Ok(None);
+        };
+        std::fs::read(dir.join(path))
+            .map(Some)
+            .map_err(|e| CurlParseError::UnreadableFile {
+                path: path.to_string(),
+                message: e.to_string(),
+            })
+    }
+
+    fn read_text(&mut self, flag: &str, path: &str) -> Result<Option<String>, CurlParseError> {
+        let Some(data) = self.read(flag, path)? else {
+            return Ok(None);
+        };
+        match String::from_utf8(data) {
+            Ok(text) => Ok(Some(text)),
+            Err(e) => {
+                self.warnings.push(format!(
+                    "File '{}' is not valid UTF-8, invalid bytes were replaced",
+                    path
+                ));
+                Ok(Some(String::from_utf8_lossy(e.as_bytes()).into_owned()))
+            }
+        }
+    }
+
+    // NOTE: A PEM `--cert` without `--key` is expected to hold the key too,
+    // which is how curl reads it.
+    fn certificates(&mut self) -> Result<Option<CertificateConfig>, CurlParseError> {
+        let client = match self.cert.take() {
+            Some((path, password)) => {
+                let data = self.read("--cert", &path)?.map(bytes::Bytes::from);
+                let key = match self.key.take() {
+                    Some(key) => self.read("--key", &key)?.map(bytes::Bytes::from),
+                    None => data.clone(),
+                };
+                let is_p12 = self.cert_type.as_deref().is_some_and(|cert_type| {
+                    cert_type.eq_ignore_ascii_case("p12") || cert_type.eq_ignore_ascii_case("pfx")
+                });
+                match (data, key) {
+                    (Some(data), _) if is_p12 => Some(CertificateType::Pfx {
+                        data,
+                        password: password.unwrap_or_default(),
+                    }),
+                    (Some(cert), Some(key)) => {
+                        if password.is_some() {
+                            self.warnings
+                                .push("Passphrases for PEM keys are not supported, ignored".into());
+                        }
+                        Some(CertificateType::Pem { cert, key })
+                    }
+                    _ => None,
+                }
+            }
+            None => {
+                if self.key.is_some() {
+                    self.warnings
+                        .push("'--key' without '--cert' ignored".into());
+                }
+                None
+            }
+        };
+
+        let mut ca = Vec::new();
+        for path in std::mem::take(&mut self.cacert) {
+            if let Some(data) = self.read("--cacert", &path)? {
+                ca.push(bytes::Bytes::from(data));
+            }
+        }
+
+        if client.is_none() && ca.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(CertificateConfig {
+            client,
+            ca: (!ca.is_empty()).then_some(ca),
+        }))
+    }
+
+    fn content_type(&self) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn content(&self, data: String) -> ContentType {
+        let mime = self.content_type().and_then(|ct| ct.parse::<Mime>().ok());
+
+        match mime {
+            Some(ref mime) if mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON) => {
+                match serde_json::from_str(&data) {
+                    Ok(content) => ContentType::Json {
+                        content,
+                        raw: Some(data),
+                        media_type: MediaType::from_str(mime.essence_str())
+                            .unwrap_or(MediaType::Json),
+                    },
+                    Err(_) => ContentType::Text {
+                        content: data,
+                        media_type: MediaType::Json,
+                    },
+                }
+            }
+            Some(ref mime) if mime.subtype() == mime::XML => ContentType::Xml {
+                content: data,
+                media_type: MediaType::from_str(mime.essence_str()).unwrap_or(MediaType::Xml),
+            },
+            Some(ref mime) if (mime.type_(), mime.subtype()) == (mime::TEXT, mime::CSV) => {
+                ContentType::Csv {
+                    content: data,
+                    has_header: mime
+                        .get_param("header")
+                        .is_some_and(|header| header.as_str().eq_ignore_ascii_case("present")),
+                    delimiter: csv::DEFAULT_DELIMITER,
+                }
+            }
+            Some(ref mime)
+                if (mime.type_(), mime.subtype())
+                    != (mime::APPLICATION, mime::WWW_FORM_URLENCODED) =>
+            {
+                ContentType::Text {
+                    content: data,
+                    media_type: MediaType::from_str(mime.essence_str())
+                        .unwrap_or(MediaType::TextPlain),
+                }
+            }
+            _ => ContentType::Urlencoded {
+                content: data,
+                media_type: MediaType::FormUrlEncoded,
+            },
+        }
+    }
+
+    fn build(mut self) -> Result<CurlCommand, CurlParseError> {
+        let mut url = self.url.take().ok_or(CurlParseError::MissingUrl)?;
+        let data = (!self.data.is_empty()).then(|| self.data.join("&"));
+        let certificates = self.certificates()?;
+
+        let content = if self.get {
+            if let Some(query) = data {
+                url.push(if url.contains('?') { '&' } else { '?' });
+                url.push_str(&query);
+            }
+            None
+        } else if !self.form.is_empty() {
+            if data.is_some() {
+                self.warnings
+                    .push("Both form fields and data provided, data ignored".into());
+            }
+
+            let mut fields: Vec<(String, Vec<FormValue>)> = Vec::new();
+            for (name, value) in self.form.drain(..) {
+                content::push_field(&mut fields, name, value);
+            }
+
+            Some(ContentType::Multipart {
+                content: fields,
+                media_type: MediaType::MultipartFormData,
+            })
+        } else {
+            data.map(|data| self.content(data))
+        };
+
+        let method = match self.method.take() {
+            Some(method) => method,
+            None if self.head => Method::HEAD,
+            None if content.is_some() => Method::POST,
+            None => Method::GET,
+        };
+
+        // NOTE: Repeated headers are folded the way a server would see them,
+        // cookies use `; ` as their separator while everything else uses `, `.
+        let mut headers = Headers::new();
+        for (name, value) in self.headers {
+            match headers.get_mut(&name) {
+                Some(current) => {
+                    let separator = if name.eq_ignore_ascii_case("cookie") {
+                        "; "
+                    } else {
+                        ", "
+                    };
+                    current.push_str(separator);
+                    current.push_str(&value);
+                }
+                None => headers.append(name, value),
+            }
+        }
+
+        let proxy = self.proxy.map(|mut proxy| {
+            proxy.auth = self.proxy_user.map(|(username, password)| ProxyAuth {
+                username,
+                password,
+                scheme: self.proxy_scheme,
+                token: None,
+            });
+            proxy
+        });
+
+        let tuned = self.enable_alpn.is_some()
+            || self.false_start.is_some()
+            || self.session_id_cache.is_some();
+        let security = (self.insecure || certificates.is_some() || tuned).then(|| SecurityConfig {
+            certificates,
+            verify_host: self.insecure.then_some(false),
+            verify_peer: self.insecure.then_some(false),
+            min_tls_version: None,
+            enable_alpn: self.enable_alpn,
+            false_start: self.false_start,
+            session_id_cache: self.session_id_cache,
+        });
+
+        let request = Request {
+            id: 0,
+            url,
+            method,
+            version: self.version.unwrap_or(Version::HTTP_11),
+            headers: (!headers.is_empty()).then_some(headers),
+            params: None,
+            content,
+            auth: self.auth,
+            security,
+            proxy,
+            meta: Some(RequestMeta {
+                options: Some(self.options),
+            }),
+            trace_context: None,
+            range: self.range,
+            accept: None,
+            raw_options: Vec::new(),
+            secrets: Default::default(),
+            target_policy: None,
+        };
+
+        tracing::debug!(
+            method = %request.method,
+            url = %request.url,
+            warnings = self.warnings.len(),
+            "Parsed curl command"
+        );
+
+        Ok(CurlCommand {
+            request,
+            warnings: self.warnings,
+            files: self.files,
+        })
+    }
+}
+
+// NOTE: `--cert` takes `file:password`, a `\:` is a colon in the file name,
+// and so is the one after a Windows drive letter.
+fn split_cert_password(value: &str) -> (String, Option<String>) {
+    let mut path = String::new();
+    let mut chars = value.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '\\' if chars.peek().is_some_and(|&(_, next)| next == ':') => {
+                path.push(':');
+                chars.next();
+            }
+            ':' if idx == 1
+                && path.chars().all(|c| c.is_ascii_alphabetic())
+                && value[2..].starts_with(['\\', '/']) =>
+            {
+                path.push(c)
+            }
+            ':' => return (path, Some(value[idx + 1..].to_string())),
+            c => path.push(c),
+        }
+    }
+    (path, None)
+}
+
+// NOTE: POSIX shell style word splitting, covering what browsers and API docs
+// emit: single and double quotes, `$'...'` ANSI-C quotes, backslash escapes and
+// line continuations, both `\` and Windows `^` style.
+fn tokenize(command: &str) -> Result<Vec<String>, CurlParseError> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' | '^' if matches!(chars.peek(), Some('\n') | Some('\r')) => {
+                while matches!(chars.peek(), Some('\n') | Some('\r')) {
+                    chars.next();
+                }
+            }
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                    in_token = true;
+                }
+            }
+            '\'' => {
+                in_token = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => current.push(c),
+                        None => return Err(CurlParseError::UnterminatedQuote('\'')),
+                    }
+                }
+            }
+            '"' => {
+                in_token = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => current.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                current.push('\\');
+                                current.push(c);
+                            }
+                            None => return Err(CurlParseError::UnterminatedQuote('"')),
+                        },
+                        Some(c) => current.push(c),
+                        None => return Err(CurlParseError::UnterminatedQuote('"')),
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'\'') => {
+                chars.next();
+                in_token = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => current.push('\n'),
+                            Some('t') => current.push('\t'),
+                            Some('r') => current.push('\r'),
+                            Some('0') => current.push('\0'),
+                            Some('x') => {
+                                let hex = [chars.next(), chars.next()]
+                                    .into_iter()
+                                    .flatten()
+                                    .collect::<String>();
+                                match u8::from_str_radix(&hex, 16) {
+                                    Ok(byte) => current.push(byte as char),
+                                    Err(_) => {
+                                        current.push_str("\\x");
+                                        current.push_str(&hex);
+                                    }
+                                }
+                            }
+                            Some(c) => current.push(c),
+                            None => return Err(CurlParseError::UnterminatedQuote('\'')),
+                        },
+                        Some(c) => current.push(c),
+                        None => return Err(CurlParseError::UnterminatedQuote('\'')),
+                    }
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+
+    if in_token {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
@@ -0,0 +1,339 @@
+use dashmap::DashMap;
+use openssl::hash::{hash, MessageDigest};
+
+use crate::{
+    content,
+    error::{RelayError, Result},
+    integrity,
+    interop::{AuthType, ContentType, DigestAlgorithm, DigestQop, Request},
+    query,
+};
+
+lazy_static::lazy_static! {
+    static ref NONCE_COUNTS: DashMap<String, u32> = DashMap::new();
+}
+
+// NOTE: Nonces are kept only while in use, a server hands out a fresh one
+// when its old one goes stale, so forgetting them all past this many only
+// costs a fresh challenge for the ones still live.
+const MAX_TRACKED_NONCES: usize = 1024;
+
+// NOTE: The `nc` for the next request under `nonce`, counting up from 1 so
+// the server can tell a replayed request from a new one.
+fn next_nc(nonce: &str) -> String {
+    if NONCE_COUNTS.len() >= MAX_TRACKED_NONCES && !NONCE_COUNTS.contains_key(nonce) {
+        NONCE_COUNTS.clear();
+    }
+    let mut count = NONCE_COUNTS.entry(nonce.to_string()).or_insert(0);
+    *count = count.wrapping_add(1);
+    format!("{:08x}", *count)
+}
+
+fn unsupported(message: impl Into<String>) -> RelayError {
+    let message = message.into();
+    tracing::error!(message = %message, "Cannot answer digest challenge");
+    RelayError::UnsupportedFeature {
+        feature: "digest authentication".into(),
+        message,
+        relay: "curl".into(),
+    }
+}
+
+#[derive(Debug, Default)]
+struct Challenge {
+    scheme: String,
+    params: Vec<(String, String)>,
+}
+
+impl Challenge {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // NOTE: RFC 7616 sends one challenge per algorithm, some servers list
+    // them in one `algorithm` instead. `-sess` variants are not supported.
+    fn algorithms(&self) -> Vec<DigestAlgorithm> {
+        let offered = self.param("algorithm").unwrap_or("MD5");
+        offered
+            .split(',')
+            .filter_map(|name| match name.trim().to_ascii_uppercase().as_str() {
+                "MD5" => Some(DigestAlgorithm::Md5),
+                "SHA-256" => Some(DigestAlgorithm::Sha256),
+                "SHA-512-256" => Some(DigestAlgorithm::Sha512_256),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn qops(&self) -> Vec<DigestQop> {
+        self.param("qop")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|name| match name.trim().to_ascii_lowercase().as_str() {
+                "auth" => Some(DigestQop::Auth),
+                "auth-int" => Some(DigestQop::AuthInt),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+// NOTE: Commas separate both challenges and their parameters, and may sit
+// inside quoted values. An item without `=` before its first space starts a
+// new challenge, e.g. `Digest realm="api"`.
+fn parse(header: &str) -> Vec<Challenge> {
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in header.chars() {
+        match c {
+            _ if escaped => {
+                item.push(c);
+                escaped = false;
+            }
+            '\\' if quoted => escaped = true,
+            '"' => {
+                quoted = !quoted;
+                item.push(c);
+            }
+            ',' if !quoted => items.push(std::mem::take(&mut item)),
+            _ => item.push(c),
+        }
+    }
+    items.push(item);
+
+    let mut challenges: Vec<Challenge> = Vec::new();
+    for item in items
+        .iter()
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+    {
+        let param = match item.split_once(char::is_whitespace) {
+            Some((scheme, rest)) if !scheme.contains('=') => {
+                challenges.push(Challenge {
+                    scheme: scheme.to_string(),
+                    ..Default::default()
+                });
+                rest.trim()
+            }
+            None if !item.contains('=') => {
+                challenges.push(Challenge {
+                    scheme: item.to_string(),
+                    ..Default::default()
+                });
+                continue;
+            }
+            _ => item,
+        };
+        if let (Some(challenge), Some((name, value))) =
+            (challenges.last_mut(), param.split_once('='))
+        {
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+            challenge
+                .params
+                .push((name.trim().to_string(), value.to_string()));
+        }
+    }
+    challenges
+}
+
+fn strength(algorithm: &DigestAlgorithm) -> u8 {
+    match algorithm {
+        DigestAlgorithm::Md5 => 0,
+        DigestAlgorithm::Sha256 => 1,
+        DigestAlgorithm::Sha512 | DigestAlgorithm::Sha512_256 => 2,
+    }
+}
+
+fn wire_name(algorithm: &DigestAlgorithm) -> &'static str {
+    match algorithm {
+        DigestAlgorithm::Md5 => "MD5",
+        DigestAlgorithm::Sha256 => "SHA-256",
+        DigestAlgorithm::Sha512 => "SHA-512",
+        DigestAlgorithm::Sha512_256 => "SHA-512-256",
+    }
+}
+
+impl AuthType {
+    // NOTE: Answers the `WWW-Authenticate` of a `401` with the strongest
+    // algorithm it offers, SHA-512-256 over SHA-256 over MD5, and `auth-int`
+    // only when the request has a body to protect. Fills in the challenge
+    // fields of `Digest` credentials, which relay then sends up front
+    // instead of waiting for curl to be challenged.
+    pub fn negotiate_digest(&mut self, www_authenticate: &str, has_body: bool) -> Result<()> {
+        let AuthType::Digest {
+            realm,
+            nonce,
+            opaque,
+            algorithm,
+            qop,
+            nc,
+            cnonce,
+            ..
+        } = self
+        else {
+            return Err(unsupported(
+                "only digest credentials answer a digest challenge",
+            ));
+        };
+
+        let (challenge, chosen) = parse(www_authenticate)
+            .into_iter()
+            .filter(|challenge| challenge.scheme.eq_ignore_ascii_case("digest"))
+            .filter(|challenge| challenge.param("nonce").is_some())
+            .flat_map(|challenge| {
+                challenge
+                    .algorithms()
+                    .into_iter()
+                    .map(|algorithm| (strength(&algorithm), algorithm))
+                    .max_by_key(|(strength, _)| *strength)
+                    .map(|chosen| (challenge, chosen))
+            })
+            .max_by_key(|(_, (strength, _))| *strength)
+            .ok_or_else(|| unsupported("no digest challenge with a supported algorithm"))?;
+
+        let offered = challenge.qops();
+        let chosen_qop = if has_body && offered.contains(&DigestQop::AuthInt) {
+            Some(DigestQop::AuthInt)
+        } else if offered.contains(&DigestQop::Auth) {
+            Some(DigestQop::Auth)
+        } else {
+            offered.into_iter().next()
+        };
+
+        tracing::debug!(
+            algorithm = wire_name(&chosen.1),
+            qop = ?chosen_qop,
+            "Negotiated digest challenge"
+        );
+        *realm = challenge.param("realm").map(str::to_string);
+        *nonce = challenge.param("nonce").map(str::to_string);
+        *opaque = challenge.param("opaque").map(str::to_string);
+        *algorithm = Some(chosen.1);
+        *qop = chosen_qop;
+        *nc = None;
+        *cnonce = None;
+        Ok(())
+    }
+}
+
+fn digest_hex(algorithm: &DigestAlgorithm, data: &[u8]) -> Result<String> {
+    let digest = match algorithm {
+        DigestAlgorithm::Md5 => MessageDigest::md5(),
+        DigestAlgorithm::Sha256 => MessageDigest::sha256(),
+        DigestAlgorithm::Sha512 => MessageDigest::sha512(),
+        DigestAlgorithm::Sha512_256 => MessageDigest::from_name("SHA512-256")
+            .ok_or_else(|| unsupported("OpenSSL does not provide SHA-512-256"))?,
+    };
+    hash(digest, data)
+        .map(|bytes| integrity::hex(&bytes))
+        .map_err(|e| RelayError::Parse {
+            message: "Failed to compute digest response".into(),
+            cause: Some(e.into()),
+            partial: None,
+        })
+}
+
+fn quoted(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// NOTE: The `Authorization` for digest credentials whose challenge is known,
+// `None` while there is no nonce and curl answers the challenge itself.
+pub(crate) fn authorization(request: &Request) -> Result<Option<(String, String)>> {
+    let Some(AuthType::Digest {
+        ref username,
+        ref password,
+        ref realm,
+        nonce: Some(ref nonce),
+        ref opaque,
+        ref algorithm,
+        ref qop,
+        ref nc,
+        ref cnonce,
+    }) = request.auth
+    else {
+        return Ok(None);
+    };
+
+    let algorithm = algorithm.clone().unwrap_or(DigestAlgorithm::Md5);
+    let realm = realm.as_deref().unwrap_or_default();
+    let wire_url = query::wire_url(request)?;
+    let url = url::Url::parse(&query::with_default_scheme(&wire_url))
+        .map_err(|e| unsupported(format!("invalid URL: {}", e)))?;
+    let uri = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+
+    let ha1 = digest_hex(
+        &algorithm,
+        format!("{}:{}:{}", username, realm, password).as_bytes(),
+    )?;
+    let ha2 = match qop {
+        Some(DigestQop::AuthInt) => {
+            let body = match request.content {
+                Some(ContentType::Form { .. } | ContentType::Multipart { .. }) => {
+                    return Err(unsupported(
+                        "form and multipart bodies are encoded by curl and cannot be covered by auth-int",
+                    ))
+                }
+                Some(ref content) => content::body_bytes(content).unwrap_or_default(),
+                None => Vec::new(),
+            };
+            let body_hash = digest_hex(&algorithm, &body)?;
+            digest_hex(
+                &algorithm,
+                format!("{}:{}:{}", request.method, uri, body_hash).as_bytes(),
+            )?
+        }
+        _ => digest_hex(&algorithm, format!("{}:{}", request.method, uri).as_bytes())?,
+    };
+
+    let mut fields = vec![
+        format!("username={}", quoted(username)),
+        format!("realm={}", quoted(realm)),
+        format!("nonce={}", quoted(nonce)),
+        format!("uri={}", quoted(&uri)),
+        format!("algorithm={}", wire_name(&algorithm)),
+    ];
+    let response = match qop {
+        Some(qop) => {
+            let qop = match qop {
+                DigestQop::Auth => "auth",
+                DigestQop::AuthInt => "auth-int",
+            };
+            // NOTE: An `nc` given with the credentials is sent as is, the
+            // caller is then counting.
+            let nc = nc.clone().unwrap_or_else(|| next_nc(nonce));
+            let cnonce = cnonce
+                .clone()
+                .unwrap_or_else(|| integrity::hex(&rand::random::<[u8; 16]>()));
+            fields.push(format!("qop={}", qop));
+            fields.push(format!("nc={}", nc));
+            fields.push(format!("cnonce={}", quoted(&cnonce)));
+            digest_hex(
+                &algorithm,
+                format!("{}:{}:{}:{}:{}:{}", ha1, nonce, nc, cnonce, qop, ha2).as_bytes(),
+            )?
+        }
+        None => digest_hex(&algorithm, format!("{}:{}:{}", ha1, nonce, ha2).as_bytes())?,
+    };
+    fields.push(format!("response={}", quoted(&response)));
+    if let Some(opaque) = opaque {
+        fields.push(format!("opaque={}", quoted(opaque)));
+    }
+
+    Ok(Some((
+        "Authorization".to_string(),
+        format!("Digest {}", fields.join(", ")),
+    )))
+}
@@ -0,0 +1,544 @@
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use dashmap::DashMap;
+
+use crate::{
+    client::RelayClient,
+    error::{Cause, RelayError, Result},
+    integrity,
+    interop::{BodyHash, ByteRange, ContentRange, Progress, Request},
+    transfer::ProgressCallback,
+};
+
+lazy_static::lazy_static! {
+    static ref TARGETS: DashMap<i64, Arc<Target>> = DashMap::new();
+}
+
+const PART_SUFFIX: &str = ".part";
+
+// NOTE: Next to the partial file, the `ETag` or `Last-Modified` of the
+// response it came from, sent back as `If-Range` when resuming.
+const VALIDATOR_SUFFIX: &str = ".validator";
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Clone)]
+pub struct DownloadOptions {
+    resume: bool,
+    checksum: Option<BodyHash>,
+    progress: Option<ProgressCallback>,
+    headers: Vec<(String, String)>,
+}
+
+impl std::fmt::Debug for DownloadOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DownloadOptions")
+            .field("resume", &self.resume)
+            .field("checksum", &self.checksum)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            resume: true,
+            checksum: None,
+            progress: None,
+            headers: Vec::new(),
+        }
+    }
+}
+
+impl DownloadOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // NOTE: On by default. Off, a partial file left by an earlier attempt is
+    // discarded and the download starts over.
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    // NOTE: Checked against the whole file, resumed bytes included, before it
+    // is moved into place. A mismatch deletes the partial file.
+    pub fn with_checksum(mut self, checksum: BodyHash) -> Self {
+        self.checksum = Some(checksum);
+        self
+    }
+
+    // NOTE: `downloaded` counts the bytes already on disk from an earlier
+    // attempt, `download_total` is the full size, `0` when the server did not
+    // say.
+    pub fn with_progress<F>(mut self, progress: F) -> Self
+    where
+        F: Fn(&Progress) + Send + Sync + 'static,
+    {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadResult {
+    pub path: PathBuf,
+    // NOTE: Size of the finished file.
+    pub size: u64,
+    // NOTE: Bytes kept from an earlier attempt, `0` when the download started
+    // from scratch, including when the server ignored the range.
+    pub resumed_from: u64,
+    // NOTE: Bytes received by this call.
+    pub downloaded: u64,
+    // NOTE: Hex digest of the file, set when a checksum was given.
+    pub digest: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    file: Option<File>,
+    seen: bool,
+    taken: bool,
+    complete: bool,
+    start: u64,
+    written: u64,
+    total: Option<u64>,
+    failure: Option<io::Error>,
+}
+
+// NOTE: Where the transfer writes a download's body instead of memory. Each
+// response's headers go through `start`, which decides whether its body is
+// the file's, an error page is left to be buffered as usual.
+pub(crate) struct Target {
+    part: PathBuf,
+    validator: PathBuf,
+    offset: u64,
+    progress: Option<ProgressCallback>,
+    state: Mutex<State>,
+}
+
+impl std::fmt::Debug for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Target")
+            .field("part", &self.part)
+            .field("offset", &self.offset)
+            .finish_non_exhaustive()
+    }
+}
+
+fn header<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl Target {
+    fn new(part: PathBuf, offset: u64, progress: Option<ProgressCallback>) -> Self {
+        Self {
+            validator: validator_path(&part),
+            part,
+            offset,
+            progress,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // NOTE: A `206` has to pick up exactly where the partial file ends, a
+    // `200` replaces it, and a `416` whose complete length is the partial
+    // file's size means there was nothing left to fetch. The file is only
+    // touched once body bytes arrive, a proxy's `200` to `CONNECT` goes
+    // through here too.
+    pub(crate) fn start(&self, status: u16, headers: &HashMap<String, String>) -> io::Result<bool> {
+        let mut state = self.state();
+        state.seen = true;
+        state.taken = false;
+        state.file = None;
+
+        let content_range =
+            header(headers, "content-range").and_then(|value| value.parse::<ContentRange>().ok());
+        match status {
+            200 => {
+                state.start = 0;
+                state.total =
+                    header(headers, "content-length").and_then(|length| length.trim().parse().ok());
+                // NOTE: Weak tags cannot be used with `If-Range`, a file
+                // without a validator is downloaded whole next time.
+                match header(headers, "etag")
+                    .filter(|tag| !tag.trim_start().starts_with("W/"))
+                    .or_else(|| header(headers, "last-modified"))
+                {
+                    Some(validator) => fs::write(&self.validator, validator.trim())?,
+                    None => remove_if_present(&self.validator)?,
+                }
+            }
+            206 => {
+                let range = content_range.ok_or_else(|| {
+                    invalid("partial response without a single Content-Range".to_string())
+                })?;
+                if range.start != Some(self.offset) {
+                    return Err(invalid(format!(
+                        "server resumed at byte {:?} instead of {}",
+                        range.start, self.offset
+                    )));
+                }
+                state.start = self.offset;
+                state.total = range.complete_length;
+            }
+            416 => {
+                state.complete = self.offset > 0
+                    && content_range.and_then(|range| range.complete_length) == Some(self.offset);
+                return Ok(false);
+            }
+            _ => return Ok(false),
+        }
+
+        tracing::debug!(
+            part = %self.part.display(),
+            status = status,
+            start = state.start,
+            total = ?state.total,
+            "Streaming download to file"
+        );
+        state.written = 0;
+        state.taken = true;
+        Ok(true)
+    }
+
+    fn open(&self, state: &mut State) -> io::Result<()> {
+        if state.file.is_some() {
+            return Ok(());
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&self.part)?;
+        file.set_len(state.start)?;
+        file.seek(SeekFrom::End(0))?;
+        state.file = Some(file);
+        Ok(())
+    }
+
+    pub(crate) fn write(&self, data: &[u8]) -> io::Result<()> {
+        let mut state = self.state();
+        self.open(&mut state)?;
+        if let Some(ref mut file) = state.file {
+            file.write_all(data)?;
+        }
+        state.written += data.len() as u64;
+
+        if let Some(ref progress) = self.progress {
+            progress(&Progress {
+                uploaded: 0,
+                upload_total: 0,
+                downloaded: state.start + state.written,
+                download_total: state.total.unwrap_or_default(),
+                part: None,
+            });
+        }
+        Ok(())
+    }
+
+    // NOTE: Where the file starts and how much was written to it, `None`
+    // when the response's body was not the download's.
+    fn finish(&self) -> io::Result<Option<(u64, u64)>> {
+        let mut state = self.state();
+        if !state.taken {
+            return Ok(None);
+        }
+        self.open(&mut state)?;
+        if let Some(file) = state.file.take() {
+            file.sync_all()?;
+        }
+        Ok(Some((state.start, state.written)))
+    }
+
+    // NOTE: Kept for after the transfer, curl only learns that a callback
+    // gave up, not why.
+    pub(crate) fn fail(&self, error: io::Error) {
+        tracing::error!(part = %self.part.display(), error = %error, "Failed to write download");
+        self.state().failure = Some(error);
+    }
+
+    pub(crate) fn take_failure(&self) -> Option<RelayError> {
+        self.state().failure.take().map(|e| RelayError::Io {
+            message: format!("Failed to write download to {}", self.part.display()),
+            cause: Some(e.into()),
+        })
+    }
+}
+
+pub(crate) struct Registration {
+    request_id: i64,
+    target: Arc<Target>,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        TARGETS.remove_if(&self.request_id, |_, target| {
+            Arc::ptr_eq(target, &self.target)
+        });
+    }
+}
+
+fn register(request_id: i64, target: Arc<Target>) -> Registration {
+    TARGETS.insert(request_id, Arc::clone(&target));
+    Registration { request_id, target }
+}
+
+pub(crate) fn target(request_id: i64) -> Option<Arc<Target>> {
+    if TARGETS.is_empty() {
+        return None;
+    }
+    TARGETS
+        .get(&request_id)
+        .map(|target| Arc::clone(target.value()))
+}
+
+fn part_path(dest: &Path) -> PathBuf {
+    let mut part = OsString::from(dest.as_os_str());
+    part.push(PART_SUFFIX);
+    PathBuf::from(part)
+}
+
+fn validator_path(part: &Path) -> PathBuf {
+    let mut validator = OsString::from(part.as_os_str());
+    validator.push(VALIDATOR_SUFFIX);
+    PathBuf::from(validator)
+}
+
+fn remove_if_present(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+// NOTE: File system work that can take a while, e.g. hashing or syncing a
+// large file, runs on tokio's blocking pool instead of the runtime's thread.
+async fn blocking<T, F>(what: &str, work: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .unwrap_or_else(|e| match e.try_into_panic() {
+            Ok(panic) => std::panic::resume_unwind(panic),
+            Err(e) => Err(RelayError::Io {
+                message: format!("{} was cancelled", what),
+                cause: Some(Cause::new(e)),
+            }),
+        })
+}
+
+fn io_error(message: String, e: io::Error) -> RelayError {
+    tracing::error!(error = %e, message = %message, "Download failed");
+    RelayError::Io {
+        message,
+        cause: Some(e.into()),
+    }
+}
+
+// NOTE: Hashes the file in chunks, a download may not fit in memory.
+fn file_digest(path: &Path, checksum: &BodyHash) -> Result<Vec<u8>> {
+    let mut hasher = integrity::hasher(checksum)?;
+    let mut file =
+        File::open(path).map_err(|e| io_error(format!("Failed to open {}", path.display()), e))?;
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| io_error(format!("Failed to read {}", path.display()), e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]).map_err(|e| RelayError::Io {
+            message: "Failed to hash download".into(),
+            cause: Some(e.into()),
+        })?;
+    }
+    hasher
+        .finish()
+        .map(|digest| digest.to_vec())
+        .map_err(|e| RelayError::Io {
+            message: "Failed to finish download hash".into(),
+            cause: Some(e.into()),
+        })
+}
+
+impl RelayClient {
+    // NOTE: Fetches `url` into `dest` without holding the body in memory.
+    // The body lands in `dest` with `.part` appended and is renamed over
+    // `dest` once complete and verified, so `dest` never holds a partial
+    // file. A `.part` left by an interrupted call is resumed with a `Range`
+    // request, conditional on `If-Range` so a changed file starts over. Goes
+    // through the client's interceptors and defaults, but not
+    // its cache or retries, calling `download` again is the retry.
+    #[tracing::instrument(skip(self, url, options), fields(dest = %dest.as_ref().display()), level = "debug")]
+    pub async fn download(
+        &self,
+        url: impl Into<String>,
+        dest: impl AsRef<Path>,
+        options: DownloadOptions,
+    ) -> Result<DownloadResult> {
+        let dest = dest.as_ref();
+        let part = part_path(dest);
+
+        let validator_file = validator_path(&part);
+        let validator = match options.resume {
+            true => tokio::fs::read_to_string(&validator_file).await.ok(),
+            false => None,
+        };
+        // NOTE: Without a validator there is no telling whether the partial
+        // file is of the version the server has now.
+        let offset = match validator {
+            Some(_) => tokio::fs::metadata(&part)
+                .await
+                .map(|metadata| metadata.len())
+                .unwrap_or(0),
+            None => 0,
+        };
+
+        let mut builder = Request::builder().get(url);
+        for (name, value) in options.headers {
+            builder = builder.header(name, value);
+        }
+        let mut request = builder.build()?;
+        request.ensure_id();
+        if let Some(validator) = validator.filter(|_| offset > 0) {
+            tracing::info!(offset = offset, part = %part.display(), "Resuming download");
+            request.range = Some(ByteRange::from_start(offset));
+            request
+                .headers
+                .get_or_insert_with(Default::default)
+                .insert("If-Range", validator);
+        }
+
+        let target = Arc::new(Target::new(part.clone(), offset, options.progress));
+        let registration = register(request.id, Arc::clone(&target));
+        #[cfg(feature = "metrics")]
+        let started = std::time::Instant::now();
+        let result = self.send(&request, None).await;
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_request(&request.method, &request.url, &result, started.elapsed());
+        let response = result?;
+        drop(registration);
+
+        // NOTE: A transport other than curl's hands the body back in memory,
+        // it is written out the same way.
+        if !target.state().seen {
+            let streaming = target
+                .start(response.status.as_u16(), &response.headers)
+                .and_then(|streaming| match streaming {
+                    true => target.write(&response.body.body),
+                    false => Ok(()),
+                });
+            if let Err(e) = streaming {
+                return Err(io_error(
+                    format!("Failed to write download to {}", part.display()),
+                    e,
+                ));
+            }
+        }
+
+        let finished = {
+            let (target, part) = (Arc::clone(&target), part.clone());
+            blocking("Flushing the download", move || {
+                target
+                    .finish()
+                    .map_err(|e| io_error(format!("Failed to flush {}", part.display()), e))
+            })
+            .await?
+        };
+        let (resumed_from, downloaded) = match finished {
+            Some(written) => written,
+            None if target.state().complete => {
+                tracing::debug!("Partial file already holds the whole download");
+                (offset, 0)
+            }
+            None => {
+                tracing::error!(status = %response.status, "Download failed with HTTP status");
+                return Err(RelayError::HttpStatus {
+                    status: response.status,
+                    headers: response.headers,
+                    body: response.body.body,
+                });
+            }
+        };
+
+        let digest = match options.checksum {
+            Some(ref checksum) => {
+                let digest = {
+                    let (part, checksum) = (part.clone(), checksum.clone());
+                    blocking("Hashing the download", move || {
+                        file_digest(&part, &checksum)
+                    })
+                    .await?
+                };
+                if let Err(e) = integrity::verify(checksum, &digest) {
+                    if let Err(e) = tokio::fs::remove_file(&part).await {
+                        tracing::warn!(error = %e, "Failed to remove corrupt partial download");
+                    }
+                    let _ = tokio::fs::remove_file(&validator_file).await;
+                    return Err(e);
+                }
+                Some(integrity::hex(&digest))
+            }
+            None => None,
+        };
+
+        tokio::fs::rename(&part, dest)
+            .await
+            .map_err(|e| io_error(format!("Failed to move {} into place", part.display()), e))?;
+        if let Err(e) = tokio::fs::remove_file(&validator_file).await {
+            if e.kind() != io::ErrorKind::NotFound {
+                tracing::warn!(error = %e, "Failed to remove download validator");
+            }
+        }
+        let size = tokio::fs::metadata(dest)
+            .await
+            .map(|metadata| metadata.len())
+            .map_err(|e| io_error(format!("Failed to read {}", dest.display()), e))?;
+
+        tracing::info!(
+            dest = %dest.display(),
+            size = size,
+            resumed_from = resumed_from,
+            downloaded = downloaded,
+            "Download complete"
+        );
+        Ok(DownloadResult {
+            path: dest.to_path_buf(),
+            size,
+            resumed_from,
+            downloaded,
+            digest,
+        })
+    }
+}
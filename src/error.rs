@@ -1,61 +1,728 @@
-use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, error::Error, fmt, sync::Arc};
+
+use bytes::Bytes;
+use http::StatusCode;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use thiserror::Error;
 
-#[derive(Debug, Error, Serialize, Deserialize)]
+use crate::{
+    interop::{ErrorCode, ErrorPayload, HashAlgorithm, PartialResponse, ValidationIssue},
+    util::{CURLE_HTTP3, CURLE_QUIC_CONNECT_ERROR},
+};
+
+// NOTE: curl failures that may pass on another attempt. Other codes behind a
+// `Network` error, such as a rejected option, fail the same way every time.
+const TRANSIENT_CURL_CODES: &[curl_sys::CURLcode] = &[
+    curl_sys::CURLE_HTTP2,
+    curl_sys::CURLE_HTTP2_STREAM,
+    curl_sys::CURLE_AGAIN,
+    CURLE_HTTP3,
+    CURLE_QUIC_CONNECT_ERROR,
+];
+
+// NOTE: What caused a `RelayError`. Serializes as the plain message, while the
+// typed error it came from stays available on the Rust side through
+// `Error::source` and can be downcast, e.g. to `curl::Error`.
+#[derive(Debug, Clone)]
+pub struct Cause {
+    message: String,
+    error: Option<Arc<dyn Error + Send + Sync>>,
+}
+
+impl Cause {
+    pub fn new<E: Error + Send + Sync + 'static>(error: E) -> Self {
+        Self {
+            message: error.to_string(),
+            error: Some(Arc::new(error)),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn error(&self) -> Option<&(dyn Error + Send + Sync + 'static)> {
+        self.error.as_deref()
+    }
+
+    fn as_source(&self) -> &(dyn Error + 'static) {
+        match self.error {
+            Some(ref error) => error.as_ref(),
+            None => self,
+        }
+    }
+
+    // NOTE: Folds the typed error's own sources into the message, which is all
+    // that survives serialization.
+    fn flatten(&self) -> Self {
+        let mut message = self.message.clone();
+        let mut source = self.error().and_then(|error| error.source());
+        while let Some(error) = source {
+            message.push_str(": ");
+            message.push_str(&error.to_string());
+            source = error.source();
+        }
+        Self {
+            message,
+            error: None,
+        }
+    }
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for Cause {}
+
+impl From<String> for Cause {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            error: None,
+        }
+    }
+}
+
+impl From<&str> for Cause {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+macro_rules! cause_from {
+    ($($error:ty),* $(,)?) => {
+        $(
+            impl From<$error> for Cause {
+                fn from(error: $error) -> Self {
+                    Self::new(error)
+                }
+            }
+        )*
+    };
+}
+
+cause_from!(
+    curl::Error,
+    curl::FormError,
+    std::io::Error,
+    std::string::FromUtf8Error,
+    std::char::DecodeUtf16Error,
+    serde_json::Error,
+    url::ParseError,
+    http::method::InvalidMethod,
+    openssl::error::ErrorStack,
+    CurlParseError,
+    regex::Error,
+);
+
+impl Serialize for Cause {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.message)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cause {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for Cause {
+    fn schema_name() -> String {
+        String::schema_name()
+    }
+
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        String::json_schema(gen)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "kind", rename_all = "snake_case")]
 pub enum RelayError {
-    #[error("Unsupported feature '{feature}' in relay '{relay}': {message}")]
+    Validation {
+        message: String,
+        issues: Vec<ValidationIssue>,
+    },
+
     UnsupportedFeature {
         feature: String,
         message: String,
         relay: String,
     },
 
-    #[error("Network error: {message}")]
+    InvalidHeader {
+        name: String,
+        reason: String,
+    },
+
     Network {
         message: String,
         #[serde(skip_serializing_if = "Option::is_none")]
-        cause: Option<String>,
+        cause: Option<Cause>,
+    },
+
+    DnsResolution {
+        host: String,
+        message: Cause,
+        code: i64,
+    },
+
+    ConnectionRefused {
+        addr: String,
+        message: Cause,
+        code: i64,
+    },
+
+    ConnectionReset {
+        message: Cause,
+        code: i64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        partial: Option<Box<PartialResponse>>,
     },
 
-    #[error("Request timed out during {}", .phase.as_ref().map_or("execution", TimeoutPhase::as_str))]
     Timeout {
-        message: String,
+        message: Cause,
         phase: Option<TimeoutPhase>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        code: Option<i64>,
+        // NOTE: `elapsed_ms` is measured against the limit that was hit, so
+        // for `FirstByte` it counts from when the request was sent.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        elapsed_ms: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        limit_ms: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        partial: Option<Box<PartialResponse>>,
+        // NOTE: curl's debug trace of the transfer, redacted as it is for
+        // logging, when the request set `captureTrace`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        trace: Option<Vec<String>>,
+    },
+
+    TlsHandshake {
+        detail: String,
+        message: Cause,
+        code: i64,
+    },
+
+    // NOTE: `chain` lists the URLs requested in order, ending with the
+    // redirect that was not followed, or with the URL that closed a loop.
+    TooManyRedirects {
+        message: Cause,
+        code: i64,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        chain: Vec<String>,
+    },
+
+    UnsupportedProtocol {
+        message: Cause,
+        code: i64,
+    },
+
+    ProxyError {
+        message: Cause,
+        code: i64,
+    },
+
+    // NOTE: A reply curl could not make sense of as HTTP at all, `received`
+    // holds the first bytes of it (lossily decoded) to show what came back.
+    MalformedResponse {
+        message: Cause,
+        code: i64,
+        received: String,
+    },
+
+    ResponseTime {
+        message: String,
+        elapsed_ms: u64,
+        limit_ms: u64,
+    },
+
+    // NOTE: `actual` is the hex digest of the body received.
+    BodyHashMismatch {
+        algorithm: HashAlgorithm,
+        expected: String,
+        actual: String,
+    },
+
+    HttpStatus {
+        #[serde(with = "http_serde::status_code")]
+        #[cfg_attr(feature = "schema", schemars(with = "u16"))]
+        status: StatusCode,
+        // NOTE: Folded the same way as `Response.headers`.
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        headers: HashMap<String, String>,
+        body: Bytes,
     },
 
-    #[error("Certificate error: {message}")]
     Certificate {
         message: String,
         #[serde(skip_serializing_if = "Option::is_none")]
-        cause: Option<String>,
+        cause: Option<Cause>,
     },
 
-    #[error("Failed to parse response: {message}")]
     Parse {
         message: String,
         #[serde(skip_serializing_if = "Option::is_none")]
-        cause: Option<String>,
+        cause: Option<Cause>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        partial: Option<Box<PartialResponse>>,
+    },
+
+    Io {
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cause: Option<Cause>,
     },
 
-    #[error("Request aborted: {message}")]
-    Abort { message: String },
+    Abort {
+        message: String,
+    },
+
+    // NOTE: `target` is the host or address the rule refused.
+    PolicyViolation {
+        rule: PolicyRule,
+        target: String,
+        message: String,
+    },
+
+    Interceptor {
+        name: String,
+        error: Box<RelayError>,
+    },
+}
+
+// NOTE: Causes are left to `source()` rather than repeated here, so reports
+// walking the chain (`anyhow`, `eyre`) print each level once. Variants that
+// say nothing beyond their kind keep curl's message, it is all a host
+// showing only this level would have to go on.
+impl fmt::Display for RelayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelayError::Validation { message, .. } => write!(f, "Invalid request: {}", message),
+            RelayError::UnsupportedFeature {
+                feature,
+                message,
+                relay,
+            } => write!(
+                f,
+                "Unsupported feature '{}' in relay '{}': {}",
+                feature, relay, message
+            ),
+            RelayError::InvalidHeader { name, reason } => {
+                write!(f, "Invalid header '{}': {}", name, reason)
+            }
+            RelayError::Network { message, .. } => write!(f, "Network error: {}", message),
+            RelayError::DnsResolution { host, .. } => {
+                write!(f, "Could not resolve host '{}'", host)
+            }
+            RelayError::ConnectionRefused { addr, .. } => {
+                write!(f, "Connection refused by {}", addr)
+            }
+            RelayError::ConnectionReset { message, .. } => {
+                write!(f, "Connection reset: {}", message)
+            }
+            RelayError::Timeout {
+                phase,
+                elapsed_ms,
+                limit_ms,
+                ..
+            } => {
+                write!(
+                    f,
+                    "Request timed out during {}",
+                    phase.as_ref().map_or("execution", TimeoutPhase::as_str)
+                )?;
+                match (elapsed_ms, limit_ms) {
+                    (Some(elapsed), Some(limit)) => {
+                        write!(f, " after {}ms (limit {}ms)", elapsed, limit)
+                    }
+                    (Some(elapsed), None) => write!(f, " after {}ms", elapsed),
+                    (None, Some(limit)) => write!(f, " (limit {}ms)", limit),
+                    (None, None) => Ok(()),
+                }
+            }
+            RelayError::TlsHandshake { detail, .. } => {
+                write!(f, "TLS handshake failed: {}", detail)
+            }
+            RelayError::TooManyRedirects { message, .. } => {
+                write!(f, "Too many redirects: {}", message)
+            }
+            RelayError::UnsupportedProtocol { message, .. } => {
+                write!(f, "Unsupported protocol: {}", message)
+            }
+            RelayError::ProxyError { message, .. } => write!(f, "Proxy error: {}", message),
+            RelayError::MalformedResponse { received, .. } => {
+                write!(f, "Malformed response from server: {:?}", received)
+            }
+            RelayError::ResponseTime { message, .. } => {
+                write!(f, "Response time limit exceeded: {}", message)
+            }
+            RelayError::BodyHashMismatch {
+                algorithm,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Response body {} is {}, expected {}",
+                algorithm.as_str(),
+                actual,
+                expected
+            ),
+            RelayError::HttpStatus { status, .. } => write!(f, "HTTP error: {}", status),
+            RelayError::Certificate { message, .. } => write!(f, "Certificate error: {}", message),
+            RelayError::Parse { message, .. } => {
+                write!(f, "Failed to parse response: {}", message)
+            }
+            RelayError::Io { message, .. } => write!(f, "I/O error: {}", message),
+            RelayError::Abort { message } => write!(f, "Request aborted: {}", message),
+            RelayError::PolicyViolation { rule, message, .. } => {
+                write!(f, "Blocked by {} policy: {}", rule.as_str(), message)
+            }
+            RelayError::Interceptor { name, .. } => write!(f, "Interceptor '{}' failed", name),
+        }
+    }
+}
+
+impl Error for RelayError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RelayError::Network { cause, .. }
+            | RelayError::Certificate { cause, .. }
+            | RelayError::Parse { cause, .. }
+            | RelayError::Io { cause, .. } => cause.as_ref().map(Cause::as_source),
+            RelayError::DnsResolution { message, .. }
+            | RelayError::ConnectionRefused { message, .. }
+            | RelayError::ConnectionReset { message, .. }
+            | RelayError::Timeout { message, .. }
+            | RelayError::TlsHandshake { message, .. }
+            | RelayError::TooManyRedirects { message, .. }
+            | RelayError::UnsupportedProtocol { message, .. }
+            | RelayError::ProxyError { message, .. }
+            | RelayError::MalformedResponse { message, .. } => Some(message.as_source()),
+            RelayError::Interceptor { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl RelayError {
+    // NOTE: Failures that may well succeed on a second attempt, the server or
+    // network was unavailable rather than the request being wrong.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            RelayError::Network { cause, .. } => cause
+                .as_ref()
+                .and_then(Cause::error)
+                .and_then(|error| error.downcast_ref::<curl::Error>())
+                .is_some_and(|error| TRANSIENT_CURL_CODES.contains(&error.code())),
+            RelayError::Timeout { .. }
+            | RelayError::DnsResolution { .. }
+            | RelayError::ConnectionRefused { .. }
+            | RelayError::ConnectionReset { .. } => true,
+            _ => false,
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            RelayError::Validation { .. } => ErrorCode::Validation,
+            RelayError::UnsupportedFeature { .. } => ErrorCode::UnsupportedFeature,
+            RelayError::InvalidHeader { .. } => ErrorCode::InvalidHeader,
+            RelayError::Network { .. } => ErrorCode::Network,
+            RelayError::DnsResolution { .. } => ErrorCode::DnsResolution,
+            RelayError::ConnectionRefused { .. } => ErrorCode::ConnectionRefused,
+            RelayError::ConnectionReset { .. } => ErrorCode::ConnectionReset,
+            RelayError::Timeout { .. } => ErrorCode::Timeout,
+            RelayError::TlsHandshake { .. } => ErrorCode::TlsHandshake,
+            RelayError::TooManyRedirects { .. } => ErrorCode::TooManyRedirects,
+            RelayError::UnsupportedProtocol { .. } => ErrorCode::UnsupportedProtocol,
+            RelayError::ProxyError { .. } => ErrorCode::ProxyError,
+            RelayError::MalformedResponse { .. } => ErrorCode::MalformedResponse,
+            RelayError::ResponseTime { .. } => ErrorCode::ResponseTime,
+            RelayError::BodyHashMismatch { .. } => ErrorCode::BodyHashMismatch,
+            RelayError::HttpStatus { .. } => ErrorCode::HttpStatus,
+            RelayError::Certificate { .. } => ErrorCode::Certificate,
+            RelayError::Parse { .. } => ErrorCode::Parse,
+            RelayError::Io { .. } => ErrorCode::Io,
+            RelayError::Abort { .. } => ErrorCode::Abort,
+            RelayError::PolicyViolation { .. } => ErrorCode::PolicyViolation,
+            RelayError::Interceptor { .. } => ErrorCode::Interceptor,
+        }
+    }
+
+    // NOTE: The variant name as serialized in `kind`, the same string as
+    // `code` serializes to.
+    pub fn kind(&self) -> &'static str {
+        self.code().as_str()
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, RelayError::Timeout { .. })
+    }
+
+    pub fn partial(&self) -> Option<&PartialResponse> {
+        match self {
+            RelayError::ConnectionReset { partial, .. }
+            | RelayError::Timeout { partial, .. }
+            | RelayError::Parse { partial, .. } => partial.as_deref(),
+            _ => None,
+        }
+    }
+
+    // NOTE: The form sent across the serde boundary, typed causes are folded
+    // into their message since only the message is serialized.
+    pub fn to_interop(&self) -> RelayError {
+        let mut error = self.clone();
+        match error {
+            RelayError::Network { ref mut cause, .. }
+            | RelayError::Certificate { ref mut cause, .. }
+            | RelayError::Parse { ref mut cause, .. }
+            | RelayError::Io { ref mut cause, .. } => {
+                if let Some(cause) = cause {
+                    *cause = cause.flatten();
+                }
+            }
+            RelayError::DnsResolution {
+                ref mut message, ..
+            }
+            | RelayError::ConnectionRefused {
+                ref mut message, ..
+            }
+            | RelayError::ConnectionReset {
+                ref mut message, ..
+            }
+            | RelayError::Timeout {
+                ref mut message, ..
+            }
+            | RelayError::TlsHandshake {
+                ref mut message, ..
+            }
+            | RelayError::TooManyRedirects {
+                ref mut message, ..
+            }
+            | RelayError::UnsupportedProtocol {
+                ref mut message, ..
+            }
+            | RelayError::ProxyError {
+                ref mut message, ..
+            }
+            | RelayError::MalformedResponse {
+                ref mut message, ..
+            } => *message = message.flatten(),
+            RelayError::Interceptor {
+                error: ref mut inner,
+                ..
+            } => **inner = inner.to_interop(),
+            _ => {}
+        }
+        error
+    }
+
+    // NOTE: Only variants a transfer can fail with mid-response carry partial
+    // data, for any other error this is a no-op.
+    pub(crate) fn attach_partial(&mut self, response: PartialResponse) {
+        match self {
+            RelayError::ConnectionReset { partial, .. }
+            | RelayError::Timeout { partial, .. }
+            | RelayError::Parse { partial, .. } => *partial = Some(Box::new(response)),
+            _ => {}
+        }
+    }
+
+    pub(crate) fn attach_trace(&mut self, lines: Vec<String>) {
+        if let RelayError::Timeout { trace, .. } = self {
+            *trace = Some(lines);
+        }
+    }
+
+    pub fn trace(&self) -> Option<&[String]> {
+        match self {
+            RelayError::Timeout {
+                trace: Some(trace), ..
+            } => Some(trace),
+            _ => None,
+        }
+    }
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::Validation => "validation",
+            ErrorCode::UnsupportedFeature => "unsupported_feature",
+            ErrorCode::InvalidHeader => "invalid_header",
+            ErrorCode::Network => "network",
+            ErrorCode::DnsResolution => "dns_resolution",
+            ErrorCode::ConnectionRefused => "connection_refused",
+            ErrorCode::ConnectionReset => "connection_reset",
+            ErrorCode::Timeout => "timeout",
+            ErrorCode::TlsHandshake => "tls_handshake",
+            ErrorCode::TooManyRedirects => "too_many_redirects",
+            ErrorCode::UnsupportedProtocol => "unsupported_protocol",
+            ErrorCode::ProxyError => "proxy_error",
+            ErrorCode::MalformedResponse => "malformed_response",
+            ErrorCode::ResponseTime => "response_time",
+            ErrorCode::BodyHashMismatch => "body_hash_mismatch",
+            ErrorCode::HttpStatus => "http_status",
+            ErrorCode::Certificate => "certificate",
+            ErrorCode::Parse => "parse",
+            ErrorCode::Io => "io",
+            ErrorCode::Abort => "abort",
+            ErrorCode::PolicyViolation => "policy_violation",
+            ErrorCode::Interceptor => "interceptor",
+        }
+    }
+}
+
+impl ErrorPayload {
+    // NOTE: For hosts that match on `code` rather than on `RelayError`'s
+    // variants, e.g. `result.map_err(|e| ErrorPayload::new(id, e))` on what
+    // any `execute` returns.
+    pub fn new(request_id: i64, error: RelayError) -> Self {
+        Self {
+            request_id: Some(request_id),
+            ..error.into()
+        }
+    }
+}
+
+impl From<RelayError> for ErrorPayload {
+    fn from(error: RelayError) -> Self {
+        let mut causes = Vec::new();
+        let mut source = error.source();
+        while let Some(cause) = source {
+            causes.push(cause.to_string());
+            source = cause.source();
+        }
+
+        let partial = error.partial().cloned();
+        let status = match error {
+            RelayError::HttpStatus { status, .. } => Some(status),
+            _ => partial.as_ref().and_then(|partial| partial.status),
+        };
+        let elapsed_ms = match error {
+            RelayError::Timeout {
+                elapsed_ms: Some(elapsed_ms),
+                ..
+            }
+            | RelayError::ResponseTime { elapsed_ms, .. } => Some(elapsed_ms),
+            _ => partial
+                .as_ref()
+                .map(|partial| partial.timing.end.saturating_sub(partial.timing.start)),
+        };
+
+        Self {
+            code: error.code(),
+            message: error.to_string(),
+            cause: (!causes.is_empty()).then(|| causes.join(": ")),
+            request_id: None,
+            retryable: error.is_retryable(),
+            status,
+            elapsed_ms,
+            timing: partial.as_ref().map(|partial| partial.timing.clone()),
+            partial,
+            trace: error.trace().map(<[String]>::to_vec),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum CurlParseError {
+    #[error("Empty curl command")]
+    Empty,
+
+    #[error("Not a curl command, expected it to start with 'curl' but found '{0}'")]
+    NotCurl(String),
+
+    #[error("Unterminated {0} quote in curl command")]
+    UnterminatedQuote(char),
+
+    #[error("Missing value for flag '{0}'")]
+    MissingValue(String),
+
+    #[error("No URL found in curl command")]
+    MissingUrl,
+
+    #[error("Invalid header '{0}', expected 'Name: value'")]
+    InvalidHeader(String),
+
+    #[error("Invalid value '{value}' for flag '{flag}'")]
+    InvalidValue { flag: String, value: String },
+
+    #[error("Cannot read '{path}' referenced by the curl command: {message}")]
+    UnreadableFile { path: String, message: String },
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnresolvedVariable {
+    pub name: String,
+    pub field: String,
+}
+
+#[derive(Debug, Clone, Error, PartialEq, Serialize, Deserialize)]
+#[error("Unresolved variables: {}", .variables.iter().map(|v| format!("{{{{{}}}}} in {}", v.name, v.field)).collect::<Vec<_>>().join(", "))]
+pub struct UnresolvedVars {
+    pub variables: Vec<UnresolvedVariable>,
+}
+
+// NOTE: Where a transfer stalled. `Response` is the overall timeout running
+// out with the request sent and nothing back yet, `FirstByte` the same wait
+// cut short by `first_byte_timeout`. `Body` means part of the response had
+// arrived.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum TimeoutPhase {
+    Dns,
     Connect,
     Tls,
     Response,
+    FirstByte,
+    Body,
 }
 
 impl TimeoutPhase {
     fn as_str(&self) -> &'static str {
         match self {
+            TimeoutPhase::Dns => "DNS resolution",
             TimeoutPhase::Connect => "connection establishment",
             TimeoutPhase::Tls => "TLS handshake",
             TimeoutPhase::Response => "response waiting",
+            TimeoutPhase::FirstByte => "first byte",
+            TimeoutPhase::Body => "body read",
+        }
+    }
+}
+
+// NOTE: The `TargetPolicy` rule a request broke.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyRule {
+    AllowHosts,
+    DenyHosts,
+    DenyIpRanges,
+    AllowSchemes,
+}
+
+impl PolicyRule {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            PolicyRule::AllowHosts => "allow_hosts",
+            PolicyRule::DenyHosts => "deny_hosts",
+            PolicyRule::DenyIpRanges => "deny_ip_ranges",
+            PolicyRule::AllowSchemes => "allow_schemes",
         }
     }
 }
@@ -67,4 +734,17 @@ pub enum RequestResult<T> {
     Error { error: RelayError },
 }
 
+// NOTE: The response carries its request's id, for failures that need one
+// use `ErrorPayload::new` instead.
+impl<T> From<Result<T>> for RequestResult<T> {
+    fn from(result: Result<T>) -> Self {
+        match result {
+            Ok(response) => RequestResult::Success { response },
+            Err(error) => RequestResult::Error {
+                error: error.to_interop(),
+            },
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, RelayError>;
@@ -0,0 +1,176 @@
+use std::{borrow::Cow, io};
+
+use crate::{
+    interop::{BodyEstimate, ContentType, FormValue, PartEstimate},
+    multipart,
+};
+
+// NOTE: curl picks a new random boundary for every form it sends, but always
+// of 24 dashes and 22 random characters, see `MIME_BOUNDARY_LEN` in curl's
+// `lib/mime.h`. Only its length matters for the size of the body.
+pub(crate) const FORM_BOUNDARY_LEN: usize = 46;
+
+// NOTE: curl escapes field names and filenames the way browsers do, see:
+// https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#multipart-form-data
+pub(crate) fn form_escape(value: &str) -> Cow<'_, str> {
+    if !value.contains(['"', '\r', '\n']) {
+        return Cow::Borrowed(value);
+    }
+    Cow::Owned(
+        value
+            .replace('"', "%22")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A"),
+    )
+}
+
+// NOTE: The headers curl writes for a form part, ending with the blank line.
+pub(crate) fn form_part_head(name: &str, value: &FormValue) -> String {
+    match value {
+        FormValue::Text { .. } => format!(
+            "Content-Disposition: form-data; name=\"{}\"\r\n\r\n",
+            form_escape(name)
+        ),
+        FormValue::File {
+            filename,
+            content_type,
+            ..
+        } => format!(
+            "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+            form_escape(name),
+            form_escape(filename),
+            content_type
+        ),
+    }
+}
+
+#[derive(Default)]
+struct ByteCounter(u64);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn json_len(value: &serde_json::Value) -> u64 {
+    let mut counter = ByteCounter::default();
+    // NOTE: Writing a `Value` to a sink that never fails cannot fail.
+    let _ = serde_json::to_writer(&mut counter, value);
+    counter.0
+}
+
+// NOTE: Parts come in with `total_bytes` set to their head and data, every
+// part then opens with `--boundary\r\n` and its data is followed by `\r\n`,
+// and the body ends with `--boundary--\r\n`.
+fn multipart_estimate(
+    boundary_len: usize,
+    parts: impl Iterator<Item = PartEstimate>,
+) -> BodyEstimate {
+    let boundary_line = (boundary_len + 4) as u64;
+    let mut estimate = BodyEstimate::default();
+
+    for mut part in parts {
+        part.offset = estimate.total_bytes;
+        part.total_bytes += boundary_line + 2;
+        estimate.total_bytes += part.total_bytes;
+        estimate.parts.push(part);
+    }
+
+    if !estimate.parts.is_empty() {
+        estimate.total_bytes += boundary_line + 2;
+    }
+    estimate
+}
+
+fn part(
+    name: String,
+    filename: Option<String>,
+    content_type: Option<String>,
+    head: String,
+    data_bytes: usize,
+) -> PartEstimate {
+    PartEstimate {
+        name,
+        filename,
+        content_type,
+        offset: 0,
+        data_bytes: data_bytes as u64,
+        total_bytes: (head.len() + data_bytes) as u64,
+    }
+}
+
+impl ContentType {
+    // NOTE: The number of bytes the body takes on the wire, which is what
+    // relay reports as its `Content-Length`, computed without encoding it.
+    // Multipart bodies are broken down by part, boundary lines and part
+    // headers included, e.g. to check parts against a server's upload limit.
+    // Urlencoded content is already encoded and is counted as given.
+    pub fn estimated_size(&self) -> BodyEstimate {
+        let total_bytes = match self {
+            ContentType::Text { content, .. }
+            | ContentType::Xml { content, .. }
+            | ContentType::Csv { content, .. }
+            | ContentType::Urlencoded { content, .. }
+            | ContentType::Json {
+                raw: Some(content), ..
+            } => content.len() as u64,
+            ContentType::Json { content, .. }
+            | ContentType::JsonMergePatch { content }
+            | ContentType::JsonPatch {
+                operations: content,
+            } => json_len(content),
+            ContentType::Binary { content, .. } => content.len() as u64,
+            ContentType::Form { content, .. } | ContentType::Multipart { content, .. } => {
+                return multipart_estimate(
+                    FORM_BOUNDARY_LEN,
+                    content.iter().flat_map(|(name, values)| {
+                        values.iter().map(move |value| {
+                            let head = form_part_head(name, value);
+                            match value {
+                                FormValue::Text { value } => {
+                                    part(name.clone(), None, None, head, value.len())
+                                }
+                                FormValue::File {
+                                    filename,
+                                    content_type,
+                                    data,
+                                } => part(
+                                    name.clone(),
+                                    Some(filename.clone()),
+                                    Some(content_type.to_string()),
+                                    head,
+                                    data.len(),
+                                ),
+                            }
+                        })
+                    }),
+                )
+            }
+            ContentType::MultipartRelated { root, parts, .. } => {
+                return multipart_estimate(
+                    multipart::RELATED_BOUNDARY_LEN,
+                    std::iter::once(root).chain(parts).map(|related| {
+                        part(
+                            multipart::bare_content_id(&related.content_id).to_string(),
+                            None,
+                            Some(related.content_type.clone()),
+                            related.head(),
+                            related.body.len(),
+                        )
+                    }),
+                )
+            }
+        };
+
+        BodyEstimate {
+            total_bytes,
+            parts: Vec::new(),
+        }
+    }
+}
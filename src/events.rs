@@ -0,0 +1,216 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use curl::easy::InfoType;
+use dashmap::DashMap;
+
+use crate::{
+    error::Result,
+    interop::{Response, TransferEvent, TransferEventKind},
+};
+
+// NOTE: Byte counts are reported each time they cross a multiple of this.
+const MILESTONE_BYTES: u64 = 64 * 1024;
+
+pub(crate) type EventListener = Arc<dyn Fn(&TransferEvent) + Send + Sync>;
+
+lazy_static::lazy_static! {
+    static ref LISTENERS: DashMap<i64, Arc<Emitter>> = DashMap::new();
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+enum Phase {
+    #[default]
+    Idle,
+    ResolvingDns,
+    Connecting,
+    TlsHandshake,
+    Sending,
+    Waiting,
+    Receiving,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    sequence: u64,
+    phase: Phase,
+    sent_milestones: u64,
+    received_milestones: u64,
+}
+
+// NOTE: Events are numbered and delivered under one lock, so they reach the
+// listener in order even when the client and the transfer thread both emit.
+// The listener should return quickly, the transfer waits on it.
+pub(crate) struct Emitter {
+    request_id: i64,
+    listener: EventListener,
+    state: Mutex<State>,
+}
+
+impl Emitter {
+    fn emit_locked(&self, state: &mut State, kind: TransferEventKind) {
+        let at = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|since| since.as_millis() as u64)
+            .unwrap_or_default();
+        let event = TransferEvent {
+            request_id: self.request_id,
+            sequence: state.sequence,
+            at,
+            kind,
+        };
+        state.sequence += 1;
+        tracing::trace!(sequence = event.sequence, kind = ?event.kind, "Transfer event");
+        (self.listener)(&event);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn emit(&self, kind: TransferEventKind) {
+        let mut state = self.lock();
+        self.emit_locked(&mut state, kind);
+    }
+
+    fn enter(&self, state: &mut State, phase: Phase, kind: TransferEventKind) {
+        state.phase = phase;
+        self.emit_locked(state, kind);
+    }
+
+    // NOTE: Called as each transfer, i.e. each attempt, begins.
+    pub(crate) fn transfer_started(&self) {
+        let mut state = self.lock();
+        state.sent_milestones = 0;
+        state.received_milestones = 0;
+        self.enter(
+            &mut state,
+            Phase::ResolvingDns,
+            TransferEventKind::ResolvingDns,
+        );
+    }
+
+    // NOTE: Phase changes are read off curl's debug callback, which sees
+    // them as they happen. TLS records are only a handshake while the
+    // connection is still being set up, afterwards they carry the request.
+    pub(crate) fn observe_debug(&self, info_type: &InfoType, data: &[u8]) {
+        let mut state = self.lock();
+        match info_type {
+            InfoType::Text => {
+                let Ok(text) = std::str::from_utf8(data) else {
+                    return;
+                };
+                let text = text.trim();
+                if let Some(address) = text.strip_prefix("Trying ") {
+                    let address = address.trim_end_matches('.').trim().to_string();
+                    self.enter(
+                        &mut state,
+                        Phase::Connecting,
+                        TransferEventKind::Connecting { address },
+                    );
+                } else if let Some(url) = text.strip_prefix("Issue another request to this URL: ") {
+                    let url = url.trim_matches('\'').to_string();
+                    state.sent_milestones = 0;
+                    state.received_milestones = 0;
+                    self.enter(&mut state, Phase::Idle, TransferEventKind::Redirect { url });
+                }
+            }
+            InfoType::SslDataOut if state.phase == Phase::Connecting => {
+                self.enter(
+                    &mut state,
+                    Phase::TlsHandshake,
+                    TransferEventKind::TlsHandshake,
+                );
+            }
+            InfoType::HeaderOut if state.phase < Phase::Sending => {
+                self.enter(&mut state, Phase::Sending, TransferEventKind::Sending);
+            }
+            InfoType::HeaderIn if state.phase < Phase::Receiving => {
+                if state.phase == Phase::Sending {
+                    self.enter(&mut state, Phase::Waiting, TransferEventKind::Waiting);
+                }
+                self.enter(&mut state, Phase::Receiving, TransferEventKind::Receiving);
+            }
+            _ => {}
+        }
+    }
+
+    // NOTE: Waiting starts once the whole body is uploaded, which only the
+    // progress callback knows.
+    pub(crate) fn observe_progress(&self, downloaded: u64, uploaded: u64, upload_total: u64) {
+        let mut state = self.lock();
+        if state.phase == Phase::Sending && uploaded >= upload_total {
+            self.enter(&mut state, Phase::Waiting, TransferEventKind::Waiting);
+        }
+
+        let sent_milestones = uploaded / MILESTONE_BYTES;
+        if sent_milestones > state.sent_milestones {
+            state.sent_milestones = sent_milestones;
+            self.emit_locked(&mut state, TransferEventKind::BytesSent { total: uploaded });
+        }
+        let received_milestones = downloaded / MILESTONE_BYTES;
+        if received_milestones > state.received_milestones {
+            state.received_milestones = received_milestones;
+            self.emit_locked(
+                &mut state,
+                TransferEventKind::BytesReceived { total: downloaded },
+            );
+        }
+    }
+
+    // NOTE: The last event, `Done` carries the final response's own timing.
+    pub(crate) fn finish(&self, result: &Result<Response>) {
+        let kind = match result {
+            Ok(response) => TransferEventKind::Done {
+                timing: response.meta.timing.clone(),
+            },
+            Err(error) => TransferEventKind::Failed {
+                message: error.to_string(),
+            },
+        };
+        self.emit(kind);
+    }
+}
+
+// NOTE: Delivers `request_id`'s events to `listener` until dropped.
+pub(crate) struct Registration(Arc<Emitter>);
+
+impl Registration {
+    pub(crate) fn emitter(&self) -> &Emitter {
+        &self.0
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        LISTENERS.remove_if(&self.0.request_id, |_, emitter| {
+            Arc::ptr_eq(emitter, &self.0)
+        });
+    }
+}
+
+pub(crate) fn listen(request_id: i64, listener: EventListener) -> Registration {
+    let emitter = Arc::new(Emitter {
+        request_id,
+        listener,
+        state: Mutex::default(),
+    });
+    LISTENERS.insert(request_id, Arc::clone(&emitter));
+    emitter.emit(TransferEventKind::Queued);
+    Registration(emitter)
+}
+
+// NOTE: `None` for requests nobody listens to, the common case, so every
+// emitting site reduces to a map lookup.
+pub(crate) fn emitter(request_id: i64) -> Option<Arc<Emitter>> {
+    if LISTENERS.is_empty() {
+        return None;
+    }
+    LISTENERS
+        .get(&request_id)
+        .map(|emitter| Arc::clone(emitter.value()))
+}
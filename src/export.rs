@@ -0,0 +1,148 @@
+use std::{borrow::Cow, collections::HashSet};
+
+use crate::{
+    interop::{ApiKeyLocation, AuthType, Request},
+    redact::REDACTED,
+};
+
+const DEFAULT_SECRET_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+];
+
+// NOTE: What HAR and curl exports keep out of shared captures. Values of
+// secret headers, and the cookies and credentials they carry, are written as
+// `***` unless secrets are included, e.g. for a capture that stays local. The
+// header or query parameter of an API key sent with `AuthType::ApiKey` is
+// always secret.
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    secret_headers: HashSet<String>,
+    include_secrets: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            secret_headers: DEFAULT_SECRET_HEADERS
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            include_secrets: false,
+        }
+    }
+}
+
+impl ExportOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_secret_header(mut self, name: impl AsRef<str>) -> Self {
+        self.secret_headers
+            .insert(name.as_ref().to_ascii_lowercase());
+        self
+    }
+
+    // NOTE: Replaces the default set, e.g. to export cookies while still
+    // hiding `Authorization`.
+    pub fn with_secret_headers<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.secret_headers = names
+            .into_iter()
+            .map(|name| name.as_ref().to_ascii_lowercase())
+            .collect();
+        self
+    }
+
+    pub fn with_secrets_included(mut self, include: bool) -> Self {
+        self.include_secrets = include;
+        self
+    }
+
+    pub(crate) fn is_secret(&self, request: &Request, name: &str) -> bool {
+        if self.include_secrets {
+            return false;
+        }
+        if self.secret_headers.contains(&name.to_ascii_lowercase()) {
+            return true;
+        }
+        matches!(
+            request.auth,
+            Some(AuthType::ApiKey {
+                ref key,
+                location: ApiKeyLocation::Header,
+                ..
+            }) if key.eq_ignore_ascii_case(name)
+        )
+    }
+
+    pub(crate) fn is_secret_param(&self, request: &Request, name: &str) -> bool {
+        !self.include_secrets
+            && matches!(
+                request.auth,
+                Some(AuthType::ApiKey {
+                    ref key,
+                    location: ApiKeyLocation::Query,
+                    ..
+                }) if key == name
+            )
+    }
+
+    // NOTE: Rewrites only the values of secret parameters, the rest of the
+    // URL stays as written, which may be without a scheme.
+    pub(crate) fn url<'a>(&self, request: &Request, url: &'a str) -> Cow<'a, str> {
+        let Some((base, query)) = url.split_once('?') else {
+            return Cow::Borrowed(url);
+        };
+        let (query, fragment) = match query.split_once('#') {
+            Some((query, fragment)) => (query, Some(fragment)),
+            None => (query, None),
+        };
+
+        let mut redacted = false;
+        let pairs = query
+            .split('&')
+            .map(|pair| {
+                let name = pair.split_once('=').map_or(pair, |(name, _)| name);
+                let decoded = url::form_urlencoded::parse(name.as_bytes())
+                    .next()
+                    .map(|(name, _)| name);
+                match decoded {
+                    Some(decoded) if self.is_secret_param(request, &decoded) => {
+                        redacted = true;
+                        Cow::Owned(format!("{}={}", name, REDACTED))
+                    }
+                    _ => Cow::Borrowed(pair),
+                }
+            })
+            .collect::<Vec<_>>();
+        if !redacted {
+            return Cow::Borrowed(url);
+        }
+
+        let mut url = format!("{}?{}", base, pairs.join("&"));
+        if let Some(fragment) = fragment {
+            url.push('#');
+            url.push_str(fragment);
+        }
+        Cow::Owned(url)
+    }
+
+    pub(crate) fn header_value<'a>(
+        &self,
+        request: &Request,
+        name: &str,
+        value: &'a str,
+    ) -> Cow<'a, str> {
+        match self.is_secret(request, name) {
+            true => Cow::Borrowed(REDACTED),
+            false => Cow::Borrowed(value),
+        }
+    }
+}
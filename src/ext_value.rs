@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    interop::Response,
+    link::{split_outside_quotes, unquote},
+};
+
+/// How a non-ASCII header value is made safe to send.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeaderEncoding {
+    /// RFC 8187 extended value, e.g. `UTF-8''r%C3%A9sum%C3%A9.pdf`.
+    Rfc8187,
+    /// Plain percent-encoding of the UTF-8 bytes, e.g. `r%C3%A9sum%C3%A9.pdf`.
+    Percent,
+}
+
+// NOTE: `attr-char` from RFC 8187, everything else is percent-encoded.
+fn is_attr_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte)
+}
+
+pub fn encode_header_value(value: &str, encoding: HeaderEncoding) -> String {
+    match encoding {
+        HeaderEncoding::Rfc8187 => {
+            let mut encoded = String::from("UTF-8''");
+            for byte in value.bytes() {
+                if is_attr_char(byte) {
+                    encoded.push(byte as char);
+                } else {
+                    encoded.push_str(&format!("%{:02X}", byte));
+                }
+            }
+            encoded
+        }
+        HeaderEncoding::Percent => urlencoding::encode(value).into_owned(),
+    }
+}
+
+// NOTE: Only the two charsets RFC 8187 requires are understood, anything else
+// is left to the caller as the raw value.
+pub(crate) fn decode_ext_value(value: &str) -> Option<String> {
+    let (charset, rest) = value.trim().split_once('\'')?;
+    let (_language, encoded) = rest.split_once('\'')?;
+
+    let bytes = urlencoding::decode_binary(encoded.as_bytes());
+    if charset.eq_ignore_ascii_case("utf-8") {
+        String::from_utf8(bytes.into_owned()).ok()
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Some(bytes.iter().map(|&byte| byte as char).collect())
+    } else {
+        tracing::debug!(charset = %charset, "Unsupported charset in extended header value");
+        None
+    }
+}
+
+impl Response {
+    pub(crate) fn header_str(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // NOTE: Joins every value received under `name` whatever its casing, with
+    // `, ` as RFC 9110 prescribes. `Set-Cookie` cannot be comma joined, its
+    // values stay one per line.
+    pub fn header_joined(&self, name: &str) -> Option<String> {
+        let mut matching = self
+            .headers
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .collect::<Vec<_>>();
+        if matching.is_empty() {
+            return None;
+        }
+        matching.sort_by_key(|(key, _)| *key);
+
+        let separator = if name.eq_ignore_ascii_case("set-cookie") {
+            "\n"
+        } else {
+            ", "
+        };
+        Some(
+            matching
+                .into_iter()
+                .map(|(_, value)| value.as_str())
+                .collect::<Vec<_>>()
+                .join(separator),
+        )
+    }
+
+    // NOTE: Decodes values sent entirely as an RFC 8187 extended value, any
+    // other value is returned unchanged.
+    pub fn decoded_header(&self, name: &str) -> Option<String> {
+        let value = self.header_str(name)?;
+        Some(decode_ext_value(value).unwrap_or_else(|| value.to_string()))
+    }
+
+    // NOTE: Reads a `;` separated parameter such as `filename` from headers
+    // like `Content-Disposition`, preferring the RFC 8187 `filename*` form
+    // when present and decodable.
+    pub fn header_param(&self, name: &str, param: &str) -> Option<String> {
+        let value = self.header_str(name)?;
+        let extended = format!("{}*", param);
+
+        let mut plain = None;
+        for segment in split_outside_quotes(value, ';') {
+            let Some((key, value)) = segment.split_once('=') else {
+                continue;
+            };
+            let key = key.trim();
+
+            if key.eq_ignore_ascii_case(&extended) {
+                if let Some(decoded) = decode_ext_value(value) {
+                    return Some(decoded);
+                }
+            } else if key.eq_ignore_ascii_case(param) && plain.is_none() {
+                plain = Some(unquote(value.trim()));
+            }
+        }
+
+        plain
+    }
+}
@@ -0,0 +1,114 @@
+use std::{collections::HashMap, io::Read};
+
+use flate2::read::MultiGzDecoder;
+
+use crate::{
+    error::{RelayError, Result},
+    interop::Request,
+};
+
+// NOTE: The gzip magic bytes followed by the deflate method, the only one
+// defined, which makes a plain binary body starting with `1f 8b` less likely
+// to be mistaken for gzip.
+const MAGIC: [u8; 3] = [0x1f, 0x8b, 0x08];
+
+// NOTE: Media types of gzip files, whose body is the payload itself and must
+// not be unpacked.
+const GZIP_MEDIA_TYPES: &[&str] = &["application/gzip", "application/x-gzip"];
+
+enum Failure {
+    LimitExceeded(u64),
+    Invalid(String),
+}
+
+// NOTE: A body may hold several gzip members back to back, they decode to
+// their contents concatenated. Reading stops one byte past `limit`, so a
+// small body that inflates to a huge one is never held in full.
+fn inflate(body: &[u8], limit: Option<u64>) -> std::result::Result<Vec<u8>, Failure> {
+    let mut output = Vec::new();
+    let read_limit = limit.map_or(u64::MAX, |limit| limit.saturating_add(1));
+    let read = MultiGzDecoder::new(body)
+        .take(read_limit)
+        .read_to_end(&mut output);
+    read.map_err(|e| Failure::Invalid(e.to_string()))?;
+
+    match limit {
+        Some(limit) if output.len() as u64 > limit => Err(Failure::LimitExceeded(limit)),
+        _ => Ok(output),
+    }
+}
+
+fn header<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+// NOTE: Only bodies that arrived without any content coding are sniffed, and
+// never when the caller turned decompression off or the server labelled the
+// body as a gzip file.
+fn should_sniff(request: &Request, headers: &HashMap<String, String>, body: &[u8]) -> bool {
+    let Some(options) = request.meta.as_ref().and_then(|meta| meta.options.as_ref()) else {
+        return false;
+    };
+    if options.detect_gzip != Some(true) || options.decompress == Some(false) {
+        return false;
+    }
+
+    let encoded = header(headers, "content-encoding").is_some_and(|coding| {
+        coding
+            .split(',')
+            .any(|coding| !coding.trim().eq_ignore_ascii_case("identity"))
+    });
+    let gzip_file = header(headers, "content-type").is_some_and(|content_type| {
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+        GZIP_MEDIA_TYPES
+            .iter()
+            .any(|media_type| essence.eq_ignore_ascii_case(media_type))
+    });
+
+    !encoded && !gzip_file && body.starts_with(&MAGIC)
+}
+
+// NOTE: The decompressed body when the request opted into `detectGzip` and
+// the body is gzip the server did not declare. A body that only looks like
+// gzip is kept as received.
+pub(crate) fn sniff(
+    request: &Request,
+    headers: &HashMap<String, String>,
+    body: &[u8],
+) -> Result<Option<Vec<u8>>> {
+    if !should_sniff(request, headers, body) {
+        return Ok(None);
+    }
+
+    let limit = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.max_decompressed_bytes);
+
+    match inflate(body, limit) {
+        Ok(decoded) => {
+            tracing::debug!(
+                encoded = body.len(),
+                decoded = decoded.len(),
+                "Decompressed undeclared gzip body"
+            );
+            Ok(Some(decoded))
+        }
+        Err(Failure::LimitExceeded(limit)) => {
+            tracing::error!(limit = limit, "Decompression limit exceeded");
+            Err(RelayError::Parse {
+                message: "decompression limit exceeded".into(),
+                cause: Some(format!("Response body exceeded {} bytes", limit).into()),
+                partial: None,
+            })
+        }
+        Err(Failure::Invalid(reason)) => {
+            tracing::debug!(reason = %reason, "Body starts like gzip but does not decode, keeping it");
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,864 @@
+use std::str::FromStr;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use bytes::Bytes;
+use http::{Method, Version};
+use mime::Mime;
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    content,
+    error::{RelayError, Result},
+    export::ExportOptions,
+    header::Headers,
+    interop::{ContentType, FormData, FormValue, MediaType, Request, Response, TimingInfo},
+    query,
+};
+
+pub const HAR_VERSION: &str = "1.2";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Har {
+    pub log: Log,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Log {
+    pub version: String,
+    pub creator: Creator,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Creator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    pub started_date_time: String,
+    pub time: f64,
+    pub request: HarRequest,
+    pub response: HarResponse,
+    #[serde(default)]
+    pub cache: Cache,
+    pub timings: Timings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarRequest {
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub http_version: String,
+    #[serde(default)]
+    pub cookies: Vec<HarCookie>,
+    #[serde(default)]
+    pub headers: Vec<NameValue>,
+    #[serde(default)]
+    pub query_string: Vec<NameValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<PostData>,
+    #[serde(default = "unknown_size")]
+    pub headers_size: i64,
+    #[serde(default = "unknown_size")]
+    pub body_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub status_text: String,
+    #[serde(default)]
+    pub http_version: String,
+    #[serde(default)]
+    pub cookies: Vec<HarCookie>,
+    #[serde(default)]
+    pub headers: Vec<NameValue>,
+    pub content: HarContent,
+    #[serde(default, rename = "redirectURL")]
+    pub redirect_url: String,
+    #[serde(default = "unknown_size")]
+    pub headers_size: i64,
+    #[serde(default = "unknown_size")]
+    pub body_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NameValue {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarCookie {
+    pub name: String,
+    pub value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_only: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secure: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostData {
+    #[serde(default)]
+    pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<PostParam>,
+    #[serde(default)]
+    pub text: String,
+    // NOTE: Not part of HAR 1.2, custom fields must be prefixed with `_`.
+    #[serde(
+        default,
+        rename = "_encoding",
+        alias = "encoding",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub encoding: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostParam {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    // NOTE: Not part of HAR 1.2, custom fields must be prefixed with `_`.
+    #[serde(
+        default,
+        rename = "_encoding",
+        alias = "encoding",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub encoding: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarContent {
+    pub size: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression: Option<i64>,
+    #[serde(default)]
+    pub mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Cache {}
+
+// NOTE: `-1` marks a phase that doesn't apply or wasn't measured,
+// `send`, `wait` and `receive` are required to be non-negative.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timings {
+    #[serde(default = "unknown_timing")]
+    pub blocked: f64,
+    #[serde(default = "unknown_timing")]
+    pub dns: f64,
+    #[serde(default = "unknown_timing")]
+    pub connect: f64,
+    pub send: f64,
+    pub wait: f64,
+    pub receive: f64,
+    #[serde(default = "unknown_timing")]
+    pub ssl: f64,
+}
+
+fn unknown_size() -> i64 {
+    -1
+}
+
+fn unknown_timing() -> f64 {
+    -1.0
+}
+
+pub(crate) fn http_version(version: Version) -> String {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_11 => "HTTP/1.1",
+        Version::HTTP_2 => "HTTP/2",
+        Version::HTTP_3 => "HTTP/3",
+        _ => "unknown",
+    }
+    .to_string()
+}
+
+fn request_cookies(request: &Request, options: &ExportOptions) -> Vec<HarCookie> {
+    request
+        .headers
+        .iter()
+        .flatten()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("cookie"))
+        .flat_map(|(_, value)| value.split(';'))
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(name, value)| HarCookie {
+            name: name.trim().to_string(),
+            value: options
+                .header_value(request, "cookie", value.trim())
+                .into_owned(),
+            path: None,
+            domain: None,
+            expires: None,
+            http_only: None,
+            secure: None,
+        })
+        .collect()
+}
+
+fn response_cookies(
+    request: &Request,
+    response: &Response,
+    options: &ExportOptions,
+) -> Vec<HarCookie> {
+    response
+        .cookies
+        .iter()
+        .flatten()
+        .map(|cookie| HarCookie {
+            name: cookie.name.clone(),
+            value: options
+                .header_value(request, "set-cookie", &cookie.value)
+                .into_owned(),
+            path: cookie.path.clone(),
+            domain: cookie.domain.clone(),
+            expires: cookie
+                .expires
+                .and_then(|expires| expires.format(&Rfc3339).ok()),
+            http_only: cookie.http_only,
+            secure: cookie.secure,
+        })
+        .collect()
+}
+
+fn query_string(url: &str) -> Vec<NameValue> {
+    url::Url::parse(url)
+        .map(|url| {
+            url.query_pairs()
+                .map(|(name, value)| NameValue {
+                    name: name.into_owned(),
+                    value: value.into_owned(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn post_data(content: &ContentType) -> PostData {
+    match content {
+        ContentType::Urlencoded {
+            content,
+            media_type,
+        } => PostData {
+            mime_type: media_type.to_string(),
+            params: url::form_urlencoded::parse(content.as_bytes())
+                .map(|(name, value)| PostParam {
+                    name: name.into_owned(),
+                    value: Some(value.into_owned()),
+                    file_name: None,
+                    content_type: None,
+                    encoding: None,
+                })
+                .collect(),
+            text: content.clone(),
+            encoding: None,
+        },
+        ContentType::Form {
+            content,
+            media_type,
+        }
+        | ContentType::Multipart {
+            content,
+            media_type,
+        } => PostData {
+            mime_type: media_type.to_string(),
+            params: content
+                .iter()
+                .flat_map(|(name, values)| values.iter().map(move |value| (name, value)))
+                .map(|(name, value)| match value {
+                    FormValue::Text { value } => PostParam {
+                        name: name.clone(),
+                        value: Some(value.clone()),
+                        file_name: None,
+                        content_type: None,
+                        encoding: None,
+                    },
+                    FormValue::File {
+                        filename,
+                        content_type,
+                        data,
+                    } => {
+                        let (value, encoding) = text_or_base64(data);
+                        PostParam {
+                            name: name.clone(),
+                            value: Some(value),
+                            file_name: Some(filename.clone()),
+                            content_type: Some(content_type.to_string()),
+                            encoding,
+                        }
+                    }
+                })
+                .collect(),
+            text: String::new(),
+            encoding: None,
+        },
+        ContentType::Text { .. }
+        | ContentType::Json { .. }
+        | ContentType::JsonPatch { .. }
+        | ContentType::JsonMergePatch { .. }
+        | ContentType::Xml { .. }
+        | ContentType::Csv { .. }
+        | ContentType::Binary { .. }
+        | ContentType::MultipartRelated { .. } => {
+            let body = content::body_bytes(content).unwrap_or_default();
+            let (text, encoding) = text_or_base64(&body);
+            PostData {
+                mime_type: content::content_headers(content)
+                    .remove("Content-Type")
+                    .unwrap_or_else(|| content::media_type(content).to_string()),
+                params: Vec::new(),
+                text,
+                encoding,
+            }
+        }
+    }
+}
+
+fn text_or_base64(data: &[u8]) -> (String, Option<String>) {
+    match std::str::from_utf8(data) {
+        Ok(text) => (text.to_string(), None),
+        Err(_) => (BASE64.encode(data), Some("base64".into())),
+    }
+}
+
+fn headers(
+    headers: impl IntoIterator<Item = (String, String)>,
+    request: &Request,
+    options: &ExportOptions,
+) -> Vec<NameValue> {
+    headers
+        .into_iter()
+        .map(|(name, value)| {
+            let value = options.header_value(request, &name, &value).into_owned();
+            (name, value)
+        })
+        .flat_map(|(name, value)| {
+            // NOTE: Repeated `Set-Cookie` headers are joined with newlines
+            // when received, HAR expects one entry per header line.
+            if name.eq_ignore_ascii_case("set-cookie") {
+                value
+                    .split('\n')
+                    .map(|value| NameValue {
+                        name: name.clone(),
+                        value: value.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+            } else {
+                vec![NameValue { name, value }]
+            }
+        })
+        .collect()
+}
+
+// NOTE: Secret headers are redacted as `ExportOptions::default()` decides,
+// see `to_har_entry_with`.
+pub fn to_har_entry(request: &Request, response: &Response) -> Entry {
+    to_har_entry_with(request, response, &ExportOptions::default())
+}
+
+pub fn to_har_entry_with(request: &Request, response: &Response, options: &ExportOptions) -> Entry {
+    let timing = &response.meta.timing;
+    let elapsed = timing.duration().as_millis() as f64;
+    let timings = timings(timing, elapsed);
+    // NOTE: HAR requires `time` to be the sum of the phases, `ssl` aside as
+    // `connect` already includes it.
+    let time = match timing.phases {
+        Some(marks) => marks.total,
+        None => elapsed,
+    };
+
+    let started_date_time =
+        OffsetDateTime::from_unix_timestamp_nanos(timing.start as i128 * 1_000_000)
+            .ok()
+            .and_then(|start| start.format(&Rfc3339).ok())
+            .unwrap_or_default();
+
+    let request_body_size = request
+        .content
+        .as_ref()
+        .and_then(content::body_bytes)
+        .map_or(0, |body| body.len() as i64);
+
+    let url = query::wire_url(request)
+        .map(|url| query::with_default_scheme(&url).into_owned())
+        .unwrap_or_else(|_| request.url.clone());
+    let url = options.url(request, &url).into_owned();
+
+    let (text, encoding) = text_or_base64(&response.body.body);
+
+    let redirect_url = response
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("location"))
+        .map(|(_, value)| value.clone())
+        .unwrap_or_default();
+
+    Entry {
+        started_date_time,
+        time,
+        request: HarRequest {
+            method: request.method.to_string(),
+            url: url.clone(),
+            http_version: http_version(request.version),
+            cookies: request_cookies(request, options),
+            headers: headers(
+                request.headers.clone().unwrap_or_default(),
+                request,
+                options,
+            ),
+            query_string: query_string(&url),
+            post_data: request.content.as_ref().map(post_data),
+            headers_size: -1,
+            body_size: request_body_size,
+        },
+        response: HarResponse {
+            status: response.status.as_u16(),
+            status_text: response
+                .status
+                .canonical_reason()
+                .unwrap_or_default()
+                .to_string(),
+            http_version: http_version(response.version),
+            cookies: response_cookies(request, response, options),
+            headers: headers(response.headers.clone(), request, options),
+            content: HarContent {
+                size: response.body.body.len() as i64,
+                compression: None,
+                mime_type: response.body.media_type.to_string(),
+                text: Some(text),
+                encoding,
+            },
+            redirect_url,
+            headers_size: response.meta.size.headers as i64,
+            body_size: response.meta.size.body as i64,
+        },
+        cache: Cache::default(),
+        timings,
+    }
+}
+
+// NOTE: Each phase is the time between two of curl's milestones, one never
+// reached means the phase didn't happen. HAR's `connect` includes `ssl`, and
+// redirects followed before the final transfer count as `blocked`. curl
+// doesn't tell sending the request apart from waiting for the answer, so
+// `send` stays `0` and `wait` covers both. Without milestones, e.g. for a
+// cached response, everything is `wait`.
+fn timings(timing: &TimingInfo, elapsed: f64) -> Timings {
+    let Some(marks) = timing.phases else {
+        return Timings {
+            blocked: -1.0,
+            dns: -1.0,
+            connect: -1.0,
+            send: 0.0,
+            wait: elapsed,
+            receive: 0.0,
+            ssl: -1.0,
+        };
+    };
+
+    let reached = |mark: f64| mark > 0.0;
+    let span = |from: f64, to: f64| (to - from).max(0.0);
+
+    let dns_end = if reached(marks.namelookup) {
+        marks.namelookup.max(marks.redirect)
+    } else {
+        marks.redirect
+    };
+    let connected = if reached(marks.appconnect) {
+        marks.appconnect
+    } else {
+        marks.connect
+    };
+    let ready = dns_end.max(connected);
+    let first_byte = if reached(marks.starttransfer) {
+        marks.starttransfer.max(ready)
+    } else {
+        marks.total.max(ready)
+    };
+
+    Timings {
+        blocked: if reached(marks.redirect) {
+            marks.redirect
+        } else {
+            -1.0
+        },
+        dns: if reached(marks.namelookup) {
+            span(marks.redirect, dns_end)
+        } else {
+            -1.0
+        },
+        connect: if reached(marks.connect) {
+            span(dns_end, connected)
+        } else {
+            -1.0
+        },
+        send: 0.0,
+        wait: span(ready, first_byte),
+        receive: span(first_byte, marks.total),
+        ssl: if reached(marks.appconnect) && reached(marks.connect) {
+            span(marks.connect, marks.appconnect)
+        } else {
+            -1.0
+        },
+    }
+}
+
+pub fn to_har<'a>(
+    entries: impl IntoIterator<Item = (&'a Request, &'a Response)>,
+) -> Result<String> {
+    to_har_with(entries, &ExportOptions::default())
+}
+
+pub fn to_har_with<'a>(
+    entries: impl IntoIterator<Item = (&'a Request, &'a Response)>,
+    options: &ExportOptions,
+) -> Result<String> {
+    let har = Har {
+        log: Log {
+            version: HAR_VERSION.into(),
+            creator: Creator {
+                name: env!("CARGO_PKG_NAME").into(),
+                version: env!("CARGO_PKG_VERSION").into(),
+            },
+            entries: entries
+                .into_iter()
+                .map(|(request, response)| to_har_entry_with(request, response, options))
+                .collect(),
+        },
+    };
+
+    tracing::debug!(entries = har.log.entries.len(), "Exporting HAR");
+
+    serde_json::to_string_pretty(&har).map_err(|e| {
+        tracing::error!(error = %e, "Failed to serialize HAR");
+        RelayError::Parse {
+            message: "Failed to serialize HAR".into(),
+            cause: Some(e.into()),
+            partial: None,
+        }
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct HarDiagnostic {
+    pub entry: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct HarImport {
+    pub requests: Vec<Request>,
+    pub diagnostics: Vec<HarDiagnostic>,
+}
+
+pub fn from_har(har: &str) -> Result<Vec<Request>> {
+    let imported = import_har(har)?;
+    for diagnostic in &imported.diagnostics {
+        tracing::warn!(
+            entry = diagnostic.entry,
+            message = %diagnostic.message,
+            "Skipped HAR entry"
+        );
+    }
+    Ok(imported.requests)
+}
+
+// NOTE: Only each entry's `request` is read, and only when the entry gets to
+// it, so one entry a recorder left incomplete does not fail the rest.
+#[derive(Deserialize)]
+struct ImportHar {
+    log: ImportLog,
+}
+
+#[derive(Deserialize)]
+struct ImportLog {
+    entries: Vec<serde_json::Value>,
+}
+
+#[tracing::instrument(skip(har), level = "debug")]
+pub fn import_har(har: &str) -> Result<HarImport> {
+    let har: ImportHar = serde_json::from_str(har).map_err(|e| {
+        tracing::error!(error = %e, "Failed to parse HAR");
+        RelayError::Parse {
+            message: "Failed to parse HAR".into(),
+            cause: Some(e.into()),
+            partial: None,
+        }
+    })?;
+
+    let mut requests = Vec::new();
+    let mut diagnostics = Vec::new();
+
+    for (index, mut entry) in har.log.entries.into_iter().enumerate() {
+        let request = entry
+            .get_mut("request")
+            .map(serde_json::Value::take)
+            .ok_or_else(|| "Entry has no request".to_string())
+            .and_then(|request| {
+                serde_json::from_value::<HarRequest>(request)
+                    .map_err(|e| format!("Invalid request: {}", e))
+            })
+            .and_then(|request| request_from_entry(index, request));
+
+        match request {
+            Ok(request) => requests.push(request),
+            Err(message) => diagnostics.push(HarDiagnostic {
+                entry: index,
+                message,
+            }),
+        }
+    }
+
+    tracing::debug!(
+        imported = requests.len(),
+        skipped = diagnostics.len(),
+        "Imported HAR"
+    );
+
+    Ok(HarImport {
+        requests,
+        diagnostics,
+    })
+}
+
+fn parse_http_version(version: &str) -> Version {
+    match version.trim().to_ascii_lowercase().as_str() {
+        "http/0.9" => Version::HTTP_09,
+        "http/1.0" => Version::HTTP_10,
+        "http/2" | "http/2.0" | "h2" | "h2c" => Version::HTTP_2,
+        "http/3" | "http/3.0" | "h3" => Version::HTTP_3,
+        _ => Version::HTTP_11,
+    }
+}
+
+fn decode_text(text: &str, encoding: Option<&str>) -> std::result::Result<Vec<u8>, String> {
+    match encoding {
+        Some(encoding) if encoding.eq_ignore_ascii_case("base64") => BASE64
+            .decode(text.trim())
+            .map_err(|e| format!("Invalid base64 body: {}", e)),
+        Some(encoding) => Err(format!("Unsupported body encoding '{}'", encoding)),
+        None => Ok(text.as_bytes().to_vec()),
+    }
+}
+
+fn media_type(mime_type: &str) -> MediaType {
+    mime_type
+        .parse::<Mime>()
+        .ok()
+        .and_then(|mime| MediaType::from_str(mime.essence_str()).ok())
+        .unwrap_or(MediaType::TextPlain)
+}
+
+fn content_from_post_data(
+    post_data: PostData,
+    headers: &mut Headers,
+) -> std::result::Result<ContentType, String> {
+    let mime = post_data.mime_type.parse::<Mime>().ok();
+    let essence = mime
+        .as_ref()
+        .map(|mime| mime.essence_str().to_string())
+        .unwrap_or_default();
+
+    if essence == "multipart/form-data" && !post_data.params.is_empty() {
+        // NOTE: The recorded boundary no longer matches once curl re-encodes
+        // the form, so the original `Content-Type` has to go.
+        headers.retain(|name, _| !name.eq_ignore_ascii_case("content-type"));
+
+        let mut fields: FormData = Vec::new();
+        for param in post_data.params {
+            let value = param.value.unwrap_or_default();
+            let value = match param.file_name {
+                Some(filename) => FormValue::File {
+                    filename,
+                    content_type: param
+                        .content_type
+                        .as_deref()
+                        .map(media_type)
+                        .unwrap_or(MediaType::OctetStream),
+                    data: Bytes::from(decode_text(&value, param.encoding.as_deref())?),
+                },
+                None => FormValue::Text { value },
+            };
+
+            content::push_field(&mut fields, param.name, value);
+        }
+
+        return Ok(ContentType::Multipart {
+            content: fields,
+            media_type: MediaType::MultipartFormData,
+        });
+    }
+
+    if essence == "application/x-www-form-urlencoded" {
+        // NOTE: Browsers emit both `text` and `params`, `text` is the exact body.
+        let content = if post_data.text.is_empty() {
+            url::form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(
+                    post_data
+                        .params
+                        .iter()
+                        .map(|param| (&param.name, param.value.as_deref().unwrap_or_default())),
+                )
+                .finish()
+        } else {
+            post_data.text
+        };
+
+        return Ok(ContentType::Urlencoded {
+            content,
+            media_type: MediaType::FormUrlEncoded,
+        });
+    }
+
+    let body = decode_text(&post_data.text, post_data.encoding.as_deref())?;
+    let media_type = media_type(&post_data.mime_type);
+
+    let text = match String::from_utf8(body) {
+        Ok(text) if post_data.encoding.is_none() => text,
+        Ok(text) => {
+            return Ok(ContentType::Binary {
+                content: Bytes::from(text.into_bytes()),
+                media_type,
+                filename: None,
+            })
+        }
+        Err(e) => {
+            return Ok(ContentType::Binary {
+                content: Bytes::from(e.into_bytes()),
+                media_type,
+                filename: None,
+            })
+        }
+    };
+
+    let is_json = mime
+        .as_ref()
+        .is_some_and(|mime| mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON));
+    let is_xml = mime
+        .as_ref()
+        .is_some_and(|mime| mime.subtype() == mime::XML || mime.suffix() == Some(mime::XML));
+
+    Ok(match serde_json::from_str(&text) {
+        Ok(content) if is_json => ContentType::Json {
+            content,
+            raw: Some(text),
+            media_type,
+        },
+        _ if is_xml => ContentType::Xml {
+            content: text,
+            media_type,
+        },
+        _ => ContentType::Text {
+            content: text,
+            media_type,
+        },
+    })
+}
+
+fn request_from_entry(index: usize, har: HarRequest) -> std::result::Result<Request, String> {
+    let method =
+        Method::from_str(&har.method).map_err(|_| format!("Invalid method '{}'", har.method))?;
+
+    let mut url =
+        url::Url::parse(&har.url).map_err(|e| format!("Invalid URL '{}': {}", har.url, e))?;
+    if url.query().is_none() && !har.query_string.is_empty() {
+        url.query_pairs_mut().extend_pairs(
+            har.query_string
+                .iter()
+                .map(|param| (&param.name, &param.value)),
+        );
+    }
+
+    // NOTE: HTTP/2 captures carry pseudo-headers, and `Content-Length` is
+    // recomputed by curl for whatever body is actually sent.
+    let mut headers = Headers::new();
+    for header in har.headers {
+        if header.name.starts_with(':') || header.name.eq_ignore_ascii_case("content-length") {
+            continue;
+        }
+
+        match headers.get_mut(&header.name) {
+            Some(value) => {
+                value.push_str(if header.name.eq_ignore_ascii_case("cookie") {
+                    "; "
+                } else {
+                    ", "
+                });
+                value.push_str(&header.value);
+            }
+            None => headers.append(header.name, header.value),
+        }
+    }
+
+    if !har.cookies.is_empty()
+        && !headers
+            .keys()
+            .any(|name| name.eq_ignore_ascii_case("cookie"))
+    {
+        let cookie = har
+            .cookies
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        headers.insert("Cookie", cookie);
+    }
+
+    let content = har
+        .post_data
+        .map(|post_data| content_from_post_data(post_data, &mut headers))
+        .transpose()?;
+
+    Ok(Request {
+        id: index as i64,
+        url: url.to_string(),
+        method,
+        version: parse_http_version(&har.http_version),
+        headers: (!headers.is_empty()).then_some(headers),
+        params: None,
+        content,
+        auth: None,
+        security: None,
+        proxy: None,
+        meta: None,
+        trace_context: None,
+        range: None,
+        accept: None,
+        raw_options: Vec::new(),
+        secrets: Default::default(),
+        target_policy: None,
+    })
+}
@@ -1,69 +1,648 @@
 use std::collections::HashMap;
-use std::str::FromStr;
+use std::fmt;
+use std::sync::Arc;
 
 use curl::easy::{Easy, List};
-use http::{HeaderMap, HeaderName, HeaderValue};
+use serde::{
+    de::{MapAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 
-use crate::error::{RelayError, Result};
+use crate::{
+    error::{RelayError, Result},
+    redact::{self, RedactionPolicy},
+};
 
-pub(crate) struct HeadersBuilder<'a> {
-    handle: &'a mut Easy,
+/// Request headers in the order they are sent.
+///
+/// Names keep the caller's casing but compare case-insensitively, and a name
+/// may repeat, each occurrence going out as its own header line. A suppressed
+/// name removes the header entirely, including the ones curl adds on its own
+/// such as `Accept` or `Expect`.
+///
+/// Serialized as an object in first-appearance order, a name with a single
+/// value maps to a string, a repeated name to an array of its values and a
+/// suppressed name to an empty array.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Headers {
+    entries: Vec<(String, String)>,
+    suppressed: Vec<String>,
 }
 
-impl<'a> HeadersBuilder<'a> {
-    pub(crate) fn new(handle: &'a mut Easy) -> Self {
-        Self { handle }
+impl Headers {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    #[tracing::instrument(skip(self), level = "debug")]
-    pub(crate) fn add_headers(&mut self, headers: Option<&HashMap<String, String>>) -> Result<()> {
-        let Some(headers) = headers else {
-            tracing::debug!("No headers provided");
-            return Ok(());
+    /// Number of header values, suppressed names are not counted.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.suppressed.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.entries.iter().map(|(name, value)| (name, value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|(name, _)| name)
+    }
+
+    pub fn suppressed(&self) -> impl Iterator<Item = &String> {
+        self.suppressed.iter()
+    }
+
+    pub fn is_suppressed(&self, name: &str) -> bool {
+        self.suppressed
+            .iter()
+            .any(|key| key.eq_ignore_ascii_case(name))
+    }
+
+    /// Whether the caller decided on `name`, either by giving it a value or by
+    /// suppressing it.
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.keys().any(|key| key.eq_ignore_ascii_case(name)) || self.is_suppressed(name)
+    }
+
+    /// First value sent under `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut String> {
+        self.entries
+            .iter_mut()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Adds a header after all existing ones, earlier values of the same name
+    /// are kept.
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        self.unsuppress(&name);
+        self.entries.push((name, value.into()));
+    }
+
+    /// Sets `name` to a single value, taking the position of its first
+    /// occurrence or going last when it is new. Returns the replaced value.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) -> Option<String> {
+        let name = name.into();
+        self.unsuppress(&name);
+        let Some(index) = self
+            .entries
+            .iter()
+            .position(|(key, _)| key.eq_ignore_ascii_case(&name))
+        else {
+            self.entries.push((name, value.into()));
+            return None;
         };
 
-        let mut header_map = HeaderMap::new();
-        for (key, value) in headers {
-            if let (Ok(name), Ok(val)) = (HeaderName::from_str(key), HeaderValue::from_str(value)) {
-                header_map.insert(name, val);
+        let (_, previous) = std::mem::replace(&mut self.entries[index], (name, value.into()));
+        let name = self.entries[index].0.clone();
+        let mut position = 0;
+        self.entries.retain(|(key, _)| {
+            position += 1;
+            position - 1 <= index || !key.eq_ignore_ascii_case(&name)
+        });
+        Some(previous)
+    }
+
+    /// Drops every value of `name` and keeps curl from sending a default for
+    /// it, so the header does not go out at all.
+    pub fn suppress(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        self.entries
+            .retain(|(key, _)| !key.eq_ignore_ascii_case(&name));
+        if !self.is_suppressed(&name) {
+            self.suppressed.push(name);
+        }
+    }
+
+    /// Removes every value of `name` along with any suppression, returning the
+    /// first value.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        self.unsuppress(name);
+        let mut removed = None;
+        self.entries.retain(|(key, value)| {
+            if !key.eq_ignore_ascii_case(name) {
+                return true;
+            }
+            removed.get_or_insert_with(|| value.clone());
+            false
+        });
+        removed
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&String, &mut String) -> bool) {
+        self.entries.retain_mut(|(name, value)| keep(name, value));
+    }
+
+    fn unsuppress(&mut self, name: &str) {
+        self.suppressed
+            .retain(|key| !key.eq_ignore_ascii_case(name));
+    }
+
+    // NOTE: Layers `headers` on top, names present in the new layer replace
+    // all earlier values while repeats within the layer are kept in order.
+    pub(crate) fn merge(&mut self, headers: impl Into<Headers>) {
+        let layer = headers.into();
+        if layer.is_empty() {
+            return;
+        }
+
+        self.entries.retain(|(name, _)| !layer.contains_key(name));
+        self.suppressed.retain(|name| !layer.contains_key(name));
+        self.entries.extend(layer.entries);
+        self.suppressed.extend(layer.suppressed);
+    }
+}
+
+impl FromIterator<(String, String)> for Headers {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self {
+        let mut headers = Self::new();
+        headers.extend(iter);
+        headers
+    }
+}
+
+impl Extend<(String, String)> for Headers {
+    fn extend<I: IntoIterator<Item = (String, String)>>(&mut self, iter: I) {
+        for (name, value) in iter {
+            self.append(name, value);
+        }
+    }
+}
+
+// NOTE: Only yields values, suppressed names are dropped.
+impl IntoIterator for Headers {
+    type Item = (String, String);
+    type IntoIter = std::vec::IntoIter<(String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Headers {
+    type Item = (&'a String, &'a String);
+    type IntoIter = std::iter::Map<
+        std::slice::Iter<'a, (String, String)>,
+        fn(&'a (String, String)) -> (&'a String, &'a String),
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter().map(|(name, value)| (name, value))
+    }
+}
+
+// NOTE: Plain maps have no defined order, names are sorted so the result at
+// least does not change from run to run.
+impl From<HashMap<String, String>> for Headers {
+    fn from(headers: HashMap<String, String>) -> Self {
+        let mut headers = headers.into_iter().collect::<Vec<_>>();
+        headers.sort_by_key(|(name, _)| name.to_ascii_lowercase());
+        headers.into_iter().collect()
+    }
+}
+
+impl<const N: usize> From<[(String, String); N]> for Headers {
+    fn from(headers: [(String, String); N]) -> Self {
+        headers.into_iter().collect()
+    }
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+enum HeaderValues {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl Serialize for Headers {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut grouped: Vec<(&str, Vec<&str>)> = Vec::new();
+        for (name, value) in self {
+            match grouped.iter_mut().find(|(existing, _)| *existing == name) {
+                Some((_, values)) => values.push(value),
+                None => grouped.push((name, vec![value])),
+            }
+        }
+
+        let mut map = serializer.serialize_map(Some(grouped.len() + self.suppressed.len()))?;
+        for (name, values) in grouped {
+            match values.as_slice() {
+                [value] => map.serialize_entry(name, value)?,
+                values => map.serialize_entry(name, values)?,
+            }
+        }
+        for name in &self.suppressed {
+            map.serialize_entry(name, &[] as &[&str])?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Headers {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct HeadersVisitor;
+
+        impl<'de> Visitor<'de> for HeadersVisitor {
+            type Value = Headers;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map of header names to a value or array of values")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut access: A,
+            ) -> std::result::Result<Headers, A::Error> {
+                let mut headers = Headers::new();
+                while let Some((name, values)) = access.next_entry::<String, HeaderValues>()? {
+                    match values {
+                        HeaderValues::Single(value) => headers.append(name, value),
+                        HeaderValues::Multiple(values) if values.is_empty() => {
+                            headers.suppress(name)
+                        }
+                        HeaderValues::Multiple(values) => {
+                            for value in values {
+                                headers.append(name.clone(), value);
+                            }
+                        }
+                    }
+                }
+                Ok(headers)
+            }
+        }
+
+        deserializer.deserialize_map(HeadersVisitor)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for Headers {
+    fn schema_name() -> String {
+        "Headers".into()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        <HashMap<String, HeaderValues>>::json_schema(gen)
+    }
+}
+
+fn invalid_header(name: &str, reason: String) -> RelayError {
+    tracing::error!(name = %name, reason = %reason, "Invalid header");
+    RelayError::InvalidHeader {
+        name: name.to_string(),
+        reason,
+    }
+}
+
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)
+}
+
+// NOTE: Names are RFC 9110 tokens, anything else is either mangled by curl or
+// lets a name smuggle in its own `:` separator.
+pub(crate) fn check_name(name: &str) -> std::result::Result<(), String> {
+    if name.is_empty() {
+        return Err("name is empty".into());
+    }
+
+    match name.char_indices().find(|(_, c)| !is_token_char(*c)) {
+        Some((index, c)) => Err(format!(
+            "name contains {:?} at byte {}, only token characters are allowed",
+            c, index
+        )),
+        None => Ok(()),
+    }
+}
+
+// NOTE: CR and LF would end the header line early and let the rest of the
+// value be read as further headers or a second request.
+pub(crate) fn check_value(value: &str) -> std::result::Result<(), String> {
+    let Some((index, c)) = value
+        .char_indices()
+        .find(|(_, c)| c.is_ascii_control() && *c != '\t')
+    else {
+        return Ok(());
+    };
+
+    let character = match c {
+        '\r' => "CR".to_string(),
+        '\n' => "LF".to_string(),
+        '\0' => "NUL".to_string(),
+        c => format!("control character {:?}", c),
+    };
+    Err(format!("value contains {} at byte {}", character, index))
+}
+
+// NOTE: Bytes outside ASCII go out as-is, servers typically read them as
+// Latin-1 rather than UTF-8.
+pub(crate) fn is_non_ascii(value: &str) -> bool {
+    !value.is_ascii()
+}
+
+pub(crate) fn sanitize(headers: Headers) -> Headers {
+    let suppressed = headers.suppressed().cloned().collect::<Vec<_>>();
+    let mut sanitized = headers
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let (name, value) = (name.trim().to_string(), value.trim().to_string());
+            if value.is_empty() {
+                tracing::warn!(name = %name, "Dropping header without a value");
+                return None;
+            }
+            Some((name, value))
+        })
+        .collect::<Headers>();
+    for name in suppressed {
+        sanitized.suppress(name.trim());
+    }
+    sanitized
+}
+
+// NOTE: curl keeps a single header list per handle and every `http_headers`
+// call replaces the previous one, so all headers for a transfer are collected
+// here first and handed to curl exactly once in `apply`.
+#[derive(Debug, Default)]
+pub(crate) struct HeadersBuilder {
+    headers: Headers,
+    sanitize: bool,
+    redaction: Option<Arc<RedactionPolicy>>,
+}
+
+impl HeadersBuilder {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn sanitize(mut self, sanitize: bool) -> Self {
+        self.sanitize = sanitize;
+        self
+    }
+
+    pub(crate) fn redaction(mut self, policy: Arc<RedactionPolicy>) -> Self {
+        self.redaction = Some(policy);
+        self
+    }
+
+    /// Layers `headers` on top of the list, replacing earlier values with the
+    /// same name regardless of case.
+    #[tracing::instrument(skip_all, level = "debug")]
+    pub(crate) fn add_headers(&mut self, headers: impl Into<Headers>) -> Result<&mut Self> {
+        let mut layer = headers.into();
+        if self.sanitize {
+            layer = sanitize(layer);
+        }
+
+        for name in layer.keys().chain(layer.suppressed()) {
+            check_name(name).map_err(|reason| invalid_header(name, reason))?;
+        }
+        for (name, value) in &layer {
+            check_value(value).map_err(|reason| invalid_header(name, reason))?;
+            if is_non_ascii(value) {
+                tracing::warn!(name = %name, "Header value contains non-ASCII characters");
             }
         }
 
-        let header_count = header_map.len();
-        tracing::info!(header_count, "Building header list");
+        self.headers.merge(layer);
+        Ok(self)
+    }
+
+    pub(crate) fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    // NOTE: Uses curl's header list conventions, `Name:` removes a header curl
+    // would add on its own and `Name;` sends the header with an empty value,
+    // see: https://curl.se/libcurl/c/CURLOPT_HTTPHEADER.html
+    #[tracing::instrument(skip_all, level = "debug")]
+    pub(crate) fn apply(self, handle: &mut Easy) -> Result<()> {
+        if self.headers.is_empty() {
+            tracing::debug!("No headers to set");
+            return Ok(());
+        }
 
-        let list = header_map
+        let header_count = self.headers.len();
+        let suppressed_count = self.headers.suppressed().count();
+        tracing::info!(header_count, suppressed_count, "Building header list");
+
+        let policy = self.redaction.clone().unwrap_or_else(redact::policy);
+
+        let lines = self
+            .headers
             .iter()
             .map(|(key, value)| {
-                let key_str = key.as_str();
-                let value_str = value.to_str().unwrap_or("");
-                tracing::debug!(
-                    key = ?key_str,
-                    value_count = value_str.len(),
-                    value = ?value_str,
-                    "Processing headers"
-                );
-                let header = format!("{}: {}", key_str, value_str);
-                tracing::debug!(%header, "Adding header");
-                header
+                if value.is_empty() {
+                    (format!("{};", key), format!("{};", key))
+                } else {
+                    (
+                        format!("{}: {}", key, value),
+                        format!("{}: {}", key, policy.header_value(key, value)),
+                    )
+                }
             })
-            .try_fold(List::new(), |mut list, header| {
-                list.append(&header).map_err(|e| {
-                    tracing::error!(%e, "Failed to append header: {header}");
+            .chain(
+                self.headers
+                    .suppressed()
+                    .map(|key| (format!("{}:", key), format!("{}:", key))),
+            )
+            .collect::<Vec<_>>();
+
+        tracing::debug!(
+            headers = ?lines.iter().map(|(_, redacted)| redacted).collect::<Vec<_>>(),
+            "Effective request headers"
+        );
+
+        let list = lines
+            .iter()
+            .try_fold(List::new(), |mut list, (header, redacted)| {
+                list.append(header).map_err(|e| {
+                    tracing::error!(%e, "Failed to append header: {redacted}");
                     RelayError::Network {
-                        message: format!("Failed to append header: {header}"),
-                        cause: Some(e.to_string()),
+                        message: format!("Failed to append header: {redacted}"),
+                        cause: Some(e.into()),
                     }
                 })?;
                 Ok(list)
             })?;
 
-        self.handle.http_headers(list).map_err(|e| {
+        handle.http_headers(list).map_err(|e| {
             tracing::error!(%e, "Failed to set headers");
             RelayError::Network {
                 message: "Failed to set headers".into(),
-                cause: Some(e.to_string()),
+                cause: Some(e.into()),
             }
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tracing::{span, Event, Level, Metadata, Subscriber};
+
+    use super::*;
+
+    // NOTE: Counts warnings, the crate has no subscriber of its own to test
+    // against.
+    struct Warnings(Arc<AtomicUsize>);
+
+    impl Subscriber for Warnings {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+            span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            if *event.metadata().level() == Level::WARN {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        fn enter(&self, _: &span::Id) {}
+
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    fn warnings(f: impl FnOnce()) -> usize {
+        let count = Arc::new(AtomicUsize::new(0));
+        tracing::subscriber::with_default(Warnings(count.clone()), f);
+        count.load(Ordering::SeqCst)
+    }
+
+    fn headers(pairs: &[(&str, &str)]) -> Headers {
+        let mut headers = Headers::new();
+        for (name, value) in pairs {
+            headers.append(*name, *value);
+        }
+        headers
+    }
+
+    #[test]
+    fn accepts_token_names() {
+        for name in ["Content-Type", "x-custom_1", "A!#$%&'*+-.^_`|~z"] {
+            assert_eq!(check_name(name), Ok(()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_non_token_names() {
+        assert_eq!(check_name(""), Err("name is empty".into()));
+        for (name, c, index) in [
+            ("X Custom", ' ', 1),
+            ("X-Custom:", ':', 8),
+            ("X-(a)", '(', 2),
+            ("X-Caf\u{e9}", '\u{e9}', 5),
+            ("X-\r\nInjected", '\r', 2),
+        ] {
+            assert_eq!(
+                check_name(name),
+                Err(format!(
+                    "name contains {:?} at byte {}, only token characters are allowed",
+                    c, index
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_cr_lf_and_nul_in_values() {
+        assert_eq!(
+            check_value("a\r\nX-Injected: 1"),
+            Err("value contains CR at byte 1".into())
+        );
+        assert_eq!(
+            check_value("ab\n"),
+            Err("value contains LF at byte 2".into())
+        );
+        assert_eq!(
+            check_value("\0"),
+            Err("value contains NUL at byte 0".into())
+        );
+        assert_eq!(
+            check_value("a\u{7f}"),
+            Err("value contains control character '\\u{7f}' at byte 1".into())
+        );
+    }
+
+    #[test]
+    fn accepts_tabs_and_non_ascii_values() {
+        assert_eq!(check_value("a\tb"), Ok(()));
+        assert_eq!(check_value("caf\u{e9}"), Ok(()));
+        assert_eq!(check_value(""), Ok(()));
+    }
+
+    #[test]
+    fn builder_rejects_invalid_headers() {
+        for pairs in [[("X-Bad Name", "1")], [("X-Good", "a\nb")]] {
+            let error = HeadersBuilder::new()
+                .add_headers(headers(&pairs))
+                .map(|_| ())
+                .unwrap_err();
+            let RelayError::InvalidHeader { name, .. } = error else {
+                panic!("expected an invalid header error, got {:?}", error);
+            };
+            assert_eq!(name, pairs[0].0);
+        }
+    }
+
+    #[test]
+    fn warns_once_per_non_ascii_value() {
+        let count = warnings(|| {
+            HeadersBuilder::new()
+                .add_headers(headers(&[("X-Name", "caf\u{e9}"), ("X-Plain", "cafe")]))
+                .unwrap();
+        });
+        assert_eq!(count, 1);
+        assert!(is_non_ascii("caf\u{e9}"));
+        assert!(!is_non_ascii("cafe\t"));
+    }
+
+    #[test]
+    fn sanitize_trims_and_drops_empty_values() {
+        let mut raw = headers(&[(" X-A ", " 1 "), ("X-Empty", "  "), ("X-B", "2")]);
+        raw.suppress(" Accept ");
+
+        let sanitized = sanitize(raw);
+        let pairs = sanitized
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, [("X-A", "1"), ("X-B", "2")]);
+        assert_eq!(sanitized.suppressed().collect::<Vec<_>>(), ["Accept"]);
+    }
+
+    #[test]
+    fn sanitize_mode_accepts_padded_names() {
+        let padded = headers(&[("X-A ", "1")]);
+        assert!(HeadersBuilder::new().add_headers(padded.clone()).is_err());
+
+        let mut builder = HeadersBuilder::new().sanitize(true);
+        builder.add_headers(padded).unwrap();
+        assert_eq!(builder.headers().get("x-a"), Some("1"));
+    }
+}
@@ -0,0 +1,55 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicI64, Ordering},
+};
+
+use crate::interop::Request;
+
+// NOTE: `0` is reserved for requests that still need an id, allocated ids
+// start at 1 and are unique for the lifetime of the process.
+pub const UNASSIGNED_REQUEST_ID: i64 = 0;
+
+static NEXT_REQUEST_ID: AtomicI64 = AtomicI64::new(1);
+
+pub fn next_request_id() -> i64 {
+    NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+impl Request {
+    // NOTE: Caller supplied ids are kept as-is, only an unassigned id is
+    // replaced. Returns the id the request will be executed and logged with.
+    pub fn ensure_id(&mut self) -> i64 {
+        if self.id == UNASSIGNED_REQUEST_ID {
+            self.id = next_request_id();
+            tracing::debug!(request_id = self.id, "Assigned request id");
+        }
+        self.id
+    }
+}
+
+// NOTE: Opt-in correlation header carrying the request id, nothing is added
+// when the caller already set a header with the configured name.
+pub(crate) fn headers(request: &Request) -> HashMap<String, String> {
+    let mut headers = HashMap::new();
+
+    let Some(name) = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.request_id_header.as_ref())
+        .filter(|name| !name.is_empty())
+    else {
+        return headers;
+    };
+
+    let explicit = request
+        .headers
+        .as_ref()
+        .is_some_and(|headers| headers.contains_key(name));
+
+    if !explicit {
+        headers.insert(name.clone(), request.id.to_string());
+    }
+
+    headers
+}
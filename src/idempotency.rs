@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use crate::interop::{IdempotencyKey, Request};
+
+pub(crate) const DEFAULT_HEADER: &str = "Idempotency-Key";
+
+// NOTE: A random (version 4) UUID in its hyphenated form.
+fn generate() -> String {
+    let mut bytes = rand::random::<[u8; 16]>();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn header_name(request: &Request) -> &str {
+    request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.idempotency_header.as_deref())
+        .filter(|name| !name.is_empty())
+        .unwrap_or(DEFAULT_HEADER)
+}
+
+fn key_option(request: &Request) -> Option<&IdempotencyKey> {
+    request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.idempotency_key.as_ref())
+}
+
+fn explicit_header<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request
+        .headers
+        .as_ref()
+        .and_then(|headers| headers.get(name))
+}
+
+impl Request {
+    // NOTE: Turns `IdempotencyKey::Auto` into a fixed key, so every attempt
+    // of this request sends the same one. Returns the key the request will
+    // be sent with, a header set on the request itself included.
+    pub fn ensure_idempotency_key(&mut self) -> Option<String> {
+        if key_option(self) == Some(&IdempotencyKey::Auto)
+            && explicit_header(self, header_name(self)).is_none()
+        {
+            let generated = generate();
+            tracing::debug!(key = %generated, "Generated idempotency key");
+            if let Some(options) = self.meta.as_mut().and_then(|meta| meta.options.as_mut()) {
+                options.idempotency_key = Some(IdempotencyKey::Fixed(generated));
+            }
+        }
+        key(self)
+    }
+
+    // NOTE: Whether retrying cannot apply the request twice, because the
+    // server deduplicates on the key.
+    pub(crate) fn has_idempotency_key(&self) -> bool {
+        explicit_header(self, header_name(self)).is_some()
+            || matches!(
+                key_option(self),
+                Some(IdempotencyKey::Auto | IdempotencyKey::Fixed(_))
+            )
+    }
+}
+
+// NOTE: The key a pinned request is sent with, `Auto` keys are only known
+// after `ensure_idempotency_key`.
+pub(crate) fn key(request: &Request) -> Option<String> {
+    if let Some(key) = explicit_header(request, header_name(request)) {
+        return Some(key.to_string());
+    }
+    match key_option(request) {
+        Some(IdempotencyKey::Fixed(key)) => Some(key.clone()),
+        _ => None,
+    }
+}
+
+// NOTE: An `Auto` key still unresolved here, e.g. when rendering a request
+// that was never sent, gets a fresh key each time.
+pub(crate) fn headers(request: &Request) -> HashMap<String, String> {
+    let mut headers = HashMap::new();
+
+    let name = header_name(request);
+    if explicit_header(request, name).is_some()
+        || request
+            .headers
+            .as_ref()
+            .is_some_and(|headers| headers.is_suppressed(name))
+    {
+        return headers;
+    }
+
+    let key = match key_option(request) {
+        Some(IdempotencyKey::Fixed(key)) => key.clone(),
+        Some(IdempotencyKey::Auto) => generate(),
+        None | Some(IdempotencyKey::None) => return headers,
+    };
+    headers.insert(name.to_string(), key);
+
+    headers
+}
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+use crate::interop::Request;
+
+pub mod openapi;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NamedRequest {
+    pub name: String,
+    pub summary: Option<String>,
+    pub request: Request,
+}
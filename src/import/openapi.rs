@@ -0,0 +1,685 @@
+use std::{collections::HashMap, str::FromStr};
+
+use bytes::Bytes;
+use http::{Method, Version};
+use serde_json::{Map, Value};
+
+use crate::{
+    content,
+    error::{RelayError, Result},
+    header::Headers,
+    interop::{
+        ApiKeyLocation, AuthType, ContentType, FormValue, GrantType, MediaType, QueryValue, Request,
+    },
+};
+
+use super::NamedRequest;
+
+const METHODS: &[&str] = &[
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+// NOTE: Bounds both `$ref` chains and how deeply example bodies nest.
+const MAX_DEPTH: usize = 16;
+
+struct Spec<'a> {
+    root: &'a Value,
+}
+
+impl<'a> Spec<'a> {
+    fn resolve(&self, mut value: &'a Value) -> Result<&'a Value> {
+        for _ in 0..MAX_DEPTH {
+            let Some(reference) = value.get("$ref").and_then(Value::as_str) else {
+                return Ok(value);
+            };
+
+            let Some(pointer) = reference.strip_prefix('#') else {
+                tracing::error!(reference, "Remote reference in OpenAPI document");
+                return Err(RelayError::Parse {
+                    message: format!(
+                        "Remote reference '{}' is not supported, only references within the document can be resolved",
+                        reference
+                    ),
+                    cause: None,
+                    partial: None,
+                });
+            };
+
+            let pointer = urlencoding::decode(pointer).map_err(|e| {
+                tracing::error!(reference, error = %e, "Failed to decode reference");
+                RelayError::Parse {
+                    message: format!("Invalid reference '{}'", reference),
+                    cause: Some(e.into()),
+                    partial: None,
+                }
+            })?;
+
+            value = self.root.pointer(&pointer).ok_or_else(|| {
+                tracing::error!(reference, "Reference target not found");
+                RelayError::Parse {
+                    message: format!("Reference '{}' does not point into the document", reference),
+                    cause: None,
+                    partial: None,
+                }
+            })?;
+        }
+
+        tracing::error!("Reference chain too deep");
+        Err(RelayError::Parse {
+            message: format!(
+                "Reference chain exceeds {} levels, likely a cycle",
+                MAX_DEPTH
+            ),
+            cause: None,
+            partial: None,
+        })
+    }
+
+    fn explicit_example(&self, value: &'a Value) -> Result<Option<Value>> {
+        if let Some(example) = value.get("example") {
+            return Ok(Some(example.clone()));
+        }
+
+        match value.get("examples") {
+            Some(Value::Object(examples)) => match examples.values().next() {
+                Some(example) => Ok(self.resolve(example)?.get("value").cloned()),
+                None => Ok(None),
+            },
+            // NOTE: JSON Schema 2020-12, used by OpenAPI 3.1, lists examples
+            // as a plain array on the schema itself.
+            Some(Value::Array(examples)) => Ok(examples.first().cloned()),
+            _ => Ok(None),
+        }
+    }
+
+    fn schema_hint(&self, schema: &'a Value) -> Result<Option<Value>> {
+        let schema = self.resolve(schema)?;
+
+        if let Some(example) = self.explicit_example(schema)? {
+            return Ok(Some(example));
+        }
+        if let Some(default) = schema.get("default") {
+            return Ok(Some(default.clone()));
+        }
+        if let Some(value) = schema
+            .get("enum")
+            .and_then(Value::as_array)
+            .and_then(|values| values.first())
+        {
+            return Ok(Some(value.clone()));
+        }
+        Ok(schema.get("const").cloned())
+    }
+
+    // NOTE: `None` marks a schema already being synthesized further up, the
+    // caller drops that property or array item instead of repeating it.
+    fn synthesize(&self, schema: &'a Value, stack: &mut Vec<&'a Value>) -> Result<Option<Value>> {
+        let schema = self.resolve(schema)?;
+        if stack.len() >= MAX_DEPTH || stack.iter().any(|seen| std::ptr::eq(*seen, schema)) {
+            return Ok(None);
+        }
+
+        stack.push(schema);
+        let value = self.synthesize_schema(schema, stack);
+        stack.pop();
+        value.map(Some)
+    }
+
+    fn synthesize_schema(&self, schema: &'a Value, stack: &mut Vec<&'a Value>) -> Result<Value> {
+        if let Some(value) = self.schema_hint(schema)? {
+            return Ok(value);
+        }
+
+        if let Some(parts) = schema.get("allOf").and_then(Value::as_array) {
+            let mut merged = Map::new();
+            for part in parts {
+                match self.synthesize(part, stack)? {
+                    Some(Value::Object(fields)) => merged.extend(fields),
+                    Some(other) if parts.len() == 1 => return Ok(other),
+                    _ => {}
+                }
+            }
+            return Ok(Value::Object(merged));
+        }
+
+        for keyword in ["oneOf", "anyOf"] {
+            if let Some(first) = schema
+                .get(keyword)
+                .and_then(Value::as_array)
+                .and_then(|variants| variants.first())
+            {
+                return Ok(self.synthesize(first, stack)?.unwrap_or(Value::Null));
+            }
+        }
+
+        // NOTE: OpenAPI 3.1 allows `type: [string, "null"]`, the first
+        // non-null type is the useful one for an example.
+        let kind = match schema.get("type") {
+            Some(Value::String(kind)) => Some(kind.as_str()),
+            Some(Value::Array(kinds)) => kinds
+                .iter()
+                .filter_map(Value::as_str)
+                .find(|kind| *kind != "null"),
+            _ => None,
+        };
+
+        Ok(match kind {
+            Some("object") | None if schema.get("properties").is_some() => {
+                let mut fields = Map::new();
+                if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+                    for (name, property) in properties {
+                        if self
+                            .resolve(property)?
+                            .get("readOnly")
+                            .and_then(Value::as_bool)
+                            == Some(true)
+                        {
+                            continue;
+                        }
+                        if let Some(value) = self.synthesize(property, stack)? {
+                            fields.insert(name.clone(), value);
+                        }
+                    }
+                }
+                Value::Object(fields)
+            }
+            Some("object") => Value::Object(Map::new()),
+            Some("array") => Value::Array(match schema.get("items") {
+                Some(items) => self.synthesize(items, stack)?.into_iter().collect(),
+                None => Vec::new(),
+            }),
+            Some("string") => Value::String(
+                match schema.get("format").and_then(Value::as_str) {
+                    Some("date-time") => "1970-01-01T00:00:00Z",
+                    Some("date") => "1970-01-01",
+                    Some("time") => "00:00:00Z",
+                    Some("email") => "user@example.com",
+                    Some("uuid") => "00000000-0000-0000-0000-000000000000",
+                    Some("uri") | Some("url") => "https://example.com",
+                    Some("ipv4") => "127.0.0.1",
+                    Some("ipv6") => "::1",
+                    Some("binary") | Some("byte") => "",
+                    _ => "string",
+                }
+                .into(),
+            ),
+            Some("integer") => schema
+                .get("minimum")
+                .and_then(Value::as_i64)
+                .map_or(Value::from(0), Value::from),
+            Some("number") => schema
+                .get("minimum")
+                .and_then(Value::as_f64)
+                .map_or(Value::from(0.0), Value::from),
+            Some("boolean") => Value::Bool(false),
+            _ => Value::Null,
+        })
+    }
+
+    // NOTE: Parameters without an example, default or enum become `{{name}}`
+    // placeholders, ready for `Request::resolve`.
+    fn parameter_value(&self, parameter: &'a Value, name: &str) -> Result<Value> {
+        if let Some(example) = self.explicit_example(parameter)? {
+            return Ok(example);
+        }
+        if let Some(schema) = parameter.get("schema") {
+            if let Some(value) = self.schema_hint(schema)? {
+                return Ok(value);
+            }
+        }
+        Ok(Value::String(format!("{{{{{}}}}}", name)))
+    }
+
+    fn servers(&self, scopes: &[&'a Value]) -> Option<String> {
+        let server = scopes
+            .iter()
+            .filter_map(|scope| scope.get("servers").and_then(Value::as_array))
+            .find_map(|servers| servers.first())?;
+
+        let mut url = server.get("url").and_then(Value::as_str)?.to_string();
+        if let Some(variables) = server.get("variables").and_then(Value::as_object) {
+            for (name, variable) in variables {
+                if let Some(default) = variable.get("default").and_then(Value::as_str) {
+                    url = url.replace(&format!("{{{}}}", name), default);
+                }
+            }
+        }
+        Some(url)
+    }
+
+    fn security_scheme(&self, name: &str) -> Result<Option<&'a Value>> {
+        match self
+            .root
+            .pointer("/components/securitySchemes")
+            .and_then(|schemes| schemes.get(name))
+        {
+            Some(scheme) => self.resolve(scheme).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn auth(&self, operation: &'a Value) -> Result<Option<AuthType>> {
+        let requirements = operation
+            .get("security")
+            .or_else(|| self.root.get("security"))
+            .and_then(Value::as_array);
+
+        for requirement in requirements.into_iter().flatten() {
+            for name in requirement.as_object().into_iter().flat_map(Map::keys) {
+                let Some(scheme) = self.security_scheme(name)? else {
+                    tracing::warn!(scheme = %name, "Security scheme not declared in components");
+                    continue;
+                };
+
+                if let Some(auth) = auth_from_scheme(name, scheme) {
+                    return Ok(Some(auth));
+                }
+                tracing::warn!(scheme = %name, "Skipping unsupported security scheme");
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+fn auth_from_scheme(name: &str, scheme: &Value) -> Option<AuthType> {
+    let field = |key: &str| scheme.get(key).and_then(Value::as_str);
+
+    match field("type")? {
+        "http" => match field("scheme")?.to_ascii_lowercase().as_str() {
+            "basic" => Some(AuthType::Basic {
+                username: "{{username}}".into(),
+                password: "{{password}}".into(),
+            }),
+            "bearer" => Some(AuthType::Bearer {
+                token: "{{token}}".into(),
+            }),
+            _ => None,
+        },
+        "apiKey" => Some(AuthType::ApiKey {
+            key: field("name")?.to_string(),
+            value: format!("{{{{{}}}}}", name),
+            location: match field("in")? {
+                "header" => ApiKeyLocation::Header,
+                "query" => ApiKeyLocation::Query,
+                _ => return None,
+            },
+        }),
+        "oauth2" => {
+            let flows = scheme.get("flows")?;
+            let url = |flow: &Value, key: &str| {
+                flow.get(key)
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string()
+            };
+
+            let grant_type = if let Some(flow) = flows.get("authorizationCode") {
+                GrantType::AuthorizationCode {
+                    auth_endpoint: url(flow, "authorizationUrl"),
+                    token_endpoint: url(flow, "tokenUrl"),
+                    client_id: "{{client_id}}".into(),
+                    client_secret: Some("{{client_secret}}".into()),
+                }
+            } else if let Some(flow) = flows.get("clientCredentials") {
+                GrantType::ClientCredentials {
+                    token_endpoint: url(flow, "tokenUrl"),
+                    client_id: "{{client_id}}".into(),
+                    client_secret: Some("{{client_secret}}".into()),
+                }
+            } else if let Some(flow) = flows.get("password") {
+                GrantType::Password {
+                    token_endpoint: url(flow, "tokenUrl"),
+                    username: "{{username}}".into(),
+                    password: "{{password}}".into(),
+                }
+            } else {
+                let flow = flows.get("implicit")?;
+                GrantType::Implicit {
+                    auth_endpoint: url(flow, "authorizationUrl"),
+                    client_id: "{{client_id}}".into(),
+                }
+            };
+
+            Some(AuthType::OAuth2 {
+                grant_type,
+                access_token: Some("{{access_token}}".into()),
+                refresh_token: None,
+            })
+        }
+        "openIdConnect" => Some(AuthType::Bearer {
+            token: "{{access_token}}".into(),
+        }),
+        _ => None,
+    }
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn is_json(media_type: &str) -> bool {
+    let essence = media_type.split(';').next().unwrap_or_default().trim();
+    essence == "application/json" || essence.ends_with("+json")
+}
+
+fn pick_media_type(content: &Map<String, Value>) -> Option<(&String, &Value)> {
+    content
+        .iter()
+        .find(|(media_type, _)| media_type.as_str() == "application/json")
+        .or_else(|| content.iter().find(|(media_type, _)| is_json(media_type)))
+        .or_else(|| {
+            content
+                .iter()
+                .find(|(media_type, _)| media_type.starts_with("application/x-www-form-urlencoded"))
+        })
+        .or_else(|| {
+            content
+                .iter()
+                .find(|(media_type, _)| media_type.starts_with("multipart/form-data"))
+        })
+        .or_else(|| content.iter().next())
+}
+
+fn content_from_example(media_type: &str, example: Value) -> ContentType {
+    let essence = media_type.split(';').next().unwrap_or_default().trim();
+    let parsed = MediaType::from_str(essence).ok();
+
+    let fields = |example: &Value| -> Vec<(String, String)> {
+        example
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(name, value)| (name.clone(), value_text(value)))
+            .collect()
+    };
+
+    match essence {
+        "application/json-patch+json" => ContentType::JsonPatch {
+            operations: example,
+        },
+        "application/merge-patch+json" => ContentType::JsonMergePatch { content: example },
+        _ if is_json(essence) => ContentType::Json {
+            content: example,
+            raw: None,
+            media_type: parsed.unwrap_or(MediaType::Json),
+        },
+        "application/x-www-form-urlencoded" => ContentType::Urlencoded {
+            content: url::form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(fields(&example))
+                .finish(),
+            media_type: MediaType::FormUrlEncoded,
+        },
+        "multipart/form-data" => ContentType::Multipart {
+            content: fields(&example)
+                .into_iter()
+                .map(|(name, value)| (name, vec![FormValue::Text { value }]))
+                .collect(),
+            media_type: MediaType::MultipartFormData,
+        },
+        _ if essence.ends_with("xml") => ContentType::Xml {
+            content: example.as_str().unwrap_or_default().to_string(),
+            media_type: parsed.unwrap_or(MediaType::Xml),
+        },
+        _ if essence.starts_with("text/") => ContentType::Text {
+            content: value_text(&example),
+            media_type: parsed.unwrap_or(MediaType::TextPlain),
+        },
+        _ => ContentType::Binary {
+            content: Bytes::from(example.as_str().unwrap_or_default().to_string()),
+            media_type: parsed.unwrap_or(MediaType::OctetStream),
+            filename: None,
+        },
+    }
+}
+
+fn join_url(base: Option<&str>, server: Option<String>) -> Option<String> {
+    let server = server.unwrap_or_default();
+    let server_is_absolute = url::Url::parse(&server).is_ok();
+
+    match base {
+        Some(base) if server_is_absolute || server.is_empty() => Some(base.to_string()),
+        Some(base) => Some(format!(
+            "{}/{}",
+            base.trim_end_matches('/'),
+            server.trim_start_matches('/')
+        )),
+        None if server_is_absolute => Some(server),
+        None => None,
+    }
+}
+
+struct Operation<'a> {
+    path: &'a str,
+    method: &'a str,
+    item: &'a Value,
+    operation: &'a Value,
+}
+
+impl Spec<'_> {
+    fn request(&self, id: i64, base_url: Option<&str>, op: &Operation<'_>) -> Result<Request> {
+        let method =
+            Method::from_str(&op.method.to_ascii_uppercase()).map_err(|e| RelayError::Parse {
+                message: format!("Invalid method '{}'", op.method),
+                cause: Some(e.into()),
+                partial: None,
+            })?;
+
+        let base = join_url(base_url, self.servers(&[op.operation, op.item, self.root]))
+            .ok_or_else(|| {
+                tracing::error!(path = op.path, "No absolute server URL for operation");
+                RelayError::Parse {
+                    message: format!(
+                        "No absolute server URL for '{} {}', pass a base URL",
+                        op.method.to_ascii_uppercase(),
+                        op.path
+                    ),
+                    cause: None,
+                    partial: None,
+                }
+            })?;
+
+        // NOTE: Operation parameters override path item parameters sharing
+        // the same name and location.
+        let mut parameters: Vec<&Value> = Vec::new();
+        for parameter in [op.item, op.operation]
+            .iter()
+            .filter_map(|scope| scope.get("parameters").and_then(Value::as_array))
+            .flatten()
+        {
+            let parameter = self.resolve(parameter)?;
+            let key = |p: &Value| (p.get("name").cloned(), p.get("in").cloned());
+            parameters.retain(|existing| key(existing) != key(parameter));
+            parameters.push(parameter);
+        }
+
+        let mut path = op.path.to_string();
+        let mut headers = Headers::new();
+        let mut params: HashMap<String, QueryValue> = HashMap::new();
+        let mut cookies: Vec<String> = Vec::new();
+
+        for parameter in parameters {
+            let Some(name) = parameter.get("name").and_then(Value::as_str) else {
+                continue;
+            };
+            let location = parameter
+                .get("in")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let required = parameter.get("required").and_then(Value::as_bool) == Some(true);
+
+            if location != "path" && !required {
+                continue;
+            }
+
+            let value = self.parameter_value(parameter, name)?;
+            match location {
+                "path" => {
+                    let text = value_text(&value);
+                    let text = if text.starts_with("{{") {
+                        text
+                    } else {
+                        urlencoding::encode(&text).into_owned()
+                    };
+                    path = path.replace(&format!("{{{}}}", name), &text);
+                }
+                "query" => {
+                    let value = match value {
+                        Value::Array(values) => {
+                            QueryValue::Multiple(values.iter().map(value_text).collect())
+                        }
+                        value => QueryValue::Single(value_text(&value)),
+                    };
+                    params.insert(name.to_string(), value);
+                }
+                // NOTE: The spec says these three header parameters are
+                // ignored, they are derived from the body and security instead.
+                "header"
+                    if ["accept", "content-type", "authorization"]
+                        .contains(&name.to_ascii_lowercase().as_str()) => {}
+                "header" => {
+                    headers.insert(name.to_string(), value_text(&value));
+                }
+                "cookie" => cookies.push(format!("{}={}", name, value_text(&value))),
+                _ => {}
+            }
+        }
+
+        if !cookies.is_empty() {
+            headers.insert("Cookie", cookies.join("; "));
+        }
+
+        let mut content = None;
+        if let Some(body) = op.operation.get("requestBody") {
+            let body = self.resolve(body)?;
+            if let Some((media_type, media)) = body
+                .get("content")
+                .and_then(Value::as_object)
+                .and_then(pick_media_type)
+            {
+                let example = match self.explicit_example(media)? {
+                    Some(example) => example,
+                    None => match media.get("schema") {
+                        Some(schema) => self
+                            .synthesize(schema, &mut Vec::new())?
+                            .unwrap_or(Value::Null),
+                        None => Value::Null,
+                    },
+                };
+
+                let body = content_from_example(media_type, example);
+                if !matches!(
+                    body,
+                    ContentType::Form { .. } | ContentType::Multipart { .. }
+                ) {
+                    headers.insert("Content-Type", media_type.clone());
+                }
+                headers.merge(content::content_headers(&body));
+                content = Some(body);
+            }
+        }
+
+        Ok(Request {
+            id,
+            url: format!("{}{}", base.trim_end_matches('/'), path),
+            method,
+            version: Version::HTTP_11,
+            headers: (!headers.is_empty()).then_some(headers),
+            params: (!params.is_empty()).then_some(params),
+            content,
+            auth: self.auth(op.operation)?,
+            security: None,
+            proxy: None,
+            meta: None,
+            trace_context: None,
+            range: None,
+            accept: None,
+            raw_options: Vec::new(),
+            secrets: Default::default(),
+            target_policy: None,
+        })
+    }
+}
+
+#[tracing::instrument(skip(spec_json), level = "debug")]
+pub fn requests_from_spec(spec_json: &str, base_url: Option<&str>) -> Result<Vec<NamedRequest>> {
+    let root: Value = serde_json::from_str(spec_json).map_err(|e| {
+        tracing::error!(error = %e, "Failed to parse OpenAPI document");
+        RelayError::Parse {
+            message: "Invalid OpenAPI document".into(),
+            cause: Some(e.into()),
+            partial: None,
+        }
+    })?;
+
+    match root.get("openapi").and_then(Value::as_str) {
+        Some(version) if version.starts_with("3.") => {
+            tracing::debug!(version, "Importing OpenAPI document")
+        }
+        version => {
+            let version = version
+                .or_else(|| root.get("swagger").and_then(Value::as_str))
+                .unwrap_or("unknown");
+            tracing::error!(version, "Unsupported OpenAPI version");
+            return Err(RelayError::Parse {
+                message: format!(
+                    "Unsupported OpenAPI version '{}', expected 3.0 or 3.1",
+                    version
+                ),
+                cause: None,
+                partial: None,
+            });
+        }
+    }
+
+    let spec = Spec { root: &root };
+    let mut requests = Vec::new();
+
+    for (path, item) in root
+        .get("paths")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+    {
+        let item = spec.resolve(item)?;
+
+        for method in METHODS {
+            let Some(operation) = item.get(*method) else {
+                continue;
+            };
+
+            let op = Operation {
+                path,
+                method,
+                item,
+                operation,
+            };
+            let request = spec.request(requests.len() as i64, base_url, &op)?;
+
+            let name = operation
+                .get("operationId")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("{} {}", method.to_ascii_uppercase(), path));
+
+            requests.push(NamedRequest {
+                name,
+                summary: operation
+                    .get("summary")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                request,
+            });
+        }
+    }
+
+    tracing::info!(
+        count = requests.len(),
+        "Imported requests from OpenAPI document"
+    );
+    Ok(requests)
+}
@@ -0,0 +1,190 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use openssl::hash::{Hasher, MessageDigest};
+
+use crate::{
+    error::{RelayError, Result},
+    interop::{BodyHash, HashAlgorithm},
+};
+
+impl HashAlgorithm {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Sha384 => "sha384",
+            Self::Sha512 => "sha512",
+        }
+    }
+
+    fn message_digest(self) -> MessageDigest {
+        match self {
+            Self::Sha256 => MessageDigest::sha256(),
+            Self::Sha384 => MessageDigest::sha384(),
+            Self::Sha512 => MessageDigest::sha512(),
+        }
+    }
+}
+
+impl BodyHash {
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        Self {
+            algorithm,
+            expected: None,
+        }
+    }
+
+    pub fn expect(mut self, expected: impl Into<String>) -> Self {
+        self.expected = Some(expected.into());
+        self
+    }
+}
+
+pub(crate) fn hasher(config: &BodyHash) -> Result<Hasher> {
+    Hasher::new(config.algorithm.message_digest()).map_err(|e| RelayError::Io {
+        message: format!("Failed to start {} body hash", config.algorithm.as_str()),
+        cause: Some(e.into()),
+    })
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [high, low] => u8::from_str_radix(std::str::from_utf8(&[*high, *low]).ok()?, 16).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+// NOTE: One expected value, `None` when it names another algorithm. Hex is
+// tried before base64, since a hex digest is also valid base64.
+fn expected_digest(value: &str, algorithm: HashAlgorithm) -> Option<Vec<u8>> {
+    let value = value.split('?').next().unwrap_or_default();
+    match value.split_once('-') {
+        Some((name, digest)) => {
+            if !name.eq_ignore_ascii_case(algorithm.as_str()) {
+                return None;
+            }
+            BASE64.decode(digest).ok()
+        }
+        None => decode_hex(value).or_else(|| BASE64.decode(value).ok()),
+    }
+}
+
+// NOTE: Passes when any of the expected values matches, as subresource
+// integrity does. Values for another algorithm are skipped, but at least
+// one has to be for this one.
+pub(crate) fn verify(config: &BodyHash, digest: &[u8]) -> Result<()> {
+    let Some(ref expected) = config.expected else {
+        return Ok(());
+    };
+
+    let matched = expected
+        .split_whitespace()
+        .filter_map(|value| expected_digest(value, config.algorithm))
+        .any(|candidate| candidate == digest);
+    if matched {
+        return Ok(());
+    }
+
+    let actual = hex(digest);
+    tracing::error!(
+        algorithm = config.algorithm.as_str(),
+        expected = %expected,
+        actual = %actual,
+        "Response body hash mismatch"
+    );
+    Err(RelayError::BodyHashMismatch {
+        algorithm: config.algorithm,
+        expected: expected.clone(),
+        actual,
+    })
+}
+
+pub(crate) fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NOTE: Digests of `hello`.
+    const SHA256_HEX: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+    const SHA256_SRI: &str = "sha256-LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=";
+    const SHA384_SRI: &str =
+        "sha384-WeF0h3dEjGnea4ANejO7+5/xtGPkQ1TDVTvNucZm+pASWjx5+QOXvfX2oT3oKGhP";
+
+    fn digest(algorithm: HashAlgorithm) -> Vec<u8> {
+        let mut hasher = hasher(&BodyHash::new(algorithm)).unwrap();
+        hasher.update(b"hello").unwrap();
+        hasher.finish().unwrap().to_vec()
+    }
+
+    fn check(algorithm: HashAlgorithm, expected: &str) -> Result<()> {
+        verify(
+            &BodyHash::new(algorithm).expect(expected),
+            &digest(algorithm),
+        )
+    }
+
+    #[test]
+    fn hashes_with_the_chosen_algorithm() {
+        assert_eq!(hex(&digest(HashAlgorithm::Sha256)), SHA256_HEX);
+        assert_eq!(digest(HashAlgorithm::Sha384).len(), 48);
+        assert_eq!(digest(HashAlgorithm::Sha512).len(), 64);
+    }
+
+    #[test]
+    fn accepts_the_correct_hash_in_each_form() {
+        check(HashAlgorithm::Sha256, SHA256_HEX).unwrap();
+        check(HashAlgorithm::Sha256, &SHA256_HEX.to_ascii_uppercase()).unwrap();
+        check(
+            HashAlgorithm::Sha256,
+            "LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=",
+        )
+        .unwrap();
+        check(HashAlgorithm::Sha256, SHA256_SRI).unwrap();
+        check(HashAlgorithm::Sha384, SHA384_SRI).unwrap();
+        // NOTE: Options after `?` are ignored, as in subresource integrity.
+        check(
+            HashAlgorithm::Sha256,
+            &format!("{}?ct=text/plain", SHA256_SRI),
+        )
+        .unwrap();
+        // NOTE: Nothing expected, nothing to reject.
+        verify(&BodyHash::new(HashAlgorithm::Sha256), b"anything").unwrap();
+    }
+
+    #[test]
+    fn any_matching_value_passes() {
+        let several = format!("sha256-AAAA {} {}", SHA384_SRI, SHA256_SRI);
+        check(HashAlgorithm::Sha256, &several).unwrap();
+        check(HashAlgorithm::Sha384, &several).unwrap();
+    }
+
+    #[test]
+    fn rejects_a_mismatch_with_the_actual_hash() {
+        let wrong = "0".repeat(64);
+        match check(HashAlgorithm::Sha256, &wrong) {
+            Err(RelayError::BodyHashMismatch {
+                algorithm,
+                expected,
+                actual,
+            }) => {
+                assert_eq!(algorithm, HashAlgorithm::Sha256);
+                assert_eq!(expected, wrong);
+                assert_eq!(actual, SHA256_HEX);
+            }
+            other => panic!("expected a mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn values_for_another_algorithm_do_not_count() {
+        // NOTE: Right digest, wrong algorithm named.
+        let relabeled = SHA256_SRI.replacen("sha256", "sha512", 1);
+        assert!(check(HashAlgorithm::Sha256, &relabeled).is_err());
+        assert!(check(HashAlgorithm::Sha384, SHA256_SRI).is_err());
+        assert!(check(HashAlgorithm::Sha256, "not a digest").is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use crate::{
+    error::{RelayError, Result},
+    interop::{Request, Response},
+};
+
+pub trait Interceptor: Send + Sync {
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    fn before_send(&self, _request: &mut Request) -> Result<()> {
+        Ok(())
+    }
+
+    fn after_receive(&self, _request: &Request, _response: &mut Response) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct InterceptorChain {
+    interceptors: Vec<Arc<dyn Interceptor>>,
+}
+
+impl InterceptorChain {
+    pub(crate) fn push(&mut self, interceptor: Arc<dyn Interceptor>) {
+        self.interceptors.push(interceptor);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.interceptors.len()
+    }
+
+    #[tracing::instrument(skip(self, request), fields(request_id = request.id), level = "debug")]
+    pub(crate) fn before_send(&self, request: &mut Request) -> Result<()> {
+        for interceptor in &self.interceptors {
+            tracing::trace!(interceptor = %interceptor.name(), "Running before_send");
+            interceptor
+                .before_send(request)
+                .map_err(|e| Self::wrap_error(interceptor.as_ref(), e))?;
+        }
+        Ok(())
+    }
+
+    // NOTE: Responses unwind through the chain in reverse registration order,
+    // so the first interceptor to see the request is the last to see the response.
+    #[tracing::instrument(skip(self, request, response), fields(request_id = request.id), level = "debug")]
+    pub(crate) fn after_receive(&self, request: &Request, response: &mut Response) -> Result<()> {
+        for interceptor in self.interceptors.iter().rev() {
+            tracing::trace!(interceptor = %interceptor.name(), "Running after_receive");
+            interceptor
+                .after_receive(request, response)
+                .map_err(|e| Self::wrap_error(interceptor.as_ref(), e))?;
+        }
+        Ok(())
+    }
+
+    fn wrap_error(interceptor: &dyn Interceptor, error: RelayError) -> RelayError {
+        tracing::error!(interceptor = %interceptor.name(), error = %error, "Interceptor failed");
+        RelayError::Interceptor {
+            name: interceptor.name().to_string(),
+            error: Box::new(error),
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use bytes::Bytes;
 use http::{Method, StatusCode, Version};
@@ -6,7 +6,10 @@ use serde::{Deserialize, Serialize};
 use strum::{Display, EnumString};
 use time::OffsetDateTime;
 
+use crate::{header::Headers, redact::Secrets, target_policy::TargetPolicy};
+
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Display, EnumString)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum MediaType {
     // Text
     #[serde(rename = "text/plain")]
@@ -32,6 +35,12 @@ pub enum MediaType {
     #[serde(rename = "application/ld+json")]
     #[strum(to_string = "application/ld+json")]
     JsonLd,
+    #[serde(rename = "application/json-patch+json")]
+    #[strum(to_string = "application/json-patch+json")]
+    JsonPatch,
+    #[serde(rename = "application/merge-patch+json")]
+    #[strum(to_string = "application/merge-patch+json")]
+    JsonMergePatch,
     #[serde(rename = "application/xml")]
     #[strum(to_string = "application/xml")]
     Xml,
@@ -41,6 +50,9 @@ pub enum MediaType {
     #[serde(rename = "multipart/form-data")]
     #[strum(to_string = "multipart/form-data")]
     MultipartFormData,
+    #[serde(rename = "multipart/related")]
+    #[strum(to_string = "multipart/related")]
+    MultipartRelated,
     #[serde(rename = "application/octet-stream")]
     #[strum(to_string = "application/octet-stream")]
     OctetStream,
@@ -126,6 +138,7 @@ pub enum MediaType {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "kind", rename_all = "camelCase")]
 pub enum FormValue {
     #[serde(rename_all = "camelCase")]
@@ -138,9 +151,12 @@ pub enum FormValue {
     },
 }
 
+// NOTE: Fields are sent in order. A name may appear more than once, which
+// is how a field keeps its place between fields of other names.
 pub type FormData = Vec<(String, Vec<FormValue>)>;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "kind", rename_all = "camelCase")]
 pub enum ContentType {
     #[serde(rename_all = "camelCase")]
@@ -151,13 +167,32 @@ pub enum ContentType {
     #[serde(rename_all = "camelCase")]
     Json {
         content: serde_json::Value,
+        // NOTE: The JSON text as written, sent byte for byte instead of
+        // `content` re-serialized. Keeps integers beyond 64 bits, float digits
+        // and key order intact for servers that sign or hash the body.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        raw: Option<String>,
         media_type: MediaType,
     },
     #[serde(rename_all = "camelCase")]
+    JsonPatch { operations: serde_json::Value },
+    #[serde(rename_all = "camelCase")]
+    JsonMergePatch { content: serde_json::Value },
+    #[serde(rename_all = "camelCase")]
     Xml {
         content: String,
         media_type: MediaType,
     },
+    // NOTE: Sent as `text/csv`, `hasHeader` says whether the first row
+    // names the columns.
+    #[serde(rename_all = "camelCase")]
+    Csv {
+        content: String,
+        #[serde(default)]
+        has_header: bool,
+        #[serde(default = "default_csv_delimiter")]
+        delimiter: char,
+    },
     #[serde(rename_all = "camelCase")]
     Form {
         content: FormData,
@@ -179,6 +214,31 @@ pub enum ContentType {
         content: String,
         media_type: MediaType,
     },
+    // NOTE: RFC 2387, a root document plus the parts it references by
+    // `cid:`. `typeParam` is the root's media type, taken from the root
+    // part when empty.
+    #[serde(rename_all = "camelCase")]
+    MultipartRelated {
+        root: RelatedPart,
+        parts: Vec<RelatedPart>,
+        type_param: String,
+    },
+}
+
+fn default_csv_delimiter() -> char {
+    crate::csv::DEFAULT_DELIMITER
+}
+
+// NOTE: `contentId` is given without the angle brackets it is sent in.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct RelatedPart {
+    pub body: Bytes,
+    pub content_type: String,
+    pub content_id: String,
+    #[serde(default, skip_serializing_if = "Headers::is_empty")]
+    pub headers: Headers,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -191,6 +251,7 @@ pub struct TokenResponse {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "kind", rename_all = "snake_case")]
 pub enum GrantType {
     #[serde(rename_all = "camelCase")]
@@ -220,6 +281,7 @@ pub enum GrantType {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "lowercase")]
 pub enum ApiKeyLocation {
     Header,
@@ -227,6 +289,7 @@ pub enum ApiKeyLocation {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "kind", rename_all = "lowercase")]
 pub enum AuthType {
     None,
@@ -277,14 +340,18 @@ pub enum AuthType {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "SCREAMING-KEBAB-CASE")]
 pub enum DigestAlgorithm {
     Md5,
     Sha256,
     Sha512,
+    #[serde(rename = "SHA-512-256")]
+    Sha512_256,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "kebab-case")]
 pub enum DigestQop {
     Auth,
@@ -292,6 +359,7 @@ pub enum DigestQop {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "kind", rename_all = "camelCase")]
 pub enum CertificateType {
     Pem { cert: Bytes, key: Bytes },
@@ -299,26 +367,74 @@ pub enum CertificateType {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SecurityConfig {
     pub certificates: Option<CertificateConfig>,
     #[serde(rename = "verifyHost")]
     pub verify_host: Option<bool>,
     #[serde(rename = "verifyPeer")]
     pub verify_peer: Option<bool>,
+    #[serde(
+        default,
+        rename = "minTlsVersion",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub min_tls_version: Option<TlsVersion>,
+    // NOTE: TLS tuning, `None` keeps libcurl's default. ALPN is how HTTP/2
+    // gets negotiated and is on by default, turning it off forces HTTP/1.1
+    // over TLS.
+    #[serde(
+        default,
+        rename = "enableAlpn",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub enable_alpn: Option<bool>,
+    // NOTE: Off by default, and only the Secure Transport and NSS backends
+    // implement it, with others the setting is ignored.
+    #[serde(
+        default,
+        rename = "falseStart",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub false_start: Option<bool>,
+    // NOTE: On by default, off makes every connection do a full handshake
+    // instead of resuming an earlier TLS session.
+    #[serde(
+        default,
+        rename = "sessionIdCache",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub session_id_cache: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum TlsVersion {
+    #[serde(rename = "tlsv1.0")]
+    Tls1_0,
+    #[serde(rename = "tlsv1.1")]
+    Tls1_1,
+    #[serde(rename = "tlsv1.2")]
+    Tls1_2,
+    #[serde(rename = "tlsv1.3")]
+    Tls1_3,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct CertificateConfig {
     pub client: Option<CertificateType>,
     pub ca: Option<Vec<Bytes>>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct RequestMeta {
     pub options: Option<RequestOptions>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct RequestOptions {
     pub timeout: Option<u64>,
@@ -327,65 +443,526 @@ pub struct RequestOptions {
     pub decompress: Option<bool>,
     pub cookies: Option<bool>,
     pub keep_alive: Option<bool>,
+    pub nosignal: Option<bool>,
+    pub trace_context: Option<bool>,
+    pub happy_eyeballs_timeout_ms: Option<u64>,
+    // NOTE: Seconds a resolved name is reused from the shared DNS cache,
+    // libcurl defaults to 60. `0` resolves every time.
+    pub dns_cache_ttl: Option<u64>,
+    pub max_decompressed_bytes: Option<u64>,
+    pub max_response_time: Option<u64>,
+    pub first_byte_timeout: Option<u64>,
+    pub query_array_format: Option<QueryArrayFormat>,
+    pub buffer_size: Option<usize>,
+    pub upload_buffer_size: Option<usize>,
+    pub request_id_header: Option<String>,
+    // NOTE: Decode text with this charset instead of the one the response
+    // declares, for servers that get it wrong.
+    pub force_charset: Option<String>,
+    pub idempotency_key: Option<IdempotencyKey>,
+    // NOTE: Defaults to `Idempotency-Key`.
+    pub idempotency_header: Option<String>,
+    pub tcp_fastopen: Option<bool>,
+    pub interface: Option<String>,
+    pub sanitize_headers: Option<bool>,
+    pub fail_on_error: Option<bool>,
+    pub max_partial_body_bytes: Option<u64>,
+    pub dns_servers: Option<Vec<String>>,
+    // NOTE: Where DNS queries go out from, an interface name and local
+    // addresses. Like `dns_servers` these need libcurl built with c-ares.
+    pub dns_interface: Option<String>,
+    pub dns_local_ip4: Option<String>,
+    pub dns_local_ip6: Option<String>,
+    pub doh_url: Option<String>,
+    pub body_hash: Option<BodyHash>,
+    // NOTE: Checked against the response, verdicts come back on
+    // `ResponseMeta.assertions`.
+    pub assertions: Option<Vec<Assertion>>,
+    // NOTE: Decompress a body that starts with the gzip magic bytes although
+    // the response declares no `Content-Encoding`. `bodyHash` still covers
+    // the body as received.
+    pub detect_gzip: Option<bool>,
+    pub body_capture: Option<BodyCapture>,
+    // NOTE: Keep curl's debug trace, redacted as for logging, and attach it
+    // to a `Timeout` error.
+    pub capture_trace: Option<bool>,
+    // NOTE: Accepted but never serialized, so a one-off decision to skip
+    // verification cannot end up in saved requests, cassettes or exports.
+    #[serde(default, skip_serializing)]
+    pub insecure_override: Option<InsecureOverride>,
+}
+
+// NOTE: How much of the response body is kept in `Response.body`. Sizes in
+// `ResponseMeta.size` always count every byte received, and
+// `Response.bodyTruncated` is set whenever bytes were left out. `Truncate`
+// keeps the first `maxBytes` and reads the rest without storing it, or with
+// `abort` ends the transfer there, leaving the connection to be closed.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "mode", rename_all = "camelCase")]
+pub enum BodyCapture {
+    #[default]
+    Full,
+    #[serde(rename_all = "camelCase")]
+    Truncate {
+        max_bytes: u64,
+        #[serde(default)]
+        abort: bool,
+    },
+    Discard,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+// NOTE: Hashes the response body as curl delivers it, after any declared
+// `Content-Encoding` is decoded but before `detectGzip` unpacks undeclared
+// gzip, whose digest is then of the gzip bytes and not `Response.body`.
+// `expected` takes a hex digest or a
+// subresource integrity value such as `sha256-<base64>`, several of which
+// may be given separated by spaces.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct BodyHash {
+    #[serde(default)]
+    pub algorithm: HashAlgorithm,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+}
+
+// NOTE: A check a collection runner makes on the response. Header names
+// compare case-insensitively, `path` is a dotted path into a JSON body such
+// as `$.items[0].id`, and `pattern` is a regular expression that only has to
+// match part of the header value.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Assertion {
+    StatusEquals {
+        status: u16,
+    },
+    // NOTE: Inclusive, `200..=299` is `{ min: 200, max: 299 }`.
+    StatusInRange {
+        min: u16,
+        max: u16,
+    },
+    HeaderPresent {
+        name: String,
+    },
+    HeaderEquals {
+        name: String,
+        value: String,
+    },
+    HeaderMatches {
+        name: String,
+        pattern: String,
+    },
+    JsonPathEquals {
+        path: String,
+        value: serde_json::Value,
+    },
+    JsonPathExists {
+        path: String,
+    },
+    BodyContains {
+        text: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    ResponseTimeUnder {
+        limit_ms: u64,
+    },
+    #[serde(rename_all = "camelCase")]
+    BodySizeUnder {
+        limit_bytes: u64,
+    },
+}
+
+// NOTE: `actual` is the value the assertion looked at, `None` when it was
+// missing, e.g. a header that was not sent, or when it is the body itself,
+// which is never copied here.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct AssertionResult {
+    pub assertion: Assertion,
+    pub passed: bool,
+    pub actual: Option<serde_json::Value>,
+    pub message: String,
+}
+
+// NOTE: Skips TLS verification for a single request, and only while its URL
+// points at `host`. `reason` is logged as the audit trail.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct InsecureOverride {
+    pub host: String,
+    pub reason: String,
+}
+
+// NOTE: `Auto` generates a UUID once per logical request, retries of it
+// reuse the same key.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "kind", content = "key", rename_all = "snake_case")]
+pub enum IdempotencyKey {
+    None,
+    Auto,
+    Fixed(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum QueryValue {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum QueryArrayFormat {
+    // `key=a&key=b`
+    #[default]
+    Repeat,
+    // `key[]=a&key[]=b`
+    Brackets,
+    // `key=a,b`
+    Comma,
+}
+
+// NOTE: `option` is the numeric `CURLOPT_*` id, e.g. `curl_sys::CURLOPT_TCP_KEEPALIVE`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RawCurlOption {
+    Bool { option: u32, value: bool },
+    Long { option: u32, value: u64 },
+    String { option: u32, value: String },
+    List { option: u32, value: Vec<String> },
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Request {
     pub id: i64,
     pub url: String,
     #[serde(with = "http_serde::method")]
+    #[cfg_attr(feature = "schema", schemars(with = "String"))]
     pub method: Method,
     #[serde(with = "http_serde::version")]
+    #[cfg_attr(feature = "schema", schemars(with = "String"))]
     pub version: Version,
-    pub headers: Option<HashMap<String, String>>,
-    pub params: Option<HashMap<String, String>>,
+    pub headers: Option<Headers>,
+    pub params: Option<HashMap<String, QueryValue>>,
     pub content: Option<ContentType>,
     pub auth: Option<AuthType>,
     pub security: Option<SecurityConfig>,
     pub proxy: Option<ProxyConfig>,
     pub meta: Option<RequestMeta>,
+    // NOTE: The caller's span to continue, propagated as `traceparent` and
+    // `tracestate` whether or not `meta.options.traceContext` is enabled.
+    #[serde(
+        default,
+        rename = "traceContext",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub trace_context: Option<TraceContext>,
+    // NOTE: Sent as a `Range` header unless the request sets one itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<ByteRange>,
+    // NOTE: Acceptable response types, sent as an `Accept` header ordered by
+    // quality unless the request sets one itself. Replaces a client's default
+    // `Accept`. Without it, `Json` content asks for JSON first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accept: Option<Vec<AcceptEntry>>,
+    // NOTE: Applied after every managed setting, so these override them.
+    // Options picking the target are refused under a `TargetPolicy`.
+    #[serde(default, rename = "rawOptions", skip_serializing_if = "Vec::is_empty")]
+    pub raw_options: Vec<RawCurlOption>,
+    // NOTE: Filled in by `resolve_with` with the values of secret variables.
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub secrets: Secrets,
+    // NOTE: Set by `RelayClient` on every attempt from its own policy and
+    // carried with the request to the transfer, so a request is never
+    // enforced with another client's policy.
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub target_policy: Option<Arc<TargetPolicy>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct ResponseBody {
     pub body: Bytes,
     pub media_type: MediaType,
+    // NOTE: What `text` decodes with, the `charset` of `Content-Type` unless
+    // the request forced another.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub charset: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Response {
     pub id: i64,
     #[serde(with = "http_serde::status_code")]
+    #[cfg_attr(feature = "schema", schemars(with = "u16"))]
     pub status: StatusCode,
     #[serde(rename = "statusText")]
     pub status_text: String,
     #[serde(with = "http_serde::version")]
+    #[cfg_attr(feature = "schema", schemars(with = "String"))]
     pub version: Version,
+    #[serde(rename = "finalUrl")]
+    pub final_url: String,
     pub headers: HashMap<String, String>,
     pub cookies: Option<Vec<Cookie>>,
     pub body: ResponseBody,
     pub meta: ResponseMeta,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<RequestMetrics>,
+    // NOTE: Hex digest of the body, set when the request asked for a
+    // `bodyHash`.
+    #[serde(default, rename = "bodyHash", skip_serializing_if = "Option::is_none")]
+    pub body_hash: Option<String>,
+    // NOTE: `Link` header values of any `103 Early Hints` received before the
+    // final response, in order, for preloading what the page will need.
+    #[serde(default, rename = "earlyHints", skip_serializing_if = "Vec::is_empty")]
+    pub early_hints: Vec<String>,
+    // NOTE: `body` holds less than was received, see `BodyCapture`.
+    #[serde(
+        default,
+        rename = "bodyTruncated",
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub body_truncated: bool,
+}
+
+// NOTE: Totals across every attempt made for a request, including those a
+// retry policy discarded. Byte counts are as seen on the wire, headers
+// included, and only cover attempts that got a response.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct RequestMetrics {
+    pub attempts: u32,
+    pub retry_delay_ms: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub not_modified: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum StatusCategory {
+    Informational,
+    Success,
+    Redirect,
+    ClientError,
+    ServerError,
+    Unknown,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct PartProgress {
+    pub index: usize,
+    pub count: usize,
+    pub name: String,
+    pub filename: Option<String>,
+    pub sent: u64,
+    pub size: u64,
+}
+
+// NOTE: One part of a multipart body as it goes on the wire. `total_bytes`
+// covers its boundary line and part headers as well as its `data_bytes`,
+// `offset` is where the part starts in the body.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct PartEstimate {
+    pub name: String,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub offset: u64,
+    pub data_bytes: u64,
+    pub total_bytes: u64,
+}
+
+// NOTE: Size of a request body before it is sent, see
+// `ContentType::estimated_size`. `parts` is empty unless the body is
+// multipart.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct BodyEstimate {
+    pub total_bytes: u64,
+    pub parts: Vec<PartEstimate>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Progress {
+    pub uploaded: u64,
+    pub upload_total: u64,
+    pub downloaded: u64,
+    pub download_total: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub part: Option<PartProgress>,
 }
 
+// NOTE: One step of a request's lifecycle. `sequence` counts up from zero
+// for each request, so events can be ordered without relying on `at`, the
+// wall clock in milliseconds since the epoch.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TransferEvent {
+    pub request_id: i64,
+    pub sequence: u64,
+    pub at: u64,
+    #[serde(flatten)]
+    pub kind: TransferEventKind,
+}
+
+// NOTE: Phases repeat for every redirect hop and retry attempt, a reused
+// connection goes straight from `resolvingDns` to `sending`. Byte counts
+// are per hop and reported every 64 KiB. `done` carries the timing the
+// response has, and is, like `failed`, always the last event.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum TransferEventKind {
+    Queued,
+    ResolvingDns,
+    Connecting {
+        address: String,
+    },
+    TlsHandshake,
+    Sending,
+    Waiting,
+    Receiving,
+    BytesSent {
+        total: u64,
+    },
+    BytesReceived {
+        total: u64,
+    },
+    Redirect {
+        url: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Retry {
+        attempt: u32,
+        delay_ms: u64,
+    },
+    Done {
+        timing: TimingInfo,
+    },
+    Failed {
+        message: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationCode {
+    InvalidUrl,
+    UnsupportedScheme,
+    InvalidHeaderName,
+    InvalidHeaderValue,
+    NonAsciiHeaderValue,
+    ConflictingHeaders,
+    BodyNotAllowed,
+    BodyDiscouraged,
+    InvalidContent,
+    IncompleteAuth,
+    InvalidCertificate,
+    InvalidProxy,
+    InvalidResolver,
+    DuplicateId,
+    InvalidRange,
+    InvalidTraceContext,
+    ConflictingOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationSeverity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationIssue {
+    pub code: ValidationCode,
+    pub severity: ValidationSeverity,
+    // NOTE: Dotted path to the offending field, e.g. `headers.X-Api-Key`,
+    // `auth.username` or `proxy.url`.
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ProxyConfig {
     pub url: String,
     pub auth: Option<ProxyAuth>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ProxyAuth {
+    #[serde(default)]
     pub username: String,
+    #[serde(default)]
     pub password: String,
+    // NOTE: Unset lets curl pick whichever scheme the proxy offers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheme: Option<ProxyAuthScheme>,
+    // NOTE: Only used by `ProxyAuthScheme::Bearer`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ProxyAuthScheme {
+    Basic,
+    Digest,
+    Ntlm,
+    Negotiate,
+    Bearer,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Cookie {
     pub name: String,
     pub value: String,
     pub domain: Option<String>,
     pub path: Option<String>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
     pub expires: Option<OffsetDateTime>,
     pub secure: Option<bool>,
     #[serde(rename = "httpOnly")]
@@ -395,6 +972,7 @@ pub struct Cookie {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum SameSite {
     Strict,
     Lax,
@@ -402,18 +980,362 @@ pub enum SameSite {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ResponseMeta {
     pub timing: TimingInfo,
     pub size: SizeInfo,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace: Option<TraceContext>,
+    // NOTE: The code as received when it is not a valid HTTP status, `status`
+    // is then `502 Bad Gateway` as a proxy would report it.
+    #[serde(default, rename = "rawStatus", skip_serializing_if = "Option::is_none")]
+    pub raw_status: Option<u32>,
+    #[serde(
+        default,
+        rename = "protocolWarnings",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub protocol_warnings: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection: Option<ConnectionInfo>,
+    // NOTE: Set when the request asked for a range or the server answered
+    // `206 Partial Content`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<RangeInfo>,
+    // NOTE: Set when the request sent an `Accept` header and the response
+    // has a `Content-Type` to compare against it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub negotiation: Option<NegotiationInfo>,
+    // NOTE: Set when the response carries any `Access-Control-*` header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cors: Option<CorsInfo>,
+    // NOTE: The idempotency key the request was sent with, for persisting
+    // and reusing it when retrying from another process.
+    #[serde(
+        default,
+        rename = "idempotencyKey",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub idempotency_key: Option<String>,
+    // NOTE: Only set for responses that went through a client's `HttpCache`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<CacheStatus>,
+    // NOTE: One verdict per `RequestOptions.assertions` entry, in order. A
+    // failed assertion never fails the request.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assertions: Vec<AssertionResult>,
+    // NOTE: Set when `detectGzip` found and decompressed an undeclared gzip
+    // body.
+    #[serde(
+        default,
+        rename = "gzipDetected",
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub gzip_detected: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TlsInfo {
+    pub verification_skipped: bool,
+    // NOTE: Whether the handshake resumed a cached session instead of
+    // making a full one, `None` when it is not known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_resumed: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum IpFamily {
+    Ipv4,
+    Ipv6,
+}
+
+// NOTE: Which address the transfer ended up connected to. `attempted` lists
+// the addresses curl tried in order, both families racing under Happy
+// Eyeballs, and is empty when an existing connection was reused.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionInfo {
+    pub remote_ip: String,
+    pub family: IpFamily,
+    pub attempted: Vec<String>,
+}
+
+// NOTE: Positions are zero based and `end` is inclusive, as in the header.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ByteRange {
+    Bounded { start: u64, end: u64 },
+    From { start: u64 },
+    // NOTE: The last `length` bytes.
+    Suffix { length: u64 },
+    Multiple { ranges: Vec<ByteRange> },
+}
+
+// NOTE: A parsed `Content-Range`. `start` and `end` are both `None` for the
+// `bytes */1234` form sent with `416 Range Not Satisfiable`, and
+// `completeLength` is `None` when the server does not know it.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ContentRange {
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+    pub complete_length: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct RangeInfo {
+    pub content_range: Option<ContentRange>,
+    // NOTE: From `Accept-Ranges`, `None` when the server did not say.
+    pub accepts_ranges: Option<bool>,
+    // NOTE: A range was asked for but the server sent the whole body with
+    // `200 OK`.
+    pub ignored: bool,
+}
+
+// NOTE: One media range of an `Accept` header, wildcards such as `*/*` and
+// `image/*` included. `quality` is its q-value, from `0`, which refuses the
+// type, to `1`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct AcceptEntry {
+    pub media_type: String,
+    pub quality: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct NegotiationInfo {
+    // NOTE: `false` when the response type matches no entry, or only one
+    // with `q=0`, i.e. the server ignored the `Accept` header.
+    pub honored: bool,
+    // NOTE: The most specific entry matching the response type.
+    pub matched: Option<AcceptEntry>,
+}
+
+// NOTE: The `Access-Control-*` response headers. Method and header names
+// are kept as sent, `*` included.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CorsInfo {
+    pub allow_origin: Option<String>,
+    pub allow_methods: Vec<String>,
+    pub allow_headers: Vec<String>,
+    pub allow_credentials: bool,
+    pub expose_headers: Vec<String>,
+    // NOTE: Seconds a browser may cache the preflight result.
+    pub max_age: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "verdict", rename_all = "snake_case")]
+pub enum CorsVerdict {
+    Allowed,
+    // NOTE: Every check that failed, in the order a browser makes them.
+    Denied { reasons: Vec<String> },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum CacheStatus {
+    // NOTE: Served from the cache without contacting the server.
+    Hit,
+    // NOTE: The server confirmed the cached response with `304 Not Modified`.
+    Revalidated,
+    // NOTE: Fetched from the server, and stored when cacheable.
+    Miss,
+    // NOTE: Not eligible for caching, sent as is.
+    Bypass,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TraceContext {
+    pub trace_id: String,
+    pub span_id: String,
+    pub sampled: bool,
+    pub trace_state: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct TimingInfo {
     pub start: u64,
     pub end: u64,
+    // NOTE: Whether the host's address came from the shared DNS cache. Only
+    // set for transfers that share one and look up a name, not an IP
+    // literal or a proxy.
+    #[serde(
+        default,
+        rename = "dnsCacheHit",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub dns_cache_hit: Option<bool>,
+    // NOTE: curl's milestones for the transfer, `None` for responses that
+    // never went through curl, e.g. served from the cache or a mock.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phases: Option<PhaseMarks>,
+}
+
+// NOTE: Milliseconds from the start of the transfer until each of curl's
+// milestones, redirects followed on the way included. A milestone that was
+// never reached stays at `0`, e.g. `appconnect` for plain HTTP, or the lookup
+// and connect of a reused connection.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct PhaseMarks {
+    pub redirect: f64,
+    pub namelookup: f64,
+    pub connect: f64,
+    pub appconnect: f64,
+    pub pretransfer: f64,
+    pub starttransfer: f64,
+    pub total: f64,
+}
+
+// NOTE: What had arrived when a transfer failed part way, `status` is only set
+// once the status line was received. `body` holds at most the configured
+// prefix while `body_size` counts everything received.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct PartialResponse {
+    #[serde(default, with = "optional_status")]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<u16>"))]
+    pub status: Option<StatusCode>,
+    pub headers: HashMap<String, String>,
+    #[serde(with = "base64_body")]
+    #[cfg_attr(feature = "schema", schemars(with = "String"))]
+    pub body: Bytes,
+    pub body_size: u64,
+    pub timing: TimingInfo,
+}
+
+mod optional_status {
+    use http::StatusCode;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(
+        status: &Option<StatusCode>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match status {
+            Some(status) => serializer.serialize_some(&status.as_u16()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<StatusCode>, D::Error> {
+        Option::<u16>::deserialize(deserializer)?
+            .map(|status| StatusCode::from_u16(status).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+mod base64_body {
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+    use bytes::Bytes;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(body: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&BASE64.encode(body))
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Bytes, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        BASE64
+            .decode(encoded)
+            .map(Bytes::from)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+// NOTE: One per `RelayError` variant, serialized as the variant's `kind`.
+// Values are only ever added, hosts can match on them across versions.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    Validation,
+    UnsupportedFeature,
+    InvalidHeader,
+    Network,
+    DnsResolution,
+    ConnectionRefused,
+    ConnectionReset,
+    Timeout,
+    TlsHandshake,
+    TooManyRedirects,
+    UnsupportedProtocol,
+    ProxyError,
+    MalformedResponse,
+    ResponseTime,
+    BodyHashMismatch,
+    HttpStatus,
+    Certificate,
+    Parse,
+    Io,
+    Abort,
+    Interceptor,
+    PolicyViolation,
+}
+
+// NOTE: A failed request flattened for hosts, the same shape for every
+// error. `cause` is the underlying error's message chain, `elapsedMs` how long
+// the request ran before failing when known, and `status` the HTTP status
+// when one was received. The variant's own fields are in `RelayError`'s
+// serialized form.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorPayload {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cause: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<i64>,
+    pub retryable: bool,
+    #[serde(
+        default,
+        with = "optional_status",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<u16>"))]
+    pub status: Option<StatusCode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timing: Option<TimingInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partial: Option<PartialResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SizeInfo {
     pub headers: u64,
     pub body: u64,
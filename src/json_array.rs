@@ -0,0 +1,250 @@
+use std::iter::FusedIterator;
+
+use serde_json::Value;
+
+use crate::{
+    error::{RelayError, Result},
+    interop::Response,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Start,
+    First,
+    Next,
+    Done,
+}
+
+// NOTE: Elements are parsed one at a time straight from the body bytes, so
+// only the element being yielded is ever materialized as a `Value` tree.
+#[derive(Debug)]
+pub struct JsonArrayElements<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    state: State,
+    index: usize,
+}
+
+impl<'a> JsonArrayElements<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            position: 0,
+            state: State::Start,
+            index: 0,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.position)
+            .is_some_and(|byte| matches!(byte, b' ' | b'\t' | b'\n' | b'\r'))
+        {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: &[u8]) -> Result<u8> {
+        self.skip_whitespace();
+        match self.bytes.get(self.position) {
+            Some(byte) if expected.contains(byte) => {
+                self.position += 1;
+                Ok(*byte)
+            }
+            found => Err(self.error(format!(
+                "expected {} but found {}",
+                expected
+                    .iter()
+                    .map(|byte| format!("'{}'", *byte as char))
+                    .collect::<Vec<_>>()
+                    .join(" or "),
+                found.map_or("end of input".to_string(), |byte| {
+                    format!("'{}'", byte.escape_ascii())
+                })
+            ))),
+        }
+    }
+
+    fn error(&mut self, cause: String) -> RelayError {
+        self.state = State::Done;
+        tracing::error!(position = self.position, cause = %cause, "Invalid JSON array");
+        RelayError::Parse {
+            message: format!("Invalid JSON array at byte {}", self.position),
+            cause: Some(cause.into()),
+            partial: None,
+        }
+    }
+
+    fn element(&mut self) -> Result<Value> {
+        let mut stream =
+            serde_json::Deserializer::from_slice(&self.bytes[self.position..]).into_iter::<Value>();
+
+        match stream.next() {
+            Some(Ok(value)) => {
+                self.position += stream.byte_offset();
+                self.index += 1;
+                Ok(value)
+            }
+            Some(Err(e)) => Err(self.error(format!("element {}: {}", self.index, e))),
+            None => Err(self.error("unexpected end of input".into())),
+        }
+    }
+
+    fn advance(&mut self) -> Result<Option<Value>> {
+        match self.state {
+            State::Start => {
+                self.expect(b"[")?;
+                self.state = State::First;
+                self.advance()
+            }
+            State::First => {
+                self.skip_whitespace();
+                if self.bytes.get(self.position) == Some(&b']') {
+                    self.position += 1;
+                    return self.finish();
+                }
+                self.state = State::Next;
+                self.element().map(Some)
+            }
+            State::Next => match self.expect(b",]")? {
+                b',' => self.element().map(Some),
+                _ => self.finish(),
+            },
+            State::Done => Ok(None),
+        }
+    }
+
+    fn finish(&mut self) -> Result<Option<Value>> {
+        self.skip_whitespace();
+        if self.position < self.bytes.len() {
+            return Err(self.error("trailing characters after array".into()));
+        }
+        self.state = State::Done;
+        tracing::debug!(elements = self.index, "Finished reading JSON array");
+        Ok(None)
+    }
+}
+
+impl Iterator for JsonArrayElements<'_> {
+    type Item = Result<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.advance().transpose()
+    }
+}
+
+impl FusedIterator for JsonArrayElements<'_> {}
+
+impl Response {
+    // NOTE: For large top-level arrays, yields elements one by one instead of
+    // parsing the whole document up front. Iteration stops after the first
+    // error.
+    pub fn json_array(&self) -> JsonArrayElements<'_> {
+        JsonArrayElements::new(&self.body.body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn elements(input: &str) -> Vec<Result<Value>> {
+        JsonArrayElements::new(input.as_bytes()).collect()
+    }
+
+    fn values(input: &str) -> Vec<Value> {
+        elements(input)
+            .into_iter()
+            .map(|element| element.unwrap())
+            .collect()
+    }
+
+    fn message(error: &RelayError) -> String {
+        let RelayError::Parse { message, cause, .. } = error else {
+            panic!("expected a parse error, got {:?}", error);
+        };
+        format!("{}: {}", message, cause.as_ref().unwrap())
+    }
+
+    #[test]
+    fn yields_each_element_in_order() {
+        assert_eq!(
+            values(r#" [1, "two", {"three": [3]}, null, [], true, -1.5] "#),
+            [
+                json!(1),
+                json!("two"),
+                json!({ "three": [3] }),
+                json!(null),
+                json!([]),
+                json!(true),
+                json!(-1.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_arrays_yield_nothing() {
+        assert!(values("[]").is_empty());
+        assert!(values("\n[ \t\r\n]\n").is_empty());
+    }
+
+    #[test]
+    fn rejects_documents_that_are_not_arrays() {
+        for input in ["", "  ", "{}", "\"[1]\""] {
+            let elements = elements(input);
+            assert_eq!(elements.len(), 1, "{:?}", input);
+            assert!(elements[0].is_err(), "{:?}", input);
+        }
+        let elements = elements("{}");
+        assert_eq!(
+            message(elements[0].as_ref().unwrap_err()),
+            "Invalid JSON array at byte 0: expected '[' but found '{'"
+        );
+    }
+
+    #[test]
+    fn stops_after_the_first_error() {
+        let elements = elements("[1, 2 3, 4]");
+        assert_eq!(elements.len(), 3);
+        assert_eq!(*elements[0].as_ref().unwrap(), json!(1));
+        assert_eq!(*elements[1].as_ref().unwrap(), json!(2));
+        assert_eq!(
+            message(elements[2].as_ref().unwrap_err()),
+            "Invalid JSON array at byte 6: expected ',' or ']' but found '3'"
+        );
+    }
+
+    #[test]
+    fn rejects_trailing_commas_and_truncation() {
+        for input in ["[1,]", "[1,", "[1", "[", "[1, {\"a\": "] {
+            let elements = elements(input);
+            assert!(elements.last().unwrap().is_err(), "{:?}", input);
+            assert!(elements.iter().rev().skip(1).all(Result::is_ok));
+        }
+    }
+
+    #[test]
+    fn rejects_trailing_characters() {
+        let elements = elements("[1] [2]");
+        assert_eq!(elements.len(), 2);
+        assert_eq!(
+            message(elements[1].as_ref().unwrap_err()),
+            "Invalid JSON array at byte 4: trailing characters after array"
+        );
+    }
+
+    #[test]
+    fn is_fused_after_the_end() {
+        let mut elements = JsonArrayElements::new(b"[1]");
+        assert!(elements.next().is_some());
+        assert!(elements.next().is_none());
+        assert!(elements.next().is_none());
+
+        let mut elements = JsonArrayElements::new(b"[,]");
+        assert!(elements.next().unwrap().is_err());
+        assert!(elements.next().is_none());
+    }
+}
@@ -1,14 +1,102 @@
+mod accept;
+mod assertion;
 mod auth;
+mod builder;
+mod cache;
+mod charset;
+mod client;
+mod clock;
+mod conditional;
+mod config;
 mod content;
+mod cors;
+mod csv;
+mod curl_command;
+mod digest;
+mod download;
 pub mod error;
+mod estimate;
+mod events;
+mod export;
+mod ext_value;
+mod gzip;
+pub mod har;
 mod header;
+mod id;
+mod idempotency;
+pub mod import;
+mod integrity;
+mod interceptor;
 mod interop;
+mod json_array;
+mod link;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "mock")]
+pub mod mock;
+mod multipart;
+mod paginate;
+mod pool;
+mod proxy;
+mod query;
+mod range;
+mod raw_option;
+mod recording;
+mod redact;
 mod relay;
+mod render;
 mod request;
 mod response;
+mod retry;
+#[cfg(feature = "schema")]
+pub mod schema;
 mod security;
+mod share;
+mod signing;
+mod snapshot;
+mod target_policy;
+mod template;
+mod trace;
 mod transfer;
+mod transport;
 mod util;
+mod validate;
+pub mod ws;
 
-pub use interop::{Request, Response};
-pub use relay::{cancel, execute};
+pub use builder::{RequestBuilder, SecurityConfigBuilder};
+pub use cache::{CacheStats, HttpCache};
+pub use client::RelayClient;
+pub use clock::{Clock, SystemClock};
+pub use config::ClientConfig;
+pub use curl_command::CurlCommand;
+pub use download::{DownloadOptions, DownloadResult};
+pub use export::ExportOptions;
+pub use ext_value::{encode_header_value, HeaderEncoding};
+pub use header::Headers;
+pub use id::{next_request_id, UNASSIGNED_REQUEST_ID};
+pub use interceptor::Interceptor;
+pub use interop::{
+    AcceptEntry, ApiKeyLocation, Assertion, AssertionResult, AuthType, BodyCapture, BodyEstimate,
+    BodyHash, ByteRange, CacheStatus, CertificateConfig, CertificateType, ConnectionInfo,
+    ContentRange, CorsInfo, CorsVerdict, DigestAlgorithm, DigestQop, ErrorCode, ErrorPayload,
+    GrantType, HashAlgorithm, IdempotencyKey, InsecureOverride, IpFamily, MediaType,
+    NegotiationInfo, PartEstimate, PartProgress, PartialResponse, PhaseMarks, Progress, ProxyAuth,
+    ProxyAuthScheme, ProxyConfig, QueryArrayFormat, QueryValue, RangeInfo, RawCurlOption,
+    RelatedPart, Request, RequestMetrics, Response, ResponseBody, SecurityConfig, StatusCategory,
+    TlsInfo, TlsVersion, TraceContext, TransferEvent, TransferEventKind, ValidationCode,
+    ValidationIssue, ValidationSeverity,
+};
+pub use json_array::JsonArrayElements;
+pub use link::LinkRelation;
+pub use multipart::BodyPart;
+pub use paginate::{BlockingPages, Pages, DEFAULT_MAX_PAGES};
+pub use range::RangePart;
+pub use recording::{Cassette, CassetteMode, Interaction, MatchOn, RecordingLayer};
+pub use redact::{set_redaction_policy, RedactionPolicy, Secrets};
+pub use relay::{cancel, execute, execute_interop, execute_with_events, execute_with_progress};
+pub use retry::{BackoffStrategy, RetryPolicy};
+pub use signing::{CanonicalRequest, HmacSigner, RequestSigner};
+pub use snapshot::NormalizedResponse;
+pub use target_policy::TargetPolicy;
+pub use template::Variables;
+pub use transport::{CurlTransport, Transport, TransportFuture};
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ext_value, interop::Response};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkRelation {
+    pub url: String,
+    pub rel: String,
+    // NOTE: Remaining target attributes such as `title` or `type`, keyed by
+    // lowercased name with quotes and escapes removed.
+    pub params: HashMap<String, String>,
+}
+
+// NOTE: Splits on `separator` only outside of `<...>` targets and quoted
+// strings, so commas inside URLs or titles do not break links apart.
+pub(crate) fn split_outside_quotes(input: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut in_target = false;
+    let mut escaped = false;
+
+    for (index, c) in input.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' if !in_target => in_quotes = !in_quotes,
+            '<' if !in_quotes => in_target = true,
+            '>' if !in_quotes => in_target = false,
+            c if c == separator && !in_quotes && !in_target => {
+                parts.push(&input[start..index]);
+                start = index + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+
+    parts.push(&input[start..]);
+    parts
+}
+
+pub(crate) fn unquote(value: &str) -> String {
+    let Some(inner) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    else {
+        return value.to_string();
+    };
+
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+fn parse_link(link: &str) -> Option<(String, HashMap<String, String>)> {
+    let link = link.trim();
+    let end = link.find('>')?;
+    let target = link.strip_prefix('<')?.get(..end - 1)?.trim().to_string();
+
+    let mut params = HashMap::new();
+    for param in split_outside_quotes(&link[end + 1..], ';') {
+        let param = param.trim();
+        if param.is_empty() {
+            continue;
+        }
+
+        let (name, value) = match param.split_once('=') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => (param, ""),
+        };
+
+        // NOTE: RFC 8187 forms such as `title*` take precedence over the plain
+        // parameter, otherwise RFC 8288 says occurrences after the first are
+        // ignored.
+        let decoded = name
+            .strip_suffix('*')
+            .and_then(|name| Some((name, ext_value::decode_ext_value(value)?)));
+        match decoded {
+            Some((name, value)) => {
+                params.insert(name.to_ascii_lowercase(), value);
+            }
+            None => {
+                params
+                    .entry(name.to_ascii_lowercase())
+                    .or_insert_with(|| unquote(value));
+            }
+        }
+    }
+
+    Some((target, params))
+}
+
+// NOTE: Every relation of every link, in header order, repeats included.
+fn relations(header: &str, base: Option<&url::Url>) -> Vec<LinkRelation> {
+    let mut links = Vec::new();
+
+    for link in split_outside_quotes(header, ',') {
+        if link.trim().is_empty() {
+            continue;
+        }
+
+        let Some((target, mut params)) = parse_link(link) else {
+            tracing::warn!(link = %link.trim(), "Skipping malformed Link header entry");
+            continue;
+        };
+
+        let Some(rels) = params.remove("rel") else {
+            tracing::debug!(target = %target, "Skipping Link header entry without rel");
+            continue;
+        };
+
+        let url = base
+            .and_then(|base| base.join(&target).ok())
+            .map(String::from)
+            .unwrap_or(target);
+
+        // NOTE: A single link can carry several relation types, e.g.
+        // `rel="last next"`, and relation types compare case-insensitively.
+        links.extend(rels.split_ascii_whitespace().map(|rel| LinkRelation {
+            url: url.clone(),
+            rel: rel.to_ascii_lowercase(),
+            params: params.clone(),
+        }));
+    }
+
+    links
+}
+
+pub(crate) fn parse(header: &str, base: Option<&url::Url>) -> HashMap<String, LinkRelation> {
+    let mut links = HashMap::new();
+    for link in relations(header, base) {
+        links.entry(link.rel.clone()).or_insert(link);
+    }
+    links
+}
+
+impl Response {
+    pub fn links(&self) -> HashMap<String, LinkRelation> {
+        let Some(header) = self
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("link"))
+            .map(|(_, value)| value)
+        else {
+            return HashMap::new();
+        };
+
+        let base = url::Url::parse(&self.final_url).ok();
+        let links = parse(header, base.as_ref());
+        tracing::debug!(
+            request_id = self.id,
+            rels = ?links.keys().collect::<Vec<_>>(),
+            "Parsed Link header"
+        );
+        links
+    }
+
+    // NOTE: A list rather than a map keyed by `rel`, since early hints are
+    // mostly several `rel=preload` links.
+    pub fn early_hint_links(&self) -> Vec<LinkRelation> {
+        let base = url::Url::parse(&self.final_url).ok();
+        self.early_hints
+            .iter()
+            .flat_map(|header| relations(header, base.as_ref()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> url::Url {
+        url::Url::parse("https://api.example.com/items?page=2").unwrap()
+    }
+
+    #[test]
+    fn resolves_targets_against_the_base() {
+        let links = parse(
+            r#"</items?page=3>; rel="next", <https://other.example.com/1>; rel=first"#,
+            Some(&base()),
+        );
+        assert_eq!(links["next"].url, "https://api.example.com/items?page=3");
+        assert_eq!(links["first"].url, "https://other.example.com/1");
+        assert_eq!(links.len(), 2);
+    }
+
+    #[test]
+    fn keeps_relative_targets_without_a_base() {
+        let links = parse("</items?page=3>; rel=next", None);
+        assert_eq!(links["next"].url, "/items?page=3");
+    }
+
+    #[test]
+    fn ignores_separators_inside_targets_and_quotes() {
+        let links = parse(
+            r#"<https://example.com/a,b;c>; rel="next"; title="one, two; \"three\"""#,
+            None,
+        );
+        let next = &links["next"];
+        assert_eq!(next.url, "https://example.com/a,b;c");
+        assert_eq!(next.params["title"], r#"one, two; "three""#);
+    }
+
+    #[test]
+    fn splits_space_separated_relations() {
+        let links = parse(r#"</last>; rel="Last NEXT""#, Some(&base()));
+        assert_eq!(links["last"].url, "https://api.example.com/last");
+        assert_eq!(links["next"].url, "https://api.example.com/last");
+        assert_eq!(links["next"].rel, "next");
+    }
+
+    #[test]
+    fn first_link_per_relation_wins() {
+        let links = parse("</a>; rel=next, </b>; rel=next", None);
+        assert_eq!(links["next"].url, "/a");
+    }
+
+    #[test]
+    fn prefers_extended_parameters() {
+        let links = parse(
+            r#"</a>; rel=next; title*=UTF-8''caf%C3%A9; title="cafe"; TYPE="text/html""#,
+            None,
+        );
+        let next = &links["next"];
+        assert_eq!(next.params["title"], "caf\u{e9}");
+        assert_eq!(next.params["type"], "text/html");
+        assert!(!next.params.contains_key("rel"));
+    }
+
+    #[test]
+    fn skips_malformed_entries_and_entries_without_rel() {
+        let links = parse(
+            r#"no-brackets; rel=prev, </a>; title="x", , </b>; rel=next"#,
+            None,
+        );
+        assert_eq!(links.keys().collect::<Vec<_>>(), ["next"]);
+        assert_eq!(links["next"].url, "/b");
+    }
+
+    #[test]
+    fn unquotes_escapes() {
+        assert_eq!(unquote(r#""a\"b\\c""#), r#"a"b\c"#);
+        assert_eq!(unquote("bare"), "bare");
+        assert_eq!(unquote(r#""unterminated"#), r#""unterminated"#);
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use curl::easy::Easy;
+use http::Method;
+
+use crate::{
+    error::{RelayError, Result},
+    interop::Response,
+};
+
+lazy_static::lazy_static! {
+    static ref METRICS_SINK: RwLock<Option<Arc<dyn MetricsSink>>> = RwLock::new(None);
+}
+
+pub const REQUESTS_TOTAL: &str = "relay_requests_total";
+pub const ERRORS_TOTAL: &str = "relay_errors_total";
+pub const RETRIES_TOTAL: &str = "relay_retries_total";
+pub const CONNECTIONS_TOTAL: &str = "relay_connections_total";
+pub const BYTES_SENT_TOTAL: &str = "relay_bytes_sent_total";
+pub const BYTES_RECEIVED_TOTAL: &str = "relay_bytes_received_total";
+pub const REQUEST_DURATION_SECONDS: &str = "relay_request_duration_seconds";
+pub const PHASE_DURATION_SECONDS: &str = "relay_phase_duration_seconds";
+
+// NOTE: Where relay reports its metrics, e.g. an adapter onto the `metrics`
+// crate or a Prometheus registry. Names follow Prometheus conventions, and
+// every label value comes from a small fixed set unless `host_labels` is on.
+pub trait MetricsSink: Send + Sync {
+    fn increment_counter(&self, name: &str, labels: &[(&str, &str)], value: u64);
+
+    fn record_histogram(&self, name: &str, labels: &[(&str, &str)], value: f64);
+
+    // NOTE: Adds a `host` label to `relay_requests_total`, one series per
+    // host ever contacted, which is why it is off unless asked for.
+    fn host_labels(&self) -> bool {
+        false
+    }
+}
+
+pub fn set_metrics_sink(sink: impl MetricsSink + 'static) {
+    tracing::debug!("Installing metrics sink");
+    let sink: Arc<dyn MetricsSink> = Arc::new(sink);
+    match METRICS_SINK.write() {
+        Ok(mut current) => *current = Some(sink),
+        Err(poisoned) => *poisoned.into_inner() = Some(sink),
+    }
+}
+
+pub fn clear_metrics_sink() {
+    tracing::debug!("Removing metrics sink");
+    match METRICS_SINK.write() {
+        Ok(mut current) => *current = None,
+        Err(poisoned) => *poisoned.into_inner() = None,
+    }
+}
+
+fn sink() -> Option<Arc<dyn MetricsSink>> {
+    match METRICS_SINK.read() {
+        Ok(current) => current.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+fn status_class(response: &Response) -> &'static str {
+    match response.status.as_u16() {
+        100..=199 => "1xx",
+        200..=299 => "2xx",
+        300..=399 => "3xx",
+        400..=499 => "4xx",
+        _ => "5xx",
+    }
+}
+
+// NOTE: Called once per executed request, whichever way it ended and however
+// many attempts it took. A request that never got a response has status class
+// `none`, and `abort` errors are counted as `cancelled` rather than `error`.
+pub(crate) fn record_request(
+    method: &Method,
+    url: &str,
+    result: &Result<Response>,
+    elapsed: Duration,
+) {
+    let Some(sink) = sink() else {
+        return;
+    };
+
+    let (outcome, status_class) = match result {
+        Ok(response) => ("success", status_class(response)),
+        Err(RelayError::Abort { .. }) => ("cancelled", "none"),
+        Err(_) => ("error", "none"),
+    };
+
+    let host = sink
+        .host_labels()
+        .then(|| url::Url::parse(url).ok())
+        .flatten()
+        .and_then(|url| url.host_str().map(str::to_string));
+    let mut labels = vec![
+        ("method", method.as_str()),
+        ("status_class", status_class),
+        ("outcome", outcome),
+    ];
+    if let Some(ref host) = host {
+        labels.push(("host", host));
+    }
+    sink.increment_counter(REQUESTS_TOTAL, &labels, 1);
+
+    if let Err(ref error) = result {
+        sink.increment_counter(
+            ERRORS_TOTAL,
+            &[("method", method.as_str()), ("kind", error.kind())],
+            1,
+        );
+    }
+
+    sink.record_histogram(
+        REQUEST_DURATION_SECONDS,
+        &[("method", method.as_str()), ("outcome", outcome)],
+        elapsed.as_secs_f64(),
+    );
+}
+
+pub(crate) fn record_retry(method: &Method) {
+    if let Some(sink) = sink() {
+        sink.increment_counter(RETRIES_TOTAL, &[("method", method.as_str())], 1);
+    }
+}
+
+// NOTE: Bytes are counted for every transfer, phases and connection reuse
+// only for completed ones, as curl's timings of a failed transfer do not
+// say which phase it failed in. Each phase is the time between curl's marks
+// since the start, TLS is skipped for plain HTTP and reused connections,
+// whose handshake mark stays at `0`.
+pub(crate) fn record_transfer(handle: &mut Easy, completed: bool) {
+    let Some(sink) = sink() else {
+        return;
+    };
+
+    // NOTE: `request_size` covers only the request head, the body is in
+    // `upload_size`.
+    let sent = handle.request_size().unwrap_or_default()
+        + handle.upload_size().map_or(0, |size| size as u64);
+    let received = handle.header_size().unwrap_or_default()
+        + handle.download_size().map_or(0, |size| size as u64);
+    if sent > 0 {
+        sink.increment_counter(BYTES_SENT_TOTAL, &[], sent);
+    }
+    if received > 0 {
+        sink.increment_counter(BYTES_RECEIVED_TOTAL, &[], received);
+    }
+
+    if !completed {
+        return;
+    }
+
+    let marks = [
+        handle.namelookup_time(),
+        handle.connect_time(),
+        handle.appconnect_time(),
+        handle.pretransfer_time(),
+        handle.starttransfer_time(),
+        handle.total_time(),
+    ]
+    .map(|mark| mark.unwrap_or_default());
+    let [dns, connect, tls, pretransfer, first_byte, total] = marks;
+
+    let phase = |name: &str, from: Duration, to: Duration| {
+        if !to.is_zero() && to >= from {
+            sink.record_histogram(
+                PHASE_DURATION_SECONDS,
+                &[("phase", name)],
+                (to - from).as_secs_f64(),
+            );
+        }
+    };
+    phase("dns", Duration::ZERO, dns);
+    phase("connect", dns, connect);
+    phase("tls", connect, tls);
+    phase("waiting", pretransfer, first_byte);
+    phase("receiving", first_byte, total);
+
+    // NOTE: A completed transfer that opened no connection went out on a
+    // reused one.
+    let reused = handle.num_connects().unwrap_or_default() == 0;
+    sink.increment_counter(
+        CONNECTIONS_TOTAL,
+        &[("reused", if reused { "true" } else { "false" })],
+        1,
+    );
+}
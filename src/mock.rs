@@ -0,0 +1,421 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant, SystemTime},
+};
+
+use bytes::Bytes;
+use http::{Method, StatusCode, Version};
+use regex::Regex;
+
+use crate::{
+    clock::{Clock, Stopwatch},
+    content,
+    error::{RelayError, Result},
+    interop::{MediaType, Request, Response, ResponseBody, ResponseMeta, SizeInfo, TimingInfo},
+    transport::{Transport, TransportFuture},
+};
+
+#[derive(Debug, Clone)]
+pub enum UrlMatcher {
+    Exact(String),
+    Regex(Regex),
+}
+
+impl UrlMatcher {
+    pub fn exact(url: impl Into<String>) -> Self {
+        Self::Exact(url.into())
+    }
+
+    pub fn regex(pattern: &str) -> Result<Self> {
+        Regex::new(pattern)
+            .map(Self::Regex)
+            .map_err(|e| RelayError::Parse {
+                message: format!("Invalid URL pattern: {}", pattern),
+                cause: Some(e.into()),
+                partial: None,
+            })
+    }
+
+    fn matches(&self, url: &str) -> bool {
+        match self {
+            Self::Exact(expected) => expected == url,
+            Self::Regex(pattern) => pattern.is_match(url),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::Exact(expected) => format!("{:?}", expected),
+            Self::Regex(pattern) => format!("/{}/", pattern.as_str()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum BodyMatcher {
+    Exact(Bytes),
+    Contains(String),
+    Json(serde_json::Value),
+}
+
+impl BodyMatcher {
+    fn matches(&self, body: Option<&[u8]>) -> bool {
+        let Some(body) = body else {
+            return false;
+        };
+
+        match self {
+            Self::Exact(expected) => expected.as_ref() == body,
+            Self::Contains(needle) => String::from_utf8_lossy(body).contains(needle.as_str()),
+            Self::Json(expected) => serde_json::from_slice::<serde_json::Value>(body)
+                .map(|actual| &actual == expected)
+                .unwrap_or(false),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::Exact(expected) => format!("{:?}", String::from_utf8_lossy(expected)),
+            Self::Contains(needle) => format!("contains {:?}", needle),
+            Self::Json(expected) => format!("json {}", expected),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum MockOutcome {
+    Respond(Box<Response>),
+    Fail(RelayError),
+}
+
+#[derive(Debug, Clone)]
+pub struct MockExpectation {
+    method: Method,
+    url: UrlMatcher,
+    headers: Vec<(String, String)>,
+    body: Option<BodyMatcher>,
+    outcomes: VecDeque<MockOutcome>,
+    hits: usize,
+}
+
+impl MockExpectation {
+    pub fn new(method: Method, url: impl Into<String>) -> Self {
+        Self::matching(method, UrlMatcher::exact(url))
+    }
+
+    pub fn matching(method: Method, url: UrlMatcher) -> Self {
+        Self {
+            method,
+            url,
+            headers: Vec::new(),
+            body: None,
+            outcomes: VecDeque::new(),
+            hits: 0,
+        }
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn body(mut self, matcher: BodyMatcher) -> Self {
+        self.body = Some(matcher);
+        self
+    }
+
+    // NOTE: Outcomes are served in the order they were added, the last one
+    // is repeated for every subsequent matching request.
+    pub fn respond(mut self, response: Response) -> Self {
+        self.outcomes
+            .push_back(MockOutcome::Respond(Box::new(response)));
+        self
+    }
+
+    pub fn fail(mut self, error: RelayError) -> Self {
+        self.outcomes.push_back(MockOutcome::Fail(error));
+        self
+    }
+
+    fn mismatches(&self, request: &Request) -> Vec<(String, String)> {
+        let mut mismatches = Vec::new();
+
+        if self.method != request.method {
+            mismatches.push((
+                format!("method: {}", self.method),
+                format!("method: {}", request.method),
+            ));
+        }
+
+        if !self.url.matches(&request.url) {
+            mismatches.push((
+                format!("url: {}", self.url.describe()),
+                format!("url: {:?}", request.url),
+            ));
+        }
+
+        for (name, value) in &self.headers {
+            let actual = request.headers.as_ref().and_then(|headers| {
+                headers
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                    .map(|(_, value)| value.as_str())
+            });
+
+            if actual != Some(value.as_str()) {
+                mismatches.push((
+                    format!("header {}: {:?}", name, value),
+                    format!("header {}: {:?}", name, actual),
+                ));
+            }
+        }
+
+        if let Some(ref matcher) = self.body {
+            let body = request.content.as_ref().and_then(content::body_bytes);
+            if !matcher.matches(body.as_deref()) {
+                mismatches.push((
+                    format!("body: {}", matcher.describe()),
+                    format!("body: {:?}", body.as_deref().map(String::from_utf8_lossy)),
+                ));
+            }
+        }
+
+        mismatches
+    }
+
+    fn next_outcome(&mut self) -> Option<MockOutcome> {
+        self.hits += 1;
+        if self.outcomes.len() > 1 {
+            self.outcomes.pop_front()
+        } else {
+            self.outcomes.front().cloned()
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    expectations: Vec<MockExpectation>,
+    received: Vec<Request>,
+}
+
+// NOTE: Only moves when told to, clones share the same time so a test can
+// keep one handle and advance the clock the code under test reads from.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    start: SystemTime,
+    origin: Instant,
+    offset: Arc<Mutex<Duration>>,
+}
+
+impl MockClock {
+    pub fn new(start: SystemTime) -> Self {
+        Self {
+            start,
+            origin: Instant::now(),
+            offset: Arc::new(Mutex::new(Duration::ZERO)),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.offset() += duration;
+    }
+
+    fn offset(&self) -> MutexGuard<'_, Duration> {
+        self.offset
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new(SystemTime::UNIX_EPOCH)
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        self.start + *self.offset()
+    }
+
+    fn monotonic(&self) -> Instant {
+        self.origin + *self.offset()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+    clock: Option<Arc<dyn Clock>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // NOTE: Mocked responses are stamped with the clock's current time, without
+    // one their timing is left as configured.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    pub fn expect(&self, expectation: MockExpectation) -> &Self {
+        self.state().expectations.push(expectation);
+        self
+    }
+
+    pub fn received(&self) -> Vec<Request> {
+        self.state().received.clone()
+    }
+
+    pub fn verify(&self) -> Result<()> {
+        let state = self.state();
+        let unused = state
+            .expectations
+            .iter()
+            .filter(|expectation| expectation.hits == 0)
+            .map(|expectation| format!("{} {}", expectation.method, expectation.url.describe()))
+            .collect::<Vec<_>>();
+
+        if unused.is_empty() {
+            Ok(())
+        } else {
+            Err(RelayError::Abort {
+                message: format!(
+                    "Mock expectations were never matched:\n{}",
+                    unused.join("\n")
+                ),
+            })
+        }
+    }
+
+    pub fn response(status: StatusCode, body: impl Into<Bytes>) -> Response {
+        let body = body.into();
+        let size = body.len() as u64;
+
+        Response {
+            id: 0,
+            status,
+            status_text: status.to_string(),
+            version: Version::HTTP_11,
+            final_url: String::new(),
+            headers: HashMap::new(),
+            cookies: None,
+            body: ResponseBody {
+                body,
+                media_type: MediaType::TextPlain,
+                charset: None,
+            },
+            meta: ResponseMeta {
+                timing: TimingInfo {
+                    start: 0,
+                    end: 0,
+                    dns_cache_hit: None,
+                    phases: None,
+                },
+                size: SizeInfo {
+                    headers: 0,
+                    body: size,
+                    total: size,
+                },
+                trace: None,
+                raw_status: None,
+                protocol_warnings: Vec::new(),
+                tls: None,
+                range: None,
+                negotiation: None,
+                cors: None,
+                connection: None,
+                idempotency_key: None,
+                cache: None,
+                assertions: Vec::new(),
+                gzip_detected: false,
+            },
+            metrics: None,
+            body_hash: None,
+            early_hints: Vec::new(),
+            body_truncated: false,
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    #[tracing::instrument(skip(self, request), fields(request_id = request.id), level = "debug")]
+    fn handle(&self, request: Request) -> Result<Response> {
+        let mut state = self.state();
+        state.received.push(request.clone());
+
+        let matched = state
+            .expectations
+            .iter_mut()
+            .find(|expectation| expectation.mismatches(&request).is_empty());
+
+        match matched.map(MockExpectation::next_outcome) {
+            Some(Some(MockOutcome::Respond(mut response))) => {
+                tracing::debug!(status = %response.status, "Serving mocked response");
+                response.id = request.id;
+                if let Some(ref clock) = self.clock {
+                    response.meta.timing = Stopwatch::start(clock.as_ref()).timing();
+                }
+                if response.final_url.is_empty() {
+                    response.final_url = request.url.clone();
+                }
+                Ok(*response)
+            }
+            Some(Some(MockOutcome::Fail(error))) => {
+                tracing::debug!(error = %error, "Serving mocked error");
+                Err(error)
+            }
+            Some(None) => Err(RelayError::Abort {
+                message: format!(
+                    "Mock expectation for {} {} has no outcome configured",
+                    request.method, request.url
+                ),
+            }),
+            None => {
+                let diff = Self::closest_diff(&state.expectations, &request);
+                tracing::error!(method = %request.method, url = %request.url, "No mock expectation matched");
+                Err(RelayError::Abort {
+                    message: format!(
+                        "No mock expectation matched request {} {}\n{}",
+                        request.method, request.url, diff
+                    ),
+                })
+            }
+        }
+    }
+
+    fn closest_diff(expectations: &[MockExpectation], request: &Request) -> String {
+        let closest = expectations
+            .iter()
+            .enumerate()
+            .map(|(index, expectation)| (index, expectation.mismatches(request)))
+            .min_by_key(|(_, mismatches)| mismatches.len());
+
+        let Some((index, mismatches)) = closest else {
+            return "No expectations registered".into();
+        };
+
+        let mut diff = format!("Closest expectation (#{}):\n", index);
+        for (expected, actual) in mismatches {
+            let _ = writeln!(diff, "- {}", expected);
+            let _ = writeln!(diff, "+ {}", actual);
+        }
+        diff
+    }
+}
+
+impl Transport for MockTransport {
+    fn send(&self, request: Request) -> TransportFuture<'_> {
+        Box::pin(async move { self.handle(request) })
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+
+use crate::{
+    error::{RelayError, Result},
+    interop::{RelatedPart, Response},
+};
+
+// NOTE: One part of a `multipart/*` response body, headers keep the casing
+// they were sent with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BodyPart {
+    pub headers: HashMap<String, String>,
+    pub body: Bytes,
+}
+
+impl BodyPart {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim())
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.header("content-type")
+    }
+
+    // NOTE: Without the angle brackets, as referenced by a `cid:` URL.
+    pub fn content_id(&self) -> Option<&str> {
+        self.header("content-id").map(bare_content_id)
+    }
+}
+
+pub(crate) fn bare_content_id(id: &str) -> &str {
+    let id = id.trim();
+    let id = id.strip_prefix("cid:").unwrap_or(id);
+    id.strip_prefix('<')
+        .and_then(|id| id.strip_suffix('>'))
+        .unwrap_or(id)
+}
+
+impl RelatedPart {
+    pub fn new(
+        content_id: impl Into<String>,
+        content_type: impl Into<String>,
+        body: impl Into<Bytes>,
+    ) -> Self {
+        Self {
+            body: body.into(),
+            content_type: content_type.into(),
+            content_id: content_id.into(),
+            headers: Default::default(),
+        }
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    // NOTE: Part headers and the blank line ending them.
+    pub(crate) fn head(&self) -> String {
+        let mut head = format!(
+            "Content-Type: {}\r\nContent-ID: <{}>\r\n",
+            self.content_type,
+            bare_content_id(&self.content_id)
+        );
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        head
+    }
+
+    fn write(&self, body: &mut Vec<u8>) {
+        body.extend_from_slice(self.head().as_bytes());
+        body.extend_from_slice(&self.body);
+    }
+}
+
+const RELATED_BOUNDARY_PREFIX: &str = "relay-related-";
+const RELATED_BOUNDARY_HASH_BYTES: usize = 12;
+
+// NOTE: Boundaries are a fixed prefix and a fixed number of hex digits.
+pub(crate) const RELATED_BOUNDARY_LEN: usize =
+    RELATED_BOUNDARY_PREFIX.len() + RELATED_BOUNDARY_HASH_BYTES * 2;
+
+// NOTE: The boundary is derived from the parts, so the same content always
+// encodes the same way, which keeps rendered requests and recordings stable.
+fn related_boundary(parts: &[&RelatedPart]) -> String {
+    let mut hasher = openssl::sha::Sha256::new();
+    for part in parts {
+        hasher.update(part.content_id.as_bytes());
+        hasher.update(part.content_type.as_bytes());
+        hasher.update(&part.body);
+    }
+    let digest = hasher.finish();
+    let hex = digest[..RELATED_BOUNDARY_HASH_BYTES]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    format!("{}{}", RELATED_BOUNDARY_PREFIX, hex)
+}
+
+// NOTE: The `Content-Type` value and body for a `multipart/related` request,
+// the root goes first and is named by `start`.
+pub(crate) fn related(
+    root: &RelatedPart,
+    parts: &[RelatedPart],
+    type_param: &str,
+) -> (String, Vec<u8>) {
+    let all = std::iter::once(root).chain(parts).collect::<Vec<_>>();
+    let boundary = related_boundary(&all);
+
+    let mut body = Vec::new();
+    for part in all {
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        part.write(&mut body);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+    let type_param = match type_param.trim() {
+        "" => root
+            .content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim(),
+        type_param => type_param,
+    };
+    let content_type = format!(
+        "multipart/related; type=\"{}\"; start=\"<{}>\"; boundary=\"{}\"",
+        type_param,
+        bare_content_id(&root.content_id),
+        boundary
+    );
+    (content_type, body)
+}
+
+fn parse_error(message: impl Into<String>) -> RelayError {
+    RelayError::Parse {
+        message: message.into(),
+        cause: None,
+        partial: None,
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|position| position + from)
+}
+
+fn parse_part(content: Bytes) -> BodyPart {
+    let (head, body) = if content.starts_with(b"\r\n") {
+        (Bytes::new(), content.slice(2..))
+    } else {
+        match find(&content, b"\r\n\r\n", 0) {
+            Some(end) => (content.slice(..end), content.slice(end + 4..)),
+            None => (content, Bytes::new()),
+        }
+    };
+
+    let headers = String::from_utf8_lossy(&head)
+        .split("\r\n")
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    BodyPart { headers, body }
+}
+
+// NOTE: RFC 2046 body parts. Text before the first delimiter and after the
+// closing one is preamble and epilogue, and is dropped.
+pub(crate) fn split(body: &Bytes, boundary: &str) -> Result<Vec<BodyPart>> {
+    let delimiter = format!("--{}", boundary);
+    let delimiter = delimiter.as_bytes();
+    let next_delimiter = [b"\r\n", delimiter].concat();
+
+    let mut position = if body.starts_with(delimiter) {
+        0
+    } else {
+        find(body, &next_delimiter, 0)
+            .map(|start| start + 2)
+            .ok_or_else(|| parse_error("Multipart body has no opening boundary"))?
+    };
+
+    let mut parts = Vec::new();
+    loop {
+        let after = position + delimiter.len();
+        if body.get(after..after + 2) == Some(b"--") {
+            return Ok(parts);
+        }
+
+        // NOTE: The delimiter line may carry trailing whitespace.
+        let start = find(body, b"\r\n", after)
+            .map(|end| end + 2)
+            .ok_or_else(|| parse_error("Multipart body ends inside a boundary line"))?;
+        let end = find(body, &next_delimiter, start)
+            .ok_or_else(|| parse_error("Multipart body has no closing boundary"))?;
+
+        parts.push(parse_part(body.slice(start..end)));
+        position = end + 2;
+    }
+}
+
+impl Response {
+    // NOTE: Splits a `multipart/*` body, e.g. `multipart/byteranges` or
+    // `multipart/mixed`, at the boundary from its `Content-Type`.
+    pub fn multipart_parts(&self) -> Result<Vec<BodyPart>> {
+        let content_type = self.header_str("content-type").unwrap_or_default();
+        if !content_type
+            .trim_start()
+            .to_ascii_lowercase()
+            .starts_with("multipart/")
+        {
+            return Err(parse_error(format!(
+                "Response is not multipart: {:?}",
+                content_type
+            )));
+        }
+
+        let boundary = self
+            .header_param("content-type", "boundary")
+            .filter(|boundary| !boundary.is_empty())
+            .ok_or_else(|| parse_error("Multipart response has no boundary"))?;
+
+        let parts = split(&self.body.body, &boundary)?;
+        tracing::debug!(parts = parts.len(), "Split multipart response");
+        Ok(parts)
+    }
+
+    // NOTE: Finds a part of a `multipart/related` response by its
+    // `Content-ID`, given bare, bracketed or as a `cid:` reference.
+    pub fn related_part(&self, content_id: &str) -> Result<Option<BodyPart>> {
+        let content_id = bare_content_id(content_id);
+        Ok(self
+            .multipart_parts()?
+            .into_iter()
+            .find(|part| part.content_id() == Some(content_id)))
+    }
+}
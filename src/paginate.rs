@@ -0,0 +1,134 @@
+use crate::{
+    client::RelayClient,
+    error::{RelayError, Result},
+    id::UNASSIGNED_REQUEST_ID,
+    interop::{Request, Response},
+};
+
+pub const DEFAULT_MAX_PAGES: usize = 100;
+
+const CREDENTIAL_HEADERS: &[&str] = &["authorization", "cookie", "proxy-authorization"];
+
+pub struct Pages<'a> {
+    client: &'a RelayClient,
+    next: Option<Request>,
+    visited: Vec<String>,
+    fetched: usize,
+    max_pages: usize,
+}
+
+impl<'a> Pages<'a> {
+    pub fn max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+
+    // NOTE: Fetches the next page, `None` once no next link is left, a page
+    // failed, or `max_pages` is reached.
+    pub async fn next_page(&mut self) -> Option<Result<Response>> {
+        let mut request = self.next.take()?;
+
+        if self.fetched >= self.max_pages {
+            tracing::warn!(max_pages = self.max_pages, "Reached page limit, stopping");
+            return None;
+        }
+
+        request.ensure_id();
+        self.fetched += 1;
+        self.visited.push(request.url.clone());
+
+        tracing::debug!(
+            request_id = request.id,
+            page = self.fetched,
+            "Fetching page"
+        );
+
+        let result = self.client.execute(request.clone()).await;
+        if let Ok(ref response) = result {
+            self.next = self.follow(&request, response);
+            if self.next.is_none() {
+                tracing::info!(pages = self.fetched, "Pagination complete");
+            }
+        }
+
+        Some(result)
+    }
+
+    // NOTE: For callers outside an async runtime, drives the pages on a
+    // runtime of its own that blocks the calling thread for each page.
+    // Panics when iterated from within a runtime, use `next_page` there.
+    pub fn blocking(self) -> Result<BlockingPages<'a>> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .map_err(|e| RelayError::Io {
+                message: "Failed to start a runtime for pagination".into(),
+                cause: Some(e.into()),
+            })?;
+        Ok(BlockingPages {
+            pages: self,
+            runtime,
+        })
+    }
+
+    fn follow(&mut self, request: &Request, response: &Response) -> Option<Request> {
+        let next = response.links().remove("next")?;
+
+        if self.visited.contains(&next.url) {
+            tracing::warn!(url = %next.url, "Next link points to a visited page, stopping");
+            return None;
+        }
+
+        let mut follow = request.clone();
+        follow.id = UNASSIGNED_REQUEST_ID;
+        // NOTE: The next link is a complete URL, parameters from the first
+        // request are already part of it and would otherwise be appended again.
+        follow.params = None;
+
+        // NOTE: Same rule curl applies to redirects, credentials are only
+        // re-sent while the pages stay on the origin they were meant for.
+        let same_origin = match (url::Url::parse(&request.url), url::Url::parse(&next.url)) {
+            (Ok(current), Ok(next)) => current.origin() == next.origin(),
+            _ => false,
+        };
+        if !same_origin {
+            tracing::warn!(url = %next.url, "Next link changes origin, dropping credentials");
+            follow.auth = None;
+            if let Some(ref mut headers) = follow.headers {
+                headers.retain(|name, _| {
+                    !CREDENTIAL_HEADERS.contains(&name.to_ascii_lowercase().as_str())
+                });
+            }
+        }
+
+        follow.url = next.url;
+        Some(follow)
+    }
+}
+
+pub struct BlockingPages<'a> {
+    pages: Pages<'a>,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl Iterator for BlockingPages<'_> {
+    type Item = Result<Response>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.pages.next_page())
+    }
+}
+
+impl RelayClient {
+    // NOTE: Walks the pages of `request` in turn with `Pages::next_page`,
+    // following `rel="next"` links with the same auth and headers.
+    pub fn paginate(&self, request: Request) -> Pages<'_> {
+        Pages {
+            client: self,
+            next: Some(request),
+            visited: Vec::new(),
+            fetched: 0,
+            max_pages: DEFAULT_MAX_PAGES,
+        }
+    }
+}
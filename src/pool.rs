@@ -0,0 +1,54 @@
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::{
+    interop::Request,
+    transport::{Transport, TransportFuture},
+};
+
+// NOTE: Every transfer runs on its own easy handle and holds its connection
+// until it finishes, so the client's connections are bounded by bounding how
+// many transfers run at once. Waiting transfers are let in first come first
+// served, which tokio's semaphore guarantees.
+#[derive(Debug)]
+pub(crate) struct ConnectionLimit {
+    max: usize,
+    permits: Semaphore,
+}
+
+impl ConnectionLimit {
+    pub(crate) fn new(max: usize) -> Self {
+        let max = max.max(1);
+        Self {
+            max,
+            permits: Semaphore::new(max),
+        }
+    }
+
+    // NOTE: Waits without blocking the runtime's thread while the pool is
+    // full. The semaphore is never closed, so a permit always comes.
+    pub(crate) async fn acquire(&self) -> Option<SemaphorePermit<'_>> {
+        if self.permits.available_permits() == 0 {
+            tracing::debug!(max = self.max, "Connection pool is full, queueing transfer");
+        }
+        self.permits.acquire().await.ok()
+    }
+}
+
+// NOTE: Holds a permit for as long as the wrapped transport's send runs,
+// so only requests that go out on the wire take a place in the pool.
+pub(crate) struct LimitedTransport<'a> {
+    pub(crate) transport: &'a dyn Transport,
+    pub(crate) limit: Option<&'a ConnectionLimit>,
+}
+
+impl Transport for LimitedTransport<'_> {
+    fn send(&self, request: Request) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let _permit = match self.limit {
+                Some(limit) => limit.acquire().await,
+                None => None,
+            };
+            self.transport.send(request).await
+        })
+    }
+}
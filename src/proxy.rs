@@ -0,0 +1,85 @@
+use crate::{interop::ProxyConfig, query};
+
+// NOTE: Lowercase names win over uppercase ones, the same order curl and most
+// CLI tools check them in. `http_proxy` is only read in lowercase, as curl
+// does, since CGI servers put a client's `Proxy:` header in `HTTP_PROXY`,
+// see: https://httpoxy.org
+fn lookup(name: &str, var: &impl Fn(&str) -> Option<String>) -> Option<String> {
+    let names = match name.to_ascii_lowercase() {
+        lower if lower == "http_proxy" => vec![lower],
+        lower => vec![lower, name.to_ascii_uppercase()],
+    };
+    names
+        .iter()
+        .find_map(|name| var(name))
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+// NOTE: `NO_PROXY` is a comma separated list of hosts, each matching itself and
+// its subdomains, optionally restricted to a port. A leading `.` or `*.` is
+// ignored and a lone `*` disables proxying altogether.
+fn bypassed(no_proxy: &str, host: &str, port: Option<u16>) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    no_proxy
+        .split(',')
+        .map(|entry| entry.trim().to_ascii_lowercase())
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| {
+            if entry == "*" {
+                return true;
+            }
+
+            let (pattern, entry_port) = match entry.rsplit_once(':') {
+                Some((pattern, entry_port)) if !pattern.contains(':') || pattern.ends_with(']') => {
+                    (pattern.to_string(), entry_port.parse::<u16>().ok())
+                }
+                _ => (entry.clone(), None),
+            };
+            let pattern = pattern
+                .trim_start_matches("*.")
+                .trim_start_matches('.')
+                .trim_start_matches('[')
+                .trim_end_matches(']');
+
+            let host_matches = host == pattern
+                || host
+                    .strip_suffix(pattern)
+                    .is_some_and(|rest| rest.ends_with('.'));
+
+            host_matches && entry_port.map_or(true, |entry_port| Some(entry_port) == port)
+        })
+}
+
+pub(crate) fn select(url: &str, var: impl Fn(&str) -> Option<String>) -> Option<ProxyConfig> {
+    let (url, _) = query::split_zone(url);
+    let url = url::Url::parse(&query::with_default_scheme(&url)).ok()?;
+    let host = url.host_str()?.to_ascii_lowercase();
+
+    if let Some(no_proxy) = lookup("no_proxy", &var) {
+        if bypassed(&no_proxy, &host, url.port_or_known_default()) {
+            tracing::debug!(host = %host, "Host excluded by NO_PROXY");
+            return None;
+        }
+    }
+
+    let proxy = match url.scheme() {
+        "https" => lookup("https_proxy", &var),
+        "http" => lookup("http_proxy", &var),
+        _ => None,
+    }
+    .or_else(|| lookup("all_proxy", &var))?;
+
+    tracing::debug!(scheme = url.scheme(), host = %host, "Using proxy from environment");
+
+    // NOTE: A proxy without a scheme, e.g. `proxy:3128`, is an HTTP proxy.
+    Some(ProxyConfig {
+        url: query::with_default_scheme(&proxy).into_owned(),
+        auth: None,
+    })
+}
+
+pub(crate) fn from_env(url: &str) -> Option<ProxyConfig> {
+    select(url, |name| std::env::var(name).ok())
+}
@@ -0,0 +1,365 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use url::{Position, Url};
+
+use crate::{
+    error::{RelayError, Result},
+    interop::{QueryArrayFormat, QueryValue, Request},
+};
+
+// NOTE: Characters that are never valid in a URL, anything else is either
+// allowed as-is or percent-encoded by the WHATWG parser,
+// see: https://url.spec.whatwg.org/#url-code-points
+fn is_forbidden(c: char) -> bool {
+    c.is_control()
+        || c.is_whitespace()
+        || matches!(c, '<' | '>' | '"' | '`' | '{' | '}' | '|' | '\\' | '^')
+}
+
+// NOTE: curl takes URLs without a scheme and assumes `http://`, e.g.
+// `example.com/path` or a proxy given as `proxy.corp:3128`. Only a
+// `scheme://` prefix counts as a scheme, `host:port` alone does not.
+pub(crate) fn has_scheme(raw: &str) -> bool {
+    raw.trim_start().find("://").is_some_and(|end| {
+        let scheme = &raw.trim_start()[..end];
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
+
+pub(crate) fn with_default_scheme(raw: &str) -> Cow<'_, str> {
+    if has_scheme(raw) {
+        Cow::Borrowed(raw)
+    } else {
+        Cow::Owned(format!("http://{}", raw.trim_start()))
+    }
+}
+
+// NOTE: WHATWG URLs have no notion of IPv6 zone ids, see RFC 6874, so a zone
+// such as `[fe80::1%eth0]` or `[fe80::1%25eth0]` is split off before parsing
+// and put back with `with_zone` in the URL handed to curl.
+pub(crate) fn split_zone(raw: &str) -> (Cow<'_, str>, Option<String>) {
+    let authority_start = raw.find("://").map_or(0, |i| i + 3);
+    let authority_end = raw[authority_start..]
+        .find(['/', '?', '#'])
+        .map_or(raw.len(), |i| authority_start + i);
+    let authority = &raw[authority_start..authority_end];
+
+    let (Some(open), Some(close)) = (authority.find('['), authority.rfind(']')) else {
+        return (Cow::Borrowed(raw), None);
+    };
+
+    let Some(percent) = authority[open..close].find('%').map(|i| open + i) else {
+        return (Cow::Borrowed(raw), None);
+    };
+
+    // NOTE: A zone that itself starts with `25` has to be written in the
+    // `%25` form, e.g. `[fe80::1%2525g0]`. A bare `%25` is zone `25`.
+    let zone = &authority[percent + 1..close];
+    let zone = match zone.strip_prefix("25") {
+        Some(rest) if !rest.is_empty() => rest,
+        _ => zone,
+    };
+    if zone.is_empty() {
+        return (Cow::Borrowed(raw), None);
+    }
+
+    let stripped = format!(
+        "{}{}{}",
+        &raw[..authority_start + percent],
+        &authority[close..],
+        &raw[authority_end..]
+    );
+
+    tracing::trace!(zone = %zone, "Split IPv6 zone id from URL");
+    (Cow::Owned(stripped), Some(zone.to_string()))
+}
+
+// NOTE: curl expects the RFC 6874 `%25` form and leaves the zone out of `Host`.
+pub(crate) fn with_zone(url: &Url, zone: Option<&str>) -> String {
+    match (zone, url.host_str()) {
+        (Some(zone), Some(host)) if host.ends_with(']') => format!(
+            "{}{}%25{}]{}",
+            &url[..Position::BeforeHost],
+            &host[..host.len() - 1],
+            zone,
+            &url[Position::AfterHost..]
+        ),
+        _ => url.to_string(),
+    }
+}
+
+pub(crate) fn parse(raw: &str) -> Result<Url> {
+    Url::parse(raw).map_err(|e| {
+        let position = raw.trim().char_indices().find(|(_, c)| is_forbidden(*c));
+
+        tracing::error!(error = %e, url = %raw, position = ?position.map(|(i, _)| i), "Failed to parse URL");
+        RelayError::Parse {
+            message: match position {
+                Some((index, c)) => {
+                    format!("Invalid URL, unexpected {:?} at position {}", c, index)
+                }
+                None => format!("Invalid URL: {}", raw),
+            },
+            cause: Some(e.into()),
+            partial: None,
+        }
+    })
+}
+
+fn encode_pairs(
+    params: &HashMap<String, QueryValue>,
+    format: QueryArrayFormat,
+) -> Vec<(String, String)> {
+    // NOTE: Sorted so the same params always produce the same URL,
+    // which matters for caching and recorded request matching.
+    let mut keys: Vec<&String> = params.keys().collect();
+    keys.sort();
+
+    let mut pairs = Vec::new();
+    for key in keys {
+        match &params[key] {
+            QueryValue::Single(value) => pairs.push((key.clone(), value.clone())),
+            QueryValue::Multiple(values) => match format {
+                QueryArrayFormat::Repeat => {
+                    pairs.extend(values.iter().map(|value| (key.clone(), value.clone())))
+                }
+                QueryArrayFormat::Brackets => pairs.extend(
+                    values
+                        .iter()
+                        .map(|value| (format!("{}[]", key), value.clone())),
+                ),
+                QueryArrayFormat::Comma => pairs.push((key.clone(), values.join(","))),
+            },
+        }
+    }
+    pairs
+}
+
+// NOTE: Existing query pairs in the URL are preserved and params are appended
+// after them, so a key present in both is sent twice. Fragments are client-side
+// only and are never sent on the wire. A URL without a scheme gets curl's
+// `http://`.
+pub(crate) fn build_url(request: &Request) -> Result<Url> {
+    let (raw, _) = split_zone(&request.url);
+    let mut url = parse(&with_default_scheme(&raw))?;
+    url.set_fragment(None);
+
+    let format = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.query_array_format)
+        .unwrap_or_default();
+
+    if let Some(params) = request.params.as_ref().filter(|params| !params.is_empty()) {
+        let pairs = encode_pairs(params, format);
+        url.query_pairs_mut().extend_pairs(&pairs);
+
+        tracing::debug!(
+            params = pairs.len(),
+            format = ?format,
+            "Merged query params into URL"
+        );
+    }
+
+    Ok(url)
+}
+
+// NOTE: Without params or a zone the URL goes out as written, less its
+// fragment, since serializing the parsed URL would rewrite it, e.g. turn
+// `%7e` into `%7E`, which some servers and signatures tell apart.
+pub(crate) fn wire_url(request: &Request) -> Result<String> {
+    let url = build_url(request)?;
+    let (_, zone) = split_zone(&request.url);
+    if zone.is_none() && request.params.as_ref().map_or(true, HashMap::is_empty) {
+        let raw = request.url.trim();
+        return Ok(raw.split_once('#').map_or(raw, |(url, _)| url).to_string());
+    }
+    Ok(with_zone(&url, zone.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interop::{RequestMeta, RequestOptions};
+
+    fn wire(url: &str) -> String {
+        wire_url(&Request::builder().get(url).build().unwrap()).unwrap()
+    }
+
+    fn with_params(
+        url: &str,
+        params: &[(&str, QueryValue)],
+        format: Option<QueryArrayFormat>,
+    ) -> Request {
+        let mut request = Request::builder().get(url).build().unwrap();
+        request.params = Some(
+            params
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+        );
+        request.meta = Some(RequestMeta {
+            options: Some(RequestOptions {
+                query_array_format: format,
+                ..Default::default()
+            }),
+        });
+        request
+    }
+
+    fn pairs(url: &Url) -> Vec<(String, String)> {
+        url.query_pairs()
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect()
+    }
+
+    fn parse_error(raw: &str) -> String {
+        match parse(raw) {
+            Err(RelayError::Parse { message, .. }) => message,
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn splits_both_zone_forms() {
+        for raw in [
+            "http://[fe80::1%eth0]:8080/a",
+            "http://[fe80::1%25eth0]:8080/a",
+        ] {
+            let (url, zone) = split_zone(raw);
+            assert_eq!(url, "http://[fe80::1]:8080/a");
+            assert_eq!(zone.as_deref(), Some("eth0"));
+        }
+    }
+
+    #[test]
+    fn leaves_urls_without_a_zone_alone() {
+        for raw in ["http://[fe80::1]/a", "http://example.com/%25?q=[a%b]"] {
+            let (url, zone) = split_zone(raw);
+            assert_eq!(url, raw);
+            assert_eq!(zone, None);
+        }
+    }
+
+    #[test]
+    fn zones_starting_with_25_need_the_encoded_form() {
+        assert_eq!(
+            split_zone("http://[fe80::1%2525g0]/").1.as_deref(),
+            Some("25g0")
+        );
+        assert_eq!(split_zone("http://[fe80::1%25]/").1.as_deref(), Some("25"));
+    }
+
+    #[test]
+    fn wire_url_sends_the_zone_percent_encoded() {
+        for raw in [
+            "http://[fe80::1%eth0]:8080/a?b=1",
+            "http://[fe80::1%25eth0]:8080/a?b=1",
+        ] {
+            assert_eq!(wire(raw), "http://[fe80::1%25eth0]:8080/a?b=1");
+        }
+        assert_eq!(wire("http://[fe80::1%2525g0]/"), "http://[fe80::1%2525g0]/");
+    }
+
+    #[test]
+    fn wire_url_keeps_the_zone_with_params() {
+        let mut request = Request::builder()
+            .get("http://[fe80::1%eth0]/a")
+            .build()
+            .unwrap();
+        request.params = Some(HashMap::from([(
+            "q".to_string(),
+            QueryValue::Single("1".into()),
+        )]));
+        assert_eq!(wire_url(&request).unwrap(), "http://[fe80::1%25eth0]/a?q=1");
+    }
+
+    #[test]
+    fn built_urls_parse_back_to_the_same_pairs() {
+        let request = with_params(
+            "https://example.com/search?q=caf%C3%A9&q=2#results",
+            &[
+                ("tag", QueryValue::Single("a b&c=d".into())),
+                ("empty", QueryValue::Single(String::new())),
+            ],
+            None,
+        );
+        let url = build_url(&request).unwrap();
+
+        let reparsed = parse(url.as_str()).unwrap();
+        assert_eq!(reparsed, url);
+        assert_eq!(reparsed.fragment(), None);
+        assert_eq!(
+            pairs(&reparsed),
+            [("q", "café"), ("q", "2"), ("empty", ""), ("tag", "a b&c=d"),]
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+        );
+    }
+
+    #[test]
+    fn encodes_arrays_in_each_format() {
+        let params = [
+            (
+                "ids",
+                QueryValue::Multiple(vec!["1".into(), "2 3".into(), "4,5".into()]),
+            ),
+            ("a", QueryValue::Single("x".into())),
+        ];
+        let query = |format| {
+            let url = build_url(&with_params("http://example.com/", &params, format)).unwrap();
+            (url.query().unwrap_or_default().to_string(), pairs(&url))
+        };
+        let owned = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        let (repeat, repeat_pairs) = query(Some(QueryArrayFormat::Repeat));
+        assert_eq!(repeat, "a=x&ids=1&ids=2+3&ids=4%2C5");
+        assert_eq!(
+            repeat_pairs,
+            owned(&[("a", "x"), ("ids", "1"), ("ids", "2 3"), ("ids", "4,5")])
+        );
+        assert_eq!(query(None).0, repeat);
+
+        let (brackets, brackets_pairs) = query(Some(QueryArrayFormat::Brackets));
+        assert_eq!(brackets, "a=x&ids%5B%5D=1&ids%5B%5D=2+3&ids%5B%5D=4%2C5");
+        assert_eq!(
+            brackets_pairs,
+            owned(&[
+                ("a", "x"),
+                ("ids[]", "1"),
+                ("ids[]", "2 3"),
+                ("ids[]", "4,5")
+            ])
+        );
+
+        // NOTE: Lossy by design, a comma inside a value cannot be told apart
+        // from the separator once parsed back.
+        let (comma, comma_pairs) = query(Some(QueryArrayFormat::Comma));
+        assert_eq!(comma, "a=x&ids=1%2C2+3%2C4%2C5");
+        assert_eq!(comma_pairs, owned(&[("a", "x"), ("ids", "1,2 3,4,5")]));
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_offending_character() {
+        assert_eq!(
+            parse_error("http://exa mple.com/"),
+            "Invalid URL, unexpected ' ' at position 10"
+        );
+        assert_eq!(
+            parse_error("  http://ex^ample.com/"),
+            "Invalid URL, unexpected '^' at position 9"
+        );
+        assert_eq!(
+            parse_error("http://example.com:99999/"),
+            "Invalid URL: http://example.com:99999/"
+        );
+    }
+}
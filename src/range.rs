@@ -0,0 +1,249 @@
+use std::{collections::HashMap, str::FromStr};
+
+use bytes::Bytes;
+use http::StatusCode;
+
+use crate::{
+    error::{RelayError, Result},
+    interop::{ByteRange, ContentRange, RangeInfo, Request, Response},
+};
+
+impl ByteRange {
+    pub fn bounded(start: u64, end: u64) -> Self {
+        Self::Bounded { start, end }
+    }
+
+    pub fn from_start(start: u64) -> Self {
+        Self::From { start }
+    }
+
+    pub fn suffix(length: u64) -> Self {
+        Self::Suffix { length }
+    }
+
+    pub fn multiple(ranges: impl IntoIterator<Item = ByteRange>) -> Self {
+        Self::Multiple {
+            ranges: ranges.into_iter().collect(),
+        }
+    }
+
+    // NOTE: Nested `Multiple` ranges are flattened into one list.
+    fn push_specs(&self, specs: &mut Vec<String>) {
+        match self {
+            Self::Bounded { start, end } => specs.push(format!("{}-{}", start, end)),
+            Self::From { start } => specs.push(format!("{}-", start)),
+            Self::Suffix { length } => specs.push(format!("-{}", length)),
+            Self::Multiple { ranges } => ranges.iter().for_each(|range| range.push_specs(specs)),
+        }
+    }
+
+    pub fn header_value(&self) -> String {
+        let mut specs = Vec::new();
+        self.push_specs(&mut specs);
+        format!("bytes={}", specs.join(","))
+    }
+}
+
+// NOTE: Accepts a `Range` header value, `bytes=0-99,-500`, or the bare spec
+// list curl's `--range` takes, `0-99,-500`.
+impl FromStr for ByteRange {
+    type Err = RelayError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let invalid = || RelayError::Parse {
+            message: format!("Invalid byte range: {:?}", value),
+            cause: None,
+            partial: None,
+        };
+        let number = |digits: &str| digits.trim().parse::<u64>().map_err(|_| invalid());
+
+        let specs = value.trim();
+        let specs = specs.strip_prefix("bytes=").unwrap_or(specs);
+
+        let mut ranges = specs
+            .split(',')
+            .map(|spec| match spec.trim().split_once('-') {
+                Some(("", length)) => Ok(Self::Suffix {
+                    length: number(length)?,
+                }),
+                Some((start, "")) => Ok(Self::From {
+                    start: number(start)?,
+                }),
+                Some((start, end)) => Ok(Self::Bounded {
+                    start: number(start)?,
+                    end: number(end)?,
+                }),
+                None => Err(invalid()),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        match ranges.len() {
+            1 => Ok(ranges.remove(0)),
+            _ => Ok(Self::Multiple { ranges }),
+        }
+    }
+}
+
+impl FromStr for ContentRange {
+    type Err = RelayError;
+
+    // NOTE: Only the `bytes` unit is understood.
+    fn from_str(value: &str) -> Result<Self> {
+        let invalid = || RelayError::Parse {
+            message: format!("Invalid Content-Range: {:?}", value),
+            cause: None,
+            partial: None,
+        };
+
+        let (unit, rest) = value.trim().split_once(' ').ok_or_else(invalid)?;
+        if !unit.eq_ignore_ascii_case("bytes") {
+            return Err(invalid());
+        }
+        let (range, complete_length) = rest.trim().split_once('/').ok_or_else(invalid)?;
+
+        let complete_length = match complete_length.trim() {
+            "*" => None,
+            length => Some(length.parse().map_err(|_| invalid())?),
+        };
+        let (start, end) = match range.trim() {
+            "*" => (None, None),
+            range => {
+                let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+                let start = start.trim().parse::<u64>().map_err(|_| invalid())?;
+                let end = end.trim().parse::<u64>().map_err(|_| invalid())?;
+                if end < start {
+                    return Err(invalid());
+                }
+                (Some(start), Some(end))
+            }
+        };
+        if start.is_none() && complete_length.is_none() {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            start,
+            end,
+            complete_length,
+        })
+    }
+}
+
+// NOTE: One range of a `206 Partial Content` response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangePart {
+    pub range: ContentRange,
+    pub content_type: Option<String>,
+    pub body: Bytes,
+}
+
+impl Response {
+    pub fn content_range(&self) -> Option<ContentRange> {
+        self.header_str("content-range")?
+            .parse()
+            .map_err(|e| tracing::debug!(error = %e, "Ignoring unparseable Content-Range"))
+            .ok()
+    }
+
+    // NOTE: `None` when the server sent no `Accept-Ranges`, which does not
+    // mean ranges are unsupported.
+    pub fn accepts_ranges(&self) -> Option<bool> {
+        let accept_ranges = self.header_str("accept-ranges")?;
+        Some(
+            accept_ranges
+                .split(',')
+                .any(|unit| unit.trim().eq_ignore_ascii_case("bytes")),
+        )
+    }
+
+    // NOTE: Every range in the response, one per part of a
+    // `multipart/byteranges` body. A `200 OK`, a server that ignored the
+    // `Range`, comes back as a single part spanning the whole body.
+    pub fn byte_ranges(&self) -> Result<Vec<RangePart>> {
+        let content_type = self.header_str("content-type").map(str::to_string);
+
+        if self.status != StatusCode::PARTIAL_CONTENT {
+            let length = self.body.body.len() as u64;
+            return Ok(vec![RangePart {
+                range: ContentRange {
+                    start: Some(0),
+                    end: Some(length.saturating_sub(1)),
+                    complete_length: Some(length),
+                },
+                content_type,
+                body: self.body.body.clone(),
+            }]);
+        }
+
+        let missing_range = || RelayError::Parse {
+            message: "Partial content without a Content-Range".into(),
+            cause: None,
+            partial: None,
+        };
+
+        let is_multipart = content_type.as_deref().is_some_and(|content_type| {
+            content_type
+                .trim_start()
+                .to_ascii_lowercase()
+                .starts_with("multipart/byteranges")
+        });
+        if !is_multipart {
+            return Ok(vec![RangePart {
+                range: self.content_range().ok_or_else(missing_range)?,
+                content_type,
+                body: self.body.body.clone(),
+            }]);
+        }
+
+        self.multipart_parts()?
+            .into_iter()
+            .map(|part| {
+                let range = part
+                    .header("content-range")
+                    .ok_or_else(missing_range)?
+                    .parse()?;
+                Ok(RangePart {
+                    range,
+                    content_type: part.content_type().map(str::to_string),
+                    body: part.body,
+                })
+            })
+            .collect()
+    }
+}
+
+fn has_range_header(request: &Request) -> bool {
+    request
+        .headers
+        .as_ref()
+        .is_some_and(|headers| headers.contains_key("range"))
+}
+
+// NOTE: A `Range` header the caller set, or suppressed, wins over `range`.
+pub(crate) fn headers(request: &Request) -> HashMap<String, String> {
+    let mut headers = HashMap::new();
+    if let Some(ref range) = request.range {
+        if !has_range_header(request) {
+            headers.insert("Range".to_string(), range.header_value());
+        }
+    }
+    headers
+}
+
+pub(crate) fn info(request: &Request, response: &Response) -> Option<RangeInfo> {
+    let requested = request.range.is_some() || has_range_header(request);
+    if !requested && response.status != StatusCode::PARTIAL_CONTENT {
+        return None;
+    }
+
+    let ignored = requested && response.status == StatusCode::OK;
+    if ignored {
+        tracing::debug!("Server ignored the requested range");
+    }
+
+    Some(RangeInfo {
+        content_range: response.content_range(),
+        accepts_ranges: response.accepts_ranges(),
+        ignored,
+    })
+}
@@ -0,0 +1,267 @@
+use std::{ffi::CString, os::raw::c_long};
+
+use curl::easy::Easy;
+
+use crate::{
+    error::{RelayError, Result},
+    interop::RawCurlOption,
+};
+
+// NOTE: Options `curl_sys` does not define, being newer,
+// see: https://curl.se/libcurl/c/easy_setopt_options.html
+const CURLOPT_PRE_PROXY: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 262;
+const CURLOPT_STREAM_DEPENDS: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 240;
+const CURLOPT_STREAM_DEPENDS_E: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 241;
+const CURLOPT_MIMEPOST: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 269;
+const CURLOPT_RESOLVER_START_DATA: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 273;
+const CURLOPT_TRAILERDATA: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 284;
+const CURLOPT_CURLU: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 282;
+const CURLOPT_HSTSREADDATA: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 302;
+const CURLOPT_HSTSWRITEDATA: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 304;
+const CURLOPT_PREREQDATA: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 313;
+const CURLOPT_SSH_HOSTKEYDATA: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 317;
+const CURLOPT_PROTOCOLS_STR: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 318;
+const CURLOPT_REDIR_PROTOCOLS_STR: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 319;
+
+// NOTE: Object options taking a `curl_slist`, the remaining object options
+// take a string unless listed in `POINTER_OPTIONS`.
+const LIST_OPTIONS: &[curl_sys::CURLoption] = &[
+    curl_sys::CURLOPT_HTTPHEADER,
+    curl_sys::CURLOPT_QUOTE,
+    curl_sys::CURLOPT_POSTQUOTE,
+    curl_sys::CURLOPT_TELNETOPTIONS,
+    curl_sys::CURLOPT_PREQUOTE,
+    curl_sys::CURLOPT_HTTP200ALIASES,
+    curl_sys::CURLOPT_MAIL_RCPT,
+    curl_sys::CURLOPT_RESOLVE,
+    curl_sys::CURLOPT_PROXYHEADER,
+    curl_sys::CURLOPT_CONNECT_TO,
+];
+
+// NOTE: Object options taking callback data, handles or buffers curl does
+// not copy, none of which can be expressed as a raw option.
+const POINTER_OPTIONS: &[curl_sys::CURLoption] = &[
+    curl_sys::CURLOPT_WRITEDATA,
+    curl_sys::CURLOPT_READDATA,
+    curl_sys::CURLOPT_ERRORBUFFER,
+    curl_sys::CURLOPT_POSTFIELDS,
+    curl_sys::CURLOPT_HTTPPOST,
+    curl_sys::CURLOPT_HEADERDATA,
+    curl_sys::CURLOPT_STDERR,
+    curl_sys::CURLOPT_PROGRESSDATA,
+    curl_sys::CURLOPT_DEBUGDATA,
+    curl_sys::CURLOPT_SHARE,
+    curl_sys::CURLOPT_PRIVATE,
+    curl_sys::CURLOPT_SSL_CTX_DATA,
+    curl_sys::CURLOPT_IOCTLDATA,
+    curl_sys::CURLOPT_SOCKOPTDATA,
+    curl_sys::CURLOPT_OPENSOCKETDATA,
+    curl_sys::CURLOPT_SEEKDATA,
+    curl_sys::CURLOPT_SSH_KEYDATA,
+    curl_sys::CURLOPT_INTERLEAVEDATA,
+    curl_sys::CURLOPT_CHUNK_DATA,
+    curl_sys::CURLOPT_FNMATCH_DATA,
+    curl_sys::CURLOPT_CLOSESOCKETDATA,
+    CURLOPT_STREAM_DEPENDS,
+    CURLOPT_STREAM_DEPENDS_E,
+    CURLOPT_MIMEPOST,
+    CURLOPT_RESOLVER_START_DATA,
+    CURLOPT_TRAILERDATA,
+    CURLOPT_CURLU,
+    CURLOPT_HSTSREADDATA,
+    CURLOPT_HSTSWRITEDATA,
+    CURLOPT_PREREQDATA,
+    CURLOPT_SSH_HOSTKEYDATA,
+];
+
+// NOTE: Options deciding where a request goes, by which protocols and which
+// addresses. Raw options override managed settings, these included, unless a
+// `TargetPolicy` applies to the request. The policy checks them as relay sets
+// them and a raw value would slip past those checks, so with a policy they
+// are only taken through their own settings on `Request`. `CURLOPT_RESOLVE`
+// and `CURLOPT_CONNECT_TO` stay allowed, the policy checks the address they
+// lead to when the connection is opened.
+const POLICY_OPTIONS: &[curl_sys::CURLoption] = &[
+    curl_sys::CURLOPT_URL,
+    curl_sys::CURLOPT_PORT,
+    curl_sys::CURLOPT_PROXY,
+    CURLOPT_PRE_PROXY,
+    curl_sys::CURLOPT_NOPROXY,
+    curl_sys::CURLOPT_DNS_SERVERS,
+    curl_sys::CURLOPT_DOH_URL,
+    curl_sys::CURLOPT_UNIX_SOCKET_PATH,
+    curl_sys::CURLOPT_ABSTRACT_UNIX_SOCKET,
+    curl_sys::CURLOPT_FOLLOWLOCATION,
+    curl_sys::CURLOPT_PROTOCOLS,
+    curl_sys::CURLOPT_REDIR_PROTOCOLS,
+    CURLOPT_PROTOCOLS_STR,
+    CURLOPT_REDIR_PROTOCOLS_STR,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OptionKind {
+    Long,
+    OffT,
+    String,
+    List,
+    Unsupported,
+}
+
+impl OptionKind {
+    // NOTE: An option's type is the `CURLOPTTYPE_*` base its id is counted
+    // from, each base is 10000 apart.
+    fn of(option: &RawCurlOption) -> Self {
+        let id = i64::from(option.id());
+        let raw = option.raw_id();
+        if id < i64::from(curl_sys::CURLOPTTYPE_OBJECTPOINT) {
+            OptionKind::Long
+        } else if id < i64::from(curl_sys::CURLOPTTYPE_FUNCTIONPOINT) {
+            if LIST_OPTIONS.contains(&raw) {
+                OptionKind::List
+            } else if POINTER_OPTIONS.contains(&raw) {
+                OptionKind::Unsupported
+            } else {
+                OptionKind::String
+            }
+        } else if id >= i64::from(curl_sys::CURLOPTTYPE_OFF_T)
+            && id < i64::from(curl_sys::CURLOPTTYPE_BLOB)
+        {
+            OptionKind::OffT
+        } else {
+            OptionKind::Unsupported
+        }
+    }
+}
+
+impl RawCurlOption {
+    pub fn id(&self) -> u32 {
+        match self {
+            RawCurlOption::Bool { option, .. }
+            | RawCurlOption::Long { option, .. }
+            | RawCurlOption::String { option, .. }
+            | RawCurlOption::List { option, .. } => *option,
+        }
+    }
+
+    // NOTE: `CURLoption` is signed on MSVC and unsigned elsewhere.
+    fn raw_id(&self) -> curl_sys::CURLoption {
+        self.id() as _
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            RawCurlOption::Bool { .. } => "bool",
+            RawCurlOption::Long { .. } => "long",
+            RawCurlOption::String { .. } => "string",
+            RawCurlOption::List { .. } => "list",
+        }
+    }
+}
+
+// NOTE: Owns a `curl_slist` handed to the handle, curl only borrows it so it
+// has to outlive the transfer.
+pub(crate) struct RawList(*mut curl_sys::curl_slist);
+
+impl Drop for RawList {
+    fn drop(&mut self) {
+        // SAFETY: The list was built by `curl_slist_append` and is freed once.
+        unsafe { curl_sys::curl_slist_free_all(self.0) }
+    }
+}
+
+fn unsupported(option: &RawCurlOption, message: String) -> RelayError {
+    tracing::error!(option = option.id(), message = %message, "Rejected raw curl option");
+    RelayError::UnsupportedFeature {
+        feature: format!("raw curl option {}", option.id()),
+        message,
+        relay: "curl".into(),
+    }
+}
+
+fn setopt_failed(option: &RawCurlOption, code: curl_sys::CURLcode) -> RelayError {
+    let e = curl::Error::new(code);
+    tracing::error!(option = option.id(), error = %e, "Failed to set raw curl option");
+    RelayError::Network {
+        message: format!("Failed to set raw curl option {}", option.id()),
+        cause: Some(e.into()),
+    }
+}
+
+fn c_string(option: &RawCurlOption, value: &str) -> Result<CString> {
+    CString::new(value).map_err(|_| unsupported(option, "value contains a NUL byte".into()))
+}
+
+pub(crate) fn apply(
+    handle: &mut Easy,
+    options: &[RawCurlOption],
+    policy_active: bool,
+) -> Result<Vec<RawList>> {
+    let mut lists = Vec::new();
+
+    for option in options {
+        if policy_active && POLICY_OPTIONS.contains(&option.raw_id()) {
+            return Err(unsupported(
+                option,
+                "a target policy applies, set it through the request instead".into(),
+            ));
+        }
+        // NOTE: libcurl takes every option through varargs, so the value
+        // type is checked against the option's type before passing it on.
+        let kind = OptionKind::of(option);
+        tracing::debug!(option = option.id(), kind = ?kind, "Setting raw curl option");
+
+        // SAFETY: `raw` is a valid easy handle and each arm passes exactly
+        // the type libcurl declares for the option. Strings are copied by
+        // libcurl, lists are kept alive by the returned `RawList`s.
+        let code = match (option, kind) {
+            (RawCurlOption::Bool { value, .. }, OptionKind::Long) => unsafe {
+                curl_sys::curl_easy_setopt(handle.raw(), option.raw_id(), *value as c_long)
+            },
+            (RawCurlOption::Long { value, .. }, OptionKind::Long) => {
+                let value = c_long::try_from(*value).map_err(|_| {
+                    unsupported(option, format!("{} does not fit in a long", value))
+                })?;
+                unsafe { curl_sys::curl_easy_setopt(handle.raw(), option.raw_id(), value) }
+            }
+            (RawCurlOption::Long { value, .. }, OptionKind::OffT) => {
+                let value = curl_sys::curl_off_t::try_from(*value).map_err(|_| {
+                    unsupported(option, format!("{} does not fit in a curl_off_t", value))
+                })?;
+                unsafe { curl_sys::curl_easy_setopt(handle.raw(), option.raw_id(), value) }
+            }
+            (RawCurlOption::String { value, .. }, OptionKind::String) => {
+                let value = c_string(option, value)?;
+                unsafe { curl_sys::curl_easy_setopt(handle.raw(), option.raw_id(), value.as_ptr()) }
+            }
+            (RawCurlOption::List { value, .. }, OptionKind::List) => {
+                let mut list = RawList(std::ptr::null_mut());
+                for item in value {
+                    let item = c_string(option, item)?;
+                    // SAFETY: `curl_slist_append` copies `item`, on failure the
+                    // existing list is left intact and freed by `RawList`.
+                    let appended = unsafe { curl_sys::curl_slist_append(list.0, item.as_ptr()) };
+                    if appended.is_null() {
+                        return Err(setopt_failed(option, curl_sys::CURLE_OUT_OF_MEMORY));
+                    }
+                    list.0 = appended;
+                }
+                let code =
+                    unsafe { curl_sys::curl_easy_setopt(handle.raw(), option.raw_id(), list.0) };
+                lists.push(list);
+                code
+            }
+            _ => {
+                return Err(unsupported(
+                    option,
+                    format!("a {} value cannot be used for this option", option.kind()),
+                ))
+            }
+        };
+
+        if code != curl_sys::CURLE_OK {
+            return Err(setopt_failed(option, code));
+        }
+    }
+
+    Ok(lists)
+}
@@ -0,0 +1,259 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    content,
+    error::{RelayError, Result},
+    interop::{Request, Response},
+    redact::RedactionPolicy,
+    transport::{CurlTransport, Transport, TransportFuture},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CassetteMode {
+    Record,
+    Replay,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchOn {
+    Method,
+    Url,
+    Body,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: Request,
+    pub response: Response,
+    #[serde(skip)]
+    replayed: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path).map_err(|e| {
+            tracing::error!(error = %e, path = %path.display(), "Failed to read cassette");
+            RelayError::Io {
+                message: format!("Failed to read cassette {}", path.display()),
+                cause: Some(e.into()),
+            }
+        })?;
+
+        serde_json::from_slice(&data).map_err(|e| {
+            tracing::error!(error = %e, path = %path.display(), "Failed to parse cassette");
+            RelayError::Parse {
+                message: format!("Failed to parse cassette {}", path.display()),
+                cause: Some(e.into()),
+                partial: None,
+            }
+        })
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_vec_pretty(self).map_err(|e| {
+            tracing::error!(error = %e, "Failed to serialize cassette");
+            RelayError::Parse {
+                message: "Failed to serialize cassette".into(),
+                cause: Some(e.into()),
+                partial: None,
+            }
+        })?;
+
+        std::fs::write(path, data).map_err(|e| {
+            tracing::error!(error = %e, path = %path.display(), "Failed to write cassette");
+            RelayError::Io {
+                message: format!("Failed to write cassette {}", path.display()),
+                cause: Some(e.into()),
+            }
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct RecordingLayer {
+    mode: CassetteMode,
+    path: PathBuf,
+    match_on: Vec<MatchOn>,
+    redaction: RedactionPolicy,
+    inner: Arc<dyn Transport>,
+    cassette: Arc<Mutex<Cassette>>,
+}
+
+impl RecordingLayer {
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        Self {
+            mode: CassetteMode::Record,
+            path: path.into(),
+            match_on: vec![MatchOn::Method, MatchOn::Url],
+            redaction: RedactionPolicy::default(),
+            inner: Arc::new(CurlTransport),
+            cassette: Arc::new(Mutex::new(Cassette::default())),
+        }
+    }
+
+    pub fn replay(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let cassette = Cassette::load(&path)?;
+
+        tracing::debug!(
+            path = %path.display(),
+            interactions = cassette.interactions.len(),
+            "Loaded cassette for replay"
+        );
+
+        Ok(Self {
+            mode: CassetteMode::Replay,
+            cassette: Arc::new(Mutex::new(cassette)),
+            ..Self::record(path)
+        })
+    }
+
+    pub fn with_transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.inner = Arc::new(transport);
+        self
+    }
+
+    pub fn match_on(mut self, match_on: &[MatchOn]) -> Self {
+        self.match_on = match_on.to_vec();
+        self
+    }
+
+    // NOTE: Header values matching the policy are replaced before anything is
+    // written to disk, defaults to the same headers redacted from logs.
+    pub fn with_redaction(mut self, policy: RedactionPolicy) -> Self {
+        self.redaction = policy;
+        self
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    pub fn cassette(&self) -> Cassette {
+        self.lock_cassette().clone()
+    }
+
+    fn lock_cassette(&self) -> MutexGuard<'_, Cassette> {
+        self.cassette
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn matches(&self, recorded: &Request, request: &Request) -> bool {
+        self.match_on.iter().all(|criterion| match criterion {
+            MatchOn::Method => recorded.method == request.method,
+            MatchOn::Url => recorded.url == request.url,
+            MatchOn::Body => {
+                recorded.content.as_ref().and_then(content::body_bytes)
+                    == request.content.as_ref().and_then(content::body_bytes)
+            }
+        })
+    }
+
+    // NOTE: Credentials and client certificates never end up in a cassette,
+    // they are not part of request matching so nothing is lost on replay.
+    fn sanitize(&self, mut request: Request, mut response: Response) -> Interaction {
+        request.headers = request
+            .headers
+            .as_ref()
+            .map(|headers| self.redaction.request_headers(headers));
+        request.auth = None;
+        request.security = None;
+        if let Some(ref mut proxy) = request.proxy {
+            proxy.auth = None;
+        }
+
+        response.headers = self.redaction.headers(&response.headers);
+
+        Interaction {
+            request,
+            response,
+            replayed: false,
+        }
+    }
+
+    #[tracing::instrument(skip(self, request), fields(request_id = request.id), level = "debug")]
+    async fn record_interaction(&self, request: Request) -> Result<Response> {
+        let response = self.inner.send(request.clone()).await?;
+
+        let mut cassette = self.lock_cassette();
+        cassette
+            .interactions
+            .push(self.sanitize(request, response.clone()));
+        cassette.save(&self.path)?;
+
+        tracing::debug!(
+            path = %self.path.display(),
+            interactions = cassette.interactions.len(),
+            "Recorded interaction"
+        );
+
+        Ok(response)
+    }
+
+    #[tracing::instrument(skip(self, request), fields(request_id = request.id), level = "debug")]
+    fn replay_interaction(&self, request: &Request) -> Result<Response> {
+        let mut cassette = self.lock_cassette();
+
+        let position = cassette
+            .interactions
+            .iter()
+            .position(|i| !i.replayed && self.matches(&i.request, request))
+            .or_else(|| {
+                cassette
+                    .interactions
+                    .iter()
+                    .rposition(|i| self.matches(&i.request, request))
+            });
+
+        let Some(position) = position else {
+            let available = cassette
+                .interactions
+                .iter()
+                .map(|i| format!("{} {}", i.request.method, i.request.url))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            tracing::error!(method = %request.method, url = %request.url, "No recorded interaction matched");
+            return Err(RelayError::Abort {
+                message: format!(
+                    "No recorded interaction matched request {} {} in {}, recorded:\n{}",
+                    request.method,
+                    request.url,
+                    self.path.display(),
+                    available
+                ),
+            });
+        };
+
+        let interaction = &mut cassette.interactions[position];
+        interaction.replayed = true;
+
+        let mut response = interaction.response.clone();
+        response.id = request.id;
+
+        tracing::debug!(position, status = %response.status, "Replaying recorded interaction");
+        Ok(response)
+    }
+}
+
+impl Transport for RecordingLayer {
+    fn send(&self, request: Request) -> TransportFuture<'_> {
+        Box::pin(async move {
+            match self.mode {
+                CassetteMode::Record => self.record_interaction(request).await,
+                CassetteMode::Replay => self.replay_interaction(&request),
+            }
+        })
+    }
+}
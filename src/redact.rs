@@ -0,0 +1,266 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+};
+
+use crate::{header::Headers, interop::Request};
+
+pub(crate) const REDACTED: &str = "***";
+
+lazy_static::lazy_static! {
+    static ref REDACTION_POLICY: RwLock<Arc<RedactionPolicy>> =
+        RwLock::new(Arc::new(RedactionPolicy::default()));
+}
+
+#[derive(Clone)]
+pub struct RedactionPolicy {
+    headers: HashSet<String>,
+    json_keys: Vec<String>,
+    secrets: HashSet<String>,
+}
+
+impl Default for RedactionPolicy {
+    fn default() -> Self {
+        Self::none()
+            .with_header("Authorization")
+            .with_header("Cookie")
+            .with_header("Set-Cookie")
+            .with_header("Proxy-Authorization")
+    }
+}
+
+// NOTE: Secret values must never be printed, not even by the policy itself.
+impl std::fmt::Debug for RedactionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedactionPolicy")
+            .field("headers", &self.headers)
+            .field("json_keys", &self.json_keys)
+            .field("secrets", &self.secrets.len())
+            .finish()
+    }
+}
+
+impl RedactionPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn none() -> Self {
+        Self {
+            headers: HashSet::new(),
+            json_keys: Vec::new(),
+            secrets: HashSet::new(),
+        }
+    }
+
+    pub fn with_header(mut self, name: impl AsRef<str>) -> Self {
+        self.headers.insert(name.as_ref().to_ascii_lowercase());
+        self
+    }
+
+    // NOTE: Key patterns are matched case-insensitively and support `*` as a
+    // wildcard, e.g. `*token*` scrubs both `access_token` and `tokenType`.
+    pub fn with_json_key(mut self, pattern: impl AsRef<str>) -> Self {
+        self.json_keys.push(pattern.as_ref().to_ascii_lowercase());
+        self
+    }
+
+    // NOTE: Secret values are scrubbed wherever they appear, including URLs
+    // and header values whose names are not otherwise sensitive.
+    pub fn with_secret(mut self, value: impl Into<String>) -> Self {
+        let value = value.into();
+        if !value.is_empty() {
+            self.secrets.insert(value);
+        }
+        self
+    }
+
+    pub(crate) fn text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for secret in &self.secrets {
+            if text.contains(secret.as_str()) {
+                text = Cow::Owned(text.replace(secret.as_str(), REDACTED));
+            }
+        }
+        text
+    }
+
+    pub(crate) fn is_sensitive_header(&self, name: &str) -> bool {
+        self.headers.contains(&name.to_ascii_lowercase())
+    }
+
+    pub(crate) fn header_value<'a>(&self, name: &str, value: &'a str) -> Cow<'a, str> {
+        if self.is_sensitive_header(name) {
+            Cow::Borrowed(REDACTED)
+        } else {
+            self.text(value)
+        }
+    }
+
+    pub(crate) fn headers(&self, headers: &HashMap<String, String>) -> HashMap<String, String> {
+        headers
+            .iter()
+            .map(|(key, value)| (key.clone(), self.header_value(key, value).into_owned()))
+            .collect()
+    }
+
+    pub(crate) fn request_headers(&self, headers: &Headers) -> Headers {
+        let mut redacted = headers.clone();
+        redacted.retain(|key, value| {
+            *value = self.header_value(key, value).into_owned();
+            true
+        });
+        redacted
+    }
+
+    // NOTE: Used for raw header blocks such as curl's debug output, where each
+    // line is either a `Name: value` pair or a status/request line.
+    pub(crate) fn header_lines<'a>(&self, block: &'a str) -> Cow<'a, str> {
+        if self.headers.is_empty() {
+            return self.text(block);
+        }
+
+        let mut redacted = false;
+        let lines = block
+            .split_inclusive('\n')
+            .map(|line| match line.split_once(':') {
+                Some((name, _)) if self.is_sensitive_header(name.trim()) => {
+                    redacted = true;
+                    let ending = &line[line.trim_end_matches(['\r', '\n']).len()..];
+                    format!("{}: {}{}", name, REDACTED, ending)
+                }
+                _ => {
+                    let line = self.text(line);
+                    redacted |= matches!(line, Cow::Owned(_));
+                    line.into_owned()
+                }
+            })
+            .collect::<String>();
+
+        if redacted {
+            Cow::Owned(lines)
+        } else {
+            Cow::Borrowed(block)
+        }
+    }
+
+    pub(crate) fn json_body(&self, body: &[u8]) -> Option<String> {
+        let mut value = serde_json::from_slice::<serde_json::Value>(body).ok()?;
+        self.scrub_json(&mut value);
+        Some(value.to_string())
+    }
+
+    fn scrub_json(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if self.is_sensitive_json_key(key) {
+                        *value = serde_json::Value::String(REDACTED.into());
+                    } else {
+                        self.scrub_json(value);
+                    }
+                }
+            }
+            serde_json::Value::String(text) => {
+                if let Cow::Owned(scrubbed) = self.text(text) {
+                    *text = scrubbed;
+                }
+            }
+            serde_json::Value::Array(items) => {
+                items.iter_mut().for_each(|item| self.scrub_json(item));
+            }
+            _ => {}
+        }
+    }
+
+    fn is_sensitive_json_key(&self, key: &str) -> bool {
+        let key = key.to_ascii_lowercase();
+        self.json_keys
+            .iter()
+            .any(|pattern| wildcard_match(pattern, &key))
+    }
+}
+
+fn wildcard_match(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let Some(first) = parts.next() else {
+        return value.is_empty();
+    };
+
+    let Some(mut rest) = value.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts = parts.collect::<Vec<_>>();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
+}
+
+pub fn set_redaction_policy(policy: RedactionPolicy) {
+    tracing::debug!(policy = ?policy, "Updating log redaction policy");
+    match REDACTION_POLICY.write() {
+        Ok(mut current) => *current = Arc::new(policy),
+        Err(poisoned) => *poisoned.into_inner() = Arc::new(policy),
+    }
+}
+
+pub(crate) fn policy() -> Arc<RedactionPolicy> {
+    match REDACTION_POLICY.read() {
+        Ok(current) => Arc::clone(&current),
+        Err(poisoned) => Arc::clone(&poisoned.into_inner()),
+    }
+}
+
+// NOTE: The policy for one request's logs, the global one plus the request's
+// own secrets.
+pub(crate) fn policy_for(request: &Request) -> Arc<RedactionPolicy> {
+    let policy = policy();
+    if request.secrets.is_empty() {
+        return policy;
+    }
+    Arc::new(
+        request
+            .secrets
+            .0
+            .iter()
+            .fold((*policy).clone(), |policy, secret| {
+                policy.with_secret(secret.as_str())
+            }),
+    )
+}
+
+// NOTE: Values redacted from the logs of the request holding them and no
+// other, e.g. those of secret template variables. Never serialized, and
+// never printed, not even by `Debug`.
+#[derive(Clone, Default)]
+pub struct Secrets(HashSet<String>);
+
+impl std::fmt::Debug for Secrets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Secrets").field(&self.0.len()).finish()
+    }
+}
+
+impl Secrets {
+    pub fn insert(&mut self, value: impl Into<String>) {
+        let value = value.into();
+        if !value.is_empty() {
+            self.0.insert(value);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
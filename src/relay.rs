@@ -1,124 +1,576 @@
-use std::{
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
-    time::SystemTime,
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
 };
 
-use curl::easy::Easy;
-use dashmap::DashMap;
+use curl::easy::{Easy, InfoType};
+use dashmap::{mapref::entry::Entry, DashMap};
 use http::StatusCode;
 use tokio_util::sync::CancellationToken;
 
 use crate::{
+    accept,
+    clock::{Clock, Stopwatch, SystemClock},
+    download,
     error::{RelayError, Result},
-    interop::{Request, Response},
+    events, gzip, idempotency, integrity,
+    interop::{
+        ContentType, ErrorPayload, Progress, Request, RequestMetrics, Response, TlsInfo,
+        TransferEvent, ValidationCode, ValidationIssue, ValidationSeverity,
+    },
+    range, raw_option, redact,
     request::CurlRequest,
     response::ResponseHandler,
-    transfer::TransferHandler,
+    share::{self, Share},
+    signing, target_policy, trace,
+    transfer::{
+        self, DebugSignals, ProgressCallback, TransferHandler, DEFAULT_MAX_PARTIAL_BODY_BYTES,
+    },
+    validate,
 };
 
 lazy_static::lazy_static! {
     static ref ACTIVE_REQUESTS: DashMap<i64, Arc<AtomicBool>> = DashMap::new();
 }
 
-#[tracing::instrument(skip(request), fields(request_id = request.id), level = "debug")]
-fn execute_request(request: &Request, cancel_token: &CancellationToken) -> Result<Response> {
+// NOTE: Past this many lines, a captured trace keeps only its start.
+const MAX_TRACE_LINES: usize = 1000;
+
+// NOTE: curl's debug output laid out as `curl -v` prints it, `*` for curl's
+// own notes and `>` and `<` for what was sent and received. Bodies are noted
+// by size only, like in the log.
+#[derive(Default)]
+struct DebugTrace {
+    lines: std::sync::Mutex<(Vec<String>, usize)>,
+}
+
+impl DebugTrace {
+    fn record(&self, info_type: &InfoType, text: &str) {
+        let prefix = match info_type {
+            InfoType::Text => '*',
+            InfoType::HeaderOut | InfoType::DataOut => '>',
+            InfoType::HeaderIn | InfoType::DataIn => '<',
+            _ => return,
+        };
+        let mut lines = self
+            .lines
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (kept, dropped) = &mut *lines;
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            if kept.len() < MAX_TRACE_LINES {
+                kept.push(format!("{} {}", prefix, line));
+            } else {
+                *dropped += 1;
+            }
+        }
+    }
+
+    fn take(&self) -> Vec<String> {
+        let mut lines = self
+            .lines
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (mut kept, dropped) = std::mem::take(&mut *lines);
+        if dropped > 0 {
+            kept.push(format!("* {} more lines left out", dropped));
+        }
+        kept
+    }
+}
+
+#[tracing::instrument(
+    skip(request, progress, clock, share),
+    fields(
+        request_id = request.id,
+        trace_id = tracing::field::Empty,
+        span_id = tracing::field::Empty
+    ),
+    level = "debug"
+)]
+fn execute_request(
+    request: &Request,
+    cancel_token: &CancellationToken,
+    progress: Option<ProgressCallback>,
+    clock: &dyn Clock,
+    share: Option<&Share>,
+) -> Result<Response> {
     tracing::info!(
         method = %request.method,
-        url = %request.url,
+        url = %redact::policy_for(request).text(&request.url),
         "Executing request"
     );
 
+    validate::ensure_valid(request)?;
+
     let id = request.id;
+    // NOTE: Outlives `handle`, which calls back into it on every connect.
+    let target_policy = target_policy::guard(request);
     let mut handle = Easy::new();
-    let start_time = SystemTime::now();
+    let stopwatch = Stopwatch::start(clock);
+    let dns_cache_hit = share::attach(&mut handle, request, share);
+    if let Some(ref target_policy) = target_policy {
+        target_policy.attach(&mut handle)?;
+    }
+
+    let trace_context = trace::resolve(request);
+    if let Some(ref context) = trace_context {
+        let span = tracing::Span::current();
+        span.record("trace_id", context.trace_id.as_str());
+        span.record("span_id", context.span_id.as_str());
+    }
 
-    let mut curl_request = CurlRequest::new(&mut handle, request);
+    let signer = signing::signer(id);
+    let mut curl_request = CurlRequest::new(&mut handle, request)
+        .with_trace_context(trace_context.as_ref())
+        .with_signer(signer.as_deref());
     curl_request.prepare()?;
+    let verification_skipped = curl_request.verification_skipped();
 
-    tracing::debug!(request = ?request, "Full request details before sending");
+    let policy = redact::policy_for(request);
+
+    tracing::debug!(
+        headers = ?request.headers.as_ref().map(|headers| policy.request_headers(headers)),
+        params = ?request.params,
+        version = ?request.version,
+        "Full request details before sending"
+    );
 
     handle.verbose(true).map_err(|e| RelayError::Network {
         message: "Failed to set verbose mode".into(),
-        cause: Some(e.to_string()),
+        cause: Some(e.into()),
     })?;
 
+    let signals = Arc::new(DebugSignals::default());
+    let observed = Arc::clone(&signals);
+    let events = events::emitter(id);
+    let observed_events = events.clone();
+    let debug_trace = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.capture_trace)
+        .unwrap_or(false)
+        .then(|| Arc::new(DebugTrace::default()));
+    let captured_trace = debug_trace.clone();
+
     handle
-        .debug_function(|info_type, data| {
-            if let Ok(s) = std::str::from_utf8(data) {
-                tracing::debug!(info_type = ?info_type, s = ?s, "cURL debug fn");
+        .debug_function(move |info_type, data| {
+            observed.observe(&info_type, data);
+            if let Some(ref events) = observed_events {
+                events.observe_debug(&info_type, data);
+            }
+            // NOTE: Bodies may carry secrets no policy knows about, e.g. a
+            // form's password field, so only their size is logged.
+            match info_type {
+                InfoType::HeaderIn | InfoType::HeaderOut | InfoType::Text => {
+                    if let Ok(s) = std::str::from_utf8(data) {
+                        let s = match info_type {
+                            InfoType::Text => policy.text(s),
+                            _ => policy.header_lines(s),
+                        };
+                        tracing::debug!(info_type = ?info_type, s = ?s, "cURL debug fn");
+                        if let Some(ref trace) = captured_trace {
+                            trace.record(&info_type, &s);
+                        }
+                    }
+                }
+                _ => {
+                    tracing::debug!(info_type = ?info_type, bytes = data.len(), "cURL debug fn");
+                    if let Some(ref trace) = captured_trace {
+                        trace.record(&info_type, &format!("[{} bytes]", data.len()));
+                    }
+                }
             }
         })
         .map_err(|e| RelayError::Network {
             message: "Failed to set debug function".into(),
-            cause: Some(e.to_string()),
+            cause: Some(e.into()),
         })?;
 
-    let mut transfer_handler = TransferHandler::new();
-    transfer_handler.handle_transfer(&mut handle, cancel_token)?;
+    // NOTE: Kept alive until the transfer is done, curl does not copy lists.
+    let _raw_lists = raw_option::apply(
+        &mut handle,
+        &request.raw_options,
+        request.target_policy.is_some(),
+    )?;
+
+    let max_decompressed_bytes = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.max_decompressed_bytes);
+
+    let first_byte_timeout = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.first_byte_timeout);
+
+    let timeout = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.timeout);
+
+    let estimate = request.content.as_ref().map(ContentType::estimated_size);
+    if let Some(ref estimate) = estimate {
+        tracing::debug!(
+            total_bytes = estimate.total_bytes,
+            parts = estimate.parts.len(),
+            "Estimated request body size"
+        );
+    }
+
+    let body_hash = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.body_hash.as_ref());
+    let hasher = body_hash.map(integrity::hasher).transpose()?;
+
+    let body_capture = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.body_capture);
+
+    let mut transfer_handler = TransferHandler::new()
+        .with_max_decompressed_bytes(max_decompressed_bytes)
+        .with_body_capture(body_capture)
+        .with_progress(
+            progress,
+            estimate.filter(|estimate| !estimate.parts.is_empty()),
+        )
+        .with_first_byte_timeout(first_byte_timeout.map(std::time::Duration::from_millis))
+        .with_timeout(timeout.map(std::time::Duration::from_millis))
+        .with_hasher(hasher)
+        .with_download(download::target(id))
+        .with_target_policy(target_policy.clone())
+        .with_events(events)
+        .with_debug_signals(Arc::clone(&signals));
+    let transferred = transfer_handler.handle_transfer(&mut handle, cancel_token);
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_transfer(&mut handle, transferred.is_ok());
+    if let Err(mut error) = transferred {
+        let max_partial_body_bytes = request
+            .meta
+            .as_ref()
+            .and_then(|meta| meta.options.as_ref())
+            .and_then(|options| options.max_partial_body_bytes)
+            .unwrap_or(DEFAULT_MAX_PARTIAL_BODY_BYTES);
+
+        if let Some(partial) =
+            transfer_handler.partial(&mut handle, stopwatch.timing(), max_partial_body_bytes)
+        {
+            error.attach_partial(partial);
+        }
+        if let Some(ref trace) = debug_trace {
+            error.attach_trace(trace.take());
+        }
+        return Err(error);
+    }
+    share::resolved(request, share, dns_cache_hit);
 
     let status = handle.response_code().map_err(|e| {
         tracing::error!(error = %e, "Failed to get response code");
         RelayError::Network {
             message: "Failed to get response code".into(),
-            cause: Some(e.to_string()),
+            cause: Some(e.into()),
         }
-    })? as u16;
+    })?;
 
     let header_size = handle.header_size().map_err(|e| {
         tracing::error!(error = %e, "Failed to get header size");
         RelayError::Network {
             message: "Failed to get header size".into(),
-            cause: Some(e.to_string()),
+            cause: Some(e.into()),
         }
     })?;
 
-    let (body, headers) = transfer_handler.into_parts();
+    let final_url = handle
+        .effective_url()
+        .map_err(|e| {
+            tracing::error!(error = %e, "Failed to get effective URL");
+            RelayError::Network {
+                message: "Failed to get effective URL".into(),
+                cause: Some(e.into()),
+            }
+        })?
+        .unwrap_or(&request.url)
+        .to_string();
+
+    let max_response_time = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.max_response_time);
+
+    // NOTE: Unlike `timeout` this never aborts the transfer, the response is
+    // received in full and only then rejected for exceeding the limit.
+    if let Some(limit_ms) = max_response_time {
+        let elapsed = handle.total_time().map_err(|e| {
+            tracing::error!(error = %e, "Failed to get total time");
+            RelayError::Network {
+                message: "Failed to get total time".into(),
+                cause: Some(e.into()),
+            }
+        })?;
+        let elapsed_ms = elapsed.as_millis().min(u64::MAX as u128) as u64;
+
+        if elapsed_ms > limit_ms {
+            tracing::warn!(elapsed_ms, limit_ms, "Response time exceeded limit");
+            return Err(RelayError::ResponseTime {
+                message: format!(
+                    "Response took {}ms, exceeding the {}ms limit",
+                    elapsed_ms, limit_ms
+                ),
+                elapsed_ms,
+                limit_ms,
+            });
+        }
+    }
+
+    // NOTE: A transfer stopped at the capture limit only hashed part of the
+    // body, so there is no digest. Validation refuses such a request when it
+    // expects a hash.
+    let digest = match transfer_handler.capture_stopped() {
+        true => None,
+        false => transfer_handler.body_digest()?,
+    };
+    let truncated_size = transfer_handler.truncated_size();
+    let early_hints = transfer_handler.take_early_hints();
+    let (mut body, headers, head) = transfer_handler.into_parts();
+    let gzip_detected = match gzip::sniff(request, &headers, &body)? {
+        Some(decoded) => {
+            body = decoded.into();
+            true
+        }
+        None => false,
+    };
 
     tracing::info!(
         status = status,
         body_size = body.len(),
         header_size = header_size,
+        final_url = %redact::policy_for(request).text(&final_url),
         "Request completed"
     );
 
-    // NOTE: If this fails, something has gone very wrong.
-    let status_code = StatusCode::from_u16(status).unwrap();
+    // NOTE: curl accepts codes `StatusCode` cannot hold, those are kept as
+    // `raw_status` rather than failing a response that was otherwise fine.
+    let (status_code, raw_status) = match u16::try_from(status)
+        .ok()
+        .and_then(|code| StatusCode::from_u16(code).ok())
+    {
+        Some(status_code) => (status_code, None),
+        None => {
+            tracing::warn!(status, "Invalid status code, reporting 502 Bad Gateway");
+            (StatusCode::BAD_GATEWAY, Some(status))
+        }
+    };
 
-    ResponseHandler::new(
+    let mut response = ResponseHandler::new(
         id,
         headers,
         body,
         status_code,
         header_size,
-        start_time,
-        SystemTime::now(),
+        stopwatch,
         request.version.clone(),
+        final_url,
     )
-    .build()
+    .with_head(head, raw_status)
+    .with_body_size(truncated_size)
+    .with_redaction(redact::policy_for(request))
+    .build()?;
+
+    response.meta.trace = trace_context;
+    response.meta.timing.dns_cache_hit = dns_cache_hit;
+    response.meta.timing.phases = Some(transfer::phase_marks(&mut handle));
+    response.meta.gzip_detected = gzip_detected;
+    response.early_hints = early_hints;
+    response.body_truncated = truncated_size.is_some();
+    response.meta.connection = transfer::connection_info(&mut handle, &signals);
+    response.meta.range = range::info(request, &response);
+    response.meta.negotiation = accept::info(request, &response);
+    response.meta.idempotency_key = idempotency::key(request);
+    if let Some(assertions) = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.assertions.as_deref())
+    {
+        response.meta.assertions = response.check(assertions);
+    }
+    if let (Some(config), Some(digest)) = (body_hash, digest) {
+        response.body_hash = Some(integrity::hex(&digest));
+        integrity::verify(config, &digest)?;
+    }
+    if let Some(charset) = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.force_charset.clone())
+    {
+        tracing::debug!(declared = ?response.body.charset, forced = %charset, "Overriding response charset");
+        response.body.charset = Some(charset);
+    }
+    response.metrics = Some(RequestMetrics {
+        attempts: 1,
+        retry_delay_ms: 0,
+        bytes_sent: handle.request_size().unwrap_or_default(),
+        bytes_received: header_size + handle.download_size().map_or(0, |size| size as u64),
+        not_modified: status_code == StatusCode::NOT_MODIFIED,
+    });
+    let session_resumed = signals.session_resumed();
+    if verification_skipped || session_resumed.is_some() {
+        response.meta.tls = Some(TlsInfo {
+            verification_skipped,
+            session_resumed,
+        });
+    }
+
+    let fail_on_error = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.fail_on_error)
+        .unwrap_or(false);
+
+    // NOTE: Checked here instead of through curl's `CURLOPT_FAILONERROR`,
+    // which aborts the transfer before the body is read.
+    if fail_on_error
+        && raw_status.is_none()
+        && (status_code.is_client_error() || status_code.is_server_error())
+    {
+        tracing::warn!(status = %status_code, "Failing on error status");
+        return Err(RelayError::HttpStatus {
+            status: status_code,
+            headers: response.headers,
+            body: response.body.body,
+        });
+    }
+
+    Ok(response)
 }
 
 #[tracing::instrument(skip(request), fields(request_id = request.id), level = "debug")]
 pub async fn execute(request: Request) -> Result<Response> {
-    let request_id = request.id;
+    run(request, None, None).await
+}
+
+// NOTE: `execute` for hosts that take errors as an `ErrorPayload`, with the
+// id the request was executed under filled in.
+pub async fn execute_interop(mut request: Request) -> std::result::Result<Response, ErrorPayload> {
+    let request_id = request.ensure_id();
+    execute(request)
+        .await
+        .map_err(|e| ErrorPayload::new(request_id, e))
+}
+
+// NOTE: One attempt for a `Transport`, attached to `share` when given, which
+// the transfer thread keeps alive until its handle is gone. Left out of the
+// request metrics, `RelayClient` counts the request once its retries are done.
+pub(crate) async fn transfer(request: Request, share: Option<Arc<Share>>) -> Result<Response> {
+    run_transfer(request, None, share).await
+}
+
+// NOTE: `progress` is called from the transfer thread whenever the byte counts
+// change, for multipart uploads it also reports the part being sent.
+#[tracing::instrument(skip(request, progress), fields(request_id = request.id), level = "debug")]
+pub async fn execute_with_progress<F>(request: Request, progress: F) -> Result<Response>
+where
+    F: Fn(&Progress) + Send + Sync + 'static,
+{
+    run(request, Some(Arc::new(progress)), None).await
+}
+
+// NOTE: `listener` gets the request's `TransferEvent`s, from `Queued` to
+// `Done` or `Failed`, called from the transfer thread while it runs.
+#[tracing::instrument(skip(request, listener), fields(request_id = request.id), level = "debug")]
+pub async fn execute_with_events<F>(mut request: Request, listener: F) -> Result<Response>
+where
+    F: Fn(&TransferEvent) + Send + Sync + 'static,
+{
+    let registration = events::listen(request.ensure_id(), Arc::new(listener));
+    let result = run(request, None, None).await;
+    registration.emitter().finish(&result);
+    result
+}
+
+// NOTE: Every public way of executing goes through here, which is what lets
+// metrics count each request exactly once however it ends.
+async fn run(
+    request: Request,
+    progress: Option<ProgressCallback>,
+    share: Option<Arc<Share>>,
+) -> Result<Response> {
+    #[cfg(feature = "metrics")]
+    let (method, url, started) = (
+        request.method.clone(),
+        request.url.clone(),
+        std::time::Instant::now(),
+    );
+
+    let result = run_transfer(request, progress, share).await;
+
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_request(&method, &url, &result, started.elapsed());
+
+    result
+}
+
+async fn run_transfer(
+    mut request: Request,
+    progress: Option<ProgressCallback>,
+    share: Option<Arc<Share>>,
+) -> Result<Response> {
+    let request_id = request.ensure_id();
+    tracing::Span::current().record("request_id", request_id);
+    request.ensure_idempotency_key();
     let cancelled = Arc::new(AtomicBool::new(false));
 
+    let policy = redact::policy_for(&request);
     tracing::info!(
         method = %request.method,
-        url = %request.url,
+        url = %policy.text(&request.url),
         "Starting request execution"
     );
 
-    ACTIVE_REQUESTS.insert(request_id, Arc::clone(&cancelled));
+    // NOTE: Cancellation and log correlation are keyed by id, so a second
+    // request reusing an in-flight id is rejected rather than shadowing it.
+    match ACTIVE_REQUESTS.entry(request_id) {
+        Entry::Occupied(_) => {
+            let message = format!("Request id {} is already in flight", request_id);
+            tracing::error!("{}", message);
+            return Err(RelayError::Validation {
+                message: format!("id: {}", message),
+                issues: vec![ValidationIssue {
+                    code: ValidationCode::DuplicateId,
+                    severity: ValidationSeverity::Error,
+                    field: "id".into(),
+                    message,
+                }],
+            });
+        }
+        Entry::Vacant(entry) => {
+            entry.insert(Arc::clone(&cancelled));
+        }
+    }
 
     let cancel_token = CancellationToken::new();
     let cancel_token_clone = cancel_token.clone();
     let cancelled_clone = Arc::clone(&cancelled);
 
     let handle = std::thread::spawn(move || {
-        let result = execute_request(&request, &cancel_token);
+        let result = execute_request(
+            &request,
+            &cancel_token,
+            progress,
+            &SystemClock,
+            share.as_deref(),
+        );
         if cancel_token_clone.is_cancelled() {
             cancelled_clone.store(true, Ordering::SeqCst);
         }
@@ -149,7 +601,14 @@ pub async fn execute(request: Request) -> Result<Response> {
     ACTIVE_REQUESTS.remove(&request_id);
     tracing::debug!("Request execution completed");
 
-    tracing::debug!("Result {:#?}", result);
+    match result {
+        Ok(ref response) => tracing::debug!(
+            status = %response.status,
+            headers = ?policy.headers(&response.headers),
+            "Result ok"
+        ),
+        Err(ref error) => tracing::debug!(error = ?error, "Result error"),
+    }
 
     result
 }
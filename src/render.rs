@@ -0,0 +1,273 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use http::{Method, Version};
+
+use crate::{
+    accept, auth, content,
+    error::Result,
+    estimate,
+    header::Headers,
+    id, idempotency,
+    interop::{AuthType, ContentType, FormValue, MediaType, Request, Response},
+    query, range, trace, util,
+};
+
+// NOTE: curl picks a random boundary per transfer, so rendered multipart
+// bodies are only accurate up to the boundary. It is as long as curl's, which
+// keeps the rendered `Content-Length` exact.
+const RENDER_BOUNDARY: &str = "------------------------relayrenderboundary000";
+const _: () = assert!(RENDER_BOUNDARY.len() == estimate::FORM_BOUNDARY_LEN);
+
+fn version_token(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_2 => "HTTP/2",
+        Version::HTTP_3 => "HTTP/3",
+        _ => "HTTP/1.1",
+    }
+}
+
+pub(crate) fn body_text(body: &[u8], media_type: impl std::fmt::Display) -> String {
+    match std::str::from_utf8(body) {
+        Ok(text) => text.to_string(),
+        Err(_) => format!("<{} bytes of {}>", body.len(), media_type),
+    }
+}
+
+// NOTE: Mirrors what curl sends for `CURLOPT_ACCEPT_ENCODING` set to `""`,
+// which is every encoding the linked libcurl can decode.
+fn accept_encoding(request: &Request) -> String {
+    let decompress = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.decompress)
+        .unwrap_or(true);
+
+    if !decompress {
+        return "identity".into();
+    }
+
+    util::content_encodings().join(", ")
+}
+
+fn multipart_body(content: &[(String, Vec<FormValue>)]) -> Vec<u8> {
+    let mut body = Vec::new();
+
+    for (name, values) in content {
+        for value in values {
+            body.extend_from_slice(format!("--{}\r\n", RENDER_BOUNDARY).as_bytes());
+            body.extend_from_slice(estimate::form_part_head(name, value).as_bytes());
+            match value {
+                FormValue::Text { value } => body.extend_from_slice(value.as_bytes()),
+                FormValue::File {
+                    content_type, data, ..
+                } => match std::str::from_utf8(data) {
+                    Ok(_) => body.extend_from_slice(data),
+                    Err(_) => body.extend_from_slice(
+                        format!("<{} bytes of {}>", data.len(), content_type).as_bytes(),
+                    ),
+                },
+            }
+            body.extend_from_slice(b"\r\n");
+        }
+    }
+
+    body.extend_from_slice(format!("--{}--\r\n", RENDER_BOUNDARY).as_bytes());
+    body
+}
+
+fn has_header(headers: &[(String, String)], name: &str) -> bool {
+    headers
+        .iter()
+        .any(|(existing, _)| existing.eq_ignore_ascii_case(name))
+}
+
+fn has_header_name(names: &[String], name: &str) -> bool {
+    names
+        .iter()
+        .any(|existing| existing.eq_ignore_ascii_case(name))
+}
+
+// NOTE: The headers relay derives from the request itself, caller headers
+// last so they win, before curl adds its own.
+pub(crate) fn derived_headers(request: &Request) -> Headers {
+    let mut computed = Headers::new();
+    if let Some(ref content) = request.content {
+        computed.merge(content::content_headers(content));
+    }
+    if let Some(ref auth) = request.auth {
+        computed.merge(auth::auth_headers(auth));
+    }
+    computed.merge(id::headers(request));
+    computed.merge(idempotency::headers(request));
+    computed.merge(range::headers(request));
+    computed.merge(accept::headers(request));
+    computed.merge(trace::headers(request, trace::resolve(request).as_ref()));
+    if let Some(ref headers) = request.headers {
+        computed.merge(headers.clone());
+    }
+    computed
+}
+
+impl Request {
+    // NOTE: Renders the request as HTTP/1.1 text using the same header
+    // derivation as the send path, plus the headers curl adds on its own.
+    // Digest credentials and OAuth2 token exchanges depend on the server and
+    // are shown as placeholders.
+    #[tracing::instrument(skip(self), fields(request_id = self.id), level = "debug")]
+    pub fn to_http_text(&self) -> Result<String> {
+        let url = query::build_url(self)?;
+
+        let mut target = url.path().to_string();
+        if let Some(query) = url.query() {
+            target.push('?');
+            target.push_str(query);
+        }
+
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let computed = derived_headers(self);
+        let suppressed = computed.suppressed().cloned().collect::<Vec<_>>();
+        let explicit = computed.into_iter().collect::<Vec<_>>();
+
+        let mut headers = vec![("Host".to_string(), host)];
+
+        if !has_header(&explicit, "authorization") {
+            match self.auth {
+                Some(AuthType::Basic {
+                    ref username,
+                    ref password,
+                }) => headers.push((
+                    "Authorization".into(),
+                    format!(
+                        "Basic {}",
+                        BASE64.encode(format!("{}:{}", username, password))
+                    ),
+                )),
+                Some(AuthType::Digest { .. }) => headers.push((
+                    "Authorization".into(),
+                    "Digest <computed from the server challenge>".into(),
+                )),
+                Some(AuthType::OAuth2 {
+                    access_token: None, ..
+                }) => headers.push((
+                    "Authorization".into(),
+                    "Bearer <obtained from the token endpoint>".into(),
+                )),
+                _ => {}
+            }
+        }
+
+        if !has_header(&explicit, "accept") {
+            headers.push(("Accept".into(), "*/*".into()));
+        }
+        let encoding = accept_encoding(self);
+        if !encoding.is_empty() && !has_header(&explicit, "accept-encoding") {
+            headers.push(("Accept-Encoding".into(), encoding));
+        }
+
+        headers.extend(explicit);
+
+        let body = match (&self.content, self.method == Method::HEAD) {
+            (Some(form @ ContentType::Form { content, .. }), false)
+            | (Some(form @ ContentType::Multipart { content, .. }), false) => {
+                if !has_header(&headers, "content-type") {
+                    headers.push((
+                        "Content-Type".into(),
+                        format!(
+                            "{}; boundary={}",
+                            MediaType::MultipartFormData,
+                            RENDER_BOUNDARY
+                        ),
+                    ));
+                }
+                let body = multipart_body(content);
+                if !has_header(&headers, "content-length") {
+                    headers.push((
+                        "Content-Length".into(),
+                        form.estimated_size().total_bytes.to_string(),
+                    ));
+                }
+                Some(String::from_utf8_lossy(&body).into_owned())
+            }
+            (Some(content), false) => {
+                let bytes = content::body_bytes(content).unwrap_or_default();
+                // NOTE: curl labels `CURLOPT_POSTFIELDS` bodies as urlencoded
+                // unless a `Content-Type` is given explicitly.
+                if !has_header(&headers, "content-type") {
+                    headers.push(("Content-Type".into(), MediaType::FormUrlEncoded.to_string()));
+                }
+                if !has_header(&headers, "content-length") {
+                    headers.push((
+                        "Content-Length".into(),
+                        content.estimated_size().total_bytes.to_string(),
+                    ));
+                }
+                Some(body_text(&bytes, content::media_type(content)))
+            }
+            _ => None,
+        };
+
+        // NOTE: Suppressed names never reach the wire, whether given by the
+        // caller or added by curl.
+        headers.retain(|(name, _)| !has_header_name(&suppressed, name));
+
+        let policy = crate::redact::policy_for(self);
+        tracing::debug!(
+            headers = ?headers
+                .iter()
+                .map(|(name, value)| format!("{}: {}", name, policy.header_value(name, value)))
+                .collect::<Vec<_>>(),
+            "Rendered request headers"
+        );
+
+        let mut text = format!(
+            "{} {} {}\r\n",
+            self.method,
+            target,
+            version_token(self.version)
+        );
+        for (name, value) in &headers {
+            text.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        text.push_str("\r\n");
+        if let Some(body) = body {
+            text.push_str(&body);
+        }
+
+        Ok(text)
+    }
+}
+
+impl Response {
+    pub fn to_http_text(&self) -> String {
+        let mut text = format!(
+            "{} {} {}\r\n",
+            version_token(self.version),
+            self.status.as_u16(),
+            self.status_text
+        );
+
+        let mut headers = self.headers.iter().collect::<Vec<_>>();
+        headers.sort_by_key(|(name, _)| name.to_ascii_lowercase());
+
+        // NOTE: Repeated `Set-Cookie` headers are joined with newlines when
+        // received, so they are split back into one line each.
+        for (name, value) in headers {
+            for value in value.split('\n') {
+                text.push_str(&format!("{}: {}\r\n", name, value));
+            }
+        }
+        text.push_str("\r\n");
+
+        if !self.body.body.is_empty() {
+            text.push_str(&body_text(&self.body.body, self.body.media_type));
+        }
+
+        text
+    }
+}
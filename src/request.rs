@@ -1,19 +1,113 @@
-use curl::easy::Easy;
+use curl::easy::{Easy, PostRedirections};
+use http::Method;
 use std::{collections::HashMap, ops::Not};
 
 use crate::{
-    auth::AuthHandler,
+    accept,
+    auth::{self, AuthHandler},
     content::ContentHandler,
+    digest,
     error::{RelayError, Result},
-    header::HeadersBuilder,
-    interop::{ApiKeyLocation, AuthType, Request},
-    security::SecurityHandler,
-    util::ToCurlVersion,
+    header::{Headers, HeadersBuilder},
+    id, idempotency,
+    interop::{ApiKeyLocation, AuthType, ProxyAuthScheme, Request, TraceContext},
+    query, range, redact,
+    security::{self, SecurityHandler},
+    signing::{self, RequestSigner},
+    target_policy, trace,
+    util::{
+        self, EasyExt, ToCurlVersion, CURLE_NOT_BUILT_IN, CURLFOLLOW_OBEYCODE,
+        CURLOPT_DNS_INTERFACE, CURLOPT_DNS_LOCAL_IP4, CURLOPT_DNS_LOCAL_IP6,
+        CURLOPT_HAPPY_EYEBALLS_TIMEOUT_MS, CURLOPT_TCP_FASTOPEN,
+    },
 };
 
+// NOTE: Whether curl reaches `url` through an HTTP proxy with `CONNECT`,
+// which it does for everything but plain HTTP.
+fn tunnels(url: &str) -> bool {
+    url::Url::parse(&query::with_default_scheme(url))
+        .map_or(true, |url| !url.scheme().eq_ignore_ascii_case("http"))
+}
+
+// NOTE: Receive and send buffer limits enforced by libcurl, which clamps
+// out of range values silently. The defaults are 16 KiB for downloads and
+// 64 KiB for uploads, raising them mostly helps large transfers on fast links.
+const BUFFER_SIZE_RANGE: (usize, usize) = (1024, 10 * 1024 * 1024);
+const UPLOAD_BUFFER_SIZE_RANGE: (usize, usize) = (16 * 1024, 2 * 1024 * 1024);
+
+fn clamp_buffer_size(name: &str, size: usize, (min, max): (usize, usize)) -> usize {
+    let clamped = size.clamp(min, max);
+    if clamped != size {
+        tracing::warn!(
+            option = name,
+            requested = size,
+            applied = clamped,
+            min,
+            max,
+            "Buffer size out of range, clamping"
+        );
+    }
+    clamped
+}
+
+// NOTE: `CURLE_NOT_BUILT_IN` means libcurl lacks the resolver backend, c-ares
+// for DNS servers or DoH support, and a libcurl older than the option reports
+// it as unknown. Either is a missing feature rather than a failure.
+fn resolver_error(e: curl::Error, option: &str, what: &str) -> RelayError {
+    if e.code() == CURLE_NOT_BUILT_IN || e.is_unknown_option() {
+        tracing::error!(option, error = %e, "Resolver option not supported by libcurl");
+        RelayError::UnsupportedFeature {
+            feature: option.into(),
+            message: format!("libcurl was built without support for {}", what),
+            relay: "curl".into(),
+        }
+    } else {
+        tracing::error!(option, error = %e, "Failed to set resolver option");
+        RelayError::Network {
+            message: format!("Failed to set {}", what),
+            cause: Some(e.into()),
+        }
+    }
+}
+
+// NOTE: How request content is attached for each method, curl is always
+// driven through `custom_request` so the verb is preserved even when
+// `post_fields_copy` or `httppost` would otherwise switch it to POST.
+//
+// | Method            | Content    | Notes                                      |
+// |-------------------|------------|--------------------------------------------|
+// | POST, PUT, PATCH  | Attached   |                                            |
+// | DELETE            | Attached   | e.g. bulk delete APIs                      |
+// | Extensions        | Attached   | e.g. WebDAV `PROPFIND`, `QUERY`            |
+// | GET, OPTIONS      | Attached   | No defined semantics, logged as a warning  |
+// | HEAD              | Dropped    | Response has no body, `nobody` is set      |
+// | TRACE, CONNECT    | Dropped    | Must not carry content, see RFC 9110       |
+//
+// Dropped content is left out of the transfer with a warning, `validate`
+// reports it the same way so callers can catch it before sending.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum BodyPolicy {
+    Attach,
+    Discouraged,
+    Dropped,
+}
+
+impl BodyPolicy {
+    pub(crate) fn for_method(method: &Method) -> Self {
+        match *method {
+            Method::GET | Method::OPTIONS => Self::Discouraged,
+            Method::HEAD | Method::TRACE | Method::CONNECT => Self::Dropped,
+            _ => Self::Attach,
+        }
+    }
+}
+
 pub(crate) struct CurlRequest<'a> {
     handle: &'a mut Easy,
     request: &'a Request,
+    trace_context: Option<&'a TraceContext>,
+    signer: Option<&'a dyn RequestSigner>,
+    verification_skipped: bool,
 }
 
 impl<'a> CurlRequest<'a> {
@@ -24,7 +118,27 @@ impl<'a> CurlRequest<'a> {
             method = %request.method,
             "Creating new curl request"
         );
-        Self { handle, request }
+        Self {
+            handle,
+            request,
+            trace_context: None,
+            signer: None,
+            verification_skipped: false,
+        }
+    }
+
+    pub(crate) fn with_trace_context(mut self, trace_context: Option<&'a TraceContext>) -> Self {
+        self.trace_context = trace_context;
+        self
+    }
+
+    pub(crate) fn with_signer(mut self, signer: Option<&'a dyn RequestSigner>) -> Self {
+        self.signer = signer;
+        self
+    }
+
+    pub(crate) fn verification_skipped(&self) -> bool {
+        self.verification_skipped
     }
 
     #[tracing::instrument(skip(self), fields(request_id = self.request.id), level = "debug")]
@@ -37,15 +151,26 @@ impl<'a> CurlRequest<'a> {
                 tracing::error!(error = %e, "Failed to set request method");
                 RelayError::Network {
                     message: "Failed to set request method".into(),
-                    cause: Some(e.to_string()),
+                    cause: Some(e.into()),
+                }
+            })?;
+
+        if self.request.method == Method::HEAD {
+            self.handle.nobody(true).map_err(|e| {
+                tracing::error!(error = %e, "Failed to set nobody for HEAD request");
+                RelayError::Network {
+                    message: "Failed to set nobody for HEAD request".into(),
+                    cause: Some(e.into()),
                 }
             })?;
+        }
 
-        self.handle.url(&self.request.url).map_err(|e| {
+        let url = query::wire_url(self.request)?;
+        self.handle.url(&url).map_err(|e| {
             tracing::error!(error = %e, "Failed to set URL");
             RelayError::Network {
                 message: "Failed to set URL".into(),
-                cause: Some(e.to_string()),
+                cause: Some(e.into()),
             }
         })?;
 
@@ -58,7 +183,8 @@ impl<'a> CurlRequest<'a> {
                     tracing::error!(error = %e, "Failed to parse URL for API key addition");
                     RelayError::Parse {
                         message: "Failed to parse URL for API key addition".into(),
-                        cause: Some(e.to_string()),
+                        cause: Some(e.into()),
+                        partial: None,
                     }
                 })?;
 
@@ -70,7 +196,7 @@ impl<'a> CurlRequest<'a> {
                     tracing::error!(error = %e, "Failed to set URL with API key");
                     RelayError::Network {
                         message: "Failed to set URL with API key".into(),
-                        cause: Some(e.to_string()),
+                        cause: Some(e.into()),
                     }
                 })?;
             } else {
@@ -78,7 +204,7 @@ impl<'a> CurlRequest<'a> {
                     tracing::error!(error = %e, "Failed to set URL");
                     RelayError::Network {
                         message: "Failed to set URL".into(),
-                        cause: Some(e.to_string()),
+                        cause: Some(e.into()),
                     }
                 })?;
             }
@@ -87,7 +213,7 @@ impl<'a> CurlRequest<'a> {
                 tracing::error!(error = %e, "Failed to set URL");
                 RelayError::Network {
                     message: "Failed to set URL".into(),
-                    cause: Some(e.to_string()),
+                    cause: Some(e.into()),
                 }
             })?;
         }
@@ -99,7 +225,7 @@ impl<'a> CurlRequest<'a> {
                 tracing::error!(error = %e, "Failed to set HTTP version");
                 RelayError::Network {
                     message: "Failed to set HTTP version".into(),
-                    cause: Some(e.to_string()),
+                    cause: Some(e.into()),
                 }
             })?;
 
@@ -109,10 +235,48 @@ impl<'a> CurlRequest<'a> {
             tracing::error!(error = %e, "Failed to set accept-encoding");
             RelayError::Network {
                 message: "Failed to set accept-encoding".into(),
-                cause: Some(e.to_string()),
+                cause: Some(e.into()),
             }
         })?;
 
+        // NOTE: Signal based DNS timeouts are not safe in multithreaded programs,
+        // so unless explicitly opted out, this uses the signal-free resolver path,
+        // see: https://curl.se/libcurl/c/CURLOPT_NOSIGNAL.html
+        let nosignal = self
+            .request
+            .meta
+            .as_ref()
+            .and_then(|meta| meta.options.as_ref())
+            .and_then(|options| options.nosignal)
+            .unwrap_or(true);
+
+        tracing::debug!(nosignal = nosignal, "Setting signal handling");
+        self.handle.signal(!nosignal).map_err(|e| {
+            tracing::error!(error = %e, "Failed to set signal handling");
+            RelayError::Network {
+                message: "Failed to set signal handling".into(),
+                cause: Some(e.into()),
+            }
+        })?;
+
+        // NOTE: DoH takes over from the resolver backend whenever it is set.
+        let options = self
+            .request
+            .meta
+            .as_ref()
+            .and_then(|meta| meta.options.as_ref());
+        let resolver = match (
+            options.and_then(|options| options.doh_url.as_ref()),
+            options.and_then(|options| options.dns_servers.as_ref()),
+        ) {
+            (Some(doh_url), _) => format!("DNS-over-HTTPS via {}", doh_url),
+            (None, Some(servers)) => {
+                format!("{} via {}", util::resolver_backend(), servers.join(","))
+            }
+            (None, None) => util::resolver_backend(),
+        };
+        tracing::debug!(resolver = %resolver, "Resolving host names");
+
         let Some(ref meta) = self.request.meta else {
             tracing::debug!("No meta configuration provided");
             return Ok(());
@@ -123,15 +287,35 @@ impl<'a> CurlRequest<'a> {
             return Ok(());
         };
 
+        // NOTE: curl repeats a `custom_request` method on every redirect
+        // unless told to obey the status code. It then switches to GET on a
+        // 303 for anything but HEAD, and on a 301 or 302 for POST only, as
+        // browsers do. Other methods keep their content on a 301 or 302.
+        // libcurl before 8.13 does not know the mode, so it gets plain
+        // following there and keeps repeating the method.
         if let Some(follow) = options.follow_redirects {
             tracing::debug!(follow_redirects = follow, "Setting redirect behavior");
-            self.handle.follow_location(follow).map_err(|e| {
-                tracing::error!(error = %e, "Failed to set follow_location");
-                RelayError::Network {
-                    message: "Failed to set redirect behavior".into(),
-                    cause: Some(e.to_string()),
-                }
-            })?;
+            let mode = if !follow {
+                0
+            } else if util::libcurl_at_least(8, 13) {
+                CURLFOLLOW_OBEYCODE
+            } else {
+                1
+            };
+            let mut redirects = PostRedirections::new();
+            if self.request.method != Method::POST {
+                redirects.redirect_301(true).redirect_302(true);
+            }
+            self.handle
+                .setopt_long(curl_sys::CURLOPT_FOLLOWLOCATION, mode)
+                .and_then(|()| self.handle.post_redirections(&redirects))
+                .map_err(|e| {
+                    tracing::error!(error = %e, "Failed to set follow_location");
+                    RelayError::Network {
+                        message: "Failed to set redirect behavior".into(),
+                        cause: Some(e.into()),
+                    }
+                })?;
         }
 
         if let Some(max) = options.max_redirects {
@@ -140,7 +324,7 @@ impl<'a> CurlRequest<'a> {
                 tracing::error!(error = %e, "Failed to set max_redirections");
                 RelayError::Network {
                     message: "Failed to set maximum redirects".into(),
-                    cause: Some(e.to_string()),
+                    cause: Some(e.into()),
                 }
             })?;
         }
@@ -153,7 +337,7 @@ impl<'a> CurlRequest<'a> {
                     tracing::error!(error = %e, "Failed to set timeout");
                     RelayError::Network {
                         message: "Failed to set timeout".into(),
-                        cause: Some(e.to_string()),
+                        cause: Some(e.into()),
                     }
                 })?;
         }
@@ -165,7 +349,7 @@ impl<'a> CurlRequest<'a> {
                     tracing::error!(error = %e, "Failed to disable decompression");
                     RelayError::Network {
                         message: "Failed to disable decompression".into(),
-                        cause: Some(e.to_string()),
+                        cause: Some(e.into()),
                     }
                 })?;
             }
@@ -178,7 +362,7 @@ impl<'a> CurlRequest<'a> {
                     tracing::error!(error = %e, "Failed to enable cookies");
                     RelayError::Network {
                         message: "Failed to enable cookie handling".into(),
-                        cause: Some(e.to_string()),
+                        cause: Some(e.into()),
                     }
                 })?;
             }
@@ -190,7 +374,136 @@ impl<'a> CurlRequest<'a> {
                 tracing::error!(error = %e, "Failed to set keep-alive");
                 RelayError::Network {
                     message: "Failed to set keep-alive".into(),
-                    cause: Some(e.to_string()),
+                    cause: Some(e.into()),
+                }
+            })?;
+        }
+
+        if let Some(timeout_ms) = options.happy_eyeballs_timeout_ms {
+            tracing::debug!(timeout_ms = timeout_ms, "Setting happy eyeballs timeout");
+            self.handle
+                .setopt_long(
+                    CURLOPT_HAPPY_EYEBALLS_TIMEOUT_MS,
+                    timeout_ms.try_into().unwrap_or(std::os::raw::c_long::MAX),
+                )
+                .map_err(|e| {
+                    tracing::error!(error = %e, "Failed to set happy eyeballs timeout");
+                    RelayError::Network {
+                        message: "Failed to set happy eyeballs timeout".into(),
+                        cause: Some(e.into()),
+                    }
+                })?;
+        }
+
+        // NOTE: TCP Fast Open depends on OS support, where libcurl was built
+        // without it the option is rejected, which is logged and ignored
+        // so the request still goes out over a regular handshake.
+        if let Some(fastopen) = options.tcp_fastopen {
+            tracing::debug!(tcp_fastopen = fastopen, "Setting TCP Fast Open");
+            if let Err(e) = self
+                .handle
+                .setopt_long(CURLOPT_TCP_FASTOPEN, fastopen.into())
+            {
+                tracing::warn!(error = %e, "TCP Fast Open not supported, ignoring");
+            }
+        }
+
+        if let Some(ref interface) = options.interface {
+            tracing::debug!(interface = %interface, "Binding outgoing interface");
+            self.handle.interface(interface).map_err(|e| {
+                tracing::error!(error = %e, "Failed to set interface");
+                RelayError::Network {
+                    message: format!("Failed to bind to interface: {}", interface),
+                    cause: Some(e.into()),
+                }
+            })?;
+        }
+
+        // NOTE: A resolver override that cannot be honoured fails the request
+        // rather than silently falling back to the system resolver. Options
+        // only c-ares takes are checked against libcurl's version info before
+        // any is set, so the error names the missing backend.
+        let c_ares_option = [
+            ("dns_servers", options.dns_servers.is_some()),
+            ("dns_interface", options.dns_interface.is_some()),
+            ("dns_local_ip4", options.dns_local_ip4.is_some()),
+            ("dns_local_ip6", options.dns_local_ip6.is_some()),
+        ]
+        .into_iter()
+        .find_map(|(option, set)| set.then_some(option));
+        if let Some(option) = c_ares_option.filter(|_| !util::has_c_ares()) {
+            tracing::error!(
+                option,
+                resolver = %util::resolver_backend(),
+                "Resolver option needs c-ares"
+            );
+            return Err(RelayError::UnsupportedFeature {
+                feature: option.into(),
+                message: "libcurl built without c-ares, which resolver overrides need".into(),
+                relay: "curl".into(),
+            });
+        }
+
+        if let Some(ref servers) = options.dns_servers {
+            let servers = servers.join(",");
+            tracing::debug!(dns_servers = %servers, "Setting DNS servers");
+            self.handle
+                .dns_servers(&servers)
+                .map_err(|e| resolver_error(e, "dns_servers", "custom DNS servers"))?;
+        }
+
+        for (option, name, value) in [
+            (
+                CURLOPT_DNS_INTERFACE,
+                "dns_interface",
+                &options.dns_interface,
+            ),
+            (
+                CURLOPT_DNS_LOCAL_IP4,
+                "dns_local_ip4",
+                &options.dns_local_ip4,
+            ),
+            (
+                CURLOPT_DNS_LOCAL_IP6,
+                "dns_local_ip6",
+                &options.dns_local_ip6,
+            ),
+        ] {
+            if let Some(value) = value {
+                tracing::debug!(option = name, value = %value, "Setting DNS source");
+                self.handle.setopt_str(option, value).map_err(|e| {
+                    resolver_error(e, name, "choosing where DNS queries go out from")
+                })?;
+            }
+        }
+
+        if let Some(ref doh_url) = options.doh_url {
+            tracing::debug!(doh_url = %doh_url, "Setting DNS-over-HTTPS URL");
+            self.handle
+                .doh_url(Some(doh_url))
+                .map_err(|e| resolver_error(e, "doh_url", "DNS-over-HTTPS"))?;
+        }
+
+        if let Some(size) = options.buffer_size {
+            let size = clamp_buffer_size("buffer_size", size, BUFFER_SIZE_RANGE);
+            tracing::debug!(buffer_size = size, "Setting receive buffer size");
+            self.handle.buffer_size(size).map_err(|e| {
+                tracing::error!(error = %e, "Failed to set buffer size");
+                RelayError::Network {
+                    message: "Failed to set buffer size".into(),
+                    cause: Some(e.into()),
+                }
+            })?;
+        }
+
+        if let Some(size) = options.upload_buffer_size {
+            let size = clamp_buffer_size("upload_buffer_size", size, UPLOAD_BUFFER_SIZE_RANGE);
+            tracing::debug!(upload_buffer_size = size, "Setting upload buffer size");
+            self.handle.upload_buffer_size(size).map_err(|e| {
+                tracing::error!(error = %e, "Failed to set upload buffer size");
+                RelayError::Network {
+                    message: "Failed to set upload buffer size".into(),
+                    cause: Some(e.into()),
                 }
             })?;
         }
@@ -206,14 +519,48 @@ impl<'a> CurlRequest<'a> {
 
         let mut headers = HashMap::new();
 
-        if let Some(ref content) = self.request.content {
+        let content = self.request.content.as_ref().filter(|_| {
+            match BodyPolicy::for_method(&self.request.method) {
+                BodyPolicy::Attach => true,
+                BodyPolicy::Discouraged => {
+                    tracing::warn!(
+                        method = %self.request.method,
+                        "Attaching content to a method without defined body semantics"
+                    );
+                    true
+                }
+                BodyPolicy::Dropped => {
+                    tracing::warn!(
+                        method = %self.request.method,
+                        "Dropping content from a method that must not carry it"
+                    );
+                    false
+                }
+            }
+        });
+
+        if let Some(content) = content {
             tracing::trace!(content_type = ?content, "Setting request content");
             ContentHandler::new(self.handle, &mut headers).set_content(content)?;
         }
 
         if let Some(ref auth) = self.request.auth {
             tracing::trace!(auth_type = ?auth, "Configuring authentication");
-            AuthHandler::new(self.handle, &mut headers).set_auth(auth)?;
+            AuthHandler::new(self.handle, &mut headers)
+                .with_target_policy(target_policy::side_guard(self.request))
+                .set_auth(auth)?;
+
+            // NOTE: curl would add basic credentials only while sending, a
+            // signed request sends them as a header so the signature covers
+            // them. Digest answers a challenge and cannot be signed ahead,
+            // unless the challenge was negotiated beforehand.
+            if let (Some(_), AuthType::Basic { username, password }) = (self.signer, auth) {
+                let (name, value) = auth::basic_header(username, password);
+                headers.insert(name, value);
+            }
+            if let Some((name, value)) = digest::authorization(self.request)? {
+                headers.insert(name, value);
+            }
         }
 
         if let Some(ref security) = self.request.security {
@@ -225,6 +572,17 @@ impl<'a> CurlRequest<'a> {
             SecurityHandler::new(self.handle).configure(security)?;
         }
 
+        if let Some(insecure) = security::matching_override(self.request) {
+            tracing::warn!(
+                request_id = self.request.id,
+                host = %insecure.host,
+                reason = %insecure.reason,
+                "Skipping TLS verification for this request"
+            );
+            SecurityHandler::new(self.handle).skip_verification()?;
+            self.verification_skipped = true;
+        }
+
         if let Some(ref proxy) = self.request.proxy {
             tracing::trace!(proxy_url = %proxy.url, "Setting up proxy");
 
@@ -232,41 +590,134 @@ impl<'a> CurlRequest<'a> {
                 .proxy(&proxy.url)
                 .map_err(|e| RelayError::Network {
                     message: "Failed to set proxy".into(),
-                    cause: Some(e.to_string()),
+                    cause: Some(e.into()),
                 })?;
+        }
 
-            self.handle
-                .proxy_auth(curl::easy::Auth::new().auto(true))
-                .map_err(|e| RelayError::Network {
-                    message: "Failed to set proxy authentication to auto".into(),
-                    cause: Some(e.to_string()),
-                })?;
+        // NOTE: An empty url tells curl not to use any proxy, which is how a
+        // request opts out of a client wide default.
+        if let Some(proxy) = self
+            .request
+            .proxy
+            .as_ref()
+            .filter(|proxy| !proxy.url.is_empty())
+        {
+            let scheme = proxy.auth.as_ref().and_then(|auth| auth.scheme);
+            let mut proxy_auth = curl::easy::Auth::new();
+            match scheme {
+                None => proxy_auth.auto(true),
+                Some(ProxyAuthScheme::Basic) => proxy_auth.basic(true),
+                Some(ProxyAuthScheme::Digest) => proxy_auth.digest(true),
+                Some(ProxyAuthScheme::Ntlm) => proxy_auth.ntlm(true),
+                Some(ProxyAuthScheme::Negotiate) => proxy_auth.gssnegotiate(true),
+                // NOTE: curl has no bearer scheme for proxies, the token is
+                // sent as a `Proxy-Authorization` header below instead.
+                Some(ProxyAuthScheme::Bearer) => &mut proxy_auth,
+            };
+
+            if scheme != Some(ProxyAuthScheme::Bearer) {
+                tracing::trace!(scheme = ?scheme, "Setting proxy authentication scheme");
+                self.handle
+                    .proxy_auth(&proxy_auth)
+                    .map_err(|e| RelayError::Network {
+                        message: "Failed to set proxy authentication scheme".into(),
+                        cause: Some(e.into()),
+                    })?;
+            }
 
             if let Some(ref auth) = proxy.auth {
-                if (auth.username.trim().is_empty() || auth.password.trim().is_empty()).not() {
+                // NOTE: Negotiate authenticates with the current Kerberos
+                // ticket when no credentials are given, curl still needs them
+                // set, even if empty, to attempt it.
+                let credentials = scheme == Some(ProxyAuthScheme::Negotiate)
+                    || (auth.username.trim().is_empty() || auth.password.trim().is_empty()).not();
+
+                if scheme == Some(ProxyAuthScheme::Bearer) {
+                    let token = auth.token.as_deref().unwrap_or_default();
+                    let value = format!("Bearer {}", token);
+                    // NOTE: Proxy headers only go out with `CONNECT`, which
+                    // tunnelled requests start with.
+                    let mut proxy_headers = curl::easy::List::new();
+                    proxy_headers
+                        .append(&format!("Proxy-Authorization: {}", value))
+                        .map_err(|e| RelayError::Network {
+                            message: "Failed to add proxy authorization header".into(),
+                            cause: Some(e.into()),
+                        })?;
+                    self.handle
+                        .proxy_headers(proxy_headers)
+                        .map_err(|e| RelayError::Network {
+                            message: "Failed to set proxy headers".into(),
+                            cause: Some(e.into()),
+                        })?;
+
+                    // NOTE: A plain HTTP request is sent to the proxy as is,
+                    // so the token goes with its headers. Redirects are then
+                    // kept to plain HTTP, inside a tunnel those headers would
+                    // reach the origin.
+                    if !tunnels(&self.request.url) {
+                        tracing::trace!("Sending proxy bearer token with the request");
+                        headers.insert("Proxy-Authorization".to_string(), value);
+                        self.handle
+                            .setopt_long(
+                                curl_sys::CURLOPT_REDIR_PROTOCOLS,
+                                curl_sys::CURLPROTO_HTTP as std::os::raw::c_long,
+                            )
+                            .map_err(|e| RelayError::Network {
+                                message: "Failed to restrict redirect protocols".into(),
+                                cause: Some(e.into()),
+                            })?;
+                    }
+                } else if credentials {
                     self.handle.proxy_username(&auth.username).map_err(|e| {
                         RelayError::Network {
                             message: "Failed to set proxy username".into(),
-                            cause: Some(e.to_string()),
+                            cause: Some(e.into()),
                         }
                     })?;
 
                     self.handle.proxy_password(&auth.password).map_err(|e| {
                         RelayError::Network {
                             message: "Failed to set proxy password".into(),
-                            cause: Some(e.to_string()),
+                            cause: Some(e.into()),
                         }
                     })?;
                 }
             }
         }
 
-        if let Some(ref request_headers) = self.request.headers {
-            headers.extend(request_headers.clone());
-            HeadersBuilder::new(self.handle).add_headers(Some(&headers))?;
-        } else if !headers.is_empty() {
-            HeadersBuilder::new(self.handle).add_headers(Some(&headers))?;
+        // NOTE: Later layers win by name, so explicit request headers override
+        // the computed content, auth, id, idempotency, range, accept and trace
+        // headers.
+        let sanitize = self
+            .request
+            .meta
+            .as_ref()
+            .and_then(|meta| meta.options.as_ref())
+            .and_then(|options| options.sanitize_headers)
+            .unwrap_or(false);
+
+        let mut builder = HeadersBuilder::new()
+            .sanitize(sanitize)
+            .redaction(redact::policy_for(self.request));
+        builder
+            .add_headers(headers)?
+            .add_headers(id::headers(self.request))?
+            .add_headers(idempotency::headers(self.request))?
+            .add_headers(range::headers(self.request))?
+            .add_headers(accept::headers(self.request))?
+            .add_headers(trace::headers(self.request, self.trace_context))?
+            .add_headers(self.request.headers.clone().unwrap_or_default())?;
+
+        // NOTE: Last, so the signature covers every header above, and may
+        // replace any of them.
+        if let Some(signer) = self.signer {
+            let url = query::wire_url(self.request)?;
+            let url = query::with_default_scheme(&url);
+            let signed = signing::sign(signer, self.request, &url, builder.headers())?;
+            builder.add_headers(signed.into_iter().collect::<Headers>())?;
         }
+        builder.apply(self.handle)?;
 
         Ok(())
     }
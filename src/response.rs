@@ -1,35 +1,91 @@
-use std::{collections::HashMap, str::FromStr, time::SystemTime};
+use std::{collections::HashMap, str::FromStr, sync::Arc};
 
 use bytes::Bytes;
 use http::{StatusCode, Version};
 use mime::Mime;
 
 use crate::{
-    error::{RelayError, Result},
-    interop::{MediaType, Response, ResponseBody, ResponseMeta, SizeInfo, TimingInfo},
+    clock::Stopwatch,
+    error::Result,
+    interop::{MediaType, Response, ResponseBody, ResponseMeta, SizeInfo, StatusCategory},
+    redact::{self, RedactionPolicy},
+    transfer::ResponseHead,
 };
 
-pub(crate) struct ResponseHandler {
+impl Response {
+    pub fn status_category(&self) -> StatusCategory {
+        match self.status.as_u16() {
+            100..=199 => StatusCategory::Informational,
+            200..=299 => StatusCategory::Success,
+            300..=399 => StatusCategory::Redirect,
+            400..=499 => StatusCategory::ClientError,
+            500..=599 => StatusCategory::ServerError,
+            _ => StatusCategory::Unknown,
+        }
+    }
+
+    pub fn is_informational(&self) -> bool {
+        self.status_category() == StatusCategory::Informational
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.status_category() == StatusCategory::Success
+    }
+
+    pub fn is_redirect(&self) -> bool {
+        self.status_category() == StatusCategory::Redirect
+    }
+
+    pub fn is_client_error(&self) -> bool {
+        self.status_category() == StatusCategory::ClientError
+    }
+
+    pub fn is_server_error(&self) -> bool {
+        self.status_category() == StatusCategory::ServerError
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.is_client_error() || self.is_server_error()
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.body.body
+    }
+
+    pub fn into_bytes(self) -> Bytes {
+        self.body.body
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.body.body.is_empty()
+    }
+}
+
+pub(crate) struct ResponseHandler<'a> {
     id: i64,
     headers: HashMap<String, String>,
     body: Bytes,
     status: StatusCode,
     header_size: u64,
-    start_time: SystemTime,
-    end_time: SystemTime,
+    stopwatch: Stopwatch<'a>,
     version: Version,
+    final_url: String,
+    head: ResponseHead,
+    raw_status: Option<u32>,
+    body_size: Option<u64>,
+    redaction: Option<Arc<RedactionPolicy>>,
 }
 
-impl ResponseHandler {
+impl<'a> ResponseHandler<'a> {
     pub(crate) fn new(
         id: i64,
         headers: HashMap<String, String>,
         body: Bytes,
         status: StatusCode,
         header_size: u64,
-        start_time: SystemTime,
-        end_time: SystemTime,
+        stopwatch: Stopwatch<'a>,
         version: Version,
+        final_url: String,
     ) -> Self {
         Self {
             id,
@@ -37,21 +93,77 @@ impl ResponseHandler {
             body,
             status,
             header_size,
-            start_time,
-            end_time,
+            stopwatch,
             version,
+            final_url,
+            head: ResponseHead::default(),
+            raw_status: None,
+            body_size: None,
+            redaction: None,
+        }
+    }
+
+    pub(crate) fn with_head(mut self, head: ResponseHead, raw_status: Option<u32>) -> Self {
+        self.head = head;
+        self.raw_status = raw_status;
+        self
+    }
+
+    // NOTE: The size received when the body kept is only part of it.
+    pub(crate) fn with_body_size(mut self, size: Option<u64>) -> Self {
+        self.body_size = size;
+        self
+    }
+
+    // NOTE: The request's policy, which may know secrets the global one
+    // does not.
+    pub(crate) fn with_redaction(mut self, policy: Arc<RedactionPolicy>) -> Self {
+        self.redaction = Some(policy);
+        self
+    }
+
+    // NOTE: Prefers the reason phrase the server sent, the registered one is
+    // only filled in when it sent none.
+    fn status_text(&self) -> String {
+        let code = self
+            .raw_status
+            .map_or_else(|| self.status.as_str().to_string(), |raw| raw.to_string());
+        let reason = self.head.reason.as_deref().or_else(|| {
+            self.raw_status
+                .is_none()
+                .then(|| self.status.canonical_reason())
+                .flatten()
+        });
+
+        match reason {
+            Some(reason) => format!("{} {}", code, reason),
+            None => code,
         }
     }
 
+    fn protocol_warnings(&self) -> Vec<String> {
+        let mut warnings = self.head.warnings();
+        match self.raw_status {
+            Some(raw) => warnings.push(format!("Status code {} is not a valid HTTP status", raw)),
+            None if self.status.as_u16() >= 600 => warnings.push(format!(
+                "Status code {} is outside the 1xx to 5xx classes",
+                self.status.as_u16()
+            )),
+            None => {}
+        }
+        warnings
+    }
+
     #[tracing::instrument(skip(self), fields(request_id = self.id), level = "debug")]
     pub(crate) fn build(self) -> Result<Response> {
         tracing::debug!(status = %self.status, "Building response");
         let media_type = self.determine_media_type();
-        let timing = self.calculate_timing()?;
+        let timing = self.stopwatch.timing();
+        let body_size = self.body_size.unwrap_or(self.body.len() as u64);
         let size = SizeInfo {
             headers: self.header_size,
-            body: self.body.len() as u64,
-            total: self.header_size + self.body.len() as u64,
+            body: body_size,
+            total: self.header_size + body_size,
         };
 
         tracing::debug!(
@@ -63,21 +175,71 @@ impl ResponseHandler {
             "Response built successfully"
         );
 
+        let policy = self.redaction.clone().unwrap_or_else(redact::policy);
+        tracing::trace!(headers = ?policy.headers(&self.headers), "Response headers");
+        if matches!(media_type, MediaType::Json | MediaType::JsonLd) {
+            if let Some(body) = policy.json_body(&self.body) {
+                tracing::trace!(body = %body, "Response body");
+            }
+        }
+
+        let status_text = self.status_text();
+        let protocol_warnings = self.protocol_warnings();
+        if !protocol_warnings.is_empty() {
+            tracing::warn!(warnings = ?protocol_warnings, "Response deviates from HTTP");
+        }
+
+        let charset = self.charset();
         let body = ResponseBody {
             body: self.body,
             media_type,
+            charset,
         };
 
-        Ok(Response {
+        let mut response = Response {
             id: self.id,
             status: self.status,
-            status_text: self.status.to_string(),
+            status_text,
             version: self.version,
+            final_url: self.final_url,
             headers: self.headers,
             cookies: None,
-            meta: ResponseMeta { timing, size },
+            meta: ResponseMeta {
+                timing,
+                size,
+                trace: None,
+                raw_status: self.raw_status,
+                protocol_warnings,
+                tls: None,
+                range: None,
+                negotiation: None,
+                cors: None,
+                connection: None,
+                idempotency_key: None,
+                cache: None,
+                assertions: Vec::new(),
+                gzip_detected: false,
+            },
+            metrics: None,
+            body_hash: None,
+            early_hints: Vec::new(),
+            body_truncated: false,
             body,
-        })
+        };
+        response.meta.cors = response.cors();
+
+        Ok(response)
+    }
+
+    fn charset(&self) -> Option<String> {
+        self.headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            .and_then(|(_, value)| value.parse::<Mime>().ok())
+            .and_then(|mime| {
+                mime.get_param(mime::CHARSET)
+                    .map(|charset| charset.to_string())
+            })
     }
 
     fn determine_media_type(&self) -> MediaType {
@@ -118,42 +280,92 @@ impl ResponseHandler {
                 .flatten())
             .unwrap_or(MediaType::TextPlain)
     }
+}
 
-    fn calculate_timing(&self) -> Result<TimingInfo> {
-        let start_ms = self
-            .start_time
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .map_err(|e| {
-                tracing::error!(error = %e, "Failed to get start time");
-                RelayError::Parse {
-                    message: "Failed to get start time".into(),
-                    cause: Some(e.to_string()),
-                }
-            })?
-            .as_millis() as u64;
-
-        let end_ms = self
-            .end_time
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .map_err(|e| {
-                tracing::error!(error = %e, "Failed to get end time");
-                RelayError::Parse {
-                    message: "Failed to get end time".into(),
-                    cause: Some(e.to_string()),
-                }
-            })?
-            .as_millis() as u64;
-
-        tracing::trace!(
-            start_ms = start_ms,
-            end_ms = end_ms,
-            duration_ms = end_ms - start_ms,
-            "Calculated request timing"
-        );
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SystemClock;
+
+    fn response(status: u16, body: &'static [u8]) -> Response {
+        ResponseHandler::new(
+            1,
+            HashMap::new(),
+            Bytes::from_static(body),
+            StatusCode::from_u16(status).unwrap(),
+            0,
+            Stopwatch::start(&SystemClock),
+            Version::HTTP_11,
+            "http://localhost/".to_string(),
+        )
+        .build()
+        .unwrap()
+    }
+
+    #[test]
+    fn categorizes_each_status_class() {
+        let cases = [
+            (100, StatusCategory::Informational),
+            (199, StatusCategory::Informational),
+            (200, StatusCategory::Success),
+            (299, StatusCategory::Success),
+            (300, StatusCategory::Redirect),
+            (399, StatusCategory::Redirect),
+            (400, StatusCategory::ClientError),
+            (499, StatusCategory::ClientError),
+            (500, StatusCategory::ServerError),
+            (599, StatusCategory::ServerError),
+        ];
+        for (status, category) in cases {
+            assert_eq!(
+                response(status, b"").status_category(),
+                category,
+                "{}",
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn codes_outside_the_classes_are_unknown() {
+        for status in [600, 999] {
+            let response = response(status, b"");
+            assert_eq!(response.status_category(), StatusCategory::Unknown);
+            assert!(!response.is_success() && !response.is_error());
+        }
+    }
+
+    #[test]
+    fn predicates_follow_the_category() {
+        let predicates = |status| {
+            let response = response(status, b"");
+            [
+                response.is_informational(),
+                response.is_success(),
+                response.is_redirect(),
+                response.is_client_error(),
+                response.is_server_error(),
+                response.is_error(),
+            ]
+        };
+
+        assert_eq!(predicates(103), [true, false, false, false, false, false]);
+        assert_eq!(predicates(204), [false, true, false, false, false, false]);
+        assert_eq!(predicates(304), [false, false, true, false, false, false]);
+        assert_eq!(predicates(404), [false, false, false, true, false, true]);
+        assert_eq!(predicates(503), [false, false, false, false, true, true]);
+    }
+
+    #[test]
+    fn exposes_the_body_bytes() {
+        let full = response(200, b"hello");
+        assert_eq!(full.bytes(), b"hello");
+        assert!(!full.is_empty());
+        assert_eq!(full.into_bytes(), Bytes::from_static(b"hello"));
 
-        Ok(TimingInfo {
-            start: start_ms,
-            end: end_ms,
-        })
+        let empty = response(204, b"");
+        assert_eq!(empty.bytes(), b"");
+        assert!(empty.is_empty());
+        assert!(empty.into_bytes().is_empty());
     }
 }
@@ -0,0 +1,282 @@
+use std::time::Duration;
+
+use http::{Method, StatusCode};
+use rand::Rng;
+
+use crate::{
+    error::{RelayError, Result},
+    interop::{Request, RequestMetrics, Response},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackoffStrategy {
+    Constant(Duration),
+    Exponential {
+        base: Duration,
+        factor: f64,
+        max: Duration,
+    },
+    // NOTE: Full jitter, the delay is drawn uniformly from `[0, exponential]`
+    // so that clients failing together don't retry together,
+    // see: https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/
+    ExponentialJitter {
+        base: Duration,
+        factor: f64,
+        max: Duration,
+    },
+    // NOTE: Decorrelated jitter, each delay is drawn uniformly from
+    // `[base, 3 * previous]` and capped at `max`, so it grows from the last
+    // wait rather than from the attempt count.
+    DecorrelatedJitter {
+        base: Duration,
+        max: Duration,
+    },
+}
+
+impl Default for BackoffStrategy {
+    fn default() -> Self {
+        Self::ExponentialJitter {
+            base: Duration::from_millis(100),
+            factor: 2.0,
+            max: Duration::from_secs(10),
+        }
+    }
+}
+
+impl BackoffStrategy {
+    // NOTE: `attempt` is the zero-based index of the retry, so the first retry
+    // waits `base` for both exponential variants. Decorrelated jitter has no
+    // previous delay to go on here and draws the first retry's delay.
+    pub fn next_delay(&self, attempt: u32) -> Duration {
+        self.next_delay_after(attempt, Duration::ZERO)
+    }
+
+    // NOTE: Like `next_delay`, given the delay waited before the previous
+    // attempt, `Duration::ZERO` before the first retry. Only decorrelated
+    // jitter depends on it.
+    pub fn next_delay_after(&self, attempt: u32, previous: Duration) -> Duration {
+        match *self {
+            Self::Constant(delay) => delay,
+            Self::Exponential { base, factor, max } => {
+                Self::exponential(base, factor, max, attempt)
+            }
+            Self::ExponentialJitter { base, factor, max } => {
+                let ceiling = Self::exponential(base, factor, max, attempt);
+                ceiling.mul_f64(rand::random::<f64>())
+            }
+            Self::DecorrelatedJitter { base, max } => {
+                let floor = base.min(max).as_secs_f64();
+                let ceiling = (previous.max(base).as_secs_f64() * 3.0).min(max.as_secs_f64());
+                if ceiling <= floor {
+                    return Duration::from_secs_f64(floor);
+                }
+                Duration::from_secs_f64(rand::thread_rng().gen_range(floor..=ceiling))
+            }
+        }
+    }
+
+    fn exponential(base: Duration, factor: f64, max: Duration, attempt: u32) -> Duration {
+        let multiplier = factor.max(1.0).powi(attempt.min(i32::MAX as u32) as i32);
+        let delay = base.as_secs_f64() * multiplier;
+
+        if !delay.is_finite() || delay >= max.as_secs_f64() {
+            max
+        } else {
+            Duration::from_secs_f64(delay)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub backoff: BackoffStrategy,
+    pub retry_on_status: Vec<StatusCode>,
+    pub retry_non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            backoff: BackoffStrategy::default(),
+            retry_on_status: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, backoff: BackoffStrategy) -> Self {
+        Self {
+            max_retries,
+            backoff,
+            ..Self::default()
+        }
+    }
+
+    // NOTE: A request with an idempotency key is safe to retry whatever its
+    // method, the server applies it at most once.
+    pub(crate) fn is_retryable_request(&self, request: &Request) -> bool {
+        self.retry_non_idempotent
+            || request.has_idempotency_key()
+            || matches!(
+                request.method,
+                Method::GET
+                    | Method::HEAD
+                    | Method::PUT
+                    | Method::DELETE
+                    | Method::OPTIONS
+                    | Method::TRACE
+            )
+    }
+
+    pub(crate) fn should_retry_response(&self, response: &Response) -> bool {
+        self.retry_on_status.contains(&response.status)
+    }
+
+    pub(crate) fn should_retry_error(&self, error: &RelayError) -> bool {
+        match error {
+            RelayError::HttpStatus { status, .. } => self.retry_on_status.contains(status),
+            error => error.is_retryable(),
+        }
+    }
+}
+
+// NOTE: What the attempts for one request cost so far, folded into the
+// metrics of whichever response is finally returned.
+#[derive(Debug, Default)]
+pub(crate) struct AttemptLog {
+    attempts: u32,
+    retry_delay: Duration,
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+impl AttemptLog {
+    pub(crate) fn attempted(&mut self, result: &Result<Response>) {
+        self.attempts += 1;
+        if let Some(metrics) = result
+            .as_ref()
+            .ok()
+            .and_then(|response| response.metrics.as_ref())
+        {
+            self.bytes_sent += metrics.bytes_sent;
+            self.bytes_received += metrics.bytes_received;
+        }
+    }
+
+    pub(crate) fn waited(&mut self, delay: Duration) {
+        self.retry_delay += delay;
+    }
+
+    pub(crate) fn finish(self, result: Result<Response>) -> Result<Response> {
+        result.map(|mut response| {
+            let not_modified = response.status == StatusCode::NOT_MODIFIED;
+            let metrics = response.metrics.get_or_insert_with(RequestMetrics::default);
+
+            metrics.attempts = self.attempts;
+            metrics.retry_delay_ms = self.retry_delay.as_millis() as u64;
+            metrics.bytes_sent = self.bytes_sent;
+            metrics.bytes_received = self.bytes_received;
+            metrics.not_modified = not_modified;
+
+            tracing::debug!(
+                attempts = self.attempts,
+                retry_delay_ms = metrics.retry_delay_ms,
+                "Recorded request metrics"
+            );
+            response
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: usize = 1000;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn constant_waits_the_same_every_time() {
+        let backoff = BackoffStrategy::Constant(ms(250));
+        let delays: Vec<_> = (0..4).map(|attempt| backoff.next_delay(attempt)).collect();
+        assert_eq!(delays, [ms(250); 4]);
+    }
+
+    #[test]
+    fn exponential_doubles_up_to_max() {
+        let backoff = BackoffStrategy::Exponential {
+            base: ms(100),
+            factor: 2.0,
+            max: ms(1000),
+        };
+        let delays: Vec<_> = (0..6).map(|attempt| backoff.next_delay(attempt)).collect();
+        assert_eq!(
+            delays,
+            [ms(100), ms(200), ms(400), ms(800), ms(1000), ms(1000)]
+        );
+    }
+
+    #[test]
+    fn exponential_clamps_overflowing_attempts() {
+        let backoff = BackoffStrategy::Exponential {
+            base: ms(100),
+            factor: 10.0,
+            max: Duration::from_secs(30),
+        };
+        assert_eq!(backoff.next_delay(u32::MAX), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn full_jitter_stays_within_the_exponential_delay() {
+        let backoff = BackoffStrategy::ExponentialJitter {
+            base: ms(100),
+            factor: 2.0,
+            max: ms(1000),
+        };
+        for attempt in 0..8 {
+            let cap = ms(100 << attempt).min(ms(1000));
+            for _ in 0..SAMPLES {
+                assert!(backoff.next_delay(attempt) <= cap);
+            }
+        }
+    }
+
+    #[test]
+    fn decorrelated_jitter_stays_between_base_and_three_times_previous() {
+        let backoff = BackoffStrategy::DecorrelatedJitter {
+            base: ms(100),
+            max: ms(1000),
+        };
+        let mut previous = Duration::ZERO;
+        for attempt in 0..SAMPLES as u32 {
+            let delay = backoff.next_delay_after(attempt, previous);
+            assert!(delay >= ms(100));
+            // NOTE: The draw goes through `f64`, allow for its rounding.
+            let cap = (previous.max(ms(100)) * 3).min(ms(1000));
+            assert!(delay <= cap + Duration::from_nanos(1));
+            previous = delay;
+        }
+    }
+
+    #[test]
+    fn decorrelated_jitter_never_exceeds_a_max_below_base() {
+        let backoff = BackoffStrategy::DecorrelatedJitter {
+            base: ms(500),
+            max: ms(200),
+        };
+        for _ in 0..SAMPLES {
+            assert_eq!(backoff.next_delay_after(3, ms(400)), ms(200));
+        }
+    }
+}
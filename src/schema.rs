@@ -0,0 +1,48 @@
+use schemars::{
+    gen::SchemaSettings,
+    schema::{RootSchema, SchemaObject},
+};
+
+use crate::{
+    config::ClientConfig,
+    error::RelayError,
+    interop::{
+        AuthType, BodyEstimate, ContentType, Cookie, ErrorPayload, Progress, ProxyConfig, Request,
+        Response, SecurityConfig, TransferEvent, ValidationIssue,
+    },
+};
+
+// NOTE: Every type that crosses the serde boundary ends up under
+// `definitions`, the root itself is an empty schema so no single type is
+// singled out as the document.
+pub fn interop_schema() -> RootSchema {
+    let mut generator = SchemaSettings::draft07().into_generator();
+
+    generator.subschema_for::<Request>();
+    generator.subschema_for::<Response>();
+    generator.subschema_for::<RelayError>();
+    generator.subschema_for::<ErrorPayload>();
+    generator.subschema_for::<ContentType>();
+    generator.subschema_for::<AuthType>();
+    generator.subschema_for::<SecurityConfig>();
+    generator.subschema_for::<ProxyConfig>();
+    generator.subschema_for::<Cookie>();
+    generator.subschema_for::<Progress>();
+    generator.subschema_for::<BodyEstimate>();
+    generator.subschema_for::<TransferEvent>();
+    generator.subschema_for::<ValidationIssue>();
+    generator.subschema_for::<ClientConfig>();
+
+    RootSchema {
+        meta_schema: generator.settings().meta_schema.clone(),
+        schema: SchemaObject::default(),
+        definitions: generator.take_definitions(),
+    }
+}
+
+pub fn interop_schema_json() -> String {
+    let mut json =
+        serde_json::to_string_pretty(&interop_schema()).expect("JSON Schema always serializes");
+    json.push('\n');
+    json
+}
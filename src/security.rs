@@ -1,13 +1,139 @@
+use std::collections::HashMap;
+
 use bytes::Bytes;
-use curl::easy::Easy;
+use curl::easy::{Easy, SslVersion};
 
 use openssl::pkcs12::Pkcs12;
 
 use crate::{
     error::{RelayError, Result},
-    interop::{CertificateConfig, CertificateType, SecurityConfig},
+    interop::{
+        CertificateConfig, CertificateType, InsecureOverride, Request, SecurityConfig, TlsVersion,
+    },
+    query,
+    util::{EasyExt, CURLOPT_SSL_ENABLE_ALPN, CURLOPT_SSL_FALSESTART},
 };
 
+// NOTE: Hosts are matched case-insensitively, either exactly or through a
+// leading wildcard label, `*.example.com` matches `api.example.com` only.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HostCertificates {
+    hosts: HashMap<String, CertificateType>,
+    default: Option<CertificateType>,
+}
+
+impl HostCertificates {
+    pub(crate) fn insert(&mut self, host: &str, certificate: CertificateType) {
+        self.hosts.insert(host.to_ascii_lowercase(), certificate);
+    }
+
+    pub(crate) fn set_default(&mut self, certificate: CertificateType) {
+        self.default = Some(certificate);
+    }
+
+    pub(crate) fn select(&self, url: &str) -> Option<&CertificateType> {
+        let (url, _) = query::split_zone(url);
+        let host = url::Url::parse(&url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase));
+
+        let matched = host.as_deref().and_then(|host| {
+            self.hosts.get(host).or_else(|| {
+                host.split_once('.')
+                    .and_then(|(_, parent)| self.hosts.get(&format!("*.{}", parent)))
+            })
+        });
+
+        matched.or(self.default.as_ref())
+    }
+
+    // NOTE: A client certificate set on the request itself always wins.
+    #[tracing::instrument(skip(self, request), fields(request_id = request.id), level = "debug")]
+    pub(crate) fn apply(&self, request: &mut Request) {
+        let has_client_cert = request
+            .security
+            .as_ref()
+            .and_then(|security| security.certificates.as_ref())
+            .is_some_and(|certs| certs.client.is_some());
+
+        if has_client_cert {
+            tracing::trace!("Request has explicit client certificate");
+            return;
+        }
+
+        let Some(certificate) = self.select(&request.url) else {
+            tracing::trace!("No client certificate registered for host");
+            return;
+        };
+
+        tracing::debug!(url = %request.url, "Selected client certificate for host");
+
+        let security = request.security.get_or_insert_with(|| SecurityConfig {
+            certificates: None,
+            verify_host: None,
+            verify_peer: None,
+            min_tls_version: None,
+            enable_alpn: None,
+            false_start: None,
+            session_id_cache: None,
+        });
+        let certificates = security
+            .certificates
+            .get_or_insert_with(|| CertificateConfig {
+                client: None,
+                ca: None,
+            });
+        certificates.client = Some(certificate.clone());
+    }
+}
+
+// NOTE: An override for any other host than the request's is ignored, loudly,
+// so an override granted for one host can never weaken another.
+pub(crate) fn matching_override(request: &Request) -> Option<&InsecureOverride> {
+    let insecure = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.insecure_override.as_ref())?;
+
+    let (url, _) = query::split_zone(&request.url);
+    let host = url::Url::parse(&url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_ascii_lowercase));
+    let expected = insecure
+        .host
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_ascii_lowercase();
+
+    match host {
+        Some(host) if host.trim_start_matches('[').trim_end_matches(']') == expected => {
+            Some(insecure)
+        }
+        host => {
+            tracing::warn!(
+                request_id = request.id,
+                override_host = %insecure.host,
+                request_host = ?host,
+                "Insecure override does not match the request host, ignoring it"
+            );
+            None
+        }
+    }
+}
+
+impl From<TlsVersion> for SslVersion {
+    fn from(version: TlsVersion) -> Self {
+        match version {
+            TlsVersion::Tls1_0 => SslVersion::Tlsv10,
+            TlsVersion::Tls1_1 => SslVersion::Tlsv11,
+            TlsVersion::Tls1_2 => SslVersion::Tlsv12,
+            TlsVersion::Tls1_3 => SslVersion::Tlsv13,
+        }
+    }
+}
+
 pub(crate) struct SecurityHandler<'a> {
     handle: &'a mut Easy,
 }
@@ -27,7 +153,7 @@ impl<'a> SecurityHandler<'a> {
                 tracing::error!(error = %e, "Failed to set SSL verify peer");
                 RelayError::Certificate {
                     message: "Failed to set SSL verify peer".into(),
-                    cause: Some(e.to_string()),
+                    cause: Some(e.into()),
                 }
             })?;
         }
@@ -38,7 +164,54 @@ impl<'a> SecurityHandler<'a> {
                 tracing::error!(error = %e, "Failed to set SSL verify host");
                 RelayError::Certificate {
                     message: "Failed to set SSL verify host".into(),
-                    cause: Some(e.to_string()),
+                    cause: Some(e.into()),
+                }
+            })?;
+        }
+
+        if let Some(version) = security.min_tls_version {
+            tracing::debug!(version = ?version, "Setting minimum TLS version");
+            self.handle.ssl_version(version.into()).map_err(|e| {
+                tracing::error!(error = %e, "Failed to set minimum TLS version");
+                RelayError::Certificate {
+                    message: "Failed to set minimum TLS version".into(),
+                    cause: Some(e.into()),
+                }
+            })?;
+        }
+
+        if let Some(enable) = security.enable_alpn {
+            tracing::debug!(enable = enable, "Setting ALPN");
+            self.handle
+                .setopt_long(CURLOPT_SSL_ENABLE_ALPN, enable.into())
+                .map_err(|e| {
+                    tracing::error!(error = %e, "Failed to set ALPN");
+                    RelayError::Certificate {
+                        message: "Failed to set ALPN".into(),
+                        cause: Some(e.into()),
+                    }
+                })?;
+        }
+
+        // NOTE: Most TLS backends, OpenSSL included, reject false start, as
+        // an optimization it is logged and skipped rather than failing.
+        if let Some(enable) = security.false_start {
+            tracing::debug!(enable = enable, "Setting TLS false start");
+            if let Err(e) = self
+                .handle
+                .setopt_long(CURLOPT_SSL_FALSESTART, enable.into())
+            {
+                tracing::warn!(error = %e, "TLS false start not supported, ignoring");
+            }
+        }
+
+        if let Some(enable) = security.session_id_cache {
+            tracing::debug!(enable = enable, "Setting TLS session ID cache");
+            self.handle.ssl_sessionid_cache(enable).map_err(|e| {
+                tracing::error!(error = %e, "Failed to set TLS session ID cache");
+                RelayError::Certificate {
+                    message: "Failed to set TLS session ID cache".into(),
+                    cause: Some(e.into()),
                 }
             })?;
         }
@@ -51,6 +224,29 @@ impl<'a> SecurityHandler<'a> {
         Ok(())
     }
 
+    // NOTE: Redirects are not followed while verification is off, curl would
+    // carry the setting over to whatever host the redirect points at.
+    pub(crate) fn skip_verification(&mut self) -> Result<()> {
+        self.handle
+            .ssl_verify_peer(false)
+            .and_then(|_| self.handle.ssl_verify_host(false))
+            .map_err(|e| {
+                tracing::error!(error = %e, "Failed to disable TLS verification");
+                RelayError::Certificate {
+                    message: "Failed to disable TLS verification".into(),
+                    cause: Some(e.into()),
+                }
+            })?;
+
+        self.handle.follow_location(false).map_err(|e| {
+            tracing::error!(error = %e, "Failed to disable redirects");
+            RelayError::Network {
+                message: "Failed to disable redirects".into(),
+                cause: Some(e.into()),
+            }
+        })
+    }
+
     #[tracing::instrument(skip(self), level = "debug")]
     fn configure_certificates(&mut self, certs: &CertificateConfig) -> Result<()> {
         if let Some(ref client_cert) = certs.client {
@@ -79,7 +275,7 @@ impl<'a> SecurityHandler<'a> {
             tracing::error!(error = %e, "Failed to set certificate type");
             RelayError::Certificate {
                 message: "Failed to set certificate type".into(),
-                cause: Some(e.to_string()),
+                cause: Some(e.into()),
             }
         })?;
 
@@ -88,7 +284,7 @@ impl<'a> SecurityHandler<'a> {
             tracing::error!(error = %e, "Failed to set client certificate");
             RelayError::Certificate {
                 message: "Failed to set client certificate".into(),
-                cause: Some(e.to_string()),
+                cause: Some(e.into()),
             }
         })?;
 
@@ -97,7 +293,7 @@ impl<'a> SecurityHandler<'a> {
             tracing::error!(error = %e, "Failed to set key type");
             RelayError::Certificate {
                 message: "Failed to set key type".into(),
-                cause: Some(e.to_string()),
+                cause: Some(e.into()),
             }
         })?;
 
@@ -106,7 +302,7 @@ impl<'a> SecurityHandler<'a> {
             tracing::error!(error = %e, "Failed to set client key");
             RelayError::Certificate {
                 message: "Failed to set client key".into(),
-                cause: Some(e.to_string()),
+                cause: Some(e.into()),
             }
         })?;
 
@@ -118,7 +314,7 @@ impl<'a> SecurityHandler<'a> {
             tracing::error!(error = %e, "Failed to parse PKCS#12 data");
             RelayError::Certificate {
                 message: "Failed to parse PKCS#12 data".into(),
-                cause: Some(e.to_string()),
+                cause: Some(e.into()),
             }
         })?;
 
@@ -126,7 +322,7 @@ impl<'a> SecurityHandler<'a> {
             tracing::error!(error = %e, "Failed to parse PKCS#12 password");
             RelayError::Certificate {
                 message: "Failed to parse PKCS#12 password".into(),
-                cause: Some(e.to_string()),
+                cause: Some(e.into()),
             }
         })?;
 
@@ -135,7 +331,7 @@ impl<'a> SecurityHandler<'a> {
                 tracing::error!(error = %e, "Failed to convert certificate to PEM");
                 RelayError::Certificate {
                     message: "Failed to convert certificate to PEM".into(),
-                    cause: Some(e.to_string()),
+                    cause: Some(e.into()),
                 }
             })?;
 
@@ -143,7 +339,7 @@ impl<'a> SecurityHandler<'a> {
                 tracing::error!(error = %e, "Failed to convert private key to PEM");
                 RelayError::Certificate {
                     message: "Failed to convert private key to PEM".into(),
-                    cause: Some(e.to_string()),
+                    cause: Some(e.into()),
                 }
             })?;
 
@@ -164,10 +360,78 @@ impl<'a> SecurityHandler<'a> {
                 tracing::error!(error = %e, cert_index = index, "Failed to set CA certificate");
                 RelayError::Certificate {
                     message: format!("Failed to set CA certificate at index {}", index),
-                    cause: Some(e.to_string()),
+                    cause: Some(e.into()),
                 }
             })?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pem(name: &'static str) -> CertificateType {
+        CertificateType::Pem {
+            cert: Bytes::from_static(name.as_bytes()),
+            key: Bytes::new(),
+        }
+    }
+
+    // NOTE: Each test certificate carries its name as the cert bytes.
+    fn selected<'a>(certificates: &'a HostCertificates, url: &str) -> Option<&'a str> {
+        match certificates.select(url)? {
+            CertificateType::Pem { cert, .. } => std::str::from_utf8(cert).ok(),
+            CertificateType::Pfx { .. } => None,
+        }
+    }
+
+    fn registered() -> HostCertificates {
+        let mut certificates = HostCertificates::default();
+        certificates.insert("API.example.com", pem("exact"));
+        certificates.insert("*.example.com", pem("wildcard"));
+        certificates
+    }
+
+    #[test]
+    fn selects_an_exact_host_whatever_its_case() {
+        let certificates = registered();
+        assert_eq!(
+            selected(&certificates, "https://api.example.com/v1"),
+            Some("exact")
+        );
+        assert_eq!(
+            selected(&certificates, "https://Api.Example.COM:8443/"),
+            Some("exact")
+        );
+    }
+
+    #[test]
+    fn a_wildcard_matches_one_leading_label() {
+        let certificates = registered();
+        assert_eq!(
+            selected(&certificates, "https://www.example.com/"),
+            Some("wildcard")
+        );
+        assert_eq!(selected(&certificates, "https://example.com/"), None);
+        assert_eq!(selected(&certificates, "https://a.b.example.com/"), None);
+    }
+
+    #[test]
+    fn falls_back_to_the_default() {
+        let mut certificates = registered();
+        assert_eq!(selected(&certificates, "https://other.org/"), None);
+
+        certificates.set_default(pem("default"));
+        assert_eq!(
+            selected(&certificates, "https://other.org/"),
+            Some("default")
+        );
+        assert_eq!(selected(&certificates, "not a url"), Some("default"));
+        assert_eq!(
+            selected(&certificates, "https://api.example.com/"),
+            Some("exact")
+        );
+    }
+}
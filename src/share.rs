@@ -0,0 +1,460 @@
+use std::{
+    collections::HashMap,
+    os::raw::c_void,
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use curl::easy::Easy;
+
+use crate::{
+    error::{RelayError, Result},
+    interop::Request,
+    transfer::{self, DebugSignals},
+    util::{CURLOPT_DNS_INTERFACE, CURLOPT_DNS_LOCAL_IP4, CURLOPT_DNS_LOCAL_IP6},
+};
+
+lazy_static::lazy_static! {
+    static ref DNS_SHARE: Option<Share> = Share::new(&[curl_sys::CURL_LOCK_DATA_DNS])
+        .map_err(|e| tracing::warn!(error = %e, "DNS cache is unavailable"))
+        .ok();
+}
+
+// NOTE: One slot for every `CURL_LOCK_DATA_*` value libcurl defines.
+const LOCK_SLOTS: usize = 8;
+
+// NOTE: libcurl's own default for `CURLOPT_DNS_CACHE_TIMEOUT`.
+const DEFAULT_DNS_CACHE_TTL: Duration = Duration::from_secs(60);
+
+// NOTE: Past this many tracked names, expired ones are dropped.
+const MAX_TRACKED_NAMES: usize = 1024;
+
+// NOTE: Raw options that change what a name resolves to. `CURLOPT_RESOLVE`
+// writes its entries straight into the DNS cache, where every other handle
+// on the share would find them.
+const RESOLVER_OPTIONS: &[curl_sys::CURLoption] = &[
+    curl_sys::CURLOPT_RESOLVE,
+    curl_sys::CURLOPT_CONNECT_TO,
+    CURLOPT_DNS_INTERFACE,
+    CURLOPT_DNS_LOCAL_IP4,
+    CURLOPT_DNS_LOCAL_IP6,
+];
+
+// NOTE: libcurl locks and unlocks from separate callbacks, which a
+// `MutexGuard` cannot span, so each slot is a flag guarded by a mutex.
+#[derive(Default)]
+struct Lock {
+    held: Mutex<bool>,
+    released: Condvar,
+}
+
+impl Lock {
+    fn acquire(&self) {
+        let mut held = self
+            .held
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        while *held {
+            held = self
+                .released
+                .wait(held)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+        *held = true;
+    }
+
+    fn release(&self) {
+        *self
+            .held
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = false;
+        self.released.notify_one();
+    }
+}
+
+#[derive(Default)]
+struct Locks([Lock; LOCK_SLOTS]);
+
+// NOTE: libcurl can neither tell whether a name is cached nor empty the
+// cache, so the share keeps its own account of when each `host:port` was
+// resolved. Like libcurl's, an entry ages from its lookup and a hit does
+// not refresh it.
+#[derive(Default)]
+struct DnsLedger {
+    flushed_at: Option<Instant>,
+    resolved: HashMap<String, Instant>,
+}
+
+impl DnsLedger {
+    // NOTE: Entries from before the last flush are treated as expired,
+    // which is what makes a flush work: libcurl drops an expired entry
+    // when it is looked up. Whole seconds, rounded down, as libcurl takes
+    // the timeout in seconds.
+    fn timeout(&self, ttl: Option<u64>) -> Duration {
+        let ttl = ttl.map_or(DEFAULT_DNS_CACHE_TTL, Duration::from_secs);
+        match self.flushed_at {
+            Some(at) => ttl.min(Duration::from_secs(at.elapsed().as_secs())),
+            None => ttl,
+        }
+    }
+}
+
+impl Locks {
+    // SAFETY: `userptr` is the `Locks` registered alongside the callbacks,
+    // which lives as long as the share handle.
+    unsafe fn from_userptr<'a>(userptr: *mut c_void) -> &'a Locks {
+        &*(userptr as *const Locks)
+    }
+}
+
+extern "C" fn lock(
+    _handle: *mut curl_sys::CURL,
+    data: curl_sys::curl_lock_data,
+    _access: curl_sys::curl_lock_access,
+    userptr: *mut c_void,
+) {
+    let locks = unsafe { Locks::from_userptr(userptr) };
+    if let Some(lock) = locks.0.get(data as usize) {
+        lock.acquire();
+    }
+}
+
+extern "C" fn unlock(
+    _handle: *mut curl_sys::CURL,
+    data: curl_sys::curl_lock_data,
+    userptr: *mut c_void,
+) {
+    let locks = unsafe { Locks::from_userptr(userptr) };
+    if let Some(lock) = locks.0.get(data as usize) {
+        lock.release();
+    }
+}
+
+// NOTE: A `CURLSH` handle, curl-rust has no wrapper for the share interface.
+// Handles attached to it may run on any thread at the same time, the lock
+// callbacks serialize their access to the shared data. The share has to
+// outlive every handle attached to it.
+pub(crate) struct Share {
+    raw: *mut curl_sys::CURLSH,
+    locks: Box<Locks>,
+    dns: Mutex<DnsLedger>,
+}
+
+// SAFETY: libcurl serializes access to the shared data through the lock
+// callbacks, and the handle itself is only freed on drop.
+unsafe impl Send for Share {}
+unsafe impl Sync for Share {}
+
+fn share_failed(what: &str, code: curl_sys::CURLSHcode) -> RelayError {
+    // SAFETY: `curl_share_strerror` returns a static string for any code.
+    let reason = unsafe { std::ffi::CStr::from_ptr(curl_sys::curl_share_strerror(code)) };
+    RelayError::Network {
+        message: format!("Failed to {}", what),
+        cause: Some(reason.to_string_lossy().into_owned().into()),
+    }
+}
+
+impl Share {
+    pub(crate) fn new(data: &[curl_sys::curl_lock_data]) -> Result<Self> {
+        curl::init();
+
+        // SAFETY: A null handle is checked for before use.
+        let raw = unsafe { curl_sys::curl_share_init() };
+        if raw.is_null() {
+            return Err(share_failed("create share handle", curl_sys::CURLSHE_NOMEM));
+        }
+
+        // NOTE: Built before any option can fail, so `Drop` cleans up.
+        let share = Self {
+            raw,
+            locks: Box::default(),
+            dns: Mutex::default(),
+        };
+        let userptr = &*share.locks as *const Locks as *mut c_void;
+
+        // SAFETY: Each option is passed the type libcurl documents for it.
+        unsafe {
+            share.check(
+                "set share lock function",
+                curl_sys::curl_share_setopt(
+                    raw,
+                    curl_sys::CURLSHOPT_LOCKFUNC,
+                    lock as curl_sys::curl_lock_function,
+                ),
+            )?;
+            share.check(
+                "set share unlock function",
+                curl_sys::curl_share_setopt(
+                    raw,
+                    curl_sys::CURLSHOPT_UNLOCKFUNC,
+                    unlock as curl_sys::curl_unlock_function,
+                ),
+            )?;
+            share.check(
+                "set share user data",
+                curl_sys::curl_share_setopt(raw, curl_sys::CURLSHOPT_USERDATA, userptr),
+            )?;
+            for &data in data {
+                share.check(
+                    "share data between handles",
+                    curl_sys::curl_share_setopt(raw, curl_sys::CURLSHOPT_SHARE, data),
+                )?;
+            }
+        }
+
+        Ok(share)
+    }
+
+    // NOTE: What a `RelayClient` shares between its transfers: resolved
+    // names, TLS sessions for resumption unless `tls_sessions` is off and,
+    // for requests with `cookies` enabled, the cookie jar. libcurl caps the
+    // shared TLS session cache itself, at 25 hosts as of 8.12, and has no
+    // option to size it.
+    pub(crate) fn session(tls_sessions: bool) -> Result<Self> {
+        if tls_sessions {
+            Self::new(&[
+                curl_sys::CURL_LOCK_DATA_DNS,
+                curl_sys::CURL_LOCK_DATA_SSL_SESSION,
+                curl_sys::CURL_LOCK_DATA_COOKIE,
+            ])
+        } else {
+            Self::new(&[
+                curl_sys::CURL_LOCK_DATA_DNS,
+                curl_sys::CURL_LOCK_DATA_COOKIE,
+            ])
+        }
+    }
+
+    fn check(&self, what: &str, code: curl_sys::CURLSHcode) -> Result<()> {
+        if code == curl_sys::CURLSHE_OK {
+            Ok(())
+        } else {
+            Err(share_failed(what, code))
+        }
+    }
+
+    pub(crate) fn attach(&self, handle: &mut Easy) -> Result<()> {
+        // SAFETY: The share outlives every handle attached to it, and curl
+        // detaches a handle from its share on cleanup.
+        let code =
+            unsafe { curl_sys::curl_easy_setopt(handle.raw(), curl_sys::CURLOPT_SHARE, self.raw) };
+        if code != curl_sys::CURLE_OK {
+            let e = curl::Error::new(code);
+            return Err(RelayError::Network {
+                message: "Failed to attach share handle".into(),
+                cause: Some(e.into()),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Share {
+    // NOTE: Sets how long `handle` trusts cached names, and whether `name`
+    // will come from the cache. A miss is only recorded with `record` once
+    // the transfer shows the name resolved.
+    fn resolve(&self, handle: &mut Easy, name: Option<&str>, ttl: Option<u64>) -> Option<bool> {
+        let ledger = self
+            .dns
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let timeout = ledger.timeout(ttl);
+        if let Err(e) = handle.dns_cache_timeout(timeout) {
+            tracing::warn!(error = %e, "Failed to set DNS cache timeout");
+        }
+
+        let name = name?;
+        let hit = ledger
+            .resolved
+            .get(name)
+            .is_some_and(|at| at.elapsed() < timeout);
+        tracing::trace!(
+            hit = hit,
+            timeout_secs = timeout.as_secs(),
+            "DNS cache lookup"
+        );
+        Some(hit)
+    }
+
+    // NOTE: Entries are dropped by age with the default TTL, the longest
+    // any of them is trusted for unless a request asked for more.
+    fn record(&self, name: String) {
+        let mut ledger = self
+            .dns
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if ledger.resolved.len() >= MAX_TRACKED_NAMES {
+            ledger
+                .resolved
+                .retain(|_, at| at.elapsed() < DEFAULT_DNS_CACHE_TTL);
+        }
+        ledger.resolved.insert(name, Instant::now());
+    }
+
+    pub(crate) fn flush_dns(&self) {
+        let mut ledger = self
+            .dns
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        tracing::debug!(names = ledger.resolved.len(), "Flushing DNS cache");
+        ledger.flushed_at = Some(Instant::now());
+        ledger.resolved.clear();
+    }
+}
+
+impl std::fmt::Debug for Share {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Share").finish_non_exhaustive()
+    }
+}
+
+impl Drop for Share {
+    fn drop(&mut self) {
+        // SAFETY: The handle was created by `curl_share_init` and is freed once.
+        unsafe { curl_sys::curl_share_cleanup(self.raw) };
+    }
+}
+
+// NOTE: A request with its own resolver, or its own answers, keeps its
+// lookups out of the shared cache, they may differ from what the system
+// resolver gives.
+fn uses_custom_resolver(request: &Request) -> bool {
+    let resolver_options = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .is_some_and(|options| {
+            options.dns_servers.is_some()
+                || options.dns_interface.is_some()
+                || options.dns_local_ip4.is_some()
+                || options.dns_local_ip6.is_some()
+                || options.doh_url.is_some()
+        });
+    resolver_options
+        || request
+            .raw_options
+            .iter()
+            .any(|option| RESOLVER_OPTIONS.contains(&(option.id() as curl_sys::CURLoption)))
+}
+
+// NOTE: The `host:port` whose address the transfer looks up, `None` when
+// it connects to an IP literal or through a proxy, which resolves the name.
+fn dns_name(request: &Request) -> Option<String> {
+    if request
+        .proxy
+        .as_ref()
+        .is_some_and(|proxy| !proxy.url.is_empty())
+    {
+        return None;
+    }
+    let url = url::Url::parse(&request.url).ok()?;
+    match url.host()? {
+        url::Host::Domain(domain) => Some(format!(
+            "{}:{}",
+            domain.to_ascii_lowercase(),
+            url.port_or_known_default()?
+        )),
+        url::Host::Ipv4(_) | url::Host::Ipv6(_) => None,
+    }
+}
+
+fn dns_cache_ttl(request: &Request) -> Option<u64> {
+    request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.dns_cache_ttl)
+}
+
+// NOTE: Attaches `session` when given, a client's share, or else the process
+// wide DNS cache, and returns whether the request's host comes from that
+// cache. Sharing is an optimization, a transfer that cannot share runs on its
+// own instead of failing. A request with its own resolver shares nothing,
+// libcurl cannot split a share's DNS cache from the rest of it.
+pub(crate) fn attach(
+    handle: &mut Easy,
+    request: &Request,
+    session: Option<&Share>,
+) -> Option<bool> {
+    if uses_custom_resolver(request) {
+        tracing::trace!("Request uses its own resolver, skipping shared caches");
+        return None;
+    }
+    let share = session.or(DNS_SHARE.as_ref())?;
+    if let Err(e) = share.attach(handle) {
+        tracing::warn!(error = %e, "Failed to attach share handle");
+        return None;
+    }
+    share.resolve(handle, dns_name(request).as_deref(), dns_cache_ttl(request))
+}
+
+// NOTE: Called once a transfer that `attach` reported as a miss succeeded,
+// by which time libcurl has cached the name. A hit is left as it is, the
+// entry ages from the lookup that cached it.
+pub(crate) fn resolved(request: &Request, session: Option<&Share>, hit: Option<bool>) {
+    if hit != Some(false) {
+        return;
+    }
+    if let (Some(share), Some(name)) = (session.or(DNS_SHARE.as_ref()), dns_name(request)) {
+        share.record(name);
+    }
+}
+
+// NOTE: Connects without sending anything, which is the only way to make
+// libcurl resolve a name. A host that resolves but refuses the connection
+// still ends up cached, so only failing to resolve is an error. Proxies from
+// the environment are turned off, through one the name would never be
+// resolved here.
+#[tracing::instrument(skip(session), level = "debug")]
+pub(crate) fn warm_dns(
+    host: &str,
+    port: u16,
+    session: Option<&Share>,
+    ttl: Option<u64>,
+) -> Result<()> {
+    let Some(share) = session.or(DNS_SHARE.as_ref()) else {
+        return Err(RelayError::UnsupportedFeature {
+            feature: "DNS cache".into(),
+            message: "libcurl could not create a share handle".into(),
+            relay: "curl".into(),
+        });
+    };
+
+    let name = format!("{}:{}", host.to_ascii_lowercase(), port);
+    let mut handle = Easy::new();
+    share.attach(&mut handle)?;
+    let hit = share.resolve(&mut handle, Some(&name), ttl);
+
+    let host = if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]", host)
+    } else {
+        host.to_string()
+    };
+    handle
+        .url(&format!("http://{}:{}/", host, port))
+        .and_then(|_| handle.proxy(""))
+        .and_then(|_| handle.connect_only(true))
+        .map_err(|e| RelayError::Network {
+            message: "Failed to set up DNS warm-up".into(),
+            cause: Some(e.into()),
+        })?;
+
+    match handle.perform() {
+        Ok(()) => tracing::debug!(host = %host, port = port, "Warmed DNS cache"),
+        Err(e) if e.is_couldnt_resolve_host() => {
+            return Err(transfer::perform_error(
+                &mut handle,
+                &e,
+                None,
+                &DebugSignals::default(),
+            ));
+        }
+        Err(e) => {
+            tracing::warn!(host = %host, port = port, error = %e, "Warmed DNS cache but could not connect")
+        }
+    }
+    if hit == Some(false) {
+        share.record(name);
+    }
+    Ok(())
+}
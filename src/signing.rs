@@ -0,0 +1,307 @@
+use std::{sync::Arc, time::UNIX_EPOCH};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use dashmap::DashMap;
+use http::Method;
+use openssl::{hash::MessageDigest, pkey::PKey, sign::Signer};
+use url::Url;
+
+use crate::{
+    clock::{Clock, SystemClock},
+    content,
+    error::{RelayError, Result},
+    header::Headers,
+    integrity,
+    interop::{ContentType, Request},
+};
+
+lazy_static::lazy_static! {
+    static ref SIGNERS: DashMap<i64, Arc<dyn RequestSigner>> = DashMap::new();
+}
+
+pub(crate) const DEFAULT_SIGNATURE_HEADER: &str = "X-Signature";
+pub(crate) const DEFAULT_TIMESTAMP_HEADER: &str = "X-Timestamp";
+pub(crate) const DEFAULT_BODY_HASH_HEADER: &str = "X-Content-SHA256";
+
+// NOTE: Computes the headers carrying a signature over the request as it is
+// about to be sent. Runs on the transfer thread after every other header is
+// in place, on each attempt, so retries are signed afresh.
+pub trait RequestSigner: Send + Sync {
+    fn sign(&self, canonical: &CanonicalRequest) -> Result<Vec<(String, String)>>;
+}
+
+// NOTE: The request as curl will send it. `headers` are the ones relay sets,
+// client defaults, content type and auth included. Headers libcurl adds on
+// its own, `Host`, `Content-Length`, `Accept-Encoding` and cookies from the
+// jar, are not, sign them from `url` and `body` where a scheme needs them.
+#[derive(Debug)]
+pub struct CanonicalRequest<'a> {
+    method: &'a Method,
+    url: Url,
+    headers: &'a Headers,
+    body: &'a [u8],
+}
+
+impl CanonicalRequest<'_> {
+    pub fn method(&self) -> &Method {
+        self.method
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn headers(&self) -> &Headers {
+        self.headers
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    // NOTE: Empty for requests without content.
+    pub fn body(&self) -> &[u8] {
+        self.body
+    }
+
+    // NOTE: The `Host` header curl sends, the port only when not the
+    // scheme's default.
+    pub fn authority(&self) -> String {
+        let host = self.url.host_str().unwrap_or_default();
+        match self.url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        }
+    }
+
+    // NOTE: The path with the query, as it appears in the request line.
+    pub fn path_and_query(&self) -> String {
+        match self.url.query() {
+            Some(query) => format!("{}?{}", self.url.path(), query),
+            None => self.url.path().to_string(),
+        }
+    }
+}
+
+pub(crate) struct Registration {
+    request_id: i64,
+    signer: Arc<dyn RequestSigner>,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        SIGNERS.remove_if(&self.request_id, |_, signer| {
+            Arc::ptr_eq(signer, &self.signer)
+        });
+    }
+}
+
+pub(crate) fn register(request_id: i64, signer: Arc<dyn RequestSigner>) -> Registration {
+    SIGNERS.insert(request_id, Arc::clone(&signer));
+    Registration { request_id, signer }
+}
+
+pub(crate) fn signer(request_id: i64) -> Option<Arc<dyn RequestSigner>> {
+    if SIGNERS.is_empty() {
+        return None;
+    }
+    SIGNERS
+        .get(&request_id)
+        .map(|signer| Arc::clone(signer.value()))
+}
+
+fn unsigned(message: String) -> RelayError {
+    tracing::error!(message = %message, "Cannot sign request");
+    RelayError::UnsupportedFeature {
+        feature: "request signing".into(),
+        message,
+        relay: "curl".into(),
+    }
+}
+
+// NOTE: Form and multipart bodies are encoded by curl, boundary included,
+// so their bytes are not known until sent and cannot be signed.
+pub(crate) fn sign(
+    signer: &dyn RequestSigner,
+    request: &Request,
+    url: &str,
+    headers: &Headers,
+) -> Result<Vec<(String, String)>> {
+    let body = match request.content {
+        Some(ContentType::Form { .. } | ContentType::Multipart { .. }) => {
+            return Err(unsigned(
+                "form and multipart bodies are encoded by curl and cannot be signed".to_string(),
+            ));
+        }
+        Some(ref content) => content::body_bytes(content).unwrap_or_default(),
+        None => Vec::new(),
+    };
+    let url = Url::parse(url).map_err(|e| unsigned(format!("invalid URL: {}", e)))?;
+
+    let canonical = CanonicalRequest {
+        method: &request.method,
+        url,
+        headers,
+        body: &body,
+    };
+    let signed = signer.sign(&canonical)?;
+    tracing::debug!(
+        headers = ?signed.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        "Signed request"
+    );
+    Ok(signed)
+}
+
+/// Signs with HMAC-SHA256 over the method, path and query, a Unix timestamp
+/// in seconds, the hex SHA-256 of the body, and any headers chosen with
+/// `with_signed_headers`, one per line:
+///
+/// ```text
+/// POST
+/// /v1/orders?dry_run=true
+/// 1700000000
+/// e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855
+/// content-type:application/json
+/// ```
+///
+/// Sends the timestamp, body hash and base64 signature in `X-Timestamp`,
+/// `X-Content-SHA256` and `X-Signature` unless renamed.
+#[derive(Clone)]
+pub struct HmacSigner {
+    key: Vec<u8>,
+    key_id: Option<String>,
+    signed_headers: Vec<String>,
+    signature_header: String,
+    timestamp_header: String,
+    body_hash_header: String,
+    clock: Arc<dyn Clock>,
+}
+
+impl std::fmt::Debug for HmacSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HmacSigner")
+            .field("key_id", &self.key_id)
+            .field("signed_headers", &self.signed_headers)
+            .field("signature_header", &self.signature_header)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HmacSigner {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            key: key.into(),
+            key_id: None,
+            signed_headers: Vec::new(),
+            signature_header: DEFAULT_SIGNATURE_HEADER.to_string(),
+            timestamp_header: DEFAULT_TIMESTAMP_HEADER.to_string(),
+            body_hash_header: DEFAULT_BODY_HASH_HEADER.to_string(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    // NOTE: Sent as `keyId=<id>,signature=<signature>` instead of the bare
+    // signature, for servers holding more than one key.
+    pub fn with_key_id(mut self, key_id: impl Into<String>) -> Self {
+        self.key_id = Some(key_id.into());
+        self
+    }
+
+    // NOTE: Appended to the string to sign in the order given, as lowercase
+    // `name:value` lines, a header the request lacks as `name:`. `host` is
+    // taken from the URL unless the request sets it.
+    pub fn with_signed_headers<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.signed_headers = names
+            .into_iter()
+            .map(|name| name.into().to_ascii_lowercase())
+            .collect();
+        self
+    }
+
+    pub fn with_signature_header(mut self, name: impl Into<String>) -> Self {
+        self.signature_header = name.into();
+        self
+    }
+
+    pub fn with_timestamp_header(mut self, name: impl Into<String>) -> Self {
+        self.timestamp_header = name.into();
+        self
+    }
+
+    pub fn with_body_hash_header(mut self, name: impl Into<String>) -> Self {
+        self.body_hash_header = name.into();
+        self
+    }
+
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    // NOTE: What the signature covers, for checking a server's verifier
+    // against.
+    pub fn string_to_sign(&self, canonical: &CanonicalRequest, timestamp: u64) -> String {
+        let body_hash = integrity::hex(&openssl::sha::sha256(canonical.body()));
+        let mut lines = vec![
+            canonical.method().to_string(),
+            canonical.path_and_query(),
+            timestamp.to_string(),
+            body_hash,
+        ];
+        lines.extend(self.signed_headers.iter().map(|name| {
+            let value = match canonical.header(name) {
+                Some(value) => value.trim().to_string(),
+                None if name == "host" => canonical.authority(),
+                None => String::new(),
+            };
+            format!("{}:{}", name, value)
+        }));
+        lines.join("\n")
+    }
+
+    fn hmac(&self, message: &str) -> Result<Vec<u8>> {
+        let failed = |e: openssl::error::ErrorStack| {
+            tracing::error!(error = %e, "Failed to compute HMAC");
+            RelayError::Parse {
+                message: "Failed to compute request signature".into(),
+                cause: Some(e.into()),
+                partial: None,
+            }
+        };
+        let key = PKey::hmac(&self.key).map_err(failed)?;
+        let mut signer = Signer::new(MessageDigest::sha256(), &key).map_err(failed)?;
+        signer.update(message.as_bytes()).map_err(failed)?;
+        signer.sign_to_vec().map_err(failed)
+    }
+}
+
+impl RequestSigner for HmacSigner {
+    fn sign(&self, canonical: &CanonicalRequest) -> Result<Vec<(String, String)>> {
+        let timestamp = self
+            .clock
+            .now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let signature = BASE64.encode(self.hmac(&self.string_to_sign(canonical, timestamp))?);
+
+        Ok(vec![
+            (self.timestamp_header.clone(), timestamp.to_string()),
+            (
+                self.body_hash_header.clone(),
+                integrity::hex(&openssl::sha::sha256(canonical.body())),
+            ),
+            (
+                self.signature_header.clone(),
+                match self.key_id {
+                    Some(ref key_id) => format!("keyId={},signature={}", key_id, signature),
+                    None => signature,
+                },
+            ),
+        ])
+    }
+}
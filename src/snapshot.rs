@@ -0,0 +1,143 @@
+use std::fmt;
+
+use bytes::Bytes;
+use http::StatusCode;
+
+use crate::{
+    interop::{MediaType, Response},
+    render,
+};
+
+// NOTE: Headers that differ between otherwise identical responses, because
+// they carry the time, how long the server took, or ids minted per request.
+const VOLATILE_HEADERS: &[&str] = &[
+    "date",
+    "age",
+    "expires",
+    "server-timing",
+    "x-response-time",
+    "x-runtime",
+    "request-id",
+    "x-request-id",
+    "x-correlation-id",
+    "x-amzn-requestid",
+    "x-amz-request-id",
+    "cf-ray",
+    "traceparent",
+    "tracestate",
+];
+
+// NOTE: A response reduced to what a snapshot test should compare. Header
+// names are lowercased and headers are sorted, so the order a server sent
+// them in never shows up as a difference.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NormalizedResponse {
+    pub status: StatusCode,
+    pub headers: Vec<(String, String)>,
+    pub body: Bytes,
+    pub media_type: MediaType,
+}
+
+fn header_values<'a>(headers: &'a [(String, String)], name: &str) -> Vec<&'a str> {
+    headers
+        .iter()
+        .filter(|(existing, _)| existing == name)
+        .map(|(_, value)| value.as_str())
+        .collect()
+}
+
+impl NormalizedResponse {
+    // NOTE: One line per difference, empty when both are equal. Bodies are
+    // compared as a whole and not diffed line by line.
+    pub fn diff(&self, other: &NormalizedResponse) -> Vec<String> {
+        let mut differences = Vec::new();
+
+        if self.status != other.status {
+            differences.push(format!(
+                "status: {} != {}",
+                self.status.as_u16(),
+                other.status.as_u16()
+            ));
+        }
+
+        let mut names = self
+            .headers
+            .iter()
+            .chain(&other.headers)
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+
+        for name in names {
+            let (ours, theirs) = (
+                header_values(&self.headers, name),
+                header_values(&other.headers, name),
+            );
+            if ours != theirs {
+                differences.push(format!("header {}: {:?} != {:?}", name, ours, theirs));
+            }
+        }
+
+        if self.body != other.body {
+            differences.push(format!(
+                "body: {} bytes != {} bytes",
+                self.body.len(),
+                other.body.len()
+            ));
+        }
+
+        differences
+    }
+}
+
+// NOTE: HTTP/1.1 style text, e.g. for a snapshot file. Binary bodies are
+// written as their size and media type.
+impl fmt::Display for NormalizedResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.status)?;
+        for (name, value) in &self.headers {
+            writeln!(f, "{}: {}", name, value)?;
+        }
+        writeln!(f)?;
+        if !self.body.is_empty() {
+            write!(f, "{}", render::body_text(&self.body, self.media_type))?;
+        }
+        Ok(())
+    }
+}
+
+impl Response {
+    // NOTE: Drops the volatile headers, such as `Date`, timing and request
+    // ids, and any named in `ignore_headers`, for snapshot tests that should
+    // not fail on every run. Repeated headers, e.g. `Set-Cookie`, are kept
+    // as one entry per value.
+    pub fn normalized_for_snapshot(&self, ignore_headers: &[&str]) -> NormalizedResponse {
+        let ignored = |name: &str| {
+            VOLATILE_HEADERS
+                .iter()
+                .chain(ignore_headers)
+                .any(|ignored| ignored.eq_ignore_ascii_case(name))
+        };
+
+        let mut headers = self
+            .headers
+            .iter()
+            .filter(|(name, _)| !ignored(name))
+            .flat_map(|(name, value)| {
+                let name = name.to_ascii_lowercase();
+                value
+                    .split('\n')
+                    .map(move |value| (name.clone(), value.trim().to_string()))
+            })
+            .collect::<Vec<_>>();
+        headers.sort();
+
+        NormalizedResponse {
+            status: self.status,
+            headers,
+            body: self.body.body.clone(),
+            media_type: self.body.media_type,
+        }
+    }
+}
@@ -0,0 +1,442 @@
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::raw::c_void,
+    sync::{Arc, Mutex},
+};
+
+use curl::easy::Easy;
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use url::{Host, Url};
+
+use crate::{
+    error::{PolicyRule, RelayError, Result},
+    interop::Request,
+};
+
+// NOTE: Loopback, private and link-local networks, the latter holding the
+// cloud metadata endpoints at `169.254.169.254` and `fd00:ec2::254`. NAT64
+// and 6to4 addresses carry an IPv4 address a gateway forwards to, so both
+// prefixes are refused as a whole.
+const DEFAULT_DENIED_RANGES: &[(IpAddr, u8)] = &[
+    (IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 8),
+    (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 0)), 8),
+    (IpAddr::V4(Ipv4Addr::new(100, 64, 0, 0)), 10),
+    (IpAddr::V4(Ipv4Addr::new(127, 0, 0, 0)), 8),
+    (IpAddr::V4(Ipv4Addr::new(169, 254, 0, 0)), 16),
+    (IpAddr::V4(Ipv4Addr::new(172, 16, 0, 0)), 12),
+    (IpAddr::V4(Ipv4Addr::new(192, 168, 0, 0)), 16),
+    (IpAddr::V6(Ipv6Addr::UNSPECIFIED), 128),
+    (IpAddr::V6(Ipv6Addr::LOCALHOST), 128),
+    (
+        IpAddr::V6(Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0)),
+        96,
+    ),
+    (IpAddr::V6(Ipv6Addr::new(0x2002, 0, 0, 0, 0, 0, 0, 0)), 16),
+    (IpAddr::V6(Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 0)), 7),
+    (IpAddr::V6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0)), 10),
+];
+
+const DEFAULT_ALLOWED_SCHEMES: &[&str] = &["http", "https", "ws", "wss"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IpRange {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl IpRange {
+    fn new(network: IpAddr, prefix: u8) -> Self {
+        let prefix = match network {
+            IpAddr::V4(_) => prefix.min(32),
+            IpAddr::V6(_) => prefix.min(128),
+        };
+        Self { network, prefix }
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+// NOTE: Where a client may send requests, for servers that fetch URLs their
+// users supply. Checked against the URL's host before sending, against every
+// address curl connects to, so a name resolving to a denied address is
+// caught however it was resolved, and against each redirect before it is
+// followed. Denied hosts and ranges win over allowed hosts.
+//
+// Hosts are matched case-insensitively, `*.example.com` matching any
+// subdomain of `example.com` but not `example.com` itself. With a proxy,
+// curl connects to the proxy and the proxy resolves the host, so addresses
+// are only checked when the URL's host is an IP literal.
+#[derive(Debug, Clone)]
+pub struct TargetPolicy {
+    allow_hosts: Vec<String>,
+    deny_hosts: Vec<String>,
+    deny_ip_ranges: Vec<IpRange>,
+    allow_schemes: Vec<String>,
+}
+
+impl Default for TargetPolicy {
+    fn default() -> Self {
+        Self {
+            allow_hosts: Vec::new(),
+            deny_hosts: Vec::new(),
+            deny_ip_ranges: DEFAULT_DENIED_RANGES
+                .iter()
+                .map(|&(network, prefix)| IpRange::new(network, prefix))
+                .collect(),
+            allow_schemes: DEFAULT_ALLOWED_SCHEMES
+                .iter()
+                .map(|scheme| scheme.to_string())
+                .collect(),
+        }
+    }
+}
+
+fn host_pattern(pattern: &str) -> String {
+    pattern.trim().trim_end_matches('.').to_ascii_lowercase()
+}
+
+fn matches_host(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.')),
+        None => pattern == host,
+    }
+}
+
+// NOTE: An IPv4 address written as IPv6, mapped as `::ffff:127.0.0.1` or in
+// the deprecated compatible form `::127.0.0.1`, reaches the IPv4 host and is
+// checked as one. `::` and `::1` are IPv6 addresses of their own.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) if v6.is_unspecified() || v6.is_loopback() => ip,
+        IpAddr::V6(v6) => v6.to_ipv4().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+fn violation(rule: PolicyRule, target: &str, message: String) -> RelayError {
+    tracing::warn!(rule = rule.as_str(), target = %target, message = %message, "Target policy violation");
+    RelayError::PolicyViolation {
+        rule,
+        target: target.to_string(),
+        message,
+    }
+}
+
+impl TargetPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // NOTE: Once any host is allowed, every other host is refused.
+    pub fn with_allowed_host(mut self, pattern: impl AsRef<str>) -> Self {
+        self.allow_hosts.push(host_pattern(pattern.as_ref()));
+        self
+    }
+
+    pub fn with_denied_host(mut self, pattern: impl AsRef<str>) -> Self {
+        self.deny_hosts.push(host_pattern(pattern.as_ref()));
+        self
+    }
+
+    pub fn with_denied_ip_range(mut self, network: IpAddr, prefix: u8) -> Self {
+        self.deny_ip_ranges.push(IpRange::new(network, prefix));
+        self
+    }
+
+    // NOTE: Replaces the default ranges, e.g. to let an internal service
+    // through while still refusing the metadata endpoint.
+    pub fn with_denied_ip_ranges<I>(mut self, ranges: I) -> Self
+    where
+        I: IntoIterator<Item = (IpAddr, u8)>,
+    {
+        self.deny_ip_ranges = ranges
+            .into_iter()
+            .map(|(network, prefix)| IpRange::new(network, prefix))
+            .collect();
+        self
+    }
+
+    // NOTE: Replaces the default of `http`, `https`, `ws` and `wss`.
+    pub fn with_allowed_schemes<I, S>(mut self, schemes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.allow_schemes = schemes
+            .into_iter()
+            .map(|scheme| scheme.as_ref().to_ascii_lowercase())
+            .collect();
+        self
+    }
+
+    pub fn check_url(&self, url: &str) -> Result<()> {
+        let parsed = Url::parse(url).map_err(|e| {
+            violation(
+                PolicyRule::AllowHosts,
+                url,
+                format!("URL cannot be parsed: {}", e),
+            )
+        })?;
+
+        if !self
+            .allow_schemes
+            .iter()
+            .any(|scheme| scheme == parsed.scheme())
+        {
+            return Err(violation(
+                PolicyRule::AllowSchemes,
+                url,
+                format!("scheme '{}' is not allowed", parsed.scheme()),
+            ));
+        }
+
+        let host = match parsed.host() {
+            Some(Host::Ipv4(ip)) => {
+                self.check_ip(IpAddr::V4(ip))?;
+                ip.to_string()
+            }
+            Some(Host::Ipv6(ip)) => {
+                self.check_ip(IpAddr::V6(ip))?;
+                ip.to_string()
+            }
+            Some(Host::Domain(domain)) => host_pattern(domain),
+            None => {
+                return Err(violation(
+                    PolicyRule::AllowHosts,
+                    url,
+                    "URL has no host".to_string(),
+                ))
+            }
+        };
+
+        if let Some(pattern) = self
+            .deny_hosts
+            .iter()
+            .find(|pattern| matches_host(pattern, &host))
+        {
+            return Err(violation(
+                PolicyRule::DenyHosts,
+                &host,
+                format!("host '{}' matches denied host '{}'", host, pattern),
+            ));
+        }
+        if !self.allow_hosts.is_empty()
+            && !self
+                .allow_hosts
+                .iter()
+                .any(|pattern| matches_host(pattern, &host))
+        {
+            return Err(violation(
+                PolicyRule::AllowHosts,
+                &host,
+                format!("host '{}' is not an allowed host", host),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn check_ip(&self, ip: IpAddr) -> Result<()> {
+        let ip = canonical(ip);
+        match self.deny_ip_ranges.iter().find(|range| range.contains(ip)) {
+            Some(range) => Err(violation(
+                PolicyRule::DenyIpRanges,
+                &ip.to_string(),
+                format!("address {} is in denied range {}", ip, range),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+// NOTE: The policy as one transfer enforces it. Callbacks record the first
+// violation and fail the connect or header they were given, the transfer
+// then reports the violation instead of the error curl makes of that.
+pub(crate) struct Guard {
+    policy: Arc<TargetPolicy>,
+    addresses: bool,
+    redirects: bool,
+    violation: Mutex<Option<RelayError>>,
+}
+
+// NOTE: For requests a transfer makes on a handle of its own, e.g. the OAuth2
+// token exchange, which connect directly whatever proxy the request has and
+// never follow redirects.
+pub(crate) fn side_guard(request: &Request) -> Option<Guard> {
+    let policy = Arc::clone(request.target_policy.as_ref()?);
+    Some(Guard {
+        policy,
+        addresses: true,
+        redirects: false,
+        violation: Mutex::new(None),
+    })
+}
+
+pub(crate) fn guard(request: &Request) -> Option<Arc<Guard>> {
+    let policy = Arc::clone(request.target_policy.as_ref()?);
+    let proxied = request
+        .proxy
+        .as_ref()
+        .is_some_and(|proxy| !proxy.url.is_empty());
+    let redirects = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.follow_redirects)
+        .unwrap_or(false);
+    Some(Arc::new(Guard {
+        policy,
+        addresses: !proxied,
+        redirects,
+        violation: Mutex::new(None),
+    }))
+}
+
+impl Guard {
+    fn deny(&self, error: RelayError) {
+        self.violation
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get_or_insert(error);
+    }
+
+    pub(crate) fn check_url(&self, url: &str) -> Result<()> {
+        self.policy.check_url(url)
+    }
+
+    pub(crate) fn take_violation(&self) -> Option<RelayError> {
+        self.violation
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take()
+    }
+
+    // NOTE: Called with a redirect's `Location` before curl follows it,
+    // `false` when the transfer has to stop.
+    pub(crate) fn allows_redirect(&self, from: Option<&str>, location: &str) -> bool {
+        if !self.redirects {
+            return true;
+        }
+        let target = match from.and_then(|from| Url::parse(from).ok()) {
+            Some(base) => base.join(location).map(String::from),
+            None => Url::parse(location).map(String::from),
+        };
+        let target = target.unwrap_or_else(|_| location.to_string());
+        match self.policy.check_url(&target) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::error!(location = %target, "Refusing to follow redirect");
+                self.deny(e);
+                false
+            }
+        }
+    }
+
+    // NOTE: `self` has to outlive `handle`, curl keeps the pointer.
+    pub(crate) fn attach(&self, handle: &mut Easy) -> Result<()> {
+        if !self.addresses {
+            return Ok(());
+        }
+        let callback: curl_sys::curl_opensocket_callback = open_socket;
+        let failed = |e: curl::Error| {
+            tracing::error!(error = %e, "Failed to set open socket callback");
+            RelayError::Network {
+                message: "Failed to enforce target policy".into(),
+                cause: Some(e.into()),
+            }
+        };
+        // SAFETY: The callback matches `curl_opensocket_callback`, and the
+        // data pointer is to a `Guard` that outlives the handle.
+        let code = unsafe {
+            curl_sys::curl_easy_setopt(handle.raw(), curl_sys::CURLOPT_OPENSOCKETFUNCTION, callback)
+        };
+        if code != curl_sys::CURLE_OK {
+            return Err(failed(curl::Error::new(code)));
+        }
+        let code = unsafe {
+            curl_sys::curl_easy_setopt(
+                handle.raw(),
+                curl_sys::CURLOPT_OPENSOCKETDATA,
+                self as *const Guard as *mut c_void,
+            )
+        };
+        if code != curl_sys::CURLE_OK {
+            return Err(failed(curl::Error::new(code)));
+        }
+        Ok(())
+    }
+
+    fn open(&self, address: &curl_sys::curl_sockaddr) -> Option<curl_sys::curl_socket_t> {
+        // SAFETY: curl passes `addrlen` bytes of a `sockaddr` of the given
+        // family, copied into storage large enough for any of them.
+        let (_, socket_address) = unsafe {
+            SockAddr::try_init(|storage, len| {
+                let size = (address.addrlen as usize).min(*len as usize);
+                std::ptr::copy_nonoverlapping(
+                    &address.addr as *const _ as *const u8,
+                    storage as *mut u8,
+                    size,
+                );
+                *len = size as _;
+                Ok(())
+            })
+        }
+        .ok()?;
+
+        if let Some(ip) = socket_address.as_socket().map(|address| address.ip()) {
+            if let Err(e) = self.policy.check_ip(ip) {
+                self.deny(e);
+                return None;
+            }
+            tracing::trace!(address = %ip, "Connecting to allowed address");
+        }
+
+        // NOTE: Opened the way curl-rust opens them, with `CLOEXEC` set.
+        let socket = Socket::new(
+            Domain::from(address.family),
+            Type::from(address.socktype),
+            Some(Protocol::from(address.protocol)),
+        )
+        .ok()?;
+        #[cfg(unix)]
+        let raw = std::os::unix::io::IntoRawFd::into_raw_fd(socket);
+        #[cfg(windows)]
+        let raw = std::os::windows::io::IntoRawSocket::into_raw_socket(socket);
+        Some(raw)
+    }
+}
+
+extern "C" fn open_socket(
+    data: *mut c_void,
+    _purpose: curl_sys::curlsocktype,
+    address: *mut curl_sys::curl_sockaddr,
+) -> curl_sys::curl_socket_t {
+    // SAFETY: `data` is the `Guard` given in `attach`, `address` is valid for
+    // the duration of the call.
+    let opened = std::panic::catch_unwind(|| unsafe { (*(data as *const Guard)).open(&*address) });
+    opened.ok().flatten().unwrap_or(curl_sys::CURL_SOCKET_BAD)
+}
@@ -0,0 +1,390 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    error::{UnresolvedVariable, UnresolvedVars},
+    header::Headers,
+    interop::{AuthType, ContentType, FormValue, GrantType, QueryValue, RelatedPart, Request},
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    values: HashMap<String, String>,
+    secrets: HashSet<String>,
+    strict: bool,
+}
+
+impl From<HashMap<String, String>> for Variables {
+    fn from(values: HashMap<String, String>) -> Self {
+        Self {
+            values,
+            ..Self::default()
+        }
+    }
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.values.insert(name.into(), value.into());
+        self
+    }
+
+    // NOTE: Substituted secret values are redacted from every log line of
+    // the resolved request, wherever they end up in it.
+    pub fn secret(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let name = name.into();
+        self.secrets.insert(name.clone());
+        self.values.insert(name, value.into());
+        self
+    }
+
+    // NOTE: Strict resolution fails on unknown variables, otherwise they are
+    // left in place as `{{name}}` and only logged.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+}
+
+struct Resolver<'a> {
+    variables: &'a Variables,
+    unresolved: Vec<UnresolvedVariable>,
+    used_secrets: HashSet<&'a str>,
+}
+
+impl<'a> Resolver<'a> {
+    // NOTE: `\{{` escapes a literal `{{`, an unterminated `{{` is kept as-is.
+    fn substitute(&mut self, field: &str, input: &str) -> String {
+        let mut output = String::with_capacity(input.len());
+        let mut rest = input;
+
+        while let Some(start) = rest.find("{{") {
+            if rest[..start].ends_with('\\') {
+                output.push_str(&rest[..start - 1]);
+                output.push_str("{{");
+                rest = &rest[start + 2..];
+                continue;
+            }
+
+            output.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+
+            let Some(end) = after.find("}}") else {
+                output.push_str(&rest[start..]);
+                return output;
+            };
+
+            let name = after[..end].trim();
+            match self.variables.values.get_key_value(name) {
+                Some((key, value)) => {
+                    if self.variables.secrets.contains(key) {
+                        self.used_secrets.insert(value);
+                    }
+                    output.push_str(value);
+                }
+                None => {
+                    self.unresolved.push(UnresolvedVariable {
+                        name: name.to_string(),
+                        field: field.to_string(),
+                    });
+                    output.push_str(&rest[start..start + 2 + end + 2]);
+                }
+            }
+
+            rest = &after[end + 2..];
+        }
+
+        output.push_str(rest);
+        output
+    }
+
+    fn string(&mut self, field: &str, value: &mut String) {
+        if value.contains("{{") {
+            *value = self.substitute(field, value);
+        }
+    }
+
+    fn optional(&mut self, field: &str, value: &mut Option<String>) {
+        if let Some(value) = value {
+            self.string(field, value);
+        }
+    }
+
+    fn json(&mut self, field: &str, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::String(text) => self.string(field, text),
+            serde_json::Value::Array(items) => {
+                for (index, item) in items.iter_mut().enumerate() {
+                    self.json(&format!("{}.{}", field, index), item);
+                }
+            }
+            serde_json::Value::Object(map) => {
+                for (key, item) in map.iter_mut() {
+                    self.json(&format!("{}.{}", field, key), item);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn form(&mut self, field: &str, content: &mut [(String, Vec<FormValue>)]) {
+        for (name, values) in content.iter_mut() {
+            let field = format!("{}.{}", field, name);
+            for (index, value) in values.iter_mut().enumerate() {
+                match value {
+                    FormValue::Text { value } => {
+                        self.string(&format!("{}.{}.value", field, index), value)
+                    }
+                    FormValue::File { filename, .. } => {
+                        self.string(&format!("{}.{}.filename", field, index), filename)
+                    }
+                }
+            }
+            self.string(&format!("{}.name", field), name);
+        }
+    }
+
+    // NOTE: Bodies are only templated when they are text, binary parts are
+    // left untouched.
+    fn related_part(&mut self, field: &str, part: &mut RelatedPart) {
+        self.string(&format!("{}.contentId", field), &mut part.content_id);
+        self.string(&format!("{}.contentType", field), &mut part.content_type);
+        if let Some(body) = std::str::from_utf8(&part.body)
+            .ok()
+            .filter(|body| body.contains("{{"))
+        {
+            let mut body = body.to_string();
+            self.string(&format!("{}.body", field), &mut body);
+            part.body = body.into();
+        }
+    }
+
+    fn content(&mut self, content: &mut ContentType) {
+        match content {
+            ContentType::Text { content, .. }
+            | ContentType::Xml { content, .. }
+            | ContentType::Csv { content, .. }
+            | ContentType::Urlencoded { content, .. } => self.string("content", content),
+            ContentType::Json { content, raw, .. } => {
+                self.json("content", content);
+                self.optional("content.raw", raw);
+            }
+            ContentType::JsonMergePatch { content } => self.json("content", content),
+            ContentType::JsonPatch { operations } => self.json("content.operations", operations),
+            ContentType::Form { content, .. } | ContentType::Multipart { content, .. } => {
+                self.form("content", content)
+            }
+            ContentType::Binary { filename, .. } => self.optional("content.filename", filename),
+            ContentType::MultipartRelated { root, parts, .. } => {
+                self.related_part("content.root", root);
+                for (index, part) in parts.iter_mut().enumerate() {
+                    self.related_part(&format!("content.parts.{}", index), part);
+                }
+            }
+        }
+    }
+
+    fn grant_type(&mut self, grant_type: &mut GrantType) {
+        match grant_type {
+            GrantType::AuthorizationCode {
+                auth_endpoint,
+                token_endpoint,
+                client_id,
+                client_secret,
+            } => {
+                self.string("auth.grantType.authEndpoint", auth_endpoint);
+                self.string("auth.grantType.tokenEndpoint", token_endpoint);
+                self.string("auth.grantType.clientId", client_id);
+                self.optional("auth.grantType.clientSecret", client_secret);
+            }
+            GrantType::ClientCredentials {
+                token_endpoint,
+                client_id,
+                client_secret,
+            } => {
+                self.string("auth.grantType.tokenEndpoint", token_endpoint);
+                self.string("auth.grantType.clientId", client_id);
+                self.optional("auth.grantType.clientSecret", client_secret);
+            }
+            GrantType::Password {
+                token_endpoint,
+                username,
+                password,
+            } => {
+                self.string("auth.grantType.tokenEndpoint", token_endpoint);
+                self.string("auth.grantType.username", username);
+                self.string("auth.grantType.password", password);
+            }
+            GrantType::Implicit {
+                auth_endpoint,
+                client_id,
+            } => {
+                self.string("auth.grantType.authEndpoint", auth_endpoint);
+                self.string("auth.grantType.clientId", client_id);
+            }
+        }
+    }
+
+    fn auth(&mut self, auth: &mut AuthType) {
+        match auth {
+            AuthType::None => {}
+            AuthType::Basic { username, password } => {
+                self.string("auth.username", username);
+                self.string("auth.password", password);
+            }
+            AuthType::Bearer { token } => self.string("auth.token", token),
+            AuthType::Digest {
+                username,
+                password,
+                realm,
+                nonce,
+                opaque,
+                cnonce,
+                ..
+            } => {
+                self.string("auth.username", username);
+                self.string("auth.password", password);
+                self.optional("auth.realm", realm);
+                self.optional("auth.nonce", nonce);
+                self.optional("auth.opaque", opaque);
+                self.optional("auth.cnonce", cnonce);
+            }
+            AuthType::ApiKey { key, value, .. } => {
+                self.string("auth.key", key);
+                self.string("auth.value", value);
+            }
+            AuthType::OAuth2 {
+                grant_type,
+                access_token,
+                refresh_token,
+            } => {
+                self.grant_type(grant_type);
+                self.optional("auth.accessToken", access_token);
+                self.optional("auth.refreshToken", refresh_token);
+            }
+            AuthType::Aws {
+                access_key,
+                secret_key,
+                region,
+                service,
+                session_token,
+                ..
+            } => {
+                self.string("auth.accessKey", access_key);
+                self.string("auth.secretKey", secret_key);
+                self.string("auth.region", region);
+                self.string("auth.service", service);
+                self.optional("auth.sessionToken", session_token);
+            }
+        }
+    }
+
+    fn request(&mut self, request: &mut Request) {
+        self.string("url", &mut request.url);
+
+        if let Some(headers) = request.headers.take() {
+            let mut templated = headers
+                .iter()
+                .map(|(name, value)| {
+                    let (mut name, mut value) = (name.clone(), value.clone());
+                    let field = format!("headers.{}", name);
+                    self.string(&field, &mut value);
+                    self.string(&field, &mut name);
+                    (name, value)
+                })
+                .collect::<Headers>();
+            for name in headers.suppressed() {
+                let mut name = name.clone();
+                self.string(&format!("headers.{}", name), &mut name);
+                templated.suppress(name);
+            }
+            request.headers = Some(templated);
+        }
+
+        if let Some(params) = request.params.take() {
+            request.params = Some(
+                params
+                    .into_iter()
+                    .map(|(mut name, mut value)| {
+                        let field = format!("params.{}", name);
+                        match value {
+                            QueryValue::Single(ref mut value) => self.string(&field, value),
+                            QueryValue::Multiple(ref mut values) => {
+                                for (index, value) in values.iter_mut().enumerate() {
+                                    self.string(&format!("{}.{}", field, index), value);
+                                }
+                            }
+                        }
+                        self.string(&field, &mut name);
+                        (name, value)
+                    })
+                    .collect(),
+            );
+        }
+
+        if let Some(ref mut content) = request.content {
+            self.content(content);
+        }
+
+        if let Some(ref mut auth) = request.auth {
+            self.auth(auth);
+        }
+
+        if let Some(ref mut proxy) = request.proxy {
+            self.string("proxy.url", &mut proxy.url);
+            if let Some(ref mut auth) = proxy.auth {
+                self.string("proxy.auth.username", &mut auth.username);
+                self.string("proxy.auth.password", &mut auth.password);
+                self.optional("proxy.auth.token", &mut auth.token);
+            }
+        }
+    }
+}
+
+impl Request {
+    pub fn resolve(
+        &self,
+        vars: &HashMap<String, String>,
+    ) -> std::result::Result<Request, UnresolvedVars> {
+        self.resolve_with(&Variables::from(vars.clone()).strict(true))
+    }
+
+    #[tracing::instrument(skip(self, variables), fields(request_id = self.id), level = "debug")]
+    pub fn resolve_with(
+        &self,
+        variables: &Variables,
+    ) -> std::result::Result<Request, UnresolvedVars> {
+        let mut resolver = Resolver {
+            variables,
+            unresolved: Vec::new(),
+            used_secrets: HashSet::new(),
+        };
+
+        let mut request = self.clone();
+        resolver.request(&mut request);
+
+        for secret in resolver.used_secrets {
+            request.secrets.insert(secret);
+        }
+
+        if resolver.unresolved.is_empty() {
+            tracing::debug!("All template variables resolved");
+            return Ok(request);
+        }
+
+        let unresolved = UnresolvedVars {
+            variables: resolver.unresolved,
+        };
+
+        if variables.strict {
+            tracing::error!(error = %unresolved, "Failed to resolve template variables");
+            Err(unresolved)
+        } else {
+            tracing::warn!(error = %unresolved, "Leaving unresolved template variables in place");
+            Ok(request)
+        }
+    }
+}
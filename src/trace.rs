@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use crate::interop::{Request, TraceContext};
+
+pub(crate) const TRACEPARENT: &str = "traceparent";
+pub(crate) const TRACESTATE: &str = "tracestate";
+
+fn is_zero(id: &str) -> bool {
+    id.bytes().all(|b| b == b'0')
+}
+
+// NOTE: Lowercase hex of the exact length, and not all zeros.
+pub(crate) fn is_valid_id(id: &str, len: usize) -> bool {
+    id.len() == len
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+        && !is_zero(id)
+}
+
+impl TraceContext {
+    // NOTE: For continuing a trace received from elsewhere, e.g. the
+    // `traceparent` of the server request being handled. `None` when the
+    // header is malformed.
+    pub fn from_traceparent(traceparent: &str, tracestate: Option<&str>) -> Option<Self> {
+        Self::parse(traceparent, tracestate)
+    }
+
+    // NOTE: See https://www.w3.org/TR/trace-context/#traceparent-header-field-values
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{}-{}-{:02x}",
+            self.trace_id,
+            self.span_id,
+            u8::from(self.sampled)
+        )
+    }
+
+    fn parse(traceparent: &str, tracestate: Option<&str>) -> Option<Self> {
+        let mut parts = traceparent.trim().split('-');
+        let (Some(version), Some(trace_id), Some(span_id), Some(flags)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return None;
+        };
+
+        let is_hex =
+            |s: &str, len: usize| s.len() == len && s.bytes().all(|b| b.is_ascii_hexdigit());
+        if !is_hex(version, 2) || !is_hex(trace_id, 32) || !is_hex(span_id, 16) || !is_hex(flags, 2)
+        {
+            return None;
+        }
+        // NOTE: Version `ff` and all-zero ids are invalid per the spec.
+        if version.eq_ignore_ascii_case("ff") || is_zero(trace_id) || is_zero(span_id) {
+            return None;
+        }
+
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+
+        Some(Self {
+            trace_id: trace_id.to_lowercase(),
+            span_id: span_id.to_lowercase(),
+            sampled: flags & 0x01 == 0x01,
+            trace_state: tracestate.map(str::to_string),
+        })
+    }
+
+    fn generate() -> Self {
+        let trace_id = rand::random::<u128>().max(1);
+        let span_id = rand::random::<u64>().max(1);
+
+        Self {
+            trace_id: format!("{:032x}", trace_id),
+            span_id: format!("{:016x}", span_id),
+            sampled: true,
+            trace_state: None,
+        }
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    fn from_current_span() -> Option<Self> {
+        use opentelemetry::trace::TraceContextExt;
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        let context = tracing::Span::current().context();
+        let span = context.span();
+        let span_context = span.span_context();
+
+        if !span_context.is_valid() {
+            return None;
+        }
+
+        let trace_state = span_context.trace_state().header();
+
+        Some(Self {
+            trace_id: span_context.trace_id().to_string(),
+            span_id: span_context.span_id().to_string(),
+            sampled: span_context.is_sampled(),
+            trace_state: (!trace_state.is_empty()).then_some(trace_state),
+        })
+    }
+
+    #[cfg(not(feature = "opentelemetry"))]
+    fn from_current_span() -> Option<Self> {
+        None
+    }
+}
+
+fn find_header<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request.headers.as_ref().and_then(|headers| {
+        headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    })
+}
+
+pub(crate) fn has_explicit_context(request: &Request) -> bool {
+    find_header(request, TRACEPARENT).is_some()
+}
+
+// NOTE: Propagation headers for `context`, nothing is added when the caller
+// already sent a `traceparent` of their own.
+pub(crate) fn headers(
+    request: &Request,
+    context: Option<&TraceContext>,
+) -> HashMap<String, String> {
+    let mut headers = HashMap::new();
+
+    if let Some(context) = context {
+        if !has_explicit_context(request) {
+            headers.insert(TRACEPARENT.to_string(), context.traceparent());
+            if let Some(ref state) = context.trace_state {
+                headers.insert(TRACESTATE.to_string(), state.clone());
+            }
+        }
+    }
+
+    headers
+}
+
+#[tracing::instrument(skip(request), fields(request_id = request.id), level = "debug")]
+pub(crate) fn resolve(request: &Request) -> Option<TraceContext> {
+    let enabled = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.trace_context)
+        .unwrap_or(false);
+
+    if !enabled && request.trace_context.is_none() {
+        tracing::trace!("Trace context propagation disabled");
+        return None;
+    }
+
+    if let Some(traceparent) = find_header(request, TRACEPARENT) {
+        tracing::debug!("Using caller provided trace context");
+        return TraceContext::parse(traceparent, find_header(request, TRACESTATE));
+    }
+
+    if let Some(ref context) = request.trace_context {
+        tracing::debug!(
+            trace_id = %context.trace_id,
+            span_id = %context.span_id,
+            "Continuing trace context set on the request"
+        );
+        return Some(context.clone());
+    }
+
+    let context = TraceContext::from_current_span().unwrap_or_else(|| {
+        tracing::debug!("No active span context, generating trace context");
+        TraceContext::generate()
+    });
+
+    tracing::debug!(
+        trace_id = %context.trace_id,
+        span_id = %context.span_id,
+        "Resolved trace context"
+    );
+
+    Some(context)
+}
@@ -1,14 +1,234 @@
-use std::collections::HashMap;
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
 use bytes::{Bytes, BytesMut};
-use curl::easy::Easy;
+use curl::easy::{Easy, InfoType};
+use http::StatusCode;
+use openssl::hash::Hasher;
 use tokio_util::sync::CancellationToken;
 
-use crate::error::{RelayError, Result};
+use crate::{
+    content,
+    download::Target,
+    error::{Cause, RelayError, Result, TimeoutPhase},
+    events::Emitter,
+    interop::{
+        BodyCapture, BodyEstimate, ConnectionInfo, IpFamily, PartialResponse, PhaseMarks, Progress,
+        TimingInfo,
+    },
+    target_policy::Guard,
+    util,
+};
+
+// NOTE: Enough to show what went wrong without holding on to a whole body.
+pub(crate) const DEFAULT_MAX_PARTIAL_BODY_BYTES: u64 = 64 * 1024;
+
+// NOTE: Enough to show a status line and the first few header lines.
+const RECEIVED_PREVIEW_BYTES: usize = 256;
+
+pub(crate) type ProgressCallback = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+// NOTE: What only curl's debug callback gets to see, fed from there and read
+// when classifying a failed transfer. `received` holds the start of the
+// response being read, restarting when a status line follows a header block.
+#[derive(Debug, Default)]
+pub(crate) struct DebugSignals {
+    tls_started: AtomicBool,
+    tls_handshake: AtomicBool,
+    session_offered: AtomicBool,
+    server_certificate: AtomicBool,
+    received: Mutex<(Vec<u8>, bool)>,
+    attempted: Mutex<Vec<String>>,
+}
+
+impl DebugSignals {
+    pub(crate) fn observe(&self, info_type: &InfoType, data: &[u8]) {
+        match info_type {
+            InfoType::SslDataOut => self.tls_started.store(true, Ordering::Relaxed),
+            // NOTE: curl announces every connect attempt as `Trying
+            // 192.0.2.1:443...`, IPv6 addresses bracketed since 7.81.
+            InfoType::Text => {
+                let Ok(text) = std::str::from_utf8(data) else {
+                    return;
+                };
+                self.observe_tls(text);
+                let Some(address) = text.trim().strip_prefix("Trying ") else {
+                    return;
+                };
+                let address = address.trim_end_matches('.').trim();
+                tracing::trace!(address = %address, "Connect attempt");
+                self.attempted
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .push(address.to_string());
+            }
+            InfoType::HeaderIn | InfoType::DataIn => {
+                let mut received = self
+                    .received
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                let (bytes, head_done) = &mut *received;
+
+                if matches!(info_type, InfoType::HeaderIn) {
+                    if *head_done && data.starts_with(b"HTTP/") {
+                        bytes.clear();
+                    }
+                    *head_done = matches!(data, b"\r\n" | b"\n");
+                }
+                let room = RECEIVED_PREVIEW_BYTES.saturating_sub(bytes.len());
+                bytes.extend_from_slice(&data[..data.len().min(room)]);
+            }
+            _ => {}
+        }
+    }
+
+    // NOTE: curl says when it offers a cached session, `SSL reusing session`
+    // since 8.12 and `SSL re-using session ID` before, and with OpenSSL
+    // traces every handshake message as e.g. `TLSv1.3 (IN), TLS handshake,
+    // Certificate (11):`. Other TLS backends trace no messages.
+    fn observe_tls(&self, text: &str) {
+        let text = text.trim();
+        if text.starts_with("SSL reusing session") || text.starts_with("SSL re-using session") {
+            self.session_offered.store(true, Ordering::Relaxed);
+        } else if text.contains("(OUT), TLS handshake, Client hello") {
+            self.tls_handshake.store(true, Ordering::Relaxed);
+        } else if text.contains("(IN), TLS handshake, Certificate (") {
+            self.server_certificate.store(true, Ordering::Relaxed);
+        }
+    }
+
+    // NOTE: A server that resumes the offered session skips sending its
+    // certificate. `None` when the transfer made no handshake of its own, it
+    // went out on a reused connection or curl did not trace the handshake.
+    pub(crate) fn session_resumed(&self) -> Option<bool> {
+        self.tls_handshake.load(Ordering::Relaxed).then(|| {
+            self.session_offered.load(Ordering::Relaxed)
+                && !self.server_certificate.load(Ordering::Relaxed)
+        })
+    }
+
+    fn tls_started(&self) -> bool {
+        self.tls_started.load(Ordering::Relaxed)
+    }
+
+    fn attempted(&self) -> Vec<String> {
+        self.attempted
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    fn received(&self) -> String {
+        let received = self
+            .received
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        String::from_utf8_lossy(&received.0).into_owned()
+    }
+}
+
+// NOTE: How the final response's status line and header block were framed,
+// curl is lenient about both and so is relay, the oddities are reported as
+// protocol warnings instead.
+#[derive(Debug, Default)]
+pub(crate) struct ResponseHead {
+    pub(crate) reason: Option<String>,
+    http1: bool,
+    missing_reason: bool,
+    bare_lf: bool,
+}
+
+impl ResponseHead {
+    fn status_line(line: &str) -> Self {
+        let reason = line
+            .trim_end_matches(['\r', '\n'])
+            .splitn(3, ' ')
+            .nth(2)
+            .map(str::trim)
+            .filter(|reason| !reason.is_empty())
+            .map(str::to_string);
+
+        // NOTE: HTTP/2 and HTTP/3 have no reason phrase to miss, and curl
+        // writes their header lines out itself.
+        let mut head = Self {
+            http1: line.starts_with("HTTP/1"),
+            missing_reason: reason.is_none() && line.starts_with("HTTP/1"),
+            reason,
+            bare_lf: false,
+        };
+        head.line(line);
+        head
+    }
+
+    fn line(&mut self, line: &str) {
+        if line.ends_with('\n') && !line.ends_with("\r\n") {
+            self.bare_lf = true;
+        }
+    }
+
+    pub(crate) fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.missing_reason {
+            warnings.push("Status line has no reason phrase".to_string());
+        }
+        if self.bare_lf && self.http1 {
+            warnings.push("Header lines end with a bare LF instead of CRLF".to_string());
+        }
+        warnings
+    }
+}
+
+// NOTE: Repeated headers fold into one comma separated value as RFC 9110
+// allows, except `Set-Cookie` whose values may themselves contain commas and
+// are joined with newlines instead.
+pub(crate) fn fold_header(headers: &mut HashMap<String, String>, key: &str, value: &str) {
+    let separator = if key.eq_ignore_ascii_case("set-cookie") {
+        "\n"
+    } else {
+        ", "
+    };
+    match headers.entry(key.to_string()) {
+        std::collections::hash_map::Entry::Occupied(mut e) => {
+            let existing = e.get_mut();
+            existing.push_str(separator);
+            existing.push_str(value);
+        }
+        std::collections::hash_map::Entry::Vacant(e) => {
+            e.insert(value.to_string());
+        }
+    }
+}
 
 pub(crate) struct TransferHandler {
     body: BytesMut,
     headers: HashMap<String, String>,
+    max_decompressed_bytes: Option<u64>,
+    limit_exceeded: bool,
+    progress: Option<ProgressCallback>,
+    estimate: Option<BodyEstimate>,
+    first_byte_timeout: Option<Duration>,
+    first_byte_waited: Option<Duration>,
+    timeout: Option<Duration>,
+    signals: Arc<DebugSignals>,
+    head: ResponseHead,
+    redirects: Vec<String>,
+    redirect_loop: bool,
+    hasher: Option<Hasher>,
+    events: Option<Arc<Emitter>>,
+    download: Option<Arc<Target>>,
+    target_policy: Option<Arc<Guard>>,
+    early_hints: Vec<String>,
+    body_capture: BodyCapture,
+    received: u64,
+    truncated: bool,
+    capture_stopped: bool,
 }
 
 impl TransferHandler {
@@ -16,9 +236,84 @@ impl TransferHandler {
         Self {
             body: BytesMut::new(),
             headers: HashMap::new(),
+            max_decompressed_bytes: None,
+            limit_exceeded: false,
+            progress: None,
+            estimate: None,
+            first_byte_timeout: None,
+            first_byte_waited: None,
+            timeout: None,
+            signals: Arc::default(),
+            head: ResponseHead::default(),
+            redirects: Vec::new(),
+            redirect_loop: false,
+            hasher: None,
+            events: None,
+            download: None,
+            target_policy: None,
+            early_hints: Vec::new(),
+            body_capture: BodyCapture::Full,
+            received: 0,
+            truncated: false,
+            capture_stopped: false,
         }
     }
 
+    pub(crate) fn with_max_decompressed_bytes(mut self, limit: Option<u64>) -> Self {
+        self.max_decompressed_bytes = limit;
+        self
+    }
+
+    pub(crate) fn with_progress(
+        mut self,
+        progress: Option<ProgressCallback>,
+        estimate: Option<BodyEstimate>,
+    ) -> Self {
+        self.progress = progress;
+        self.estimate = estimate;
+        self
+    }
+
+    pub(crate) fn with_first_byte_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.first_byte_timeout = timeout;
+        self
+    }
+
+    pub(crate) fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub(crate) fn with_events(mut self, events: Option<Arc<Emitter>>) -> Self {
+        self.events = events;
+        self
+    }
+
+    pub(crate) fn with_body_capture(mut self, capture: Option<BodyCapture>) -> Self {
+        self.body_capture = capture.unwrap_or_default();
+        self
+    }
+
+    pub(crate) fn with_download(mut self, download: Option<Arc<Target>>) -> Self {
+        self.download = download;
+        self
+    }
+
+    pub(crate) fn with_target_policy(mut self, target_policy: Option<Arc<Guard>>) -> Self {
+        self.target_policy = target_policy;
+        self
+    }
+
+    pub(crate) fn with_hasher(mut self, hasher: Option<Hasher>) -> Self {
+        self.hasher = hasher;
+        self
+    }
+
+    pub(crate) fn with_debug_signals(mut self, signals: Arc<DebugSignals>) -> Self {
+        self.signals = signals;
+        self
+    }
+
     #[tracing::instrument(skip(self, handle), level = "debug")]
     pub(crate) fn handle_transfer(
         &mut self,
@@ -26,14 +321,85 @@ impl TransferHandler {
         cancel_token: &CancellationToken,
     ) -> Result<()> {
         tracing::debug!("Setting up transfer handlers");
+
+        // NOTE: The progress callback is also what aborts cancelled transfers,
+        // curl only invokes it once `progress(true)` clears `CURLOPT_NOPROGRESS`.
+        handle.progress(true).map_err(|e| {
+            tracing::error!(error = %e, "Failed to enable progress meter");
+            RelayError::Network {
+                message: "Failed to enable progress meter".into(),
+                cause: Some(e.into()),
+            }
+        })?;
+
+        let raw = handle.raw();
+        let first_byte_seen = Cell::new(false);
+        let first_byte = &first_byte_seen;
+        let streaming = Cell::new(false);
+        let streaming = &streaming;
         let mut transfer = handle.transfer();
 
         let body = &mut self.body;
         let headers = &mut self.headers;
+        let head = &mut self.head;
+        let redirects = &mut self.redirects;
+        let redirect_loop = &mut self.redirect_loop;
+        let max_decompressed_bytes = self.max_decompressed_bytes;
+        let limit_exceeded = &mut self.limit_exceeded;
+        let hasher = &mut self.hasher;
+        let download = self.download.as_deref();
+        let target_policy = self.target_policy.as_deref();
+        let early_hints = &mut self.early_hints;
+        let body_capture = self.body_capture;
+        let received = &mut self.received;
+        let truncated = &mut self.truncated;
+        let capture_stopped = &mut self.capture_stopped;
 
         transfer
             .write_function(move |data| {
-                body.extend_from_slice(data);
+                if streaming.get() {
+                    if let Some(download) = download {
+                        if let Err(e) = download.write(data) {
+                            download.fail(e);
+                            return Ok(0);
+                        }
+                    }
+                } else {
+                    // NOTE: curl hands over already decoded data, so this bounds the
+                    // decompressed size. Returning less than `data.len()` aborts the transfer.
+                    if let Some(limit) = max_decompressed_bytes {
+                        if (body.len() + data.len()) as u64 > limit {
+                            tracing::error!(limit = limit, "Decompression limit exceeded");
+                            *limit_exceeded = true;
+                            return Ok(0);
+                        }
+                    }
+                    *received += data.len() as u64;
+                    let kept = match body_capture {
+                        BodyCapture::Full => data.len(),
+                        BodyCapture::Truncate { max_bytes, .. } => {
+                            (max_bytes as usize).saturating_sub(body.len()).min(data.len())
+                        }
+                        BodyCapture::Discard => 0,
+                    };
+                    body.extend_from_slice(&data[..kept]);
+                    if kept < data.len() {
+                        if !*truncated {
+                            tracing::debug!(kept = body.len(), capture = ?body_capture, "Truncating response body");
+                        }
+                        *truncated = true;
+                        if let BodyCapture::Truncate { abort: true, .. } = body_capture {
+                            *capture_stopped = true;
+                            return Ok(0);
+                        }
+                    }
+                }
+                if let Some(hasher) = hasher {
+                    if let Err(e) = hasher.update(data) {
+                        tracing::error!(error = %e, "Failed to hash response data");
+                        return Ok(0);
+                    }
+                }
                 tracing::trace!(bytes = data.len(), "Received response data chunk");
                 Ok(data.len())
             })
@@ -41,33 +407,68 @@ impl TransferHandler {
                 tracing::error!(error = %e, "Failed to set write callback");
                 RelayError::Network {
                     message: "Failed to set write callback".into(),
-                    cause: Some(e.to_string()),
+                    cause: Some(e.into()),
                 }
             })?;
 
+        let mut status_code = 0;
+
         transfer
             .header_function(move |header| {
+                first_byte.set(true);
                 if let Ok(header_str) = String::from_utf8(header.to_vec()) {
-                    if let Some(idx) = header_str.find(':') {
+                    // NOTE: Each status line starts a new response, only the
+                    // headers of the last one (after redirects and interim
+                    // `1xx` responses) are kept.
+                    if header_str.starts_with("HTTP/") {
+                        headers.clear();
+                        streaming.set(false);
+                        *head = ResponseHead::status_line(&header_str);
+
+                        let status = header_str.split(' ').nth(1).unwrap_or_default();
+                        status_code = status.parse().unwrap_or_default();
+                        if !status.starts_with('1') {
+                            redirects.extend(util::effective_url(raw));
+                            if status.starts_with('3') && repeats_hop(redirects) {
+                                tracing::error!(chain = ?redirects, "Redirect loop detected");
+                                *redirect_loop = true;
+                                return false;
+                            }
+                        }
+                    } else if let Some(idx) = header_str.find(':') {
+                        head.line(&header_str);
                         let (key, value) = header_str.split_at(idx);
-                        let key = key.trim().to_string();
-                        let value = value[1..].trim().to_string();
-
-                        if key.to_lowercase() == "set-cookie" {
-                            // NOTE: Special handling workaround.
-                            // Concatenate multiple `Set-Cookie` headers.
-                            match headers.entry(key) {
-                                std::collections::hash_map::Entry::Occupied(mut e) => {
-                                    let existing = e.get_mut();
-                                    existing.push_str("\n");
-                                    existing.push_str(&value);
-                                }
-                                std::collections::hash_map::Entry::Vacant(e) => {
-                                    e.insert(value);
+                        // NOTE: Interim responses are dropped with the next
+                        // status line, only the hints of a `103` are kept.
+                        if status_code == 103 && key.trim().eq_ignore_ascii_case("link") {
+                            early_hints.push(value[1..].trim().to_string());
+                        }
+                        fold_header(headers, key.trim(), value[1..].trim());
+                    } else if header_str.trim().is_empty() && status_code >= 200 {
+                        // NOTE: Checked before curl follows a redirect, so a
+                        // refused target is never requested.
+                        let location = headers
+                            .iter()
+                            .find(|(name, _)| name.eq_ignore_ascii_case("location"))
+                            .map(|(_, value)| value.as_str());
+                        if let (Some(policy), Some(location)) = (target_policy, location) {
+                            let from = redirects.last().map(String::as_str);
+                            if (300..400).contains(&status_code)
+                                && !policy.allows_redirect(from, location)
+                            {
+                                return false;
+                            }
+                        }
+                        // NOTE: The blank line ending a final response's
+                        // headers, whose body a download may take.
+                        if let Some(download) = download {
+                            match download.start(status_code, headers) {
+                                Ok(taken) => streaming.set(taken),
+                                Err(e) => {
+                                    download.fail(e);
+                                    return false;
                                 }
                             }
-                        } else {
-                            headers.entry(key).or_insert(value);
                         }
                     }
                 }
@@ -77,40 +478,495 @@ impl TransferHandler {
                 tracing::error!(error = %e, "Failed to set header callback");
                 RelayError::Network {
                     message: "Failed to set header callback".into(),
-                    cause: Some(e.to_string()),
+                    cause: Some(e.into()),
                 }
             })?;
 
+        let progress = self.progress.clone();
+        let estimate = self.estimate.clone();
+        let mut last_reported = None;
+        let first_byte_timeout = self.first_byte_timeout;
+        let first_byte_waited = &mut self.first_byte_waited;
+        let mut sent_at = None;
+        let events = self.events.clone();
+
         transfer
-            .progress_function(|_, _, _, _| {
+            .progress_function(move |dltotal, dlnow, ultotal, ulnow| {
                 let cancelled = cancel_token.is_cancelled();
                 if cancelled {
                     tracing::warn!("Request cancelled by user");
+                    return false;
                 }
-                !cancelled
+
+                // NOTE: The clock starts once the connection is up and the whole
+                // body is uploaded, so slow connects and uploads never count.
+                // Checked from the progress callback, so resolution is ~1s at worst.
+                if let Some(timeout) = first_byte_timeout {
+                    if !first_byte.get() {
+                        let sent = ulnow >= ultotal && util::pretransfer_reached(raw);
+                        match sent_at {
+                            None if sent => sent_at = Some(Instant::now()),
+                            Some(at) if at.elapsed() > timeout => {
+                                tracing::error!(
+                                    timeout_ms = timeout.as_millis() as u64,
+                                    "No response bytes received before first byte timeout"
+                                );
+                                *first_byte_waited = Some(at.elapsed());
+                                return false;
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+
+                if let Some(ref events) = events {
+                    events.observe_progress(dlnow as u64, ulnow as u64, ultotal as u64);
+                }
+
+                if let Some(ref progress) = progress {
+                    let current = (dlnow as u64, ulnow as u64);
+                    if last_reported != Some(current) {
+                        last_reported = Some(current);
+
+                        let part = estimate.as_ref().and_then(|estimate| {
+                            content::locate_part(estimate, ulnow as u64, ultotal as u64)
+                        });
+                        if let Some(ref part) = part {
+                            tracing::trace!(
+                                part = part.index,
+                                name = %part.name,
+                                sent = part.sent,
+                                size = part.size,
+                                "Multipart upload progress"
+                            );
+                        }
+
+                        progress(&Progress {
+                            uploaded: ulnow as u64,
+                            upload_total: ultotal as u64,
+                            downloaded: dlnow as u64,
+                            download_total: dltotal as u64,
+                            part,
+                        });
+                    }
+                }
+
+                true
             })
             .map_err(|e| {
                 tracing::error!(error = %e, "Failed to set progress callback");
                 RelayError::Network {
                     message: "Failed to set progress callback".into(),
-                    cause: Some(e.to_string()),
+                    cause: Some(e.into()),
                 }
             })?;
 
         tracing::debug!("Starting transfer");
-        transfer.perform().map_err(|e| {
-            tracing::error!(error = %e, "Failed to perform request");
-            RelayError::Network {
-                message: "Failed to perform request".into(),
-                cause: Some(e.to_string()),
+        if let Some(ref events) = self.events {
+            events.transfer_started();
+        }
+        let result = transfer.perform();
+        drop(transfer);
+
+        if let Some(waited) = self.first_byte_waited {
+            let limit = self.first_byte_timeout.unwrap_or_default();
+            return Err(RelayError::Timeout {
+                message: format!(
+                    "No response received within {}ms of sending the request",
+                    limit.as_millis()
+                )
+                .into(),
+                phase: Some(TimeoutPhase::FirstByte),
+                code: None,
+                elapsed_ms: Some(waited.as_millis() as u64),
+                limit_ms: Some(limit.as_millis() as u64),
+                partial: None,
+                trace: None,
+            });
+        }
+
+        if self.redirect_loop {
+            return Err(RelayError::TooManyRedirects {
+                message: format!("Redirect loop: {}", self.redirects.join(" -> ")).into(),
+                code: i64::from(curl_sys::CURLE_TOO_MANY_REDIRECTS),
+                chain: self.redirects.clone(),
+            });
+        }
+
+        // NOTE: Ending the transfer at the capture limit is a write error to
+        // curl, but the response is complete as far as relay is concerned.
+        let result = match result {
+            Err(e) if self.capture_stopped && e.is_write_error() => {
+                tracing::debug!(
+                    received = self.received,
+                    "Stopped transfer at capture limit"
+                );
+                Ok(())
             }
+            result => result,
+        };
+
+        if let Some(violation) = self
+            .target_policy
+            .as_ref()
+            .and_then(|policy| policy.take_violation())
+        {
+            return Err(violation);
+        }
+
+        if let Some(error) = self
+            .download
+            .as_ref()
+            .and_then(|download| download.take_failure())
+        {
+            return Err(error);
+        }
+
+        if self.limit_exceeded {
+            return Err(RelayError::Parse {
+                message: "decompression limit exceeded".into(),
+                cause: self
+                    .max_decompressed_bytes
+                    .map(|limit| format!("Response body exceeded {} bytes", limit).into()),
+                partial: None,
+            });
+        }
+
+        result.map_err(|e| {
+            tracing::error!(error = %e, code = e.code(), "Failed to perform request");
+            if e.is_bad_content_encoding() {
+                return decoding_error(&e, &self.headers);
+            }
+            let mut error = perform_error(handle, &e, self.timeout, &self.signals);
+            if let RelayError::TooManyRedirects { ref mut chain, .. } = error {
+                *chain = self.redirects.clone();
+                chain.extend(handle.redirect_url().ok().flatten().map(str::to_string));
+            }
+            error
         })?;
 
         tracing::debug!("Transfer completed successfully");
         Ok(())
     }
 
-    pub(crate) fn into_parts(self) -> (Bytes, HashMap<String, String>) {
-        (self.body.into(), self.headers)
+    // NOTE: Whatever arrived before a failed transfer gave up, `None` when
+    // nothing did. The body is cut down to `max_body_bytes`.
+    pub(crate) fn partial(
+        &self,
+        handle: &mut Easy,
+        timing: TimingInfo,
+        max_body_bytes: u64,
+    ) -> Option<PartialResponse> {
+        let status = handle
+            .response_code()
+            .ok()
+            .and_then(|code| u16::try_from(code).ok())
+            .and_then(|code| StatusCode::from_u16(code).ok());
+
+        if status.is_none() && self.headers.is_empty() && self.body.is_empty() {
+            return None;
+        }
+
+        let kept = self.body.len().min(max_body_bytes as usize);
+        tracing::debug!(
+            status = ?status,
+            body_size = self.body.len(),
+            kept,
+            "Keeping partial response"
+        );
+
+        Some(PartialResponse {
+            status,
+            headers: self.headers.clone(),
+            body: Bytes::copy_from_slice(&self.body[..kept]),
+            body_size: self.received.max(self.body.len() as u64),
+            timing,
+        })
+    }
+
+    // NOTE: The digest of everything the write callback received, `None`
+    // when no hash was asked for.
+    pub(crate) fn body_digest(&mut self) -> Result<Option<Vec<u8>>> {
+        let Some(ref mut hasher) = self.hasher else {
+            return Ok(None);
+        };
+        hasher
+            .finish()
+            .map(|digest| Some(digest.to_vec()))
+            .map_err(|e| RelayError::Io {
+                message: "Failed to finish response body hash".into(),
+                cause: Some(e.into()),
+            })
+    }
+
+    // NOTE: Every body byte received when `body` holds fewer, `None` when
+    // the whole body was kept.
+    pub(crate) fn truncated_size(&self) -> Option<u64> {
+        self.truncated.then_some(self.received)
+    }
+
+    // NOTE: Whether the transfer ended at the capture limit, leaving the rest
+    // of the body unread.
+    pub(crate) fn capture_stopped(&self) -> bool {
+        self.capture_stopped
+    }
+
+    pub(crate) fn take_early_hints(&mut self) -> Vec<String> {
+        if !self.early_hints.is_empty() {
+            tracing::debug!(hints = ?self.early_hints, "Received early hints");
+        }
+        std::mem::take(&mut self.early_hints)
+    }
+
+    pub(crate) fn into_parts(self) -> (Bytes, HashMap<String, String>, ResponseHead) {
+        (self.body.into(), self.headers, self.head)
+    }
+}
+
+// NOTE: A loop is the same redirect taken a second time, e.g. `/a` to `/b`
+// twice. Revisiting a URL alone is not one, servers commonly redirect back
+// to the same page after setting a cookie.
+fn repeats_hop(chain: &[String]) -> bool {
+    let [.., from, to] = chain else {
+        return false;
+    };
+    chain[..chain.len() - 1]
+        .windows(2)
+        .any(|hop| hop[0] == *from && hop[1] == *to)
+}
+
+// NOTE: `Content-Encoding` lists codings in the order they were applied,
+// curl undoes them in reverse. A failure is either a coding this libcurl
+// was built without or a body that does not decode.
+fn decoding_error(e: &curl::Error, headers: &HashMap<String, String>) -> RelayError {
+    let content_encoding = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-encoding"))
+        .map(|(_, value)| value.to_ascii_lowercase())
+        .unwrap_or_default();
+    let supported = util::content_encodings();
+
+    let unsupported = content_encoding
+        .split(',')
+        .map(str::trim)
+        .find(|coding| !coding.is_empty() && *coding != "identity" && !supported.contains(coding));
+    if let Some(coding) = unsupported {
+        tracing::warn!(coding = %coding, supported = ?supported, "Cannot decode response");
+        return RelayError::UnsupportedFeature {
+            feature: format!("{} content encoding", coding),
+            message: format!(
+                "libcurl cannot decode {}, disable decompress to receive the encoded body",
+                coding
+            ),
+            relay: "curl".into(),
+        };
+    }
+
+    RelayError::Parse {
+        message: format!(
+            "Failed to decode response body with Content-Encoding {:?}",
+            content_encoding
+        ),
+        cause: Some(e.clone().into()),
+        partial: None,
+    }
+}
+
+fn target_url(handle: &mut Easy) -> Option<url::Url> {
+    handle
+        .effective_url()
+        .ok()
+        .flatten()
+        .and_then(|url| url::Url::parse(url).ok())
+}
+
+// NOTE: Prefers the address curl actually tried, falling back to the host and
+// port from the URL when curl never got as far as picking one.
+fn peer_addr(handle: &mut Easy) -> String {
+    let ip = handle
+        .primary_ip()
+        .ok()
+        .flatten()
+        .filter(|ip| !ip.is_empty())
+        .map(str::to_string);
+    let port = handle.primary_port().ok().filter(|port| *port != 0);
+
+    match (ip, port) {
+        (Some(ip), Some(port)) if ip.contains(':') => format!("[{}]:{}", ip, port),
+        (Some(ip), Some(port)) => format!("{}:{}", ip, port),
+        _ => target_url(handle)
+            .and_then(|url| {
+                let host = url.host_str()?.to_string();
+                Some(format!("{}:{}", host, url.port_or_known_default()?))
+            })
+            .unwrap_or_default(),
+    }
+}
+
+// NOTE: `None` when curl reports no address, e.g. for a transfer through a
+// Unix socket.
+pub(crate) fn connection_info(handle: &mut Easy, signals: &DebugSignals) -> Option<ConnectionInfo> {
+    let remote_ip = handle
+        .primary_ip()
+        .ok()
+        .flatten()
+        .filter(|ip| !ip.is_empty())?
+        .to_string();
+    let family = if remote_ip.contains(':') {
+        IpFamily::Ipv6
+    } else {
+        IpFamily::Ipv4
+    };
+    let attempted = signals.attempted();
+
+    tracing::debug!(
+        remote_ip = %remote_ip,
+        family = ?family,
+        attempts = attempted.len(),
+        "Connected"
+    );
+    Some(ConnectionInfo {
+        remote_ip,
+        family,
+        attempted,
+    })
+}
+
+pub(crate) fn phase_marks(handle: &mut Easy) -> PhaseMarks {
+    let [redirect, namelookup, connect, appconnect, pretransfer, starttransfer, total] = [
+        handle.redirect_time(),
+        handle.namelookup_time(),
+        handle.connect_time(),
+        handle.appconnect_time(),
+        handle.pretransfer_time(),
+        handle.starttransfer_time(),
+        handle.total_time(),
+    ]
+    .map(|mark| mark.map_or(0.0, |mark| mark.as_secs_f64() * 1000.0));
+
+    PhaseMarks {
+        redirect,
+        namelookup,
+        connect,
+        appconnect,
+        pretransfer,
+        starttransfer,
+        total,
+    }
+}
+
+// NOTE: curl leaves the time of every milestone it never reached at zero, the
+// first one missing is where the transfer stalled. The connect time is only
+// recorded once the whole connection is up, including any TLS handshake.
+fn timeout_phase(handle: &mut Easy, tls_started: bool) -> TimeoutPhase {
+    let reached = |time: std::result::Result<Duration, curl::Error>| {
+        time.is_ok_and(|time| time > Duration::ZERO)
+    };
+
+    let phase = if !reached(handle.namelookup_time()) {
+        TimeoutPhase::Dns
+    } else if !reached(handle.connect_time()) {
+        if tls_started {
+            TimeoutPhase::Tls
+        } else {
+            TimeoutPhase::Connect
+        }
+    } else if !reached(handle.starttransfer_time()) {
+        TimeoutPhase::Response
+    } else {
+        TimeoutPhase::Body
+    };
+
+    tracing::debug!(phase = ?phase, tls_started, "Classified timeout");
+    phase
+}
+
+// NOTE: Maps a failed transfer onto the matching `RelayError`, keeping curl's
+// code and message. Failures without a dedicated variant stay `Network`.
+pub(crate) fn perform_error(
+    handle: &mut Easy,
+    e: &curl::Error,
+    timeout: Option<Duration>,
+    signals: &DebugSignals,
+) -> RelayError {
+    let code = i64::from(e.code());
+    let message = Cause::from(e.clone());
+
+    if e.is_couldnt_resolve_host() {
+        let host = target_url(handle)
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+        RelayError::DnsResolution {
+            host,
+            message,
+            code,
+        }
+    } else if e.is_couldnt_resolve_proxy() || e.code() == util::CURLE_PROXY {
+        RelayError::ProxyError { message, code }
+    } else if e.is_couldnt_connect() {
+        RelayError::ConnectionRefused {
+            addr: peer_addr(handle),
+            message,
+            code,
+        }
+    } else if e.is_send_error() || e.is_recv_error() || e.is_got_nothing() || e.is_partial_file() {
+        RelayError::ConnectionReset {
+            message,
+            code,
+            partial: None,
+        }
+    } else if e.is_operation_timedout() {
+        RelayError::Timeout {
+            message,
+            phase: Some(timeout_phase(handle, signals.tls_started())),
+            code: Some(code),
+            elapsed_ms: handle.total_time().ok().map(|time| time.as_millis() as u64),
+            limit_ms: timeout.map(|timeout| timeout.as_millis() as u64),
+            partial: None,
+            trace: None,
+        }
+    } else if e.is_peer_failed_verification()
+        || e.is_ssl_cacert()
+        || e.is_ssl_cacert_badfile()
+        || e.is_ssl_certproblem()
+        || e.is_ssl_issuer_error()
+    {
+        RelayError::Certificate {
+            message: "Certificate verification failed".into(),
+            cause: Some(message),
+        }
+    } else if e.is_ssl_connect_error() || e.is_ssl_cipher() || e.is_use_ssl_failed() {
+        RelayError::TlsHandshake {
+            detail: e
+                .extra_description()
+                .unwrap_or_else(|| e.description())
+                .to_string(),
+            message,
+            code,
+        }
+    } else if e.is_too_many_redirects() {
+        RelayError::TooManyRedirects {
+            message,
+            code,
+            chain: Vec::new(),
+        }
+    } else if (e.code() == curl_sys::CURLE_FTP_WEIRD_SERVER_REPLY || e.is_unsupported_protocol())
+        && !signals.received().is_empty()
+    {
+        // NOTE: `CURLE_WEIRD_SERVER_REPLY` still goes by its old FTP name in
+        // `curl_sys`. curl also reports replies it cannot parse as an
+        // unsupported protocol, having received anything tells the two apart.
+        RelayError::MalformedResponse {
+            message,
+            code,
+            received: signals.received(),
+        }
+    } else if e.is_unsupported_protocol() {
+        RelayError::UnsupportedProtocol { message, code }
+    } else {
+        RelayError::Network {
+            message: "Failed to perform request".into(),
+            cause: Some(message),
+        }
     }
 }
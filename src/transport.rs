@@ -0,0 +1,43 @@
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use crate::{
+    error::Result,
+    interop::{Request, Response},
+    relay,
+    share::Share,
+};
+
+pub type TransportFuture<'a> = Pin<Box<dyn Future<Output = Result<Response>> + Send + 'a>>;
+
+pub trait Transport: Send + Sync {
+    fn send(&self, request: Request) -> TransportFuture<'_>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CurlTransport;
+
+impl Transport for CurlTransport {
+    fn send(&self, request: Request) -> TransportFuture<'_> {
+        Box::pin(relay::transfer(request, None))
+    }
+}
+
+// NOTE: The transport behind `RelayClient`, every transfer goes through the
+// client's share so clones of the client reuse one another's lookups, TLS
+// sessions and cookies.
+#[derive(Debug, Clone)]
+pub(crate) struct SessionTransport {
+    share: Arc<Share>,
+}
+
+impl SessionTransport {
+    pub(crate) fn new(share: Arc<Share>) -> Self {
+        Self { share }
+    }
+}
+
+impl Transport for SessionTransport {
+    fn send(&self, request: Request) -> TransportFuture<'_> {
+        Box::pin(relay::transfer(request, Some(Arc::clone(&self.share))))
+    }
+}
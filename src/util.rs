@@ -13,3 +13,136 @@ impl ToCurlVersion for http::Version {
         }
     }
 }
+
+// NOTE: Options not exposed by `curl::easy::Easy`,
+// see: https://curl.se/libcurl/c/easy_setopt_options.html
+pub(crate) const CURLOPT_HAPPY_EYEBALLS_TIMEOUT_MS: curl_sys::CURLoption =
+    curl_sys::CURLOPTTYPE_LONG + 271;
+pub(crate) const CURLOPT_TCP_FASTOPEN: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_LONG + 244;
+pub(crate) const CURLOPT_SSL_ENABLE_ALPN: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_LONG + 226;
+pub(crate) const CURLOPT_SSL_FALSESTART: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_LONG + 233;
+pub(crate) const CURLOPT_DNS_INTERFACE: curl_sys::CURLoption =
+    curl_sys::CURLOPTTYPE_OBJECTPOINT + 221;
+pub(crate) const CURLOPT_DNS_LOCAL_IP4: curl_sys::CURLoption =
+    curl_sys::CURLOPTTYPE_OBJECTPOINT + 222;
+pub(crate) const CURLOPT_DNS_LOCAL_IP6: curl_sys::CURLoption =
+    curl_sys::CURLOPTTYPE_OBJECTPOINT + 223;
+
+// NOTE: `CURLOPT_FOLLOWLOCATION` modes beyond on and off, added in 8.13.
+pub(crate) const CURLFOLLOW_OBEYCODE: std::os::raw::c_long = 2;
+
+// NOTE: Error codes `curl_sys` does not define, being newer or reused,
+// see: https://curl.se/libcurl/c/libcurl-errors.html
+pub(crate) const CURLE_NOT_BUILT_IN: curl_sys::CURLcode = 4;
+pub(crate) const CURLE_HTTP3: curl_sys::CURLcode = 95;
+pub(crate) const CURLE_QUIC_CONNECT_ERROR: curl_sys::CURLcode = 96;
+pub(crate) const CURLE_PROXY: curl_sys::CURLcode = 97;
+
+// NOTE: Content codings the linked libcurl decodes, what it advertises for
+// `CURLOPT_ACCEPT_ENCODING` set to `""`.
+pub(crate) fn content_encodings() -> Vec<&'static str> {
+    let version = curl::Version::get();
+    let mut encodings = Vec::new();
+    if version.feature_libz() {
+        encodings.extend(["deflate", "gzip"]);
+    }
+    if version.feature_brotli() {
+        encodings.push("br");
+    }
+    if version.feature_zstd() {
+        encodings.push("zstd");
+    }
+    encodings
+}
+
+// NOTE: The resolver backend of the linked libcurl, from its version info.
+// Only c-ares takes DNS servers, an interface or local addresses to query
+// from, the threaded and synchronous resolvers go through the system.
+pub(crate) fn resolver_backend() -> String {
+    let version = curl::Version::get();
+    match version.ares_version() {
+        Some(ares) => format!("c-ares {}", ares),
+        None if version.feature_async_dns() => "threaded".into(),
+        None => "system".into(),
+    }
+}
+
+pub(crate) fn has_c_ares() -> bool {
+    curl::Version::get().ares_version().is_some()
+}
+
+// NOTE: For options and flags the linked libcurl only honours from a given
+// release on, older ones reject or silently ignore them.
+pub(crate) fn libcurl_at_least(major: u32, minor: u32) -> bool {
+    curl::Version::get().version_num() >= (major << 16) | (minor << 8)
+}
+
+pub(crate) trait EasyExt {
+    fn setopt_long(
+        &mut self,
+        option: curl_sys::CURLoption,
+        value: std::os::raw::c_long,
+    ) -> Result<(), curl::Error>;
+
+    fn setopt_str(&mut self, option: curl_sys::CURLoption, value: &str) -> Result<(), curl::Error>;
+}
+
+impl EasyExt for curl::easy::Easy {
+    fn setopt_long(
+        &mut self,
+        option: curl_sys::CURLoption,
+        value: std::os::raw::c_long,
+    ) -> Result<(), curl::Error> {
+        // SAFETY: `raw` is a valid easy handle for the lifetime of `self`,
+        // and `option` is always a `CURLOPTTYPE_LONG` option taking a `long`.
+        let code = unsafe { curl_sys::curl_easy_setopt(self.raw(), option, value) };
+        if code == curl_sys::CURLE_OK {
+            Ok(())
+        } else {
+            Err(curl::Error::new(code))
+        }
+    }
+
+    fn setopt_str(&mut self, option: curl_sys::CURLoption, value: &str) -> Result<(), curl::Error> {
+        let value = std::ffi::CString::new(value)
+            .map_err(|_| curl::Error::new(curl_sys::CURLE_BAD_FUNCTION_ARGUMENT))?;
+        // SAFETY: `raw` is a valid easy handle for the lifetime of `self`,
+        // `option` is always a string option, and libcurl copies the string.
+        let code = unsafe { curl_sys::curl_easy_setopt(self.raw(), option, value.as_ptr()) };
+        if code == curl_sys::CURLE_OK {
+            Ok(())
+        } else {
+            Err(curl::Error::new(code))
+        }
+    }
+}
+
+// NOTE: Read through the raw handle so it can be polled from inside transfer
+// callbacks, where the `Easy` itself is mutably borrowed by the transfer.
+// Pretransfer time stays zero until the connection is ready to send.
+pub(crate) fn pretransfer_reached(raw: *mut curl_sys::CURL) -> bool {
+    let mut seconds: std::os::raw::c_double = 0.0;
+    // SAFETY: `raw` comes from a live `Easy` that outlives the transfer and
+    // `CURLINFO_PRETRANSFER_TIME` writes a single `double`.
+    let code = unsafe {
+        curl_sys::curl_easy_getinfo(raw, curl_sys::CURLINFO_PRETRANSFER_TIME, &mut seconds)
+    };
+    code == curl_sys::CURLE_OK && seconds > 0.0
+}
+
+// NOTE: The URL of the response being received, read the same way as
+// `pretransfer_reached` so it works from inside transfer callbacks.
+pub(crate) fn effective_url(raw: *mut curl_sys::CURL) -> Option<String> {
+    let mut url: *const std::os::raw::c_char = std::ptr::null();
+    // SAFETY: `raw` comes from a live `Easy` that outlives the transfer and
+    // `CURLINFO_EFFECTIVE_URL` writes a pointer to a string curl owns, which
+    // is copied out before the transfer moves on.
+    let code =
+        unsafe { curl_sys::curl_easy_getinfo(raw, curl_sys::CURLINFO_EFFECTIVE_URL, &mut url) };
+    if code != curl_sys::CURLE_OK || url.is_null() {
+        return None;
+    }
+    // SAFETY: Checked non-null above, curl hands out a NUL-terminated string.
+    let url = unsafe { std::ffi::CStr::from_ptr(url) };
+    Some(url.to_string_lossy().into_owned())
+}
@@ -0,0 +1,774 @@
+use std::collections::HashSet;
+
+use http::Method;
+
+use crate::{
+    content,
+    error::RelayError,
+    header,
+    interop::{
+        AuthType, BodyCapture, ByteRange, CertificateType, ContentType, ProxyAuthScheme, Request,
+        ValidationCode, ValidationIssue, ValidationSeverity,
+    },
+    multipart, query,
+    request::BodyPolicy,
+    trace,
+};
+
+const PROXY_SCHEMES: &[&str] = &["http", "https", "socks4", "socks4a", "socks5", "socks5h"];
+
+struct Issues(Vec<ValidationIssue>);
+
+impl Issues {
+    fn push(
+        &mut self,
+        code: ValidationCode,
+        severity: ValidationSeverity,
+        field: impl Into<String>,
+        message: impl Into<String>,
+    ) {
+        self.0.push(ValidationIssue {
+            code,
+            severity,
+            field: field.into(),
+            message: message.into(),
+        });
+    }
+
+    fn error(
+        &mut self,
+        code: ValidationCode,
+        field: impl Into<String>,
+        message: impl Into<String>,
+    ) {
+        self.push(code, ValidationSeverity::Error, field, message);
+    }
+
+    fn warning(
+        &mut self,
+        code: ValidationCode,
+        field: impl Into<String>,
+        message: impl Into<String>,
+    ) {
+        self.push(code, ValidationSeverity::Warning, field, message);
+    }
+}
+
+impl Request {
+    // NOTE: Returns every issue found, including warnings, so UIs can annotate
+    // fields inline. Only `validate` decides whether the request can be sent.
+    pub fn validation_issues(&self) -> Vec<ValidationIssue> {
+        let mut issues = Issues(Vec::new());
+
+        check_url(self, &mut issues);
+        check_headers(self, &mut issues);
+        check_content(self, &mut issues);
+        check_auth(self, &mut issues);
+        check_certificates(self, &mut issues);
+        check_proxy(self, &mut issues);
+        check_resolver(self, &mut issues);
+        check_range(self, &mut issues);
+        check_trace_context(self, &mut issues);
+        check_body_hash(self, &mut issues);
+
+        issues.0
+    }
+
+    pub fn validate(&self) -> std::result::Result<(), Vec<ValidationIssue>> {
+        let issues = self.validation_issues();
+
+        if issues
+            .iter()
+            .any(|issue| issue.severity == ValidationSeverity::Error)
+        {
+            Err(issues)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[tracing::instrument(skip(request), fields(request_id = request.id), level = "debug")]
+pub(crate) fn ensure_valid(request: &Request) -> crate::error::Result<()> {
+    let issues = request.validation_issues();
+
+    for issue in &issues {
+        match issue.severity {
+            ValidationSeverity::Error => {
+                tracing::error!(code = ?issue.code, field = %issue.field, "{}", issue.message)
+            }
+            ValidationSeverity::Warning => {
+                tracing::warn!(code = ?issue.code, field = %issue.field, "{}", issue.message)
+            }
+        }
+    }
+
+    let errors: Vec<&ValidationIssue> = issues
+        .iter()
+        .filter(|issue| issue.severity == ValidationSeverity::Error)
+        .collect();
+
+    match errors.first() {
+        None => Ok(()),
+        Some(first) => Err(RelayError::Validation {
+            message: match errors.len() {
+                1 => format!("{}: {}", first.field, first.message),
+                n => format!("{}: {} (and {} more)", first.field, first.message, n - 1),
+            },
+            issues,
+        }),
+    }
+}
+
+fn check_url(request: &Request, issues: &mut Issues) {
+    let (url, _) = query::split_zone(&request.url);
+    match url::Url::parse(&query::with_default_scheme(&url)) {
+        Ok(url) if !matches!(url.scheme(), "http" | "https") => issues.error(
+            ValidationCode::UnsupportedScheme,
+            "url",
+            format!("Unsupported URL scheme '{}'", url.scheme()),
+        ),
+        Ok(url) if !matches!(url.host_str(), Some(host) if !host.is_empty()) => {
+            issues.error(ValidationCode::InvalidUrl, "url", "URL has no host")
+        }
+        Ok(_) => {}
+        Err(e) => issues.error(
+            ValidationCode::InvalidUrl,
+            "url",
+            format!("Invalid URL: {}", e),
+        ),
+    }
+}
+
+fn check_headers(request: &Request, issues: &mut Issues) {
+    let Some(ref headers) = request.headers else {
+        return;
+    };
+
+    let sanitize = request
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.options.as_ref())
+        .and_then(|options| options.sanitize_headers)
+        .unwrap_or(false);
+    let headers = if sanitize {
+        header::sanitize(headers.clone())
+    } else {
+        headers.clone()
+    };
+
+    for name in headers.keys().chain(headers.suppressed()) {
+        if let Err(reason) = header::check_name(name) {
+            issues.error(
+                ValidationCode::InvalidHeaderName,
+                format!("headers.{}", name),
+                format!("Header {}", reason),
+            );
+        }
+    }
+
+    for (name, value) in &headers {
+        let field = format!("headers.{}", name);
+
+        if let Err(reason) = header::check_value(value) {
+            issues.error(
+                ValidationCode::InvalidHeaderValue,
+                field,
+                format!("Header {}", reason),
+            );
+        } else if header::is_non_ascii(value) {
+            issues.warning(
+                ValidationCode::NonAsciiHeaderValue,
+                field,
+                "Header value contains non-ASCII characters, servers may read them as Latin-1, \
+                 consider `RequestBuilder::header_encoded`",
+            );
+        }
+    }
+
+    let find = |target: &str| {
+        headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(target))
+    };
+
+    if let (Some((encoding_name, encoding)), Some((length_name, _))) =
+        (find("transfer-encoding"), find("content-length"))
+    {
+        if encoding.to_ascii_lowercase().contains("chunked") {
+            issues.error(
+                ValidationCode::ConflictingHeaders,
+                format!("headers.{}", length_name),
+                format!(
+                    "Content-Length must not be sent together with '{}: {}'",
+                    encoding_name, encoding
+                ),
+            );
+        }
+    }
+}
+
+fn check_content(request: &Request, issues: &mut Issues) {
+    let Some(ref content) = request.content else {
+        return;
+    };
+
+    match BodyPolicy::for_method(&request.method) {
+        BodyPolicy::Attach => {}
+        BodyPolicy::Discouraged => issues.warning(
+            ValidationCode::BodyDiscouraged,
+            "content",
+            format!(
+                "Content on a {} request has no defined semantics and may be ignored",
+                request.method
+            ),
+        ),
+        BodyPolicy::Dropped => issues.warning(
+            ValidationCode::BodyNotAllowed,
+            "content",
+            format!(
+                "{} requests cannot carry content, it is not sent",
+                request.method
+            ),
+        ),
+    }
+
+    if let ContentType::JsonPatch { operations } = content {
+        if let Err(RelayError::Parse { message, .. }) = content::validate_json_patch(operations) {
+            issues.error(
+                ValidationCode::InvalidContent,
+                "content.operations",
+                message,
+            );
+        }
+    }
+
+    if let ContentType::MultipartRelated { root, parts, .. } = content {
+        let mut seen = HashSet::new();
+        for (index, part) in std::iter::once(root).chain(parts).enumerate() {
+            let path = match index {
+                0 => "content.root".to_string(),
+                index => format!("content.parts[{}]", index - 1),
+            };
+            let content_id = multipart::bare_content_id(&part.content_id);
+            if content_id.is_empty() {
+                issues.error(
+                    ValidationCode::InvalidContent,
+                    format!("{}.contentId", path),
+                    "Content-ID is required",
+                );
+            } else if !seen.insert(content_id) {
+                issues.error(
+                    ValidationCode::InvalidContent,
+                    format!("{}.contentId", path),
+                    format!("Content-ID {} is used by more than one part", content_id),
+                );
+            }
+            for (name, value) in &part.headers {
+                if let Err(reason) = header::check_value(value) {
+                    issues.error(
+                        ValidationCode::InvalidContent,
+                        format!("{}.headers.{}", path, name),
+                        reason,
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn check_auth(request: &Request, issues: &mut Issues) {
+    let Some(ref auth) = request.auth else {
+        return;
+    };
+
+    match auth {
+        AuthType::Basic { username, .. } | AuthType::Digest { username, .. }
+            if username.is_empty() =>
+        {
+            issues.error(
+                ValidationCode::IncompleteAuth,
+                "auth.username",
+                "Username is required",
+            )
+        }
+        AuthType::Bearer { token } if token.is_empty() => issues.error(
+            ValidationCode::IncompleteAuth,
+            "auth.token",
+            "Token is required",
+        ),
+        AuthType::ApiKey { key, .. } if key.is_empty() => issues.error(
+            ValidationCode::IncompleteAuth,
+            "auth.key",
+            "Key name is required",
+        ),
+        _ => {}
+    }
+}
+
+fn has_pem_marker(data: &[u8]) -> bool {
+    data.windows(b"-----BEGIN ".len())
+        .any(|window| window == b"-----BEGIN ")
+}
+
+fn check_certificates(request: &Request, issues: &mut Issues) {
+    let Some(certificates) = request
+        .security
+        .as_ref()
+        .and_then(|security| security.certificates.as_ref())
+    else {
+        return;
+    };
+
+    match certificates.client {
+        Some(CertificateType::Pem { ref cert, ref key }) => {
+            if !has_pem_marker(cert) {
+                issues.error(
+                    ValidationCode::InvalidCertificate,
+                    "security.certificates.client.cert",
+                    "Client certificate is not PEM encoded, no BEGIN marker found",
+                );
+            }
+            if !has_pem_marker(key) {
+                issues.error(
+                    ValidationCode::InvalidCertificate,
+                    "security.certificates.client.key",
+                    "Client key is not PEM encoded, no BEGIN marker found",
+                );
+            }
+        }
+        Some(CertificateType::Pfx { ref data, .. }) if data.is_empty() => issues.error(
+            ValidationCode::InvalidCertificate,
+            "security.certificates.client.data",
+            "Client PFX bundle is empty",
+        ),
+        _ => {}
+    }
+
+    for (index, ca) in certificates.ca.iter().flatten().enumerate() {
+        if !has_pem_marker(ca) {
+            issues.error(
+                ValidationCode::InvalidCertificate,
+                format!("security.certificates.ca.{}", index),
+                "CA certificate is not PEM encoded, no BEGIN marker found",
+            );
+        }
+    }
+}
+
+fn check_proxy(request: &Request, issues: &mut Issues) {
+    let Some(ref proxy) = request.proxy else {
+        return;
+    };
+    if proxy.url.is_empty() {
+        return;
+    }
+
+    if let Some(ref auth) = proxy.auth {
+        if auth.scheme == Some(ProxyAuthScheme::Bearer)
+            && matches!(auth.token.as_deref(), None | Some(""))
+        {
+            issues.error(
+                ValidationCode::IncompleteAuth,
+                "proxy.auth.token",
+                "Token is required",
+            );
+        }
+    }
+
+    // NOTE: Like curl, a proxy without a scheme is an HTTP proxy.
+    match url::Url::parse(&query::with_default_scheme(&proxy.url)) {
+        Ok(url) if !PROXY_SCHEMES.contains(&url.scheme()) => issues.error(
+            ValidationCode::InvalidProxy,
+            "proxy.url",
+            format!("Unsupported proxy scheme '{}'", url.scheme()),
+        ),
+        Ok(_) => {}
+        Err(e) => issues.error(
+            ValidationCode::InvalidProxy,
+            "proxy.url",
+            format!("Invalid proxy URL: {}", e),
+        ),
+    }
+}
+
+// NOTE: DNS servers are IP addresses with an optional port, IPv6 ones in
+// brackets when a port follows, as curl's `CURLOPT_DNS_SERVERS` expects.
+fn is_dns_server(server: &str) -> bool {
+    server.parse::<std::net::IpAddr>().is_ok() || server.parse::<std::net::SocketAddr>().is_ok()
+}
+
+fn check_resolver(request: &Request, issues: &mut Issues) {
+    let Some(options) = request.meta.as_ref().and_then(|meta| meta.options.as_ref()) else {
+        return;
+    };
+
+    if let Some(ref servers) = options.dns_servers {
+        if servers.is_empty() {
+            issues.error(
+                ValidationCode::InvalidResolver,
+                "meta.options.dnsServers",
+                "At least one DNS server is required",
+            );
+        }
+        for server in servers.iter().filter(|server| !is_dns_server(server)) {
+            issues.error(
+                ValidationCode::InvalidResolver,
+                "meta.options.dnsServers",
+                format!("Invalid DNS server '{}'", server),
+            );
+        }
+    }
+
+    if let Some(ref interface) = options.dns_interface {
+        if interface.trim().is_empty() {
+            issues.error(
+                ValidationCode::InvalidResolver,
+                "meta.options.dnsInterface",
+                "DNS interface must not be empty",
+            );
+        }
+    }
+    if let Some(ref ip) = options.dns_local_ip4 {
+        if ip.parse::<std::net::Ipv4Addr>().is_err() {
+            issues.error(
+                ValidationCode::InvalidResolver,
+                "meta.options.dnsLocalIp4",
+                format!("Invalid local IPv4 address for DNS '{}'", ip),
+            );
+        }
+    }
+    if let Some(ref ip) = options.dns_local_ip6 {
+        if ip.parse::<std::net::Ipv6Addr>().is_err() {
+            issues.error(
+                ValidationCode::InvalidResolver,
+                "meta.options.dnsLocalIp6",
+                format!("Invalid local IPv6 address for DNS '{}'", ip),
+            );
+        }
+    }
+
+    if let Some(ref doh_url) = options.doh_url {
+        match url::Url::parse(doh_url) {
+            // NOTE: Release builds of libcurl refuse plain HTTP for DoH.
+            Ok(url) if url.scheme() != "https" => issues.error(
+                ValidationCode::InvalidResolver,
+                "meta.options.dohUrl",
+                format!("Unsupported DNS-over-HTTPS scheme '{}'", url.scheme()),
+            ),
+            Ok(_) => {}
+            Err(e) => issues.error(
+                ValidationCode::InvalidResolver,
+                "meta.options.dohUrl",
+                format!("Invalid DNS-over-HTTPS URL: {}", e),
+            ),
+        }
+    }
+}
+
+fn check_range_spec(range: &ByteRange, issues: &mut Issues) {
+    match *range {
+        ByteRange::Bounded { start, end } if end < start => issues.error(
+            ValidationCode::InvalidRange,
+            "range",
+            format!("Range end {} is before its start {}", end, start),
+        ),
+        ByteRange::Suffix { length: 0 } => issues.error(
+            ValidationCode::InvalidRange,
+            "range",
+            "A suffix range must cover at least one byte",
+        ),
+        ByteRange::Multiple { ref ranges } if ranges.is_empty() => issues.error(
+            ValidationCode::InvalidRange,
+            "range",
+            "At least one range is required",
+        ),
+        ByteRange::Multiple { ref ranges } => {
+            ranges
+                .iter()
+                .for_each(|range| check_range_spec(range, issues));
+        }
+        _ => {}
+    }
+}
+
+fn check_range(request: &Request, issues: &mut Issues) {
+    let Some(ref range) = request.range else {
+        return;
+    };
+
+    check_range_spec(range, issues);
+
+    // NOTE: RFC 9110 only defines `Range` for `GET`, servers ignore it on
+    // anything else.
+    if request.method != Method::GET {
+        issues.warning(
+            ValidationCode::InvalidRange,
+            "range",
+            format!("Servers ignore ranges on {} requests", request.method),
+        );
+    }
+    if request
+        .headers
+        .as_ref()
+        .is_some_and(|headers| headers.contains_key("range"))
+    {
+        issues.warning(
+            ValidationCode::ConflictingHeaders,
+            "headers.Range",
+            "The Range header overrides `range`",
+        );
+    }
+}
+
+fn check_trace_context(request: &Request, issues: &mut Issues) {
+    let Some(ref context) = request.trace_context else {
+        return;
+    };
+
+    if !trace::is_valid_id(&context.trace_id, 32) {
+        issues.error(
+            ValidationCode::InvalidTraceContext,
+            "traceContext.traceId",
+            "Trace id must be 32 lowercase hex digits and not all zeros",
+        );
+    }
+    if !trace::is_valid_id(&context.span_id, 16) {
+        issues.error(
+            ValidationCode::InvalidTraceContext,
+            "traceContext.spanId",
+            "Span id must be 16 lowercase hex digits and not all zeros",
+        );
+    }
+    if let Some(ref state) = context.trace_state {
+        if let Err(reason) = header::check_value(state) {
+            issues.error(
+                ValidationCode::InvalidTraceContext,
+                "traceContext.traceState",
+                format!("Invalid tracestate: {}", reason),
+            );
+        }
+    }
+}
+
+// NOTE: A transfer aborted at the capture limit only hashes part of the body,
+// an expected hash could never be checked and the response would pass
+// unverified.
+fn check_body_hash(request: &Request, issues: &mut Issues) {
+    let Some(options) = request.meta.as_ref().and_then(|meta| meta.options.as_ref()) else {
+        return;
+    };
+
+    let expects_hash = options
+        .body_hash
+        .as_ref()
+        .is_some_and(|hash| hash.expected.is_some());
+    if expects_hash
+        && matches!(
+            options.body_capture,
+            Some(BodyCapture::Truncate { abort: true, .. })
+        )
+    {
+        issues.error(
+            ValidationCode::ConflictingOptions,
+            "meta.options.bodyCapture",
+            "An expected body hash cannot be verified when the transfer aborts at the capture limit",
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{builder::SecurityConfigBuilder, interop::CertificateType};
+
+    const PEM: &str = "-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\n";
+
+    fn request() -> Request {
+        Request::builder()
+            .get("https://example.com/")
+            .build()
+            .unwrap()
+    }
+
+    // NOTE: Set after `build`, which already refuses invalid headers.
+    fn with_header(name: &str, value: &str) -> Request {
+        let mut request = request();
+        request
+            .headers
+            .get_or_insert_with(Default::default)
+            .insert(name, value);
+        request
+    }
+
+    fn issues(request: &Request) -> Vec<(ValidationCode, ValidationSeverity, String)> {
+        request
+            .validation_issues()
+            .into_iter()
+            .map(|issue| (issue.code, issue.severity, issue.field))
+            .collect()
+    }
+
+    fn error(code: ValidationCode, field: &str) -> (ValidationCode, ValidationSeverity, String) {
+        (code, ValidationSeverity::Error, field.to_string())
+    }
+
+    #[test]
+    fn a_valid_request_has_no_issues() {
+        assert!(request().validation_issues().is_empty());
+        assert!(request().validate().is_ok());
+        assert!(ensure_valid(&request()).is_ok());
+    }
+
+    #[test]
+    fn rejects_cr_and_lf_in_header_values() {
+        for value in ["a\r\nInjected: yes", "a\nb", "a\rb"] {
+            assert_eq!(
+                issues(&with_header("X-Custom", value)),
+                [error(
+                    ValidationCode::InvalidHeaderValue,
+                    "headers.X-Custom"
+                )],
+                "{:?}",
+                value
+            );
+        }
+
+        let message = with_header("X-Custom", "a\r\nb").validation_issues()[0]
+            .message
+            .clone();
+        assert_eq!(message, "Header value contains CR at byte 1");
+    }
+
+    #[test]
+    fn rejects_cr_and_lf_in_header_names() {
+        assert_eq!(
+            issues(&with_header("X-Custom\r\nInjected", "a")),
+            [error(
+                ValidationCode::InvalidHeaderName,
+                "headers.X-Custom\r\nInjected"
+            )]
+        );
+    }
+
+    #[test]
+    fn warns_about_non_ascii_header_values() {
+        assert_eq!(
+            issues(&with_header("X-Name", "caf\u{e9}")),
+            [(
+                ValidationCode::NonAsciiHeaderValue,
+                ValidationSeverity::Warning,
+                "headers.X-Name".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn rejects_chunked_with_content_length() {
+        let request = Request::builder()
+            .post("https://example.com/")
+            .header("Transfer-Encoding", "gzip, chunked")
+            .header("Content-Length", "10")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            issues(&request),
+            [error(
+                ValidationCode::ConflictingHeaders,
+                "headers.Content-Length"
+            )]
+        );
+    }
+
+    #[test]
+    fn requires_a_username_for_basic_and_digest() {
+        let digest = Request::builder()
+            .get("https://example.com/")
+            .digest_auth("", "secret")
+            .build()
+            .unwrap();
+        let basic = Request::builder()
+            .get("https://example.com/")
+            .basic_auth("", "secret")
+            .build()
+            .unwrap();
+
+        for request in [digest, basic] {
+            assert_eq!(
+                issues(&request),
+                [error(ValidationCode::IncompleteAuth, "auth.username")]
+            );
+        }
+    }
+
+    #[test]
+    fn requires_pem_markers() {
+        let mut request = request();
+        request.security = Some(
+            SecurityConfigBuilder::new()
+                .with_client_cert(CertificateType::Pem {
+                    cert: Bytes::from_static(b"MIIB without markers"),
+                    key: Bytes::from_static(PEM.as_bytes()),
+                })
+                .with_ca(PEM)
+                .with_ca("not a certificate")
+                .build(),
+        );
+
+        assert_eq!(
+            issues(&request),
+            [
+                error(
+                    ValidationCode::InvalidCertificate,
+                    "security.certificates.client.cert"
+                ),
+                error(
+                    ValidationCode::InvalidCertificate,
+                    "security.certificates.ca.1"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn serializes_issues_with_their_field_paths() {
+        let mut request = with_header("X-Custom", "a\nb");
+        request.auth = Some(AuthType::Bearer {
+            token: String::new(),
+        });
+
+        let Err(found) = request.validate() else {
+            panic!("expected validation to fail");
+        };
+        assert_eq!(
+            serde_json::to_value(&found).unwrap(),
+            json!([
+                {
+                    "code": "invalid_header_value",
+                    "severity": "error",
+                    "field": "headers.X-Custom",
+                    "message": "Header value contains LF at byte 1",
+                },
+                {
+                    "code": "incomplete_auth",
+                    "severity": "error",
+                    "field": "auth.token",
+                    "message": "Token is required",
+                },
+            ])
+        );
+
+        let Err(RelayError::Validation { message, issues }) = ensure_valid(&request) else {
+            panic!("expected a validation error");
+        };
+        assert_eq!(
+            message,
+            "headers.X-Custom: Header value contains LF at byte 1 (and 1 more)"
+        );
+        assert_eq!(issues, found);
+    }
+}
@@ -0,0 +1,579 @@
+use std::{
+    collections::HashMap,
+    io,
+    mem::{ManuallyDrop, MaybeUninit},
+    os::raw::{c_int, c_long, c_uint, c_void},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use curl::easy::Easy;
+use http::{Method, StatusCode};
+use socket2::Socket;
+
+use crate::{
+    error::{RelayError, Result},
+    interop::Request,
+    request::CurlRequest,
+    share::{self, Share},
+    target_policy::{self, Guard},
+    transfer::{self, DebugSignals},
+    util, validate,
+};
+
+// NOTE: `curl_sys` has no bindings for the WebSocket API yet, these match
+// `curl/websockets.h` from libcurl 7.86 on.
+#[repr(C)]
+#[allow(dead_code)]
+struct WsFrame {
+    age: c_int,
+    flags: c_int,
+    offset: curl_sys::curl_off_t,
+    bytesleft: curl_sys::curl_off_t,
+    len: usize,
+}
+
+extern "C" {
+    fn curl_ws_recv(
+        curl: *mut curl_sys::CURL,
+        buffer: *mut c_void,
+        buflen: usize,
+        recv: *mut usize,
+        meta: *mut *const WsFrame,
+    ) -> curl_sys::CURLcode;
+
+    fn curl_ws_send(
+        curl: *mut curl_sys::CURL,
+        buffer: *const c_void,
+        buflen: usize,
+        sent: *mut usize,
+        fragsize: curl_sys::curl_off_t,
+        flags: c_uint,
+    ) -> curl_sys::CURLcode;
+}
+
+const CURLWS_TEXT: c_int = 1 << 0;
+const CURLWS_BINARY: c_int = 1 << 1;
+const CURLWS_CONT: c_int = 1 << 2;
+const CURLWS_CLOSE: c_int = 1 << 3;
+const CURLWS_PING: c_int = 1 << 4;
+const CURLWS_PONG: c_int = 1 << 6;
+
+// NOTE: `CURLOPT_CONNECT_ONLY` set to 2 performs the upgrade request and
+// hands the connection over once the `101` is in.
+const CONNECT_ONLY_WEBSOCKET: c_long = 2;
+
+// NOTE: Pings are answered in `complete` instead, so they can still be
+// handed to the caller. The flag is new in libcurl 8.14, older releases
+// always answer pings themselves.
+const CURLOPT_WS_OPTIONS: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_LONG + 320;
+const CURLWS_NOAUTOPONG: c_long = 1 << 1;
+
+// NOTE: `CURLINFO_SOCKET + 44`, the socket a connect only transfer leaves
+// open.
+const CURLINFO_ACTIVESOCKET: curl_sys::CURLINFO = 0x500000 + 44;
+
+const RECV_CHUNK_BYTES: usize = 16 * 1024;
+
+// NOTE: Messages are buffered whole before they are returned, a peer sending
+// more is cut off with close code 1009.
+pub const DEFAULT_MAX_MESSAGE_BYTES: usize = 16 * 1024 * 1024;
+const CLOSE_MESSAGE_TOO_BIG: u16 = 1009;
+
+// NOTE: Only for a full send buffer, which has no portable wait. Receiving
+// waits on the socket instead.
+const SEND_RETRY_INTERVAL: Duration = Duration::from_millis(5);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WsMessage {
+    Text(String),
+    Binary(Bytes),
+    // NOTE: Already answered with a matching pong when returned.
+    Ping(Bytes),
+    Pong(Bytes),
+    // NOTE: `code` is absent when the peer sent an empty close frame.
+    Close { code: Option<u16>, reason: String },
+}
+
+// NOTE: An upgraded connection, reads and writes go straight to the socket
+// from the calling thread. Not `Sync`, a connection has one reader.
+pub struct WsConnection {
+    handle: Easy,
+    // NOTE: Declared after `handle` so the handle is dropped first.
+    _share: Option<Arc<Share>>,
+    _policy: Option<Arc<Guard>>,
+    url: String,
+    headers: HashMap<String, String>,
+    frame: Vec<u8>,
+    message: Vec<u8>,
+    max_message_bytes: usize,
+    // NOTE: Whether libcurl answers pings itself, see `CURLWS_NOAUTOPONG`.
+    auto_pong: bool,
+    closed: bool,
+}
+
+impl std::fmt::Debug for WsConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WsConnection")
+            .field("url", &self.url)
+            .field("headers", &self.headers)
+            .field("closed", &self.closed)
+            .finish_non_exhaustive()
+    }
+}
+
+// NOTE: `ws` and `wss` are checked as `http` and `https`, everything else
+// about the request is validated as for a plain transfer.
+fn http_url(url: &str) -> String {
+    match url.split_once("://") {
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case("ws") => format!("http://{}", rest),
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case("wss") => format!("https://{}", rest),
+        _ => url.to_string(),
+    }
+}
+
+fn websocket_url(url: &str) -> String {
+    match url.split_once("://") {
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case("http") => format!("ws://{}", rest),
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case("https") => format!("wss://{}", rest),
+        _ => url.to_string(),
+    }
+}
+
+fn unsupported(message: impl Into<String>) -> RelayError {
+    RelayError::UnsupportedFeature {
+        feature: "WebSocket".into(),
+        message: message.into(),
+        relay: "curl".into(),
+    }
+}
+
+fn network(message: &str, e: curl::Error) -> RelayError {
+    tracing::error!(error = %e, "{}", message);
+    RelayError::Network {
+        message: message.into(),
+        cause: Some(e.into()),
+    }
+}
+
+fn set_long(
+    handle: &mut Easy,
+    option: curl_sys::CURLoption,
+    value: c_long,
+    what: &str,
+) -> Result<()> {
+    // SAFETY: Only called with options libcurl documents as taking a long.
+    let code = unsafe { curl_sys::curl_easy_setopt(handle.raw(), option, value) };
+    if code != curl_sys::CURLE_OK {
+        return Err(network(
+            &format!("Failed to {}", what),
+            curl::Error::new(code),
+        ));
+    }
+    Ok(())
+}
+
+// NOTE: Upgrades `request` with the same URL, header, auth, proxy and TLS
+// handling as a plain transfer. A server refusing the upgrade is reported as
+// `RelayError::HttpStatus` with its headers, libcurl does not read the body.
+pub fn connect(request: &Request) -> Result<WsConnection> {
+    connect_shared(request, None)
+}
+
+#[tracing::instrument(skip(request, share), fields(request_id = request.id), level = "debug")]
+pub(crate) fn connect_shared(request: &Request, share: Option<Arc<Share>>) -> Result<WsConnection> {
+    if request.method != Method::GET {
+        return Err(unsupported(format!(
+            "upgrades are sent as GET, not {}",
+            request.method
+        )));
+    }
+    if request.content.is_some() {
+        return Err(unsupported("upgrade requests cannot carry content"));
+    }
+
+    let mut checked = request.clone();
+    checked.url = http_url(&request.url);
+    validate::ensure_valid(&checked)?;
+
+    let mut upgrade = request.clone();
+    upgrade.url = websocket_url(&checked.url);
+    tracing::info!(url = %crate::redact::policy_for(request).text(&upgrade.url), "Opening WebSocket");
+
+    let policy = target_policy::guard(&upgrade);
+    let mut handle = Easy::new();
+    let dns_cache_hit = share::attach(&mut handle, &upgrade, share.as_deref());
+    CurlRequest::new(&mut handle, &upgrade).prepare()?;
+    if let Some(ref policy) = policy {
+        policy.attach(&mut handle)?;
+    }
+
+    let signals = Arc::new(DebugSignals::default());
+    let observed = Arc::clone(&signals);
+    handle
+        .verbose(true)
+        .and_then(|_| {
+            handle.debug_function(move |info_type, data| observed.observe(&info_type, data))
+        })
+        .map_err(|e| network("Failed to set debug function", e))?;
+
+    let headers = Arc::new(Mutex::new(HashMap::new()));
+    let received = Arc::clone(&headers);
+    handle
+        .header_function(move |line| {
+            let mut headers = received
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            let line = String::from_utf8_lossy(line);
+            if line.starts_with("HTTP/") {
+                headers.clear();
+            } else if let Some((key, value)) = line.split_once(':') {
+                transfer::fold_header(&mut headers, key.trim(), value.trim());
+            }
+            true
+        })
+        .map_err(|e| network("Failed to set header callback", e))?;
+
+    set_long(
+        &mut handle,
+        curl_sys::CURLOPT_CONNECT_ONLY,
+        CONNECT_ONLY_WEBSOCKET,
+        "enable connect only mode",
+    )?;
+    let auto_pong = !util::libcurl_at_least(8, 14);
+    if !auto_pong {
+        set_long(
+            &mut handle,
+            CURLOPT_WS_OPTIONS,
+            CURLWS_NOAUTOPONG,
+            "disable automatic pongs",
+        )?;
+    }
+
+    let performed = handle.perform();
+    let status = handle.response_code().unwrap_or_default();
+    let headers = std::mem::take(
+        &mut *headers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()),
+    );
+
+    if let Some(violation) = policy.as_ref().and_then(|policy| policy.take_violation()) {
+        return Err(violation);
+    }
+
+    if status != 0 && status != 101 {
+        tracing::warn!(status, "Server refused the WebSocket upgrade");
+        return Err(RelayError::HttpStatus {
+            status: u16::try_from(status)
+                .ok()
+                .and_then(|code| StatusCode::from_u16(code).ok())
+                .unwrap_or(StatusCode::BAD_GATEWAY),
+            headers,
+            body: Bytes::new(),
+        });
+    }
+
+    if let Err(e) = performed {
+        // NOTE: libcurl built without WebSocket support knows no `ws` scheme.
+        if e.is_unsupported_protocol() && status == 0 {
+            return Err(unsupported("libcurl was built without WebSocket support"));
+        }
+        return Err(transfer::perform_error(&mut handle, &e, None, &signals));
+    }
+    share::resolved(&upgrade, share.as_deref(), dns_cache_hit);
+
+    tracing::info!(headers = ?crate::redact::policy_for(request).headers(&headers), "WebSocket open");
+
+    Ok(WsConnection {
+        handle,
+        _share: share,
+        _policy: policy,
+        url: upgrade.url,
+        headers,
+        frame: Vec::new(),
+        message: Vec::new(),
+        max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+        auto_pong,
+        closed: false,
+    })
+}
+
+impl WsConnection {
+    // NOTE: Replaces `DEFAULT_MAX_MESSAGE_BYTES`, counting a fragmented
+    // message as a whole.
+    pub fn with_max_message_bytes(mut self, max: usize) -> Self {
+        self.max_message_bytes = max;
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    // NOTE: Headers of the `101 Switching Protocols` response, e.g. the
+    // negotiated `Sec-WebSocket-Protocol`.
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    fn failed(&mut self, what: &str, code: curl_sys::CURLcode) -> RelayError {
+        let e = curl::Error::new(code);
+        tracing::error!(error = %e, "Failed to {} WebSocket frame", what);
+        match transfer::perform_error(&mut self.handle, &e, None, &DebugSignals::default()) {
+            RelayError::Network { cause, .. } => RelayError::Network {
+                message: format!("Failed to {} WebSocket frame", what),
+                cause,
+            },
+            error => error,
+        }
+    }
+
+    fn ensure_open(&self) -> Result<()> {
+        if self.closed {
+            return Err(RelayError::Network {
+                message: "WebSocket connection is closed".into(),
+                cause: None,
+            });
+        }
+        Ok(())
+    }
+
+    // NOTE: libcurl takes a partially sent frame back with the rest of the
+    // payload, so the loop resumes from whatever was accepted so far.
+    fn send_frame(&mut self, payload: &[u8], flags: c_int) -> Result<()> {
+        self.ensure_open()?;
+
+        let mut offset = 0;
+        loop {
+            let rest = &payload[offset..];
+            let mut sent = 0;
+            // SAFETY: `rest` is valid for its length and the handle completed
+            // a connect only WebSocket upgrade.
+            let code = unsafe {
+                curl_ws_send(
+                    self.handle.raw(),
+                    rest.as_ptr().cast(),
+                    rest.len(),
+                    &mut sent,
+                    0,
+                    flags as c_uint,
+                )
+            };
+            offset += sent;
+
+            match code {
+                curl_sys::CURLE_OK if offset >= payload.len() => return Ok(()),
+                curl_sys::CURLE_OK => {}
+                curl_sys::CURLE_AGAIN => std::thread::sleep(SEND_RETRY_INTERVAL),
+                code => return Err(self.failed("send", code)),
+            }
+        }
+    }
+
+    pub fn send_text(&mut self, text: &str) -> Result<()> {
+        tracing::trace!(len = text.len(), "Sending WebSocket text");
+        self.send_frame(text.as_bytes(), CURLWS_TEXT)
+    }
+
+    pub fn send_binary(&mut self, data: &[u8]) -> Result<()> {
+        tracing::trace!(len = data.len(), "Sending WebSocket binary");
+        self.send_frame(data, CURLWS_BINARY)
+    }
+
+    // NOTE: Control frames carry at most 125 bytes, libcurl rejects more.
+    pub fn ping(&mut self, payload: &[u8]) -> Result<()> {
+        tracing::trace!(len = payload.len(), "Sending WebSocket ping");
+        self.send_frame(payload, CURLWS_PING)
+    }
+
+    pub fn close(&mut self, code: u16, reason: &str) -> Result<()> {
+        tracing::debug!(code, reason = %reason, "Closing WebSocket");
+        let mut payload = code.to_be_bytes().to_vec();
+        payload.extend_from_slice(reason.as_bytes());
+        self.send_frame(&payload, CURLWS_CLOSE)?;
+        self.closed = true;
+        Ok(())
+    }
+
+    // NOTE: Blocks until a whole message is in, fragments are reassembled
+    // and control frames arriving in between are returned as they come.
+    pub fn next_message(&mut self) -> Result<WsMessage> {
+        loop {
+            if let Some(message) = self.try_next_message()? {
+                return Ok(message);
+            }
+            self.wait_readable()?;
+        }
+    }
+
+    fn active_socket(&mut self) -> Result<curl_sys::curl_socket_t> {
+        let mut socket: curl_sys::curl_socket_t = curl_sys::CURL_SOCKET_BAD;
+        // SAFETY: `CURLINFO_ACTIVESOCKET` writes a single `curl_socket_t`.
+        let code = unsafe {
+            curl_sys::curl_easy_getinfo(self.handle.raw(), CURLINFO_ACTIVESOCKET, &mut socket)
+        };
+        if code != curl_sys::CURLE_OK || socket == curl_sys::CURL_SOCKET_BAD {
+            return Err(self.failed("receive", code));
+        }
+        Ok(socket)
+    }
+
+    // NOTE: Sleeps in the kernel until the peer sends something, or closes
+    // the connection, which the next receive then reports. curl keeps the
+    // socket non-blocking, it is made blocking only for the wait.
+    fn wait_readable(&mut self) -> Result<()> {
+        let raw = self.active_socket()?;
+        // SAFETY: The socket stays open and owned by the handle, wrapping it
+        // in `ManuallyDrop` keeps socket2 from closing it.
+        #[cfg(unix)]
+        let socket = ManuallyDrop::new(unsafe {
+            <Socket as std::os::unix::io::FromRawFd>::from_raw_fd(raw)
+        });
+        #[cfg(windows)]
+        let socket = ManuallyDrop::new(unsafe {
+            <Socket as std::os::windows::io::FromRawSocket>::from_raw_socket(raw as _)
+        });
+
+        let waited = socket.set_nonblocking(false).and_then(|_| {
+            let mut byte = [MaybeUninit::<u8>::uninit()];
+            loop {
+                match socket.peek(&mut byte) {
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    peeked => return peeked.map(drop),
+                }
+            }
+        });
+        let restored = socket.set_nonblocking(true);
+
+        waited.and(restored).map_err(|e| {
+            tracing::error!(error = %e, "Failed to wait for WebSocket data");
+            RelayError::Network {
+                message: "Failed to wait for WebSocket data".into(),
+                cause: Some(e.into()),
+            }
+        })
+    }
+
+    // NOTE: Reads whatever has arrived without waiting, returning `None`
+    // until a whole message is in. For driving the connection from an
+    // event loop.
+    pub fn try_next_message(&mut self) -> Result<Option<WsMessage>> {
+        self.ensure_open()?;
+
+        let mut chunk = vec![0; RECV_CHUNK_BYTES];
+        loop {
+            let mut received = 0;
+            let mut meta: *const WsFrame = std::ptr::null();
+            // SAFETY: `chunk` is valid for its length and the handle completed
+            // a connect only WebSocket upgrade.
+            let code = unsafe {
+                curl_ws_recv(
+                    self.handle.raw(),
+                    chunk.as_mut_ptr().cast(),
+                    chunk.len(),
+                    &mut received,
+                    &mut meta,
+                )
+            };
+
+            match code {
+                curl_sys::CURLE_OK => {}
+                curl_sys::CURLE_AGAIN => return Ok(None),
+                code => return Err(self.failed("receive", code)),
+            }
+
+            // SAFETY: libcurl points `meta` at frame details it owns, valid
+            // until the next call on the handle.
+            let Some(meta) = (unsafe { meta.as_ref() }) else {
+                return Err(RelayError::Network {
+                    message: "WebSocket frame arrived without metadata".into(),
+                    cause: None,
+                });
+            };
+
+            self.frame.extend_from_slice(&chunk[..received]);
+            if self.message.len() + self.frame.len() > self.max_message_bytes {
+                return Err(self.too_big());
+            }
+            if meta.bytesleft > 0 {
+                continue;
+            }
+
+            let payload = std::mem::take(&mut self.frame);
+            if let Some(message) = self.complete(meta.flags, payload)? {
+                return Ok(Some(message));
+            }
+        }
+    }
+
+    fn too_big(&mut self) -> RelayError {
+        let limit = self.max_message_bytes;
+        tracing::warn!(limit, "WebSocket message exceeds the size limit, closing");
+        self.frame.clear();
+        self.message.clear();
+        if let Err(e) = self.close(CLOSE_MESSAGE_TOO_BIG, "Message too big") {
+            tracing::debug!(error = %e, "Failed to close oversized WebSocket");
+        }
+        self.closed = true;
+        RelayError::Parse {
+            message: format!("WebSocket message exceeds {} bytes", limit),
+            cause: None,
+            partial: None,
+        }
+    }
+
+    fn complete(&mut self, flags: c_int, payload: Vec<u8>) -> Result<Option<WsMessage>> {
+        if flags & CURLWS_CLOSE != 0 {
+            // NOTE: Echoes the status code back as RFC 6455 asks, the peer
+            // is going away either way so a failure is only logged.
+            let echo = payload.get(..2).unwrap_or_default().to_vec();
+            if let Err(e) = self.send_frame(&echo, CURLWS_CLOSE) {
+                tracing::debug!(error = %e, "Failed to answer WebSocket close");
+            }
+            self.closed = true;
+            let (code, reason) = match payload.split_first_chunk::<2>() {
+                Some((code, reason)) => (
+                    Some(u16::from_be_bytes(*code)),
+                    String::from_utf8_lossy(reason).into_owned(),
+                ),
+                None => (None, String::new()),
+            };
+            tracing::debug!(code = ?code, reason = %reason, "WebSocket closed by peer");
+            return Ok(Some(WsMessage::Close { code, reason }));
+        }
+        if flags & CURLWS_PING != 0 {
+            if !self.auto_pong {
+                self.send_frame(&payload, CURLWS_PONG)?;
+            }
+            return Ok(Some(WsMessage::Ping(payload.into())));
+        }
+        if flags & CURLWS_PONG != 0 {
+            return Ok(Some(WsMessage::Pong(payload.into())));
+        }
+
+        self.message.extend_from_slice(&payload);
+        if flags & CURLWS_CONT != 0 {
+            tracing::trace!(buffered = self.message.len(), "Buffered WebSocket fragment");
+            return Ok(None);
+        }
+
+        let message = std::mem::take(&mut self.message);
+        if flags & CURLWS_TEXT != 0 {
+            String::from_utf8(message)
+                .map(|text| Some(WsMessage::Text(text)))
+                .map_err(|e| RelayError::Parse {
+                    message: "WebSocket text message is not valid UTF-8".into(),
+                    cause: Some(e.into()),
+                    partial: None,
+                })
+        } else {
+            Ok(Some(WsMessage::Binary(message.into())))
+        }
+    }
+}
@@ -0,0 +1,220 @@
+mod common;
+
+use common::{response, Server};
+use relay::{Assertion, AssertionResult, Request};
+use serde_json::{json, Value};
+
+const BODY: &str = r#"{"items":[{"id":7,"tags":["new"]}],"a.b":true,"empty":null}"#;
+
+fn server() -> Server {
+    Server::start(|_| {
+        response(
+            "201 Created",
+            &[
+                ("Content-Type", "application/json"),
+                ("X-Request-Id", "req-42"),
+            ],
+            BODY,
+        )
+    })
+}
+
+// NOTE: Runs every assertion against one request, a failed one must not
+// make the request fail.
+async fn verdicts(server: &Server, assertions: Vec<Assertion>) -> Vec<AssertionResult> {
+    let mut builder = Request::builder().get(server.url("/"));
+    for assertion in assertions {
+        builder = builder.assertion(assertion);
+    }
+    let response = relay::execute(builder.build().unwrap()).await.unwrap();
+    response.meta.assertions
+}
+
+fn passed(results: &[AssertionResult]) -> Vec<bool> {
+    results.iter().map(|result| result.passed).collect()
+}
+
+#[tokio::test]
+async fn status_assertions() {
+    let server = server();
+    let results = verdicts(
+        &server,
+        vec![
+            Assertion::StatusEquals { status: 201 },
+            Assertion::StatusEquals { status: 200 },
+            Assertion::StatusInRange { min: 200, max: 299 },
+            Assertion::StatusInRange { min: 400, max: 599 },
+        ],
+    )
+    .await;
+
+    assert_eq!(passed(&results), [true, false, true, false]);
+    assert!(results
+        .iter()
+        .all(|result| result.actual == Some(json!(201))));
+    assert_eq!(results[1].message, "Expected status 200, got 201");
+}
+
+#[tokio::test]
+async fn header_assertions() {
+    let server = server();
+    let results = verdicts(
+        &server,
+        vec![
+            Assertion::HeaderPresent {
+                name: "x-request-id".into(),
+            },
+            Assertion::HeaderPresent {
+                name: "X-Missing".into(),
+            },
+            Assertion::HeaderEquals {
+                name: "X-REQUEST-ID".into(),
+                value: "req-42".into(),
+            },
+            Assertion::HeaderEquals {
+                name: "X-Request-Id".into(),
+                value: "req-4".into(),
+            },
+            Assertion::HeaderMatches {
+                name: "Content-Type".into(),
+                pattern: "json".into(),
+            },
+            Assertion::HeaderMatches {
+                name: "Content-Type".into(),
+                pattern: "^text/".into(),
+            },
+            Assertion::HeaderMatches {
+                name: "Content-Type".into(),
+                pattern: "(".into(),
+            },
+        ],
+    )
+    .await;
+
+    assert_eq!(
+        passed(&results),
+        [true, false, true, false, true, false, false]
+    );
+    assert_eq!(results[0].actual, Some(json!("req-42")));
+    assert_eq!(results[1].actual, None);
+    assert!(results[6].message.starts_with("Invalid pattern /(/"));
+}
+
+#[tokio::test]
+async fn json_path_assertions() {
+    let server = server();
+    let results = verdicts(
+        &server,
+        vec![
+            Assertion::JsonPathEquals {
+                path: "$.items[0].id".into(),
+                value: json!(7),
+            },
+            Assertion::JsonPathEquals {
+                path: "items.0.tags".into(),
+                value: json!(["new"]),
+            },
+            Assertion::JsonPathEquals {
+                path: "$.items[0].id".into(),
+                value: json!("7"),
+            },
+            Assertion::JsonPathExists {
+                path: "$['a.b']".into(),
+            },
+            Assertion::JsonPathExists {
+                path: "$.empty".into(),
+            },
+            Assertion::JsonPathExists {
+                path: "$.items[1]".into(),
+            },
+            Assertion::JsonPathExists {
+                path: "$..items".into(),
+            },
+        ],
+    )
+    .await;
+
+    assert_eq!(
+        passed(&results),
+        [true, true, false, true, true, false, false]
+    );
+    assert_eq!(results[2].actual, Some(json!(7)));
+    assert_eq!(results[4].actual, Some(Value::Null));
+    assert_eq!(results[5].actual, None);
+    assert_eq!(results[6].message, "Invalid JSON path $..items");
+}
+
+#[tokio::test]
+async fn json_path_on_a_body_that_is_not_json() {
+    let server = Server::start(|_| response("200 OK", &[], "plain"));
+    let results = verdicts(
+        &server,
+        vec![Assertion::JsonPathExists {
+            path: "$.id".into(),
+        }],
+    )
+    .await;
+
+    assert_eq!(passed(&results), [false]);
+    assert_eq!(results[0].message, "Body is not JSON");
+}
+
+#[tokio::test]
+async fn body_assertions() {
+    let server = server();
+    let results = verdicts(
+        &server,
+        vec![
+            Assertion::BodyContains {
+                text: r#""tags":["new"]"#.into(),
+            },
+            Assertion::BodyContains { text: "old".into() },
+            Assertion::BodySizeUnder {
+                limit_bytes: BODY.len() as u64 + 1,
+            },
+            Assertion::BodySizeUnder {
+                limit_bytes: BODY.len() as u64,
+            },
+        ],
+    )
+    .await;
+
+    assert_eq!(passed(&results), [true, false, true, false]);
+    // NOTE: The body is never copied into a verdict.
+    assert_eq!(results[0].actual, None);
+    assert_eq!(results[3].actual, Some(json!(BODY.len())));
+}
+
+#[tokio::test]
+async fn response_time_assertions() {
+    let server = server();
+    let results = verdicts(
+        &server,
+        vec![
+            Assertion::ResponseTimeUnder { limit_ms: 60_000 },
+            Assertion::ResponseTimeUnder { limit_ms: 0 },
+        ],
+    )
+    .await;
+
+    assert_eq!(passed(&results), [true, false]);
+    assert!(results[0].actual.as_ref().is_some_and(Value::is_u64));
+}
+
+#[tokio::test]
+async fn verdicts_keep_the_order_and_the_assertion() {
+    let server = server();
+    let assertions = vec![
+        Assertion::StatusEquals { status: 500 },
+        Assertion::HeaderPresent {
+            name: "x-request-id".into(),
+        },
+    ];
+    let results = verdicts(&server, assertions.clone()).await;
+
+    let checked = results
+        .into_iter()
+        .map(|result| result.assertion)
+        .collect::<Vec<_>>();
+    assert_eq!(checked, assertions);
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use common::{ok, Server};
+use relay::{error::RelayError, BodyCapture, BodyHash, HashAlgorithm, Request, ValidationCode};
+
+const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+fn request(url: String, abort: bool) -> Request {
+    Request::builder()
+        .get(url)
+        .body_hash(BodyHash::new(HashAlgorithm::Sha256).expect(HELLO_SHA256))
+        .body_capture(BodyCapture::Truncate {
+            max_bytes: 2,
+            abort,
+        })
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn expected_hash_with_aborting_capture_is_refused() {
+    let server = Server::start(|_| ok("hello"));
+
+    let issues = request(server.url("/"), true).validate().unwrap_err();
+    assert_eq!(issues[0].code, ValidationCode::ConflictingOptions);
+
+    let error = relay::execute(request(server.url("/"), true))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RelayError::Validation { .. }));
+    assert!(server.requests().is_empty());
+}
+
+#[tokio::test]
+async fn expected_hash_with_truncating_capture_verifies_the_whole_body() {
+    let server = Server::start(|_| ok("hello"));
+
+    let response = relay::execute(request(server.url("/"), false))
+        .await
+        .unwrap();
+    assert_eq!(&response.body.body[..], b"he");
+    assert_eq!(response.body_hash.as_deref(), Some(HELLO_SHA256));
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use common::{ok, Server};
+use relay::{error::RelayError, BodyHash, HashAlgorithm, Request};
+
+const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+fn request(url: String, hash: BodyHash) -> Request {
+    Request::builder().get(url).body_hash(hash).build().unwrap()
+}
+
+#[tokio::test]
+async fn reports_the_hash_of_the_body() {
+    let server = Server::start(|_| ok("hello"));
+
+    let unchecked = relay::execute(request(
+        server.url("/"),
+        BodyHash::new(HashAlgorithm::Sha256),
+    ))
+    .await
+    .unwrap();
+    assert_eq!(unchecked.body_hash.as_deref(), Some(HELLO_SHA256));
+
+    let checked = relay::execute(request(
+        server.url("/"),
+        BodyHash::new(HashAlgorithm::Sha256)
+            .expect("sha256-LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ="),
+    ))
+    .await
+    .unwrap();
+    assert_eq!(checked.body_hash.as_deref(), Some(HELLO_SHA256));
+}
+
+#[tokio::test]
+async fn a_body_that_does_not_match_is_an_error() {
+    let server = Server::start(|_| ok("hello, tampered"));
+
+    let error = relay::execute(request(
+        server.url("/"),
+        BodyHash::new(HashAlgorithm::Sha256).expect(HELLO_SHA256),
+    ))
+    .await
+    .unwrap_err();
+
+    let RelayError::BodyHashMismatch {
+        algorithm,
+        expected,
+        actual,
+    } = error
+    else {
+        panic!("expected a body hash mismatch, got {:?}", error);
+    };
+    assert_eq!(algorithm, HashAlgorithm::Sha256);
+    assert_eq!(expected, HELLO_SHA256);
+    assert_ne!(actual, HELLO_SHA256);
+    assert_eq!(actual.len(), 64);
+}
@@ -0,0 +1,283 @@
+mod common;
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use common::{response, Server};
+use relay::{CacheStatus, Clock, HttpCache, RelayClient, Request, RequestBuilder, Response};
+
+// NOTE: The system clock, moved forward on demand so freshness can expire
+// without waiting for it.
+#[derive(Debug, Clone, Default)]
+struct Skipping(Arc<Mutex<Duration>>);
+
+impl Skipping {
+    fn skip(&self, duration: Duration) {
+        *self.0.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for Skipping {
+    fn now(&self) -> SystemTime {
+        SystemTime::now() + *self.0.lock().unwrap()
+    }
+
+    fn monotonic(&self) -> Instant {
+        Instant::now() + *self.0.lock().unwrap()
+    }
+}
+
+async fn send(client: &RelayClient, request: RequestBuilder) -> Response {
+    client.execute(request.build().unwrap()).await.unwrap()
+}
+
+async fn get(client: &RelayClient, url: String) -> Response {
+    send(client, Request::builder().get(url)).await
+}
+
+fn body(response: &Response) -> String {
+    String::from_utf8_lossy(&response.body.body).into_owned()
+}
+
+#[tokio::test]
+async fn evicts_the_least_recently_used_url() {
+    let server = Server::start(|_| response("200 OK", &[("Cache-Control", "max-age=60")], "ok"));
+    let cache = HttpCache::new().with_capacity(2);
+    let client = RelayClient::new().with_cache(cache.clone());
+
+    get(&client, server.url("/a")).await;
+    get(&client, server.url("/b")).await;
+    // NOTE: Makes `/b` the least recently used.
+    get(&client, server.url("/a")).await;
+    get(&client, server.url("/c")).await;
+    assert_eq!(server.requests().len(), 3);
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.stats().evicted, 1);
+
+    get(&client, server.url("/a")).await;
+    assert_eq!(server.requests().len(), 3);
+    get(&client, server.url("/b")).await;
+    assert_eq!(server.requests().len(), 4);
+}
+
+#[tokio::test]
+async fn serves_fresh_responses_until_max_age_passes() {
+    let server = Server::start(|_| response("200 OK", &[("Cache-Control", "max-age=60")], "ok"));
+    let clock = Skipping::default();
+    let client = RelayClient::new().with_cache(HttpCache::new().with_clock(clock.clone()));
+
+    let first = get(&client, server.url("/")).await;
+    let second = get(&client, server.url("/")).await;
+    assert_eq!(first.meta.cache, Some(CacheStatus::Miss));
+    assert_eq!(second.meta.cache, Some(CacheStatus::Hit));
+    assert_eq!(body(&second), "ok");
+    assert_eq!(server.requests().len(), 1);
+
+    clock.skip(Duration::from_secs(61));
+    let expired = get(&client, server.url("/")).await;
+    assert_eq!(expired.meta.cache, Some(CacheStatus::Miss));
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn serves_fresh_responses_until_they_expire() {
+    let server = Server::start(|_| {
+        response(
+            "200 OK",
+            &[
+                ("Date", "Mon, 01 Jan 2024 00:00:00 GMT"),
+                ("Expires", "Mon, 01 Jan 2024 00:01:00 GMT"),
+            ],
+            "ok",
+        )
+    });
+    let clock = Skipping::default();
+    let client = RelayClient::new().with_cache(HttpCache::new().with_clock(clock.clone()));
+
+    get(&client, server.url("/")).await;
+    // NOTE: `Expires` is measured against the server's `Date`, so a minute
+    // of freshness even though both dates are long past.
+    let cached = get(&client, server.url("/")).await;
+    assert_eq!(cached.meta.cache, Some(CacheStatus::Hit));
+    assert_eq!(server.requests().len(), 1);
+
+    clock.skip(Duration::from_secs(61));
+    get(&client, server.url("/")).await;
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn max_age_wins_over_expires() {
+    let server = Server::start(|_| {
+        response(
+            "200 OK",
+            &[
+                ("Cache-Control", "max-age=0"),
+                ("Date", "Mon, 01 Jan 2024 00:00:00 GMT"),
+                ("Expires", "Tue, 01 Jan 2030 00:00:00 GMT"),
+            ],
+            "ok",
+        )
+    });
+    let client = RelayClient::new().with_cache(HttpCache::new());
+
+    get(&client, server.url("/")).await;
+    let second = get(&client, server.url("/")).await;
+    assert_eq!(second.meta.cache, Some(CacheStatus::Miss));
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn revalidates_stale_responses_and_keeps_the_cached_body() {
+    let server = Server::start(|captured| {
+        if captured.header("if-none-match").as_deref() == Some("\"v1\"") {
+            response(
+                "304 Not Modified",
+                &[("ETag", "\"v1\""), ("X-Revalidated", "yes")],
+                "",
+            )
+        } else {
+            response(
+                "200 OK",
+                &[("Cache-Control", "max-age=0"), ("ETag", "\"v1\"")],
+                "original",
+            )
+        }
+    });
+    let cache = HttpCache::new();
+    let client = RelayClient::new().with_cache(cache.clone());
+
+    let first = get(&client, server.url("/")).await;
+    let second = get(&client, server.url("/")).await;
+
+    assert_eq!(first.meta.cache, Some(CacheStatus::Miss));
+    assert_eq!(second.meta.cache, Some(CacheStatus::Revalidated));
+    assert_eq!(second.status, 200);
+    assert_eq!(body(&second), "original");
+    assert_eq!(
+        second.header_joined("x-revalidated").as_deref(),
+        Some("yes")
+    );
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].header("if-none-match"), None);
+    assert_eq!(
+        requests[1].header("if-none-match").as_deref(),
+        Some("\"v1\"")
+    );
+    assert_eq!(cache.stats().revalidated, 1);
+}
+
+#[tokio::test]
+async fn bypasses_uncacheable_requests() {
+    let server = Server::start(|_| response("200 OK", &[("Cache-Control", "max-age=60")], "ok"));
+    let cache = HttpCache::new();
+    let client = RelayClient::new().with_cache(cache.clone());
+    get(&client, server.url("/")).await;
+
+    let requests = [
+        Request::builder()
+            .get(server.url("/"))
+            .header("Cache-Control", "no-store"),
+        Request::builder().get(server.url("/")).bearer("token"),
+        Request::builder()
+            .get(server.url("/"))
+            .header("Authorization", "Basic dXNlcjpwYXNz"),
+        Request::builder().head(server.url("/")),
+    ];
+    let count = requests.len();
+    for request in requests {
+        let response = send(&client, request).await;
+        assert_eq!(response.meta.cache, Some(CacheStatus::Bypass));
+    }
+
+    assert_eq!(server.requests().len(), 1 + count);
+    assert_eq!(cache.stats().bypassed, count as u64);
+    let cached = get(&client, server.url("/")).await;
+    assert_eq!(cached.meta.cache, Some(CacheStatus::Hit));
+}
+
+#[tokio::test]
+async fn unsafe_requests_bypass_and_invalidate() {
+    let server = Server::start(|_| response("200 OK", &[("Cache-Control", "max-age=60")], "ok"));
+    let client = RelayClient::new().with_cache(HttpCache::new());
+    get(&client, server.url("/")).await;
+
+    let posted = send(&client, Request::builder().post(server.url("/")).text("x")).await;
+    assert_eq!(posted.meta.cache, Some(CacheStatus::Bypass));
+
+    let refetched = get(&client, server.url("/")).await;
+    assert_eq!(refetched.meta.cache, Some(CacheStatus::Miss));
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn authenticated_requests_are_cached_when_enabled() {
+    let server = Server::start(|_| response("200 OK", &[("Cache-Control", "max-age=60")], "ok"));
+    let client = RelayClient::new().with_cache(HttpCache::new().with_authenticated(true));
+
+    for _ in 0..2 {
+        send(&client, Request::builder().get(server.url("/")).bearer("t")).await;
+    }
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn stores_a_variant_per_vary_value() {
+    let server = Server::start(|captured| {
+        response(
+            "200 OK",
+            &[("Cache-Control", "max-age=60"), ("Vary", "Accept-Language")],
+            captured.header("accept-language").unwrap_or_default(),
+        )
+    });
+    let cache = HttpCache::new();
+    let client = RelayClient::new().with_cache(cache.clone());
+    let in_language = |language: &str| {
+        Request::builder()
+            .get(server.url("/"))
+            .header("Accept-Language", language)
+    };
+
+    let mut served = Vec::new();
+    for language in ["en", "fr", "en", "fr"] {
+        let response = send(&client, in_language(language)).await;
+        served.push((body(&response), response.meta.cache.unwrap()));
+    }
+
+    assert_eq!(
+        served,
+        [
+            ("en".to_string(), CacheStatus::Miss),
+            ("fr".to_string(), CacheStatus::Miss),
+            ("en".to_string(), CacheStatus::Hit),
+            ("fr".to_string(), CacheStatus::Hit),
+        ]
+    );
+    assert_eq!(server.requests().len(), 2);
+    assert_eq!(cache.len(), 2);
+}
+
+#[tokio::test]
+async fn purges_one_url_or_everything() {
+    let server = Server::start(|_| response("200 OK", &[("Cache-Control", "max-age=60")], "ok"));
+    let cache = HttpCache::new();
+    let client = RelayClient::new().with_cache(cache.clone());
+    get(&client, server.url("/a")).await;
+    get(&client, server.url("/b")).await;
+
+    cache.purge_url(&server.url("/a")).unwrap();
+    assert_eq!(cache.len(), 1);
+    let a = get(&client, server.url("/a")).await;
+    let b = get(&client, server.url("/b")).await;
+    assert_eq!(a.meta.cache, Some(CacheStatus::Miss));
+    assert_eq!(b.meta.cache, Some(CacheStatus::Hit));
+
+    cache.purge().unwrap();
+    assert!(cache.is_empty());
+    get(&client, server.url("/b")).await;
+    assert_eq!(server.requests().len(), 4);
+}
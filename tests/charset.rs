@@ -0,0 +1,46 @@
+use bytes::Bytes;
+use relay::{error::RelayError, MediaType, ResponseBody};
+
+fn body(bytes: &'static [u8], charset: Option<&str>) -> ResponseBody {
+    ResponseBody {
+        body: Bytes::from_static(bytes),
+        media_type: MediaType::TextPlain,
+        charset: charset.map(str::to_string),
+    }
+}
+
+#[test]
+fn decodes_shift_jis() {
+    let text = body(
+        b"\x82\xb1\x82\xf1\x82\xc9\x82\xbf\x82\xcd",
+        Some("Shift_JIS"),
+    );
+    assert_eq!(text.text().unwrap(), "こんにちは");
+}
+
+#[test]
+fn reads_latin1_as_windows_1252() {
+    let text = body(b"\x80 caf\xe9", Some("iso-8859-1"));
+    assert_eq!(text.text().unwrap(), "€ café");
+}
+
+#[test]
+fn lets_a_byte_order_mark_win() {
+    let text = body(b"\xff\xfeh\0i\0", Some("utf-8"));
+    assert_eq!(text.text().unwrap(), "hi");
+}
+
+#[test]
+fn rejects_malformed_text() {
+    let text = body(b"ok \xff", None);
+    assert!(matches!(text.text(), Err(RelayError::Parse { .. })));
+}
+
+#[test]
+fn rejects_unknown_labels() {
+    let text = body(b"ok", Some("klingon"));
+    assert!(matches!(
+        text.text(),
+        Err(RelayError::UnsupportedFeature { .. })
+    ));
+}
@@ -0,0 +1,167 @@
+#![allow(dead_code)]
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+// NOTE: One request as the server read it, the head verbatim and the body
+// read to its `Content-Length`.
+#[derive(Debug, Clone)]
+pub struct Captured {
+    pub head: String,
+    pub body: Vec<u8>,
+}
+
+impl Captured {
+    pub fn request_line(&self) -> &str {
+        self.head.lines().next().unwrap_or_default()
+    }
+
+    pub fn header(&self, name: &str) -> Option<String> {
+        self.head.lines().skip(1).find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case(name)
+                .then(|| value.trim().to_string())
+        })
+    }
+}
+
+type Handler = dyn Fn(&Captured) -> Vec<u8> + Send + Sync;
+
+// NOTE: A blocking HTTP/1.1 server on a loopback port, one thread per
+// connection, answering every request with whatever the handler returns.
+pub struct Server {
+    pub port: u16,
+    requests: Arc<Mutex<Vec<Captured>>>,
+    active: Arc<AtomicUsize>,
+    peak: Arc<AtomicUsize>,
+}
+
+impl Server {
+    pub fn start(handler: impl Fn(&Captured) -> Vec<u8> + Send + Sync + 'static) -> Server {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handler: Arc<Handler> = Arc::new(handler);
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        {
+            let (requests, active, peak) = (requests.clone(), active.clone(), peak.clone());
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let (handler, requests, active, peak) = (
+                        handler.clone(),
+                        requests.clone(),
+                        active.clone(),
+                        peak.clone(),
+                    );
+                    thread::spawn(move || {
+                        let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                        peak.fetch_max(now, Ordering::SeqCst);
+                        serve(stream, &*handler, &requests);
+                        active.fetch_sub(1, Ordering::SeqCst);
+                    });
+                }
+            });
+        }
+
+        Server {
+            port,
+            requests,
+            active,
+            peak,
+        }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://127.0.0.1:{}{}", self.port, path)
+    }
+
+    pub fn requests(&self) -> Vec<Captured> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn peak_connections(&self) -> usize {
+        self.peak.load(Ordering::SeqCst)
+    }
+}
+
+fn serve(stream: TcpStream, handler: &Handler, requests: &Mutex<Vec<Captured>>) {
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+
+    loop {
+        let mut head = String::new();
+        loop {
+            let mut line = String::new();
+            match reader.read_line(&mut line) {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+            if line == "\r\n" && !head.is_empty() {
+                break;
+            }
+            head.push_str(&line);
+        }
+
+        let mut captured = Captured {
+            head,
+            body: Vec::new(),
+        };
+        if captured
+            .header("expect")
+            .is_some_and(|expect| expect.eq_ignore_ascii_case("100-continue"))
+        {
+            let _ = writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n");
+        }
+        let length = captured
+            .header("content-length")
+            .and_then(|length| length.parse::<usize>().ok())
+            .unwrap_or(0);
+        captured.body = vec![0; length];
+        if reader.read_exact(&mut captured.body).is_err() {
+            return;
+        }
+
+        let response = handler(&captured);
+        requests.lock().unwrap().push(captured);
+        if writer.write_all(&response).is_err() {
+            return;
+        }
+        if !String::from_utf8_lossy(&response)
+            .to_ascii_lowercase()
+            .contains("connection: keep-alive")
+        {
+            return;
+        }
+    }
+}
+
+// NOTE: A complete response that closes the connection, with the given extra
+// header lines.
+pub fn response(status: &str, headers: &[(&str, &str)], body: impl AsRef<[u8]>) -> Vec<u8> {
+    let body = body.as_ref();
+    let mut text = format!("HTTP/1.1 {}\r\n", status);
+    for (name, value) in headers {
+        text.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    text.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    ));
+    let mut bytes = text.into_bytes();
+    bytes.extend_from_slice(body);
+    bytes
+}
+
+pub fn ok(body: impl AsRef<[u8]>) -> Vec<u8> {
+    response("200 OK", &[], body)
+}
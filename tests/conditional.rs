@@ -0,0 +1,114 @@
+mod common;
+
+use common::{response, Server};
+use relay::{RelayClient, Request, Response};
+use time::{OffsetDateTime, UtcOffset};
+
+// NOTE: RFC 7231's example date, `Sun, 06 Nov 1994 08:49:37 GMT`.
+const EXAMPLE_DATE: i64 = 784_111_777;
+
+fn request() -> Request {
+    Request::builder()
+        .put("http://example.com/items/1")
+        .build()
+        .unwrap()
+}
+
+fn header<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request.headers.as_ref()?.get(name)
+}
+
+async fn served_with(etag: &'static str) -> Response {
+    let server = Server::start(move |_| {
+        response(
+            "200 OK",
+            &[
+                ("ETag", etag),
+                ("Last-Modified", "Sun, 06 Nov 1994 08:49:37 GMT"),
+            ],
+            "item",
+        )
+    });
+    let request = Request::builder().get(server.url("/")).build().unwrap();
+    RelayClient::new().execute(request).await.unwrap()
+}
+
+#[test]
+fn entity_tags_are_quoted_unless_already_valid() {
+    let cases = [
+        ("abc", "\"abc\""),
+        ("\"abc\"", "\"abc\""),
+        (" abc ", "\"abc\""),
+        ("W/\"abc\"", "W/\"abc\""),
+        ("*", "*"),
+    ];
+    for (etag, expected) in cases {
+        let request = request().if_none_match(etag);
+        assert_eq!(
+            header(&request, "If-None-Match"),
+            Some(expected),
+            "{}",
+            etag
+        );
+    }
+
+    let request = request().if_match("v2");
+    assert_eq!(header(&request, "If-Match"), Some("\"v2\""));
+}
+
+#[test]
+fn dates_are_sent_as_imf_fixdates_in_gmt() {
+    let date = OffsetDateTime::from_unix_timestamp(EXAMPLE_DATE).unwrap();
+    let request = request().if_modified_since(date);
+    assert_eq!(
+        header(&request, "If-Modified-Since"),
+        Some("Sun, 06 Nov 1994 08:49:37 GMT")
+    );
+
+    // NOTE: Converted to GMT, single digit days and hours are zero padded.
+    let offset = UtcOffset::from_hms(2, 0, 0).unwrap();
+    let date = OffsetDateTime::from_unix_timestamp(1_709_611_506)
+        .unwrap()
+        .to_offset(offset);
+    let request = request().if_unmodified_since(date);
+    assert_eq!(
+        header(&request, "If-Unmodified-Since"),
+        Some("Tue, 05 Mar 2024 04:05:06 GMT")
+    );
+}
+
+#[tokio::test]
+async fn conditions_copy_the_validators() {
+    let previous = served_with("\"v1\"").await;
+    let request = request().with_conditions_from(&previous);
+
+    assert_eq!(header(&request, "If-None-Match"), Some("\"v1\""));
+    assert_eq!(
+        header(&request, "If-Modified-Since"),
+        Some("Sun, 06 Nov 1994 08:49:37 GMT")
+    );
+}
+
+#[tokio::test]
+async fn preconditions_copy_strong_validators() {
+    let previous = served_with("\"v1\"").await;
+    let request = request().with_preconditions_from(&previous);
+
+    assert_eq!(header(&request, "If-Match"), Some("\"v1\""));
+    assert_eq!(
+        header(&request, "If-Unmodified-Since"),
+        Some("Sun, 06 Nov 1994 08:49:37 GMT")
+    );
+}
+
+#[tokio::test]
+async fn preconditions_skip_weak_etags() {
+    let previous = served_with("W/\"v1\"").await;
+    let request = request().with_preconditions_from(&previous);
+
+    assert_eq!(header(&request, "If-Match"), None);
+    assert_eq!(
+        header(&request, "If-Unmodified-Since"),
+        Some("Sun, 06 Nov 1994 08:49:37 GMT")
+    );
+}
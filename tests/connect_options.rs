@@ -0,0 +1,59 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{ok, Server};
+use relay::{RelayClient, Request};
+
+// NOTE: `localhost` resolves to both `::1` and `127.0.0.1` while the server
+// only listens on the latter, so the connect has to fall back across families.
+fn localhost(server: &Server) -> String {
+    format!("http://localhost:{}/", server.port)
+}
+
+#[tokio::test]
+async fn happy_eyeballs_timeout_still_connects() {
+    let server = Server::start(|_| ok("connected"));
+
+    let request = Request::builder()
+        .get(localhost(&server))
+        .happy_eyeballs_timeout(Duration::from_millis(1))
+        .build()
+        .unwrap();
+    let response = relay::execute(request).await.unwrap();
+    assert_eq!(response.body.body.as_ref(), b"connected");
+
+    let client = RelayClient::new().with_happy_eyeballs_timeout(Duration::from_secs(5));
+    let request = Request::builder().get(localhost(&server)).build().unwrap();
+    let response = client.execute(request).await.unwrap();
+    assert_eq!(response.body.body.as_ref(), b"connected");
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn tcp_fastopen_is_accepted() {
+    let server = Server::start(|_| ok("connected"));
+
+    // NOTE: Where the platform lacks it the option is ignored, either way the
+    // request goes through.
+    let request = Request::builder()
+        .get(server.url("/"))
+        .tcp_fastopen(true)
+        .build()
+        .unwrap();
+    let response = relay::execute(request).await.unwrap();
+    assert_eq!(response.body.body.as_ref(), b"connected");
+}
+
+#[tokio::test]
+async fn binds_to_a_loopback_interface() {
+    let server = Server::start(|_| ok("connected"));
+
+    let request = Request::builder()
+        .get(server.url("/"))
+        .interface("127.0.0.1")
+        .build()
+        .unwrap();
+    let response = relay::execute(request).await.unwrap();
+    assert_eq!(response.body.body.as_ref(), b"connected");
+}
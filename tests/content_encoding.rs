@@ -0,0 +1,96 @@
+mod common;
+
+use std::io::Write;
+
+use common::{response, Server};
+use flate2::{
+    write::{GzEncoder, ZlibEncoder},
+    Compression,
+};
+use relay::{error::RelayError, Request, Response};
+
+const TEXT: &[u8] = b"The quick brown fox jumps over the lazy dog, again and again and again.";
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+// NOTE: HTTP's `deflate` is the zlib format, not raw deflate.
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn brotli(data: &[u8]) -> Vec<u8> {
+    let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+    encoder.write_all(data).unwrap();
+    encoder.into_inner()
+}
+
+// NOTE: Sends `body` in chunks of a few bytes, so encoded data is split
+// across chunk boundaries.
+fn chunked(content_encoding: &str, body: &[u8]) -> Vec<u8> {
+    let mut bytes = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Encoding: {}\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n",
+        content_encoding
+    )
+    .into_bytes();
+    for chunk in body.chunks(7) {
+        bytes.extend(format!("{:x}\r\n", chunk.len()).into_bytes());
+        bytes.extend_from_slice(chunk);
+        bytes.extend_from_slice(b"\r\n");
+    }
+    bytes.extend_from_slice(b"0\r\n\r\n");
+    bytes
+}
+
+async fn fetch(raw: Vec<u8>) -> relay::error::Result<Response> {
+    let server = Server::start(move |_| raw.clone());
+    let request = Request::builder().get(server.url("/")).build().unwrap();
+    relay::execute(request).await
+}
+
+#[tokio::test]
+async fn decodes_gzip_sent_with_content_length() {
+    let raw = response("200 OK", &[("Content-Encoding", "gzip")], gzip(TEXT));
+    let response = fetch(raw).await.unwrap();
+    assert_eq!(&response.body.body[..], TEXT);
+}
+
+#[tokio::test]
+async fn decodes_gzip_after_removing_chunked_framing() {
+    let response = fetch(chunked("gzip", &gzip(TEXT))).await.unwrap();
+    assert_eq!(&response.body.body[..], TEXT);
+}
+
+#[tokio::test]
+async fn undoes_stacked_codings_in_reverse_order() {
+    // NOTE: Applied deflate first, then gzip.
+    let body = gzip(&deflate(TEXT));
+    let response = fetch(chunked("deflate, gzip", &body)).await.unwrap();
+    assert_eq!(&response.body.body[..], TEXT);
+}
+
+#[tokio::test]
+async fn decodes_brotli_over_gzip_or_reports_it_unsupported() {
+    let body = brotli(&gzip(TEXT));
+    let result = fetch(chunked("gzip, br", &body)).await;
+
+    if curl::Version::get().feature_brotli() {
+        assert_eq!(&result.unwrap().body.body[..], TEXT);
+    } else {
+        assert!(matches!(
+            result,
+            Err(RelayError::UnsupportedFeature { ref feature, .. }) if feature == "br content encoding"
+        ));
+    }
+}
+
+#[tokio::test]
+async fn a_body_that_does_not_decode_is_a_parse_error() {
+    let result = fetch(chunked("gzip", b"certainly not gzip")).await;
+    assert!(matches!(result, Err(RelayError::Parse { .. })));
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use common::{ok, Captured, Server};
+use relay::{MediaType, RelatedPart, RelayClient, Request, RequestBuilder};
+use serde_json::json;
+
+fn post(server: &Server) -> RequestBuilder {
+    Request::builder()
+        .post(server.url("/"))
+        .header("X-One", "1")
+        .header("X-Two", "2")
+        .bearer("token")
+}
+
+fn content_types(captured: &Captured) -> Vec<String> {
+    captured
+        .head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(name, _)| name.trim().eq_ignore_ascii_case("content-type"))
+        .map(|(_, value)| value.trim().to_string())
+        .collect()
+}
+
+fn related_part(content_type: &str, content_id: &str) -> RelatedPart {
+    RelatedPart {
+        body: "{}".into(),
+        content_type: content_type.into(),
+        content_id: content_id.into(),
+        headers: Default::default(),
+    }
+}
+
+#[tokio::test]
+async fn custom_headers_arrive_with_every_content_type() {
+    let server = Server::start(|_| ok("ok"));
+
+    let mut xml = post(&server)
+        .header("Content-Type", "application/xml")
+        .build()
+        .unwrap();
+    xml.content = serde_json::from_value(json!({
+        "kind": "xml",
+        "content": "<a/>",
+        "mediaType": "application/xml",
+    }))
+    .unwrap();
+
+    // NOTE: Set after `build`, so only the transfer adds `Content-Type`.
+    let mut form = post(&server).build().unwrap();
+    form.content = serde_json::from_value(json!({
+        "kind": "form",
+        "content": [["field", [{ "kind": "text", "value": "value" }]]],
+        "mediaType": "multipart/form-data",
+    }))
+    .unwrap();
+
+    let cases = [
+        (
+            "text",
+            post(&server).text("hello").build().unwrap(),
+            "text/plain",
+        ),
+        (
+            "json",
+            post(&server).json(&json!({ "a": 1 })).build().unwrap(),
+            "application/json",
+        ),
+        (
+            "jsonPatch",
+            post(&server)
+                .json_patch(json!([{ "op": "remove", "path": "/a" }]))
+                .build()
+                .unwrap(),
+            "application/json-patch+json",
+        ),
+        (
+            "jsonMergePatch",
+            post(&server)
+                .merge_patch(&json!({ "a": null }))
+                .build()
+                .unwrap(),
+            "application/merge-patch+json",
+        ),
+        ("xml", xml, "application/xml"),
+        (
+            "csv",
+            post(&server).csv("a,b\n1,2\n", true).build().unwrap(),
+            "text/csv",
+        ),
+        ("form", form, "multipart/form-data; boundary="),
+        (
+            "binary",
+            post(&server)
+                .binary(vec![0u8, 1, 2], MediaType::OctetStream)
+                .build()
+                .unwrap(),
+            "application/octet-stream",
+        ),
+        (
+            "multipart",
+            post(&server)
+                .multipart_text("field", "value")
+                .build()
+                .unwrap(),
+            "multipart/form-data; boundary=",
+        ),
+        (
+            "urlencoded",
+            post(&server).form([("a", "1")]).build().unwrap(),
+            "application/x-www-form-urlencoded",
+        ),
+        (
+            "multipartRelated",
+            post(&server)
+                .multipart_related(
+                    related_part("application/json", "root"),
+                    [related_part("application/json", "part")],
+                )
+                .build()
+                .unwrap(),
+            "multipart/related",
+        ),
+    ];
+
+    let client = RelayClient::new();
+    let count = cases.len();
+    for (kind, request, expected) in cases {
+        client.execute(request).await.unwrap();
+
+        let requests = server.requests();
+        let sent = requests.last().unwrap();
+        assert_eq!(sent.header("x-one").as_deref(), Some("1"), "{}", kind);
+        assert_eq!(sent.header("x-two").as_deref(), Some("2"), "{}", kind);
+        assert_eq!(
+            sent.header("authorization").as_deref(),
+            Some("Bearer token"),
+            "{}",
+            kind
+        );
+
+        let content_types = content_types(sent);
+        assert_eq!(content_types.len(), 1, "{}: {:?}", kind, content_types);
+        assert!(
+            content_types[0].starts_with(expected),
+            "{}: {}",
+            kind,
+            content_types[0]
+        );
+    }
+    assert_eq!(server.requests().len(), count);
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use common::{response, Server};
+use relay::{Request, Response};
+
+const BODY: &str = "id,name\n1,\"Smith, J\"\n2,\"O\"\"Brien\"\n";
+
+async fn fetch(content_type: &'static str, body: &'static str) -> Response {
+    let server =
+        Server::start(move |_| response("200 OK", &[("Content-Type", content_type)], body));
+    let request = Request::builder().get(server.url("/")).build().unwrap();
+    relay::execute(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn header_present_drops_the_first_row() {
+    for content_type in [
+        "text/csv; header=present",
+        "text/csv;charset=utf-8;HEADER=\"Present\"",
+    ] {
+        let response = fetch(content_type, BODY).await;
+        assert_eq!(
+            response.csv_records().unwrap(),
+            [["1", "Smith, J"], ["2", "O\"Brien"]],
+            "{}",
+            content_type
+        );
+    }
+}
+
+#[tokio::test]
+async fn without_header_present_every_row_is_a_record() {
+    for content_type in ["text/csv", "text/csv; header=absent"] {
+        let response = fetch(content_type, BODY).await;
+        assert_eq!(
+            response.csv_records().unwrap(),
+            [["id", "name"], ["1", "Smith, J"], ["2", "O\"Brien"]],
+            "{}",
+            content_type
+        );
+    }
+}
+
+#[tokio::test]
+async fn the_header_flag_can_be_given_explicitly() {
+    let response = fetch("text/plain", "a;b\n\"1;2\";3\n").await;
+
+    assert_eq!(
+        response.csv_records_with(';', true).unwrap(),
+        [["1;2", "3"]]
+    );
+    assert_eq!(
+        response.csv_records_with(';', false).unwrap(),
+        [["a", "b"], ["1;2", "3"]]
+    );
+    // NOTE: A lone header row leaves nothing.
+    let header_only = fetch("text/csv; header=present", "id,name\n").await;
+    assert!(header_only.csv_records().unwrap().is_empty());
+}
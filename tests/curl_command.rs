@@ -0,0 +1,98 @@
+use std::{env, fs};
+
+use relay::{CurlCommand, Request};
+
+fn scratch_dir(name: &str) -> std::path::PathBuf {
+    let dir = env::temp_dir().join(format!("relay-curl-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn parse_does_not_read_referenced_files() {
+    let dir = scratch_dir("parse");
+    fs::write(dir.join("body.json"), r#"{"secret":true}"#).unwrap();
+    let path = dir.join("body.json").display().to_string();
+
+    let parsed = CurlCommand::parse(&format!(
+        "curl https://example.com -d @{} --cacert {}",
+        path, path
+    ))
+    .unwrap();
+    assert!(parsed.request.content.is_none());
+    assert!(parsed.request.security.is_none());
+    assert_eq!(parsed.files, vec![path.clone(), path]);
+    assert_eq!(parsed.warnings.len(), 2);
+
+    // NOTE: A missing file is not an error either, nothing is opened.
+    let parsed = CurlCommand::parse("curl https://example.com -d @missing.json").unwrap();
+    assert_eq!(parsed.files, vec!["missing.json".to_string()]);
+
+    let request = Request::from_curl_command("curl https://example.com -d @missing.json", 1);
+    assert!(request.unwrap().content.is_none());
+}
+
+#[test]
+fn files_are_read_relative_to_the_given_directory() {
+    let dir = scratch_dir("files");
+    fs::write(dir.join("body.txt"), "a=1\nb=2\n").unwrap();
+
+    let parsed =
+        CurlCommand::parse_with_files("curl https://example.com -d @body.txt", &dir).unwrap();
+    assert_eq!(parsed.files, vec!["body.txt".to_string()]);
+    assert!(parsed.warnings.is_empty());
+    assert_eq!(
+        parsed.request.content.unwrap().estimated_size().total_bytes,
+        "a=1b=2".len() as u64
+    );
+
+    let request = Request::from_curl_command_with_files(
+        "curl https://example.com --data-binary @missing.txt",
+        1,
+        &dir,
+    );
+    assert!(request.is_err());
+}
+
+#[test]
+fn unknown_flags_do_not_swallow_the_url() {
+    let parsed = CurlCommand::parse("curl --frobnicate https://example.com/a").unwrap();
+    assert!(parsed.request.url.starts_with("https://example.com/a"));
+    assert_eq!(parsed.warnings, vec!["Unknown flag '--frobnicate' ignored"]);
+}
+
+#[test]
+fn values_of_ignored_flags_are_not_taken_for_the_url() {
+    for command in [
+        "curl --connect-timeout 5 https://example.com/a",
+        "curl --connect-timeout=5 https://example.com/a",
+        "curl https://example.com/a -sSo out.json",
+    ] {
+        let parsed = CurlCommand::parse(command).unwrap();
+        assert!(
+            parsed.request.url.starts_with("https://example.com/a"),
+            "{}",
+            command
+        );
+        assert_eq!(parsed.warnings.len(), 1, "{}", command);
+    }
+}
+
+#[test]
+fn fail_flags_import_as_fail_with_body() {
+    for flag in ["-f", "--fail"] {
+        let parsed = CurlCommand::parse(&format!("curl {} https://example.com", flag)).unwrap();
+        assert_eq!(parsed.warnings.len(), 1);
+        assert!(parsed.warnings[0].contains("--fail-with-body"));
+        let command = parsed.request.to_curl_command().unwrap();
+        assert!(command.contains("--fail-with-body"));
+    }
+
+    let parsed = CurlCommand::parse("curl --fail-with-body https://example.com").unwrap();
+    assert!(parsed.warnings.is_empty());
+    assert!(parsed
+        .request
+        .to_curl_command()
+        .unwrap()
+        .contains("--fail-with-body"));
+}
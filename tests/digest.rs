@@ -0,0 +1,129 @@
+mod common;
+
+use common::{ok, Server};
+use relay::{AuthType, DigestAlgorithm, RelayClient, Request};
+
+// NOTE: The example exchange from RFC 7616 section 3.9.1.
+const CHALLENGE: &str = concat!(
+    r#"Digest realm="http-auth@example.org", qop="auth, auth-int", algorithm=SHA-256, "#,
+    r#"nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", "#,
+    r#"opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS", "#,
+    r#"Digest realm="http-auth@example.org", qop="auth, auth-int", algorithm=MD5, "#,
+    r#"nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", "#,
+    r#"opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#,
+);
+const CNONCE: &str = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+
+fn mufasa() -> AuthType {
+    AuthType::Digest {
+        username: "Mufasa".into(),
+        password: "Circle of Life".into(),
+        realm: None,
+        nonce: None,
+        opaque: None,
+        algorithm: None,
+        qop: None,
+        nc: None,
+        cnonce: None,
+    }
+}
+
+fn credentials(algorithm: Option<DigestAlgorithm>) -> AuthType {
+    let mut auth = mufasa();
+    auth.negotiate_digest(CHALLENGE, false).unwrap();
+    if let AuthType::Digest {
+        algorithm: ref mut chosen,
+        ref mut nc,
+        ref mut cnonce,
+        ..
+    } = auth
+    {
+        if let Some(algorithm) = algorithm {
+            *chosen = Some(algorithm);
+        }
+        *nc = Some("00000001".into());
+        *cnonce = Some(CNONCE.into());
+    }
+    auth
+}
+
+async fn authorization(server: &Server, auth: AuthType) -> String {
+    let mut request = Request::builder()
+        .get(server.url("/dir/index.html"))
+        .build()
+        .unwrap();
+    request.auth = Some(auth);
+    RelayClient::new().execute(request).await.unwrap();
+    server
+        .requests()
+        .last()
+        .and_then(|request| request.header("authorization"))
+        .unwrap()
+}
+
+fn field<'a>(header: &'a str, name: &str) -> Option<&'a str> {
+    header
+        .trim_start_matches("Digest ")
+        .split(", ")
+        .find_map(|field| field.strip_prefix(name)?.strip_prefix('='))
+        .map(|value| value.trim_matches('"'))
+}
+
+#[test]
+fn prefers_sha256_over_md5() {
+    let auth = credentials(None);
+    let AuthType::Digest { algorithm, .. } = auth else {
+        unreachable!()
+    };
+    assert_eq!(algorithm, Some(DigestAlgorithm::Sha256));
+}
+
+#[tokio::test]
+async fn answers_with_sha256() {
+    let server = Server::start(|_| ok("ok"));
+    let header = authorization(&server, credentials(None)).await;
+    assert_eq!(field(&header, "algorithm"), Some("SHA-256"));
+    assert_eq!(
+        field(&header, "response"),
+        Some("753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1")
+    );
+}
+
+#[tokio::test]
+async fn answers_with_md5() {
+    let server = Server::start(|_| ok("ok"));
+    let header = authorization(&server, credentials(Some(DigestAlgorithm::Md5))).await;
+    assert_eq!(field(&header, "algorithm"), Some("MD5"));
+    assert_eq!(
+        field(&header, "response"),
+        Some("8ca523f5e9506fed4657c9700eebdbec")
+    );
+}
+
+#[tokio::test]
+async fn counts_requests_per_nonce() {
+    let server = Server::start(|_| ok("ok"));
+    let mut auth = mufasa();
+    auth.negotiate_digest(
+        r#"Digest realm="api", qop="auth", nonce="counted-nonce""#,
+        false,
+    )
+    .unwrap();
+
+    let first = authorization(&server, auth.clone()).await;
+    let second = authorization(&server, auth).await;
+    assert_eq!(field(&first, "nc"), Some("00000001"));
+    assert_eq!(field(&second, "nc"), Some("00000002"));
+}
+
+#[test]
+fn keeps_sha512_distinct_from_sha512_256() {
+    assert_eq!(
+        serde_json::to_string(&DigestAlgorithm::Sha512).unwrap(),
+        r#""SHA512""#
+    );
+    assert_eq!(
+        serde_json::to_string(&DigestAlgorithm::Sha512_256).unwrap(),
+        r#""SHA-512-256""#
+    );
+}
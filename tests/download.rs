@@ -0,0 +1,114 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use common::{response, Server};
+use relay::{DownloadOptions, RelayClient};
+
+const BODY: &[u8] = b"0123456789";
+
+fn dest(name: &str) -> PathBuf {
+    let dest = std::env::temp_dir().join(format!("relay-{}-{}", std::process::id(), name));
+    let _ = fs::remove_file(&dest);
+    let _ = fs::remove_file(dest.with_extension("part"));
+    let _ = fs::remove_file(dest.with_extension("part.validator"));
+    dest
+}
+
+// NOTE: Promises the whole body but hangs up halfway through it.
+fn cut_short(etag: &str) -> Vec<u8> {
+    let mut bytes = format!(
+        "HTTP/1.1 200 OK\r\nETag: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        etag,
+        BODY.len()
+    )
+    .into_bytes();
+    bytes.extend_from_slice(&BODY[..5]);
+    bytes
+}
+
+#[tokio::test]
+async fn resumes_an_interrupted_download_with_if_range() {
+    let server = Server::start(|request| match request.header("range") {
+        None => cut_short("\"v1\""),
+        Some(range) => {
+            assert_eq!(range, "bytes=5-");
+            assert_eq!(request.header("if-range").as_deref(), Some("\"v1\""));
+            response(
+                "206 Partial Content",
+                &[("ETag", "\"v1\""), ("Content-Range", "bytes 5-9/10")],
+                &BODY[5..],
+            )
+        }
+    });
+    let dest = dest("resume");
+    let client = RelayClient::new();
+
+    let first = client
+        .download(&server.url("/file"), &dest, DownloadOptions::new())
+        .await;
+    assert!(first.is_err());
+    assert!(!dest.exists());
+
+    let second = client
+        .download(&server.url("/file"), &dest, DownloadOptions::new())
+        .await
+        .unwrap();
+    assert_eq!(second.resumed_from, 5);
+    assert_eq!(second.downloaded, 5);
+    assert_eq!(fs::read(&dest).unwrap(), BODY);
+    assert_eq!(server.requests().len(), 2);
+    let _ = fs::remove_file(&dest);
+}
+
+#[tokio::test]
+async fn starts_over_when_the_file_changed() {
+    let server = Server::start(|request| match request.header("if-range") {
+        // NOTE: The validator no longer matches, so the whole new body.
+        Some(_) => response("200 OK", &[("ETag", "\"v2\"")], b"abcdefghij"),
+        None => cut_short("\"v1\""),
+    });
+    let dest = dest("changed");
+    let client = RelayClient::new();
+
+    let _ = client
+        .download(&server.url("/file"), &dest, DownloadOptions::new())
+        .await;
+    let result = client
+        .download(&server.url("/file"), &dest, DownloadOptions::new())
+        .await
+        .unwrap();
+    assert_eq!(result.resumed_from, 0);
+    assert_eq!(fs::read(&dest).unwrap(), b"abcdefghij");
+    let _ = fs::remove_file(&dest);
+}
+
+#[tokio::test]
+async fn does_not_resume_without_a_validator() {
+    let server = Server::start(|request| match request.header("range") {
+        Some(_) => panic!("resumed without a validator"),
+        None if request.request_line().contains("/first") => {
+            let mut bytes = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                BODY.len()
+            )
+            .into_bytes();
+            bytes.extend_from_slice(&BODY[..5]);
+            bytes
+        }
+        None => response("200 OK", &[], BODY),
+    });
+    let dest = dest("no-validator");
+    let client = RelayClient::new();
+
+    let _ = client
+        .download(&server.url("/first"), &dest, DownloadOptions::new())
+        .await;
+    let result = client
+        .download(&server.url("/second"), &dest, DownloadOptions::new())
+        .await
+        .unwrap();
+    assert_eq!(result.resumed_from, 0);
+    assert_eq!(fs::read(&dest).unwrap(), BODY);
+    let _ = fs::remove_file(&dest);
+}
@@ -0,0 +1,53 @@
+use relay::error::RelayError;
+
+#[test]
+fn shows_the_tls_handshake_detail() {
+    let error = RelayError::TlsHandshake {
+        detail: "certificate has expired".into(),
+        message: "SSL connect error".into(),
+        code: 35,
+    };
+    assert_eq!(
+        error.to_string(),
+        "TLS handshake failed: certificate has expired"
+    );
+}
+
+#[test]
+fn keeps_curls_message_for_terse_variants() {
+    let errors = [
+        (
+            RelayError::ConnectionReset {
+                message: "Recv failure: Connection reset by peer".into(),
+                code: 56,
+                partial: None,
+            },
+            "Connection reset: Recv failure: Connection reset by peer",
+        ),
+        (
+            RelayError::TooManyRedirects {
+                message: "Maximum (5) redirects followed".into(),
+                code: 47,
+                chain: Vec::new(),
+            },
+            "Too many redirects: Maximum (5) redirects followed",
+        ),
+        (
+            RelayError::UnsupportedProtocol {
+                message: "Protocol \"gopher\" not supported".into(),
+                code: 1,
+            },
+            "Unsupported protocol: Protocol \"gopher\" not supported",
+        ),
+        (
+            RelayError::ProxyError {
+                message: "CONNECT tunnel failed, response 407".into(),
+                code: 56,
+            },
+            "Proxy error: CONNECT tunnel failed, response 407",
+        ),
+    ];
+    for (error, expected) in errors {
+        assert_eq!(error.to_string(), expected);
+    }
+}
@@ -0,0 +1,94 @@
+use relay::{error::RelayError, ErrorCode, ErrorPayload, HashAlgorithm, RelayClient, Request};
+use serde_json::json;
+
+#[test]
+fn pins_the_json_shape() {
+    let error = RelayError::ResponseTime {
+        message: "Response too slow".into(),
+        elapsed_ms: 1500,
+        limit_ms: 1000,
+    };
+    let message = error.to_string();
+    let payload = serde_json::to_value(ErrorPayload::new(7, error)).unwrap();
+    assert_eq!(
+        payload,
+        json!({
+            "code": "response_time",
+            "message": message,
+            "requestId": 7,
+            "retryable": false,
+            "elapsedMs": 1500,
+        })
+    );
+}
+
+#[test]
+fn leaves_out_unset_fields() {
+    let error = RelayError::BodyHashMismatch {
+        algorithm: HashAlgorithm::Sha256,
+        expected: "aa".into(),
+        actual: "bb".into(),
+    };
+    let payload = serde_json::to_value(ErrorPayload::from(error)).unwrap();
+    let mut keys = payload.as_object().unwrap().keys().collect::<Vec<_>>();
+    keys.sort();
+    assert_eq!(keys, ["code", "message", "retryable"]);
+    assert_eq!(payload["code"], "body_hash_mismatch");
+}
+
+#[test]
+fn kind_matches_the_serialized_code() {
+    let errors = [
+        RelayError::Network {
+            message: "down".into(),
+            cause: None,
+        },
+        RelayError::InvalidHeader {
+            name: "X".into(),
+            reason: "bad".into(),
+        },
+        RelayError::Io {
+            message: "disk".into(),
+            cause: None,
+        },
+    ];
+    for error in errors {
+        let code = serde_json::to_value(error.code()).unwrap();
+        assert_eq!(code, error.kind());
+        assert_eq!(
+            serde_json::from_value::<ErrorCode>(code).unwrap(),
+            error.code()
+        );
+    }
+}
+
+#[tokio::test]
+async fn fills_in_the_request_id() {
+    // NOTE: Nothing listens on port 1.
+    let request = Request::builder()
+        .get("http://127.0.0.1:1/")
+        .build()
+        .unwrap();
+    let payload = RelayClient::new()
+        .execute_interop(request)
+        .await
+        .unwrap_err();
+    assert!(payload.request_id.is_some());
+    assert_eq!(payload.code, ErrorCode::ConnectionRefused);
+    assert!(payload.retryable);
+}
+
+#[test]
+fn retries_network_errors_only_for_transient_curl_codes() {
+    let network = |code| RelayError::Network {
+        message: "Failed to perform request".into(),
+        cause: Some(curl::Error::new(code).into()),
+    };
+    assert!(network(curl_sys::CURLE_HTTP2_STREAM).is_retryable());
+    assert!(!network(curl_sys::CURLE_BAD_FUNCTION_ARGUMENT).is_retryable());
+    assert!(!RelayError::Network {
+        message: "down".into(),
+        cause: None,
+    }
+    .is_retryable());
+}
@@ -0,0 +1,206 @@
+mod common;
+
+use std::{
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use common::{ok, response, Server};
+use relay::{
+    error::Result, BackoffStrategy, RelayClient, Request, Response, RetryPolicy, TransferEvent,
+    TransferEventKind,
+};
+use serde_json::Value;
+
+async fn with_events(client: &RelayClient, url: String) -> (Result<Response>, Vec<TransferEvent>) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&events);
+    let request = Request::builder()
+        .get(url)
+        .follow_redirects(true)
+        .build()
+        .unwrap();
+    let result = client
+        .execute_with_events(request, move |event| {
+            recorded.lock().unwrap().push(event.clone())
+        })
+        .await;
+    let events = events.lock().unwrap().clone();
+    (result, events)
+}
+
+fn kinds(events: &[TransferEvent]) -> Vec<String> {
+    events
+        .iter()
+        .map(
+            |event| match serde_json::to_value(&event.kind).unwrap()["kind"] {
+                Value::String(ref kind) => kind.clone(),
+                ref other => panic!("event without a kind: {}", other),
+            },
+        )
+        .collect()
+}
+
+fn assert_numbered_in_order(events: &[TransferEvent], request_id: i64) {
+    for (index, event) in events.iter().enumerate() {
+        assert_eq!(event.sequence, index as u64, "{:?}", event);
+        assert_eq!(event.request_id, request_id, "{:?}", event);
+    }
+    assert!(events.windows(2).all(|pair| pair[0].at <= pair[1].at));
+}
+
+#[tokio::test]
+async fn a_request_goes_through_each_phase_in_order() {
+    let server = Server::start(|_| ok("hello"));
+    let (result, events) = with_events(&RelayClient::new(), server.url("/")).await;
+    let response = result.unwrap();
+
+    assert_eq!(
+        kinds(&events),
+        [
+            "queued",
+            "resolvingDns",
+            "connecting",
+            "sending",
+            "waiting",
+            "receiving",
+            "done"
+        ]
+    );
+    assert_numbered_in_order(&events, response.id);
+    assert!(matches!(
+        &events[2].kind,
+        TransferEventKind::Connecting { address } if address.starts_with("127.0.0.1")
+    ));
+}
+
+#[tokio::test]
+async fn the_last_event_has_the_response_timing() {
+    let server = Server::start(|_| ok("hello"));
+    let (result, events) = with_events(&RelayClient::new(), server.url("/")).await;
+    let response = result.unwrap();
+
+    let Some(TransferEventKind::Done { timing }) = events.last().map(|event| &event.kind) else {
+        panic!("expected done last, got {:?}", events.last());
+    };
+    assert_eq!(
+        serde_json::to_value(timing).unwrap(),
+        serde_json::to_value(&response.meta.timing).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn redirects_repeat_the_phases_for_each_hop() {
+    let server = Server::start(|captured| {
+        if captured.request_line().contains(" /old ") {
+            response("302 Found", &[("Location", "/new")], "")
+        } else {
+            ok("moved")
+        }
+    });
+    let (result, events) = with_events(&RelayClient::new(), server.url("/old")).await;
+    result.unwrap();
+
+    let kinds = kinds(&events);
+    let redirect = kinds.iter().position(|kind| kind == "redirect").unwrap();
+    assert!(matches!(
+        &events[redirect].kind,
+        TransferEventKind::Redirect { url } if *url == server.url("/new")
+    ));
+    for phase in ["sending", "waiting", "receiving"] {
+        assert!(
+            kinds[..redirect].contains(&phase.to_string()),
+            "{:?}",
+            kinds
+        );
+        assert!(
+            kinds[redirect..].contains(&phase.to_string()),
+            "{:?}",
+            kinds
+        );
+    }
+    assert_eq!(kinds.last().map(String::as_str), Some("done"));
+}
+
+#[tokio::test]
+async fn retries_are_announced_before_the_next_attempt() {
+    let seen = Arc::new(AtomicUsize::new(0));
+    let server = Server::start(move |_| {
+        if seen.fetch_add(1, Ordering::SeqCst) == 0 {
+            response("503 Service Unavailable", &[], "")
+        } else {
+            ok("up")
+        }
+    });
+    let client = RelayClient::new().with_retry(RetryPolicy::new(
+        2,
+        BackoffStrategy::Constant(Duration::from_millis(5)),
+    ));
+    let (result, events) = with_events(&client, server.url("/")).await;
+    let response = result.unwrap();
+
+    let kinds = kinds(&events);
+    let retry = kinds.iter().position(|kind| kind == "retry").unwrap();
+    assert!(matches!(
+        events[retry].kind,
+        TransferEventKind::Retry {
+            attempt: 1,
+            delay_ms: 5
+        }
+    ));
+    assert_eq!(kinds[retry + 1], "resolvingDns");
+    assert_eq!(
+        kinds.iter().filter(|kind| *kind == "resolvingDns").count(),
+        2
+    );
+    assert_eq!(kinds.last().map(String::as_str), Some("done"));
+    assert_numbered_in_order(&events, response.id);
+}
+
+#[tokio::test]
+async fn large_bodies_report_growing_byte_counts() {
+    let body = vec![b'x'; 300 * 1024];
+    let server = Server::start(move |_| ok(&body));
+    let (result, events) = with_events(&RelayClient::new(), server.url("/")).await;
+    result.unwrap();
+
+    let totals = events
+        .iter()
+        .filter_map(|event| match event.kind {
+            TransferEventKind::BytesReceived { total } => Some(total),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert!(!totals.is_empty());
+    assert!(
+        totals.windows(2).all(|pair| pair[0] < pair[1]),
+        "{:?}",
+        totals
+    );
+    assert!(totals.iter().all(|&total| total <= 300 * 1024));
+}
+
+#[tokio::test]
+async fn a_failed_request_ends_with_failed() {
+    // NOTE: Bound then dropped, so nothing listens on the port.
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let (result, events) =
+        with_events(&RelayClient::new(), format!("http://127.0.0.1:{}/", port)).await;
+    let error = result.unwrap_err();
+
+    let kinds = kinds(&events);
+    assert_eq!(kinds.first().map(String::as_str), Some("queued"));
+    assert!(!kinds.contains(&"done".to_string()), "{:?}", kinds);
+    assert!(matches!(
+        &events.last().unwrap().kind,
+        TransferEventKind::Failed { message } if *message == error.to_string()
+    ));
+}
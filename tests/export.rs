@@ -0,0 +1,85 @@
+mod common;
+
+use common::{ok, Server};
+use relay::{har, ApiKeyLocation, AuthType, ExportOptions, RelayClient, Request};
+
+fn with_api_key(url: &str, location: ApiKeyLocation) -> Request {
+    let mut request = Request::builder()
+        .get(url)
+        .header("Authorization", "Bearer header-secret")
+        .header("X-Trace", "visible")
+        .build()
+        .unwrap();
+    request.auth = Some(AuthType::ApiKey {
+        key: "api_key".into(),
+        value: "key-secret".into(),
+        location,
+    });
+    request
+}
+
+#[test]
+fn curl_export_redacts_secret_headers_and_query_keys() {
+    let request = with_api_key(
+        "https://example.com/items?api_key=key-secret&page=2",
+        ApiKeyLocation::Query,
+    );
+    let command = request.to_curl_command().unwrap();
+    assert!(!command.contains("header-secret"));
+    assert!(!command.contains("key-secret"));
+    assert!(command.contains("'https://example.com/items?api_key=***&page=2'"));
+    assert!(command.contains("Authorization: ***"));
+    assert!(command.contains("X-Trace: visible"));
+
+    let included = request
+        .to_curl_command_with(&ExportOptions::new().with_secrets_included(true))
+        .unwrap();
+    assert!(included.contains("api_key=key-secret"));
+    assert!(included.contains("Bearer header-secret"));
+}
+
+#[test]
+fn curl_export_redacts_api_key_headers() {
+    let mut request = Request::builder()
+        .get("https://example.com/")
+        .header("api_key", "key-secret")
+        .build()
+        .unwrap();
+    request.auth = Some(AuthType::ApiKey {
+        key: "api_key".into(),
+        value: "key-secret".into(),
+        location: ApiKeyLocation::Header,
+    });
+    let command = request
+        .to_curl_command_with(&ExportOptions::new().with_secret_headers(Vec::<String>::new()))
+        .unwrap();
+    assert!(!command.contains("key-secret"));
+}
+
+#[tokio::test]
+async fn har_export_redacts_the_url_and_query_string() {
+    let server = Server::start(|_| ok("ok"));
+    let request = with_api_key(
+        &server.url("/items?api_key=key-secret&page=2"),
+        ApiKeyLocation::Query,
+    );
+    let response = RelayClient::new().execute(request.clone()).await.unwrap();
+
+    let entry = har::to_har_entry(&request, &response);
+    assert!(entry.request.url.ends_with("/items?api_key=***&page=2"));
+    let api_key = entry
+        .request
+        .query_string
+        .iter()
+        .find(|pair| pair.name == "api_key")
+        .unwrap();
+    assert_eq!(api_key.value, "***");
+    let authorization = entry
+        .request
+        .headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case("authorization"))
+        .unwrap();
+    assert_eq!(authorization.value, "***");
+    assert!(!serde_json::to_string(&entry).unwrap().contains("secret"));
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::{response, Server};
+use http::StatusCode;
+use relay::{error::RelayError, RelayClient, Request};
+
+fn request(server: &Server, fail_on_error: Option<bool>) -> Request {
+    let mut builder = Request::builder().get(server.url("/"));
+    if let Some(fail) = fail_on_error {
+        builder = builder.fail_on_error(fail);
+    }
+    builder.build().unwrap()
+}
+
+#[tokio::test]
+async fn error_statuses_fail_with_their_body() {
+    for (status, code) in [
+        ("404 Not Found", StatusCode::NOT_FOUND),
+        (
+            "500 Internal Server Error",
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+    ] {
+        let server = Server::start(move |_| response(status, &[("X-Reason", "test")], "details"));
+        let error = RelayClient::new()
+            .execute(request(&server, Some(true)))
+            .await
+            .unwrap_err();
+
+        let RelayError::HttpStatus {
+            status,
+            headers,
+            body,
+        } = error
+        else {
+            panic!("expected an HTTP status error, got {:?}", error);
+        };
+        assert_eq!(status, code);
+        assert_eq!(&body[..], b"details");
+        assert!(headers
+            .iter()
+            .any(|(name, value)| name.eq_ignore_ascii_case("x-reason") && value == "test"));
+    }
+}
+
+#[tokio::test]
+async fn error_statuses_are_responses_without_the_flag() {
+    for (status, code) in [
+        ("404 Not Found", StatusCode::NOT_FOUND),
+        (
+            "500 Internal Server Error",
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+    ] {
+        for fail_on_error in [None, Some(false)] {
+            let server = Server::start(move |_| response(status, &[], "details"));
+            let response = RelayClient::new()
+                .execute(request(&server, fail_on_error))
+                .await
+                .unwrap();
+            assert_eq!(response.status, code);
+            assert_eq!(&response.body.body[..], b"details");
+        }
+    }
+}
+
+#[tokio::test]
+async fn success_statuses_pass_with_the_flag() {
+    let server = Server::start(|_| response("204 No Content", &[], ""));
+    let response = RelayClient::new()
+        .execute(request(&server, Some(true)))
+        .await
+        .unwrap();
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+}
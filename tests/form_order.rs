@@ -0,0 +1,76 @@
+mod common;
+
+use common::{ok, Server};
+use relay::{Request, RequestBuilder};
+
+const FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/petstore.json");
+
+async fn sent(builder: impl FnOnce(RequestBuilder) -> RequestBuilder) -> String {
+    let server = Server::start(|_| ok(""));
+    let request = builder(Request::builder().post(server.url("/")))
+        .build()
+        .unwrap();
+    relay::execute(request).await.unwrap();
+    String::from_utf8_lossy(&server.requests()[0].body).into_owned()
+}
+
+// NOTE: The `name` of each part, in the order the parts were sent.
+fn part_names(body: &str) -> Vec<&str> {
+    body.split("; name=\"")
+        .skip(1)
+        .filter_map(|rest| rest.split_once('"'))
+        .map(|(name, _)| name)
+        .collect()
+}
+
+fn multipart(builder: RequestBuilder) -> RequestBuilder {
+    builder
+        .multipart_text("meta", "first")
+        .multipart_file("file", FILE)
+        .multipart_text("meta", "second")
+        .multipart_text("checksum", "abc")
+}
+
+#[tokio::test]
+async fn multipart_parts_go_out_in_the_order_added() {
+    let body = sent(multipart).await;
+
+    assert_eq!(part_names(&body), ["meta", "file", "meta", "checksum"]);
+    let first = body.find("first").unwrap();
+    let file = body.find("petstore").unwrap();
+    let second = body.find("second").unwrap();
+    assert!(first < file && file < second);
+}
+
+#[tokio::test]
+async fn sorted_multipart_parts_keep_the_order_of_repeated_names() {
+    let body = sent(|builder| multipart(builder).sort_form_fields()).await;
+
+    assert_eq!(part_names(&body), ["checksum", "file", "meta", "meta"]);
+    assert!(body.find("first").unwrap() < body.find("second").unwrap());
+}
+
+#[tokio::test]
+async fn urlencoded_pairs_go_out_in_the_order_added() {
+    let pairs = [("z", "1"), ("a b", "2&3"), ("z", "0"), ("m", "")];
+
+    assert_eq!(
+        sent(|builder| builder.form(pairs)).await,
+        "z=1&a+b=2%263&z=0&m="
+    );
+    assert_eq!(
+        sent(|builder| builder.form(pairs).sort_form_fields()).await,
+        "a+b=2%263&m=&z=1&z=0"
+    );
+}
+
+#[tokio::test]
+async fn the_same_fields_always_encode_the_same() {
+    let once = sent(|builder| builder.form([("b", "1"), ("a", "2")])).await;
+    for _ in 0..3 {
+        assert_eq!(
+            sent(|builder| builder.form([("b", "1"), ("a", "2")])).await,
+            once
+        );
+    }
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use std::io::Write;
+
+use common::{response, Server};
+use flate2::{write::GzEncoder, Compression};
+use relay::{error::RelayError, Request};
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn serve(body: Vec<u8>) -> Server {
+    Server::start(move |_| response("200 OK", &[("Content-Type", "text/plain")], &body))
+}
+
+fn request(url: String, limit: Option<u64>) -> Request {
+    let mut request = Request::builder()
+        .get(url)
+        .detect_gzip(true)
+        .build()
+        .unwrap();
+    if let Some(options) = request.meta.as_mut().and_then(|meta| meta.options.as_mut()) {
+        options.max_decompressed_bytes = limit;
+    }
+    request
+}
+
+#[tokio::test]
+async fn undeclared_gzip_members_are_decoded_concatenated() {
+    let mut body = gzip(b"hello ");
+    body.extend(gzip(b"world"));
+    let server = serve(body);
+
+    let response = relay::execute(request(server.url("/"), None))
+        .await
+        .unwrap();
+    assert_eq!(&response.body.body[..], b"hello world");
+}
+
+#[tokio::test]
+async fn decompression_stops_at_the_limit() {
+    let server = serve(gzip(&[b'a'; 64 * 1024]));
+
+    let error = relay::execute(request(server.url("/"), Some(1024)))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RelayError::Parse { .. }));
+
+    let response = relay::execute(request(server.url("/"), Some(64 * 1024)))
+        .await
+        .unwrap();
+    assert_eq!(response.body.body.len(), 64 * 1024);
+}
+
+#[tokio::test]
+async fn declared_gzip_stops_at_the_limit() {
+    // NOTE: 16 MiB of zeros compresses to a few KiB, the limit has to catch
+    // it as curl decodes rather than after the body is in memory.
+    let body = gzip(&vec![0; 16 * 1024 * 1024]);
+    assert!(body.len() < 64 * 1024);
+    let server = Server::start(move |_| response("200 OK", &[("Content-Encoding", "gzip")], &body));
+
+    let mut request = Request::builder().get(server.url("/")).build().unwrap();
+    if let Some(options) = request.meta.as_mut().and_then(|meta| meta.options.as_mut()) {
+        options.max_decompressed_bytes = Some(64 * 1024);
+    }
+
+    let error = relay::execute(request).await.unwrap_err();
+    let RelayError::Parse { message, .. } = error else {
+        panic!("expected a parse error, got {:?}", error);
+    };
+    assert_eq!(message, "decompression limit exceeded");
+}
+
+#[tokio::test]
+async fn body_that_only_looks_like_gzip_is_kept() {
+    let mut body = gzip(b"hello");
+    body.truncate(body.len() - 4);
+    let server = serve(body.clone());
+
+    let response = relay::execute(request(server.url("/"), None))
+        .await
+        .unwrap();
+    assert_eq!(&response.body.body[..], &body[..]);
+}
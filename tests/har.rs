@@ -0,0 +1,459 @@
+mod common;
+
+use common::{response, Captured, Server};
+use http::{Method, Version};
+use jsonschema::JSONSchema;
+use relay::{
+    har::{from_har, import_har, to_har, to_har_entry},
+    PhaseMarks, RelayClient, Request, Response,
+};
+use serde_json::{json, Value};
+
+const HAR_SCHEMA: &str = include_str!("fixtures/har-1.2.schema.json");
+
+fn har(entries: Value) -> String {
+    json!({
+        "log": {
+            "version": "1.2",
+            "creator": { "name": "browser", "version": "1" },
+            "entries": entries,
+        }
+    })
+    .to_string()
+}
+
+fn entry(request: Value) -> Value {
+    json!({
+        "startedDateTime": "2024-01-01T00:00:00Z",
+        "time": 12.5,
+        "request": request,
+        "response": {
+            "status": 200,
+            "content": { "size": 0 },
+        },
+        "timings": { "send": 0, "wait": 12.5, "receive": 0 },
+    })
+}
+
+fn content(request: &relay::Request) -> Value {
+    serde_json::to_value(&request.content).unwrap()
+}
+
+#[test]
+fn imports_method_url_and_query_string() {
+    let requests = from_har(&har(json!([entry(json!({
+        "method": "PUT",
+        "url": "https://example.com/items",
+        "httpVersion": "HTTP/2",
+        "queryString": [
+            { "name": "page", "value": "2" },
+            { "name": "q", "value": "a b" },
+        ],
+    }))])))
+    .unwrap();
+
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, Method::PUT);
+    assert_eq!(requests[0].url, "https://example.com/items?page=2&q=a+b");
+    assert_eq!(requests[0].version, Version::HTTP_2);
+}
+
+#[test]
+fn missing_http_version_defaults_to_http_1_1() {
+    let requests = from_har(&har(json!([entry(json!({
+        "method": "GET",
+        "url": "https://example.com/",
+    }))])))
+    .unwrap();
+    assert_eq!(requests[0].version, Version::HTTP_11);
+}
+
+#[test]
+fn joins_duplicated_headers_and_drops_pseudo_headers() {
+    let requests = from_har(&har(json!([entry(json!({
+        "method": "GET",
+        "url": "https://example.com/",
+        "headers": [
+            { "name": ":authority", "value": "example.com" },
+            { "name": "Accept", "value": "text/html" },
+            { "name": "accept", "value": "application/json" },
+            { "name": "Cookie", "value": "a=1" },
+            { "name": "Cookie", "value": "b=2" },
+            { "name": "Content-Length", "value": "10" },
+        ],
+    }))])))
+    .unwrap();
+
+    let headers = requests[0].headers.as_ref().unwrap();
+    assert_eq!(headers.get("accept"), Some("text/html, application/json"));
+    assert_eq!(headers.get("cookie"), Some("a=1; b=2"));
+    assert!(!headers.contains_key(":authority"));
+    assert!(!headers.contains_key("content-length"));
+}
+
+#[test]
+fn builds_a_cookie_header_from_cookies() {
+    let requests = from_har(&har(json!([entry(json!({
+        "method": "GET",
+        "url": "https://example.com/",
+        "cookies": [
+            { "name": "session", "value": "abc" },
+            { "name": "theme", "value": "dark" },
+        ],
+    }))])))
+    .unwrap();
+
+    let headers = requests[0].headers.as_ref().unwrap();
+    assert_eq!(headers.get("cookie"), Some("session=abc; theme=dark"));
+}
+
+#[test]
+fn urlencoded_text_wins_over_params() {
+    let requests = from_har(&har(json!([entry(json!({
+        "method": "POST",
+        "url": "https://example.com/login",
+        "postData": {
+            "mimeType": "application/x-www-form-urlencoded",
+            "text": "user=a%20b&remember=1",
+            "params": [
+                { "name": "user", "value": "a b" },
+                { "name": "remember", "value": "1" },
+            ],
+        },
+    }))])))
+    .unwrap();
+
+    let content = content(&requests[0]);
+    assert_eq!(content["kind"], "urlencoded");
+    assert_eq!(content["content"], "user=a%20b&remember=1");
+}
+
+#[test]
+fn urlencoded_params_are_encoded_without_text() {
+    let requests = from_har(&har(json!([entry(json!({
+        "method": "POST",
+        "url": "https://example.com/login",
+        "postData": {
+            "mimeType": "application/x-www-form-urlencoded",
+            "params": [
+                { "name": "user", "value": "a b" },
+                { "name": "remember", "value": "1" },
+            ],
+        },
+    }))])))
+    .unwrap();
+
+    assert_eq!(content(&requests[0])["content"], "user=a+b&remember=1");
+}
+
+#[test]
+fn decodes_base64_bodies() {
+    let requests = from_har(&har(json!([entry(json!({
+        "method": "POST",
+        "url": "https://example.com/upload",
+        "postData": {
+            "mimeType": "application/octet-stream",
+            "text": "AAEC/w==",
+            "encoding": "base64",
+        },
+    }))])))
+    .unwrap();
+
+    let content = content(&requests[0]);
+    assert_eq!(content["kind"], "binary");
+    assert_eq!(content["content"], json!([0, 1, 2, 255]));
+}
+
+#[test]
+fn rebuilds_multipart_with_base64_file_parts() {
+    let requests = from_har(&har(json!([entry(json!({
+        "method": "POST",
+        "url": "https://example.com/upload",
+        "headers": [
+            { "name": "Content-Type", "value": "multipart/form-data; boundary=recorded" },
+        ],
+        "postData": {
+            "mimeType": "multipart/form-data; boundary=recorded",
+            "params": [
+                { "name": "title", "value": "report" },
+                {
+                    "name": "file",
+                    "value": "aGk=",
+                    "fileName": "hi.txt",
+                    "contentType": "text/plain",
+                    "_encoding": "base64",
+                },
+            ],
+        },
+    }))])))
+    .unwrap();
+
+    assert!(requests[0].headers.is_none());
+    let content = content(&requests[0]);
+    assert_eq!(content["kind"], "multipart");
+    assert_eq!(
+        content["content"],
+        json!([
+            ["title", [{ "kind": "text", "value": "report" }]],
+            [
+                "file",
+                [{
+                    "kind": "file",
+                    "filename": "hi.txt",
+                    "contentType": "text/plain",
+                    "data": [104, 105],
+                }]
+            ],
+        ])
+    );
+}
+
+#[test]
+fn imports_entries_missing_fields_outside_the_request() {
+    let requests = from_har(&har(json!([
+        { "request": { "method": "GET", "url": "https://example.com/a" } },
+        {
+            "request": { "method": "GET", "url": "https://example.com/b" },
+            "timings": { "wait": 3 },
+            "response": { "status": 200 },
+        },
+    ])))
+    .unwrap();
+
+    let urls = requests.iter().map(|r| r.url.as_str()).collect::<Vec<_>>();
+    assert_eq!(urls, ["https://example.com/a", "https://example.com/b"]);
+}
+
+#[test]
+fn skips_unmappable_entries_with_diagnostics() {
+    let imported = import_har(&har(json!([
+        entry(json!({ "method": "GET", "url": "https://example.com/ok" })),
+        { "response": { "status": 200 } },
+        entry(json!({ "method": "GET" })),
+        entry(json!({ "method": "GET", "url": "not a url" })),
+        entry(json!({
+            "method": "POST",
+            "url": "https://example.com/",
+            "postData": { "mimeType": "text/plain", "text": "%%%", "encoding": "base64" },
+        })),
+        entry(json!({ "method": "GET", "url": "https://example.com/also-ok" })),
+    ])))
+    .unwrap();
+
+    let urls = imported
+        .requests
+        .iter()
+        .map(|r| r.url.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        urls,
+        ["https://example.com/ok", "https://example.com/also-ok"]
+    );
+
+    let skipped = imported
+        .diagnostics
+        .iter()
+        .map(|d| d.entry)
+        .collect::<Vec<_>>();
+    assert_eq!(skipped, [1, 2, 3, 4]);
+    assert_eq!(imported.diagnostics[0].message, "Entry has no request");
+    assert!(imported.diagnostics[3].message.contains("base64"));
+}
+
+#[test]
+fn rejects_documents_without_entries() {
+    assert!(import_har(r#"{"log": {"version": "1.2"}}"#).is_err());
+    assert!(import_har("not json").is_err());
+}
+
+fn assert_valid_har(har: &str) {
+    let schema = JSONSchema::compile(&serde_json::from_str(HAR_SCHEMA).unwrap()).unwrap();
+    let instance: Value = serde_json::from_str(har).unwrap();
+    if let Err(errors) = schema.validate(&instance) {
+        let errors = errors
+            .map(|error| format!("{} at {}", error, error.instance_path))
+            .collect::<Vec<_>>();
+        panic!("invalid HAR:\n{}\n{}", errors.join("\n"), har);
+    }
+}
+
+// NOTE: The request line, `Content-Type` and body, with any multipart
+// boundary replaced since curl picks a fresh one per request.
+fn wire(captured: &Captured) -> (String, Option<String>, String) {
+    let content_type = captured.header("content-type");
+    let mut body = String::from_utf8_lossy(&captured.body).into_owned();
+    if let Some((_, boundary)) = content_type
+        .as_deref()
+        .and_then(|value| value.split_once("boundary="))
+    {
+        body = body.replace(boundary, "BOUNDARY");
+    }
+    let content_type = content_type.map(|value| match value.split_once(';') {
+        Some((essence, _)) => essence.to_string(),
+        None => value,
+    });
+    (captured.request_line().to_string(), content_type, body)
+}
+
+// NOTE: `blocked`, `dns`, `connect`, `ssl`, `send`, `wait` and `receive`.
+fn timings_for(response: &mut Response, marks: PhaseMarks) -> [f64; 7] {
+    response.meta.timing.phases = Some(marks);
+    let request = Request::builder()
+        .get("http://example.com/")
+        .build()
+        .unwrap();
+    let timings = to_har_entry(&request, response).timings;
+    [
+        timings.blocked,
+        timings.dns,
+        timings.connect,
+        timings.ssl,
+        timings.send,
+        timings.wait,
+        timings.receive,
+    ]
+}
+
+#[tokio::test]
+async fn exported_entries_match_the_schema_and_import_back() {
+    let server = Server::start(|captured| {
+        let body: &[u8] = match captured.request_line().starts_with("GET") {
+            true => &[0, 159, 146, 150],
+            false => b"{\"ok\":true}",
+        };
+        response(
+            "200 OK",
+            &[("Set-Cookie", "theme=dark; Path=/; HttpOnly")],
+            body,
+        )
+    });
+    let requests = [
+        Request::builder()
+            .get(server.url("/items?page=2&q=a+b"))
+            .header("X-Trace", "1"),
+        Request::builder()
+            .post(server.url("/items"))
+            .json(&json!({ "name": "relay", "tags": ["http"] })),
+        Request::builder()
+            .put(server.url("/items/1"))
+            .form([("name", "a b"), ("note", "x&y")]),
+        Request::builder()
+            .post(server.url("/upload"))
+            .multipart_text("title", "report")
+            .multipart_text("title", "draft"),
+        Request::builder()
+            .patch(server.url("/items/1"))
+            .text("plain text"),
+    ]
+    .map(|builder| builder.build().unwrap());
+
+    let client = RelayClient::new();
+    let mut responses = Vec::new();
+    for request in &requests {
+        responses.push(client.execute(request.clone()).await.unwrap());
+    }
+    let har = to_har(requests.iter().zip(&responses)).unwrap();
+    assert_valid_har(&har);
+
+    let imported = from_har(&har).unwrap();
+    assert_eq!(imported.len(), requests.len());
+    for request in imported {
+        client.execute(request).await.unwrap();
+    }
+
+    let sent = server.requests();
+    let (original, replayed) = sent.split_at(requests.len());
+    for (original, replayed) in original.iter().zip(replayed) {
+        assert_eq!(wire(original), wire(replayed));
+    }
+    assert_eq!(original[0].header("x-trace").as_deref(), Some("1"));
+    assert_eq!(replayed[0].header("x-trace").as_deref(), Some("1"));
+}
+
+#[tokio::test]
+async fn transfer_timings_add_up_to_the_entry_time() {
+    let server = Server::start(|_| response("200 OK", &[], "ok"));
+    let request = Request::builder().get(server.url("/")).build().unwrap();
+    let response = RelayClient::new().execute(request.clone()).await.unwrap();
+    assert!(response.meta.timing.phases.is_some());
+
+    let entry = to_har_entry(&request, &response);
+    let timings = &entry.timings;
+    let phases = [
+        timings.blocked,
+        timings.dns,
+        timings.connect,
+        timings.send,
+        timings.wait,
+        timings.receive,
+    ];
+    let total = phases.iter().filter(|time| **time >= 0.0).sum::<f64>();
+    assert!((total - entry.time).abs() < 1e-6, "{:?}", timings);
+    // NOTE: Plain HTTP, there is no handshake.
+    assert_eq!(timings.ssl, -1.0);
+    assert_valid_har(&to_har([(&request, &response)]).unwrap());
+}
+
+#[tokio::test]
+async fn maps_curl_milestones_onto_har_phases() {
+    let server = Server::start(|_| response("200 OK", &[], "ok"));
+    let request = Request::builder().get(server.url("/")).build().unwrap();
+    let mut response = RelayClient::new().execute(request).await.unwrap();
+
+    let tls = PhaseMarks {
+        redirect: 0.0,
+        namelookup: 2.0,
+        connect: 5.0,
+        appconnect: 12.0,
+        pretransfer: 12.5,
+        starttransfer: 40.0,
+        total: 45.0,
+    };
+    // NOTE: `connect` includes the 7ms of `ssl`.
+    assert_eq!(
+        timings_for(&mut response, tls),
+        [-1.0, 2.0, 10.0, 7.0, 0.0, 28.0, 5.0]
+    );
+
+    let reused = PhaseMarks {
+        pretransfer: 0.5,
+        starttransfer: 20.0,
+        total: 21.5,
+        ..PhaseMarks::default()
+    };
+    assert_eq!(
+        timings_for(&mut response, reused),
+        [-1.0, -1.0, -1.0, -1.0, 0.0, 20.0, 1.5]
+    );
+
+    let redirected = PhaseMarks {
+        redirect: 30.0,
+        namelookup: 31.0,
+        connect: 32.0,
+        appconnect: 0.0,
+        pretransfer: 32.0,
+        starttransfer: 50.0,
+        total: 50.0,
+    };
+    assert_eq!(
+        timings_for(&mut response, redirected),
+        [30.0, 1.0, 1.0, -1.0, 0.0, 18.0, 0.0]
+    );
+}
+
+#[tokio::test]
+async fn responses_without_milestones_only_have_a_wait() {
+    let server = Server::start(|_| response("200 OK", &[], "ok"));
+    let request = Request::builder().get(server.url("/")).build().unwrap();
+    let mut response = RelayClient::new().execute(request.clone()).await.unwrap();
+    response.meta.timing.phases = None;
+    response.meta.timing.end = response.meta.timing.start + 25;
+
+    let entry = to_har_entry(&request, &response);
+    let timings = entry.timings;
+    assert_eq!(entry.time, 25.0);
+    assert_eq!(
+        [timings.dns, timings.connect, timings.ssl, timings.wait],
+        [-1.0, -1.0, -1.0, 25.0]
+    );
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use common::{ok, response, Captured, Server};
+use relay::{Headers, RelayClient, Request};
+
+// NOTE: The `X-` header lines as they arrived, in wire order.
+fn custom_lines(captured: &Captured) -> Vec<&str> {
+    captured
+        .head
+        .lines()
+        .skip(1)
+        .map(str::trim_end)
+        .filter(|line| line.starts_with("X-"))
+        .collect()
+}
+
+#[tokio::test]
+async fn sends_headers_in_the_callers_order() {
+    let server = Server::start(|_| ok("ok"));
+    let request = Request::builder()
+        .get(server.url("/"))
+        .header("X-Zeta", "1")
+        .header("X-Alpha", "2")
+        .append_header("X-Zeta", "3")
+        .header("X-Mid", "4")
+        .build()
+        .unwrap();
+
+    RelayClient::new().execute(request).await.unwrap();
+
+    assert_eq!(
+        custom_lines(&server.requests()[0]),
+        ["X-Zeta: 1", "X-Alpha: 2", "X-Zeta: 3", "X-Mid: 4"]
+    );
+}
+
+#[tokio::test]
+async fn keeps_the_order_from_json() {
+    let server = Server::start(|_| ok("ok"));
+    let request: Request = serde_json::from_str(&format!(
+        r#"{{
+            "id": 0,
+            "url": "{}",
+            "method": "GET",
+            "version": "HTTP/1.1",
+            "headers": {{ "X-Zeta": ["1", "3"], "X-Alpha": "2", "X-Mid": ["4"] }}
+        }}"#,
+        server.url("/")
+    ))
+    .unwrap();
+
+    RelayClient::new().execute(request).await.unwrap();
+
+    assert_eq!(
+        custom_lines(&server.requests()[0]),
+        ["X-Zeta: 1", "X-Zeta: 3", "X-Alpha: 2", "X-Mid: 4"]
+    );
+}
+
+#[test]
+fn accepts_the_object_of_arrays_shape() {
+    let headers: Headers =
+        serde_json::from_str(r#"{"X-B": ["1", "2"], "X-A": ["3"], "X-Gone": []}"#).unwrap();
+
+    let pairs = headers
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(pairs, [("X-B", "1"), ("X-B", "2"), ("X-A", "3")]);
+    assert!(headers.is_suppressed("x-gone"));
+}
+
+#[test]
+fn accepts_the_object_of_strings_shape() {
+    let headers: Headers = serde_json::from_str(r#"{"X-B": "1", "X-A": "2"}"#).unwrap();
+    assert_eq!(headers.keys().collect::<Vec<_>>(), ["X-B", "X-A"]);
+    assert_eq!(headers.get("x-a"), Some("2"));
+}
+
+#[test]
+fn serializes_single_values_as_strings() {
+    let mut headers = Headers::new();
+    headers.append("X-B", "1");
+    headers.append("X-A", "2");
+    headers.append("X-B", "3");
+    headers.suppress("Accept");
+
+    let json = serde_json::to_string(&headers).unwrap();
+    assert_eq!(json, r#"{"X-B":["1","3"],"X-A":"2","Accept":[]}"#);
+
+    // NOTE: Repeats of a name come back grouped at its first position.
+    let parsed = serde_json::from_str::<Headers>(&json).unwrap();
+    assert_eq!(parsed.get_all("x-b").collect::<Vec<_>>(), ["1", "3"]);
+    assert!(parsed.is_suppressed("accept"));
+}
+
+#[tokio::test]
+async fn joins_repeated_response_headers_with_commas() {
+    let server = Server::start(|_| {
+        response(
+            "200 OK",
+            &[
+                ("Vary", "Accept"),
+                ("Vary", "Accept-Encoding"),
+                ("vary", "Origin"),
+            ],
+            "",
+        )
+    });
+    let request = Request::builder().get(server.url("/")).build().unwrap();
+
+    let response = RelayClient::new().execute(request).await.unwrap();
+    // NOTE: Whatever casing the name is asked for or arrived in.
+    assert_eq!(
+        response.header_joined("VARY").as_deref(),
+        Some("Accept, Accept-Encoding, Origin")
+    );
+    assert_eq!(response.header_joined("x-missing"), None);
+}
+
+#[tokio::test]
+async fn keeps_set_cookie_values_one_per_line() {
+    // NOTE: `Expires` has a comma of its own, comma joining would make the
+    // cookies impossible to split apart again.
+    let server = Server::start(|_| {
+        response(
+            "200 OK",
+            &[
+                ("Set-Cookie", "a=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT"),
+                ("Set-Cookie", "b=2"),
+            ],
+            "",
+        )
+    });
+    let request = Request::builder().get(server.url("/")).build().unwrap();
+
+    let response = RelayClient::new().execute(request).await.unwrap();
+    assert_eq!(
+        response.header_joined("set-cookie").as_deref(),
+        Some("a=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT\nb=2")
+    );
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::{ok, Server};
+use relay::{
+    error::{RelayError, Result},
+    Interceptor, RelayClient, Request, Response,
+};
+
+#[derive(Clone, Default)]
+struct Log(Arc<Mutex<Vec<String>>>);
+
+impl Log {
+    fn push(&self, entry: String) {
+        self.0.lock().unwrap().push(entry);
+    }
+
+    fn entries(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+struct Step {
+    name: &'static str,
+    log: Log,
+    fail_before: bool,
+    fail_after: bool,
+}
+
+impl Step {
+    fn new(name: &'static str, log: &Log) -> Self {
+        Self {
+            name,
+            log: log.clone(),
+            fail_before: false,
+            fail_after: false,
+        }
+    }
+
+    fn failing_before(mut self) -> Self {
+        self.fail_before = true;
+        self
+    }
+
+    fn failing_after(mut self) -> Self {
+        self.fail_after = true;
+        self
+    }
+
+    fn refusal(&self, stage: &str) -> RelayError {
+        RelayError::Abort {
+            message: format!("{} refused in {}", self.name, stage),
+        }
+    }
+}
+
+impl Interceptor for Step {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn before_send(&self, request: &mut Request) -> Result<()> {
+        self.log.push(format!("before {}", self.name));
+        if self.fail_before {
+            return Err(self.refusal("before_send"));
+        }
+        let headers = request.headers.get_or_insert_with(Default::default);
+        headers.append("X-Seen-By", self.name);
+        Ok(())
+    }
+
+    fn after_receive(&self, _request: &Request, response: &mut Response) -> Result<()> {
+        self.log.push(format!("after {}", self.name));
+        if self.fail_after {
+            return Err(self.refusal("after_receive"));
+        }
+        response.status_text.push_str(self.name);
+        Ok(())
+    }
+}
+
+fn request(server: &Server) -> Request {
+    Request::builder().get(server.url("/")).build().unwrap()
+}
+
+fn failed_in(error: RelayError) -> (String, String) {
+    let RelayError::Interceptor { name, error } = error else {
+        panic!("expected an interceptor error, got {:?}", error);
+    };
+    let RelayError::Abort { message } = *error else {
+        panic!("expected the interceptor's own error, got {:?}", error);
+    };
+    (name, message)
+}
+
+#[tokio::test]
+async fn runs_requests_forward_and_responses_in_reverse() {
+    let server = Server::start(|_| ok("ok"));
+    let log = Log::default();
+    let client = RelayClient::new()
+        .with_interceptor(Step::new("a", &log))
+        .with_interceptor(Step::new("b", &log))
+        .with_interceptor(Step::new("c", &log));
+
+    let response = client.execute(request(&server)).await.unwrap();
+
+    assert_eq!(
+        log.entries(),
+        ["before a", "before b", "before c", "after c", "after b", "after a"]
+    );
+    assert!(response.status_text.ends_with("cba"));
+    let sent = &server.requests()[0];
+    let seen_by = sent
+        .head
+        .lines()
+        .filter_map(|line| line.strip_prefix("X-Seen-By: "))
+        .collect::<Vec<_>>();
+    assert_eq!(seen_by, ["a", "b", "c"]);
+}
+
+#[tokio::test]
+async fn before_send_failure_stops_the_chain_and_the_request() {
+    let server = Server::start(|_| ok("ok"));
+    let log = Log::default();
+    let client = RelayClient::new()
+        .with_interceptor(Step::new("a", &log))
+        .with_interceptor(Step::new("b", &log).failing_before())
+        .with_interceptor(Step::new("c", &log));
+
+    let error = client.execute(request(&server)).await.unwrap_err();
+
+    assert_eq!(
+        failed_in(error),
+        ("b".to_string(), "b refused in before_send".to_string())
+    );
+    assert_eq!(log.entries(), ["before a", "before b"]);
+    assert!(server.requests().is_empty());
+}
+
+#[tokio::test]
+async fn after_receive_failure_skips_the_outer_interceptors() {
+    let server = Server::start(|_| ok("ok"));
+    let log = Log::default();
+    let client = RelayClient::new()
+        .with_interceptor(Step::new("a", &log))
+        .with_interceptor(Step::new("b", &log).failing_after())
+        .with_interceptor(Step::new("c", &log));
+
+    let error = client.execute(request(&server)).await.unwrap_err();
+
+    assert_eq!(
+        failed_in(error),
+        ("b".to_string(), "b refused in after_receive".to_string())
+    );
+    assert_eq!(
+        log.entries(),
+        ["before a", "before b", "before c", "after c", "after b"]
+    );
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn names_default_to_the_type_name() {
+    struct Unnamed;
+    impl Interceptor for Unnamed {}
+
+    assert!(Unnamed.name().ends_with("Unnamed"));
+}
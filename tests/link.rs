@@ -0,0 +1,98 @@
+mod common;
+
+use common::{response, Server};
+use relay::{RelayClient, Request};
+
+#[tokio::test]
+async fn reads_links_from_repeated_headers_against_the_final_url() {
+    let server = Server::start(|_| {
+        response(
+            "200 OK",
+            &[
+                ("Link", "<?page=3>; rel=\"next\""),
+                ("Link", "</items?page=1>; rel=\"first prev\""),
+            ],
+            "[]",
+        )
+    });
+    let request = Request::builder()
+        .get(server.url("/items?page=2"))
+        .build()
+        .unwrap();
+    let response = RelayClient::new().execute(request).await.unwrap();
+
+    let links = response.links();
+    assert_eq!(links["next"].url, server.url("/items?page=3"));
+    assert_eq!(links["first"].url, server.url("/items?page=1"));
+    assert_eq!(links["prev"].url, server.url("/items?page=1"));
+}
+
+#[tokio::test]
+async fn no_link_header_means_no_links() {
+    let server = Server::start(|_| response("200 OK", &[], ""));
+    let request = Request::builder().get(server.url("/")).build().unwrap();
+    let response = RelayClient::new().execute(request).await.unwrap();
+    assert!(response.links().is_empty());
+}
+
+#[tokio::test]
+async fn keeps_the_links_of_early_hints() {
+    let server = Server::start(|_| {
+        let mut raw = b"HTTP/1.1 103 Early Hints\r\n\
+            Link: </style.css>; rel=preload; as=style\r\n\
+            Link: </app.js>; rel=preload; as=script\r\n\r\n"
+            .to_vec();
+        raw.extend(response(
+            "200 OK",
+            &[("Link", "</next>; rel=next")],
+            "<html></html>",
+        ));
+        raw
+    });
+    let request = Request::builder().get(server.url("/")).build().unwrap();
+    let response = RelayClient::new().execute(request).await.unwrap();
+
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        response.early_hints,
+        [
+            "</style.css>; rel=preload; as=style",
+            "</app.js>; rel=preload; as=script",
+        ]
+    );
+    let hinted = response
+        .early_hint_links()
+        .into_iter()
+        .map(|link| (link.url, link.rel, link.params["as"].clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        hinted,
+        [
+            (
+                server.url("/style.css"),
+                "preload".to_string(),
+                "style".to_string()
+            ),
+            (
+                server.url("/app.js"),
+                "preload".to_string(),
+                "script".to_string()
+            ),
+        ]
+    );
+    // NOTE: The interim response's headers are not the final ones.
+    assert_eq!(response.links()["next"].url, server.url("/next"));
+    assert_eq!(
+        response.header_joined("link").as_deref(),
+        Some("</next>; rel=next")
+    );
+}
+
+#[tokio::test]
+async fn no_interim_response_means_no_early_hints() {
+    let server = Server::start(|_| response("200 OK", &[], ""));
+    let request = Request::builder().get(server.url("/")).build().unwrap();
+    let response = RelayClient::new().execute(request).await.unwrap();
+    assert!(response.early_hints.is_empty());
+    assert!(response.early_hint_links().is_empty());
+}
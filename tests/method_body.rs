@@ -0,0 +1,201 @@
+mod common;
+
+use common::{ok, response, Captured, Server};
+use http::Method;
+use relay::{RelayClient, Request, ValidationCode, ValidationSeverity};
+use serde_json::json;
+
+const PROPFIND: &str =
+    r#"<?xml version="1.0"?><propfind xmlns="DAV:"><prop><getetag/></prop></propfind>"#;
+
+// NOTE: Answers with the request line and body it received.
+fn echo(captured: &Captured) -> Vec<u8> {
+    let mut body = format!("{}\n", captured.request_line()).into_bytes();
+    body.extend_from_slice(&captured.body);
+    response("207 Multi-Status", &[], body)
+}
+
+fn propfind(url: String, follow_redirects: bool) -> Request {
+    let mut request = Request::builder()
+        .method(Method::from_bytes(b"PROPFIND").unwrap(), url)
+        .header("Depth", "1")
+        .follow_redirects(follow_redirects)
+        .build()
+        .unwrap();
+    request.content = serde_json::from_value(json!({
+        "kind": "xml",
+        "content": PROPFIND,
+        "mediaType": "application/xml",
+    }))
+    .unwrap();
+    request
+}
+
+#[tokio::test]
+async fn delete_sends_its_json_content() {
+    let server = Server::start(|_| ok("deleted"));
+    let request = Request::builder()
+        .delete(server.url("/items"))
+        .json(&json!({ "ids": [1, 2] }))
+        .build()
+        .unwrap();
+    assert!(request.validation_issues().is_empty());
+
+    RelayClient::new().execute(request).await.unwrap();
+
+    let sent = &server.requests()[0];
+    assert_eq!(sent.request_line(), "DELETE /items HTTP/1.1");
+    assert_eq!(sent.body, br#"{"ids":[1,2]}"#);
+    assert_eq!(
+        sent.header("content-type").as_deref(),
+        Some("application/json")
+    );
+}
+
+#[tokio::test]
+async fn head_content_is_dropped_with_a_warning() {
+    let server = Server::start(|_| response("200 OK", &[], ""));
+    let request = Request::builder()
+        .head(server.url("/"))
+        .text("ignored")
+        .build()
+        .unwrap();
+
+    let issues = request.validation_issues();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].code, ValidationCode::BodyNotAllowed);
+    assert_eq!(issues[0].severity, ValidationSeverity::Warning);
+    assert!(request.validate().is_ok());
+
+    RelayClient::new().execute(request).await.unwrap();
+
+    let sent = &server.requests()[0];
+    assert_eq!(sent.request_line(), "HEAD / HTTP/1.1");
+    assert!(sent.body.is_empty());
+    assert_eq!(sent.header("content-type"), None);
+}
+
+#[test]
+fn trace_and_connect_content_is_reported() {
+    for method in [Method::TRACE, Method::CONNECT] {
+        let request = Request::builder()
+            .method(method.clone(), "http://example.com/")
+            .text("ignored")
+            .build()
+            .unwrap();
+
+        let codes = request
+            .validation_issues()
+            .into_iter()
+            .map(|issue| (issue.code, issue.severity))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            codes,
+            [(ValidationCode::BodyNotAllowed, ValidationSeverity::Warning)],
+            "{}",
+            method
+        );
+    }
+}
+
+#[tokio::test]
+async fn propfind_sends_its_xml_content() {
+    let server = Server::start(echo);
+    let response = RelayClient::new()
+        .execute(propfind(server.url("/dav/"), false))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status, 207);
+    assert_eq!(
+        String::from_utf8_lossy(&response.body.body),
+        format!("PROPFIND /dav/ HTTP/1.1\n{}", PROPFIND)
+    );
+    let sent = &server.requests()[0];
+    assert_eq!(sent.body, PROPFIND.as_bytes());
+    assert_eq!(sent.header("depth").as_deref(), Some("1"));
+    assert_eq!(
+        sent.header("content-type").as_deref(),
+        Some("application/xml")
+    );
+}
+
+#[tokio::test]
+async fn made_up_methods_are_sent_as_written() {
+    let server = Server::start(echo);
+    let foo = Method::from_bytes(b"FOO").unwrap();
+    let client = RelayClient::new();
+
+    let with_body = Request::builder()
+        .method(foo.clone(), server.url("/a"))
+        .text("bar")
+        .build()
+        .unwrap();
+    let without_body = Request::builder()
+        .method(foo, server.url("/b"))
+        .build()
+        .unwrap();
+    let first = client.execute(with_body).await.unwrap();
+    let second = client.execute(without_body).await.unwrap();
+
+    assert_eq!(first.body.body.as_ref(), b"FOO /a HTTP/1.1\nbar");
+    assert_eq!(second.body.body.as_ref(), b"FOO /b HTTP/1.1\n");
+    let sent = server.requests();
+    assert_eq!(
+        sent[0].header("content-type").as_deref(),
+        Some("text/plain")
+    );
+    assert_eq!(sent[1].header("content-length"), None);
+}
+
+// NOTE: Redirects to `/new` from `/old`, echoing everything else.
+fn redirecting(status: &'static str) -> Server {
+    Server::start(move |captured| {
+        if captured.request_line().contains(" /old ") {
+            response(status, &[("Location", "/new")], "")
+        } else {
+            echo(captured)
+        }
+    })
+}
+
+#[tokio::test]
+async fn redirects_keep_the_method_and_content() {
+    for status in [
+        "301 Moved Permanently",
+        "302 Found",
+        "307 Temporary Redirect",
+        "308 Permanent Redirect",
+    ] {
+        let server = redirecting(status);
+        let response = RelayClient::new()
+            .execute(propfind(server.url("/old"), true))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8_lossy(&response.body.body),
+            format!("PROPFIND /new HTTP/1.1\n{}", PROPFIND),
+            "{}",
+            status
+        );
+        assert_eq!(response.final_url, server.url("/new"));
+    }
+}
+
+#[tokio::test]
+async fn see_other_switches_to_get_without_content() {
+    // NOTE: Older libcurl cannot follow by status code and repeats the
+    // method instead.
+    if curl::Version::get().version_num() < 0x08_0d_00 {
+        return;
+    }
+    let server = redirecting("303 See Other");
+    let response = RelayClient::new()
+        .execute(propfind(server.url("/old"), true))
+        .await
+        .unwrap();
+
+    assert_eq!(response.body.body.as_ref(), b"GET /new HTTP/1.1\n");
+    assert!(server.requests()[1].body.is_empty());
+}
@@ -0,0 +1,101 @@
+#![cfg(feature = "metrics")]
+
+mod common;
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use common::{ok, response, Server};
+use relay::{
+    metrics::{self, MetricsSink, BYTES_SENT_TOTAL, REQUESTS_TOTAL, RETRIES_TOTAL},
+    BackoffStrategy, RelayClient, Request, RetryPolicy,
+};
+
+// NOTE: One counter increment, its name, labels and value.
+type Increment = (String, Vec<String>, u64);
+
+// NOTE: Keeps every counter increment, labels flattened to `name=value`.
+#[derive(Clone, Default)]
+struct RecordingSink {
+    counters: Arc<Mutex<Vec<Increment>>>,
+}
+
+impl RecordingSink {
+    fn total(&self, name: &str) -> u64 {
+        self.counters
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(counter, _, _)| counter == name)
+            .map(|(_, _, value)| value)
+            .sum()
+    }
+
+    fn labels(&self, name: &str) -> Vec<Vec<String>> {
+        self.counters
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(counter, _, _)| counter == name)
+            .map(|(_, labels, _)| labels.clone())
+            .collect()
+    }
+}
+
+impl MetricsSink for RecordingSink {
+    fn increment_counter(&self, name: &str, labels: &[(&str, &str)], value: u64) {
+        let labels = labels
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        self.counters
+            .lock()
+            .unwrap()
+            .push((name.to_string(), labels, value));
+    }
+
+    fn record_histogram(&self, _: &str, _: &[(&str, &str)], _: f64) {}
+}
+
+// NOTE: One test, the sink is process-wide.
+#[tokio::test]
+async fn counts_requests_once_and_bytes_per_attempt() {
+    let sink = RecordingSink::default();
+    metrics::set_metrics_sink(sink.clone());
+
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let server = {
+        let attempts = attempts.clone();
+        Server::start(move |_| match attempts.fetch_add(1, Ordering::SeqCst) {
+            0 => response("503 Service Unavailable", &[], ""),
+            _ => ok("done"),
+        })
+    };
+    let client = RelayClient::new().with_retry(RetryPolicy::new(
+        2,
+        BackoffStrategy::Constant(Duration::from_millis(1)),
+    ));
+
+    let body = "x".repeat(1000);
+    let request = Request::builder()
+        .put(server.url("/upload"))
+        .text(body)
+        .build()
+        .unwrap();
+    let response = client.execute(request).await.unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+    assert_eq!(sink.total(REQUESTS_TOTAL), 1);
+    assert!(sink.labels(REQUESTS_TOTAL)[0].contains(&"status_class=2xx".to_string()));
+    assert_eq!(sink.total(RETRIES_TOTAL), 1);
+    // NOTE: Both attempts carried the body.
+    assert!(sink.total(BYTES_SENT_TOTAL) > 2000);
+
+    metrics::clear_metrics_sink();
+}
@@ -0,0 +1,298 @@
+#![cfg(feature = "mock")]
+
+use http::{Method, StatusCode};
+use relay::{
+    error::RelayError,
+    mock::{BodyMatcher, MockExpectation, MockTransport, UrlMatcher},
+    RelayClient, Request,
+};
+use serde_json::json;
+
+fn client(transport: &MockTransport) -> RelayClient {
+    RelayClient::new().with_transport(transport.clone())
+}
+
+fn get(url: &str) -> Request {
+    Request::builder().get(url).build().unwrap()
+}
+
+fn aborted(error: RelayError) -> String {
+    let RelayError::Abort { message } = error else {
+        panic!("expected an abort, got {:?}", error);
+    };
+    message
+}
+
+#[tokio::test]
+async fn matches_exact_urls_only() {
+    let transport = MockTransport::new();
+    transport.expect(
+        MockExpectation::new(Method::GET, "https://example.com/items")
+            .respond(MockTransport::response(StatusCode::OK, "items")),
+    );
+    let client = client(&transport);
+
+    let response = client
+        .execute(get("https://example.com/items"))
+        .await
+        .unwrap();
+    assert_eq!(response.body.body.as_ref(), b"items");
+
+    let error = client
+        .execute(get("https://example.com/items/1"))
+        .await
+        .unwrap_err();
+    assert!(aborted(error).contains("https://example.com/items/1"));
+}
+
+#[tokio::test]
+async fn matches_urls_by_pattern() {
+    let transport = MockTransport::new();
+    transport.expect(
+        MockExpectation::matching(
+            Method::GET,
+            UrlMatcher::regex(r"^https://example\.com/items/\d+$").unwrap(),
+        )
+        .respond(MockTransport::response(StatusCode::OK, "item")),
+    );
+    let client = client(&transport);
+
+    for id in [1, 42] {
+        let url = format!("https://example.com/items/{}", id);
+        assert_eq!(
+            client.execute(get(&url)).await.unwrap().status,
+            StatusCode::OK
+        );
+    }
+    assert!(client
+        .execute(get("https://example.com/items/abc"))
+        .await
+        .is_err());
+}
+
+#[test]
+fn rejects_invalid_url_patterns() {
+    assert!(matches!(
+        UrlMatcher::regex("(unclosed"),
+        Err(RelayError::Parse { .. })
+    ));
+}
+
+#[tokio::test]
+async fn matches_headers_case_insensitively() {
+    let transport = MockTransport::new();
+    transport.expect(
+        MockExpectation::new(Method::GET, "https://example.com/")
+            .header("x-api-key", "secret")
+            .respond(MockTransport::response(StatusCode::OK, "ok")),
+    );
+    let client = client(&transport);
+
+    let request = Request::builder()
+        .get("https://example.com/")
+        .header("X-Api-Key", "secret")
+        .build()
+        .unwrap();
+    assert!(client.execute(request).await.is_ok());
+
+    let request = Request::builder()
+        .get("https://example.com/")
+        .header("X-Api-Key", "other")
+        .build()
+        .unwrap();
+    assert!(client.execute(request).await.is_err());
+}
+
+#[tokio::test]
+async fn matches_bodies() {
+    let cases = [
+        (
+            BodyMatcher::Exact("name=relay".into()),
+            "name=relay",
+            "name=other",
+        ),
+        (
+            BodyMatcher::Contains("relay".into()),
+            "name=relay",
+            "name=other",
+        ),
+        (
+            BodyMatcher::Json(json!({ "a": 1, "b": [true] })),
+            r#"{ "b": [true], "a": 1 }"#,
+            r#"{"a":2,"b":[true]}"#,
+        ),
+    ];
+
+    for (matcher, matching, other) in cases {
+        let transport = MockTransport::new();
+        transport.expect(
+            MockExpectation::new(Method::POST, "https://example.com/")
+                .body(matcher.clone())
+                .respond(MockTransport::response(StatusCode::OK, "ok")),
+        );
+        let client = client(&transport);
+        let post = |body: &str| {
+            Request::builder()
+                .post("https://example.com/")
+                .text(body)
+                .build()
+                .unwrap()
+        };
+
+        assert!(
+            client.execute(post(matching)).await.is_ok(),
+            "{:?}",
+            matcher
+        );
+        assert!(client.execute(post(other)).await.is_err(), "{:?}", matcher);
+    }
+}
+
+#[tokio::test]
+async fn serves_outcomes_in_order_and_repeats_the_last() {
+    let transport = MockTransport::new();
+    transport.expect(
+        MockExpectation::new(Method::GET, "https://example.com/")
+            .respond(MockTransport::response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "down",
+            ))
+            .respond(MockTransport::response(StatusCode::OK, "up")),
+    );
+    let client = client(&transport);
+
+    let mut statuses = Vec::new();
+    for _ in 0..3 {
+        let response = client.execute(get("https://example.com/")).await.unwrap();
+        statuses.push(response.status);
+    }
+    assert_eq!(
+        statuses,
+        [
+            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::OK,
+            StatusCode::OK
+        ]
+    );
+}
+
+#[tokio::test]
+async fn serves_configured_failures() {
+    let transport = MockTransport::new();
+    transport.expect(
+        MockExpectation::new(Method::GET, "https://example.com/").fail(RelayError::Network {
+            message: "connection reset".into(),
+            cause: None,
+        }),
+    );
+
+    let error = client(&transport)
+        .execute(get("https://example.com/"))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RelayError::Network { .. }));
+}
+
+#[tokio::test]
+async fn records_every_received_request() {
+    let transport = MockTransport::new();
+    transport.expect(
+        MockExpectation::new(Method::GET, "https://example.com/known")
+            .respond(MockTransport::response(StatusCode::OK, "ok")),
+    );
+    let client = client(&transport);
+
+    client
+        .execute(get("https://example.com/known"))
+        .await
+        .unwrap();
+    let _ = client.execute(get("https://example.com/unknown")).await;
+
+    let urls = transport
+        .received()
+        .into_iter()
+        .map(|request| request.url)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        urls,
+        ["https://example.com/known", "https://example.com/unknown"]
+    );
+}
+
+#[tokio::test]
+async fn reports_a_diff_against_the_closest_expectation() {
+    let transport = MockTransport::new();
+    transport
+        .expect(
+            MockExpectation::new(Method::DELETE, "https://example.com/other")
+                .respond(MockTransport::response(StatusCode::OK, "ok")),
+        )
+        .expect(
+            MockExpectation::new(Method::POST, "https://example.com/items")
+                .header("X-Api-Key", "secret")
+                .respond(MockTransport::response(StatusCode::OK, "ok")),
+        );
+
+    let request = Request::builder()
+        .post("https://example.com/items")
+        .header("X-Api-Key", "wrong")
+        .build()
+        .unwrap();
+    let message = aborted(client(&transport).execute(request).await.unwrap_err());
+
+    assert!(
+        message.starts_with("No mock expectation matched request POST https://example.com/items")
+    );
+    assert!(
+        message.contains("Closest expectation (#1):\n"),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains("- header X-Api-Key: \"secret\"\n"),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains("+ header X-Api-Key: Some(\"wrong\")\n"),
+        "{}",
+        message
+    );
+    assert!(!message.contains("method:"), "{}", message);
+}
+
+#[tokio::test]
+async fn verify_reports_unused_expectations() {
+    let transport = MockTransport::new();
+    transport
+        .expect(
+            MockExpectation::new(Method::GET, "https://example.com/used")
+                .respond(MockTransport::response(StatusCode::OK, "ok")),
+        )
+        .expect(
+            MockExpectation::new(Method::PUT, "https://example.com/unused")
+                .respond(MockTransport::response(StatusCode::OK, "ok")),
+        );
+
+    client(&transport)
+        .execute(get("https://example.com/used"))
+        .await
+        .unwrap();
+
+    let message = aborted(transport.verify().unwrap_err());
+    assert!(message.contains("PUT \"https://example.com/unused\""));
+    assert!(!message.contains("/used\""));
+}
+
+#[tokio::test]
+async fn expectations_without_an_outcome_abort() {
+    let transport = MockTransport::new();
+    transport.expect(MockExpectation::new(Method::GET, "https://example.com/"));
+
+    let error = client(&transport)
+        .execute(get("https://example.com/"))
+        .await
+        .unwrap_err();
+    assert!(aborted(error).contains("no outcome configured"));
+    assert!(transport.verify().is_ok());
+}
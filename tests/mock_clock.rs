@@ -0,0 +1,85 @@
+#![cfg(feature = "mock")]
+
+use std::time::{Duration, SystemTime};
+
+use http::{Method, StatusCode};
+use relay::{
+    mock::{MockClock, MockExpectation, MockTransport},
+    Clock, RelayClient, Request,
+};
+
+const START_MS: u64 = 1_700_000_000_000;
+
+fn clock() -> MockClock {
+    MockClock::new(SystemTime::UNIX_EPOCH + Duration::from_millis(START_MS))
+}
+
+fn client(clock: &MockClock) -> RelayClient {
+    let transport = MockTransport::new().with_clock(clock.clone());
+    transport.expect(
+        MockExpectation::new(Method::GET, "https://example.com/")
+            .respond(MockTransport::response(StatusCode::OK, "ok")),
+    );
+    RelayClient::new().with_transport(transport)
+}
+
+fn request() -> Request {
+    Request::builder()
+        .get("https://example.com/")
+        .build()
+        .unwrap()
+}
+
+fn timing(response: &relay::Response) -> (u64, u64, Option<bool>) {
+    let timing = &response.meta.timing;
+    (timing.start, timing.end, timing.dns_cache_hit)
+}
+
+#[test]
+fn clones_share_the_same_time() {
+    let clock = clock();
+    let handle = clock.clone();
+    let (now, monotonic) = (clock.now(), clock.monotonic());
+
+    handle.advance(Duration::from_millis(1500));
+
+    assert_eq!(clock.now(), now + Duration::from_millis(1500));
+    assert_eq!(clock.monotonic(), monotonic + Duration::from_millis(1500));
+}
+
+#[test]
+fn default_starts_at_the_epoch() {
+    assert_eq!(MockClock::default().now(), SystemTime::UNIX_EPOCH);
+}
+
+#[tokio::test]
+async fn stamps_responses_with_the_clock_time() {
+    let clock = clock();
+    let client = client(&clock);
+
+    let response = client.execute(request()).await.unwrap();
+    assert_eq!(timing(&response), (START_MS, START_MS, None));
+    assert_eq!(response.meta.timing.duration(), Duration::ZERO);
+
+    clock.advance(Duration::from_millis(250));
+    let response = client.execute(request()).await.unwrap();
+    assert_eq!(timing(&response), (START_MS + 250, START_MS + 250, None));
+}
+
+#[tokio::test]
+async fn keeps_configured_timing_without_a_clock() {
+    let transport = MockTransport::new();
+    let mut response = MockTransport::response(StatusCode::OK, "ok");
+    response.meta.timing.start = 10;
+    response.meta.timing.end = 35;
+    response.meta.timing.dns_cache_hit = Some(true);
+    transport.expect(MockExpectation::new(Method::GET, "https://example.com/").respond(response));
+
+    let response = RelayClient::new()
+        .with_transport(transport)
+        .execute(request())
+        .await
+        .unwrap();
+    assert_eq!(timing(&response), (10, 35, Some(true)));
+    assert_eq!(response.meta.timing.duration(), Duration::from_millis(25));
+}
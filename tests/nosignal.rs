@@ -0,0 +1,84 @@
+mod common;
+
+use std::{
+    sync::{Arc, Barrier},
+    thread,
+    time::Duration,
+};
+
+use common::{ok, Server};
+use relay::{RelayClient, Request};
+use serde_json::json;
+
+fn request(url: String, nosignal: Option<bool>) -> Request {
+    serde_json::from_value(json!({
+        "id": 0,
+        "url": url,
+        "method": "GET",
+        "version": "HTTP/1.1",
+        "meta": { "options": { "nosignal": nosignal, "timeout": 5000 } },
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn sends_with_and_without_signal_handling() {
+    let server = Server::start(|_| ok("ok"));
+    let client = RelayClient::new();
+
+    for nosignal in [None, Some(true), Some(false)] {
+        let response = client
+            .execute(request(server.url("/"), nosignal))
+            .await
+            .unwrap();
+        assert_eq!(&response.body.body[..], b"ok", "{:?}", nosignal);
+    }
+    assert_eq!(server.requests().len(), 3);
+}
+
+// NOTE: Each thread drives its own runtime, so the transfers overlap the way
+// they do in a multithreaded host.
+#[test]
+fn concurrent_requests_from_several_threads_complete() {
+    const THREADS: usize = 8;
+    const REQUESTS: usize = 4;
+
+    let server = Server::start(|_| {
+        thread::sleep(Duration::from_millis(50));
+        ok("ok")
+    });
+    let url = server.url("/");
+    let start = Arc::new(Barrier::new(THREADS));
+
+    let threads = (0..THREADS)
+        .map(|_| {
+            let (url, start) = (url.clone(), Arc::clone(&start));
+            thread::spawn(move || {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap();
+                start.wait();
+                let client = RelayClient::new();
+                (0..REQUESTS)
+                    .map(|_| runtime.block_on(client.execute(request(url.clone(), Some(true)))))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for thread in threads {
+        for response in thread.join().unwrap() {
+            assert_eq!(&response.unwrap().body.body[..], b"ok");
+        }
+    }
+    assert_eq!(server.requests().len(), THREADS * REQUESTS);
+    assert!(server.peak_connections() > 1);
+}
+
+#[test]
+fn reads_the_option_from_json() {
+    let request = request("https://example.com/".into(), Some(false));
+    let options = serde_json::to_value(&request.meta).unwrap();
+    assert_eq!(options["options"]["nosignal"], json!(false));
+}
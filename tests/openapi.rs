@@ -0,0 +1,246 @@
+use http::Method;
+use relay::{
+    error::RelayError,
+    import::{openapi::requests_from_spec, NamedRequest},
+    ApiKeyLocation, AuthType, GrantType, QueryValue,
+};
+use serde_json::{json, Value};
+
+const PETSTORE: &str = include_str!("fixtures/petstore.json");
+
+fn petstore() -> Vec<NamedRequest> {
+    requests_from_spec(PETSTORE, None).unwrap()
+}
+
+fn find<'a>(requests: &'a [NamedRequest], name: &str) -> &'a NamedRequest {
+    requests
+        .iter()
+        .find(|request| request.name == name)
+        .unwrap_or_else(|| panic!("no request named {}", name))
+}
+
+fn header<'a>(request: &'a NamedRequest, name: &str) -> Option<&'a str> {
+    request.request.headers.as_ref()?.get(name)
+}
+
+// NOTE: A one operation document secured by `scheme`.
+fn secured_by(scheme: Value) -> String {
+    json!({
+        "openapi": "3.1.0",
+        "servers": [{ "url": "https://api.example.com" }],
+        "security": [{ "scheme": [] }],
+        "paths": { "/": { "get": { "operationId": "root" } } },
+        "components": { "securitySchemes": { "scheme": scheme } },
+    })
+    .to_string()
+}
+
+fn auth_for(scheme: Value) -> Option<AuthType> {
+    let mut requests = requests_from_spec(&secured_by(scheme), None).unwrap();
+    requests.remove(0).request.auth
+}
+
+fn parse_error(spec: Value) -> String {
+    match requests_from_spec(&spec.to_string(), None) {
+        Err(RelayError::Parse { message, .. }) => message,
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+}
+
+#[test]
+fn imports_every_petstore_operation() {
+    let requests = petstore();
+
+    let summary = requests
+        .iter()
+        .map(|request| {
+            (
+                request.name.as_str(),
+                request.request.method.clone(),
+                request.request.url.as_str(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        [
+            (
+                "listPets",
+                Method::GET,
+                "https://petstore.example.com/v1/pets"
+            ),
+            (
+                "createPet",
+                Method::POST,
+                "https://petstore.example.com/v1/pets"
+            ),
+            (
+                "showPetById",
+                Method::GET,
+                "https://petstore.example.com/v1/pets/{{petId}}"
+            ),
+            (
+                "deletePet",
+                Method::DELETE,
+                "https://petstore.example.com/v1/pets/{{petId}}"
+            ),
+        ]
+    );
+    assert_eq!(
+        find(&requests, "listPets").summary.as_deref(),
+        Some("List all pets")
+    );
+}
+
+#[test]
+fn fills_required_parameters_only() {
+    let requests = petstore();
+
+    let list = &find(&requests, "listPets").request;
+    let params = list.params.as_ref().unwrap();
+    assert_eq!(params.len(), 1);
+    assert_eq!(
+        params.get("status"),
+        Some(&QueryValue::Single("available".into()))
+    );
+
+    let delete = find(&requests, "deletePet");
+    assert_eq!(header(delete, "X-Request-Id"), Some("7f9c1a"));
+    assert!(delete.request.content.is_none());
+}
+
+#[test]
+fn synthesizes_bodies_through_refs() {
+    let requests = petstore();
+    let create = find(&requests, "createPet");
+
+    let content = serde_json::to_value(create.request.content.as_ref().unwrap()).unwrap();
+    assert_eq!(content["kind"], "json");
+    // NOTE: `id` is read only, `category` comes from its own schema.
+    assert_eq!(
+        content["content"],
+        json!({
+            "name": "doggie",
+            "category": { "id": 1, "name": "dogs" },
+            "tags": ["string"],
+        })
+    );
+    assert_eq!(header(create, "Content-Type"), Some("application/json"));
+}
+
+#[test]
+fn maps_the_petstore_security_schemes() {
+    let requests = petstore();
+
+    for name in ["listPets", "showPetById"] {
+        assert!(
+            matches!(
+                &find(&requests, name).request.auth,
+                Some(AuthType::ApiKey { key, value, location: ApiKeyLocation::Header })
+                    if key == "X-Api-Key" && value == "{{api_key}}"
+            ),
+            "{}",
+            name
+        );
+    }
+    assert!(matches!(
+        &find(&requests, "createPet").request.auth,
+        Some(AuthType::OAuth2 {
+            grant_type: GrantType::ClientCredentials { token_endpoint, .. },
+            ..
+        }) if token_endpoint == "https://petstore.example.com/oauth/token"
+    ));
+    assert!(matches!(
+        &find(&requests, "deletePet").request.auth,
+        Some(AuthType::Basic { username, password })
+            if username == "{{username}}" && password == "{{password}}"
+    ));
+}
+
+#[test]
+fn maps_each_security_scheme_type() {
+    assert!(matches!(
+        auth_for(json!({ "type": "http", "scheme": "Bearer" })),
+        Some(AuthType::Bearer { token }) if token == "{{token}}"
+    ));
+    assert!(matches!(
+        auth_for(json!({ "type": "apiKey", "name": "key", "in": "query" })),
+        Some(AuthType::ApiKey { key, location: ApiKeyLocation::Query, .. }) if key == "key"
+    ));
+    assert!(matches!(
+        auth_for(json!({ "type": "openIdConnect", "openIdConnectUrl": "https://id.example.com" })),
+        Some(AuthType::Bearer { token }) if token == "{{access_token}}"
+    ));
+    assert!(matches!(
+        auth_for(json!({
+            "type": "oauth2",
+            "flows": {
+                "authorizationCode": {
+                    "authorizationUrl": "https://id.example.com/authorize",
+                    "tokenUrl": "https://id.example.com/token",
+                    "scopes": {},
+                },
+            },
+        })),
+        Some(AuthType::OAuth2 {
+            grant_type: GrantType::AuthorizationCode { auth_endpoint, token_endpoint, .. },
+            ..
+        }) if auth_endpoint == "https://id.example.com/authorize"
+            && token_endpoint == "https://id.example.com/token"
+    ));
+    // NOTE: Neither has an equivalent, the request is imported without auth.
+    assert!(auth_for(json!({ "type": "mutualTLS" })).is_none());
+    assert!(auth_for(json!({ "type": "apiKey", "name": "key", "in": "cookie" })).is_none());
+}
+
+#[test]
+fn a_base_url_replaces_the_server() {
+    let requests = requests_from_spec(PETSTORE, Some("http://localhost:8080/")).unwrap();
+    assert_eq!(
+        find(&requests, "listPets").request.url,
+        "http://localhost:8080/pets"
+    );
+}
+
+#[test]
+fn rejects_remote_references() {
+    let message = parse_error(json!({
+        "openapi": "3.0.3",
+        "servers": [{ "url": "https://api.example.com" }],
+        "paths": {
+            "/pets": {
+                "post": {
+                    "requestBody": { "$ref": "common.json#/components/requestBodies/Pet" },
+                },
+            },
+        },
+    }));
+    assert!(message.contains("common.json#/components/requestBodies/Pet"));
+    assert!(message.starts_with("Remote reference"));
+}
+
+#[test]
+fn rejects_dangling_and_cyclic_references() {
+    let spec = |reference: &str| {
+        json!({
+            "openapi": "3.0.3",
+            "servers": [{ "url": "https://api.example.com" }],
+            "paths": { "/pets": { "$ref": reference } },
+            "components": {
+                "pathItems": {
+                    "A": { "$ref": "#/components/pathItems/B" },
+                    "B": { "$ref": "#/components/pathItems/A" },
+                },
+            },
+        })
+    };
+
+    assert!(parse_error(spec("#/components/pathItems/Missing")).contains("does not point"));
+    assert!(parse_error(spec("#/components/pathItems/A")).contains("likely a cycle"));
+}
+
+#[test]
+fn rejects_swagger_2() {
+    let message = parse_error(json!({ "swagger": "2.0", "paths": {} }));
+    assert!(message.contains("'2.0'"));
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use common::{ok, response, Server};
+use relay::{RelayClient, Request};
+
+// NOTE: Serves `/page/1` to `/page/{last}`, each linking to the one after.
+fn pages(last: usize) -> Server {
+    Server::start(move |request| {
+        let page = request
+            .request_line()
+            .split_whitespace()
+            .nth(1)
+            .and_then(|path| path.strip_prefix("/page/"))
+            .and_then(|page| page.parse::<usize>().ok())
+            .unwrap_or(1);
+        let body = format!("page {}", page);
+        if page >= last {
+            return ok(body);
+        }
+        let link = format!("</page/{}>; rel=\"next\"", page + 1);
+        response("200 OK", &[("Link", &link)], body)
+    })
+}
+
+fn body(response: &relay::Response) -> String {
+    String::from_utf8_lossy(&response.body.body).into_owned()
+}
+
+#[tokio::test]
+async fn follows_next_links_until_the_last_page() {
+    let server = pages(3);
+    let client = RelayClient::new();
+    let request = Request::builder()
+        .get(server.url("/page/1"))
+        .header("X-Custom", "kept")
+        .build()
+        .unwrap();
+
+    let mut pages = client.paginate(request);
+    let mut bodies = Vec::new();
+    while let Some(page) = pages.next_page().await {
+        bodies.push(body(&page.unwrap()));
+    }
+
+    assert_eq!(bodies, ["page 1", "page 2", "page 3"]);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert!(requests
+        .iter()
+        .all(|request| request.header("x-custom").as_deref() == Some("kept")));
+}
+
+#[tokio::test]
+async fn stops_at_max_pages() {
+    let server = pages(10);
+    let client = RelayClient::new();
+    let request = Request::builder()
+        .get(server.url("/page/1"))
+        .build()
+        .unwrap();
+
+    let mut pages = client.paginate(request).max_pages(2);
+    let mut fetched = 0;
+    while let Some(page) = pages.next_page().await {
+        page.unwrap();
+        fetched += 1;
+    }
+
+    assert_eq!(fetched, 2);
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn drops_credentials_when_the_next_link_changes_origin() {
+    let other = pages(2);
+    let next = format!("<{}>; rel=\"next\"", other.url("/page/2"));
+    let first = Server::start(move |_| response("200 OK", &[("Link", &next)], "page 1"));
+
+    let client = RelayClient::new();
+    let request = Request::builder()
+        .get(first.url("/page/1"))
+        .bearer("secret")
+        .header("Cookie", "session=abc")
+        .header("X-Custom", "kept")
+        .build()
+        .unwrap();
+
+    let mut pages = client.paginate(request);
+    while let Some(page) = pages.next_page().await {
+        page.unwrap();
+    }
+
+    let sent = &first.requests()[0];
+    assert_eq!(
+        sent.header("authorization").as_deref(),
+        Some("Bearer secret")
+    );
+    assert_eq!(sent.header("cookie").as_deref(), Some("session=abc"));
+
+    let followed = other.requests();
+    assert_eq!(followed.len(), 1);
+    assert_eq!(followed[0].header("authorization"), None);
+    assert_eq!(followed[0].header("cookie"), None);
+    assert_eq!(followed[0].header("x-custom").as_deref(), Some("kept"));
+}
+
+#[test]
+fn blocking_iterates_outside_a_runtime() {
+    let server = pages(3);
+    let client = RelayClient::new();
+    let request = Request::builder()
+        .get(server.url("/page/1"))
+        .build()
+        .unwrap();
+
+    let bodies = client
+        .paginate(request)
+        .blocking()
+        .unwrap()
+        .map(|page| body(&page.unwrap()))
+        .collect::<Vec<_>>();
+
+    assert_eq!(bodies, ["page 1", "page 2", "page 3"]);
+}
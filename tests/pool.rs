@@ -0,0 +1,30 @@
+mod common;
+
+use std::{thread, time::Duration};
+
+use common::{ok, Server};
+use relay::{RelayClient, Request};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn transfers_beyond_the_limit_wait_their_turn() {
+    let server = Server::start(|_| {
+        thread::sleep(Duration::from_millis(50));
+        ok("done")
+    });
+    let client = RelayClient::new().with_max_total_connections(2);
+
+    let tasks = (0..6)
+        .map(|_| {
+            let client = client.clone();
+            let request = Request::builder().get(server.url("/")).build().unwrap();
+            tokio::spawn(async move { client.execute(request).await })
+        })
+        .collect::<Vec<_>>();
+    for task in tasks {
+        let response = task.await.unwrap().unwrap();
+        assert_eq!(&response.body.body[..], b"done");
+    }
+
+    assert_eq!(server.requests().len(), 6);
+    assert!(server.peak_connections() <= 2);
+}
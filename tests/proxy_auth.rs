@@ -0,0 +1,55 @@
+mod common;
+
+use common::{ok, response, Server};
+use relay::{ProxyAuth, ProxyAuthScheme, ProxyConfig, Request};
+
+fn through_proxy(url: String, proxy: &Server) -> Request {
+    let mut request = Request::builder().get(url).build().unwrap();
+    request.proxy = Some(ProxyConfig {
+        url: proxy.url(""),
+        auth: Some(ProxyAuth {
+            username: String::new(),
+            password: String::new(),
+            scheme: Some(ProxyAuthScheme::Bearer),
+            token: Some("proxy-token".into()),
+        }),
+    });
+    request
+}
+
+#[tokio::test]
+async fn bearer_token_goes_with_plain_http_requests() {
+    let proxy = Server::start(|_| ok("proxied"));
+
+    let response = relay::execute(through_proxy("http://origin.invalid/".into(), &proxy))
+        .await
+        .unwrap();
+    assert_eq!(&response.body.body[..], b"proxied");
+
+    let requests = proxy.requests();
+    assert_eq!(
+        requests[0].request_line(),
+        "GET http://origin.invalid/ HTTP/1.1"
+    );
+    assert_eq!(
+        requests[0].header("proxy-authorization").as_deref(),
+        Some("Bearer proxy-token")
+    );
+}
+
+#[tokio::test]
+async fn bearer_token_goes_with_connect_for_tunnelled_requests() {
+    let proxy = Server::start(|_| response("403 Forbidden", &[], ""));
+
+    let result = relay::execute(through_proxy("https://origin.invalid/".into(), &proxy)).await;
+    assert!(result.is_err());
+
+    let requests = proxy.requests();
+    assert!(requests[0]
+        .request_line()
+        .starts_with("CONNECT origin.invalid:443 "));
+    assert_eq!(
+        requests[0].header("proxy-authorization").as_deref(),
+        Some("Bearer proxy-token")
+    );
+}
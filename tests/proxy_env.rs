@@ -0,0 +1,43 @@
+mod common;
+
+use std::env;
+
+use common::{ok, Server};
+use relay::{RelayClient, Request};
+
+// NOTE: Proxy variables are process-wide, so every case runs in this one test.
+#[tokio::test]
+async fn proxy_from_env() {
+    let proxy = Server::start(|_| ok("proxy"));
+    let target = Server::start(|_| ok("direct"));
+    let client = RelayClient::new().with_proxy_from_env(true);
+    let request = || Request::builder().get(target.url("/")).build().unwrap();
+
+    for name in [
+        "no_proxy",
+        "NO_PROXY",
+        "http_proxy",
+        "all_proxy",
+        "ALL_PROXY",
+    ] {
+        env::remove_var(name);
+    }
+
+    // NOTE: `HTTP_PROXY` can be set by a client through a `Proxy:` header in
+    // CGI, so only the lowercase name counts for plain HTTP.
+    env::set_var("HTTP_PROXY", format!("http://127.0.0.1:{}", proxy.port));
+    let response = client.execute(request()).await.unwrap();
+    assert_eq!(&response.body.body[..], b"direct");
+    assert!(proxy.requests().is_empty());
+    env::remove_var("HTTP_PROXY");
+
+    env::set_var("http_proxy", format!("127.0.0.1:{}", proxy.port));
+    let response = client.execute(request()).await.unwrap();
+    assert_eq!(&response.body.body[..], b"proxy");
+    assert_eq!(
+        proxy.requests()[0].request_line(),
+        format!("GET {} HTTP/1.1", target.url("/"))
+    );
+    assert_eq!(target.requests().len(), 1);
+    env::remove_var("http_proxy");
+}
@@ -0,0 +1,67 @@
+use relay::{QueryValue, Request};
+
+fn request(url: &str, params: &[(&str, &str)]) -> Request {
+    let mut request = Request::builder()
+        .get("http://placeholder")
+        .build()
+        .unwrap();
+    request.url = url.to_string();
+    request.params = Some(
+        params
+            .iter()
+            .map(|(name, value)| (name.to_string(), QueryValue::Single(value.to_string())))
+            .collect(),
+    );
+    request
+}
+
+fn exported_url(request: &Request) -> String {
+    let command = request.to_curl_command().unwrap();
+    let first = command.lines().next().unwrap();
+    first
+        .trim_start_matches("curl ")
+        .trim_end_matches(" \\")
+        .trim_matches('\'')
+        .to_string()
+}
+
+#[test]
+fn sends_a_url_without_params_as_written() {
+    let request = request("https://example.com/a%7eb/./c?x=%7e&y#top", &[]);
+    assert_eq!(
+        exported_url(&request),
+        "https://example.com/a%7eb/./c?x=%7e&y"
+    );
+}
+
+#[test]
+fn adds_params_to_a_url_without_a_scheme() {
+    let request = request("example.com/search?q=1", &[("page", "2")]);
+    assert_eq!(
+        exported_url(&request),
+        "http://example.com/search?q=1&page=2"
+    );
+}
+
+#[test]
+fn leaves_a_url_without_a_scheme_to_curl() {
+    let request = request("example.com/path", &[]);
+    assert_eq!(exported_url(&request), "example.com/path");
+}
+
+#[test]
+fn scoped_ipv6_host_header_leaves_out_the_zone() {
+    for url in [
+        "http://[fe80::1%eth0]:8080/a",
+        "http://[fe80::1%25eth0]:8080/a",
+    ] {
+        let request = Request::builder().get(url).build().unwrap();
+        assert_eq!(request.url, "http://[fe80::1%25eth0]:8080/a");
+        assert_eq!(exported_url(&request), "http://[fe80::1%25eth0]:8080/a");
+
+        let text = request.to_http_text().unwrap();
+        assert!(text.starts_with("GET /a HTTP/1.1\r\n"));
+        assert!(text.contains("\r\nHost: [fe80::1]:8080\r\n"));
+        assert!(!text.contains("eth0"));
+    }
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::{ok, Server};
+use relay::{Request, RequestBuilder};
+use serde_json::Value;
+
+// NOTE: Keys out of order, an integer serde_json keeps exactly either way,
+// a float with a shortest form and one integer no 64-bit type holds.
+const TEXT: &str = r#"{"ratio":0.1,"id":9223372036854775807,"big":123456789012345678901234567890}"#;
+
+async fn sent(builder: impl FnOnce(RequestBuilder) -> RequestBuilder) -> Vec<u8> {
+    let server = Server::start(|_| ok(""));
+    let request = builder(Request::builder().post(server.url("/")))
+        .build()
+        .unwrap();
+    relay::execute(request).await.unwrap();
+    server.requests().remove(0).body
+}
+
+#[tokio::test]
+async fn raw_json_is_sent_byte_for_byte() {
+    assert_eq!(
+        sent(|builder| builder.raw_json(TEXT)).await,
+        TEXT.as_bytes()
+    );
+
+    let small = r#"{"id":9223372036854775807,"ratio":0.1}"#;
+    assert_eq!(
+        sent(|builder| builder.raw_json(small)).await,
+        small.as_bytes()
+    );
+}
+
+#[tokio::test]
+async fn raw_json_must_be_json() {
+    let error = Request::builder()
+        .post("http://localhost/")
+        .raw_json(r#"{"id":"#)
+        .build()
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("Invalid JSON content"),
+        "{}",
+        error
+    );
+}
+
+#[cfg(feature = "lossless-json")]
+#[tokio::test]
+async fn parsed_values_keep_digits_and_key_order() {
+    let value = serde_json::from_str::<Value>(TEXT).unwrap();
+    assert_eq!(sent(|builder| builder.json(&value)).await, TEXT.as_bytes());
+}
+
+#[cfg(not(feature = "lossless-json"))]
+#[tokio::test]
+async fn parsed_values_are_reserialized() {
+    let value = serde_json::from_str::<Value>(TEXT).unwrap();
+    let body = sent(|builder| builder.json(&value)).await;
+    assert_ne!(body, TEXT.as_bytes());
+
+    // NOTE: Keys come out sorted and the out of range integer as a float,
+    // while `i64::MAX` and `0.1` survive unchanged.
+    let text = String::from_utf8(body).unwrap();
+    assert!(text.starts_with(r#"{"big":1.23456789"#), "{}", text);
+    assert!(
+        text.ends_with(r#""id":9223372036854775807,"ratio":0.1}"#),
+        "{}",
+        text
+    );
+    let sent = serde_json::from_str::<Value>(&text).unwrap();
+    assert_eq!(sent["id"].as_i64(), Some(i64::MAX));
+    assert_eq!(sent["ratio"].as_f64(), Some(0.1));
+}
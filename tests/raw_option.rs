@@ -0,0 +1,147 @@
+mod common;
+
+use common::{ok, response, Server};
+use http::StatusCode;
+use relay::{
+    error::{PolicyRule, RelayError},
+    RawCurlOption, RelayClient, Request, TargetPolicy,
+};
+
+async fn send(server: &Server, option: RawCurlOption) -> relay::error::Result<relay::Response> {
+    let request = Request::builder()
+        .get(server.url("/"))
+        .raw_option(option)
+        .build()
+        .unwrap();
+    RelayClient::new().execute(request).await
+}
+
+#[tokio::test]
+async fn applies_list_options() {
+    let server = Server::start(|_| ok("ok"));
+    let option = RawCurlOption::List {
+        option: curl_sys::CURLOPT_HTTPHEADER,
+        value: vec!["X-Raw: yes".into()],
+    };
+    send(&server, option).await.unwrap();
+    assert_eq!(server.requests()[0].header("x-raw").as_deref(), Some("yes"));
+}
+
+// NOTE: Without a target policy raw options override the managed ones, the
+// URL and redirect handling included.
+#[tokio::test]
+async fn override_managed_options_without_a_target_policy() {
+    let server = Server::start(|request| {
+        if request.request_line().starts_with("GET /start ") {
+            response("302 Found", &[("Location", "/end")], "")
+        } else {
+            ok("ok")
+        }
+    });
+
+    let option = RawCurlOption::String {
+        option: curl_sys::CURLOPT_URL,
+        value: server.url("/raw"),
+    };
+    send(&server, option).await.unwrap();
+
+    let request = Request::builder()
+        .get(server.url("/start"))
+        .follow_redirects(false)
+        .raw_option(RawCurlOption::Bool {
+            option: curl_sys::CURLOPT_FOLLOWLOCATION,
+            value: true,
+        })
+        .build()
+        .unwrap();
+    let response = RelayClient::new().execute(request).await.unwrap();
+    assert_eq!(response.status, StatusCode::OK);
+
+    let lines = server
+        .requests()
+        .iter()
+        .map(|request| request.request_line().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        [
+            "GET /raw HTTP/1.1",
+            "GET /start HTTP/1.1",
+            "GET /end HTTP/1.1"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn rejects_options_that_pick_the_target_under_a_policy() {
+    let server = Server::start(|_| ok("ok"));
+    let client =
+        RelayClient::new().with_target_policy(TargetPolicy::new().with_denied_ip_ranges([]));
+    for option in [
+        RawCurlOption::String {
+            option: curl_sys::CURLOPT_URL,
+            value: "http://169.254.169.254/".into(),
+        },
+        RawCurlOption::Bool {
+            option: curl_sys::CURLOPT_FOLLOWLOCATION,
+            value: true,
+        },
+        RawCurlOption::String {
+            option: curl_sys::CURLOPT_PROXY,
+            value: "http://169.254.169.254:3128".into(),
+        },
+    ] {
+        let request = Request::builder()
+            .get(server.url("/"))
+            .raw_option(option)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            client.execute(request).await,
+            Err(RelayError::UnsupportedFeature { .. })
+        ));
+    }
+    assert!(server.requests().is_empty());
+}
+
+// NOTE: A raw `CURLOPT_RESOLVE` may point a name anywhere, the policy still
+// sees the address when the connection is opened.
+#[tokio::test]
+async fn resolve_overrides_are_checked_against_the_target_policy() {
+    let server = Server::start(|_| ok("ok"));
+    let request = Request::builder()
+        .get(format!("http://pinned.invalid:{}/", server.port))
+        .raw_option(RawCurlOption::List {
+            option: curl_sys::CURLOPT_RESOLVE,
+            value: vec![format!("pinned.invalid:{}:127.0.0.1", server.port)],
+        })
+        .build()
+        .unwrap();
+
+    let error = RelayClient::new()
+        .with_target_policy(TargetPolicy::new())
+        .execute(request)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        RelayError::PolicyViolation {
+            rule: PolicyRule::DenyIpRanges,
+            ..
+        }
+    ));
+    assert!(server.requests().is_empty());
+}
+
+#[tokio::test]
+async fn rejects_pointer_options() {
+    let server = Server::start(|_| ok("ok"));
+    let option = RawCurlOption::String {
+        option: curl_sys::CURLOPT_POSTFIELDS,
+        value: "body".into(),
+    };
+    assert!(matches!(
+        send(&server, option).await,
+        Err(RelayError::UnsupportedFeature { .. })
+    ));
+}
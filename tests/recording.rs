@@ -0,0 +1,190 @@
+mod common;
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use common::{response, Server};
+use http::Method;
+use relay::{error::RelayError, MatchOn, RecordingLayer, RelayClient, Request};
+
+fn cassette(name: &str) -> PathBuf {
+    env::temp_dir().join(format!(
+        "relay-cassette-{}-{}.json",
+        name,
+        std::process::id()
+    ))
+}
+
+// NOTE: Answers with what it received, so every recorded response tells
+// which request it belongs to.
+fn echo_server() -> Server {
+    Server::start(|captured| {
+        let body = format!(
+            "{} {}",
+            captured.request_line(),
+            String::from_utf8_lossy(&captured.body)
+        );
+        response(
+            "200 OK",
+            &[("Set-Cookie", "session=set-cookie-secret")],
+            body,
+        )
+    })
+}
+
+fn request(method: Method, url: &str, body: Option<&str>) -> Request {
+    let builder = Request::builder().method(method, url);
+    match body {
+        Some(body) => builder.text(body),
+        None => builder,
+    }
+    .build()
+    .unwrap()
+}
+
+async fn body(client: &RelayClient, request: Request) -> String {
+    let response = client.execute(request).await.unwrap();
+    String::from_utf8_lossy(&response.body.body).into_owned()
+}
+
+// NOTE: Records `GET /a`, `POST /a` with `one` and `POST /a` with `two`.
+async fn record(server: &Server, path: &Path) {
+    let client = RelayClient::new().with_transport(RecordingLayer::record(path));
+    let url = server.url("/a");
+
+    body(&client, request(Method::GET, &url, None)).await;
+    body(&client, request(Method::POST, &url, Some("one"))).await;
+    body(&client, request(Method::POST, &url, Some("two"))).await;
+}
+
+fn replaying(path: &Path, match_on: &[MatchOn]) -> RelayClient {
+    RelayClient::new().with_transport(RecordingLayer::replay(path).unwrap().match_on(match_on))
+}
+
+#[tokio::test]
+async fn replays_recorded_responses_without_the_network() {
+    let server = echo_server();
+    let path = cassette("round-trip");
+    record(&server, &path).await;
+    assert_eq!(server.requests().len(), 3);
+
+    let client = replaying(&path, &[MatchOn::Method, MatchOn::Url]);
+    let url = server.url("/a");
+    let replayed = [
+        body(&client, request(Method::GET, &url, None)).await,
+        body(&client, request(Method::POST, &url, Some("any"))).await,
+        body(&client, request(Method::POST, &url, Some("any"))).await,
+        // NOTE: Once every match was replayed the last one repeats.
+        body(&client, request(Method::POST, &url, Some("any"))).await,
+    ];
+
+    assert_eq!(
+        replayed,
+        [
+            "GET /a HTTP/1.1 ",
+            "POST /a HTTP/1.1 one",
+            "POST /a HTTP/1.1 two",
+            "POST /a HTTP/1.1 two",
+        ]
+    );
+    assert_eq!(server.requests().len(), 3);
+    fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn cassettes_never_contain_credentials() {
+    let server = echo_server();
+    let path = cassette("sanitized");
+    let client = RelayClient::new().with_transport(RecordingLayer::record(&path));
+
+    let request = Request::builder()
+        .get(server.url("/"))
+        .bearer("authorization-secret")
+        .header("Cookie", "id=cookie-secret")
+        .header("Proxy-Authorization", "Basic proxy-secret")
+        .build()
+        .unwrap();
+    client.execute(request).await.unwrap();
+
+    let sent = &server.requests()[0];
+    assert_eq!(
+        sent.header("authorization").as_deref(),
+        Some("Bearer authorization-secret")
+    );
+
+    let text = fs::read_to_string(&path).unwrap();
+    for secret in [
+        "authorization-secret",
+        "cookie-secret",
+        "proxy-secret",
+        "set-cookie-secret",
+    ] {
+        assert!(!text.contains(secret), "{} was recorded", secret);
+    }
+    assert!(text.contains("Proxy-Authorization"));
+    fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn matches_on_the_configured_parts() {
+    let server = echo_server();
+    let path = cassette("match-on");
+    record(&server, &path).await;
+    let (a, b) = (server.url("/a"), server.url("/b"));
+
+    let cases = [
+        (
+            vec![MatchOn::Method, MatchOn::Url, MatchOn::Body],
+            request(Method::POST, &a, Some("two")),
+            "POST /a HTTP/1.1 two",
+        ),
+        (
+            vec![MatchOn::Method],
+            request(Method::GET, &b, None),
+            "GET /a HTTP/1.1 ",
+        ),
+        (
+            vec![MatchOn::Url],
+            request(Method::PUT, &a, None),
+            "GET /a HTTP/1.1 ",
+        ),
+        (
+            vec![MatchOn::Body],
+            request(Method::DELETE, &b, Some("two")),
+            "POST /a HTTP/1.1 two",
+        ),
+    ];
+
+    for (match_on, request, expected) in cases {
+        let client = replaying(&path, &match_on);
+        assert_eq!(body(&client, request).await, expected, "{:?}", match_on);
+    }
+    fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn replay_misses_abort_with_the_recorded_requests() {
+    let server = echo_server();
+    let path = cassette("miss");
+    record(&server, &path).await;
+
+    let client = replaying(&path, &[MatchOn::Method, MatchOn::Url]);
+    let error = client
+        .execute(request(Method::GET, &server.url("/missing"), None))
+        .await
+        .unwrap_err();
+
+    let RelayError::Abort { message } = error else {
+        panic!("expected an abort, got {:?}", error);
+    };
+    assert!(message.contains("/missing"), "{}", message);
+    assert!(
+        message.contains(&format!("POST {}", server.url("/a"))),
+        "{}",
+        message
+    );
+    assert_eq!(server.requests().len(), 3);
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use common::{response, Server};
+use relay::{set_redaction_policy, RedactionPolicy, RelayClient, Request};
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Metadata, Subscriber,
+};
+
+// NOTE: Keeps every field of every span and event as text. Transfers run on
+// threads of their own, so this has to be the global subscriber, which is
+// why this test has a binary to itself.
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<String>>>);
+
+struct Fields<'a>(&'a mut Vec<String>);
+
+impl Visit for Fields<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.push(format!("{}={:?}", field.name(), value));
+    }
+}
+
+impl Capture {
+    fn push(&self, record: impl FnOnce(&mut Fields<'_>)) {
+        let mut fields = Vec::new();
+        record(&mut Fields(&mut fields));
+        self.0.lock().unwrap().push(fields.join(" "));
+    }
+
+    fn text(&self) -> String {
+        self.0.lock().unwrap().join("\n")
+    }
+}
+
+impl Subscriber for Capture {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attributes: &span::Attributes<'_>) -> span::Id {
+        self.push(|fields| attributes.record(fields));
+        span::Id::from_u64(1)
+    }
+
+    fn record(&self, _: &span::Id, values: &span::Record<'_>) {
+        self.push(|fields| values.record(fields));
+    }
+
+    fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        self.push(|fields| event.record(fields));
+    }
+
+    fn enter(&self, _: &span::Id) {}
+
+    fn exit(&self, _: &span::Id) {}
+}
+
+#[tokio::test]
+async fn credentials_never_reach_the_logs() {
+    let capture = Capture::default();
+    tracing::subscriber::set_global_default(capture.clone()).unwrap();
+    set_redaction_policy(RedactionPolicy::default());
+
+    let server = Server::start(|_| {
+        response(
+            "200 OK",
+            &[
+                ("Set-Cookie", "session=set-cookie-secret; HttpOnly"),
+                ("X-Visible", "visible-value"),
+            ],
+            "ok",
+        )
+    });
+    let request = Request::builder()
+        .get(server.url("/"))
+        .bearer("authorization-secret")
+        .header("Cookie", "id=cookie-secret")
+        .header("Proxy-Authorization", "Basic proxy-secret")
+        .build()
+        .unwrap();
+    RelayClient::new().execute(request).await.unwrap();
+
+    let sent = &server.requests()[0];
+    assert_eq!(
+        sent.header("authorization").as_deref(),
+        Some("Bearer authorization-secret")
+    );
+
+    let logs = capture.text();
+    for secret in [
+        "authorization-secret",
+        "cookie-secret",
+        "proxy-secret",
+        "set-cookie-secret",
+    ] {
+        assert!(!logs.contains(secret), "{} was logged", secret);
+    }
+    // NOTE: The headers themselves are logged, only their values are hidden.
+    assert!(logs.contains("Authorization: ***"));
+    assert!(logs.contains("visible-value"));
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use common::{ok, response, Server};
+use relay::{error::RelayError, RelayClient, Request};
+
+fn following(url: String, max_redirects: u32) -> Request {
+    Request::builder()
+        .get(url)
+        .follow_redirects(true)
+        .max_redirects(max_redirects)
+        .build()
+        .unwrap()
+}
+
+fn redirect_chain(error: RelayError) -> Vec<String> {
+    let RelayError::TooManyRedirects { chain, .. } = error else {
+        panic!("expected too many redirects, got {:?}", error);
+    };
+    chain
+}
+
+#[tokio::test]
+async fn a_self_redirect_is_stopped_as_a_loop() {
+    let server = Server::start(|_| response("302 Found", &[("Location", "/loop")], ""));
+    let url = server.url("/loop");
+
+    let error = RelayClient::new()
+        .execute(following(url.clone(), 20))
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().contains("Redirect loop"), "{}", error);
+    // NOTE: Stopped once the `/loop` to `/loop` hop repeats, long before
+    // the limit of 20.
+    assert_eq!(redirect_chain(error), [url.clone(), url.clone(), url]);
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn a_loop_between_two_urls_is_stopped() {
+    let server = Server::start(|captured| {
+        let location = match captured.request_line().contains(" /a ") {
+            true => "/b",
+            false => "/a",
+        };
+        response("302 Found", &[("Location", location)], "")
+    });
+    let (a, b) = (server.url("/a"), server.url("/b"));
+
+    let error = RelayClient::new()
+        .execute(following(a.clone(), 20))
+        .await
+        .unwrap_err();
+
+    assert_eq!(redirect_chain(error), [a.clone(), b.clone(), a, b]);
+    assert_eq!(server.requests().len(), 4);
+}
+
+#[tokio::test]
+async fn exceeding_max_redirects_reports_the_chain() {
+    // NOTE: `/1` redirects to `/2` and so on, never repeating a hop.
+    let server = Server::start(|captured| {
+        let path = captured.request_line().split(' ').nth(1).unwrap_or("/0");
+        let next = format!("/{}", path[1..].parse::<u32>().unwrap_or(0) + 1);
+        response("302 Found", &[("Location", next.as_str())], "")
+    });
+
+    let error = RelayClient::new()
+        .execute(following(server.url("/1"), 2))
+        .await
+        .unwrap_err();
+
+    // NOTE: Ends with the redirect that was not followed.
+    assert_eq!(
+        redirect_chain(error),
+        ["/1", "/2", "/3", "/4"].map(|path| server.url(path))
+    );
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn revisiting_a_url_without_repeating_a_hop_is_not_a_loop() {
+    // NOTE: Back to the page after setting a cookie, a common pattern.
+    let server = Server::start(|captured| {
+        if captured.request_line().contains(" /login ") {
+            response("302 Found", &[("Location", "/page")], "")
+        } else if captured.header("cookie").is_none() {
+            response(
+                "302 Found",
+                &[("Location", "/login"), ("Set-Cookie", "session=1")],
+                "",
+            )
+        } else {
+            ok("welcome")
+        }
+    });
+    let request = Request::builder()
+        .get(server.url("/page"))
+        .follow_redirects(true)
+        .cookies(true)
+        .build()
+        .unwrap();
+
+    let response = RelayClient::new().execute(request).await.unwrap();
+    assert_eq!(response.body.body.as_ref(), b"welcome");
+}
+
+#[tokio::test]
+async fn final_url_is_where_redirects_ended() {
+    let server = Server::start(|captured| {
+        if captured.request_line().contains(" /start ") {
+            response("301 Moved Permanently", &[("Location", "/next?step=2")], "")
+        } else if captured.request_line().contains(" /next?step=2 ") {
+            response("302 Found", &[("Location", "/end")], "")
+        } else {
+            ok("done")
+        }
+    });
+    let client = RelayClient::new();
+
+    let followed = client
+        .execute(following(server.url("/start"), 5))
+        .await
+        .unwrap();
+    assert_eq!(followed.final_url, server.url("/end"));
+    assert_eq!(followed.body.body.as_ref(), b"done");
+
+    // NOTE: Without following, the response is the redirect itself.
+    let request = Request::builder()
+        .get(server.url("/start"))
+        .follow_redirects(false)
+        .build()
+        .unwrap();
+    let unfollowed = client.execute(request).await.unwrap();
+    assert_eq!(unfollowed.status, 301);
+    assert_eq!(unfollowed.final_url, server.url("/start"));
+}
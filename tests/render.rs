@@ -0,0 +1,188 @@
+mod common;
+
+use common::{response, Server};
+use relay::{RelayClient, Request};
+use serde_json::json;
+
+// NOTE: The header lines of rendered or received text, after the start line.
+fn header_lines(text: &str) -> Vec<&str> {
+    let head = text.split("\r\n\r\n").next().unwrap_or_default();
+    head.split("\r\n").skip(1).collect()
+}
+
+fn header<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    header_lines(text).into_iter().find_map(|line| {
+        let (key, value) = line.split_once(": ")?;
+        key.eq_ignore_ascii_case(name).then_some(value)
+    })
+}
+
+#[test]
+fn renders_a_get_request() {
+    let request = Request::builder()
+        .get("https://example.com/items")
+        .query("page", "2")
+        .query("q", "a b")
+        .header("X-Custom", "1")
+        .decompress(false)
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        request.to_http_text().unwrap(),
+        "GET /items?page=2&q=a+b HTTP/1.1\r\n\
+         Host: example.com\r\n\
+         Accept: */*\r\n\
+         Accept-Encoding: identity\r\n\
+         X-Custom: 1\r\n\
+         \r\n"
+    );
+}
+
+#[test]
+fn renders_the_port_and_basic_credentials() {
+    let request = Request::builder()
+        .get("http://example.com:8080/")
+        .basic_auth("user", "pass")
+        .build()
+        .unwrap();
+
+    let text = request.to_http_text().unwrap();
+    assert_eq!(header(&text, "host"), Some("example.com:8080"));
+    assert_eq!(header(&text, "authorization"), Some("Basic dXNlcjpwYXNz"));
+}
+
+#[test]
+fn caller_headers_replace_and_suppress_defaults() {
+    let request = Request::builder()
+        .get("https://example.com/")
+        .header("Accept", "application/json")
+        .suppress_header("Accept-Encoding")
+        .build()
+        .unwrap();
+
+    let text = request.to_http_text().unwrap();
+    assert_eq!(header(&text, "accept"), Some("application/json"));
+    assert_eq!(header(&text, "accept-encoding"), None);
+    assert_eq!(
+        header_lines(&text)
+            .iter()
+            .filter(|line| line.to_ascii_lowercase().starts_with("accept:"))
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn renders_a_json_body_with_its_length() {
+    let request = Request::builder()
+        .post("https://example.com/items")
+        .json(&json!({ "name": "relay" }))
+        .build()
+        .unwrap();
+
+    let text = request.to_http_text().unwrap();
+    let body = text.split_once("\r\n\r\n").unwrap().1;
+    assert_eq!(body, r#"{"name":"relay"}"#);
+    assert!(text.starts_with("POST /items HTTP/1.1\r\n"));
+    assert_eq!(header(&text, "content-type"), Some("application/json"));
+    assert_eq!(header(&text, "content-length"), Some("16"));
+}
+
+#[test]
+fn head_requests_render_without_a_body() {
+    let mut request = Request::builder()
+        .head("https://example.com/")
+        .build()
+        .unwrap();
+    request.content = serde_json::from_value(json!({
+        "kind": "text",
+        "content": "ignored",
+        "mediaType": "text/plain",
+    }))
+    .unwrap();
+
+    let text = request.to_http_text().unwrap();
+    assert!(text.ends_with("\r\n\r\n"));
+    assert_eq!(header(&text, "content-length"), None);
+}
+
+#[tokio::test]
+async fn multipart_renders_the_length_curl_sends() {
+    let server = Server::start(|_| response("204 No Content", &[], ""));
+    let request = Request::builder()
+        .post(server.url("/upload"))
+        .multipart_text("title", "report")
+        .multipart_text("note", "caf\u{e9}")
+        .build()
+        .unwrap();
+
+    let text = request.to_http_text().unwrap();
+    assert!(header(&text, "content-type")
+        .unwrap()
+        .starts_with("multipart/form-data; boundary="));
+    let body = text.split_once("\r\n\r\n").unwrap().1;
+    assert!(body.contains("name=\"title\"\r\n\r\nreport\r\n"));
+
+    RelayClient::new().execute(request).await.unwrap();
+    let sent = &server.requests()[0];
+    assert_eq!(
+        header(&text, "content-length"),
+        Some(sent.body.len().to_string().as_str())
+    );
+    assert_eq!(body.len(), sent.body.len());
+}
+
+#[tokio::test]
+async fn renders_responses_with_sorted_headers_and_split_cookies() {
+    let server = Server::start(|_| {
+        response(
+            "201 Created",
+            &[
+                ("X-Zeta", "z"),
+                ("Set-Cookie", "a=1"),
+                ("Set-Cookie", "b=2"),
+                ("Content-Type", "text/plain"),
+            ],
+            "created",
+        )
+    });
+    let request = Request::builder().get(server.url("/")).build().unwrap();
+    let response = RelayClient::new().execute(request).await.unwrap();
+
+    let text = response.to_http_text();
+    assert!(text.starts_with("HTTP/1.1 201 "));
+    assert!(text.ends_with("\r\n\r\ncreated"));
+
+    let names = header_lines(&text)
+        .iter()
+        .map(|line| line.split_once(": ").unwrap().0.to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    let mut sorted = names.clone();
+    sorted.sort();
+    assert_eq!(names, sorted);
+
+    let cookies = header_lines(&text)
+        .into_iter()
+        .filter(|line| line.to_ascii_lowercase().starts_with("set-cookie:"))
+        .map(|line| line.split_once(": ").unwrap().1)
+        .collect::<Vec<_>>();
+    assert_eq!(cookies, ["a=1", "b=2"]);
+}
+
+#[tokio::test]
+async fn renders_binary_response_bodies_as_a_placeholder() {
+    let server = Server::start(|_| {
+        response(
+            "200 OK",
+            &[("Content-Type", "application/octet-stream")],
+            [0xffu8, 0xfe, 0x00],
+        )
+    });
+    let request = Request::builder().get(server.url("/")).build().unwrap();
+    let response = RelayClient::new().execute(request).await.unwrap();
+
+    assert!(response
+        .to_http_text()
+        .ends_with("\r\n\r\n<3 bytes of application/octet-stream>"));
+}
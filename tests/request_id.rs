@@ -0,0 +1,101 @@
+mod common;
+
+use std::{thread, time::Duration};
+
+use common::{ok, Server};
+use relay::{
+    error::{RelayError, RequestResult},
+    RelayClient, Request, ValidationCode,
+};
+use serde_json::json;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn rejects_an_id_already_in_flight() {
+    let server = Server::start(|_| {
+        thread::sleep(Duration::from_millis(500));
+        ok("slow")
+    });
+    let request = |id| {
+        let mut request = Request::builder().get(server.url("/")).build().unwrap();
+        request.id = id;
+        request
+    };
+
+    let client = RelayClient::new();
+    let first = tokio::spawn({
+        let (client, request) = (client.clone(), request(4242));
+        async move { client.execute(request).await }
+    });
+    while server.peak_connections() == 0 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let error = client.execute(request(4242)).await.unwrap_err();
+    let RelayError::Validation { issues, .. } = error else {
+        panic!("expected a validation error, got {:?}", error);
+    };
+    assert_eq!(issues[0].code, ValidationCode::DuplicateId);
+
+    let response = first.await.unwrap().unwrap();
+    assert_eq!(response.id, 4242);
+    assert_eq!(server.requests().len(), 1);
+
+    // NOTE: Once the first request is done its id is free again.
+    client.execute(request(4242)).await.unwrap();
+}
+
+#[tokio::test]
+async fn sends_the_assigned_id_in_the_configured_header() {
+    let server = Server::start(|_| ok("ok"));
+    let client = RelayClient::new().with_request_id_header("X-Request-Id");
+
+    let request = Request::builder().get(server.url("/")).build().unwrap();
+    let response = client.execute(request).await.unwrap();
+
+    assert_ne!(response.id, relay::UNASSIGNED_REQUEST_ID);
+    assert_eq!(
+        server.requests()[0].header("x-request-id"),
+        Some(response.id.to_string())
+    );
+}
+
+#[tokio::test]
+async fn keeps_an_explicit_request_id_header() {
+    let server = Server::start(|_| ok("ok"));
+    let client = RelayClient::new().with_request_id_header("X-Request-Id");
+
+    let request = Request::builder()
+        .get(server.url("/"))
+        .header("X-Request-Id", "from-caller")
+        .build()
+        .unwrap();
+    client.execute(request).await.unwrap();
+
+    let sent = &server.requests()[0];
+    assert_eq!(sent.header("x-request-id").as_deref(), Some("from-caller"));
+    assert_eq!(sent.head.matches("X-Request-Id").count(), 1);
+}
+
+#[tokio::test]
+async fn sends_no_id_header_unless_configured() {
+    let server = Server::start(|_| ok("ok"));
+    let request = Request::builder().get(server.url("/")).build().unwrap();
+    RelayClient::new().execute(request).await.unwrap();
+    assert_eq!(server.requests()[0].header("x-request-id"), None);
+}
+
+#[test]
+fn request_result_error_keeps_its_shape() {
+    let result = RequestResult::<()>::from(Err(RelayError::Abort {
+        message: "stopped".into(),
+    }));
+    let value = serde_json::to_value(&result).unwrap();
+    let mut keys = value.as_object().unwrap().keys().collect::<Vec<_>>();
+    keys.sort();
+    assert_eq!(keys, ["error", "kind"]);
+    assert_eq!(value["kind"], "error");
+    assert_eq!(
+        value["error"],
+        json!({ "kind": "abort", "message": "stopped" })
+    );
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use common::{ok, response, Server};
+use relay::{BackoffStrategy, RelayClient, Request, RetryPolicy};
+
+// NOTE: Answers `503` to the first `failures` requests, then `200`.
+fn flaky(failures: usize) -> Server {
+    let seen = Arc::new(AtomicUsize::new(0));
+    Server::start(move |_| {
+        if seen.fetch_add(1, Ordering::SeqCst) < failures {
+            response("503 Service Unavailable", &[], "down")
+        } else {
+            ok("up")
+        }
+    })
+}
+
+fn retrying(max_retries: u32) -> RelayClient {
+    RelayClient::new().with_retry(RetryPolicy::new(
+        max_retries,
+        BackoffStrategy::Constant(Duration::from_millis(5)),
+    ))
+}
+
+fn get(server: &Server) -> Request {
+    Request::builder().get(server.url("/")).build().unwrap()
+}
+
+#[tokio::test]
+async fn counts_every_attempt_until_one_succeeds() {
+    let server = flaky(2);
+    let response = retrying(3).execute(get(&server)).await.unwrap();
+
+    assert_eq!(response.status, 200);
+    let metrics = response.metrics.unwrap();
+    assert_eq!(metrics.attempts, 3);
+    assert_eq!(metrics.retry_delay_ms, 10);
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn stops_at_the_configured_retries() {
+    let server = flaky(usize::MAX);
+    let response = retrying(2).execute(get(&server)).await.unwrap();
+
+    assert_eq!(response.status, 503);
+    let metrics = response.metrics.unwrap();
+    assert_eq!(metrics.attempts, 3);
+    assert_eq!(metrics.retry_delay_ms, 10);
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn bytes_add_up_across_attempts() {
+    let once = flaky(0);
+    let single = retrying(3).execute(get(&once)).await.unwrap();
+    let single = single.metrics.unwrap();
+
+    let server = flaky(1);
+    let retried = retrying(3).execute(get(&server)).await.unwrap();
+    let retried = retried.metrics.unwrap();
+
+    assert_eq!(single.attempts, 1);
+    assert_eq!(retried.attempts, 2);
+    assert!(retried.bytes_sent >= 2 * single.bytes_sent);
+    assert!(retried.bytes_received > single.bytes_received);
+}
+
+#[tokio::test]
+async fn requests_that_are_not_retried_make_one_attempt() {
+    let server = flaky(usize::MAX);
+
+    let without_policy = RelayClient::new().execute(get(&server)).await.unwrap();
+    assert_eq!(without_policy.metrics.unwrap().attempts, 1);
+
+    // NOTE: POST is not idempotent, so it is sent once despite the policy.
+    let post = Request::builder()
+        .post(server.url("/"))
+        .text("once")
+        .build()
+        .unwrap();
+    let posted = retrying(3).execute(post).await.unwrap();
+    let metrics = posted.metrics.unwrap();
+    assert_eq!((metrics.attempts, metrics.retry_delay_ms), (1, 0));
+    assert_eq!(server.requests().len(), 2);
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use common::{ok, response, Server};
+use relay::{RawCurlOption, RelayClient, Request};
+
+const CURLOPT_RESOLVE: u32 = 10_203;
+
+async fn dns_cache_hits(client: &RelayClient, url: &str, requests: usize) -> Vec<Option<bool>> {
+    let mut hits = Vec::new();
+    for _ in 0..requests {
+        let request = Request::builder().get(url).build().unwrap();
+        let response = client.execute(request).await.unwrap();
+        hits.push(response.meta.timing.dns_cache_hit);
+    }
+    hits
+}
+
+#[tokio::test]
+async fn resolve_overrides_stay_out_of_the_shared_cache() {
+    let server = Server::start(|_| ok("pinned"));
+    let client = RelayClient::new();
+    let url = format!("http://pinned.invalid:{}/", server.port);
+
+    let pinned = Request::builder()
+        .get(&url)
+        .raw_option(RawCurlOption::List {
+            option: CURLOPT_RESOLVE,
+            value: vec![format!("pinned.invalid:{}:127.0.0.1", server.port)],
+        })
+        .build()
+        .unwrap();
+    let response = client.execute(pinned).await.unwrap();
+    assert_eq!(&response.body.body[..], b"pinned");
+
+    let plain = Request::builder().get(&url).build().unwrap();
+    assert!(client.execute(plain).await.is_err());
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn warmed_names_come_from_the_cache() {
+    let server = Server::start(|_| ok("ok"));
+    let url = format!("http://localhost:{}/", server.port);
+    let request = || Request::builder().get(&url).build().unwrap();
+
+    let cold = RelayClient::new().execute(request()).await.unwrap();
+    assert_eq!(cold.meta.timing.dns_cache_hit, Some(false));
+
+    let client = RelayClient::new();
+    client.warm_dns("localhost", server.port).unwrap();
+    let warmed = client.execute(request()).await.unwrap();
+    assert_eq!(warmed.meta.timing.dns_cache_hit, Some(true));
+    // NOTE: Warming connects without sending a request.
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn requests_on_one_client_share_resolved_names() {
+    let server = Server::start(|_| ok("ok"));
+    let url = format!("http://localhost:{}/", server.port);
+    let client = RelayClient::new();
+    assert_eq!(
+        dns_cache_hits(&client, &url, 2).await,
+        [Some(false), Some(true)]
+    );
+
+    // NOTE: Another client has a cache of its own.
+    let request = Request::builder().get(&url).build().unwrap();
+    let other = RelayClient::new().execute(request).await.unwrap();
+    assert_eq!(other.meta.timing.dns_cache_hit, Some(false));
+}
+
+#[tokio::test]
+async fn flushing_forgets_resolved_names() {
+    let server = Server::start(|_| ok("ok"));
+    let url = format!("http://localhost:{}/", server.port);
+    let client = RelayClient::new();
+
+    assert_eq!(
+        dns_cache_hits(&client, &url, 2).await,
+        [Some(false), Some(true)]
+    );
+    // NOTE: Names cached before the flush are only trusted again once they
+    // are looked up anew.
+    client.flush_dns();
+    assert_eq!(dns_cache_hits(&client, &url, 1).await, [Some(false)]);
+}
+
+#[tokio::test]
+async fn names_count_as_resolved_only_after_a_transfer_succeeds() {
+    // NOTE: The first connection is closed without an answer.
+    let seen = Arc::new(AtomicUsize::new(0));
+    let server = Server::start(move |_| match seen.fetch_add(1, Ordering::SeqCst) {
+        0 => Vec::new(),
+        _ => ok("ok"),
+    });
+    let url = format!("http://localhost:{}/", server.port);
+    let client = RelayClient::new();
+
+    let request = Request::builder().get(&url).build().unwrap();
+    assert!(client.execute(request).await.is_err());
+    assert_eq!(
+        dns_cache_hits(&client, &url, 2).await,
+        [Some(false), Some(true)]
+    );
+}
+
+#[tokio::test]
+async fn ip_literals_are_not_looked_up() {
+    let server = Server::start(|_| ok("ok"));
+    let client = RelayClient::new();
+    assert_eq!(
+        dns_cache_hits(&client, &server.url("/"), 2).await,
+        [None, None]
+    );
+}
+
+#[tokio::test]
+async fn requests_on_one_client_share_cookies() {
+    let server = Server::start(|captured| {
+        if captured.request_line().starts_with("POST /login ") {
+            response(
+                "204 No Content",
+                &[("Set-Cookie", "session=abc; Path=/")],
+                "",
+            )
+        } else {
+            ok(captured.header("cookie").unwrap_or_default())
+        }
+    });
+    let client = RelayClient::new();
+    let login = Request::builder()
+        .post(server.url("/login"))
+        .cookies(true)
+        .build()
+        .unwrap();
+    let me = || {
+        Request::builder()
+            .get(server.url("/me"))
+            .cookies(true)
+            .build()
+            .unwrap()
+    };
+
+    client.execute(login).await.unwrap();
+    let response = client.execute(me()).await.unwrap();
+    assert_eq!(response.body.body.as_ref(), b"session=abc");
+
+    let elsewhere = RelayClient::new().execute(me()).await.unwrap();
+    assert!(elsewhere.body.body.is_empty());
+}
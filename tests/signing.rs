@@ -0,0 +1,145 @@
+mod common;
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use common::{ok, Server};
+use relay::{
+    error::Result, CanonicalRequest, Clock, Headers, HmacSigner, RelayClient, Request,
+    RequestSigner,
+};
+use serde_json::json;
+
+#[derive(Debug)]
+struct FixedClock(u64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.0)
+    }
+
+    fn monotonic(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+// NOTE: Keeps what it was asked to sign, and signs nothing.
+#[derive(Clone, Default)]
+struct Recorder {
+    seen: Arc<Mutex<Option<(Headers, Vec<u8>)>>>,
+}
+
+impl RequestSigner for Recorder {
+    fn sign(&self, canonical: &CanonicalRequest) -> Result<Vec<(String, String)>> {
+        *self.seen.lock().unwrap() = Some((canonical.headers().clone(), canonical.body().to_vec()));
+        Ok(vec![("X-Signed".into(), "yes".into())])
+    }
+}
+
+#[tokio::test]
+async fn hmac_signature_matches_a_known_answer() {
+    let server = Server::start(|_| ok(""));
+    let signer = HmacSigner::new("secret")
+        .with_signed_headers(["Content-Type", "X-Tenant"])
+        .with_clock(FixedClock(1_700_000_000));
+    let request = Request::builder()
+        .post(server.url("/v1/orders?dry_run=true"))
+        .header("X-Tenant", "acme")
+        .raw_json(r#"{"id":1,"qty":2}"#)
+        .build()
+        .unwrap();
+
+    RelayClient::new()
+        .with_signer(signer)
+        .execute(request)
+        .await
+        .unwrap();
+
+    // NOTE: Computed independently over
+    // "POST\n/v1/orders?dry_run=true\n1700000000\n<body hash>\ncontent-type:application/json\nx-tenant:acme".
+    let captured = &server.requests()[0];
+    assert_eq!(
+        captured.header("x-timestamp").as_deref(),
+        Some("1700000000")
+    );
+    assert_eq!(
+        captured.header("x-content-sha256").as_deref(),
+        Some("e44d4bb2a19e2f9417dd9c16fc2d1ace79e536cdd151d35131c637874447a102")
+    );
+    assert_eq!(
+        captured.header("x-signature").as_deref(),
+        Some("BCNFrlH/CXd7w0RX7uPgU4ANPPB2ENWDLntRlrTEjSQ=")
+    );
+}
+
+#[tokio::test]
+async fn key_ids_and_header_names_are_configurable() {
+    let server = Server::start(|_| ok(""));
+    let signer = HmacSigner::new("secret")
+        .with_key_id("k1")
+        .with_signature_header("Signature")
+        .with_timestamp_header("Date-Signed")
+        .with_body_hash_header("Digest-SHA256")
+        .with_clock(FixedClock(1_700_000_000));
+    let request = Request::builder().get(server.url("/")).build().unwrap();
+
+    RelayClient::new()
+        .with_signer(signer)
+        .execute(request)
+        .await
+        .unwrap();
+
+    let captured = &server.requests()[0];
+    assert_eq!(
+        captured.header("date-signed").as_deref(),
+        Some("1700000000")
+    );
+    // NOTE: The SHA-256 of an empty body.
+    assert_eq!(
+        captured.header("digest-sha256").as_deref(),
+        Some("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+    );
+    let signature = captured.header("signature").unwrap();
+    assert!(
+        signature.starts_with("keyId=k1,signature="),
+        "{}",
+        signature
+    );
+    assert!(captured.header("x-signature").is_none());
+}
+
+#[tokio::test]
+async fn signers_see_the_headers_and_body_as_sent() {
+    let server = Server::start(|_| ok(""));
+    let recorder = Recorder::default();
+    let request = Request::builder()
+        .post(server.url("/orders"))
+        .basic_auth("user", "pass")
+        .header("Cookie", "session=abc")
+        .json(&json!({ "b": [1, 2], "a": "x" }))
+        .build()
+        .unwrap();
+
+    RelayClient::new()
+        .with_signer(recorder.clone())
+        .execute(request)
+        .await
+        .unwrap();
+
+    let (headers, body) = recorder.seen.lock().unwrap().take().unwrap();
+    let captured = &server.requests()[0];
+    for name in ["content-type", "authorization", "cookie"] {
+        assert!(headers.get(name).is_some(), "{} was not signed", name);
+        assert_eq!(
+            headers.get(name).map(str::to_string),
+            captured.header(name),
+            "{}",
+            name
+        );
+    }
+    assert_eq!(headers.get("authorization"), Some("Basic dXNlcjpwYXNz"));
+    assert_eq!(body, captured.body);
+    assert_eq!(captured.header("x-signed").as_deref(), Some("yes"));
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use common::{response, Server};
+use relay::{Request, Response};
+
+const DATES: [&str; 2] = [
+    "Sun, 06 Nov 1994 08:49:37 GMT",
+    "Tue, 05 Mar 2024 04:05:06 GMT",
+];
+
+// NOTE: Answers the same way every time apart from `Date`, and `X-Build`
+// when `build` changes with each request.
+fn server(build_changes: bool) -> Server {
+    let seen = Arc::new(AtomicUsize::new(0));
+    Server::start(move |_| {
+        let n = seen.fetch_add(1, Ordering::SeqCst);
+        let build = if build_changes {
+            n.to_string()
+        } else {
+            "0".into()
+        };
+        response(
+            "200 OK",
+            &[
+                ("Date", DATES[n % 2]),
+                ("X-Build", build.as_str()),
+                ("Content-Type", "application/json"),
+            ],
+            r#"{"ok":true}"#,
+        )
+    })
+}
+
+async fn twice(server: &Server) -> (Response, Response) {
+    let get = || Request::builder().get(server.url("/")).build().unwrap();
+    (
+        relay::execute(get()).await.unwrap(),
+        relay::execute(get()).await.unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn responses_differing_only_in_date_are_equal() {
+    let server = server(false);
+    let (first, second) = twice(&server).await;
+    assert_ne!(first.header_joined("date"), second.header_joined("date"));
+
+    let (first, second) = (
+        first.normalized_for_snapshot(&[]),
+        second.normalized_for_snapshot(&[]),
+    );
+    assert_eq!(first, second);
+    assert!(first.diff(&second).is_empty());
+    assert!(first.headers.iter().all(|(name, _)| name != "date"));
+}
+
+#[tokio::test]
+async fn other_differences_are_reported_unless_ignored() {
+    let server = server(true);
+    let (first, second) = twice(&server).await;
+
+    assert_eq!(
+        first
+            .normalized_for_snapshot(&[])
+            .diff(&second.normalized_for_snapshot(&[])),
+        [r#"header x-build: ["0"] != ["1"]"#]
+    );
+    assert_eq!(
+        first.normalized_for_snapshot(&["X-Build"]),
+        second.normalized_for_snapshot(&["X-Build"])
+    );
+}
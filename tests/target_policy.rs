@@ -0,0 +1,228 @@
+mod common;
+
+use std::{
+    net::IpAddr,
+    sync::{Arc, Mutex},
+};
+
+use common::{ok, response, Server};
+use relay::{
+    error::{PolicyRule, RelayError},
+    AuthType, GrantType, RawCurlOption, RelayClient, Request, TargetPolicy, Transport,
+    TransportFuture,
+};
+
+fn denied(policy: &TargetPolicy, ip: &str) -> bool {
+    matches!(
+        policy.check_ip(ip.parse::<IpAddr>().unwrap()),
+        Err(RelayError::PolicyViolation {
+            rule: PolicyRule::DenyIpRanges,
+            ..
+        })
+    )
+}
+
+#[test]
+fn default_ranges_refuse_ipv4_written_as_ipv6() {
+    let policy = TargetPolicy::new();
+
+    for ip in [
+        "169.254.169.254",
+        "::ffff:169.254.169.254",
+        "::169.254.169.254",
+        "::127.0.0.1",
+        "64:ff9b::a9fe:a9fe",
+        "2002:a9fe:a9fe::1",
+        "::1",
+        "::",
+    ] {
+        assert!(denied(&policy, ip), "{} should be denied", ip);
+    }
+    for ip in ["93.184.216.34", "::ffff:93.184.216.34", "2606:2800:220:1::"] {
+        assert!(!denied(&policy, ip), "{} should be allowed", ip);
+    }
+}
+
+fn oauth2_request(token_endpoint: String) -> Request {
+    let mut request = Request::builder()
+        .get("http://api.example.invalid/")
+        .build()
+        .unwrap();
+    request.auth = Some(AuthType::OAuth2 {
+        grant_type: GrantType::ClientCredentials {
+            token_endpoint,
+            client_id: "client".into(),
+            client_secret: Some("secret".into()),
+        },
+        access_token: None,
+        refresh_token: None,
+    });
+    request
+}
+
+#[tokio::test]
+async fn token_endpoint_is_checked_against_the_policy() {
+    let server = Server::start(|_| ok(r#"{"access_token":"token"}"#));
+    let client = RelayClient::new().with_target_policy(TargetPolicy::new());
+
+    let error = client
+        .execute(oauth2_request(server.url("/token")))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        RelayError::PolicyViolation {
+            rule: PolicyRule::DenyIpRanges,
+            ..
+        }
+    ));
+    assert!(server.requests().is_empty());
+}
+
+// NOTE: The name passes the host rules, the address it resolves to is what
+// the policy refuses, the way a name pointing at the metadata endpoint would.
+#[tokio::test]
+async fn token_endpoint_resolving_to_a_denied_address_is_refused() {
+    let server = Server::start(|_| ok(r#"{"access_token":"token"}"#));
+    let client = RelayClient::new().with_target_policy(TargetPolicy::new());
+
+    let error = client
+        .execute(oauth2_request(format!(
+            "http://localhost:{}/token",
+            server.port
+        )))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        RelayError::PolicyViolation {
+            rule: PolicyRule::DenyIpRanges,
+            ..
+        }
+    ));
+    assert!(server.requests().is_empty());
+}
+
+// NOTE: The policy is checked when curl opens the connection, after the name
+// was resolved, so a name pinned to the metadata endpoint is refused without
+// a connection being made.
+#[tokio::test]
+async fn name_resolving_to_the_metadata_endpoint_is_refused() {
+    let request = Request::builder()
+        .get("http://metadata.invalid/latest/meta-data/")
+        .raw_option(RawCurlOption::List {
+            option: curl_sys::CURLOPT_RESOLVE,
+            value: vec!["metadata.invalid:80:169.254.169.254".into()],
+        })
+        .build()
+        .unwrap();
+
+    let error = RelayClient::new()
+        .with_target_policy(TargetPolicy::new())
+        .execute(request)
+        .await
+        .unwrap_err();
+    let RelayError::PolicyViolation { rule, target, .. } = error else {
+        panic!("expected a policy violation, got {:?}", error);
+    };
+    assert_eq!(rule, PolicyRule::DenyIpRanges);
+    assert_eq!(target, "169.254.169.254");
+}
+
+#[tokio::test]
+async fn redirect_to_a_denied_host_is_not_followed() {
+    let server = Server::start(|_| {
+        response(
+            "302 Found",
+            &[("Location", "http://denied.invalid/internal")],
+            "",
+        )
+    });
+    let policy = TargetPolicy::new()
+        .with_denied_ip_ranges([])
+        .with_denied_host("denied.invalid");
+    let request = Request::builder()
+        .get(server.url("/start"))
+        .follow_redirects(true)
+        .build()
+        .unwrap();
+
+    let error = RelayClient::new()
+        .with_target_policy(policy)
+        .execute(request)
+        .await
+        .unwrap_err();
+    let RelayError::PolicyViolation { rule, target, .. } = error else {
+        panic!("expected a policy violation, got {:?}", error);
+    };
+    assert_eq!(rule, PolicyRule::DenyHosts);
+    assert_eq!(target, "denied.invalid");
+    assert_eq!(server.requests().len(), 1);
+}
+
+// NOTE: Records the policy each request reached the transport with, then
+// fails it so nothing is sent.
+#[derive(Clone, Default)]
+struct PolicyProbe(Arc<Mutex<Vec<(String, bool)>>>);
+
+impl Transport for PolicyProbe {
+    fn send(&self, request: Request) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let strict = request
+                .target_policy
+                .as_ref()
+                .is_some_and(|policy| policy.check_url("http://internal.invalid/").is_err());
+            let client = request.headers.as_ref().unwrap().get("x-client").unwrap();
+            self.0.lock().unwrap().push((client.to_string(), strict));
+            tokio::task::yield_now().await;
+            Err(RelayError::Abort {
+                message: "probed".into(),
+            })
+        })
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn clients_sharing_request_ids_keep_their_own_policy() {
+    let probe = PolicyProbe::default();
+    let strict = RelayClient::new()
+        .with_transport(probe.clone())
+        .with_target_policy(TargetPolicy::new().with_denied_host("internal.invalid"));
+    let lax = RelayClient::new()
+        .with_transport(probe.clone())
+        .with_target_policy(TargetPolicy::new());
+
+    let request = |client: &str| {
+        let mut request = Request::builder()
+            .get("http://api.example.invalid/")
+            .header("X-Client", client)
+            .build()
+            .unwrap();
+        request.id = 5150;
+        request
+    };
+
+    let mut tasks = Vec::new();
+    for _ in 0..50 {
+        for (name, client) in [("strict", strict.clone()), ("lax", lax.clone())] {
+            let request = request(name);
+            tasks.push(tokio::spawn(async move {
+                client.execute(request).await.unwrap_err();
+            }));
+        }
+    }
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let seen = probe.0.lock().unwrap().clone();
+    assert_eq!(seen.len(), 100);
+    for (client, strict) in seen {
+        assert_eq!(
+            strict,
+            client == "strict",
+            "{} used the wrong policy",
+            client
+        );
+    }
+}
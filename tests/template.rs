@@ -0,0 +1,34 @@
+use relay::{Request, Variables};
+
+fn template() -> Request {
+    Request::builder()
+        .get("https://example.com/{{path}}")
+        .header("Authorization", "Bearer {{token}}")
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn secrets_stay_with_the_resolved_request() {
+    let request = template();
+    let variables = Variables::new()
+        .set("path", "items")
+        .secret("token", "s3cr3t-value");
+
+    let resolved = request.resolve_with(&variables).unwrap();
+    assert!(!resolved.secrets.is_empty());
+    assert!(!format!("{:?}", resolved.secrets).contains("s3cr3t-value"));
+    assert!(request.secrets.is_empty());
+
+    let json = serde_json::to_value(&resolved).unwrap();
+    assert!(json.get("secrets").is_none());
+}
+
+#[test]
+fn requests_without_secret_variables_carry_none() {
+    let variables = Variables::new()
+        .set("path", "items")
+        .set("token", "public-value");
+    let resolved = template().resolve_with(&variables).unwrap();
+    assert!(resolved.secrets.is_empty());
+}
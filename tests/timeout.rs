@@ -0,0 +1,114 @@
+mod common;
+
+use std::{thread, time::Duration};
+
+use common::{ok, Server};
+use relay::{
+    error::{RelayError, TimeoutPhase},
+    ErrorPayload, RelayClient, Request,
+};
+
+fn slow_server(delay: Duration) -> Server {
+    Server::start(move |_| {
+        thread::sleep(delay);
+        ok("late")
+    })
+}
+
+#[tokio::test]
+async fn waiting_on_the_server_reports_the_response_phase() {
+    let server = slow_server(Duration::from_millis(800));
+    let request = Request::builder()
+        .get(server.url("/slow"))
+        .timeout(Duration::from_millis(200))
+        .build()
+        .unwrap();
+
+    let error = RelayClient::new().execute(request).await.unwrap_err();
+    let RelayError::Timeout {
+        phase,
+        limit_ms,
+        trace,
+        ..
+    } = error
+    else {
+        panic!("expected a timeout, got {:?}", error);
+    };
+    assert_eq!(phase, Some(TimeoutPhase::Response));
+    assert_eq!(limit_ms, Some(200));
+    assert!(trace.is_none());
+}
+
+#[tokio::test]
+async fn first_byte_timeout_reports_its_own_phase() {
+    // NOTE: The first byte timeout is checked about once a second.
+    let server = slow_server(Duration::from_millis(2500));
+    let request = Request::builder()
+        .get(server.url("/slow"))
+        .first_byte_timeout(Duration::from_millis(200))
+        .build()
+        .unwrap();
+
+    let error = RelayClient::new().execute(request).await.unwrap_err();
+    assert!(matches!(
+        error,
+        RelayError::Timeout {
+            phase: Some(TimeoutPhase::FirstByte),
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn captured_trace_is_attached_and_redacted() {
+    let server = slow_server(Duration::from_millis(800));
+    let request = Request::builder()
+        .get(server.url("/slow"))
+        .header("Authorization", "Bearer secret-token")
+        .timeout(Duration::from_millis(200))
+        .capture_trace(true)
+        .build()
+        .unwrap();
+
+    let error = RelayClient::new().execute(request).await.unwrap_err();
+    let trace = error.trace().unwrap().to_vec();
+    assert!(trace.iter().any(|line| line.starts_with("> GET /slow")));
+    assert!(trace.iter().any(|line| line.starts_with("* ")));
+    assert!(trace.iter().all(|line| !line.contains("secret-token")));
+    assert_eq!(ErrorPayload::from(error).trace, Some(trace));
+}
+
+#[test]
+fn response_phase_keeps_its_serialized_name() {
+    assert_eq!(
+        serde_json::to_string(&TimeoutPhase::Response).unwrap(),
+        r#""response""#
+    );
+}
+
+#[tokio::test]
+async fn a_slow_response_past_max_response_time_is_rejected_once_received() {
+    let server = slow_server(Duration::from_millis(300));
+    let request = |limit| {
+        Request::builder()
+            .get(server.url("/slow"))
+            .max_response_time(Duration::from_millis(limit))
+            .build()
+            .unwrap()
+    };
+
+    let error = RelayClient::new().execute(request(100)).await.unwrap_err();
+    let RelayError::ResponseTime {
+        elapsed_ms,
+        limit_ms,
+        ..
+    } = error
+    else {
+        panic!("expected a response time error, got {:?}", error);
+    };
+    assert_eq!(limit_ms, 100);
+    assert!(elapsed_ms >= 300, "{}", elapsed_ms);
+
+    let response = RelayClient::new().execute(request(10_000)).await.unwrap();
+    assert_eq!(&response.body.body[..], b"late");
+}
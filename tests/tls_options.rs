@@ -0,0 +1,207 @@
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use openssl::{
+    asn1::Asn1Time,
+    bn::BigNum,
+    ec::{EcGroup, EcKey},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::{PKey, Private},
+    ssl::{select_next_proto, AlpnError, SslAcceptor, SslMethod, SslVersion},
+    x509::{X509NameBuilder, X509},
+};
+use relay::{RelayClient, Request, SecurityConfig, SecurityConfigBuilder};
+
+const HTTP_1_1: &[u8] = b"\x08http/1.1";
+
+#[derive(Debug, Clone)]
+struct Handshake {
+    // NOTE: The protocols the client offered in wire format, `None` when
+    // it sent no ALPN extension.
+    alpn: Option<Vec<u8>>,
+    resumed: bool,
+}
+
+fn certificate() -> (PKey<Private>, X509) {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", "localhost").unwrap();
+    let name = name.build();
+
+    let mut builder = X509::builder().unwrap();
+    builder.set_version(2).unwrap();
+    let serial = BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap();
+    builder.set_serial_number(&serial).unwrap();
+    builder.set_subject_name(&name).unwrap();
+    builder.set_issuer_name(&name).unwrap();
+    builder.set_pubkey(&key).unwrap();
+    builder
+        .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+        .unwrap();
+    builder
+        .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+        .unwrap();
+    builder.sign(&key, MessageDigest::sha256()).unwrap();
+    (key, builder.build())
+}
+
+// NOTE: An HTTPS server on a loopback port answering `ok` to one request per
+// connection. Capped at TLS 1.2, so resumption happens in the handshake and
+// not through tickets that arrive after it.
+struct TlsServer {
+    port: u16,
+    handshakes: Arc<Mutex<Vec<Handshake>>>,
+}
+
+impl TlsServer {
+    fn start() -> TlsServer {
+        let (key, cert) = certificate();
+        let offered = Arc::new(Mutex::new(None::<Vec<u8>>));
+
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor.set_private_key(&key).unwrap();
+        acceptor.set_certificate(&cert).unwrap();
+        acceptor
+            .set_max_proto_version(Some(SslVersion::TLS1_2))
+            .unwrap();
+        acceptor.set_session_id_context(b"relay-tests").unwrap();
+        {
+            let offered = Arc::clone(&offered);
+            acceptor.set_alpn_select_callback(move |_, client| {
+                *offered.lock().unwrap() = Some(client.to_vec());
+                select_next_proto(HTTP_1_1, client).ok_or(AlpnError::NOACK)
+            });
+        }
+        let acceptor = acceptor.build();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handshakes = Arc::new(Mutex::new(Vec::new()));
+        {
+            let handshakes = Arc::clone(&handshakes);
+            // NOTE: Connections are served one at a time, so `offered` is
+            // always the current handshake's.
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    offered.lock().unwrap().take();
+                    let Ok(mut stream) = acceptor.accept(stream) else {
+                        continue;
+                    };
+                    handshakes.lock().unwrap().push(Handshake {
+                        alpn: offered.lock().unwrap().take(),
+                        resumed: stream.ssl().session_reused(),
+                    });
+
+                    let mut head = Vec::new();
+                    let mut byte = [0u8];
+                    while !head.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap_or(0) == 1 {
+                        head.push(byte[0]);
+                    }
+                    let _ = stream.write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+                    );
+                    let _ = stream.shutdown();
+                }
+            });
+        }
+
+        TlsServer { port, handshakes }
+    }
+
+    fn url(&self) -> String {
+        format!("https://127.0.0.1:{}/", self.port)
+    }
+
+    fn handshakes(&self) -> Vec<Handshake> {
+        self.handshakes.lock().unwrap().clone()
+    }
+}
+
+// NOTE: The certificate is self-signed, verification is not what these
+// tests are about.
+fn security() -> SecurityConfigBuilder {
+    SecurityConfig::builder().insecure()
+}
+
+async fn get(client: &RelayClient, server: &TlsServer, security: SecurityConfig) {
+    let request = Request::builder()
+        .get(server.url())
+        .security(security)
+        .build()
+        .unwrap();
+    let response = client.execute(request).await.unwrap();
+    assert_eq!(&response.body.body[..], b"ok");
+}
+
+fn offers_http_1_1(handshake: &Handshake) -> bool {
+    handshake
+        .alpn
+        .as_ref()
+        .is_some_and(|offered| offered.windows(HTTP_1_1.len()).any(|name| name == HTTP_1_1))
+}
+
+#[tokio::test]
+async fn alpn_is_offered_unless_disabled() {
+    let server = TlsServer::start();
+    let client = RelayClient::new();
+
+    get(&client, &server, security().build()).await;
+    get(&client, &server, security().enable_alpn(true).build()).await;
+    get(&client, &server, security().enable_alpn(false).build()).await;
+
+    let handshakes = server.handshakes();
+    assert_eq!(
+        handshakes.iter().map(offers_http_1_1).collect::<Vec<_>>(),
+        [true, true, false]
+    );
+    assert!(handshakes[2].alpn.is_none());
+}
+
+#[tokio::test]
+async fn sessions_are_resumed_unless_the_cache_is_off() {
+    let cached = TlsServer::start();
+    let client = RelayClient::new();
+    for _ in 0..2 {
+        get(&client, &cached, security().session_id_cache(true).build()).await;
+    }
+
+    let uncached = TlsServer::start();
+    let client = RelayClient::new();
+    for _ in 0..2 {
+        get(
+            &client,
+            &uncached,
+            security().session_id_cache(false).build(),
+        )
+        .await;
+    }
+
+    let resumed = |server: &TlsServer| {
+        server
+            .handshakes()
+            .iter()
+            .map(|handshake| handshake.resumed)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(resumed(&cached), [false, true]);
+    assert_eq!(resumed(&uncached), [false, false]);
+}
+
+// NOTE: OpenSSL builds of libcurl refuse false start, which relay logs and
+// skips, so either way the request goes through.
+#[tokio::test]
+async fn false_start_never_fails_the_request() {
+    let server = TlsServer::start();
+    let client = RelayClient::new();
+
+    for enable in [true, false] {
+        get(&client, &server, security().false_start(enable).build()).await;
+    }
+    assert_eq!(server.handshakes().len(), 2);
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use common::{ok, Server};
+use regex::Regex;
+use relay::{Request, TraceContext};
+use serde_json::json;
+
+const TRACEPARENT: &str = "^00-[0-9a-f]{32}-[0-9a-f]{16}-[0-9a-f]{2}$";
+
+fn traced(url: String) -> Request {
+    serde_json::from_value(json!({
+        "id": 0,
+        "url": url,
+        "method": "GET",
+        "version": "HTTP/1.1",
+        "meta": { "options": { "traceContext": true } },
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn injects_a_generated_traceparent() {
+    let server = Server::start(|_| ok("ok"));
+    let pattern = Regex::new(TRACEPARENT).unwrap();
+
+    let mut trace_ids = Vec::new();
+    for _ in 0..2 {
+        let response = relay::execute(traced(server.url("/"))).await.unwrap();
+        let context = response.meta.trace.unwrap();
+        trace_ids.push(context.trace_id.clone());
+
+        let sent = server
+            .requests()
+            .pop()
+            .unwrap()
+            .header("traceparent")
+            .unwrap();
+        assert!(pattern.is_match(&sent), "{}", sent);
+        assert_eq!(sent, context.traceparent());
+        assert!(sent.ends_with("-01"), "{}", sent);
+    }
+    assert_ne!(trace_ids[0], trace_ids[1]);
+}
+
+#[tokio::test]
+async fn nothing_is_injected_unless_enabled() {
+    let server = Server::start(|_| ok("ok"));
+    let request = Request::builder().get(server.url("/")).build().unwrap();
+
+    let response = relay::execute(request).await.unwrap();
+    assert!(response.meta.trace.is_none());
+    assert!(server.requests()[0].header("traceparent").is_none());
+}
+
+#[tokio::test]
+async fn continues_a_context_set_on_the_request() {
+    let server = Server::start(|_| ok("ok"));
+    let context = TraceContext::from_traceparent(
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00",
+        Some("vendor=value"),
+    )
+    .unwrap();
+    let request = Request::builder()
+        .get(server.url("/"))
+        .trace_context(context)
+        .build()
+        .unwrap();
+
+    relay::execute(request).await.unwrap();
+    let captured = &server.requests()[0];
+    assert_eq!(
+        captured.header("traceparent").as_deref(),
+        Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00")
+    );
+    assert_eq!(
+        captured.header("tracestate").as_deref(),
+        Some("vendor=value")
+    );
+}
+
+#[tokio::test]
+async fn a_callers_own_traceparent_is_sent_as_is() {
+    let server = Server::start(|_| ok("ok"));
+    let mut request = traced(server.url("/"));
+    request.headers = Some(
+        [(
+            "Traceparent".to_string(),
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".to_string(),
+        )]
+        .into(),
+    );
+
+    let response = relay::execute(request).await.unwrap();
+    let captured = &server.requests()[0];
+    let traceparents = captured
+        .head
+        .lines()
+        .filter(|line| line.to_ascii_lowercase().starts_with("traceparent:"))
+        .count();
+    assert_eq!(traceparents, 1);
+    assert_eq!(
+        captured.header("traceparent").as_deref(),
+        Some("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01")
+    );
+    assert_eq!(response.meta.trace.unwrap().span_id, "b7ad6b7169203331");
+}
@@ -0,0 +1,23 @@
+mod common;
+
+use std::{env, thread, time::Duration};
+
+use common::{ok, Server};
+use relay::RelayClient;
+
+// NOTE: In a binary of its own, `http_proxy` is process-wide and would
+// reach tests running alongside.
+#[test]
+fn warm_dns_ignores_proxies_from_the_environment() {
+    let proxy = Server::start(|_| ok("proxy"));
+    let target = Server::start(|_| ok("target"));
+    env::set_var("http_proxy", format!("http://127.0.0.1:{}", proxy.port));
+
+    let warmed = RelayClient::new().warm_dns("localhost", target.port);
+    env::remove_var("http_proxy");
+
+    warmed.unwrap();
+    // NOTE: The server may not have picked up a connection yet.
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(proxy.peak_connections(), 0);
+}
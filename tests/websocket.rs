@@ -0,0 +1,286 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use openssl::sha::sha1;
+use relay::{
+    error::RelayError,
+    ws::{WsConnection, WsMessage},
+    RelayClient, Request,
+};
+
+const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+const CONTINUATION: u8 = 0x0;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xa;
+
+// NOTE: A final frame, `fragment` sends one with more to come.
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    encode(0x80 | opcode, payload)
+}
+
+fn fragment(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    encode(opcode, payload)
+}
+
+fn encode(first: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![first];
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+// NOTE: Reads one masked frame from the client, returning its opcode and
+// the unmasked payload.
+fn read_frame(reader: &mut impl Read) -> (u8, Vec<u8>) {
+    let mut head = [0; 2];
+    reader.read_exact(&mut head).unwrap();
+    let len = match head[1] & 0x7f {
+        126 => {
+            let mut len = [0; 2];
+            reader.read_exact(&mut len).unwrap();
+            u16::from_be_bytes(len) as usize
+        }
+        127 => {
+            let mut len = [0; 8];
+            reader.read_exact(&mut len).unwrap();
+            u64::from_be_bytes(len) as usize
+        }
+        len => len as usize,
+    };
+    let mut mask = [0; 4];
+    reader.read_exact(&mut mask).unwrap();
+    let mut payload = vec![0; len];
+    reader.read_exact(&mut payload).unwrap();
+    for (index, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[index % 4];
+    }
+    (head[0] & 0x0f, payload)
+}
+
+fn read_head(reader: &mut BufReader<TcpStream>) -> Vec<String> {
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if line == "\r\n" {
+            return lines;
+        }
+        lines.push(line.trim_end().to_string());
+    }
+}
+
+// NOTE: Completes the upgrade, then hands the connection to `script` and
+// keeps it open a while after.
+fn serve(script: impl FnOnce(&mut BufReader<TcpStream>, &mut TcpStream) + Send + 'static) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut reader = BufReader::new(stream);
+        let key = read_head(&mut reader)
+            .iter()
+            .find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.eq_ignore_ascii_case("sec-websocket-key")
+                    .then(|| value.trim().to_string())
+            })
+            .unwrap_or_default();
+        let accept = BASE64.encode(sha1(
+            format!("{}258EAFA5-E914-47DA-95CA-C5AB0DC85B11", key).as_bytes(),
+        ));
+        write!(
+            writer,
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            accept
+        )
+        .unwrap();
+        script(&mut reader, &mut writer);
+        thread::sleep(Duration::from_secs(1));
+    });
+    port
+}
+
+// NOTE: `None` against a libcurl built without WebSockets, there is nothing
+// to check then.
+async fn open(port: u16) -> Option<WsConnection> {
+    let request = Request::builder()
+        .get(format!("ws://127.0.0.1:{}/", port))
+        .build()
+        .unwrap();
+
+    match RelayClient::new().websocket(request).await {
+        Ok(connection) => Some(connection),
+        Err(RelayError::UnsupportedFeature { .. }) => None,
+        Err(e) => panic!("upgrade failed: {}", e),
+    }
+}
+
+#[tokio::test]
+async fn waits_for_messages_and_enforces_the_size_limit() {
+    let port = serve(|_, writer| {
+        thread::sleep(Duration::from_millis(200));
+        writer.write_all(&frame(TEXT, b"hello")).unwrap();
+        writer.write_all(&frame(BINARY, &[0; 2000])).unwrap();
+    });
+    let Some(connection) = open(port).await else {
+        return;
+    };
+    let mut connection = connection.with_max_message_bytes(1024);
+
+    assert_eq!(
+        connection.next_message().unwrap(),
+        WsMessage::Text("hello".into())
+    );
+    assert!(matches!(
+        connection.next_message(),
+        Err(RelayError::Parse { .. })
+    ));
+    assert!(connection.is_closed());
+}
+
+#[tokio::test]
+async fn reassembles_fragments_around_control_frames() {
+    let (sent, pong) = mpsc::channel();
+    let port = serve(move |reader, writer| {
+        writer.write_all(&fragment(TEXT, b"hel")).unwrap();
+        writer.write_all(&frame(PING, b"mid")).unwrap();
+        writer.write_all(&fragment(CONTINUATION, b"lo, ")).unwrap();
+        writer.write_all(&frame(CONTINUATION, b"world")).unwrap();
+        sent.send(read_frame(reader)).unwrap();
+    });
+    let Some(mut connection) = open(port).await else {
+        return;
+    };
+
+    assert_eq!(
+        connection.next_message().unwrap(),
+        WsMessage::Ping("mid".into())
+    );
+    assert_eq!(
+        connection.next_message().unwrap(),
+        WsMessage::Text("hello, world".into())
+    );
+    assert_eq!(
+        pong.recv_timeout(Duration::from_secs(5)).unwrap(),
+        (PONG, b"mid".to_vec())
+    );
+}
+
+#[tokio::test]
+async fn pings_are_answered_with_pongs() {
+    let port = serve(|reader, writer| {
+        let (opcode, payload) = read_frame(reader);
+        assert_eq!(opcode, PING);
+        writer.write_all(&frame(PONG, &payload)).unwrap();
+    });
+    let Some(mut connection) = open(port).await else {
+        return;
+    };
+
+    connection.ping(b"are you there").unwrap();
+    assert_eq!(
+        connection.next_message().unwrap(),
+        WsMessage::Pong("are you there".into())
+    );
+}
+
+#[tokio::test]
+async fn close_sends_the_code_and_reason() {
+    let (sent, close) = mpsc::channel();
+    let port = serve(move |reader, _| {
+        sent.send(read_frame(reader)).unwrap();
+    });
+    let Some(mut connection) = open(port).await else {
+        return;
+    };
+
+    connection.close(4000, "done here").unwrap();
+
+    let (opcode, payload) = close.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(opcode, CLOSE);
+    assert_eq!(payload[..2], 4000u16.to_be_bytes());
+    assert_eq!(&payload[2..], b"done here");
+    assert!(connection.is_closed());
+    assert!(matches!(
+        connection.send_text("late"),
+        Err(RelayError::Network { .. })
+    ));
+}
+
+#[tokio::test]
+async fn peer_close_is_reported_and_echoed() {
+    let (sent, echo) = mpsc::channel();
+    let port = serve(move |reader, writer| {
+        let mut payload = 1001u16.to_be_bytes().to_vec();
+        payload.extend_from_slice(b"going away");
+        writer.write_all(&frame(CLOSE, &payload)).unwrap();
+        sent.send(read_frame(reader)).unwrap();
+    });
+    let Some(mut connection) = open(port).await else {
+        return;
+    };
+
+    assert_eq!(
+        connection.next_message().unwrap(),
+        WsMessage::Close {
+            code: Some(1001),
+            reason: "going away".into(),
+        }
+    );
+    assert!(connection.is_closed());
+    assert_eq!(
+        echo.recv_timeout(Duration::from_secs(5)).unwrap(),
+        (CLOSE, 1001u16.to_be_bytes().to_vec())
+    );
+}
+
+#[tokio::test]
+async fn refused_upgrades_report_the_status_and_headers() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        read_head(&mut BufReader::new(stream));
+        writer
+            .write_all(
+                b"HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Bearer realm=\"chat\"\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            )
+            .unwrap();
+    });
+    let request = Request::builder()
+        .get(format!("ws://127.0.0.1:{}/", port))
+        .build()
+        .unwrap();
+
+    let error = RelayClient::new().websocket(request).await.unwrap_err();
+    let (status, headers) = match error {
+        RelayError::HttpStatus {
+            status, headers, ..
+        } => (status, headers),
+        RelayError::UnsupportedFeature { .. } => return,
+        error => panic!("expected the refusal, got {:?}", error),
+    };
+
+    assert_eq!(status, 401);
+    let challenge = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("www-authenticate"))
+        .map(|(_, value)| value.as_str());
+    assert_eq!(challenge, Some("Bearer realm=\"chat\""));
+}